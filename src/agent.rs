@@ -0,0 +1,738 @@
+// Bucle de herramientas, modo agente (planificar y ejecutar) y `Agent`, el agente
+// para las aplicaciones que usan la biblioteca
+use crate::backup;
+use crate::budget::{self, Fitted};
+use crate::config::Config;
+use crate::convert;
+use crate::error::IAgentError;
+use crate::events::{self, AgentObserver};
+use crate::excel::WorkbookData;
+use crate::i18n::{self, tr};
+use crate::interrupt;
+use crate::jobs::{AgentJob, Job, JobKind};
+use crate::limits;
+use crate::llm::{self, Message};
+use crate::prompts;
+use crate::sandbox::Access;
+use crate::settings::Settings;
+use crate::timing::{self, Timings};
+use crate::untrusted::{self, ConfirmTool};
+use crate::usage::UsageTracker;
+use anyhow::{bail, Result};
+use reqwest::Client;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+// Máximo de rondas de herramientas por pregunta, para evitar bucles
+const MAX_TOOL_ROUNDS: usize = 8;
+const MAX_PLAN_STEPS: usize = 10;
+
+const PLANNING_INSTRUCTIONS: &str = "Actúa como un agente que completa tareas sobre archivos Excel mediante herramientas (leer_excel, agregar, escribir_hoja, ajustar_hoja, crear_grafico, formato_condicional, validar). Antes de actuar, devuelve SOLO un objeto JSON {\"pasos\": [\"...\"]} con entre 1 y 8 pasos concretos, cada uno realizable con una o dos herramientas. No ejecutes nada todavía. Tarea:";
+
+// Envía el historial al modelo y ejecuta las herramientas que solicite
+// hasta obtener una respuesta de texto
+pub async fn ask_model(
+    client: &Client,
+    config: &Config,
+    history: &mut Vec<Message>,
+    usage_tracker: &mut UsageTracker,
+    timings: &mut Timings,
+) -> Result<String> {
+    let tool_definitions = config.tools.definitions();
+    for _ in 0..MAX_TOOL_ROUNDS {
+        let completion =
+            llm::get_deepseek_response(client, config, history, Some(&tool_definitions)).await?;
+        usage_tracker.record_completion(&config.provider_name(), &config.model, &completion);
+        let content = completion.message.content.unwrap_or_default();
+
+        let calls = completion.message.tool_calls.unwrap_or_default();
+        if calls.is_empty() {
+            history.push(Message::new("assistant", content.clone()));
+            return Ok(content);
+        }
+
+        history.push(Message::tool_request(content, calls.clone()));
+        for call in &calls {
+            events::notice(format!("🔧 {} {}", call.function.name, call.function.arguments));
+            let started = Instant::now();
+            let max_tokens = budget::available(history, &config.context_budget);
+            let confirmed = !config.confirm_tools.required(history)
+                || untrusted::confirm(&call.function.name, &call.function.arguments);
+            let result = if !confirmed {
+                events::notice(format!("⏭  {} no se ejecuta", call.function.name));
+                format!(
+                    "Error: la herramienta {} no se ha ejecutado porque el usuario no lo ha confirmado (IAGENT_CONFIRM_TOOLS)",
+                    call.function.name
+                )
+            } else {
+                match config
+                    .tools
+                    .execute(&call.function.name, &call.function.arguments, max_tokens, &config.workspace)
+                    .await
+                {
+                    // Los resultados traen contenido de archivos: se sanean y delimitan como los demás datos
+                    Ok(output) => {
+                        let sanitized = untrusted::sanitize(&output);
+                        untrusted::warn(sanitized.removed);
+                        untrusted::delimit(&sanitized.text)
+                    }
+                    Err(e) => format!("Error: {}", untrusted::sanitize(&format!("{:#}", e)).text),
+                }
+            };
+            let elapsed = started.elapsed();
+            if config.verbose {
+                events::notice(format!("⏱  {}: {}", call.function.name, timing::format_duration(elapsed)));
+            }
+            timings.record(&format!("herramienta {}", call.function.name), &call.function.arguments, elapsed);
+            // Cada llamada necesita su respuesta, aunque no quede presupuesto
+            let result = match budget::fit(history, &config.context_budget, &result) {
+                Fitted::Complete(text) | Fitted::Truncated(text, _) => text,
+                Fitted::Rejected => {
+                    "Error: presupuesto de contexto agotado; el resultado no se incluye".to_string()
+                }
+            };
+            history.push(Message::tool_result(&call.id, result));
+        }
+    }
+    bail!("El modelo superó el máximo de {} rondas de herramientas", MAX_TOOL_ROUNDS)
+}
+
+// Modo agente: el modelo planifica la tarea y después se ejecuta paso a paso.
+// Ctrl-C detiene el agente y vuelve al prompt conservando el historial; el plan y
+// los pasos terminados quedan en un trabajo que `reanudar <id>` continúa.
+pub async fn run_task(
+    client: &Client,
+    config: &Config,
+    history: &mut Vec<Message>,
+    usage_tracker: &mut UsageTracker,
+    timings: &mut Timings,
+    task: &str,
+) -> Result<()> {
+    println!("{}", tr!("🧭 Planificando: {}", "🧭 Planning: {}", task));
+    let task_start = history.len();
+    history.push(Message::new("user", format!("{} {}", PLANNING_INSTRUCTIONS, task)));
+    let planning = async {
+        let completion = llm::get_deepseek_response(client, config, history, None).await?;
+        usage_tracker.record_completion(&config.provider_name(), &config.model, &completion);
+        Ok::<String, anyhow::Error>(completion.message.content.unwrap_or_default())
+    };
+    let plan_text = match interrupt::interruptible(planning).await {
+        Some(result) => result?,
+        None => {
+            history.truncate(task_start);
+            println!("{}", tr!("⏹ Planificación interrumpida", "⏹ Planning interrupted"));
+            return Ok(());
+        }
+    };
+    history.push(Message::new("assistant", plan_text.clone()));
+
+    let steps = parse_plan(&plan_text);
+    if steps.is_empty() {
+        bail!("El modelo no devolvió un plan reconocible:\n{}", plan_text);
+    }
+    println!("{}", tr!("📋 Plan ({} pasos, Ctrl-C para detener):", "📋 Plan ({} steps, Ctrl-C to stop):", steps.len()));
+    for (idx, step) in steps.iter().enumerate() {
+        println!("  {}. {}", idx + 1, step);
+    }
+    let job = Job::create(JobKind::Agent(AgentJob {
+        task: task.to_string(),
+        steps,
+        completed: 0,
+        history: history.clone(),
+    }))?;
+    run_steps(client, config, history, usage_tracker, timings, job).await
+}
+
+// Sigue un trabajo del modo agente desde el primer paso sin terminar. Si la
+// conversación actual no es la del trabajo, se añade la guardada.
+pub async fn resume_task(
+    client: &Client,
+    config: &Config,
+    history: &mut Vec<Message>,
+    usage_tracker: &mut UsageTracker,
+    timings: &mut Timings,
+    job: Job,
+) -> Result<()> {
+    let JobKind::Agent(state) = &job.kind else {
+        bail!("El trabajo {} no es del modo agente", job.id);
+    };
+    let saved = serde_json::to_value(&state.history)?;
+    let same = history.len() >= state.history.len() && serde_json::to_value(&history[..state.history.len()])? == saved;
+    if !same {
+        println!("{}", tr!("ℹ️  Se recupera la conversación del trabajo {}", "ℹ️  Restoring the conversation of job {}", job.id));
+        history.extend(state.history.iter().skip(1).cloned());
+    }
+    println!("{}", tr!("🧭 Reanudando: {} (paso {} de {})", "🧭 Resuming: {} (step {} of {})", state.task, state.completed + 1, state.steps.len()));
+    run_steps(client, config, history, usage_tracker, timings, job).await
+}
+
+// Ejecuta los pasos pendientes del trabajo y lo guarda tras cada uno
+async fn run_steps(
+    client: &Client,
+    config: &Config,
+    history: &mut Vec<Message>,
+    usage_tracker: &mut UsageTracker,
+    timings: &mut Timings,
+    mut job: Job,
+) -> Result<()> {
+    let JobKind::Agent(state) = &job.kind else {
+        bail!("El trabajo {} no es del modo agente", job.id);
+    };
+    let (steps, first) = (state.steps.clone(), state.completed);
+    for (idx, step) in steps.iter().enumerate().skip(first) {
+        println!("{}", tr!("▶ Paso {}/{}: {}", "▶ Step {}/{}: {}", idx + 1, steps.len(), step));
+        let step_start = history.len();
+        history.push(Message::new(
+            "user",
+            format!(
+                "Ejecuta ahora el paso {} del plan: {}. Usa las herramientas necesarias y responde con un resumen breve del resultado.",
+                idx + 1,
+                step
+            ),
+        ));
+        match interrupt::interruptible(ask_model(client, config, history, usage_tracker, timings)).await {
+            Some(Ok(summary)) => println!("✅ {}", summary),
+            Some(Err(e)) => {
+                history.truncate(step_start);
+                bail!("El paso {} falló: {:#}\nℹ️  Sigue más tarde con `reanudar {}`", idx + 1, e, job.id);
+            }
+            None => {
+                // Se descarta el paso a medias (puede acabar en una llamada a
+                // herramienta sin respuesta); los pasos completados se conservan
+                history.truncate(step_start);
+                println!(
+                    "{}",
+                    tr!(
+                        "⏹ Agente detenido en el paso {}; el historial de los pasos anteriores se conserva",
+                        "⏹ Agent stopped at step {}; the history of the earlier steps is kept",
+                        idx + 1
+                    )
+                );
+                println!("{}", tr!("ℹ️  Sigue más tarde con `reanudar {}`", "ℹ️  Continue later with `resume {}`", job.id));
+                return Ok(());
+            }
+        }
+        if let JobKind::Agent(state) = &mut job.kind {
+            state.completed = idx + 1;
+            state.history = history.clone();
+        }
+        job.save()?;
+    }
+    job.finish()?;
+    println!("{}", tr!("🏁 Tarea completada", "🏁 Task completed"));
+    Ok(())
+}
+
+// Extrae los pasos del plan: JSON {"pasos": [...]} o, si no, una lista numerada
+fn parse_plan(text: &str) -> Vec<String> {
+    if let (Some(start), Some(end)) = (text.find('{'), text.rfind('}')) {
+        if let Ok(Value::Object(plan)) = serde_json::from_str::<Value>(&text[start..=end]) {
+            if let Some(Value::Array(steps)) = plan.get("pasos").or_else(|| plan.get("steps")) {
+                return steps
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .take(MAX_PLAN_STEPS)
+                    .collect();
+            }
+        }
+    }
+    text.lines()
+        .map(str::trim)
+        .filter_map(|line| {
+            let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+            let rest = if rest.len() < line.len() {
+                rest.strip_prefix('.').or_else(|| rest.strip_prefix(')'))?
+            } else {
+                rest.strip_prefix("- ")?
+            };
+            Some(rest.trim().to_string())
+        })
+        .filter(|step| !step.is_empty())
+        .take(MAX_PLAN_STEPS)
+        .collect()
+}
+
+// Una pregunta y su respuesta en `history`. Sin idioma fijado se responde en el de
+// la pregunta, con una instrucción que se retira al terminar; si falla, ni la
+// pregunta ni las rondas de herramientas que provocó se quedan en el historial.
+pub async fn answer(
+    client: &Client,
+    config: &Config,
+    history: &mut Vec<Message>,
+    usage_tracker: &mut UsageTracker,
+    question: &str,
+) -> Result<String> {
+    let lang = i18n::reply_lang(config.reply_lang, question, config.lang);
+    let start = history.len();
+    let instruction_at = i18n::reply_instruction(lang).map(|instruction| {
+        history.push(Message::new("system", instruction));
+        history.len() - 1
+    });
+    history.push(Message::new("user", question));
+    let answer = ask_model(client, config, history, usage_tracker, &mut Timings::default()).await;
+    if answer.is_err() {
+        // Con la pregunta se van las llamadas a herramientas que quedaron sin respuesta
+        history.truncate(start);
+    } else if let Some(idx) = instruction_at.filter(|idx| *idx < history.len()) {
+        history.remove(idx);
+    }
+    answer
+}
+
+// El agente para otras aplicaciones: una conversación con el modelo y sus
+// herramientas, como la de la terminal pero sin ella. Tiene sus propios ajustes
+// (idioma, límites, salidas, observadores) y los instala en cada llamada, así que
+// varios agentes pueden trabajar a la vez. Las rutas son relativas a su espacio
+// de trabajo (IAGENT_WORKSPACE).
+pub struct Agent {
+    config: Config,
+    settings: Arc<Settings>,
+    client: Client,
+    system_template: String,
+    history: Vec<Message>,
+    usage_tracker: UsageTracker,
+}
+
+impl Agent {
+    // Con la configuración de las variables de entorno y del perfil activo, como la terminal
+    pub fn from_env() -> Result<Agent, IAgentError> {
+        let config = Config::load().map_err(|e| IAgentError::classify(e, IAgentError::Config))?;
+        Agent::new(config)
+    }
+
+    pub(crate) fn new(config: Config) -> Result<Agent, IAgentError> {
+        let configured = || -> Result<_> {
+            let settings = Settings::new(&config)?;
+            let client = llm::build_client(&config.http)?;
+            let system_template = prompts::load_system_template(config.persona.as_deref())?;
+            Ok((settings, client, system_template))
+        };
+        let (settings, client, system_template) = configured().map_err(|e| IAgentError::classify(e, IAgentError::Config))?;
+        let mut agent = Agent {
+            config,
+            settings,
+            client,
+            system_template,
+            history: Vec::new(),
+            usage_tracker: UsageTracker::default(),
+        };
+        agent.reset();
+        Ok(agent)
+    }
+
+    // Recibe los eventos de este agente (no los de otros del mismo proceso)
+    pub fn subscribe(&self, observer: Arc<dyn AgentObserver>) {
+        self.settings.subscribe(observer);
+    }
+
+    pub fn unsubscribe(&self, observer: &Arc<dyn AgentObserver>) {
+        self.settings.unsubscribe(observer);
+    }
+
+    // Decide las llamadas que piden confirmación (IAGENT_CONFIRM_TOOLS); sin ella
+    // no se ejecutan, porque el agente no pregunta en la terminal
+    pub fn confirm_tools_with(&self, confirmation: Arc<dyn ConfirmTool>) {
+        self.settings.confirm_tools_with(confirmation);
+    }
+
+    // Pregunta al modelo dentro de la conversación; las herramientas que pida se
+    // ejecutan antes de la respuesta
+    pub async fn ask(&mut self, question: &str) -> Result<String, IAgentError> {
+        backup::begin_operation();
+        let settings = Arc::clone(&self.settings);
+        settings
+            .scope(answer(&self.client, &self.config, &mut self.history, &mut self.usage_tracker, question))
+            .await
+            .map_err(|e| IAgentError::classify(e, IAgentError::Model))
+    }
+
+    // Ejecuta una herramienta del modelo directamente, con sus argumentos en JSON
+    pub async fn run_tool(&self, name: &str, arguments: &Value) -> Result<String, IAgentError> {
+        backup::begin_operation();
+        let (arguments, max_tokens) = (arguments.to_string(), self.config.context_budget.per_item);
+        let run = self.config.tools.execute(name, &arguments, max_tokens, &self.config.workspace);
+        self.settings.scope(run).await.map_err(|e| {
+            IAgentError::classify(e, |message| IAgentError::Tool {
+                name: name.to_string(),
+                message,
+            })
+        })
+    }
+
+    // Definiciones de las herramientas en el formato de la API, con las de IAGENT_TOOLS
+    pub fn tools(&self) -> Value {
+        self.config.tools.definitions()
+    }
+
+    // Lee un libro (xlsx, csv, json o Parquet) del espacio de trabajo
+    pub async fn read_workbook(&self, file: &str) -> Result<WorkbookData, IAgentError> {
+        let read = async {
+            let path = self.config.workspace.resolve("leer_excel", file, Access::Read)?;
+            limits::run_blocking(&format!("La lectura de {}", file), move || convert::read_any(Path::new(&path))).await
+        };
+        let read = self.settings.scope(read).await;
+        read.map_err(|e| IAgentError::classify(e, |message| IAgentError::excel(file, message)))
+    }
+
+    // Guarda un libro en el espacio de trabajo, con la copia de seguridad y la
+    // verificación de los comandos que escriben
+    pub async fn write_workbook(&self, file: &str, data: &WorkbookData) -> Result<(), IAgentError> {
+        backup::begin_operation();
+        let data = data.clone();
+        let write = async {
+            let path = self.config.workspace.resolve("escribir_hoja", file, Access::Write)?;
+            limits::run_blocking(&format!("La escritura de {}", file), move || convert::write_any(Path::new(&path), &data)).await
+        };
+        let write = self.settings.scope(write).await;
+        write.map_err(|e| IAgentError::classify(e, |message| IAgentError::excel(file, message)))
+    }
+
+    // Vuelve a empezar la conversación
+    pub fn reset(&mut self) {
+        self.history = vec![Message::new(
+            "system",
+            prompts::render(&self.system_template, &prompts::workbook_vars(&[])),
+        )];
+    }
+
+    // Tokens y coste estimado de las preguntas de este agente
+    pub fn usage(&self) -> String {
+        self.usage_tracker.report()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::TokenBudget;
+    use crate::error::IAgentError;
+    use crate::provider::{self, MockProvider, MockReply, Provider, Reply, ReplayProvider};
+    use serde_json::json;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    fn offline(dir: &Path, replies: Vec<MockReply>) -> Config {
+        Config::offline(Arc::new(MockProvider::new(replies)), dir)
+    }
+
+    fn tool(name: &str, arguments: Value) -> MockReply {
+        MockReply::Tool {
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    fn text(reply: &str) -> MockReply {
+        MockReply::Text(reply.to_string())
+    }
+
+    fn conversation(question: &str) -> Vec<Message> {
+        vec![Message::new("system", "Eres un asistente de Excel"), Message::new("user", question)]
+    }
+
+    async fn ask(config: &Config, history: &mut Vec<Message>) -> Result<String> {
+        ask_model(&Client::new(), config, history, &mut UsageTracker::default(), &mut Timings::default()).await
+    }
+
+    // ventas.xlsx con una fila por cliente en el directorio de la prueba
+    fn workbook(name: &str, rows: usize) -> PathBuf {
+        let dir = crate::paths::test_dir(name);
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Ventas").unwrap();
+        sheet.write_string(0, 0, "Cliente").unwrap();
+        sheet.write_string(0, 1, "Importe").unwrap();
+        for row in 1..=rows as u32 {
+            sheet.write_string(row, 0, format!("Cliente {}", row)).unwrap();
+            sheet.write_number(row, 1, row as f64 * 10.0).unwrap();
+        }
+        workbook.save(dir.join("ventas.xlsx")).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn a_text_reply_ends_the_turn_and_joins_the_history() {
+        let dir = crate::paths::test_dir("a_text_reply_ends_the_turn_and_joins_the_history");
+        let config = offline(&dir, vec![text("Hola")]);
+        let mut history = conversation("Hola");
+        assert_eq!(ask(&config, &mut history).await.unwrap(), "Hola");
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[2].role, "assistant");
+        assert_eq!(history[2].content, "Hola");
+    }
+
+    #[tokio::test]
+    async fn tool_calls_run_until_the_model_answers_with_text() {
+        let dir = workbook("tool_calls_run_until_the_model_answers_with_text", 2);
+        let config = offline(
+            &dir,
+            vec![
+                tool("leer_excel", json!({"archivo": "ventas.xlsx"})),
+                tool("leer_excel", json!({"archivo": "falta.xlsx"})),
+                text("Hay 2 clientes"),
+            ],
+        );
+        let mut history = conversation("¿Cuántos clientes hay?");
+        assert_eq!(ask(&config, &mut history).await.unwrap(), "Hay 2 clientes");
+
+        let roles: Vec<&str> = history.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "tool", "assistant", "tool", "assistant"]);
+        // Cada resultado responde a la llamada que lo pidió
+        for (request, result) in [(2, 3), (4, 5)] {
+            let call = &history[request].tool_calls.as_ref().unwrap()[0];
+            assert_eq!(history[result].tool_call_id.as_deref(), Some(call.id.as_str()));
+        }
+        assert!(history[3].content.contains("Cliente"), "{}", history[3].content);
+        // Una herramienta que falla no corta el bucle: el modelo recibe el error
+        assert!(history[5].content.starts_with("Error:"), "{}", history[5].content);
+    }
+
+    #[tokio::test]
+    async fn an_api_error_reply_fails_the_question_without_an_answer() {
+        let dir = crate::paths::test_dir("an_api_error_reply_fails_the_question_without_an_answer");
+        let config = offline(&dir, vec![MockReply::Error {
+            status: 503,
+            message: "Servicio no disponible".to_string(),
+        }]);
+        let mut history = conversation("Hola");
+        let error = ask(&config, &mut history).await.unwrap_err();
+        assert!(matches!(IAgentError::find(&error), Some(IAgentError::Api { status: 503, .. })), "{:#}", error);
+        assert_eq!(history.len(), 2);
+
+        // Sin más respuestas preparadas también es un error, no una respuesta vacía
+        let error = ask(&config, &mut history).await.unwrap_err();
+        assert!(error.to_string().contains("no tiene más respuestas"), "{:#}", error);
+    }
+
+    #[tokio::test]
+    async fn an_api_error_after_a_tool_round_leaves_no_unanswered_calls() {
+        let dir = workbook("an_api_error_after_a_tool_round_leaves_no_unanswered_calls", 2);
+        let config = offline(
+            &dir,
+            vec![
+                tool("leer_excel", json!({"archivo": "ventas.xlsx"})),
+                MockReply::Error {
+                    status: 500,
+                    message: "Error interno".to_string(),
+                },
+                text("Hola"),
+            ],
+        );
+        let mut history = vec![Message::new("system", "Eres un asistente de Excel")];
+        let error = answer(&Client::new(), &config, &mut history, &mut UsageTracker::default(), "¿Cuántos clientes hay?").await;
+        assert!(error.is_err());
+        // Ni la pregunta ni la llamada a leer_excel con su resultado
+        assert_eq!(history.len(), 1);
+
+        // La siguiente pregunta sigue la conversación sin llamadas sin respuesta
+        let hello = answer(&Client::new(), &config, &mut history, &mut UsageTracker::default(), "Hola").await;
+        assert_eq!(hello.unwrap(), "Hola");
+        let roles: Vec<&str> = history.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant"]);
+    }
+
+    #[tokio::test]
+    async fn the_tool_loop_stops_after_the_maximum_rounds() {
+        let dir = workbook("the_tool_loop_stops_after_the_maximum_rounds", 1);
+        let replies = (0..MAX_TOOL_ROUNDS + 1)
+            .map(|_| tool("leer_excel", json!({"archivo": "ventas.xlsx"})))
+            .collect();
+        let config = offline(&dir, replies);
+        let mut history = conversation("Lee el libro");
+        let error = ask(&config, &mut history).await.unwrap_err();
+        assert!(error.to_string().contains("máximo de 8 rondas"), "{:#}", error);
+        assert_eq!(history.iter().filter(|m| m.role == "tool").count(), MAX_TOOL_ROUNDS);
+    }
+
+    #[tokio::test]
+    async fn tool_results_are_truncated_to_the_context_budget() {
+        let dir = workbook("tool_results_are_truncated_to_the_context_budget", 60);
+        let read = || tool("leer_excel", json!({"archivo": "ventas.xlsx"}));
+        let mut config = offline(&dir, vec![read(), text("Resumido")]);
+        config.context_budget = TokenBudget {
+            per_item: 150,
+            total: 8_000,
+        };
+        let mut history = conversation("Lee el libro");
+        ask(&config, &mut history).await.unwrap();
+        assert!(history[3].content.ends_with("[truncado]"), "{}", history[3].content);
+        assert!(budget::estimate_tokens(&history[3].content) <= 150);
+
+        // Sin presupuesto la llamada sigue teniendo respuesta, pero sin el resultado
+        let mut config = offline(&dir, vec![read(), text("Sin datos")]);
+        config.context_budget = TokenBudget { per_item: 50, total: 50 };
+        let mut history = conversation("Lee el libro");
+        ask(&config, &mut history).await.unwrap();
+        assert!(history[3].content.contains("presupuesto de contexto agotado"), "{}", history[3].content);
+    }
+
+    #[tokio::test]
+    async fn a_failed_agent_step_is_dropped_from_the_history() {
+        let dir = crate::paths::test_dir("a_failed_agent_step_is_dropped_from_the_history");
+        let config = offline(
+            &dir,
+            vec![
+                text("{\"pasos\": [\"Leer el libro\", \"Resumir las ventas\"]}"),
+                text("Libro leído"),
+                MockReply::Error {
+                    status: 500,
+                    message: "Error interno".to_string(),
+                },
+            ],
+        );
+        let mut history = vec![Message::new("system", "Eres un asistente de Excel")];
+        let error = run_task(
+            &Client::new(),
+            &config,
+            &mut history,
+            &mut UsageTracker::default(),
+            &mut Timings::default(),
+            "Resume las ventas",
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("El paso 2 falló"), "{:#}", error);
+        // Quedan el plan y el paso 1; del paso 2 no queda ni la instrucción
+        let roles: Vec<&str> = history.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user", "assistant"]);
+        assert_eq!(history[4].content, "Libro leído");
+
+        let id = error.to_string().rsplit("reanudar ").next().unwrap().trim_end_matches('`').to_string();
+        let Ok(job) = Job::load(&id) else {
+            panic!("no se guardó el trabajo {}", id);
+        };
+        let JobKind::Agent(state) = &job.kind else {
+            panic!("no es del modo agente");
+        };
+        assert_eq!(state.completed, 1);
+        job.finish().unwrap();
+    }
+
+    // Como IAGENT_RECORD, pero con las respuestas preparadas en lugar de la API
+    #[derive(Debug)]
+    struct Recording {
+        replies: MockProvider,
+        dir: PathBuf,
+    }
+
+    impl Provider for Recording {
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+
+        fn complete(&self, key: &str) -> Result<Reply> {
+            let (message, usage) = self.replies.complete(key)?;
+            provider::record(&self.dir, key, &Value::Null, &message, usage)?;
+            Ok((message, usage))
+        }
+    }
+
+    #[tokio::test]
+    async fn recorded_replies_replay_the_same_conversation() {
+        let dir = workbook("recorded_replies_replay_the_same_conversation", 2);
+        let fixtures = dir.join("fixtures");
+        let recording = Recording {
+            replies: MockProvider::new(vec![tool("leer_excel", json!({"archivo": "ventas.xlsx"})), text("Hay 2 clientes")]),
+            dir: fixtures.clone(),
+        };
+        let mut recorded = conversation("¿Cuántos clientes hay?");
+        ask(&Config::offline(Arc::new(recording), &dir), &mut recorded).await.unwrap();
+
+        let replay = Config::offline(Arc::new(ReplayProvider::new(&fixtures).unwrap()), &dir);
+        let mut replayed = conversation("¿Cuántos clientes hay?");
+        assert_eq!(ask(&replay, &mut replayed).await.unwrap(), "Hay 2 clientes");
+        assert_eq!(serde_json::to_value(&replayed).unwrap(), serde_json::to_value(&recorded).unwrap());
+
+        // Otra pregunta es otra petición, y no hay nada grabado para ella
+        let mut other = conversation("¿Y los importes?");
+        let error = ask(&replay, &mut other).await.unwrap_err();
+        assert!(error.to_string().contains("No hay respuesta grabada"), "{:#}", error);
+    }
+
+    #[tokio::test]
+    async fn each_agent_reports_its_events_to_its_own_observers() {
+        let dir = workbook("each_agent_reports_its_events_to_its_own_observers", 2);
+        let mut reader = Agent::new(offline(&dir, vec![tool("leer_excel", json!({"archivo": "ventas.xlsx"})), text("Hay 2 clientes")])).unwrap();
+        let mut other = Agent::new(offline(&dir, vec![text("Hola")])).unwrap();
+        let seen = |tools: &Arc<std::sync::Mutex<Vec<String>>>| -> Arc<dyn AgentObserver> {
+            let tools = Arc::clone(tools);
+            Arc::new(move |event: &crate::events::AgentEvent| {
+                if let crate::events::AgentEvent::ToolInvoked { name, .. } = event {
+                    tools.lock().unwrap().push(name.clone());
+                }
+            })
+        };
+        let (reader_tools, other_tools) = Default::default();
+        reader.subscribe(seen(&reader_tools));
+        other.subscribe(seen(&other_tools));
+
+        let (read, greeted) = tokio::join!(reader.ask("¿Cuántos clientes hay?"), other.ask("Hola"));
+        assert_eq!(read.unwrap(), "Hay 2 clientes");
+        assert_eq!(greeted.unwrap(), "Hola");
+        assert_eq!(*reader_tools.lock().unwrap(), ["leer_excel"]);
+        assert!(other_tools.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_embedder_confirms_tool_calls_and_hears_about_them_as_notices() {
+        let dir = workbook("the_embedder_confirms_tool_calls_and_hears_about_them_as_notices", 2);
+        let read = || tool("leer_excel", json!({"archivo": "ventas.xlsx"}));
+        let replies = vec![read(), text("No lo he leído"), read(), text("Hay 2 clientes")];
+        let mut config = offline(&dir, replies);
+        config.confirm_tools = untrusted::ToolConfirmation::Always;
+        let mut agent = Agent::new(config).unwrap();
+        let notices: Arc<std::sync::Mutex<Vec<String>>> = Default::default();
+        let seen = Arc::clone(&notices);
+        agent.subscribe(Arc::new(move |event: &crate::events::AgentEvent| {
+            if let crate::events::AgentEvent::Notice { message } = event {
+                seen.lock().unwrap().push(message.clone());
+            }
+        }));
+
+        // Sin quien confirme, la herramienta no se ejecuta (y no se pregunta en la terminal)
+        agent.ask("¿Cuántos clientes hay?").await.unwrap();
+        assert!(agent.history[3].content.contains("no lo ha confirmado"), "{}", agent.history[3].content);
+        let asked: Arc<std::sync::Mutex<Vec<String>>> = Default::default();
+        let confirmed = Arc::clone(&asked);
+        agent.confirm_tools_with(Arc::new(move |name: &str, _: &str| {
+            confirmed.lock().unwrap().push(name.to_string());
+            true
+        }));
+        assert_eq!(agent.ask("¿Y ahora?").await.unwrap(), "Hay 2 clientes");
+        assert_eq!(*asked.lock().unwrap(), ["leer_excel"]);
+        let notices = notices.lock().unwrap();
+        assert!(notices.iter().any(|n| n.starts_with("⏭  leer_excel")), "{:?}", notices);
+        assert_eq!(notices.iter().filter(|n| n.starts_with("🔧 leer_excel")).count(), 2, "{:?}", notices);
+    }
+
+    #[tokio::test]
+    async fn an_agent_reads_and_writes_workbooks_in_its_workspace() {
+        let dir = workbook("an_agent_reads_and_writes_workbooks_in_its_workspace", 3);
+        let agent = Agent::new(offline(&dir, Vec::new())).unwrap();
+        let mut data = agent.read_workbook("ventas.xlsx").await.unwrap();
+        data.sheets[0].rows.truncate(2);
+        agent.write_workbook("copia.csv", &data).await.unwrap();
+        let copy = agent.read_workbook("copia.csv").await.unwrap();
+        assert_eq!(copy.sheets[0].display_rows(), [["Cliente", "Importe"], ["Cliente 1", "10"]]);
+        // Fuera del espacio de trabajo no se escribe
+        let outside = agent.write_workbook("../fuera.csv", &data).await;
+        assert!(matches!(outside, Err(IAgentError::Excel { ref file, .. }) if file == "../fuera.csv"), "{:?}", outside);
+    }
+
+    #[tokio::test]
+    async fn agent_errors_keep_their_kind() {
+        let dir = crate::paths::test_dir("agent_errors_keep_their_kind");
+        let busy = MockReply::Error {
+            status: 503,
+            message: "Servicio no disponible".to_string(),
+        };
+        let mut agent = Agent::new(offline(&dir, vec![busy])).unwrap();
+        let asked = agent.ask("Hola").await;
+        assert!(matches!(asked, Err(IAgentError::Api { status: 503, .. })), "{:?}", asked);
+        let unknown = agent.run_tool("no_existe", &json!({})).await;
+        assert!(matches!(unknown, Err(IAgentError::Tool { ref name, .. }) if name == "no_existe"), "{:?}", unknown);
+    }
+}
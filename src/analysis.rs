@@ -0,0 +1,763 @@
+use crate::excel::{self, CellRange, CellValue, ChartKind, ChartSpec, SheetData};
+use crate::i18n::{self, tr};
+use anyhow::{bail, Context, Result};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+
+// Opciones de los comandos `top` y `bottom`
+#[derive(Debug, Clone)]
+pub struct RankOptions {
+    pub file: String,
+    pub sheet: String,
+    pub by: String,
+    pub n: usize,
+    pub group_by: Option<String>,
+    pub ascending: bool,
+    pub output: Option<String>,
+}
+
+// Convierte una celda en número si es posible (incluye texto numérico)
+pub fn numeric_value(cell: &CellValue) -> Option<f64> {
+    match cell {
+        CellValue::Number(n) | CellValue::DateTime(n) => Some(*n),
+        CellValue::Text(s) => s.trim().replace(',', ".").parse().ok(),
+        _ => None,
+    }
+}
+
+pub fn require_column(sheet: &SheetData, spec: &str) -> Result<usize> {
+    sheet.column_index(spec).context(format!(
+        "No existe la columna '{}' en la hoja '{}' (encabezados: {})",
+        spec,
+        sheet.name,
+        sheet.headers().join(", ")
+    ))
+}
+
+// Como `require_column`, pero una letra o un número fuera de los datos (ZZ)
+// también es un error
+pub fn require_data_column(sheet: &SheetData, spec: &str) -> Result<usize> {
+    let col = require_column(sheet, spec)?;
+    if !sheet.rows.iter().any(|row| row.len() > col) {
+        bail!("La columna '{}' de la hoja '{}' está fuera de los datos", spec, sheet.name);
+    }
+    Ok(col)
+}
+
+// Ranking de filas (o de grupos sumados) por una columna numérica.
+// El resultado es una hoja con encabezados lista para mostrar o guardar.
+pub fn rank(sheet: &SheetData, options: &RankOptions) -> Result<SheetData> {
+    let by = require_column(sheet, &options.by)?;
+    if sheet.data_rows().is_empty() {
+        bail!("La hoja '{}' no tiene filas de datos", sheet.name);
+    }
+    let headers = sheet.headers();
+    let data_rows = sheet.data_rows();
+    let label = if options.ascending { "Bottom" } else { "Top" };
+    let mut result = SheetData::new(&format!("{} {}", label, options.n));
+
+    let compare = |a: f64, b: f64| {
+        let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+        if options.ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    };
+
+    match &options.group_by {
+        None => {
+            let mut ranked: Vec<(f64, &Vec<CellValue>)> = data_rows
+                .iter()
+                .filter_map(|row| numeric_value(row.get(by)?).map(|value| (value, row)))
+                .collect();
+            ranked.sort_by(|a, b| compare(a.0, b.0));
+
+            let mut header_row = vec![CellValue::Text(i18n::pick(("Posición", "Rank")).to_string())];
+            header_row.extend(headers.iter().map(|h| CellValue::Text(h.clone())));
+            result.rows.push(header_row);
+            for (position, (_, row)) in ranked.into_iter().take(options.n).enumerate() {
+                let mut out = vec![CellValue::Number((position + 1) as f64)];
+                out.extend(row.iter().cloned());
+                result.rows.push(out);
+            }
+        }
+        Some(group_spec) => {
+            let group = require_column(sheet, group_spec)?;
+            // Conserva el orden de aparición de los grupos para desempates estables
+            let mut groups: Vec<(String, f64, usize)> = Vec::new();
+            for row in data_rows {
+                let Some(value) = row.get(by).and_then(numeric_value) else {
+                    continue;
+                };
+                let key = row.get(group).map(|c| c.to_string()).unwrap_or_default();
+                match groups.iter_mut().find(|(k, _, _)| *k == key) {
+                    Some(entry) => {
+                        entry.1 += value;
+                        entry.2 += 1;
+                    }
+                    None => groups.push((key, value, 1)),
+                }
+            }
+            groups.sort_by(|a, b| compare(a.1, b.1));
+
+            result.rows.push(vec![
+                CellValue::Text(i18n::pick(("Posición", "Rank")).to_string()),
+                CellValue::Text(headers.get(group).cloned().unwrap_or_default()),
+                CellValue::Text(tr!("Suma de {}", "Sum of {}", headers.get(by).cloned().unwrap_or_default())),
+                CellValue::Text(i18n::pick(("Filas", "Rows")).to_string()),
+            ]);
+            for (position, (key, total, count)) in groups.into_iter().take(options.n).enumerate() {
+                result.rows.push(vec![
+                    CellValue::Number((position + 1) as f64),
+                    CellValue::Text(key),
+                    CellValue::Number(total),
+                    CellValue::Number(count as f64),
+                ]);
+            }
+        }
+    }
+
+    if result.rows.len() == 1 {
+        bail!("La columna '{}' no contiene valores numéricos", options.by);
+    }
+    Ok(result)
+}
+
+// Opciones del comando `pareto`
+#[derive(Debug, Clone)]
+pub struct ParetoOptions {
+    pub file: String,
+    pub sheet: String,
+    pub by: String,
+    pub group_by: Option<String>,
+    // Porcentaje acumulado que marca el corte (80 para la regla 80/20)
+    pub cut: f64,
+    pub chart: Option<ChartKind>,
+    pub output: Option<String>,
+}
+
+// Hoja resultante y resumen en texto para el usuario y el modelo
+pub struct ParetoResult {
+    pub sheet: SheetData,
+    pub summary: String,
+}
+
+// Ordena por la medida de mayor a menor y añade % del total, % acumulado y el corte
+pub fn pareto(sheet: &SheetData, options: &ParetoOptions) -> Result<ParetoResult> {
+    let by = require_column(sheet, &options.by)?;
+    if sheet.data_rows().is_empty() {
+        bail!("La hoja '{}' no tiene filas de datos", sheet.name);
+    }
+    let headers = sheet.headers();
+    let measure_name = headers.get(by).cloned().unwrap_or_else(|| options.by.clone());
+
+    // Cada elemento: celdas a conservar y valor de la medida
+    let (mut header_row, mut items): (Vec<CellValue>, Vec<(Vec<CellValue>, f64)>) = match &options.group_by {
+        None => (
+            headers.iter().map(|h| CellValue::Text(h.clone())).collect(),
+            sheet
+                .data_rows()
+                .iter()
+                .filter_map(|row| Some((row.clone(), numeric_value(row.get(by)?)?)))
+                .collect(),
+        ),
+        Some(group_spec) => {
+            let group = require_column(sheet, group_spec)?;
+            let mut groups: Vec<(String, f64)> = Vec::new();
+            for row in sheet.data_rows() {
+                let Some(value) = row.get(by).and_then(numeric_value) else {
+                    continue;
+                };
+                let key = row.get(group).map(|c| c.to_string()).unwrap_or_default();
+                match groups.iter_mut().find(|(k, _)| *k == key) {
+                    Some(entry) => entry.1 += value,
+                    None => groups.push((key, value)),
+                }
+            }
+            (
+                vec![
+                    CellValue::Text(headers.get(group).cloned().unwrap_or_default()),
+                    CellValue::Text(tr!("Suma de {}", "Sum of {}", measure_name)),
+                ],
+                groups
+                    .into_iter()
+                    .map(|(key, value)| (vec![CellValue::Text(key), CellValue::Number(value)], value))
+                    .collect(),
+            )
+        }
+    };
+    if items.is_empty() {
+        bail!("La columna '{}' no contiene valores numéricos", options.by);
+    }
+    let total: f64 = items.iter().map(|(_, value)| value).sum();
+    if total == 0.0 {
+        bail!("El total de '{}' es 0; no se puede calcular la participación", measure_name);
+    }
+    items.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+
+    let width = header_row.len();
+    header_row.extend([
+        CellValue::Text(i18n::pick(("% del total", "% of total")).to_string()),
+        CellValue::Text(i18n::pick(("% acumulado", "Cumulative %")).to_string()),
+        CellValue::Text(tr!("Corte {}%", "Cut {}%", options.cut)),
+    ]);
+    let mut result = SheetData::new("Pareto");
+    result.rows.push(header_row);
+
+    let mut cumulative = 0.0;
+    let mut vital_few = 0;
+    let item_count = items.len();
+    for (mut cells, value) in items {
+        // Se incluye en el corte el elemento que cruza el umbral
+        let inside = cumulative * 100.0 < options.cut;
+        cumulative += value / total;
+        if inside {
+            vital_few += 1;
+        }
+        cells.resize(width, CellValue::Empty);
+        cells.push(CellValue::Number(value / total));
+        cells.push(CellValue::Number(cumulative));
+        cells.push(CellValue::Text(i18n::pick(if inside { ("Dentro", "In") } else { ("Fuera", "Out") }).to_string()));
+        result.rows.push(cells);
+    }
+    result.column_formats.insert(width, "0.0%".to_string());
+    result.column_formats.insert(width + 1, "0.0%".to_string());
+
+    if let Some(kind) = options.chart {
+        result.charts.push(ChartSpec {
+            kind,
+            title: tr!("Pareto de {}", "Pareto of {}", measure_name),
+            category_col: 0,
+            value_cols: vec![width, width + 1],
+            first_row: 1,
+            last_row: item_count,
+            anchor: (1, width + 4),
+        });
+    }
+
+    let vital_share = result.rows[vital_few].get(width + 1).and_then(CellValue::as_number).unwrap_or(0.0);
+    let summary = format!(
+        "Pareto de '{}' (total {}): {} de {} elementos ({:.1}%) concentran el {:.1}% del total; corte en {}%.",
+        measure_name,
+        total,
+        vital_few,
+        item_count,
+        vital_few as f64 * 100.0 / item_count as f64,
+        vital_share * 100.0,
+        options.cut
+    );
+    Ok(ParetoResult { sheet: result, summary })
+}
+
+// Opciones del comando `cohortes`
+#[derive(Debug, Clone)]
+pub struct CohortOptions {
+    pub file: String,
+    pub sheet: Option<String>,
+    pub signup: String,
+    pub event: String,
+    // Medida a sumar; sin ella se cuentan eventos (o clientes distintos)
+    pub value: Option<String>,
+    pub customer: Option<String>,
+    // Expresa cada fila como porcentaje del tamaño de la cohorte (o del mes 0)
+    pub relative: bool,
+    pub output: Option<String>,
+}
+
+pub struct CohortResult {
+    pub sheet: SheetData,
+    // Celdas de la matriz (sin encabezados), para la escala de colores
+    pub matrix_range: CellRange,
+    pub summary: String,
+}
+
+// Valor de una celda como número de serie de fecha
+pub fn date_value(cell: &CellValue) -> Option<f64> {
+    match cell {
+        CellValue::DateTime(serial) | CellValue::Number(serial) => Some(*serial),
+        CellValue::Text(text) => excel::parse_date_text(text),
+        _ => None,
+    }
+}
+
+// Índice de mes absoluto (año * 12 + mes) de un número de serie
+fn month_index(serial: f64) -> i64 {
+    let (year, month, _) = excel::serial_to_date(serial);
+    year * 12 + month as i64 - 1
+}
+
+// Acumulador por celda de la matriz
+#[derive(Default)]
+struct CohortCell {
+    sum: f64,
+    events: usize,
+    customers: HashSet<String>,
+}
+
+// Matriz de cohortes: una fila por mes de alta y una columna por meses transcurridos
+pub fn cohorts(sheet: &SheetData, options: &CohortOptions) -> Result<CohortResult> {
+    let signup = require_column(sheet, &options.signup)?;
+    let event = require_column(sheet, &options.event)?;
+    let value = options.value.as_deref().map(|v| require_column(sheet, v)).transpose()?;
+    let customer = options.customer.as_deref().map(|c| require_column(sheet, c)).transpose()?;
+    if sheet.data_rows().is_empty() {
+        bail!("La hoja '{}' no tiene filas de datos", sheet.name);
+    }
+
+    let mut matrix: BTreeMap<i64, BTreeMap<i64, CohortCell>> = BTreeMap::new();
+    // Clientes distintos de cada cohorte, base de la retención
+    let mut cohort_customers: BTreeMap<i64, HashSet<String>> = BTreeMap::new();
+    let mut skipped = 0;
+    for row in sheet.data_rows() {
+        let cell = |idx: usize| row.get(idx).unwrap_or(&CellValue::Empty);
+        let (Some(signup_date), Some(event_date)) = (date_value(cell(signup)), date_value(cell(event))) else {
+            skipped += 1;
+            continue;
+        };
+        let offset = month_index(event_date) - month_index(signup_date);
+        if offset < 0 {
+            skipped += 1;
+            continue;
+        }
+        let amount = match value {
+            Some(idx) => match numeric_value(cell(idx)) {
+                Some(amount) => amount,
+                None => {
+                    skipped += 1;
+                    continue;
+                }
+            },
+            None => 0.0,
+        };
+        let entry = matrix
+            .entry(month_index(signup_date))
+            .or_default()
+            .entry(offset)
+            .or_default();
+        entry.sum += amount;
+        entry.events += 1;
+        if let Some(idx) = customer {
+            entry.customers.insert(cell(idx).to_string());
+            cohort_customers
+                .entry(month_index(signup_date))
+                .or_default()
+                .insert(cell(idx).to_string());
+        }
+    }
+    if matrix.is_empty() {
+        bail!("No hay filas con fechas válidas en '{}' y '{}'", options.signup, options.event);
+    }
+
+    let metric = |cell: &CohortCell| match (value, customer) {
+        (Some(_), _) => cell.sum,
+        (None, Some(_)) => cell.customers.len() as f64,
+        (None, None) => cell.events as f64,
+    };
+    let max_offset = matrix
+        .values()
+        .filter_map(|offsets| offsets.keys().max())
+        .max()
+        .copied()
+        .unwrap_or(0);
+
+    let mut result = SheetData::new("Cohortes");
+    let mut header = vec![CellValue::Text(i18n::pick(("Cohorte", "Cohort")).to_string())];
+    header.extend((0..=max_offset).map(|m| CellValue::Text(tr!("Mes {}", "Month {}", m))));
+    result.rows.push(header);
+    for (cohort, offsets) in &matrix {
+        let base = match (value, cohort_customers.get(cohort)) {
+            (None, Some(customers)) => customers.len() as f64,
+            _ => offsets.get(&0).map(metric).unwrap_or(0.0),
+        };
+        let mut row = vec![CellValue::Text(format!("{:04}-{:02}", cohort / 12, cohort % 12 + 1))];
+        for offset in 0..=max_offset {
+            row.push(match offsets.get(&offset).map(metric) {
+                Some(amount) if options.relative && base != 0.0 => CellValue::Number(amount / base),
+                Some(amount) if !options.relative => CellValue::Number(amount),
+                _ => CellValue::Empty,
+            });
+        }
+        result.rows.push(row);
+    }
+    if options.relative {
+        for col in 1..=(max_offset as usize + 1) {
+            result.column_formats.insert(col, "0.0%".to_string());
+        }
+    }
+
+    let measure = match (&options.value, &options.customer) {
+        (Some(value), _) => format!("suma de {}", value),
+        (None, Some(customer)) => format!("{} distintos", customer),
+        (None, None) => "número de eventos".to_string(),
+    };
+    let summary = format!(
+        "Cohortes por mes de '{}' ({}{}): {} cohortes, hasta {} meses de seguimiento; {} filas descartadas por fechas o valores no válidos.",
+        options.signup,
+        measure,
+        match (options.relative, &options.value, &options.customer) {
+            (false, _, _) => "",
+            (true, None, Some(_)) => ", relativo al tamaño de la cohorte",
+            (true, _, _) => ", relativo al mes 0",
+        },
+        matrix.len(),
+        max_offset,
+        skipped
+    );
+    Ok(CohortResult {
+        matrix_range: CellRange {
+            first_row: 1,
+            first_col: 1,
+            last_row: matrix.len(),
+            last_col: max_offset as usize + 1,
+        },
+        sheet: result,
+        summary,
+    })
+}
+
+// Opciones del comando `estadisticas`
+#[derive(Debug, Clone)]
+pub struct StatsOptions {
+    pub file: String,
+    pub sheet: Option<String>,
+    pub columns: Vec<String>,
+    pub output: Option<String>,
+}
+
+// Estadísticos de una columna numérica
+#[derive(Debug, Clone)]
+pub struct ColumnStats {
+    pub column: String,
+    pub count: usize,
+    // Celdas con contenido que no es un número (texto, fechas, booleanos, errores)
+    pub non_numeric: usize,
+    pub empty: usize,
+    pub sum: f64,
+    pub mean: f64,
+    pub median: f64,
+    // Desviación típica de la muestra (n - 1), como DESVEST.M
+    pub stddev: Option<f64>,
+    pub min: f64,
+    pub q1: f64,
+    pub q3: f64,
+    pub max: f64,
+}
+
+impl ColumnStats {
+    const LABELS: [(&'static str, &'static str); 11] = [
+        ("Números", "Numbers"),
+        ("No numéricas", "Non-numeric"),
+        ("Vacías", "Empty"),
+        ("Suma", "Sum"),
+        ("Media", "Mean"),
+        ("Mediana", "Median"),
+        ("Desv. típica", "Std. dev."),
+        ("Mínimo", "Minimum"),
+        ("Percentil 25", "25th percentile"),
+        ("Percentil 75", "75th percentile"),
+        ("Máximo", "Maximum"),
+    ];
+
+    // Valores en el orden de LABELS
+    fn cells(&self) -> [CellValue; 11] {
+        let count = |n: usize| CellValue::Number(n as f64);
+        let number = |n: f64| CellValue::Number(round(n));
+        [
+            count(self.count),
+            count(self.non_numeric),
+            count(self.empty),
+            number(self.sum),
+            number(self.mean),
+            number(self.median),
+            self.stddev.map(number).unwrap_or(CellValue::Empty),
+            number(self.min),
+            number(self.q1),
+            number(self.q3),
+            number(self.max),
+        ]
+    }
+}
+
+pub struct StatsResult {
+    pub sheet: SheetData,
+    pub summary: String,
+}
+
+// Estadísticos descriptivos calculados aquí, sin pasar por el modelo: una columna
+// por cada columna pedida y una fila por estadístico
+pub fn statistics(sheet: &SheetData, options: &StatsOptions) -> Result<StatsResult> {
+    if sheet.data_rows().is_empty() {
+        bail!("La hoja '{}' no tiene filas de datos", sheet.name);
+    }
+    let headers = sheet.headers();
+    let stats = options
+        .columns
+        .iter()
+        .map(|spec| {
+            let col = require_column(sheet, spec)?;
+            let name = headers.get(col).cloned().unwrap_or_else(|| spec.clone());
+            describe_column(sheet, col, &name)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut result = SheetData::new("Estadísticas");
+    let mut header_row = vec![CellValue::Text(i18n::pick(("Estadístico", "Statistic")).to_string())];
+    header_row.extend(stats.iter().map(|s| CellValue::Text(s.column.clone())));
+    result.rows.push(header_row);
+    let columns: Vec<[CellValue; 11]> = stats.iter().map(ColumnStats::cells).collect();
+    for (idx, label) in ColumnStats::LABELS.into_iter().enumerate() {
+        let mut row = vec![CellValue::Text(i18n::pick(label).to_string())];
+        row.extend(columns.iter().map(|cells| cells[idx].clone()));
+        result.rows.push(row);
+    }
+
+    let described: Vec<String> = stats
+        .iter()
+        .map(|s| {
+            format!(
+                "'{}': {} números, suma {}, media {}, mediana {}, desviación típica {}, mínimo {}, P25 {}, P75 {}, máximo {}",
+                s.column,
+                s.count,
+                round(s.sum),
+                round(s.mean),
+                round(s.median),
+                s.stddev.map(|d| round(d).to_string()).unwrap_or_else(|| "-".to_string()),
+                round(s.min),
+                round(s.q1),
+                round(s.q3),
+                round(s.max)
+            )
+        })
+        .collect();
+    let summary = format!("Estadísticas de la hoja '{}': {}.", sheet.name, described.join("; "));
+    Ok(StatsResult { sheet: result, summary })
+}
+
+fn describe_column(sheet: &SheetData, col: usize, name: &str) -> Result<ColumnStats> {
+    let mut values = Vec::new();
+    let (mut non_numeric, mut empty) = (0, 0);
+    for row in sheet.data_rows() {
+        match row.get(col) {
+            None | Some(CellValue::Empty) => empty += 1,
+            Some(CellValue::Text(text)) if text.trim().is_empty() => empty += 1,
+            // La media de unas fechas como números de serie no tiene sentido aquí
+            Some(CellValue::DateTime(_)) => non_numeric += 1,
+            Some(cell) => match numeric_value(cell).filter(|n| n.is_finite()) {
+                Some(value) => values.push(value),
+                None => non_numeric += 1,
+            },
+        }
+    }
+    if values.is_empty() {
+        bail!("La columna '{}' no contiene valores numéricos", name);
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let n = values.len() as f64;
+    let sum: f64 = values.iter().sum();
+    let mean = sum / n;
+    let stddev = (values.len() > 1)
+        .then(|| (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt());
+    Ok(ColumnStats {
+        column: name.to_string(),
+        count: values.len(),
+        non_numeric,
+        empty,
+        sum,
+        mean,
+        median: percentile(&values, 0.5),
+        stddev,
+        min: values[0],
+        q1: percentile(&values, 0.25),
+        q3: percentile(&values, 0.75),
+        max: values[values.len() - 1],
+    })
+}
+
+// Percentil con interpolación lineal entre los dos valores más cercanos, como
+// PERCENTIL.INC y CUARTIL.INC de Excel; `sorted` no puede estar vacío
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p * (sorted.len() - 1) as f64;
+    let low = rank.floor() as usize;
+    let high = rank.ceil() as usize;
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64)
+}
+
+// Evita mostrar restos de coma flotante como 0.30000000000000004
+fn round(value: f64) -> f64 {
+    (value * 1e6).round() / 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::i18n::Lang;
+    use crate::provider::MockProvider;
+    use crate::settings::Settings;
+    use std::sync::Arc;
+
+    fn sales() -> SheetData {
+        SheetData::from_rows(
+            "Ventas",
+            &[
+                &["Cliente", "Zona", "Importe"],
+                &["Ana", "Norte", "50"],
+                &["Luis", "Sur", "200"],
+                &["Eva", "Norte", "120"],
+                &["Juan", "Este", "30"],
+                &["Sin importe", "Sur", ""],
+            ],
+        )
+    }
+
+    fn numbers(sheet: &SheetData, col: usize) -> Vec<f64> {
+        sheet.rows.iter().skip(1).filter_map(|row| row.get(col).and_then(numeric_value)).collect()
+    }
+
+    fn rank_options(n: usize, group_by: Option<&str>, ascending: bool) -> RankOptions {
+        RankOptions {
+            file: "ventas.xlsx".to_string(),
+            sheet: "Ventas".to_string(),
+            by: "Importe".to_string(),
+            n,
+            group_by: group_by.map(str::to_string),
+            ascending,
+            output: None,
+        }
+    }
+
+    #[test]
+    fn rank_orders_rows_or_group_totals_and_skips_non_numeric_values() {
+        let top = rank(&sales(), &rank_options(2, None, false)).unwrap();
+        assert_eq!(top.name, "Top 2");
+        assert_eq!(top.headers(), ["Posición", "Cliente", "Zona", "Importe"]);
+        assert_eq!(numbers(&top, 3), [200.0, 120.0]);
+
+        let bottom = rank(&sales(), &rank_options(10, None, true)).unwrap();
+        assert_eq!(numbers(&bottom, 3), [30.0, 50.0, 120.0, 200.0]);
+
+        let zones = rank(&sales(), &rank_options(5, Some("Zona"), false)).unwrap();
+        assert_eq!(zones.headers(), ["Posición", "Zona", "Suma de Importe", "Filas"]);
+        assert_eq!(zones.rows[1][1].to_string(), "Sur");
+        assert_eq!(numbers(&zones, 2), [200.0, 170.0, 30.0]);
+        assert_eq!(numbers(&zones, 3), [1.0, 2.0, 1.0]);
+
+        let error = rank(&sales(), &RankOptions { by: "Cliente".to_string(), ..rank_options(3, None, false) }).unwrap_err();
+        assert!(error.to_string().contains("no contiene valores numéricos"), "{}", error);
+    }
+
+    #[test]
+    fn pareto_includes_the_element_that_crosses_the_cut() {
+        let options = ParetoOptions {
+            file: "ventas.xlsx".to_string(),
+            sheet: "Ventas".to_string(),
+            by: "Importe".to_string(),
+            group_by: None,
+            cut: 80.0,
+            chart: None,
+            output: None,
+        };
+        let result = pareto(&sales(), &options).unwrap();
+        // 200 + 120 = 320 de 400: el segundo cruza el 80%
+        let cumulative = numbers(&result.sheet, 4);
+        assert_eq!(cumulative, [0.5, 0.8, 0.925, 1.0]);
+        let marks: Vec<String> = result.sheet.rows.iter().skip(1).map(|row| row[5].to_string()).collect();
+        assert_eq!(marks, ["Dentro", "Dentro", "Fuera", "Fuera"]);
+        assert!(result.summary.contains("2 de 4 elementos"), "{}", result.summary);
+
+        let zero = SheetData::from_rows("Ventas", &[&["Cliente", "Importe"], &["Ana", "0"]]);
+        assert!(pareto(&zero, &options).is_err());
+    }
+
+    #[test]
+    fn cohorts_count_customers_by_months_since_signup() {
+        let orders = SheetData::from_rows(
+            "Pedidos",
+            &[
+                &["Cliente", "Alta", "Pedido"],
+                &["Ana", "2024-01-10", "2024-01-20"],
+                &["Ana", "2024-01-10", "2024-03-02"],
+                &["Luis", "2024-01-25", "2024-02-01"],
+                &["Eva", "2024-02-03", "2024-02-03"],
+                &["Juan", "2024-02-03", "2024-01-01"],
+                &["Sin fecha", "", "2024-01-01"],
+            ],
+        );
+        let options = CohortOptions {
+            file: "pedidos.xlsx".to_string(),
+            sheet: None,
+            signup: "Alta".to_string(),
+            event: "Pedido".to_string(),
+            value: None,
+            customer: Some("Cliente".to_string()),
+            relative: true,
+            output: None,
+        };
+        let result = cohorts(&orders, &options).unwrap();
+        assert_eq!(result.sheet.headers(), ["Cohorte", "Mes 0", "Mes 1", "Mes 2"]);
+        let rows = result.sheet.display_rows();
+        assert_eq!(rows[1][0], "2024-01");
+        // Enero: 2 clientes; en el mes 0 y en el 1 compra uno de ellos
+        assert_eq!(result.sheet.rows[1][1].as_number(), Some(0.5));
+        assert_eq!(result.sheet.rows[1][2].as_number(), Some(0.5));
+        assert_eq!(result.sheet.rows[2][0].to_string(), "2024-02");
+        assert_eq!(result.sheet.rows[2][1].as_number(), Some(1.0));
+        assert_eq!(result.matrix_range.last_row, 2);
+        assert_eq!(result.matrix_range.last_col, 3);
+        // El pedido anterior al alta y la fila sin fecha no cuentan
+        assert!(result.summary.contains("2 filas descartadas"), "{}", result.summary);
+    }
+
+    #[test]
+    fn statistics_match_excel_quartiles_and_sample_deviation() {
+        let data = SheetData::from_rows("Ventas", &[&["Importe"], &["1"], &["2"], &["3"], &["4"], &["texto"], &[""]]);
+        let options = StatsOptions {
+            file: "ventas.xlsx".to_string(),
+            sheet: None,
+            columns: vec!["Importe".to_string()],
+            output: None,
+        };
+        let result = statistics(&data, &options).unwrap();
+        let value = |label: &str| {
+            let row = result.sheet.rows.iter().find(|row| row[0].to_string() == label).unwrap();
+            row[1].as_number()
+        };
+        assert_eq!(value("Números"), Some(4.0));
+        assert_eq!(value("No numéricas"), Some(1.0));
+        assert_eq!(value("Vacías"), Some(1.0));
+        assert_eq!(value("Mediana"), Some(2.5));
+        // CUARTIL.INC({1,2,3,4}; 1) = 1,75 y DESVEST.M = 1,290994
+        assert_eq!(value("Percentil 25"), Some(1.75));
+        assert_eq!(value("Percentil 75"), Some(3.25));
+        assert_eq!(value("Desv. típica"), Some(1.290994));
+    }
+
+    #[test]
+    fn the_statistics_table_follows_the_interface_language() {
+        let mut config = Config::offline(Arc::new(MockProvider::new(Vec::new())), &crate::paths::test_dir("stats_lang"));
+        config.lang = Lang::En;
+        let data = SheetData::from_rows("Ventas", &[&["Importe"], &["1"], &["2"]]);
+        let options = StatsOptions {
+            file: "ventas.xlsx".to_string(),
+            sheet: None,
+            columns: vec!["Importe".to_string()],
+            output: None,
+        };
+        let result = Settings::new(&config).unwrap().sync_scope(|| statistics(&data, &options)).unwrap();
+        assert_eq!(result.sheet.headers(), ["Statistic", "Importe"]);
+        assert_eq!(result.sheet.rows[5][0].to_string(), "Mean");
+        // Fuera del agente, en español
+        assert_eq!(statistics(&data, &options).unwrap().sheet.headers(), ["Estadístico", "Importe"]);
+    }
+
+    #[test]
+    fn a_column_must_exist_and_hold_data() {
+        let data = sales();
+        assert_eq!(require_column(&data, "importe").unwrap(), 2);
+        let error = require_column(&data, "Precio").unwrap_err();
+        assert!(error.to_string().contains("encabezados: Cliente, Zona, Importe"), "{}", error);
+        assert_eq!(require_data_column(&data, "C").unwrap(), 2);
+        assert!(require_data_column(&data, "ZZ").is_err());
+    }
+}
@@ -0,0 +1,325 @@
+// Anonimización de columnas con datos personales (`anonimizar`): se escribe una
+// copia del libro en la que cada valor de esas columnas se sustituye por un
+// seudónimo ("Cliente 0001", "persona0001@ejemplo.com") o por un hash con clave
+// ("anon-3fa9c2d1e0"). El mismo valor recibe siempre el mismo sustituto, también
+// en otras hojas y otros libros, así que los cruces y los recuentos siguen
+// saliendo igual. La correspondencia se guarda solo en local, en
+// anonimizacion.json del directorio de datos, y `desanonimizar` la usa para
+// devolver los valores reales a los libros que salen del análisis.
+use crate::analysis;
+use crate::convert;
+use crate::crypto;
+use crate::excel::{CellValue, SheetData};
+use crate::outputs;
+use crate::paths;
+use crate::secrets;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Caracteres hexadecimales del hash de cada valor
+const HASH_CHARS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    // Texto inventado con el nombre de la columna y un número
+    Pseudonym,
+    // HMAC del valor con la clave local: no se puede deshacer sin anonimizacion.json
+    Hash,
+}
+
+impl Method {
+    pub fn parse(value: &str) -> Option<Method> {
+        match value.to_lowercase().as_str() {
+            "seudonimo" | "seudónimo" | "falso" | "pseudonym" | "fake" => Some(Method::Pseudonym),
+            "hash" => Some(Method::Hash),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnonymizeOptions {
+    pub file: String,
+    // Encabezados; con `sheet`, también letras o números de columna
+    pub columns: Vec<String>,
+    // Sin hoja, las columnas con esos encabezados de todas las hojas
+    pub sheet: Option<String>,
+    pub method: Method,
+    pub output: Option<String>,
+}
+
+pub struct Anonymized {
+    pub output: String,
+    pub cells: usize,
+    // Valores que no se habían anonimizado antes
+    pub new_values: usize,
+    // Hoja!columna de cada columna anonimizada
+    pub columns: Vec<String>,
+}
+
+// anonimizacion.json: la clave de los hashes y el valor real de cada sustituto
+#[derive(Default, Serialize, Deserialize)]
+struct Mapping {
+    #[serde(rename = "clave", default)]
+    key: String,
+    #[serde(rename = "valores", default)]
+    values: BTreeMap<String, Value>,
+}
+
+fn mapping_file() -> PathBuf {
+    paths::data_dir().join("anonimizacion.json")
+}
+
+impl Mapping {
+    fn load() -> Result<Mapping> {
+        let path = mapping_file();
+        match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).context(format!("La tabla de anonimización está dañada ({})", path.display())),
+            Err(_) => Ok(Mapping::default()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = mapping_file();
+        fs::create_dir_all(paths::data_dir())?;
+        // Se escribe aparte y se renombra, como la memoria de esquemas
+        let partial = path.with_extension("json.tmp");
+        // Con permisos 0600 desde que se crea: la tabla deshace la anonimización
+        secrets::write_private(&partial, serde_json::to_string_pretty(self)?.as_bytes())
+            .context(format!("No se pudo guardar la tabla de anonimización en {}", partial.display()))?;
+        fs::rename(&partial, &path).context(format!("No se pudo guardar la tabla de anonimización en {}", path.display()))
+    }
+
+    // La clave de los hashes se crea la primera vez que se anonimiza con hash
+    fn ensure_hash_key(&mut self) -> Result<()> {
+        if self.key.is_empty() {
+            self.key = crypto::hex(&crypto::entropy()?);
+        }
+        Ok(())
+    }
+}
+
+// Sustitutos ya asignados y los que se van creando en esta ejecución
+struct Substitutes {
+    mapping: Mapping,
+    // Valor real (en JSON) -> sustituto
+    by_original: HashMap<String, String>,
+    // Último número usado por cada prefijo de seudónimo
+    counters: HashMap<String, usize>,
+    added: usize,
+}
+
+impl Substitutes {
+    fn new(mapping: Mapping) -> Substitutes {
+        let by_original = mapping
+            .values
+            .iter()
+            .map(|(substitute, original)| (original.to_string(), substitute.clone()))
+            .collect();
+        Substitutes {
+            mapping,
+            by_original,
+            counters: HashMap::new(),
+            added: 0,
+        }
+    }
+
+    // Sustituto de una celda; `None` si está vacía o ya es un sustituto
+    fn substitute(&mut self, cell: &CellValue, header: &str, method: Method) -> Option<String> {
+        if matches!(cell, CellValue::Empty | CellValue::Error(_)) {
+            return None;
+        }
+        let text = cell.to_string();
+        if text.trim().is_empty() || self.mapping.values.contains_key(&text) {
+            return None;
+        }
+        let original = convert::cell_to_json(cell);
+        if let Some(existing) = self.by_original.get(&original.to_string()) {
+            return Some(existing.clone());
+        }
+        let substitute = match method {
+            Method::Hash => {
+                let digest = crypto::hmac_sha256(self.mapping.key.as_bytes(), original.to_string().as_bytes());
+                format!("anon-{}", &crypto::hex(&digest)[..HASH_CHARS])
+            }
+            Method::Pseudonym if is_email(&text) => format!("{}@ejemplo.com", self.next("persona", "")),
+            Method::Pseudonym => self.next(&prefix(header), " "),
+        };
+        self.by_original.insert(original.to_string(), substitute.clone());
+        self.mapping.values.insert(substitute.clone(), original);
+        self.added += 1;
+        Some(substitute)
+    }
+
+    fn next(&mut self, prefix: &str, separator: &str) -> String {
+        let start = format!("{}{}", prefix, separator);
+        let values = &self.mapping.values;
+        let counter = self
+            .counters
+            .entry(prefix.to_string())
+            .or_insert_with(|| values.keys().filter(|substitute| substitute.starts_with(&start)).count());
+        loop {
+            *counter += 1;
+            let candidate = format!("{}{:04}", start, counter);
+            if !values.contains_key(&candidate) {
+                return candidate;
+            }
+        }
+    }
+}
+
+// Escribe la copia anonimizada (por defecto <archivo>_anonimo junto al original)
+pub fn anonymize(options: &AnonymizeOptions) -> Result<Anonymized> {
+    let path = Path::new(&options.file);
+    let mut data = convert::read_any(path)?;
+    let mut targets: Vec<(usize, Vec<usize>)> = Vec::new();
+    match &options.sheet {
+        Some(sheet) => {
+            let sheet = data.require_sheet(&options.file, sheet)?;
+            let columns = options
+                .columns
+                .iter()
+                .map(|spec| analysis::require_column(sheet, spec))
+                .collect::<Result<Vec<usize>>>()?;
+            let idx = data.sheets.iter().position(|s| s.name == sheet.name).unwrap_or_default();
+            targets.push((idx, columns));
+        }
+        None => {
+            for (idx, sheet) in data.sheets.iter().enumerate() {
+                let headers = sheet.headers();
+                let columns: Vec<usize> = headers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, header)| options.columns.iter().any(|c| c.trim().eq_ignore_ascii_case(header.trim())))
+                    .map(|(col, _)| col)
+                    .collect();
+                if !columns.is_empty() {
+                    targets.push((idx, columns));
+                }
+            }
+            for column in &options.columns {
+                let found = targets
+                    .iter()
+                    .any(|(idx, cols)| cols.iter().any(|col| data.sheets[*idx].headers()[*col].trim().eq_ignore_ascii_case(column.trim())));
+                if !found {
+                    bail!("Ninguna hoja de {} tiene la columna '{}'; con hoja=<nombre> también vale la letra", options.file, column);
+                }
+            }
+        }
+    }
+
+    let output = match &options.output {
+        Some(output) => outputs::target(output)?,
+        None => {
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("libro");
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("xlsx");
+            outputs::derived(path, &format!("{}_anonimo.{}", stem, extension))?
+        }
+    };
+    if same_file(&output, &options.file) {
+        bail!("La copia anonimizada debe ser otro archivo: {} conserva los datos reales", options.file);
+    }
+
+    let mut mapping = Mapping::load()?;
+    if options.method == Method::Hash {
+        mapping.ensure_hash_key()?;
+    }
+    let mut substitutes = Substitutes::new(mapping);
+    let mut cells = 0;
+    let mut described = Vec::new();
+    for (idx, columns) in targets {
+        let sheet = &mut data.sheets[idx];
+        let headers = sheet.headers();
+        for &col in &columns {
+            let header = headers.get(col).cloned().unwrap_or_default();
+            cells += anonymize_column(sheet, col, &header, options.method, &mut substitutes);
+            described.push(format!("{}!{}", sheet.name, header));
+        }
+    }
+    // La tabla se guarda antes que la copia: un sustituto escrito siempre se puede deshacer
+    if substitutes.added > 0 {
+        substitutes.mapping.save()?;
+    }
+    convert::write_any(Path::new(&output), &data)?;
+    Ok(Anonymized {
+        output,
+        cells,
+        new_values: substitutes.added,
+        columns: described,
+    })
+}
+
+fn anonymize_column(sheet: &mut SheetData, col: usize, header: &str, method: Method, substitutes: &mut Substitutes) -> usize {
+    let mut count = 0;
+    let start = sheet.data_start();
+    for row in sheet.rows.iter_mut().skip(start) {
+        let Some(cell) = row.get_mut(col) else { continue };
+        if let Some(substitute) = substitutes.substitute(cell, header, method) {
+            *cell = CellValue::Text(substitute);
+            count += 1;
+        }
+    }
+    count
+}
+
+// Devuelve los valores reales a las celdas que son un sustituto, en el archivo
+// (o en `output`); devuelve las celdas restauradas y el archivo escrito
+pub fn restore(file: &str, output: Option<&str>) -> Result<(usize, String)> {
+    let mapping = Mapping::load()?;
+    if mapping.values.is_empty() {
+        bail!("No hay datos anonimizados en {}", mapping_file().display());
+    }
+    let mut data = convert::read_any(Path::new(file))?;
+    let mut restored = 0;
+    for sheet in &mut data.sheets {
+        for row in &mut sheet.rows {
+            for cell in row.iter_mut() {
+                let CellValue::Text(text) = cell else { continue };
+                if let Some(original) = mapping.values.get(text.trim()) {
+                    *cell = convert::cell_from_json(original);
+                    restored += 1;
+                }
+            }
+        }
+    }
+    if restored == 0 {
+        bail!("{} no tiene ningún valor anonimizado", file);
+    }
+    let output = match output {
+        Some(output) => outputs::target(output)?,
+        None => file.to_string(),
+    };
+    convert::write_any(Path::new(&output), &data)?;
+    Ok((restored, output))
+}
+
+// "Cliente" para la columna "cliente" o "nombre del cliente" → "Nombre"
+fn prefix(header: &str) -> String {
+    let word: String = header
+        .split(|c: char| !c.is_alphanumeric())
+        .find(|word| !word.is_empty())
+        .unwrap_or("Valor")
+        .to_lowercase();
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => "Valor".to_string(),
+    }
+}
+
+fn is_email(text: &str) -> bool {
+    let text = text.trim();
+    !text.contains(char::is_whitespace)
+        && text.split_once('@').is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'))
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => Path::new(a) == Path::new(b),
+    }
+}
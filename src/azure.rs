@@ -0,0 +1,97 @@
+// Azure OpenAI: cada modelo se publica como un despliegue con nombre propio en
+// `https://{recurso}.openai.azure.com/openai/deployments/{despliegue}/chat/completions`,
+// con la versión de la API en `api-version` y la clave en la cabecera `api-key`
+// en lugar de un Bearer. Se activa con IAGENT_AZURE_RESOURCE (o
+// IAGENT_AZURE_ENDPOINT para un dominio propio); IAGENT_AZURE_DEPLOYMENTS asigna
+// a cada modelo lógico (el de DEEPSEEK_MODEL, `modelo` o IAGENT_SUMMARY_MODEL)
+// su despliegue. Un modelo sin despliegue asignado usa su propio nombre.
+use crate::profiles;
+use anyhow::{bail, Context, Result};
+
+// Última versión estable de la API de inferencia
+const DEFAULT_API_VERSION: &str = "2024-10-21";
+
+#[derive(Debug, Clone)]
+pub struct AzureSettings {
+    // `https://{recurso}.openai.azure.com`, sin barra final
+    pub endpoint: String,
+    pub api_version: String,
+    // (modelo, despliegue) en el orden de IAGENT_AZURE_DEPLOYMENTS
+    pub deployments: Vec<(String, String)>,
+}
+
+impl AzureSettings {
+    // None si no se ha configurado ningún recurso de Azure
+    pub fn from_env() -> Result<Option<AzureSettings>> {
+        let endpoint = match (profiles::var("IAGENT_AZURE_ENDPOINT"), profiles::var("IAGENT_AZURE_RESOURCE")) {
+            (Ok(endpoint), _) if !endpoint.trim().is_empty() => endpoint.trim().trim_end_matches('/').to_string(),
+            (_, Ok(resource)) if !resource.trim().is_empty() => {
+                let resource = resource.trim();
+                if !resource.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    bail!("Nombre de recurso no válido en IAGENT_AZURE_RESOURCE: '{}'", resource);
+                }
+                format!("https://{}.openai.azure.com", resource)
+            }
+            _ => return Ok(None),
+        };
+        // Quien copia la URL del portal suele incluir la ruta /openai
+        let endpoint = endpoint.strip_suffix("/openai").unwrap_or(&endpoint).to_string();
+        if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") {
+            bail!("IAGENT_AZURE_ENDPOINT debe empezar por https:// ('{}')", endpoint);
+        }
+        let deployments = match profiles::var("IAGENT_AZURE_DEPLOYMENTS") {
+            Ok(value) => parse_deployments(&value)?,
+            Err(_) => Vec::new(),
+        };
+        Ok(Some(AzureSettings {
+            endpoint,
+            api_version: profiles::var("IAGENT_AZURE_API_VERSION")
+                .ok()
+                .map(|version| version.trim().to_string())
+                .filter(|version| !version.is_empty())
+                .unwrap_or_else(|| DEFAULT_API_VERSION.to_string()),
+            deployments,
+        }))
+    }
+
+    // Despliegue de un modelo lógico; sin asignación, el propio nombre del modelo
+    pub fn deployment<'a>(&'a self, model: &'a str) -> &'a str {
+        self.deployments
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(model))
+            .map(|(_, deployment)| deployment.as_str())
+            .unwrap_or(model)
+    }
+
+    pub fn completions_url(&self, model: &str) -> String {
+        format!("{}/openai/deployments/{}/chat/completions", self.endpoint, self.deployment(model))
+    }
+
+    pub fn embeddings_url(&self, model: &str) -> String {
+        format!("{}/openai/deployments/{}/embeddings", self.endpoint, self.deployment(model))
+    }
+
+    // Para `modelo` y el diagnóstico
+    pub fn describe(&self, model: &str) -> String {
+        format!("Azure {}, despliegue {}", self.endpoint, self.deployment(model))
+    }
+}
+
+// "gpt-4o=prod-gpt4o, deepseek-chat=ds-chat"
+fn parse_deployments(value: &str) -> Result<Vec<(String, String)>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (model, deployment) = pair
+                .split_once('=')
+                .context(format!("Despliegue no válido en IAGENT_AZURE_DEPLOYMENTS: '{}' (usa modelo=despliegue)", pair))?;
+            let (model, deployment) = (model.trim(), deployment.trim());
+            if model.is_empty() || deployment.is_empty() || deployment.contains('/') {
+                bail!("Despliegue no válido en IAGENT_AZURE_DEPLOYMENTS: '{}' (usa modelo=despliegue)", pair);
+            }
+            Ok((model.to_string(), deployment.to_string()))
+        })
+        .collect()
+}
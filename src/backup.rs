@@ -0,0 +1,147 @@
+// Copias de seguridad automáticas antes de escribir un archivo y su restauración
+// con `deshacer`. Cada archivo tiene su propia carpeta en backups, dentro del
+// directorio de datos, con
+// una copia por operación, nombrada con la marca de tiempo en milisegundos.
+use crate::excel;
+use crate::paths;
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Copias que se conservan por archivo
+const MAX_BACKUPS_PER_FILE: usize = 20;
+// Marca de que el archivo no existía: deshacer lo elimina
+const CREATED_MARKER: &str = "nuevo";
+
+// Archivos ya copiados en la operación en curso; un comando que escribe varias
+// veces el mismo archivo genera una sola copia (la del estado anterior al comando)
+static BACKED_UP: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+pub enum RestoreOutcome {
+    // Se restauró la copia hecha en esa fecha (ISO)
+    Restored(String),
+    // El archivo lo había creado el agente y se ha eliminado
+    Removed,
+}
+
+// Empieza una operación nueva (un comando o una pregunta del usuario)
+pub fn begin_operation() {
+    if let Ok(mut backed_up) = BACKED_UP.lock() {
+        *backed_up = Some(HashSet::new());
+    }
+}
+
+// Guarda el estado actual de `path` antes de sobrescribirlo
+pub fn before_write(path: &Path) -> Result<()> {
+    let key = absolute(path);
+    if let Ok(mut backed_up) = BACKED_UP.lock() {
+        if !backed_up.get_or_insert_with(HashSet::new).insert(key) {
+            return Ok(());
+        }
+    }
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir).context(format!("No se pudo crear {}", dir.display()))?;
+    let stamp = timestamp_millis();
+    if path.exists() {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("bak");
+        let target = dir.join(format!("{:013}.{}", stamp, extension));
+        fs::copy(path, &target).context(format!(
+            "No se pudo hacer la copia de seguridad de {} en {}",
+            path.display(),
+            target.display()
+        ))?;
+    } else {
+        fs::write(dir.join(format!("{:013}.{}", stamp, CREATED_MARKER)), "")?;
+    }
+    prune(&dir)
+}
+
+// Restaura la copia más reciente y la retira, de modo que llamadas sucesivas
+// van deshaciendo operaciones anteriores
+pub fn restore_latest(path: &Path) -> Result<RestoreOutcome> {
+    let dir = backup_dir(path);
+    let Some(latest) = list_backups(&dir).pop() else {
+        bail!("No hay copias de seguridad de {}", path.display());
+    };
+    let is_created_marker = latest.extension().and_then(|e| e.to_str()) == Some(CREATED_MARKER);
+    let outcome = if is_created_marker {
+        if path.exists() {
+            fs::remove_file(path).context(format!("No se pudo eliminar {}", path.display()))?;
+        }
+        RestoreOutcome::Removed
+    } else {
+        fs::copy(&latest, path).context(format!("No se pudo restaurar {}", path.display()))?;
+        let millis: u64 = latest
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        RestoreOutcome::Restored(excel::excel_serial_to_iso(
+            millis as f64 / 86_400_000.0 + excel::EXCEL_EPOCH_OFFSET as f64,
+        ))
+    };
+    fs::remove_file(&latest)?;
+    Ok(outcome)
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    let absolute = absolute(path);
+    let name = absolute
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "archivo".to_string());
+    // El hash de la ruta completa distingue archivos homónimos de carpetas distintas
+    paths::backups_dir().join(format!("{}-{:016x}", name, fnv1a(absolute.to_string_lossy().as_bytes())))
+}
+
+// Ruta absoluta aunque el archivo todavía no exista
+fn absolute(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let parent = parent.canonicalize().unwrap_or(parent);
+    match path.file_name() {
+        Some(name) => parent.join(name),
+        None => parent,
+    }
+}
+
+// Copias ordenadas de la más antigua a la más reciente
+fn list_backups(dir: &Path) -> Vec<PathBuf> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    backups.sort();
+    backups
+}
+
+fn prune(dir: &Path) -> Result<()> {
+    let backups = list_backups(dir);
+    if backups.len() > MAX_BACKUPS_PER_FILE {
+        for old in &backups[..backups.len() - MAX_BACKUPS_PER_FILE] {
+            fs::remove_file(old)?;
+        }
+    }
+    Ok(())
+}
+
+fn timestamp_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+// Hash FNV-1a: estable entre versiones de Rust, a diferencia de DefaultHasher
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,180 @@
+// Preguntas en lote: la misma pregunta sobre cada archivo de un patrón, con
+// una conversación independiente por archivo y las respuestas consolidadas en una hoja
+use crate::agent;
+use crate::config::Config;
+use crate::convert;
+use crate::excel::{CellValue, SheetData};
+use crate::files;
+use crate::interrupt;
+use crate::llm::Message;
+use crate::progress::ProgressBar;
+use crate::prompts;
+use crate::summary;
+use crate::timing::Timings;
+use crate::untrusted;
+use crate::usage::UsageTracker;
+use anyhow::{bail, Result};
+use reqwest::Client;
+use serde_json::Value;
+
+pub const ANSWERS_SHEET: &str = "Respuestas";
+
+const ANSWER_INSTRUCTIONS: &str = "Responde solo con los datos de este archivo; si necesitas cifras exactas usa las herramientas (leer_excel, agregar) sobre él. Devuelve SOLO un objeto JSON {\"respuesta\": \"...\", \"citas\": [\"Hoja!A1:B10\"]} donde las citas son las hojas y rangos en los que se basa la respuesta.";
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    pub question: String,
+    pub pattern: String,
+    pub output: String,
+}
+
+// Respuesta obtenida para un archivo
+#[derive(Debug, Clone)]
+pub struct BatchAnswer {
+    pub file: String,
+    pub answer: String,
+    pub citations: Vec<String>,
+    pub failed: bool,
+}
+
+// Resultado del lote; `interrupted` indica que Ctrl-C lo detuvo antes del final
+pub struct BatchResult {
+    pub answers: Vec<BatchAnswer>,
+    pub interrupted: bool,
+}
+
+// Hace la pregunta sobre cada archivo con el bucle de herramientas. Cada archivo
+// parte de un historial limpio para que las respuestas no se contaminen entre sí.
+pub async fn run(
+    client: &Client,
+    config: &Config,
+    usage_tracker: &mut UsageTracker,
+    timings: &mut Timings,
+    system_template: &str,
+    options: &BatchOptions,
+) -> Result<BatchResult> {
+    let paths = files::expand_pattern(&options.pattern)?;
+    if paths.is_empty() {
+        bail!("Ningún archivo coincide con '{}'", options.pattern);
+    }
+
+    let mut answers = Vec::new();
+    let mut progress = ProgressBar::new("Lote", paths.len());
+    for (idx, path) in paths.iter().enumerate() {
+        let file = path.display().to_string();
+        progress.println(&format!("❓ [{}/{}] {}", idx + 1, paths.len(), file));
+        let data = match convert::read_any(path) {
+            Ok(data) => data,
+            Err(e) => {
+                progress.println(&format!("❌ {}: {:#}", file, e));
+                answers.push(BatchAnswer::failure(&file, format!("No se pudo leer: {:#}", e)));
+                progress.inc(&file);
+                continue;
+            }
+        };
+
+        let sheets = vec![(file.clone(), data.sheets.iter().map(|s| s.name.clone()).collect())];
+        let mut history = vec![
+            Message::new("system", prompts::render(system_template, &prompts::workbook_vars(&sheets))),
+            untrusted::file_message(&format!(
+                "Datos del archivo Excel '{}': {}",
+                file,
+                summary::summarize_workbook(&data, config.context_budget.per_item)
+            )),
+            Message::new(
+                "user",
+                format!("Archivo: {}\nPregunta: {}\n{}", file, options.question, ANSWER_INSTRUCTIONS),
+            ),
+        ];
+
+        match interrupt::interruptible(agent::ask_model(client, config, &mut history, usage_tracker, timings)).await {
+            Some(Ok(text)) => {
+                let answer = parse_answer(&file, &text);
+                progress.println(&format!("✅ {}", answer.answer));
+                answers.push(answer);
+            }
+            Some(Err(e)) => {
+                progress.println(&format!("❌ {}: {:#}", file, e));
+                answers.push(BatchAnswer::failure(&file, format!("Error: {:#}", e)));
+            }
+            None => {
+                progress.println(&format!(
+                    "⏹ Lote detenido en {}; se guardan las {} respuestas obtenidas",
+                    file,
+                    answers.len()
+                ));
+                return Ok(BatchResult { answers, interrupted: true });
+            }
+        }
+        progress.inc(&file);
+    }
+    Ok(BatchResult { answers, interrupted: false })
+}
+
+impl BatchAnswer {
+    fn failure(file: &str, message: String) -> BatchAnswer {
+        BatchAnswer {
+            file: file.to_string(),
+            answer: message,
+            citations: Vec::new(),
+            failed: true,
+        }
+    }
+}
+
+// Interpreta la respuesta JSON del modelo; si no lo es, el texto completo es la respuesta
+fn parse_answer(file: &str, text: &str) -> BatchAnswer {
+    let parsed = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str::<Value>(&text[start..=end]).ok(),
+        _ => None,
+    };
+    let answer = parsed
+        .as_ref()
+        .and_then(|value| value.get("respuesta"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let citations = parsed
+        .as_ref()
+        .and_then(|value| value.get("citas"))
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(Value::as_str).map(str::to_string).collect())
+        .unwrap_or_default();
+    BatchAnswer {
+        file: file.to_string(),
+        answer: answer.unwrap_or_else(|| text.trim().to_string()),
+        citations,
+        failed: false,
+    }
+}
+
+// Hoja consolidada: una fila por archivo con la respuesta y sus citas
+pub fn answers_sheet(answers: &[BatchAnswer]) -> SheetData {
+    let mut sheet = SheetData::new(ANSWERS_SHEET);
+    sheet.rows.push(
+        ["Archivo", "Respuesta", "Citas", "Estado"]
+            .iter()
+            .map(|h| CellValue::Text(h.to_string()))
+            .collect(),
+    );
+    for answer in answers {
+        sheet.rows.push(vec![
+            CellValue::Text(answer.file.clone()),
+            CellValue::Text(answer.answer.clone()),
+            CellValue::Text(answer.citations.join("; ")),
+            CellValue::Text(if answer.failed { "error" } else { "ok" }.to_string()),
+        ]);
+    }
+    sheet
+}
+
+// Resumen de las respuestas para dejarlo en el contexto de la sesión
+pub fn context_summary(options: &BatchOptions, answers: &[BatchAnswer]) -> String {
+    let mut text = format!("Respuestas en lote a \"{}\" sobre '{}':", options.question, options.pattern);
+    for answer in answers {
+        text.push_str(&format!("\n- {}: {}", answer.file, answer.answer));
+        if !answer.citations.is_empty() {
+            text.push_str(&format!(" [{}]", answer.citations.join("; ")));
+        }
+    }
+    text
+}
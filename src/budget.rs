@@ -0,0 +1,152 @@
+// Presupuesto de tokens para todo lo que se inserta en el contexto del modelo
+// (resúmenes de archivos, resultados de análisis y de herramientas)
+use crate::llm::Message;
+use crate::profiles;
+use crate::untrusted;
+
+const DEFAULT_ITEM_TOKENS: usize = 1_500;
+const DEFAULT_TOTAL_TOKENS: usize = 8_000;
+// Por debajo de esto un elemento recortado ya no aporta nada
+const MIN_ITEM_TOKENS: usize = 100;
+const TRUNCATION_MARK: &str = " … [truncado]";
+
+#[derive(Debug, Clone, Copy)]
+pub struct TokenBudget {
+    // Máximo por elemento insertado
+    pub per_item: usize,
+    // Máximo entre todos los elementos insertados que hay en el historial
+    pub total: usize,
+}
+
+impl TokenBudget {
+    // IAGENT_CONTEXT_ITEM_TOKENS / IAGENT_CONTEXT_TOTAL_TOKENS
+    pub fn from_env() -> TokenBudget {
+        let read = |name: &str, default: usize| {
+            profiles::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|v: &usize| *v > 0)
+                .unwrap_or(default)
+        };
+        let total = read("IAGENT_CONTEXT_TOTAL_TOKENS", DEFAULT_TOTAL_TOKENS);
+        TokenBudget {
+            per_item: read("IAGENT_CONTEXT_ITEM_TOKENS", DEFAULT_ITEM_TOKENS).min(total),
+            total,
+        }
+    }
+}
+
+// Estimación conservadora (unos 3 caracteres por token): mejor quedarse corto
+// de contexto que superar el límite de la petición
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(3)
+}
+
+// Recorta el texto para que nunca supere `max_tokens`, en un límite de línea si es posible
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    if estimate_tokens(text) <= max_tokens {
+        return text.to_string();
+    }
+    let max_chars = (max_tokens * 3).saturating_sub(TRUNCATION_MARK.chars().count());
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind('\n') {
+        Some(line_end) if line_end > cut.len() / 2 => cut[..line_end].to_string(),
+        _ => cut,
+    };
+    format!("{}{}", cut, TRUNCATION_MARK)
+}
+
+// Tokens que ocupan los elementos insertados (todo salvo el prompt de sistema
+// inicial y la conversación con el usuario)
+pub fn context_tokens(history: &[Message]) -> usize {
+    history
+        .iter()
+        .skip(1)
+        .filter(|message| message.role == "system" || message.role == "tool" || untrusted::is_file_content(message))
+        .map(|message| estimate_tokens(&message.content))
+        .sum()
+}
+
+// Resultado de ajustar un elemento al presupuesto
+pub enum Fitted {
+    Complete(String),
+    // Recortado; incluye los tokens estimados del original
+    Truncated(String, usize),
+    // No queda presupuesto en la sesión
+    Rejected,
+}
+
+// Ajusta un elemento al presupuesto por elemento y a lo que queda del total
+pub fn fit(history: &[Message], budget: &TokenBudget, text: &str) -> Fitted {
+    let remaining = budget.total.saturating_sub(context_tokens(history));
+    let limit = budget.per_item.min(remaining);
+    if limit < MIN_ITEM_TOKENS {
+        return Fitted::Rejected;
+    }
+    let tokens = estimate_tokens(text);
+    if tokens <= limit {
+        Fitted::Complete(text.to_string())
+    } else {
+        Fitted::Truncated(truncate_to_tokens(text, limit), tokens)
+    }
+}
+
+// Tokens disponibles para el próximo elemento
+pub fn available(history: &[Message], budget: &TokenBudget) -> usize {
+    budget
+        .per_item
+        .min(budget.total.saturating_sub(context_tokens(history)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGET: TokenBudget = TokenBudget {
+        per_item: 200,
+        total: 500,
+    };
+
+    #[test]
+    fn truncation_never_exceeds_the_limit_and_prefers_a_line_end() {
+        let text: String = (1..=100).map(|n| format!("fila {}\n", n)).collect();
+        let cut = truncate_to_tokens(&text, 50);
+        assert!(estimate_tokens(&cut) <= 50, "{}", cut);
+        // Se corta al final de una fila, no en medio
+        let kept = cut.strip_suffix(TRUNCATION_MARK).unwrap();
+        assert!(text.starts_with(&format!("{}\n", kept)), "{}", cut);
+        // Lo que cabe se deja tal cual
+        assert_eq!(truncate_to_tokens("corto", 50), "corto");
+    }
+
+    #[test]
+    fn only_inserted_content_counts_against_the_total() {
+        let history = vec![
+            Message::new("system", "a".repeat(3_000)),
+            Message::new("user", "b".repeat(3_000)),
+            Message::new("assistant", "c".repeat(3_000)),
+            Message::tool_result("call_1", "d".repeat(300)),
+            untrusted::file_message(&"e".repeat(300)),
+        ];
+        // El prompt de sistema y la conversación no cuentan; el aviso y los delimitadores sí
+        let file_tokens = estimate_tokens(&history[4].content);
+        assert_eq!(context_tokens(&history), 100 + file_tokens);
+        assert_eq!(available(&history, &BUDGET), 200.min(500 - 100 - file_tokens));
+    }
+
+    #[test]
+    fn items_are_kept_cut_or_rejected_by_what_is_left() {
+        let empty = [Message::new("system", "")];
+        assert!(matches!(fit(&empty, &BUDGET, "hola"), Fitted::Complete(text) if text == "hola"));
+        match fit(&empty, &BUDGET, &"x".repeat(900)) {
+            Fitted::Truncated(text, original) => {
+                assert_eq!(original, 300);
+                assert!(estimate_tokens(&text) <= 200);
+            }
+            _ => panic!("se esperaba un recorte"),
+        }
+        // Con 420 tokens ocupados quedan 80, menos que el mínimo útil
+        let full = [Message::new("system", ""), Message::tool_result("call_1", "x".repeat(1_260))];
+        assert!(matches!(fit(&full, &BUDGET, "hola"), Fitted::Rejected));
+    }
+}
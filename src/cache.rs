@@ -0,0 +1,118 @@
+// Caché en disco de las respuestas del modelo, indexada por el SHA-256 del
+// proveedor, el endpoint, el modelo y el cuerpo de la petición (mensajes,
+// herramientas y parámetros). Repetir la misma pregunta sobre los mismos datos no
+// vuelve a llamar (ni a facturar) a la API. Solo se usa si se activa con
+// IAGENT_CACHE_TTL.
+use crate::crypto;
+use crate::llm::DeepseekMessage;
+use crate::paths;
+use crate::usage::Usage;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    // Segundos desde la época Unix
+    created: u64,
+    message: DeepseekMessage,
+    usage: Option<Usage>,
+}
+
+// Clave de caché para una petición; dos endpoints con el mismo modelo (un proxy y
+// la API directa) no comparten respuestas
+pub fn key(provider: &str, url: &str, model: &str, request_body: &Value) -> String {
+    let text = format!("{}\n{}\n{}\n{}", provider, url, model, request_body);
+    crypto::hex(&crypto::sha256(text.as_bytes()))
+}
+
+// Respuesta guardada para `key` si existe y no ha caducado; las caducadas se borran
+pub fn lookup(key: &str, ttl: Duration) -> Option<(DeepseekMessage, Option<Usage>)> {
+    let path = entry_path(key);
+    let text = fs::read_to_string(&path).ok()?;
+    let entry: CachedResponse = match serde_json::from_str(&text) {
+        Ok(entry) => entry,
+        Err(_) => {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+    };
+    if now_secs().saturating_sub(entry.created) > ttl.as_secs() {
+        let _ = fs::remove_file(&path);
+        return None;
+    }
+    Some((entry.message, entry.usage))
+}
+
+// Guarda una respuesta; se escribe a un temporal y se renombra para no dejar entradas a medias
+pub fn store(key: &str, message: &DeepseekMessage, usage: Option<Usage>) -> Result<()> {
+    let dir = paths::cache_dir();
+    fs::create_dir_all(&dir).context(format!("No se pudo crear {}", dir.display()))?;
+    let entry = CachedResponse {
+        created: now_secs(),
+        message: message.clone(),
+        usage,
+    };
+    let path = entry_path(key);
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, serde_json::to_string(&entry)?)?;
+    fs::rename(&temp, &path).context(format!("No se pudo guardar {}", path.display()))?;
+    Ok(())
+}
+
+// Número de entradas y bytes ocupados
+pub fn stats() -> (usize, u64) {
+    entries().iter().fold((0, 0), |(count, bytes), path| {
+        (count + 1, bytes + fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+    })
+}
+
+// Borra todas las entradas; devuelve cuántas había
+pub fn clear() -> Result<usize> {
+    let entries = entries();
+    for path in &entries {
+        fs::remove_file(path).context(format!("No se pudo borrar {}", path.display()))?;
+    }
+    Ok(entries.len())
+}
+
+fn entries() -> Vec<PathBuf> {
+    fs::read_dir(paths::cache_dir())
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn entry_path(key: &str) -> PathBuf {
+    paths::cache_dir().join(format!("{}.json", key))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keys_separate_providers_and_endpoints() {
+        let body = json!({"messages": [{"role": "user", "content": "hola"}]});
+        let direct = key("deepseek", "https://api.deepseek.com/v1/chat/completions", "deepseek-chat", &body);
+        assert_eq!(direct.len(), 64);
+        assert_eq!(direct, key("deepseek", "https://api.deepseek.com/v1/chat/completions", "deepseek-chat", &body));
+        assert_ne!(direct, key("deepseek", "http://localhost:4000/chat/completions", "deepseek-chat", &body));
+        assert_ne!(direct, key("openai", "https://api.deepseek.com/v1/chat/completions", "deepseek-chat", &body));
+        assert_ne!(direct, key("deepseek", "https://api.deepseek.com/v1/chat/completions", "deepseek-reasoner", &body));
+    }
+}
@@ -0,0 +1,112 @@
+// Fragmentos de filas con identificador estable. Al leer un libro, el modelo
+// recibe el resumen (estadísticas por columna y una muestra) junto con la lista
+// de fragmentos de cada hoja: bloques de CHUNK_ROWS filas de datos con un id
+// "<hoja>#<n>" desde 1. Con la herramienta leer_fragmento pide las filas de un
+// bloque concreto ("más filas del fragmento 3") en lugar de quedarse con la
+// muestra recortada. El id solo depende de la hoja y de la posición de las
+// filas, así que no cambia mientras el libro no cambie.
+use crate::budget;
+use crate::excel::WorkbookData;
+use crate::table;
+use anyhow::{bail, Context, Result};
+
+pub const CHUNK_ROWS: usize = 50;
+
+// Fragmentos de una hoja con `data_rows` filas de datos
+fn chunk_count(data_rows: usize) -> usize {
+    data_rows.div_ceil(CHUNK_ROWS)
+}
+
+// Una línea por hoja con sus fragmentos:
+// "Ventas: Ventas#1 a Ventas#3 (filas 2 a 121, 50 por fragmento)"
+pub fn index(data: &WorkbookData) -> String {
+    let mut lines = Vec::new();
+    for sheet in &data.sheets {
+        let start = sheet.data_start();
+        let data_rows = sheet.rows.len().saturating_sub(start);
+        let first_row = start + 1;
+        let last_row = sheet.rows.len();
+        let line = match chunk_count(data_rows) {
+            0 => format!("{}: sin filas de datos", sheet.name),
+            1 => format!("{}: {}#1 (filas {} a {})", sheet.name, sheet.name, first_row, last_row),
+            count => format!(
+                "{}: {}#1 a {}#{} (filas {} a {}, {} por fragmento)",
+                sheet.name, sheet.name, sheet.name, count, first_row, last_row, CHUNK_ROWS
+            ),
+        };
+        lines.push(line);
+    }
+    format!(
+        "Fragmentos (con leer_fragmento se piden las filas de uno, p. ej. {{\"fragmento\": \"{}#2\"}}):\n{}",
+        data.sheets.first().map_or("Hoja", |sheet| sheet.name.as_str()),
+        lines.join("\n")
+    )
+}
+
+// Filas del fragmento `id` ("Ventas#3"), desde la fila `from` de Excel si se
+// indica, hasta llenar `max_tokens`; si no caben todas se dice desde dónde seguir
+pub fn read(file: &str, data: &WorkbookData, id: &str, from: Option<usize>, max_tokens: usize) -> Result<String> {
+    let (sheet_name, number) = id
+        .trim()
+        .rsplit_once('#')
+        .context(format!("Fragmento no válido: '{}' (usa <hoja>#<n>, p. ej. Ventas#2)", id))?;
+    let number: usize = number
+        .trim()
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)
+        .context(format!("Fragmento no válido: '{}' (el número empieza en 1)", id))?;
+    let sheet = data.require_sheet(file, sheet_name.trim())?;
+    let start = sheet.data_start();
+    let count = chunk_count(sheet.rows.len().saturating_sub(start));
+    if number > count {
+        bail!("La hoja {} de {} tiene {} fragmentos", sheet.name, file, count);
+    }
+    let first = start + (number - 1) * CHUNK_ROWS;
+    let last = (first + CHUNK_ROWS).min(sheet.rows.len());
+    // `from` es una fila de Excel (desde 1) dentro del fragmento
+    let begin = match from {
+        Some(row) if row > first && row <= last => row - 1,
+        Some(row) => bail!("La fila {} no está en el fragmento {} (filas {} a {})", row, id, first + 1, last),
+        _ => first,
+    };
+
+    let mut headers = vec!["Fila".to_string()];
+    headers.extend(sheet.headers());
+    let mut rows = Vec::new();
+    let mut end = begin;
+    for (idx, row) in sheet.rows.iter().enumerate().take(last).skip(begin) {
+        let mut values = vec![(idx + 1).to_string()];
+        values.extend(row.iter().map(|cell| cell.to_string()));
+        rows.push(values);
+        if rows.len() > 1 && budget::estimate_tokens(&table::render_table(&headers, &rows)) > max_tokens {
+            rows.pop();
+            break;
+        }
+        end = idx + 1;
+    }
+
+    let mut output = format!(
+        "Fragmento {}#{} de {} (filas {} a {} de {}):\n{}",
+        sheet.name,
+        number,
+        file,
+        begin + 1,
+        end,
+        sheet.rows.len(),
+        table::render_table(&headers, &rows)
+    );
+    if end < last {
+        output.push_str(&format!(
+            "\nNo caben las filas {} a {}: pide de nuevo {}#{} con desde={}",
+            end + 1,
+            last,
+            sheet.name,
+            number,
+            end + 1
+        ));
+    } else if number < count {
+        output.push_str(&format!("\nSiguiente: {}#{}", sheet.name, number + 1));
+    }
+    Ok(output)
+}
@@ -0,0 +1,4110 @@
+// La terminal del binario `ia_agent`: la sesión interactiva, el servidor, el
+// modo de vigilancia y el diagnóstico. No forma parte de la API de la biblioteca.
+use crate::{
+    agent, analysis, anonymize, backup, batch, cache, chunks, commands, compare, compress, budget, clipboard, conditional_format, config,
+    contexts, convert, crypto, dates, doctor, duplicates, error, excel, explain, export, extract, files, fill, formula,
+    formula_check, formulas, hyperlinks, i18n, interrupt, jobs, join, layout, limits, llm, macros, merges, metadata, model_compare, models, named_ranges::{self, SpillPolicy}, notes, outline,
+    outputs, pager, paths, profiles, progress, protection, prompts, readme, report, retrieval, row_prompts, schemas,
+    script, search, server, settings, structure, structured, suggest, summary, table, tables, text_chart, timing, tour, transform, untrusted,
+    usage, validation, verify, watch, workbook_cache,
+};
+use analysis::{CohortOptions, ParetoOptions, RankOptions, StatsOptions};
+use anonymize::AnonymizeOptions;
+use batch::BatchOptions;
+use budget::{Fitted, TokenBudget};
+use compare::CompareOptions;
+use anyhow::{bail, Context, Result};
+use conditional_format::{ConditionalFormatOptions, ConditionalRule};
+use crypto::{ColumnCryptoOptions, ColumnKey};
+use dates::{DateOptions, DayOrder};
+use duplicates::DuplicateOptions;
+use join::JoinOptions;
+use config::Config;
+use contexts::{ContextCommand, Contexts, Conversation, Switch};
+use convert::{ConvertOptions, FileFormat};
+use dotenv::dotenv;
+use error::IAgentError;
+use i18n::{tr, Lang, Msg};
+use export::{Converter, ExportOptions};
+use fill::FillOptions;
+use excel::{create_excel_file, read_excel_file, write_excel_data, CellRange, ChartKind};
+use hyperlinks::LinkOptions;
+use layout::{LayoutOptions, LayoutSpec};
+use notes::{NoteAction, NoteOptions};
+use outline::{GroupOptions, Span};
+use tables::TableOptions;
+use protection::ProtectOptions;
+use structure::EditOptions;
+use structured::ExtractOptions;
+use commands::{looks_like_argument, Route};
+use suggest::Correction;
+use text_chart::{TextChartKind, TextChartOptions};
+use validation::{Validation, ValidationOptions};
+use llm::Message;
+use report::ReportOptions;
+use reqwest::Client;
+use row_prompts::RowPromptOptions;
+use schemas::SchemaMemory;
+use search::SearchOptions;
+use settings::Settings;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+use timing::Timings;
+use transform::TransformOptions;
+use usage::UsageTracker;
+use workbook_cache::{SaveOutcome, WorkbookCache};
+
+// Filas que se muestran al leer un archivo
+const PREVIEW_ROWS: usize = 5;
+
+// Enum para comandos de Excel
+enum ExcelCommand {
+    Undo(String),
+    // (archivo, forzar streaming, evaluar fórmulas, leer el texto de las fórmulas)
+    ReadFile(String, bool, bool, bool),
+    // (archivo, hoja)
+    Formulas(String, String),
+    Explain(String),
+    ReadMany(String),
+    // Hoja (o la primera del libro activo) y número de filas; sin él, todas por páginas
+    Show(Option<String>, Option<usize>),
+    CreateFile(String),
+    // (archivo, datos, --reescribir: aunque se pierdan fórmulas o gráficos del archivo)
+    WriteData(String, String, bool),
+    // (archivo, nombre definido o Hoja!A1:B2, valores con ',' entre celdas y ';' entre filas)
+    WriteRange(String, String, String, SpillPolicy),
+    WriteFormula(String, String, String, bool),
+    // (archivo, Hoja!A1:C1 o nombre definido)
+    MergeCells(String, String),
+    WriteLink(LinkOptions),
+    Note(NoteOptions),
+    Group(GroupOptions),
+    Table(TableOptions),
+    Convert(ConvertOptions),
+    Rank(RankOptions),
+    ConditionalFormat(ConditionalFormatOptions),
+    Validate(ValidationOptions),
+    Pareto(ParetoOptions),
+    Cohorts(CohortOptions),
+    Stats(StatsOptions),
+    AskBatch(BatchOptions),
+    RowPrompts(RowPromptOptions),
+    ExtractJson(ExtractOptions),
+    Search(SearchOptions),
+    TextChart(TextChartOptions),
+    ColumnCrypto(ColumnCryptoOptions),
+    Anonymize(AnonymizeOptions),
+    // (archivo, salida)
+    Deanonymize(String, Option<String>),
+    ConvertDates(DateOptions),
+    Compare(CompareOptions),
+    Layout(LayoutOptions),
+    Protect(ProtectOptions),
+    Report(ReportOptions),
+    // (archivo, hoja, tabla desde 1) con una tabla de la última respuesta
+    Apply(String, String, Option<usize>),
+    // (archivo, hoja) con lo que haya en el portapapeles
+    Paste(String, String),
+    // Cambio de filas o columnas en un libro cargado
+    Structure(EditOptions),
+    // Hoja y pasos tal como se escribieron; se interpretan al ejecutarlo para
+    // poder informar del paso que falla
+    Transform(Vec<String>),
+    Join(JoinOptions),
+    // Archivo, hoja, celda y valor tal como se escribió
+    EditCell(String, String, String, String),
+    Fill(FillOptions),
+    Duplicates(DuplicateOptions),
+    RemoveDuplicates(DuplicateOptions),
+    Export(ExportOptions),
+    // (archivo, o el último con cambios; otro destino; guardar_como: el libro
+    // cargado pasa a ser el del destino; --reescribir: aunque se pierdan fórmulas
+    // o gráficos del original)
+    Save(Option<String>, Option<String>, bool, bool),
+}
+
+// Punto de entrada del binario; un error que llega hasta aquí termina el programa
+pub async fn run() -> ExitCode {
+    match start().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn start() -> Result<()> {
+    // Cargar variables de entorno desde un archivo .env
+    dotenv().ok();
+    // Lo que quede en el antiguo ~/.iagent pasa a los directorios del sistema
+    for note in paths::migrate_legacy() {
+        println!("{}", note);
+    }
+    let config = Config::load()?;
+    // Los ajustes de la configuración valen para toda la sesión (y los subcomandos)
+    let settings = Settings::new(&config)?;
+    settings.use_terminal();
+    // Sin nadie en la terminal (servidor, vigilancia) las llamadas que piden confirmación se rechazan
+    if config.serve.is_none() && config.watch.is_none() {
+        settings.confirm_tools_with(Arc::new(untrusted::ask_in_terminal));
+    }
+    settings.scope(run_session(config, Arc::clone(&settings))).await
+}
+
+async fn run_session(config: Config, settings: Arc<Settings>) -> Result<()> {
+    let base_template = prompts::load_system_template(config.persona.as_deref())?;
+    let system_template = i18n::with_reply_instruction(base_template.clone(), config.reply_lang);
+    let reply_lang = config.reply_lang.unwrap_or(config.lang);
+    if config.serve.is_some() {
+        return server::run(&config, &base_template).await;
+    }
+    if let Some(settings) = &config.watch {
+        return watch::run(&config, &base_template, settings).await;
+    }
+    if config.doctor {
+        let failed = doctor::run(&llm::build_client(&config.http)?, &config).await;
+        std::process::exit(if failed == 0 { 0 } else { 1 });
+    }
+
+    println!("{}", i18n::text(Msg::Title));
+    println!("{}", i18n::text(Msg::HelpHint));
+    println!("{}", i18n::text(Msg::ExitHint));
+    if let Some(profile) = &config.profile {
+        println!("{}", tr!("ℹ️  Perfil: {} (modelo {})", "ℹ️  Profile: {} (model {})", profile, config.model));
+    }
+    if let Some(provider) = &config.provider {
+        println!("{}", tr!("ℹ️  Modelo sin red: {} (no se llama a la API)", "ℹ️  Offline model: {} (the API is not called)", provider.name()));
+    }
+    if let Some(source) = config.tools.source() {
+        println!(
+            "{}",
+            tr!(
+                "🔧 Herramientas propias de {}: {}",
+                "🔧 Custom tools from {}: {}",
+                source.display(),
+                config.tools.custom_names().join(", ")
+            )
+        );
+    }
+
+    let workbooks = WorkbookCache::new(config.context_budget.per_item);
+    let mut conversation_history: Vec<Message> = vec![Message::new(
+        "system",
+        prompts::render(&system_template, &prompts::workbook_vars(&workbooks.sheet_lists())),
+    )];
+    // IAGENT_SCHEMA_MEMORY=0 desactiva la memoria de esquemas
+    let schema_memory = match SchemaMemory::load() {
+        Ok(memory) if config.schema_memory => Some(memory),
+        Ok(_) => None,
+        Err(e) => {
+            println!("⚠️  {:#}", e);
+            None
+        }
+    };
+    if let Some((count, text)) = schema_memory.as_ref().and_then(SchemaMemory::context) {
+        push_context(&mut conversation_history, &config.context_budget, text);
+        println!(
+            "{}",
+            tr!(
+                "🧠 Se conoce la estructura de {} libros de sesiones anteriores (refrescar <archivo> la actualiza)",
+                "🧠 The structure of {} workbooks from earlier sessions is known (refresh <file> updates it)",
+                count
+            )
+        );
+    }
+
+    interrupt::install();
+    let client = llm::build_client(&config.http)?;
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    let tour = if config.start_tour { Some(tour::Tour::start()?) } else { None };
+    let script = config.script.as_deref().map(script::Script::load).transpose()?;
+    let mut session = Session {
+        config,
+        system_template,
+        reply_lang,
+        workbooks,
+        retriever: retrieval::Retriever::default(),
+        conversation_history,
+        contexts: Contexts::default(),
+        schema_memory,
+        client,
+        usage_tracker: UsageTracker::default(),
+        timings: Timings::default(),
+        stdin,
+        interactive,
+        input_closed: false,
+        tour,
+        script,
+        macro_steps: VecDeque::new(),
+        macro_total: 0,
+        session_log: script::SessionLog::default(),
+        exit_warned: false,
+        response_tables: Vec::new(),
+        settings,
+    };
+
+    loop {
+        let from_macro = !session.macro_steps.is_empty();
+        let Some(input) = session.next_input()? else {
+            continue;
+        };
+        // Los alias en inglés se traducen a la forma española del comando
+        let input = i18n::normalize_command(input.trim());
+        let input = input.as_str();
+        if input.is_empty() {
+            continue;
+        }
+        // Un comando mal escrito se corrige o se sugiere en lugar de llegar al modelo
+        let corrected;
+        let input = match command_correction(input) {
+            Some(Correction::Run(command)) => {
+                let rest = input.split_once(char::is_whitespace).map_or("", |(_, rest)| rest);
+                corrected = i18n::normalize_command(format!("{} {}", command, rest).trim());
+                println!("ℹ️  {}: {}", i18n::text(Msg::CommandCorrected), corrected);
+                corrected.as_str()
+            }
+            Some(Correction::Suggest(names)) => {
+                let typed = input.split_whitespace().next().unwrap_or_default();
+                println!("❓ {} {} {}?", typed, i18n::text(Msg::NotACommand), names.join(", "));
+                continue;
+            }
+            Some(Correction::Hint(names)) => {
+                let typed = input.split_whitespace().next().unwrap_or_default();
+                println!("ℹ️  {} {} {}", typed, i18n::text(Msg::CommandHint), names.join(", "));
+                input
+            }
+            None => input,
+        };
+        // Todos los comandos se separan en argumentos con split_quoted; en un comando,
+        // unas comillas sin cerrar son un error de uso en lugar de adivinar dónde acaban
+        let tokens = match split_quoted(input) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                let name = input.split_whitespace().next().unwrap_or_default();
+                if commands::names().any(|command| command == name) {
+                    println!("❌ {:#}", e);
+                    for usage in commands::usage(name) {
+                        println!("   {}", usage);
+                    }
+                    continue;
+                }
+                // Una pregunta para el modelo puede llevar comillas sueltas
+                Vec::new()
+            }
+        };
+        let words: Vec<&str> = tokens.iter().map(String::as_str).collect();
+
+        // Las escrituras de esta entrada comparten una sola copia de seguridad por archivo
+        backup::begin_operation();
+
+        let line = Line { input, tokens: &tokens, words: &words, from_macro };
+        match dispatch(&mut session, line).await? {
+            Flow::Done => continue,
+            Flow::Exit => break,
+            Flow::NotCommand => {}
+        }
+
+        if let Some(name) = attempted_command(input) {
+            println!("❌ {}", i18n::text(Msg::Usage));
+            for usage in commands::usage(name) {
+                println!("   {}", usage);
+            }
+            continue;
+        }
+
+        ask(&mut session, input).await?;
+    }
+
+    Ok(())
+}
+
+// Estado de la sesión interactiva que comparten los comandos
+struct Session {
+    config: Config,
+    system_template: String,
+    // Idioma de las últimas respuestas; sin idioma fijado sigue al de las preguntas
+    reply_lang: Lang,
+    // Libros cargados (nombre y hojas), usados para interpolar el prompt de sistema
+    workbooks: WorkbookCache,
+    // Índices de búsqueda de las hojas grandes leídas
+    retriever: retrieval::Retriever,
+    // Historial de conversaciones para el contexto
+    conversation_history: Vec<Message>,
+    // Otros contextos con nombre; el activo es el de los campos anteriores
+    contexts: Contexts,
+    // Estructura de los libros vistos en otras sesiones
+    schema_memory: Option<SchemaMemory>,
+    client: Client,
+    usage_tracker: UsageTracker,
+    timings: Timings,
+    // Se bloquea en cada lectura, no toda la sesión: las confirmaciones de
+    // herramientas (IAGENT_CONFIRM_TOOLS) también leen de la terminal
+    stdin: io::Stdin,
+    // Con la entrada redirigida (echo "..." | ia_agent) no se muestra el prompt y
+    // el final de la entrada equivale a `salir`
+    interactive: bool,
+    input_closed: bool,
+    // Recorrido guiado en curso: sus pasos sustituyen a la entrada del usuario
+    tour: Option<tour::Tour>,
+    // Con --guion las entradas salen del archivo y la sesión termina al acabarlo
+    script: Option<script::Script>,
+    // Pasos que quedan de las macros en curso: van antes que el guion y el prompt
+    macro_steps: VecDeque<String>,
+    // Pasos añadidos desde la última macro que se escribió en el prompt
+    macro_total: usize,
+    session_log: script::SessionLog,
+    exit_warned: bool,
+    // Tablas de la última respuesta del modelo, para `aplicar`
+    response_tables: Vec<extract::ExtractedTable>,
+    // Idioma, salidas, límites, observadores... de la configuración actual; se
+    // vuelven a configurar al cambiar de perfil
+    settings: Arc<Settings>,
+}
+
+impl Session {
+    // La siguiente entrada: un paso de macro, una línea del guion, un paso del
+    // recorrido o lo que escriba el usuario; `None` al terminar el recorrido
+    fn next_input(&mut self) -> Result<Option<String>> {
+        if let Some(step) = self.macro_steps.pop_front() {
+            println!("> {}", step);
+            return Ok(Some(step));
+        }
+        if let Some(script) = self.script.as_mut() {
+            return Ok(Some(script.next_command().unwrap_or_else(|| "salir".to_string())));
+        }
+        match self.tour.as_mut().map(|t| t.next_command(&mut self.stdin.lock())).transpose()? {
+            Some(Some(command)) => return Ok(Some(command)),
+            Some(None) => {
+                self.tour = None;
+                return Ok(None);
+            }
+            None => {}
+        }
+        if self.interactive {
+            if self.contexts.active() == contexts::DEFAULT_CONTEXT {
+                print!("> ");
+            } else {
+                print!("[{}]> ", self.contexts.active());
+            }
+            io::stdout().flush()?;
+        }
+        match read_input_line(&self.stdin, self.interactive, "")? {
+            // Un texto <<FIN sigue en las líneas siguientes hasta FIN
+            Some(line) => Ok(Some(script::read_heredoc(line, || read_input_line(&self.stdin, self.interactive, "… "))?)),
+            None => {
+                self.input_closed = true;
+                Ok(Some("salir".to_string()))
+            }
+        }
+    }
+}
+
+// Una entrada ya corregida y separada en argumentos
+#[derive(Clone, Copy)]
+struct Line<'a> {
+    input: &'a str,
+    tokens: &'a [String],
+    words: &'a [&'a str],
+    // La escribió una macro, no el usuario
+    from_macro: bool,
+}
+
+// Lo que hace el bucle principal después de una entrada
+enum Flow {
+    Done,
+    Exit,
+    // No encaja con ningún comando: es una pregunta para el modelo
+    NotCommand,
+}
+
+// Atiende la entrada si es un comando con los argumentos que admite; si no,
+// devuelve NotCommand y se trata como pregunta (o se muestra el uso)
+async fn dispatch(session: &mut Session, line: Line<'_>) -> Result<Flow> {
+    let Some(route) = line.words.first().and_then(|name| commands::route(name)) else {
+        return Ok(Flow::NotCommand);
+    };
+    match (route, line.words) {
+        (Route::Exit, [_]) => return Ok(handle_exit(session)),
+        (Route::Usage, [_]) => println!("{}", session.usage_tracker.report()),
+        (Route::Page, words) => return Ok(handle_page(words)),
+        (Route::Macro, _) => handle_macro(session, line),
+        (Route::SendResult, [_]) => handle_send_result(session, line.input),
+        (Route::Copy, words) => handle_copy(session, line.input, words),
+        (Route::Model, words) => handle_model(session, line.input, words),
+        (Route::Profile, words) => handle_profile(session, line.input, words),
+        (Route::CompareModels, _) => handle_compare_models(session, line).await,
+        (Route::Help, [_]) => show_help(),
+        (Route::Help, [_, command]) => handle_help_page(command),
+        (Route::Cache, [_]) => handle_cache(&session.config),
+        (Route::Cache, [_, action]) if action.eq_ignore_ascii_case("clear") || action.eq_ignore_ascii_case("limpiar") => {
+            handle_cache_clear()
+        }
+        (Route::Doctor, [_]) => {
+            doctor::run(&session.client, &session.config).await;
+        }
+        (Route::Tour, [_]) => handle_tour(session),
+        (Route::Performance, [_]) => println!("{}", session.timings.report()),
+        (Route::Manifest, _) => handle_manifest(session, line),
+        (Route::ExportSession, _) => handle_export_session(session, line.tokens),
+        (Route::Context, words) => handle_context(session, line.input, words),
+        (Route::Checkpoint, words) => handle_checkpoint(session, line.input, words),
+        (Route::Rewind, [_, name]) => handle_rewind(session, line.input, name),
+        (Route::Agent, _) => match line.input.strip_prefix("agente ") {
+            Some(task) => handle_agent(session, line.input, task).await?,
+            None => return Ok(Flow::NotCommand),
+        },
+        (Route::Refresh, _) => handle_refresh(session, line).await,
+        (Route::Jobs, [_]) => handle_jobs(),
+        (Route::Resume, [_, id]) => handle_resume(session, line.input, id).await,
+        (Route::Excel, _) => match parse_excel_command(line.input) {
+            Some(command) => handle_excel(session, line.input, command).await,
+            None => return Ok(Flow::NotCommand),
+        },
+        _ => return Ok(Flow::NotCommand),
+    }
+    Ok(Flow::Done)
+}
+
+// salir
+fn handle_exit(session: &mut Session) -> Flow {
+    // Con cambios sin guardar, el primer `salir` solo avisa
+    let unsaved = session.workbooks.unsaved();
+    if !unsaved.is_empty() && session.input_closed {
+        println!(
+            "{}",
+            tr!(
+                "⚠️  Fin de la entrada: se descartan los cambios sin guardar en {}",
+                "⚠️  End of input: the unsaved changes in {} are discarded",
+                unsaved.join(", ")
+            )
+        );
+    } else if !unsaved.is_empty() && !session.exit_warned && session.script.is_none() {
+        println!(
+            "{}",
+            tr!(
+                "⚠️  Hay cambios sin guardar en {}; usa guardar o vuelve a escribir salir para descartarlos",
+                "⚠️  There are unsaved changes in {}; use save, or type exit again to discard them",
+                unsaved.join(", ")
+            )
+        );
+        session.exit_warned = true;
+        return Flow::Done;
+    }
+    println!("{}", session.usage_tracker.report());
+    println!("{}", i18n::text(Msg::Goodbye));
+    Flow::Exit
+}
+
+// siguiente, anterior y pagina <n>
+fn handle_page(words: &[&str]) -> Flow {
+    let movement = match (words[0], &words[1..]) {
+        ("siguiente", []) => Ok(pager::Move::Next),
+        ("anterior", []) => Ok(pager::Move::Previous),
+        ("pagina" | "página", [n]) => n.parse().map(pager::Move::Page).map_err(|_| ()),
+        ("pagina" | "página", _) => Err(()),
+        _ => return Flow::NotCommand,
+    };
+    match movement {
+        Ok(movement) => match pager::turn(movement) {
+            Ok(page) => println!("{}", page),
+            Err(e) => println!("ℹ️  {}", e),
+        },
+        Err(()) => println!("{}", tr!("❌ Uso: pagina <n>", "❌ Usage: page <n>")),
+    }
+    Flow::Done
+}
+
+// macro [<nombre> [argumentos]]
+fn handle_macro(session: &mut Session, line: Line<'_>) {
+    // El archivo se lee cada vez, así que se puede editar sin salir
+    let args = &line.tokens[1..];
+    let library = match macros::MacroLibrary::from_env() {
+        Ok(library) => library,
+        Err(e) => {
+            println!("❌ {:#}", e);
+            return;
+        }
+    };
+    let Some((name, args)) = args.split_first() else {
+        println!("{}", library.describe());
+        return;
+    };
+    let Some(found) = library.get(name) else {
+        println!("{}", tr!("❌ No existe la macro '{}'", "❌ There is no macro '{}'", name));
+        println!("{}", library.describe());
+        return;
+    };
+    if !line.from_macro {
+        session.macro_total = 0;
+    }
+    match found.expand(args) {
+        Ok(steps) if session.macro_total + steps.len() > macros::MAX_STEPS => {
+            println!(
+                "{}",
+                tr!(
+                    "❌ La macro {} supera los {} pasos seguidos; ¿se llama a sí misma?",
+                    "❌ Macro {} runs more than {} steps in a row; does it call itself?",
+                    found.name,
+                    macros::MAX_STEPS
+                )
+            );
+            session.macro_steps.clear();
+        }
+        Ok(steps) => {
+            session.macro_total += steps.len();
+            println!("{}", tr!("📜 Macro {} ({} pasos)", "📜 Macro {} ({} steps)", found.name, steps.len()));
+            // Delante de los pasos que quedaban, si la llama otra macro
+            for step in steps.into_iter().rev() {
+                session.macro_steps.push_front(step);
+            }
+        }
+        Err(e) => println!("❌ {:#}", e),
+    }
+}
+
+// enviar_resultado
+fn handle_send_result(session: &mut Session, input: &str) {
+    session.session_log.record_command(input);
+    match pager::full_result() {
+        Some((table, rows)) => {
+            push_context(
+                &mut session.conversation_history,
+                &session.config.context_budget,
+                format!("Resultado completo mostrado al usuario ({} filas):\n{}", rows, table),
+            );
+            println!("{}", tr!("✅ Resultado de {} filas añadido al contexto del modelo", "✅ Result with {} rows added to the model context", rows));
+        }
+        None => println!(
+            "{}",
+            tr!(
+                "❌ No hay ningún resultado que enviar; usa mostrar o un comando de análisis",
+                "❌ There is no result to send; use show or an analysis command"
+            )
+        ),
+    }
+}
+
+// copiar [respuesta|tabla]
+fn handle_copy(session: &mut Session, input: &str, words: &[&str]) {
+    session.session_log.record_command(input);
+    let source = match &words[1..] {
+        [] => Some(clipboard::CopySource::Last),
+        [what] => clipboard::CopySource::parse(what),
+        _ => None,
+    };
+    match source.map(clipboard::copy) {
+        Some(Ok(description)) => println!("{}", tr!("📋 Copiado al portapapeles: {}", "📋 Copied to the clipboard: {}", description)),
+        Some(Err(e)) => println!("{}", tr!("❌ No se pudo copiar: {:#}", "❌ Could not copy: {:#}", e)),
+        None => println!("{}", tr!("❌ Uso: copiar [respuesta|tabla]", "❌ Usage: copy [respuesta|tabla]")),
+    }
+}
+
+// modelo [<nombre> [url=<endpoint>]]
+fn handle_model(session: &mut Session, input: &str, words: &[&str]) {
+    let Session { config, conversation_history, session_log, .. } = session;
+    session_log.record_command(input);
+    let (names, options) = split_key_values(&words[1..]);
+    match names.as_slice() {
+        [] => {
+            println!("{}", tr!("ℹ️  Modelo actual: {} ({})", "ℹ️  Current model: {} ({})", config.model, models::profile(&config.model).describe()));
+            if let Some(azure) = &config.azure {
+                println!("   {}", azure.describe(&config.model));
+            }
+        }
+        [name] => {
+            // Una URL explícita sustituye al enrutado por despliegues de Azure
+            if let Some(url) = options.get("url") {
+                config.api_url = url.to_string();
+                config.azure = None;
+            }
+            config.model = name.to_string();
+            let profile = models::profile(&config.model);
+            println!(
+                "{}",
+                tr!(
+                    "✅ Modelo activo: {} ({}); se conserva la conversación",
+                    "✅ Active model: {} ({}); the conversation is kept",
+                    config.model, profile.describe()
+                )
+            );
+            if let Some(azure) = &config.azure {
+                println!("   {}", azure.describe(&config.model));
+            }
+            let trimmed = models::fit_window(conversation_history, &profile);
+            if trimmed.messages > 0 {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  Se han quitado {} mensajes antiguos (unos {} tokens) para caber en su ventana",
+                        "ℹ️  {} old messages (about {} tokens) were removed to fit its window",
+                        trimmed.messages, trimmed.tokens
+                    )
+                );
+            }
+            if !profile.tools {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  Este modelo no usa herramientas: las llamadas anteriores se le envían como texto",
+                        "ℹ️  This model does not use tools: earlier calls are sent to it as text"
+                    )
+                );
+            }
+        }
+        _ => println!("{}", tr!("❌ Uso: modelo [<nombre> [url=<endpoint>]]", "❌ Usage: model [<name> [url=<endpoint>]]")),
+    }
+}
+
+// perfil [<nombre>]
+fn handle_profile(session: &mut Session, input: &str, words: &[&str]) {
+    session.session_log.record_command(input);
+    match &words[1..] {
+        [] => match profiles::ProfileFile::load() {
+            Ok(file) => println!("{}", file.describe()),
+            Err(e) => println!("❌ {:#}", e),
+        },
+        [name] => {
+            let previous = profiles::active();
+            let switched = profiles::activate(Some(name)).and_then(|_| {
+                let config = Config::load()?;
+                let client = llm::build_client(&config.http)?;
+                let template = prompts::load_system_template(config.persona.as_deref())?;
+                session.settings.configure(&config)?;
+                Ok((config, client, template))
+            });
+            match switched {
+                Ok((config, client, template)) => {
+                    session.config = config;
+                    session.client = client;
+                    let config = &session.config;
+                    session.system_template = i18n::with_reply_instruction(template, config.reply_lang);
+                    session.reply_lang = config.reply_lang.unwrap_or(session.reply_lang);
+                    session.conversation_history[0].content = prompts::render(
+                        &session.system_template,
+                        &prompts::workbook_vars(&session.workbooks.sheet_lists()),
+                    );
+                    println!(
+                        "{}",
+                        tr!(
+                            "✅ Perfil activo: {} (modelo {}, directorio {}); se conserva la conversación",
+                            "✅ Active profile: {} (model {}, directory {}); the conversation is kept",
+                            config.profile.as_deref().unwrap_or(name),
+                            config.model,
+                            config.workspace.root().display()
+                        )
+                    );
+                }
+                Err(e) => {
+                    // Si el perfil nuevo no sirve se vuelve al que había
+                    match &previous {
+                        Some(previous) => {
+                            let _ = profiles::activate(Some(previous));
+                        }
+                        None => profiles::deactivate(),
+                    }
+                    println!("{}", tr!("❌ No se pudo cambiar al perfil '{}': {:#}", "❌ Could not switch to profile '{}': {:#}", name, e));
+                }
+            }
+        }
+        _ => println!("{}", tr!("❌ Uso: perfil [<nombre>]", "❌ Usage: profile [<name>]")),
+    }
+}
+
+// comparar_modelos "<pregunta>" [modelos=...]
+async fn handle_compare_models(session: &mut Session, line: Line<'_>) {
+    let Session { config, conversation_history, client, usage_tracker, session_log, .. } = session;
+    session_log.record_command(line.input);
+    // La pregunta es texto libre: solo se separa la opción modelos=
+    let mut list = model_compare::from_env();
+    let mut words = Vec::new();
+    for arg in line.tokens[1..].iter().cloned() {
+        match arg.strip_prefix("modelos=").or_else(|| arg.strip_prefix("models=")) {
+            Some(models) => list = Some(models.to_string()),
+            None => words.push(arg),
+        }
+    }
+    let prompt = words.join(" ");
+    if prompt.trim().is_empty() {
+        println!(
+            "{}",
+            tr!(
+                "❌ Uso: comparar_modelos \"<pregunta>\" [modelos=<modelo1>,<modelo2>[@url],...]",
+                "❌ Usage: compare_models \"<question>\" [modelos=<model1>,<model2>[@url],...]"
+            )
+        );
+        return;
+    }
+    let contenders = match model_compare::contenders(list.as_deref().unwrap_or_default(), &config.model) {
+        Ok(contenders) => contenders,
+        Err(e) => {
+            println!("❌ {:#}", e);
+            return;
+        }
+    };
+    let names: Vec<String> = contenders.iter().map(|c| c.model.clone()).collect();
+    println!("{}", tr!("⚖️  Preguntando a {} a la vez (sin herramientas)...", "⚖️  Asking {} at once (without tools)...", names.join(", ")));
+    let asked = model_compare::ask_all(client, config, conversation_history, &prompt, &contenders, usage_tracker);
+    match interrupt::interruptible(asked).await {
+        Some(answers) => {
+            let rendered = model_compare::render(&answers);
+            println!("{}", rendered);
+            clipboard::remember_answer(&rendered);
+        }
+        None => println!("{}", i18n::text(Msg::Cancelled)),
+    }
+}
+
+// ayuda <comando>
+fn handle_help_page(command: &str) {
+    // Un nombre mal escrito muestra la página del comando que quería decir
+    let page = commands::detail(command).or_else(|| match suggest::correct(command) {
+        Some(Correction::Run(name)) => commands::detail(&name),
+        _ => None,
+    });
+    match (page, suggest::correct(command)) {
+        (Some(page), _) => println!("{}", page),
+        (None, Some(Correction::Suggest(names))) => {
+            println!("❓ {} {} {}?", command, i18n::text(Msg::NotACommand), names.join(", "))
+        }
+        (None, _) => println!("❓ {} {}", command, i18n::text(Msg::UnknownCommand)),
+    }
+}
+
+// cache
+fn handle_cache(config: &Config) {
+    let (entries, bytes) = cache::stats();
+    match config.cache_ttl {
+        Some(ttl) => println!(
+            "{}",
+            tr!(
+                "Caché de respuestas: {} entradas ({} KB), validez {} s",
+                "Response cache: {} entries ({} KB), valid for {} s",
+                entries,
+                bytes.div_ceil(1024),
+                ttl.as_secs()
+            )
+        ),
+        None => println!(
+            "{}",
+            tr!(
+                "Caché de respuestas desactivada ({} entradas en disco); actívala con IAGENT_CACHE_TTL, p. ej. 1d",
+                "Response cache disabled ({} entries on disk); enable it with IAGENT_CACHE_TTL, e.g. 1d",
+                entries
+            )
+        ),
+    }
+}
+
+// cache clear
+fn handle_cache_clear() {
+    match cache::clear() {
+        Ok(removed) => println!("{}", tr!("✅ Caché vaciada ({} respuestas eliminadas)", "✅ Cache cleared ({} responses removed)", removed)),
+        Err(e) => println!("{}", tr!("❌ No se pudo vaciar la caché: {:#}", "❌ Could not clear the cache: {:#}", e)),
+    }
+}
+
+// tour
+fn handle_tour(session: &mut Session) {
+    match tour::Tour::start() {
+        Ok(started) => session.tour = Some(started),
+        Err(e) => println!("{}", tr!("❌ No se pudo preparar el recorrido: {:#}", "❌ Could not prepare the tour: {:#}", e)),
+    }
+}
+
+// manifiesto [archivo.json]
+fn handle_manifest(session: &mut Session, line: Line<'_>) {
+    session.session_log.record_command(line.input);
+    match &line.tokens[1..] {
+        [] => {
+            let records = verify::manifest();
+            if records.is_empty() {
+                println!("{}", tr!("ℹ️  Todavía no se ha escrito ningún archivo en esta sesión", "ℹ️  No file has been written in this session yet"));
+            } else {
+                println!("{}", tr!("🔏 Archivos escritos y verificados en esta sesión:", "🔏 Files written and verified in this session:"));
+                for record in records {
+                    println!("  {}", record.describe(true));
+                }
+            }
+        }
+        [path] => match outputs::target(path).and_then(|path| Ok((verify::save_manifest(Path::new(&path))?, path))) {
+            Ok((count, path)) => println!("{}", tr!("✅ Manifiesto de {} archivos guardado en {}", "✅ Manifest of {} files saved to {}", count, path)),
+            Err(e) => println!("{}", tr!("❌ Error al guardar el manifiesto: {:#}", "❌ Error saving the manifest: {:#}", e)),
+        },
+        _ => println!("{}", tr!("❌ Uso: manifiesto [archivo.json]", "❌ Usage: manifest [file.json]")),
+    }
+}
+
+// exportar_sesion <archivo> [--con-preguntas]
+fn handle_export_session(session: &mut Session, tokens: &[String]) {
+    let args = &tokens[1..];
+    let include_prompts = args.iter().any(|arg| arg == "--con-preguntas");
+    match args.iter().find(|arg| !arg.starts_with("--")) {
+        Some(path) => match outputs::target(path)
+            .and_then(|path| Ok((session.session_log.export(Path::new(&path), include_prompts)?, path)))
+        {
+            Ok((lines, path)) => println!(
+                "{}",
+                tr!(
+                    "✅ Sesión exportada a {} ({} entradas); reprodúcela con ia_agent --guion \"{}\"",
+                    "✅ Session exported to {} ({} entries); replay it with ia_agent --guion \"{}\"",
+                    path, lines, path
+                )
+            ),
+            Err(e) => println!("{}", tr!("❌ Error al exportar la sesión: {:#}", "❌ Error exporting the session: {:#}", e)),
+        },
+        None => println!("{}", tr!("❌ Uso: exportar_sesion <archivo> [--con-preguntas]", "❌ Usage: export_session <file> [--with-prompts]")),
+    }
+}
+
+// contexto [lista|crear|usar|borrar|ver|limpiar|quitar]
+fn handle_context(session: &mut Session, input: &str, words: &[&str]) {
+    let Session { config, system_template, workbooks, retriever, conversation_history, contexts, session_log, .. } = session;
+    session_log.record_command(input);
+    match ContextCommand::parse(&words[1..]) {
+        Some(ContextCommand::List) => {
+            for line in contexts.list(conversation_history, workbooks) {
+                println!("{}", line);
+            }
+        }
+        Some(ContextCommand::Create(name)) => {
+            let fresh = Conversation {
+                history: vec![Message::new("system", prompts::render(system_template, &prompts::workbook_vars(&[])))],
+                workbooks: WorkbookCache::new(config.context_budget.per_item),
+                retriever: retrieval::Retriever::default(),
+            };
+            match contexts.create(&name, fresh) {
+                Ok(()) => println!(
+                    "{}",
+                    tr!(
+                        "✅ Contexto '{}' creado; cámbiate a él con contexto usar {}",
+                        "✅ Context '{}' created; switch to it with context usar {}",
+                        name, name
+                    )
+                ),
+                Err(e) => println!("❌ {:#}", e),
+            }
+        }
+        Some(ContextCommand::Use(name)) => {
+            let current = Conversation {
+                history: std::mem::take(conversation_history),
+                workbooks: std::mem::replace(workbooks, WorkbookCache::new(config.context_budget.per_item)),
+                retriever: std::mem::take(retriever),
+            };
+            let next = match contexts.switch(&name, current) {
+                Switch::Switched(next) => {
+                    println!("{}", tr!("✅ Contexto activo: {}", "✅ Active context: {}", contexts.active()));
+                    next
+                }
+                Switch::AlreadyActive(current) => {
+                    println!("{}", tr!("ℹ️  El contexto '{}' ya es el activo", "ℹ️  Context '{}' is already the active one", contexts.active()));
+                    current
+                }
+                Switch::Missing(current) => {
+                    println!(
+                        "{}",
+                        tr!(
+                            "❌ No existe el contexto '{}'; créalo con contexto crear {}",
+                            "❌ There is no context '{}'; create it with context crear {}",
+                            name, name
+                        )
+                    );
+                    current
+                }
+            };
+            (*conversation_history, *workbooks, *retriever) = (next.history, next.workbooks, next.retriever);
+        }
+        Some(ContextCommand::Delete(name)) => match contexts.delete(&name) {
+            Ok(()) => println!("{}", tr!("✅ Contexto '{}' borrado", "✅ Context '{}' deleted", name)),
+            Err(e) => println!("❌ {:#}", e),
+        },
+        Some(ContextCommand::Show(None)) => {
+            println!(
+                "{}",
+                tr!(
+                    "🧾 Mensajes que se envían al modelo en el contexto '{}':",
+                    "🧾 Messages sent to the model in context '{}':",
+                    contexts.active()
+                )
+            );
+            for line in contexts::inspect(conversation_history) {
+                println!("  {}", line);
+            }
+        }
+        Some(ContextCommand::Show(Some(idx))) => match contexts::message_text(conversation_history, idx) {
+            Ok(text) => println!("{}", text),
+            Err(e) => println!("❌ {:#}", e),
+        },
+        Some(ContextCommand::Clear { data_only }) => {
+            let removed = contexts::clear(conversation_history, data_only);
+            if data_only {
+                println!(
+                    "{}",
+                    tr!(
+                        "✅ {} mensajes con datos quitados del contexto; los libros siguen cargados",
+                        "✅ {} messages with data removed from the context; the workbooks stay loaded",
+                        removed
+                    )
+                );
+            } else {
+                println!(
+                    "{}",
+                    tr!(
+                        "✅ Contexto vaciado ({} mensajes); se conserva el prompt de sistema y los libros siguen cargados",
+                        "✅ Context cleared ({} messages); the system prompt is kept and the workbooks stay loaded",
+                        removed
+                    )
+                );
+            }
+        }
+        Some(ContextCommand::Remove(idx)) => match contexts::remove_message(conversation_history, idx) {
+            Ok(1) => println!("{}", tr!("✅ Mensaje {} quitado del contexto", "✅ Message {} removed from the context", idx)),
+            Ok(removed) => println!(
+                "{}",
+                tr!(
+                    "✅ Mensaje {} quitado del contexto, con sus {} respuestas de herramientas",
+                    "✅ Message {} removed from the context, with its {} tool results",
+                    idx, removed - 1
+                )
+            ),
+            Err(e) => println!("❌ {:#}", e),
+        },
+        None => println!(
+            "{}",
+            tr!(
+                "❌ Uso: contexto [lista] | contexto crear|usar|borrar <nombre> | contexto ver [n] | contexto limpiar [datos] | contexto quitar <n>",
+                "❌ Usage: context [lista] | context crear|usar|borrar <name> | context ver [n] | context limpiar [datos] | context quitar <n>"
+            )
+        ),
+    }
+}
+
+// punto_de_control [nombre]
+fn handle_checkpoint(session: &mut Session, input: &str, words: &[&str]) {
+    session.session_log.record_command(input);
+    let contexts = &mut session.contexts;
+    let name = words[1..].join(" ");
+    let name = name.as_str();
+    if name.is_empty() {
+        let lines = contexts.checkpoints();
+        if lines.is_empty() {
+            println!(
+                "{}",
+                tr!(
+                    "ℹ️  No hay puntos de control en el contexto '{}'",
+                    "ℹ️  There are no checkpoints in context '{}'",
+                    contexts.active()
+                )
+            );
+        }
+        for line in lines {
+            println!("{}", line);
+        }
+    } else if words.len() > 2 || name.contains(char::is_whitespace) {
+        println!("{}", tr!("❌ Uso: punto_de_control [nombre] (el nombre no lleva espacios)", "❌ Usage: checkpoint [name] (the name has no spaces)"));
+    } else {
+        let snapshot = Conversation {
+            history: session.conversation_history.clone(),
+            workbooks: session.workbooks.clone(),
+            retriever: session.retriever.clone(),
+        };
+        let replaced = contexts.checkpoint(name, snapshot);
+        println!(
+            "{}",
+            tr!(
+                "✅ Punto de control '{}' {}; vuelve a él con volver {}",
+                "✅ Checkpoint '{}' {}; go back to it with rewind {}",
+                name,
+                i18n::pick(if replaced { ("actualizado", "updated") } else { ("creado", "created") }),
+                name
+            )
+        );
+    }
+}
+
+// volver <punto de control>
+fn handle_rewind(session: &mut Session, input: &str, name: &str) {
+    session.session_log.record_command(input);
+    match session.contexts.rewind(name) {
+        Ok(snapshot) => {
+            let unsaved: Vec<String> = session.workbooks.unsaved().into_iter().map(str::to_string).collect();
+            session.conversation_history = snapshot.history;
+            session.workbooks = snapshot.workbooks;
+            session.retriever = snapshot.retriever;
+            session.response_tables.clear();
+            println!(
+                "{}",
+                tr!(
+                    "✅ Vuelta al punto de control '{}': se recuperan el historial y los libros cargados entonces",
+                    "✅ Back at checkpoint '{}': the history and the workbooks loaded then are restored",
+                    name
+                )
+            );
+            if !unsaved.is_empty() {
+                println!(
+                    "{}",
+                    tr!(
+                        "⚠️  Se descartan los cambios sin guardar de {}",
+                        "⚠️  The unsaved changes in {} are discarded",
+                        unsaved.join(", ")
+                    )
+                );
+            }
+            println!(
+                "{}",
+                tr!(
+                    "ℹ️  Los archivos guardados desde entonces no cambian; restáuralos con deshacer <archivo>",
+                    "ℹ️  Files saved since then do not change; restore them with undo <file>"
+                )
+            );
+        }
+        Err(e) => println!("❌ {:#}", e),
+    }
+}
+
+// agente <tarea>
+async fn handle_agent(session: &mut Session, input: &str, task: &str) -> Result<()> {
+    let Session { config, workbooks, conversation_history, client, usage_tracker, timings, stdin, session_log, .. } = session;
+    session_log.record_prompt(input);
+    refresh_stale_workbooks(workbooks, conversation_history, &config.context_budget, &mut stdin.lock())?;
+    let started = Instant::now();
+    if let Err(e) = agent::run_task(client, config, conversation_history, usage_tracker, timings, task.trim()).await {
+        println!("{}", tr!("❌ Error en el modo agente: {:#}", "❌ Error in agent mode: {:#}", e));
+    }
+    record_timing(timings, config, "agente", task, started);
+    Ok(())
+}
+
+// refrescar <archivo>
+async fn handle_refresh(session: &mut Session, line: Line<'_>) {
+    session.session_log.record_command(line.input);
+    let Some(memory) = session.schema_memory.as_mut() else {
+        println!(
+            "{}",
+            tr!(
+                "ℹ️  La memoria de esquemas está desactivada (IAGENT_SCHEMA_MEMORY)",
+                "ℹ️  Schema memory is disabled (IAGENT_SCHEMA_MEMORY)"
+            )
+        );
+        return;
+    };
+    let [file] = &line.tokens[1..] else {
+        println!("{}", tr!("❌ Uso: refrescar <archivo>", "❌ Usage: refresh <file>"));
+        return;
+    };
+    let path = file.clone();
+    match limits::run_blocking(&format!("La lectura de {}", file), move || convert::read_any(Path::new(&path))).await {
+        Ok(data) => {
+            let description = memory.remember(file, &data, true).describe();
+            match memory.save() {
+                Ok(()) => println!("{}", tr!("✅ Estructura de {} actualizada:{}", "✅ Structure of {} updated:{}", file, description)),
+                Err(e) => println!("❌ {:#}", e),
+            }
+            push_context(
+                &mut session.conversation_history,
+                &session.config.context_budget,
+                format!("Estructura actualizada del libro '{}':{}", file, description),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al leer el archivo: {:#}", "❌ Error reading the file: {:#}", e)),
+    }
+}
+
+// trabajos
+fn handle_jobs() {
+    match jobs::list() {
+        Ok(pending) if pending.is_empty() => println!("{}", tr!("ℹ️  No hay trabajos pendientes", "ℹ️  There are no pending jobs")),
+        Ok(pending) => {
+            println!("{}", tr!("Trabajos pendientes (reanudar <id>):", "Pending jobs (resume <id>):"));
+            for job in pending {
+                println!("  {}", job.describe());
+            }
+        }
+        Err(e) => println!("❌ {:#}", e),
+    }
+}
+
+// reanudar <id>
+async fn handle_resume(session: &mut Session, input: &str, id: &str) {
+    let Session { config, conversation_history, client, usage_tracker, timings, session_log, .. } = session;
+    session_log.record_command(input);
+    let started = Instant::now();
+    let result = match jobs::Job::load(id.trim()) {
+        Ok(job @ jobs::Job { kind: jobs::JobKind::Agent(_), .. }) => {
+            agent::resume_task(client, config, conversation_history, usage_tracker, timings, job).await
+        }
+        Ok(job) => row_prompts::run_job(client, config, usage_tracker, job)
+            .await
+            .map(|outcome| print_row_outcome(&outcome)),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        println!("{}", tr!("❌ Error al reanudar: {:#}", "❌ Error resuming: {:#}", e));
+    }
+    record_timing(timings, config, "reanudar", id, started);
+}
+
+// Los comandos de archivos que interpreta parse_excel_command
+async fn handle_excel(session: &mut Session, input: &str, command: ExcelCommand) {
+    session.session_log.record_command(input);
+    let started = Instant::now();
+    match command {
+        ExcelCommand::ReadFile(filename, force_streaming, evaluate, read_formulas) => handle_read_file(session, filename, force_streaming, evaluate, read_formulas).await,
+        ExcelCommand::ReadMany(pattern) => handle_read_many(session, pattern).await,
+        ExcelCommand::Show(sheet, rows) => handle_show(session, sheet, rows),
+        ExcelCommand::Undo(filename) => handle_undo(filename),
+        ExcelCommand::CreateFile(filename) => handle_create_file(filename),
+        ExcelCommand::WriteData(filename, data, rewrite) => handle_write_data(session, filename, data, rewrite),
+        ExcelCommand::WriteRange(filename, target, values, spill) => handle_write_range(session, filename, target, values, spill),
+        ExcelCommand::WriteFormula(filename, target, text, overwrite) => handle_write_formula(session, filename, target, text, overwrite),
+        ExcelCommand::MergeCells(filename, target) => handle_merge_cells(session, filename, target),
+        ExcelCommand::WriteLink(options) => handle_write_link(session, options),
+        ExcelCommand::Note(options) => handle_note(session, options),
+        ExcelCommand::Table(options) => handle_table(session, options),
+        ExcelCommand::Group(options) => handle_group(session, options),
+        ExcelCommand::Convert(options) => handle_convert(session, options).await,
+        ExcelCommand::Rank(options) => handle_rank(session, options).await,
+        ExcelCommand::Pareto(options) => handle_pareto(session, options).await,
+        ExcelCommand::Cohorts(options) => handle_cohorts(session, options).await,
+        ExcelCommand::Stats(options) => handle_stats(session, options),
+        ExcelCommand::AskBatch(options) => handle_ask_batch(session, options).await,
+        ExcelCommand::RowPrompts(options) => handle_row_prompts(session, options).await,
+        ExcelCommand::Formulas(file, sheet) => handle_formulas(file, sheet),
+        ExcelCommand::Explain(file) => handle_explain(session, file),
+        ExcelCommand::Search(options) => handle_search(session, options),
+        ExcelCommand::TextChart(options) => handle_text_chart(session, options),
+        ExcelCommand::ExtractJson(options) => handle_extract_json(session, options).await,
+        ExcelCommand::ColumnCrypto(options) => handle_column_crypto(session, options),
+        ExcelCommand::Anonymize(options) => handle_anonymize(options),
+        ExcelCommand::Deanonymize(file, output) => handle_deanonymize(file, output),
+        ExcelCommand::ConvertDates(options) => handle_convert_dates(options),
+        ExcelCommand::Report(options) => handle_report(session, options).await,
+        ExcelCommand::Compare(options) => handle_compare(session, options),
+        ExcelCommand::Export(options) => handle_export(options),
+        ExcelCommand::Apply(file, sheet, index) => handle_apply(session, file, sheet, index),
+        ExcelCommand::Paste(file, sheet) => handle_paste(session, file, sheet),
+        ExcelCommand::Structure(options) => handle_structure(session, options),
+        ExcelCommand::Transform(args) => handle_transform(session, args),
+        ExcelCommand::Join(options) => handle_join(session, options),
+        ExcelCommand::Duplicates(options) => handle_duplicates(session, options),
+        ExcelCommand::RemoveDuplicates(options) => handle_remove_duplicates(session, options),
+        ExcelCommand::EditCell(file, sheet, cell, raw) => handle_edit_cell(session, file, sheet, cell, raw).await,
+        ExcelCommand::Fill(options) => handle_fill(session, options).await,
+        ExcelCommand::Save(file, output, rename, rewrite) => handle_save(session, file, output, rename, rewrite).await,
+        ExcelCommand::Layout(options) => handle_layout(session, options),
+        ExcelCommand::Protect(options) => handle_protect(session, options),
+        ExcelCommand::Validate(options) => handle_validate(session, options),
+        ExcelCommand::ConditionalFormat(options) => handle_conditional_format(session, options),
+    }
+    let name = input.split_whitespace().next().unwrap_or_default();
+    record_timing(&mut session.timings, &session.config, name, input, started);
+}
+
+// Tras escribir en un archivo, su copia cargada (si la hay) se vuelve a leer para
+// que mostrar, rellenar y guardar partan de lo que hay en disco
+fn reload_loaded(session: &mut Session, file: &str) {
+    if let Err(e) = session.workbooks.reload(file) {
+        println!("⚠️  {:#}", e);
+    }
+}
+
+// leer_excel
+async fn handle_read_file(session: &mut Session, filename: String, force_streaming: bool, evaluate: bool, read_formulas: bool) {
+    let Session { config, system_template, workbooks, retriever, conversation_history, schema_memory, client, timings, .. } = session;
+    let large = fs::metadata(&filename)
+        .is_ok_and(|m| m.len() >= excel::STREAMING_THRESHOLD_BYTES);
+    let streaming = force_streaming || large;
+    let evaluate = evaluate || config.evaluate_formulas;
+    let max_tokens = config.context_budget.per_item;
+    let mut formula_report = None;
+    let mut formula_count = None;
+    let mut read_times = Vec::new();
+    let spinner = progress::Spinner::start(format!("Leyendo {}", filename));
+    let result = if streaming {
+        println!("{}", tr!("ℹ️  Leyendo {} por streaming", "ℹ️  Reading {} by streaming", filename));
+        if evaluate {
+            println!("{}", tr!("⚠️  Las fórmulas no se evalúan al leer por streaming", "⚠️  Formulas are not evaluated when reading by streaming"));
+        }
+        if read_formulas {
+            println!(
+                "{}",
+                tr!(
+                    "⚠️  El texto de las fórmulas no se lee por streaming; usa formulas {} <hoja>",
+                    "⚠️  Formula text is not read by streaming; use formulas {} <sheet>",
+                    filename
+                )
+            );
+        }
+        let path = filename.clone();
+        limits::run_blocking(&format!("La lectura de {}", filename), move || {
+            summary::summarize_streaming(&path, max_tokens)
+        })
+        .await
+        .map(|(preview, summary)| workbooks.insert_with_summary(&filename, preview, summary))
+    } else {
+        let path = filename.clone();
+        limits::run_blocking(&format!("La lectura de {}", filename), move || {
+            let (mut data, sheet_times) = excel::read_excel_file_timed(&path)?;
+            let count = if read_formulas { Some(formulas::read(&path, &mut data)?) } else { None };
+            let report = if evaluate {
+                Some(formula::evaluate_file(&path, &mut data)?)
+            } else {
+                None
+            };
+            Ok((data, count, report, sheet_times))
+        })
+        .await
+        .map(|(data, count, report, sheet_times)| {
+            formula_count = count;
+            formula_report = report;
+            read_times = sheet_times;
+            workbooks.insert(&filename, data)
+        })
+    };
+    drop(spinner);
+    // Con alguna hoja lenta (o en modo detallado) se muestra lo que tardó cada una
+    let slow = read_times.iter().any(|(_, elapsed)| elapsed.as_secs() >= 1);
+    if read_times.len() > 1 && (slow || config.verbose) {
+        let times: Vec<String> = read_times
+            .iter()
+            .map(|(sheet, elapsed)| format!("{} {}", sheet, timing::format_duration(*elapsed)))
+            .collect();
+        println!("{}", tr!("⏱  Hojas leídas en paralelo: {}", "⏱  Sheets read in parallel: {}", times.join(", ")));
+    }
+    for (sheet, elapsed) in read_times {
+        timings.record("leer hoja", &format!("{} {}", filename, sheet), elapsed);
+    }
+    match result {
+        Ok(entry) => {
+            println!("{}", tr!("✅ Archivo leído correctamente", "✅ File read successfully"));
+            remember_schema(schema_memory, &filename, &entry.data, !streaming);
+            if let Some(report) = &formula_report {
+                println!("🧮 {}", report);
+            }
+            if let Some(count) = formula_count {
+                println!(
+                    "{}",
+                    tr!(
+                        "🧮 {} fórmulas leídas; el modelo ve su texto y formulas {} <hoja> las lista",
+                        "🧮 {} formulas read; the model sees their text and formulas {} <sheet> lists them",
+                        count, filename
+                    )
+                );
+            }
+            if !streaming {
+                for sheet in &entry.data.sheets {
+                    println!("{}", tr!("Hoja: {}", "Sheet: {}", sheet.name));
+                    if sheet.headerless {
+                        println!(
+                            "{}",
+                            tr!(
+                                "ℹ️  La primera fila no parece un encabezado: las columnas se llaman Columna A, Columna B...",
+                                "ℹ️  The first row does not look like a header: the columns are named Columna A, Columna B..."
+                            )
+                        );
+                    }
+                    if !sheet.merges.is_empty() {
+                        println!("{}", tr!("ℹ️  Celdas combinadas: {}", "ℹ️  Merged cells: {}", merges::describe(&sheet.merges)));
+                    }
+                    if !sheet.hyperlinks.is_empty() {
+                        println!("{}", tr!("🔗 Enlaces: {}", "🔗 Links: {}", hyperlinks::describe(&sheet.hyperlinks)));
+                    }
+                    if !sheet.tables.is_empty() {
+                        println!("{}", tr!("ℹ️  Tablas: {}", "ℹ️  Tables: {}", tables::describe(&sheet.tables)));
+                    }
+                    if !sheet.outline.rows.is_empty() || !sheet.outline.cols.is_empty() {
+                        println!("{}", tr!("ℹ️  Grupos plegables: {}", "ℹ️  Collapsible groups: {}", sheet.outline.describe()));
+                    }
+                    if !sheet.notes.is_empty() {
+                        println!("{}", tr!("📝 Notas: {}", "📝 Notes: {}", notes::describe(&sheet.notes)));
+                    }
+                    if !sheet.formulas.is_empty() && formula_count.is_some() {
+                        println!("{}", tr!("🧮 Fórmulas: {}", "🧮 Formulas: {}", formulas::describe(&sheet.formulas)));
+                    }
+                    table::print_preview(sheet, PREVIEW_ROWS);
+                }
+            }
+            let metadata_note = match metadata::WorkbookMetadata::read(
+                &filename,
+                Some(&entry.data).filter(|_| !streaming),
+            ) {
+                Ok(metadata) => {
+                    let text = metadata.render();
+                    println!("ℹ️  {}", text.replace('\n', "\nℹ️  "));
+                    format!("\n{}", text)
+                }
+                Err(e) => {
+                    println!("{}", tr!("⚠️  No se pudieron leer los metadatos del libro: {:#}", "⚠️  Could not read the workbook metadata: {:#}", e));
+                    String::new()
+                }
+            };
+            // El resumen es un formato más amigable para el contexto;
+            // si queda poco presupuesto se rehace más corto en lugar de cortarlo
+            let available = budget::available(conversation_history, &config.context_budget);
+            let data_summary = if !streaming && available < max_tokens {
+                summary::summarize_workbook(&entry.data, available)
+            } else {
+                entry.summary.clone()
+            };
+            conversation_history[0].content = prompts::render(
+                system_template,
+                &prompts::workbook_vars(&workbooks.sheet_lists()),
+            );
+            let formula_note = formula_report
+                .map(|report| format!("\nValores de fórmulas recalculados al leer: {}", report))
+                .unwrap_or_default();
+            // Los libros que se recorren por streaming no tienen las filas en memoria
+            let chunk_note = match workbooks.get(&filename) {
+                Some(entry) if !streaming => format!("\n{}", chunks::index(&entry.data)),
+                _ => String::new(),
+            };
+            push_tool_context(
+                conversation_history,
+                &config.context_budget,
+                "leer_excel",
+                serde_json::json!({ "archivo": filename }),
+                format!(
+                    "Datos del archivo Excel '{}': {}{}{}{}",
+                    filename, data_summary, metadata_note, formula_note, chunk_note
+                ),
+            );
+            if config.retrieval_rows > 0 {
+                let indexed = match workbooks.get(&filename) {
+                    Some(entry) if !streaming => {
+                        interrupt::interruptible(retriever.index_workbook(
+                            client,
+                            config,
+                            &filename,
+                            &entry.data,
+                        ))
+                        .await
+                    }
+                    _ => {
+                        interrupt::interruptible(retriever.index_streaming(client, config, &filename))
+                            .await
+                    }
+                };
+                report_indexed(&filename, indexed, config);
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al leer el archivo: {:#}", "❌ Error reading the file: {:#}", e)),
+    }
+}
+
+// leer_varios
+async fn handle_read_many(session: &mut Session, pattern: String) {
+    let Session { config, system_template, workbooks, conversation_history, schema_memory, .. } = session;
+    let available = budget::available(conversation_history, &config.context_budget);
+    match read_many(workbooks, schema_memory, &pattern, available).await {
+        Ok(merged) => {
+            conversation_history[0].content = prompts::render(
+                system_template,
+                &prompts::workbook_vars(&workbooks.sheet_lists()),
+            );
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!("Datos de los archivos que coinciden con '{}':\n{}", pattern, merged),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al leer los archivos: {:#}", "❌ Error reading the files: {:#}", e)),
+    }
+}
+
+// mostrar
+fn handle_show(session: &mut Session, sheet: Option<String>, rows: Option<usize>) {
+    let Session { workbooks, .. } = session;
+    match workbooks.find_sheet(sheet.as_deref()) {
+        Some((path, data)) => {
+            println!("{}", tr!("{} — hoja {}", "{} — sheet {}", path, data.name));
+            table::print_sheet(data, rows);
+        }
+        None => match sheet {
+            Some(name) => println!("{}", tr!("❌ Ningún libro cargado tiene la hoja '{}'", "❌ No loaded workbook has sheet '{}'", name)),
+            None => println!("{}", tr!("❌ No hay ningún libro cargado; usa leer_excel <archivo>", "❌ No workbook is loaded; use read_excel <file>")),
+        },
+    }
+}
+
+// deshacer
+fn handle_undo(filename: String) {
+    match backup::restore_latest(Path::new(&filename)) {
+        Ok(backup::RestoreOutcome::Restored(date)) => {
+            println!("{}", tr!("✅ {} restaurado a la copia del {} (UTC)", "✅ {} restored to the copy from {} (UTC)", filename, date))
+        }
+        Ok(backup::RestoreOutcome::Removed) => {
+            println!(
+                "{}",
+                tr!(
+                    "✅ {} no existía antes de la última operación; se ha eliminado",
+                    "✅ {} did not exist before the last operation; it has been removed",
+                    filename
+                )
+            )
+        }
+        Err(e) => println!("{}", tr!("❌ No se pudo deshacer: {:#}", "❌ Could not undo: {:#}", e)),
+    }
+}
+
+// crear_excel
+fn handle_create_file(filename: String) {
+    match create_excel_file(&filename) {
+        Ok(_) => println!("{}", tr!("✅ Archivo creado correctamente: {}", "✅ File created successfully: {}", filename)),
+        Err(e) => println!("{}", tr!("❌ Error al crear el archivo: {}", "❌ Error creating the file: {}", e)),
+    }
+}
+
+// escribir_excel
+fn handle_write_data(session: &mut Session, filename: String, data: String, rewrite: bool) {
+    match write_excel_data(&filename, &data, rewrite) {
+        Ok(sheets) => println!(
+            "{}",
+            tr!(
+                "✅ Datos escritos correctamente en {} (hojas: {})",
+                "✅ Data written successfully to {} (sheets: {})",
+                filename,
+                sheets.join(", ")
+            )
+        ),
+        Err(e) => println!("{}", tr!("❌ Error al escribir datos: {}", "❌ Error writing the data: {}", e)),
+    }
+    reload_loaded(session, &filename);
+}
+
+// escribir_rango
+fn handle_write_range(session: &mut Session, filename: String, target: String, values: String, spill: SpillPolicy) {
+    match named_ranges::write_range(&filename, &target, &parse_value_block(&values), spill) {
+        Ok(written) => {
+            println!("{}", tr!("✅ {} celdas escritas en {} de {}", "✅ {} cells written to {} in {}", written.cells, written.description, filename));
+            if let Some(requested) = &written.shifted_from {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  {} tenía datos: el bloque se desplazó para no pisarlos",
+                        "ℹ️  {} had data: the block was moved so as not to overwrite it",
+                        requested
+                    )
+                );
+            }
+            if written.overwritten > 0 {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  Se reemplazaron {} celdas que tenían datos",
+                        "ℹ️  {} cells that had data were replaced",
+                        written.overwritten
+                    )
+                );
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al escribir en el rango: {:#}", "❌ Error writing to the range: {:#}", e)),
+    }
+    reload_loaded(session, &filename);
+}
+
+// escribir_formula
+fn handle_write_formula(session: &mut Session, filename: String, target: String, text: String, overwrite: bool) {
+    match formula_check::write_formula(&filename, &target, &text, overwrite) {
+        Ok(written) => {
+            if written.cells == 1 {
+                println!(
+                    "{}",
+                    tr!(
+                        "✅ Fórmula ={} escrita en {} de {}",
+                        "✅ Formula ={} written to {} in {}",
+                        written.formula, written.description, filename
+                    )
+                );
+            } else {
+                println!(
+                    "{}",
+                    tr!(
+                        "✅ Fórmula ={} rellenada en {} ({} celdas) de {}",
+                        "✅ Formula ={} filled into {} ({} cells) in {}",
+                        written.formula, written.description, written.cells, filename
+                    )
+                );
+            }
+            match written.value {
+                Some(excel::CellValue::Error(error)) => println!("{}", tr!("⚠️  La primera celda da {}", "⚠️  The first cell gives {}", error)),
+                Some(value) => println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  Valor calculado de la primera celda: {}",
+                        "ℹ️  Calculated value of the first cell: {}",
+                        value
+                    )
+                ),
+                None => println!("{}", tr!("ℹ️  Excel la calculará al abrir el libro", "ℹ️  Excel will calculate it when the workbook is opened")),
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al escribir la fórmula: {:#}", "❌ Error writing the formula: {:#}", e)),
+    }
+    reload_loaded(session, &filename);
+}
+
+// combinar_celdas
+fn handle_merge_cells(session: &mut Session, filename: String, target: String) {
+    match merges::merge_cells(&filename, &target) {
+        Ok(outcome) => {
+            println!("{}", tr!("✅ Celdas {} combinadas en {}", "✅ Cells {} merged in {}", outcome.description, filename));
+            if outcome.cleared > 0 {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  Se vaciaron {} celdas del rango: Excel solo conserva el valor de la celda superior izquierda",
+                        "ℹ️  {} cells of the range were cleared: Excel only keeps the value of the top-left cell",
+                        outcome.cleared
+                    )
+                );
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al combinar celdas: {:#}", "❌ Error merging cells: {:#}", e)),
+    }
+    reload_loaded(session, &filename);
+}
+
+// escribir_enlace
+fn handle_write_link(session: &mut Session, options: LinkOptions) {
+    let existed = Path::new(&options.file).exists();
+    match hyperlinks::write_link(&options) {
+        Ok(cell) => {
+            println!("{}", tr!("✅ Enlace a {} escrito en {} de {}", "✅ Link to {} written to {} in {}", options.target, cell, options.file));
+            if existed {
+                println!(
+                    "{}",
+                    tr!(
+                        "⚠️  El archivo se reescribe a partir de los valores: conserva los formatos de celda y los anchos (deshacer {} lo recupera)",
+                        "⚠️  The file is rewritten from its values: cell formats and widths are kept (undo {} restores it)",
+                        options.file
+                    )
+                );
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al escribir el enlace: {:#}", "❌ Error writing the link: {:#}", e)),
+    }
+    reload_loaded(session, &options.file);
+}
+
+// nota
+fn handle_note(session: &mut Session, options: NoteOptions) {
+    match notes::apply(&options) {
+        Ok(outcome) => match (&options.action, outcome.previous) {
+            (NoteAction::Show, Some(note)) => println!("📝 {} ({}): {}", outcome.cell, note.author, note.text),
+            (NoteAction::Show, None) => println!("{}", tr!("ℹ️  {} no tiene nota", "ℹ️  {} has no note", outcome.cell)),
+            (NoteAction::Write(_), previous) => {
+                println!("{}", tr!("✅ Nota escrita en {} de {}", "✅ Note written to {} in {}", outcome.cell, options.file));
+                if let Some(previous) = previous {
+                    println!("{}", tr!("ℹ️  Sustituye a la anterior: {}", "ℹ️  It replaces the previous one: {}", previous.text));
+                }
+            }
+            (NoteAction::Remove, Some(_)) => println!(
+                "{}",
+                tr!(
+                    "✅ Nota de {} quitada de {}",
+                    "✅ Note on {} removed from {}",
+                    outcome.cell, options.file
+                )
+            ),
+            (NoteAction::Remove, None) => println!("{}", tr!("ℹ️  {} no tenía nota", "ℹ️  {} had no note", outcome.cell)),
+        },
+        Err(e) => println!("{}", tr!("❌ Error con la nota: {:#}", "❌ Error with the note: {:#}", e)),
+    }
+    reload_loaded(session, &options.file);
+}
+
+// crear_tabla
+fn handle_table(session: &mut Session, options: TableOptions) {
+    match tables::create(&options) {
+        Ok(outcome) => {
+            println!(
+                "{}",
+                tr!(
+                    "✅ Tabla {} creada en la hoja {} de {}",
+                    "✅ Table {} created in sheet {} of {}",
+                    outcome.table.describe(), outcome.sheet, options.file
+                )
+            );
+            println!(
+                "{}",
+                tr!(
+                    "ℹ️  En las fórmulas, sus columnas son {}[columna], p. ej. =SUM({}[Importe])",
+                    "ℹ️  In formulas its columns are {}[column], e.g. =SUM({}[Amount])",
+                    outcome.table.name, outcome.table.name
+                )
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al crear la tabla: {:#}", "❌ Error creating the table: {:#}", e)),
+    }
+    reload_loaded(session, &options.file);
+}
+
+// agrupar y desagrupar
+fn handle_group(session: &mut Session, options: GroupOptions) {
+    match outline::apply(&options) {
+        Ok(outcome) => {
+            if options.ungroup {
+                println!(
+                    "{}",
+                    tr!(
+                        "✅ Desagrupadas {} de la hoja {} ({} estaban agrupadas)",
+                        "✅ Ungrouped {} in sheet {} ({} were grouped)",
+                        options.span.describe(), outcome.sheet, outcome.ungrouped
+                    )
+                );
+            } else {
+                let state = i18n::pick(if options.collapse { ("contraídas", "collapsed") } else { ("desplegadas", "expanded") });
+                println!(
+                    "{}",
+                    tr!(
+                        "✅ Agrupadas {} de la hoja {} ({})",
+                        "✅ Grouped {} in sheet {} ({})",
+                        options.span.describe(), outcome.sheet, state
+                    )
+                );
+            }
+            let grouped = outcome.outline.describe();
+            if !grouped.is_empty() {
+                println!("{}", tr!("ℹ️  Grupos de {}: {}", "ℹ️  Groups in {}: {}", outcome.sheet, grouped));
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al agrupar: {:#}", "❌ Error grouping: {:#}", e)),
+    }
+    reload_loaded(session, &options.file);
+}
+
+// convertir
+async fn handle_convert(session: &mut Session, options: ConvertOptions) {
+    let Session { config, client, usage_tracker, .. } = session;
+    match convert::convert_files(&options) {
+        Ok(outcomes) => {
+            let mut failures = 0;
+            for outcome in &outcomes {
+                match &outcome.result {
+                    Ok(outputs) => {
+                        let names: Vec<String> =
+                            outputs.iter().map(|p| p.display().to_string()).collect();
+                        println!("✅ {} -> {}", outcome.source.display(), names.join(", "));
+                        if options.format == FileFormat::Xlsx {
+                            for output in outputs {
+                                let info = readme::GenerationInfo::for_conversion(&outcome.source);
+                                add_readme(client, config, usage_tracker, &output.to_string_lossy(), &info)
+                                    .await;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        println!("❌ {}: {:#}", outcome.source.display(), e);
+                    }
+                }
+            }
+            println!(
+                "{}",
+                tr!(
+                    "Convertidos: {}, con errores: {}",
+                    "Converted: {}, with errors: {}",
+                    outcomes.len() - failures,
+                    failures
+                )
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al convertir: {}", "❌ Error converting: {}", e)),
+    }
+}
+
+// top y bottom
+async fn handle_rank(session: &mut Session, options: RankOptions) {
+    let Session { config, client, usage_tracker, .. } = session;
+    match run_rank(&options) {
+        Ok(()) => {
+            if let Some(output) = &options.output {
+                let info = readme::GenerationInfo::for_rank(&options);
+                add_readme(client, config, usage_tracker, output, &info).await;
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error en el ranking: {:#}", "❌ Error in the ranking: {:#}", e)),
+    }
+}
+
+// pareto
+async fn handle_pareto(session: &mut Session, options: ParetoOptions) {
+    let Session { config, conversation_history, client, usage_tracker, .. } = session;
+    match run_pareto(&options) {
+        Ok(summary) => {
+            if let Some(output) = &options.output {
+                let info = readme::GenerationInfo::for_pareto(&options);
+                add_readme(client, config, usage_tracker, output, &info).await;
+            }
+            // El resumen queda en el contexto para que el modelo pueda comentarlo
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!("Análisis calculado localmente sobre {}: {}", options.file, summary),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error en el análisis de Pareto: {:#}", "❌ Error in the Pareto analysis: {:#}", e)),
+    }
+}
+
+// cohortes
+async fn handle_cohorts(session: &mut Session, options: CohortOptions) {
+    let Session { config, conversation_history, client, usage_tracker, .. } = session;
+    match run_cohorts(&options) {
+        Ok((summary, output)) => {
+            let info = readme::GenerationInfo::for_cohorts(&options);
+            add_readme(client, config, usage_tracker, &output, &info).await;
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!("Análisis calculado localmente sobre {}: {}", options.file, summary),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error en el análisis de cohortes: {:#}", "❌ Error in the cohort analysis: {:#}", e)),
+    }
+}
+
+// estadisticas
+fn handle_stats(session: &mut Session, options: StatsOptions) {
+    let Session { config, conversation_history, .. } = session;
+    match run_stats(&options) {
+        Ok(summary) => push_context(
+            conversation_history,
+            &config.context_budget,
+            format!("Análisis calculado localmente sobre {}: {}", options.file, summary),
+        ),
+        Err(e) => println!("{}", tr!("❌ Error al calcular las estadísticas: {:#}", "❌ Error calculating the statistics: {:#}", e)),
+    }
+}
+
+// preguntar_lote
+async fn handle_ask_batch(session: &mut Session, options: BatchOptions) {
+    let Session { config, system_template, reply_lang, conversation_history, client, usage_tracker, timings, .. } = session;
+    let options = match outputs::target(&options.output) {
+        Ok(output) => BatchOptions { output, ..options },
+        Err(e) => {
+            println!("❌ {:#}", e);
+            return;
+        }
+    };
+    let template = match config.reply_lang {
+        Some(_) => system_template.clone(),
+        None => i18n::with_reply_instruction(system_template.clone(), Some(*reply_lang)),
+    };
+    match batch::run(client, config, usage_tracker, timings, &template, &options)
+        .await
+    {
+        Ok(result) if result.answers.is_empty() => println!("{}", tr!("ℹ️  No se obtuvo ninguna respuesta", "ℹ️  No answer was received")),
+        Ok(result) => {
+            let answered = result.answers.iter().filter(|a| !a.failed).count();
+            match excel::write_sheet_to_file(&options.output, batch::answers_sheet(&result.answers)) {
+                Ok(()) => {
+                    println!(
+                        "{}",
+                        tr!(
+                            "✅ {} respuestas ({} con error) guardadas en la hoja {} de {}",
+                            "✅ {} answers ({} with errors) saved to sheet {} of {}",
+                            result.answers.len(),
+                            result.answers.len() - answered,
+                            batch::ANSWERS_SHEET,
+                            options.output
+                        )
+                    );
+                    if result.interrupted {
+                        println!(
+                            "{}",
+                            tr!(
+                                "ℹ️  El lote se detuvo antes de terminar; la hoja solo incluye los archivos procesados",
+                                "ℹ️  The batch stopped before finishing; the sheet only includes the processed files"
+                            )
+                        );
+                    }
+                    let files = result.answers.iter().map(|a| a.file.clone()).collect();
+                    let info = readme::GenerationInfo::for_batch(&options, files);
+                    add_readme(client, config, usage_tracker, &options.output, &info).await;
+                }
+                Err(e) => println!("{}", tr!("❌ Error al guardar las respuestas: {:#}", "❌ Error saving the answers: {:#}", e)),
+            }
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                batch::context_summary(&options, &result.answers),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error en la pregunta en lote: {:#}", "❌ Error in the batch question: {:#}", e)),
+    }
+}
+
+// para_cada_fila
+async fn handle_row_prompts(session: &mut Session, options: RowPromptOptions) {
+    let Session { config, client, usage_tracker, .. } = session;
+    match row_prompts::run(client, config, usage_tracker, &options).await {
+        Ok(outcome) => print_row_outcome(&outcome),
+        Err(e) => println!("{}", tr!("❌ Error en para_cada_fila: {:#}", "❌ Error in for_each_row: {:#}", e)),
+    }
+}
+
+// formulas
+fn handle_formulas(file: String, sheet: String) {
+    match formulas::list(&file, &sheet) {
+        Ok(rows) if rows.is_empty() => println!(
+            "{}",
+            tr!(
+                "ℹ️  La hoja {} de {} no tiene fórmulas",
+                "ℹ️  Sheet {} of {} has no formulas",
+                sheet, file
+            )
+        ),
+        Ok(rows) => {
+            let headers = [("Celda", "Cell"), ("Fórmula", "Formula"), ("Valor guardado", "Stored value")].map(|header| i18n::pick(header).to_string());
+            table::print_table(&headers, &rows);
+            println!(
+                "{}",
+                tr!(
+                    "✅ {} fórmulas en la hoja {}; enviar_resultado las pasa al modelo",
+                    "✅ {} formulas in sheet {}; send_result passes them to the model",
+                    rows.len(), sheet
+                )
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al leer las fórmulas: {:#}", "❌ Error reading the formulas: {:#}", e)),
+    }
+}
+
+// explicar
+fn handle_explain(session: &mut Session, file: String) {
+    let Session { config, conversation_history, .. } = session;
+    match explain::explain(&file) {
+        Ok(report) => {
+            let text = report.render();
+            println!("{}", text);
+            push_context(conversation_history, &config.context_budget, text);
+            println!("{}", tr!("✅ Estructura de {} añadida al contexto", "✅ Structure of {} added to the context", file));
+        }
+        Err(e) => println!("{}", tr!("❌ Error al explicar {}: {:#}", "❌ Error explaining {}: {:#}", file, e)),
+    }
+}
+
+// buscar
+fn handle_search(session: &mut Session, options: SearchOptions) {
+    let Session { config, workbooks, conversation_history, .. } = session;
+    // Un libro ya leído se busca en memoria, con sus cambios sin guardar
+    let loaded = workbooks.get(&options.file).map(|entry| entry.data.clone());
+    let result = search::Matcher::parse(&options.query).and_then(|matcher| {
+        let data = match loaded {
+            Some(data) => data,
+            None => read_excel_file(&options.file)?,
+        };
+        if let Some(sheet) = &options.sheet {
+            data.require_sheet(&options.file, sheet)?;
+        }
+        Ok(search::search(&data, &matcher, options.sheet.as_deref(), options.max_hits))
+    });
+    match result {
+        Ok(result) if result.total == 0 => println!(
+            "{}",
+            tr!(
+                "ℹ️  Ninguna celda de {} contiene '{}' ({} hojas revisadas)",
+                "ℹ️  No cell in {} contains '{}' ({} sheets checked)",
+                options.file, options.query, result.sheets_searched
+            )
+        ),
+        Ok(result) => {
+            let (headers, rows) = search::table_rows(&result);
+            table::print_table(&headers, &rows);
+            if result.total > result.hits.len() {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  {} coincidencias; se muestran las {} primeras (usa max=<n> para ver más)",
+                        "ℹ️  {} matches; showing the first {} (use max=<n> to see more)",
+                        result.total,
+                        result.hits.len()
+                    )
+                );
+            } else {
+                println!("{}", tr!("✅ {} coincidencias en {}", "✅ {} matches in {}", result.total, options.file));
+            }
+            if options.add_to_context {
+                push_context(
+                    conversation_history,
+                    &config.context_budget,
+                    search::context_summary(&options.file, &options.query, &result),
+                );
+                println!("{}", tr!("ℹ️  Coincidencias añadidas al contexto", "ℹ️  Matches added to the context"));
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al buscar: {:#}", "❌ Error searching: {:#}", e)),
+    }
+}
+
+// grafico_texto
+fn handle_text_chart(session: &mut Session, options: TextChartOptions) {
+    let Session { workbooks, .. } = session;
+    // Como en buscar, un libro ya leído se dibuja con sus cambios sin guardar
+    let loaded = workbooks.get(&options.file).map(|entry| entry.data.clone());
+    let result = match loaded {
+        Some(data) => Ok(data),
+        None => read_excel_file(&options.file),
+    }
+    .and_then(|data| {
+        let sheet = match &options.sheet {
+            Some(name) => data.require_sheet(&options.file, name)?,
+            None => data.sheets.first().context("El libro no tiene hojas")?,
+        };
+        text_chart::render(sheet, &options)
+    });
+    match result {
+        Ok(chart) => println!("{}", chart),
+        Err(e) => println!("{}", tr!("❌ Error al dibujar el gráfico: {:#}", "❌ Error drawing the chart: {:#}", e)),
+    }
+}
+
+// extraer_json
+async fn handle_extract_json(session: &mut Session, options: ExtractOptions) {
+    let Session { config, conversation_history, client, usage_tracker, .. } = session;
+    match structured::extract(client, config, usage_tracker, &options).await {
+        Ok(extraction) => {
+            match structured::records_sheet(&extraction.value) {
+                Some(sheet) => {
+                    let rows = sheet.display_rows();
+                    table::print_table(&rows[0], &rows[1..]);
+                    println!("{}", tr!("✅ {} registros", "✅ {} records", rows.len() - 1));
+                }
+                None => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&extraction.value).unwrap_or_default()
+                ),
+            }
+            if extraction.attempts > 1 {
+                println!("{}", tr!("ℹ️  Respuesta válida en el intento {}", "ℹ️  Valid answer on attempt {}", extraction.attempts));
+            }
+            if let Some(output) = &options.output {
+                match outputs::target(output)
+                    .and_then(|output| structured::save(&extraction.value, &output).map(|()| output))
+                {
+                    Ok(output) => println!("{}", tr!("✅ Resultado guardado en {}", "✅ Result saved to {}", output)),
+                    Err(e) => println!("{}", tr!("❌ Error al guardar el resultado: {:#}", "❌ Error saving the result: {:#}", e)),
+                }
+            }
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!("JSON extraído de {} ({}): {}", options.file, options.instruction, extraction.value),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error en extraer_json: {:#}", "❌ Error in extract_json: {:#}", e)),
+    }
+}
+
+// cifrar_columna y descifrar_columna
+fn handle_column_crypto(session: &mut Session, options: ColumnCryptoOptions) {
+    let Session { config, .. } = session;
+    match &config.project_key {
+        Some(key) => match ColumnKey::new(key).and_then(|key| crypto::apply(&options, &key)) {
+            Ok((changed, output)) => println!(
+                "{}",
+                tr!(
+                    "✅ {} celdas {} en {}!{} ({})",
+                    "✅ {} cells {} in {}!{} ({})",
+                    changed,
+                    i18n::pick(if options.decrypt { ("descifradas", "decrypted") } else { ("cifradas", "encrypted") }),
+                    output,
+                    options.sheet,
+                    options.columns.join(", ")
+                )
+            ),
+            Err(e) => println!("{}", tr!("❌ Error al procesar las columnas: {:#}", "❌ Error processing the columns: {:#}", e)),
+        },
+        None => println!(
+            "{}",
+            tr!(
+                "❌ Define la clave del proyecto en IAGENT_PROJECT_KEY o IAGENT_PROJECT_KEY_FILE",
+                "❌ Set the project key in IAGENT_PROJECT_KEY or IAGENT_PROJECT_KEY_FILE"
+            )
+        ),
+    }
+}
+
+// anonimizar
+fn handle_anonymize(options: AnonymizeOptions) {
+    match anonymize::anonymize(&options) {
+        Ok(result) => {
+            println!(
+                "{}",
+                tr!(
+                    "✅ {} celdas anonimizadas en {} ({}; {} valores nuevos)",
+                    "✅ {} cells anonymized in {} ({}; {} new values)",
+                    result.cells,
+                    result.output,
+                    result.columns.join(", "),
+                    result.new_values
+                )
+            );
+            println!(
+                "{}",
+                tr!(
+                    "ℹ️  Lee {} en lugar del original; la correspondencia queda solo en este equipo y desanonimizar <archivo> la deshace",
+                    "ℹ️  Read {} instead of the original; the mapping stays on this machine only and deanonymize <file> reverses it",
+                    result.output
+                )
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al anonimizar: {:#}", "❌ Error anonymizing: {:#}", e)),
+    }
+}
+
+// desanonimizar
+fn handle_deanonymize(file: String, output: Option<String>) {
+    match anonymize::restore(&file, output.as_deref()) {
+        Ok((restored, output)) => println!(
+            "{}",
+            tr!(
+                "✅ {} celdas con sus valores reales en {}",
+                "✅ {} cells with their real values in {}",
+                restored, output
+            )
+        ),
+        Err(e) => println!("{}", tr!("❌ Error al desanonimizar: {:#}", "❌ Error deanonymizing: {:#}", e)),
+    }
+}
+
+// convertir_fechas
+fn handle_convert_dates(options: DateOptions) {
+    match dates::apply(&options) {
+        Ok(report) => {
+            println!(
+                "{}",
+                tr!(
+                    "✅ {} celdas convertidas a fecha en {}!{} ({} ya eran fechas)",
+                    "✅ {} cells converted to dates in {}!{} ({} were already dates)",
+                    report.converted,
+                    report.output,
+                    options.sheet,
+                    report.unchanged
+                )
+            );
+            if !report.failed.is_empty() {
+                let shown: Vec<&str> = report.failed.iter().take(5).map(String::as_str).collect();
+                println!(
+                    "{}",
+                    tr!(
+                        "⚠️  {} celdas no parecen fechas y se han dejado igual: {}",
+                        "⚠️  {} cells do not look like dates and were left as they were: {}",
+                        report.failed.len(),
+                        shown.join(", ")
+                    )
+                );
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al convertir las fechas: {:#}", "❌ Error converting the dates: {:#}", e)),
+    }
+}
+
+// generar_informe
+async fn handle_report(session: &mut Session, options: ReportOptions) {
+    let Session { config, client, usage_tracker, .. } = session;
+    match report::generate(&options) {
+        Ok(outcome) => {
+            let sheets: Vec<String> = outcome
+                .sheets
+                .iter()
+                .map(|(name, rows)| format!("{} ({} filas)", name, rows))
+                .collect();
+            println!("{}", tr!("✅ Informe generado en {}: {}", "✅ Report generated in {}: {}", outcome.output, sheets.join(", ")));
+            if outcome.unsupported_formulas > 0 {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  {} fórmulas no se han podido calcular aquí; Excel las calculará al abrir el libro",
+                        "ℹ️  {} formulas could not be calculated here; Excel will calculate them when the workbook is opened",
+                        outcome.unsupported_formulas
+                    )
+                );
+            }
+            let info = readme::GenerationInfo::for_report(&options);
+            add_readme(client, config, usage_tracker, &outcome.output, &info).await;
+        }
+        Err(e) => println!("{}", tr!("❌ Error al generar el informe: {:#}", "❌ Error generating the report: {:#}", e)),
+    }
+}
+
+// comparar
+fn handle_compare(session: &mut Session, options: CompareOptions) {
+    let Session { config, conversation_history, .. } = session;
+    match compare::run(&options) {
+        Ok(diff) => {
+            println!("{}", diff);
+            if let Some(output) = &options.output {
+                println!("{}", tr!("✅ Diferencias resaltadas guardadas en {}", "✅ Highlighted differences saved to {}", output));
+            }
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!("Comparación calculada localmente: {}", diff),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al comparar: {:#}", "❌ Error comparing: {:#}", e)),
+    }
+}
+
+// exportar_pdf
+fn handle_export(options: ExportOptions) {
+    match export::export(&options) {
+        Ok(outcome) => {
+            let pages = outcome.pages.map(|pages| tr!(", {} página(s)", ", {} page(s)", pages)).unwrap_or_default();
+            println!("{}", tr!("✅ Exportado a {} (con {}{})", "✅ Exported to {} (with {}{})", outcome.output, outcome.converter, pages));
+            for note in &outcome.notes {
+                println!("ℹ️  {}", note);
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al exportar: {:#}", "❌ Error exporting: {:#}", e)),
+    }
+}
+
+// aplicar
+fn handle_apply(session: &mut Session, file: String, sheet: String, index: Option<usize>) {
+    let Session { response_tables, .. } = session;
+    let position = index.unwrap_or(1);
+    match response_tables.get(position.saturating_sub(1)) {
+        Some(table) => match excel::write_sheet_to_file(&file, table.to_sheet(&sheet)) {
+            Ok(()) => {
+                println!(
+                    "{}",
+                    tr!(
+                        "✅ Tabla escrita en la hoja {} de {} ({})",
+                        "✅ Table written to sheet {} of {} ({})",
+                        sheet, file, table.describe()
+                    )
+                );
+                if index.is_none() && response_tables.len() > 1 {
+                    println!(
+                        "{}",
+                        tr!(
+                            "ℹ️  La respuesta tenía {} tablas; elige otra con tabla=<n>",
+                            "ℹ️  The answer had {} tables; pick another one with table=<n>",
+                            response_tables.len()
+                        )
+                    );
+                }
+            }
+            Err(e) => println!("{}", tr!("❌ Error al escribir la tabla: {:#}", "❌ Error writing the table: {:#}", e)),
+        },
+        None if response_tables.is_empty() => {
+            println!(
+                "{}",
+                tr!(
+                    "❌ La última respuesta no tiene ninguna tabla Markdown ni bloque csv",
+                    "❌ The last answer has no Markdown table or csv block"
+                )
+            )
+        }
+        None => println!("{}", tr!("❌ La última respuesta solo tiene {} tabla(s)", "❌ The last answer only has {} table(s)", response_tables.len())),
+    }
+}
+
+// pegar_datos
+fn handle_paste(session: &mut Session, file: String, sheet: String) {
+    let Session { config, conversation_history, .. } = session;
+    let pasted = clipboard::paste_sheet(&sheet).and_then(|(data, description)| {
+        let preview = table::render_preview(&data, 5);
+        table::remember_preview(&data, 5);
+        let headers = data.headers();
+        excel::write_sheet_to_file(&file, data)?;
+        Ok((description, preview, headers))
+    });
+    match pasted {
+        Ok((description, preview, headers)) => {
+            println!("{}", tr!("✅ Datos pegados en la hoja {} de {} ({})", "✅ Data pasted into sheet {} of {} ({})", sheet, file, description));
+            println!("{}", preview);
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!(
+                    "El usuario ha pegado datos del portapapeles en la hoja {} de '{}' ({}). Encabezados: {}",
+                    sheet,
+                    file,
+                    description,
+                    headers.join(", ")
+                ),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al pegar los datos: {:#}", "❌ Error pasting the data: {:#}", e)),
+    }
+}
+
+// insertar_fila, eliminar_fila, insertar_columna, eliminar_columna y mover_columna
+fn handle_structure(session: &mut Session, options: EditOptions) {
+    let Session { config, workbooks, conversation_history, .. } = session;
+    match workbooks.edit_sheet(options.file.as_deref(), &options.sheet, |sheet| {
+        let description = structure::apply(sheet, &options.edit)?;
+        Ok((description, sheet.name.clone(), sheet.rows.len(), sheet.headers()))
+    }) {
+        Ok((path, (description, sheet, rows, headers))) => {
+            println!("{}", tr!("✅ {} — hoja {}: {}", "✅ {} — sheet {}: {}", path, sheet, description));
+            println!(
+                "{}",
+                tr!(
+                    "ℹ️  El cambio está en memoria; usa guardar {} para escribirlo",
+                    "ℹ️  The change is in memory; use save {} to write it",
+                    path
+                )
+            );
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!(
+                    "Cambio en la hoja {} de '{}': {}. Ahora tiene {} filas y los encabezados: {}",
+                    sheet,
+                    path,
+                    description,
+                    rows,
+                    headers.join(", ")
+                ),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al editar la hoja: {:#}", "❌ Error editing the sheet: {:#}", e)),
+    }
+}
+
+// transformar
+fn handle_transform(session: &mut Session, args: Vec<String>) {
+    let Session { config, workbooks, conversation_history, .. } = session;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = TransformOptions::parse(&args).and_then(|options| {
+        workbooks.edit_sheet(options.file.as_deref(), &options.sheet, |sheet| {
+            let descriptions = transform::apply(sheet, &options.steps)?;
+            Ok((descriptions, sheet.name.clone(), sheet.rows.len(), sheet.headers()))
+        })
+    });
+    match result {
+        Ok((path, (descriptions, sheet, rows, headers))) => {
+            println!("{}", tr!("✅ {} — hoja {}:", "✅ {} — sheet {}:", path, sheet));
+            for description in &descriptions {
+                println!("   · {}", description);
+            }
+            println!(
+                "{}",
+                tr!(
+                    "ℹ️  El cambio está en memoria; usa guardar {} para escribirlo",
+                    "ℹ️  The change is in memory; use save {} to write it",
+                    path
+                )
+            );
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!(
+                    "Transformación de la hoja {} de '{}': {}. Ahora tiene {} filas y los encabezados: {}",
+                    sheet,
+                    path,
+                    descriptions.join("; "),
+                    rows,
+                    headers.join(", ")
+                ),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al transformar la hoja: {:#}", "❌ Error transforming the sheet: {:#}", e)),
+    }
+}
+
+// cruzar
+fn handle_join(session: &mut Session, options: JoinOptions) {
+    let Session { config, workbooks, conversation_history, .. } = session;
+    let joined = match (
+        workbooks.find_sheet(Some(&options.left_sheet)),
+        workbooks.find_sheet(Some(&options.right_sheet)),
+    ) {
+        (Some((path, left)), Some((_, right))) => {
+            join::left_join(left, right, &options).map(|(sheet, stats)| (path.to_string(), sheet, stats))
+        }
+        (None, _) => Err(anyhow::anyhow!("Ningún libro cargado tiene la hoja '{}'", options.left_sheet)),
+        (_, None) => Err(anyhow::anyhow!("Ningún libro cargado tiene la hoja '{}'", options.right_sheet)),
+    };
+    let result = joined.and_then(|(path, sheet, stats)| {
+        let preview = table::render_preview(&sheet, 5);
+        table::remember_preview(&sheet, 5);
+        let name = sheet.name.clone();
+        workbooks.add_sheet(&path, sheet)?;
+        Ok((path, name, stats, preview))
+    });
+    match result {
+        Ok((path, name, stats, preview)) => {
+            let description = stats.describe(&options.right_sheet);
+            println!("{}", tr!("✅ Hoja {} añadida a {}: {}", "✅ Sheet {} added to {}: {}", name, path, description));
+            println!("{}", preview);
+            println!(
+                "{}",
+                tr!(
+                    "ℹ️  El cambio está en memoria; usa guardar {} para escribirlo",
+                    "ℹ️  The change is in memory; use save {} to write it",
+                    path
+                )
+            );
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!(
+                    "Cruce de las hojas {} ({}) y {} ({}) calculado localmente en la hoja nueva {} de '{}': {}",
+                    options.left_sheet,
+                    options.left_key,
+                    options.right_sheet,
+                    options.right_key,
+                    name,
+                    path,
+                    description
+                ),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al cruzar las hojas: {:#}", "❌ Error joining the sheets: {:#}", e)),
+    }
+}
+
+// duplicados
+fn handle_duplicates(session: &mut Session, options: DuplicateOptions) {
+    let Session { config, conversation_history, .. } = session;
+    match duplicates::report(&options) {
+        Ok(report) => {
+            let description = report.describe();
+            println!("{} {}", if report.groups.is_empty() { "✅" } else { "⚠️ " }, description);
+            if !report.groups.is_empty() {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  eliminar_duplicados {} escribe una copia sin ellas",
+                        "ℹ️  remove_duplicates {} writes a copy without them",
+                        options.file
+                    )
+                );
+            }
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!("Duplicados calculados localmente en {}: {}", options.file, description),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al buscar duplicados: {:#}", "❌ Error looking for duplicates: {:#}", e)),
+    }
+}
+
+// eliminar_duplicados
+fn handle_remove_duplicates(session: &mut Session, options: DuplicateOptions) {
+    let Session { config, conversation_history, .. } = session;
+    match duplicates::remove(&options) {
+        Ok((report, output)) => {
+            println!(
+                "{}",
+                tr!(
+                    "✅ {} guardado: {} fila(s) duplicada(s) eliminada(s) de la hoja {}, quedan {}",
+                    "✅ {} saved: {} duplicate row(s) removed from sheet {}, {} left",
+                    output,
+                    report.extra_rows.len(),
+                    report.sheet,
+                    report.data_rows - report.extra_rows.len()
+                )
+            );
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!(
+                    "Copia de {} sin duplicados guardada en {}: {}",
+                    options.file,
+                    output,
+                    report.describe()
+                ),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al eliminar duplicados: {:#}", "❌ Error removing duplicates: {:#}", e)),
+    }
+}
+
+// editar
+async fn handle_edit_cell(session: &mut Session, file: String, sheet: String, cell: String, raw: String) {
+    let Session { config, workbooks, conversation_history, .. } = session;
+    // Se edita la copia cargada (se carga si hace falta) y se guarda
+    let loaded = if workbooks.get(&file).is_some() {
+        Ok(())
+    } else {
+        let path = file.clone();
+        limits::run_blocking(&format!("La lectura de {}", file), move || convert::read_any(Path::new(&path)))
+            .await
+            .map(|data| {
+                workbooks.insert(&file, data);
+            })
+    };
+    let (value, formula) = parse_cell_input(&raw);
+    let result = loaded
+        .and_then(|()| excel::parse_cell_ref(&cell).context(format!("Celda no válida: {}", cell)))
+        .and_then(|cell| workbooks.edit_cell(&file, &sheet, cell, value.clone(), formula.clone()));
+    match result {
+        Ok((sheet, previous)) => {
+            let path = &file;
+            let new = match &formula {
+                Some(formula) => format!("={}", formula),
+                None => value.to_string(),
+            };
+            let kind = if formula.is_some() { "fórmula" } else { value.type_name() };
+            println!(
+                "{}",
+                tr!(
+                    "✅ {} de la hoja {} en {}: '{}' → '{}' ({})",
+                    "✅ {} of sheet {} in {}: '{}' → '{}' ({})",
+                    cell.to_uppercase(), sheet, path, previous, new, kind
+                )
+            );
+            println!(
+                "{}",
+                tr!(
+                    "ℹ️  El cambio está en memoria ({} pendiente(s)); usa guardar {} para escribirlo",
+                    "ℹ️  The change is in memory ({} pending); use save {} to write it",
+                    workbooks.pending(path),
+                    path
+                )
+            );
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!(
+                    "Celda {} de la hoja {} de '{}' cambiada de '{}' a '{}' (pendiente de guardar)",
+                    cell.to_uppercase(),
+                    sheet,
+                    path,
+                    previous,
+                    new
+                ),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al editar la celda: {:#}", "❌ Error editing the cell: {:#}", e)),
+    }
+}
+
+// rellenar
+async fn handle_fill(session: &mut Session, options: FillOptions) {
+    let Session { config, workbooks, conversation_history, .. } = session;
+    // Como editar: se rellena la copia cargada y se escribe con guardar
+    let file = options.file.clone();
+    let loaded = if workbooks.get(&file).is_some() {
+        Ok(())
+    } else {
+        let path = file.clone();
+        limits::run_blocking(&format!("La lectura de {}", file), move || convert::read_any(Path::new(&path)))
+            .await
+            .map(|data| {
+                workbooks.insert(&file, data);
+            })
+    };
+    let result = loaded
+        .and_then(|()| fill::plan(&options, &workbooks.get(&file).context("libro cargado")?.data))
+        .and_then(|plan| {
+            let preview = plan.preview();
+            let count = plan.cells.len();
+            workbooks.edit_cells(&file, &plan.sheet, plan.cells).map(|_| (plan.description, count, preview))
+        });
+    match result {
+        Ok((description, count, preview)) => {
+            println!("{}", tr!("✅ {} celdas de {} rellenadas en {}: {}", "✅ {} cells of {} filled in {}: {}", count, description, file, preview));
+            println!(
+                "{}",
+                tr!(
+                    "ℹ️  El cambio está en memoria ({} pendiente(s)); usa guardar {} para escribirlo",
+                    "ℹ️  The change is in memory ({} pending); use save {} to write it",
+                    workbooks.pending(&file),
+                    file
+                )
+            );
+            push_context(
+                conversation_history,
+                &config.context_budget,
+                format!(
+                    "{} de '{}' rellenado con {} ({} celdas: {}; pendiente de guardar)",
+                    description, file, options.pattern, count, preview
+                ),
+            );
+        }
+        Err(e) => println!("{}", tr!("❌ Error al rellenar: {:#}", "❌ Error filling: {:#}", e)),
+    }
+}
+
+// guardar y guardar_como
+async fn handle_save(session: &mut Session, file: Option<String>, output: Option<String>, rename: bool, rewrite: bool) {
+    let Session { config, workbooks, retriever, client, .. } = session;
+    let file = file.or_else(|| workbooks.unsaved().last().map(|path| path.to_string()));
+    let file = match (file, rename) {
+        (None, true) => workbooks.find_sheet(None).map(|(path, _)| path.to_string()),
+        (file, _) => file,
+    };
+    match file {
+        Some(file) => match workbooks.save(&file, output.as_deref(), rename, rewrite) {
+            Ok(SaveOutcome { target, patched_cells }) => {
+                println!("{}", tr!("✅ {} guardado en {}", "✅ {} saved to {}", file, target));
+                if patched_cells > 0 {
+                    println!(
+                        "{}",
+                        tr!(
+                            "ℹ️  {} celda(s) escritas en el XML del libro, sin reescribirlo: se conservan fórmulas y gráficos",
+                            "ℹ️  {} cell(s) written into the workbook XML without rewriting it: formulas and charts are kept",
+                            patched_cells
+                        )
+                    );
+                } else {
+                    println!(
+                        "{}",
+                        tr!(
+                            "ℹ️  El archivo se ha reescrito a partir de los valores: conserva los formatos de celda y los anchos (deshacer {} recupera el anterior)",
+                            "ℹ️  The file was rewritten from its values: cell formats and widths are kept (undo {} restores the previous one)",
+                            target
+                        )
+                    );
+                }
+                if rename && target != file {
+                    println!("{}", tr!("ℹ️  El libro cargado es ahora {}", "ℹ️  The loaded workbook is now {}", target));
+                }
+                if target == file && retriever.has(&file) {
+                    if let Some(entry) = workbooks.get(&file) {
+                        let indexed = interrupt::interruptible(retriever.index_workbook(
+                            client,
+                            config,
+                            &file,
+                            &entry.data,
+                        ))
+                        .await;
+                        report_indexed(&file, indexed, config);
+                    }
+                }
+            }
+            Err(e) => println!("{}", tr!("❌ Error al guardar: {:#}", "❌ Error saving: {:#}", e)),
+        },
+        None => println!("{}", tr!("ℹ️  No hay cambios sin guardar", "ℹ️  There are no unsaved changes")),
+    }
+}
+
+// ajustar_hoja
+fn handle_layout(session: &mut Session, options: LayoutOptions) {
+    match layout::apply(&options) {
+        Ok(layout) => println!("{}", tr!("✅ {} de {}: {}", "✅ {} of {}: {}", options.sheet, options.file, layout.describe())),
+        Err(e) => println!("{}", tr!("❌ Error al ajustar la hoja: {:#}", "❌ Error adjusting the sheet: {:#}", e)),
+    }
+    reload_loaded(session, &options.file);
+}
+
+// proteger
+fn handle_protect(session: &mut Session, options: ProtectOptions) {
+    match protection::apply(&options) {
+        Ok(outcome) => {
+            println!(
+                "{}",
+                tr!(
+                    "✅ Hoja {} de {} protegida{}: {} celda(s) con fórmula bloqueadas, {} editable(s)",
+                    "✅ Sheet {} of {} protected{}: {} formula cell(s) locked, {} editable",
+                    options.sheet,
+                    options.file,
+                    if options.password.is_some() { i18n::pick((" con contraseña", " with a password")) } else { "" },
+                    outcome.formulas,
+                    outcome.unlocked
+                )
+            );
+            if options.password.is_some() {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  La contraseña de hoja de Excel solo evita cambios accidentales: no cifra el libro",
+                        "ℹ️  An Excel sheet password only prevents accidental changes: it does not encrypt the workbook"
+                    )
+                );
+            }
+        }
+        Err(e) => println!("{}", tr!("❌ Error al proteger la hoja: {:#}", "❌ Error protecting the sheet: {:#}", e)),
+    }
+    reload_loaded(session, &options.file);
+}
+
+// validar
+fn handle_validate(session: &mut Session, options: ValidationOptions) {
+    match validation::apply(&options) {
+        Ok(description) => println!(
+            "{}",
+            tr!(
+                "✅ Validación ({}) añadida en {} de {}",
+                "✅ Validation ({}) added to {} in {}",
+                options.validation.describe(),
+                description,
+                options.file
+            )
+        ),
+        Err(e) => println!("{}", tr!("❌ Error al añadir la validación: {:#}", "❌ Error adding the validation: {:#}", e)),
+    }
+    reload_loaded(session, &options.file);
+}
+
+// formato_condicional
+fn handle_conditional_format(session: &mut Session, options: ConditionalFormatOptions) {
+    match conditional_format::apply(&options) {
+        Ok(()) => println!(
+            "{}",
+            tr!(
+                "✅ Formato condicional aplicado en {}!{} de {}",
+                "✅ Conditional format applied to {}!{} in {}",
+                options.sheet, options.range, options.file
+            )
+        ),
+        Err(e) => println!("{}", tr!("❌ Error al aplicar el formato: {:#}", "❌ Error applying the format: {:#}", e)),
+    }
+    reload_loaded(session, &options.file);
+}
+
+// Una pregunta para el modelo, con las filas relevantes de las hojas indexadas
+async fn ask(session: &mut Session, input: &str) -> Result<()> {
+    let Session { config, reply_lang, workbooks, retriever, conversation_history, client, usage_tracker, timings, stdin, session_log, response_tables, .. } = session;
+    let reloaded =
+        refresh_stale_workbooks(workbooks, conversation_history, &config.context_budget, &mut stdin.lock())?;
+    for path in reloaded {
+        if let Some(entry) = workbooks.get(&path).filter(|_| retriever.has(&path)) {
+            let indexed =
+                interrupt::interruptible(retriever.index_workbook(client, config, &path, &entry.data)).await;
+            report_indexed(&path, indexed, config);
+        }
+    }
+    let started = Instant::now();
+
+    // Filas relevantes de las hojas indexadas: solo para esta pregunta, se
+    // retiran del historial al terminar
+    session_log.record_prompt(input);
+    let history_len = conversation_history.len();
+    let retrieved_at = if retriever.is_empty() {
+        None
+    } else {
+        let available = budget::available(conversation_history, &config.context_budget);
+        match interrupt::interruptible(retriever.context_for(client, config, input, available))
+            .await
+            .unwrap_or(Ok(None))
+        {
+            Ok(Some(rows)) => {
+                conversation_history.push(untrusted::file_message(&rows));
+                Some(conversation_history.len() - 1)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                println!("{}", tr!("⚠️  No se pudo buscar filas relevantes: {:#}", "⚠️  Could not search for relevant rows: {:#}", e));
+                None
+            }
+        }
+    };
+
+    // Sin idioma fijado, el modelo responde en el de la pregunta (o en el de la
+    // anterior si no se distingue); la instrucción se retira al terminar
+    let instruction_at = match config.reply_lang {
+        Some(_) => None,
+        None => {
+            *reply_lang = i18n::reply_lang(None, input, *reply_lang);
+            i18n::reply_instruction(*reply_lang).map(|instruction| {
+                conversation_history.push(Message::new("system", instruction));
+                conversation_history.len() - 1
+            })
+        }
+    };
+
+    // Añade la entrada del usuario al historial
+    conversation_history.push(Message::new("user", input));
+
+    // Obtiene respuesta de Deepseek (la respuesta se añade al historial);
+    // Ctrl-C abandona la petición y vuelve al prompt
+    let asked = agent::ask_model(client, config, conversation_history, usage_tracker, timings);
+    match interrupt::interruptible(asked).await {
+        Some(Ok(response)) => {
+            println!("{}", response);
+            clipboard::remember_answer(&response);
+            *response_tables = extract::tables(&response);
+            if !response_tables.is_empty() {
+                println!(
+                    "{}",
+                    tr!(
+                        "ℹ️  La respuesta incluye {} tabla(s); usa aplicar <archivo> <hoja> para escribirla en un libro",
+                        "ℹ️  The answer includes {} table(s); use apply <file> <sheet> to write it to a workbook",
+                        response_tables.len()
+                    )
+                );
+            }
+        }
+        Some(Err(e)) => {
+            // Como al cancelar: la pregunta sin respuesta no se queda en el historial
+            conversation_history.truncate(history_len);
+            println!("{}: {:#}", i18n::text(Msg::ModelError), e);
+            if let Some(hint) = IAgentError::find(&e).and_then(IAgentError::hint) {
+                println!("ℹ️  {}", hint);
+            }
+        }
+        None => {
+            // Sin la pregunta ni las llamadas a herramientas a medias, el
+            // historial sigue siendo válido para la siguiente petición
+            conversation_history.truncate(history_len);
+            println!("{}", i18n::text(Msg::Cancelled));
+        }
+    }
+    for idx in [instruction_at, retrieved_at].into_iter().flatten() {
+        if idx < conversation_history.len() {
+            conversation_history.remove(idx);
+        }
+    }
+    // Las sesiones largas resumen sus turnos antiguos en lugar de crecer sin límite
+    let compressed = compress::maybe_compress(client, config, conversation_history, usage_tracker);
+    match interrupt::interruptible(compressed).await {
+        Some(Ok(Some(compressed))) => println!(
+            "{}",
+            tr!(
+                "🗜️  Historial resumido: {} mensajes antiguos sustituidos por un resumen (~{} → ~{} tokens)",
+                "🗜️  History summarized: {} old messages replaced by a summary (~{} → ~{} tokens)",
+                compressed.messages, compressed.before, compressed.after
+            )
+        ),
+        Some(Err(e)) => println!("{}", tr!("⚠️  No se pudo resumir el historial: {:#}", "⚠️  Could not summarize the history: {:#}", e)),
+        Some(Ok(None)) | None => {}
+    }
+    record_timing(timings, config, "pregunta", input, started);
+    Ok(())
+}
+
+// `None` si el usuario canceló la indexación con Ctrl-C
+fn report_indexed(path: &str, indexed: Option<Result<Vec<(String, usize)>>>, config: &Config) {
+    let Some(indexed) = indexed else {
+        println!(
+            "{}",
+            tr!(
+                "⏹ Indexación de {} cancelada; sus filas no se buscarán",
+                "⏹ Indexing of {} cancelled; its rows will not be searched",
+                path
+            )
+        );
+        return;
+    };
+    match indexed {
+        Ok(sheets) => {
+            for (sheet, chunks) in sheets {
+                println!(
+                    "{}",
+                    tr!(
+                        "🔎 Hoja {} de {} indexada para búsqueda: {} bloques de filas ({})",
+                        "🔎 Sheet {} of {} indexed for search: {} row blocks ({})",
+                        sheet,
+                        path,
+                        chunks,
+                        config.embeddings.description()
+                    )
+                );
+            }
+        }
+        Err(e) => println!("{}", tr!("⚠️  No se pudo indexar {} para búsqueda: {:#}", "⚠️  Could not index {} for search: {:#}", path, e)),
+    }
+}
+
+// Antes de preguntar al modelo, avisa de los archivos cargados que han cambiado
+// en disco y ofrece recargarlos para no razonar sobre un resumen obsoleto;
+// devuelve los que se han recargado
+fn refresh_stale_workbooks(
+    workbooks: &mut WorkbookCache,
+    history: &mut Vec<Message>,
+    budget: &TokenBudget,
+    reader: &mut impl BufRead,
+) -> Result<Vec<String>> {
+    let mut reloaded = Vec::new();
+    for stale in workbooks.changed_on_disk() {
+        if stale.missing {
+            println!("⚠️  {} {}", stale.path, i18n::text(Msg::MissingFile));
+            workbooks.acknowledge(&stale.path);
+            continue;
+        }
+        print!("⚠️  {} {} ", stale.path, i18n::text(Msg::ReloadPrompt));
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        reader.read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "s" | "si" | "sí" | "y" | "yes") {
+            workbooks.acknowledge(&stale.path);
+            continue;
+        }
+        match convert::read_any(Path::new(&stale.path)) {
+            Ok(data) => {
+                let summary = workbooks.insert(&stale.path, data).summary.clone();
+                push_context(
+                    history,
+                    budget,
+                    format!("El archivo '{}' se ha recargado; datos actuales: {}", stale.path, summary),
+                );
+                println!("✅ {} {}", stale.path, i18n::text(Msg::Reloaded));
+                reloaded.push(stale.path);
+            }
+            Err(e) => println!("{}", tr!("❌ Error al recargar {}: {:#}", "❌ Error reloading {}: {:#}", stale.path, e)),
+        }
+    }
+    Ok(reloaded)
+}
+
+// Carga en paralelo los archivos del patrón, los registra en la caché y
+// devuelve sus resúmenes combinados, repartiendo `max_tokens` entre ellos
+async fn read_many(
+    workbooks: &mut WorkbookCache,
+    schema_memory: &mut Option<SchemaMemory>,
+    pattern: &str,
+    max_tokens: usize,
+) -> Result<String> {
+    let paths = files::expand_pattern(pattern)?;
+    if paths.is_empty() {
+        bail!("Ningún archivo coincide con '{}'", pattern);
+    }
+    let results = workbook_cache::load_many(paths).await;
+    let readable = results.iter().filter(|(_, result)| result.is_ok()).count();
+    let share = max_tokens / readable.max(1);
+    let mut merged = String::new();
+    let mut loaded = 0;
+    for (path, result) in results {
+        let name = path.display().to_string();
+        match result {
+            Ok(data) => {
+                merged.push_str(&format!("--- {}\n{}", name, summary::summarize_workbook(&data, share)));
+                remember_schema(schema_memory, &name, &data, true);
+                workbooks.insert(&name, data);
+                loaded += 1;
+            }
+            Err(e) => println!("❌ {}: {:#}", name, e),
+        }
+    }
+    println!("{}", tr!("✅ {} archivos leídos correctamente", "✅ {} files read successfully", loaded));
+    if loaded == 0 {
+        bail!("No se pudo leer ningún archivo");
+    }
+    Ok(merged)
+}
+
+// Guarda la estructura del libro en la memoria de esquemas; un fallo solo se avisa
+fn remember_schema(memory: &mut Option<SchemaMemory>, file: &str, data: &excel::WorkbookData, complete: bool) {
+    let Some(memory) = memory else { return };
+    memory.remember(file, data, complete);
+    if let Err(e) = memory.save() {
+        println!("⚠️  {:#}", e);
+    }
+}
+
+// Inserta contenido de archivos en el historial, como datos delimitados (ver
+// `untrusted`), respetando el presupuesto de tokens; avisa siempre que se recorta
+// o se descarta, para que nunca ocurra en silencio
+fn push_context(history: &mut Vec<Message>, budget: &TokenBudget, text: String) {
+    if let Some(text) = fit_context(history, budget, text) {
+        history.push(untrusted::file_message(&text));
+    }
+}
+
+// Como push_context, pero como resultado de una llamada a `tool` con `arguments`,
+// para que el modelo pueda seguir pidiendo datos con otra herramienta
+fn push_tool_context(history: &mut Vec<Message>, budget: &TokenBudget, tool: &str, arguments: serde_json::Value, text: String) {
+    if let Some(text) = fit_context(history, budget, text) {
+        let seq = history.len();
+        history.extend(untrusted::file_tool_messages(tool, &arguments.to_string(), &text, seq));
+    }
+}
+
+fn fit_context(history: &[Message], budget: &TokenBudget, text: String) -> Option<String> {
+    match budget::fit(history, budget, &text) {
+        Fitted::Complete(text) => Some(text),
+        Fitted::Truncated(text, original) => {
+            println!(
+                "{}",
+                tr!(
+                    "⚠️  Contexto recortado de ~{} a ~{} tokens (IAGENT_CONTEXT_ITEM_TOKENS / IAGENT_CONTEXT_TOTAL_TOKENS)",
+                    "⚠️  Context trimmed from ~{} to ~{} tokens (IAGENT_CONTEXT_ITEM_TOKENS / IAGENT_CONTEXT_TOTAL_TOKENS)",
+                    original,
+                    budget::estimate_tokens(&text)
+                )
+            );
+            Some(text)
+        }
+        Fitted::Rejected => {
+            println!(
+                "{}",
+                tr!(
+                    "⚠️  Presupuesto de contexto agotado (~{} tokens); no se añade al historial. Aumenta IAGENT_CONTEXT_TOTAL_TOKENS o reinicia la sesión",
+                    "⚠️  Context budget exhausted (~{} tokens); it is not added to the history. Raise IAGENT_CONTEXT_TOTAL_TOKENS or restart the session",
+                    budget.total
+                )
+            );
+            None
+        }
+    }
+}
+
+fn print_row_outcome(outcome: &row_prompts::RowPromptOutcome) {
+    println!(
+        "{}",
+        tr!(
+            "✅ {} respuestas ({} con error) en la columna {} de {}!{}",
+            "✅ {} answers ({} with errors) in column {} of {}!{}",
+            outcome.answered + outcome.failed,
+            outcome.failed,
+            outcome.column,
+            outcome.output,
+            outcome.sheet
+        )
+    );
+    if outcome.resumed > 0 {
+        println!("{}", tr!("ℹ️  {} ya estaban guardadas en el trabajo", "ℹ️  {} were already saved in the job", outcome.resumed));
+    }
+    if outcome.pending > 0 {
+        println!("{}", tr!("ℹ️  {} filas quedaron sin respuesta", "ℹ️  {} rows were left without an answer", outcome.pending));
+    }
+    if let Some(id) = &outcome.job {
+        println!(
+            "{}",
+            tr!(
+                "ℹ️  Las filas que faltan o fallaron se piden con `reanudar {}`",
+                "ℹ️  Missing or failed rows are requested with `resume {}`",
+                id
+            )
+        );
+    }
+}
+
+// Una línea de la entrada estándar; `None` al final de la entrada
+fn read_input_line(stdin: &io::Stdin, interactive: bool, prompt: &str) -> Result<Option<String>> {
+    if interactive && !prompt.is_empty() {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+    }
+    let mut line = String::new();
+    if stdin.lock().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+// Registra la duración de una operación y la muestra en modo detallado
+fn record_timing(timings: &mut Timings, config: &Config, name: &str, detail: &str, started: Instant) {
+    let elapsed = started.elapsed();
+    if config.verbose {
+        println!("⏱  {}: {}", name, timing::format_duration(elapsed));
+    }
+    timings.record(name, detail, elapsed);
+}
+
+// Añade la hoja Léeme a un libro generado si está activada (--leeme o IAGENT_README_SHEET)
+async fn add_readme(
+    client: &Client,
+    config: &Config,
+    usage_tracker: &mut UsageTracker,
+    path: &str,
+    info: &readme::GenerationInfo,
+) {
+    if !config.readme_sheet {
+        return;
+    }
+    match readme::add_readme_sheet(client, config, usage_tracker, path, info).await {
+        Ok(()) => println!("{}", tr!("📄 Hoja {} añadida a {}", "📄 Sheet {} added to {}", readme::README_SHEET, path)),
+        Err(e) => println!(
+            "{}",
+            tr!(
+                "❌ No se pudo añadir la hoja {} a {}: {:#}",
+                "❌ Could not add sheet {} to {}: {:#}",
+                readme::README_SHEET, path, e
+            )
+        ),
+    }
+}
+
+// Ayuda generada desde el registro de comandos
+fn show_help() {
+    println!("{}", i18n::text(Msg::HelpHeader));
+    for line in commands::help_lines() {
+        println!("{}", line);
+    }
+    println!();
+    println!("{}", i18n::text(Msg::HelpFooter));
+}
+
+// Un comando mal escrito muestra su uso en lugar de llegar al modelo; una pregunta
+// que solo empieza por la misma palabra ("comparar las ventas de enero con febrero")
+// sigue siendo una pregunta. Se considera comando si no tiene argumentos o alguno
+// parece una ruta, una opción o un rango.
+fn attempted_command(input: &str) -> Option<&'static str> {
+    let args = split_quoted(input).ok()?;
+    let (first, rest) = args.split_first()?;
+    let name = commands::excel_names().find(|name| name == first)?;
+    (rest.is_empty() || input.contains('"') || rest.iter().any(|arg| looks_like_argument(arg))).then_some(name)
+}
+
+// Una primera palabra que se parece a un comando. Solo se intercepta la entrada si
+// los argumentos encajan con el uso de ese comando ("leer_exel datos.xlsx", "read
+// datos.xlsx"); si no, es una pregunta y el parecido queda en una pista ("busca los
+// clientes con deuda en v.xlsx").
+fn command_correction(input: &str) -> Option<Correction> {
+    let args = split_quoted(input).ok()?;
+    let (first, rest) = args.split_first()?;
+    let (run, names) = match suggest::correct(first)? {
+        Correction::Run(command) => (true, vec![command]),
+        Correction::Suggest(names) | Correction::Hint(names) => (false, names),
+    };
+    let fitting: Vec<String> = names.iter().filter(|name| commands::accepts(name, rest)).cloned().collect();
+    match fitting.as_slice() {
+        [] => Some(Correction::Hint(names)),
+        [command] if run => Some(Correction::Run(command.clone())),
+        _ => Some(Correction::Suggest(fitting)),
+    }
+}
+
+// Parsea comandos específicos de Excel
+fn parse_excel_command(input: &str) -> Option<ExcelCommand> {
+    // Las rutas con espacios van entre comillas: leer_excel "Ventas 2024.xlsx"
+    let args = split_quoted(input).ok()?;
+    let parts: Vec<&str> = args.iter().map(String::as_str).collect();
+    
+    match parts.first() {
+        Some(&"leer_excel") if parts.len() >= 2 => {
+            let flags = &parts[2..];
+            Some(ExcelCommand::ReadFile(
+                parts[1].to_string(),
+                flags.contains(&"--stream"),
+                flags.contains(&"--evaluar"),
+                flags.contains(&"--formulas"),
+            ))
+        }
+        Some(&"formulas") if parts.len() >= 3 && looks_like_argument(parts[1]) => {
+            Some(ExcelCommand::Formulas(parts[1].to_string(), parts[2..].join(" ")))
+        }
+        Some(&"explicar") if parts.len() == 2 && looks_like_argument(parts[1]) => Some(ExcelCommand::Explain(parts[1].to_string())),
+        Some(&"leer_varios") if parts.len() >= 2 => Some(ExcelCommand::ReadMany(parts[1..].join(" "))),
+        Some(&"mostrar") => {
+            // Sin número de filas, la hoja entera por páginas
+            let (sheet_parts, rows) = match parts[1..].split_last() {
+                Some((last, rest)) if last.parse::<usize>().is_ok() => (rest, Some(last.parse().ok()?)),
+                _ => (&parts[1..], None),
+            };
+            let sheet = Some(sheet_parts.join(" ")).filter(|name| !name.is_empty());
+            Some(ExcelCommand::Show(sheet, rows))
+        }
+        Some(&"deshacer") if parts.len() >= 2 => Some(ExcelCommand::Undo(parts[1..].join(" "))),
+        Some(&"crear_excel") if parts.len() >= 2 => {
+            Some(ExcelCommand::CreateFile(parts[1].to_string()))
+        }
+        Some(&"escribir_excel") if parts.len() >= 3 => {
+            // Los datos se toman tal cual para no alterar los espacios de un JSON
+            let (filename, data) = split_first_arg(input.strip_prefix("escribir_excel")?)?;
+            let data = data.trim();
+            match data.strip_prefix("--reescribir").filter(|rest| rest.starts_with(char::is_whitespace)) {
+                Some(rest) => Some(ExcelCommand::WriteData(filename, rest.trim().to_string(), true)),
+                None => Some(ExcelCommand::WriteData(filename, data.to_string(), false)),
+            }
+        }
+        Some(&"escribir_rango") if parts.len() >= 4 => parse_write_range(input),
+        Some(&"escribir_formula") if parts.len() >= 4 => parse_write_formula(input),
+        Some(&"escribir_enlace") if parts.len() >= 4 => parse_link_options(input),
+        Some(&"nota") if parts.len() >= 3 => parse_note_options(input),
+        Some(&"crear_tabla") if parts.len() >= 4 => parse_table_options(input),
+        Some(&command @ ("agrupar" | "desagrupar")) if parts.len() >= 3 => parse_group_options(input, command == "desagrupar"),
+        Some(&"combinar_celdas") if parts.len() >= 3 => {
+            let (filename, target) = split_first_arg(input.strip_prefix("combinar_celdas")?)?;
+            Some(ExcelCommand::MergeCells(filename, target.trim().to_string()))
+        }
+        Some(&"convertir") if parts.len() >= 2 => parse_convert_options(&parts[1..]),
+        Some(&command @ ("top" | "bottom")) if parts.len() >= 4 => {
+            parse_rank_options(&parts[1..], command == "bottom")
+        }
+        Some(&"pareto") if parts.len() >= 4 => parse_pareto_options(&parts[1..]),
+        Some(&"cohortes") if parts.len() >= 4 => parse_cohort_options(&parts[1..]),
+        Some(&"estadisticas") if parts.len() >= 3 => parse_stats_options(&parts[1..]),
+        Some(&command @ ("cifrar_columna" | "descifrar_columna")) if parts.len() >= 4 => {
+            parse_column_crypto_options(&parts[1..], command == "descifrar_columna")
+        }
+        Some(&"anonimizar") if parts.len() >= 3 => parse_anonymize_options(&parts[1..]),
+        Some(&"desanonimizar") if parts.len() >= 2 => {
+            let (positional, options) = split_key_values(&parts[1..]);
+            match positional.as_slice() {
+                [file] => Some(ExcelCommand::Deanonymize(file.to_string(), options.get("salida").map(|s| s.to_string()))),
+                _ => None,
+            }
+        }
+        Some(&"preguntar_lote") if parts.len() >= 3 => parse_batch_options(input),
+        Some(&"para_cada_fila") if parts.len() >= 4 => parse_row_prompt_options(input),
+        Some(&"extraer_json") if parts.len() >= 3 => parse_extract_options(input),
+        Some(&"buscar") if parts.len() >= 3 => parse_search_options(&parts[1..]),
+        Some(&"grafico_texto") if parts.len() >= 4 => parse_text_chart_options(input),
+        Some(&"convertir_fechas") if parts.len() >= 4 => parse_date_options(&parts[1..]),
+        Some(&"generar_informe") if parts.len() >= 3 => {
+            let (positional, options) = split_key_values(&parts[1..]);
+            match positional.as_slice() {
+                [template, data] => Some(ExcelCommand::Report(ReportOptions {
+                    template: template.to_string(),
+                    data: data.to_string(),
+                    output: options.get("salida").map(|s| s.to_string()),
+                })),
+                _ => None,
+            }
+        }
+        Some(&"comparar") if parts.len() >= 3 => {
+            let (positional, options) = split_key_values(&parts[1..]);
+            match positional.as_slice() {
+                [left, right] => Some(ExcelCommand::Compare(CompareOptions {
+                    left: left.to_string(),
+                    right: right.to_string(),
+                    output: options.get("salida").map(|s| s.to_string()),
+                })),
+                _ => None,
+            }
+        }
+        Some(&command @ ("insertar_fila" | "eliminar_fila" | "insertar_columna" | "eliminar_columna"
+        | "mover_columna")) if parts.len() >= 3 => {
+            let (positional, options) = split_key_values(&parts[1..]);
+            EditOptions::parse(command, &positional, &options).map(ExcelCommand::Structure)
+        }
+        Some(&"transformar") if parts.len() >= 3 => {
+            Some(ExcelCommand::Transform(parts[1..].iter().map(|arg| arg.to_string()).collect()))
+        }
+        Some(&"cruzar") => parse_join_options(&parts[1..]),
+        Some(&"editar") if parts.len() >= 5 => parse_edit_cell(input),
+        Some(&"rellenar") if parts.len() >= 4 => parse_fill(input),
+        Some(&"duplicados") => parse_duplicate_options(&parts[1..], false).map(ExcelCommand::Duplicates),
+        Some(&"eliminar_duplicados") => parse_duplicate_options(&parts[1..], true).map(ExcelCommand::RemoveDuplicates),
+        Some(&"exportar_pdf") if parts.len() >= 2 => {
+            let (positional, options) = split_key_values(&parts[1..]);
+            let converter = match options.get("conversor") {
+                Some(value) => Converter::parse(value)?,
+                None => Converter::Auto,
+            };
+            match positional.as_slice() {
+                [file] => Some(ExcelCommand::Export(ExportOptions {
+                    file: file.to_string(),
+                    sheet: options.get("hoja").map(|s| s.to_string()),
+                    output: options.get("salida").map(|s| s.to_string()),
+                    converter,
+                })),
+                _ => None,
+            }
+        }
+        Some(&"aplicar") if parts.len() >= 3 => {
+            let (positional, options) = split_key_values(&parts[1..]);
+            let index = match options.get("tabla") {
+                Some(index) => Some(index.parse().ok().filter(|index| *index > 0)?),
+                None => None,
+            };
+            match positional.as_slice() {
+                [file, sheet] => Some(ExcelCommand::Apply(file.to_string(), sheet.to_string(), index)),
+                _ => None,
+            }
+        }
+        Some(&"pegar_datos") if parts.len() >= 2 => {
+            let (positional, options) = split_key_values(&parts[1..]);
+            let sheet = options.get("hoja").map(|s| s.to_string()).unwrap_or_else(|| clipboard::DEFAULT_SHEET.to_string());
+            match positional.as_slice() {
+                [file] => Some(ExcelCommand::Paste(file.to_string(), sheet)),
+                _ => None,
+            }
+        }
+        Some(&"guardar") => {
+            let (positional, options) = split_key_values(&parts[1..]);
+            let output = options.get("salida").map(|s| s.to_string());
+            let (rewrite, positional) = take_flag(positional, "--reescribir");
+            match positional.as_slice() {
+                [] => Some(ExcelCommand::Save(None, output, false, rewrite)),
+                [file] => Some(ExcelCommand::Save(Some(file.to_string()), output, false, rewrite)),
+                _ => None,
+            }
+        }
+        Some(&"guardar_como") => {
+            let (rewrite, args) = take_flag(parts[1..].to_vec(), "--reescribir");
+            match args.as_slice() {
+                [target] => Some(ExcelCommand::Save(None, Some(target.to_string()), true, rewrite)),
+                [file, target] => Some(ExcelCommand::Save(Some(file.to_string()), Some(target.to_string()), true, rewrite)),
+                _ => None,
+            }
+        }
+        Some(&"ajustar_hoja") if parts.len() >= 3 => {
+            let (flags, options) = split_key_values(&parts[3..]);
+            Some(ExcelCommand::Layout(LayoutOptions {
+                file: parts[1].to_string(),
+                sheet: parts[2].to_string(),
+                spec: LayoutSpec::parse_options(&flags, &options).ok()?,
+            }))
+        }
+        Some(&"proteger") if parts.len() >= 3 => {
+            let (positional, options) = split_key_values(&parts[3..]);
+            let password = match positional.as_slice() {
+                [] => None,
+                [password] => Some(password.to_string()),
+                _ => return None,
+            };
+            Some(ExcelCommand::Protect(ProtectOptions {
+                file: parts[1].to_string(),
+                sheet: parts[2].to_string(),
+                password,
+                editable: match options.get("editables") {
+                    Some(ranges) => protection::parse_ranges(ranges).ok()?,
+                    None => Vec::new(),
+                },
+            }))
+        }
+        Some(&"validar") if parts.len() >= 4 => parse_validation_options(input),
+        Some(&"formato_condicional") if parts.len() >= 5 => {
+            Some(ExcelCommand::ConditionalFormat(ConditionalFormatOptions {
+                file: parts[1].to_string(),
+                sheet: parts[2].to_string(),
+                range: CellRange::parse(parts[3])?,
+                rule: conditional_format::parse_rule(&parts[4..]).ok()?,
+            }))
+        }
+        _ => None,
+    }
+}
+
+// Parsea `convertir <patrón> --a <formato> [--salida <dir>] [--validar]`
+fn parse_convert_options(args: &[&str]) -> Option<ExcelCommand> {
+    let mut format = None;
+    let mut output_dir = None;
+    let mut validate = false;
+    let mut iter = args[1..].iter();
+    while let Some(arg) = iter.next() {
+        match *arg {
+            "--a" => format = Some(FileFormat::parse(iter.next()?)?),
+            "--salida" => output_dir = Some(*iter.next()?),
+            "--validar" => validate = true,
+            _ => return None,
+        }
+    }
+    Some(ExcelCommand::Convert(ConvertOptions {
+        pattern: args[0].to_string(),
+        format: format?,
+        output_dir: outputs::target_dir(output_dir),
+        validate,
+    }))
+}
+
+// Parsea `escribir_rango <archivo> <destino> <valores> [--sobrescribir | desplazar=abajo|derecha]`;
+// el destino puede llevar una hoja entre comillas simples con espacios ('Hoja 1'!B2)
+fn parse_write_range(input: &str) -> Option<ExcelCommand> {
+    let (file, rest) = split_first_arg(input.strip_prefix("escribir_rango")?)?;
+    let (target, values) = split_target(rest)?;
+    let mut values = values.trim();
+    let mut spill = SpillPolicy::Refuse;
+    // Las opciones van al final, detrás de los valores; i18n no traduce esta línea,
+    // así que se aceptan también en inglés
+    while let Some((front, last)) = values.rsplit_once(char::is_whitespace) {
+        if last == "--sobrescribir" || last == "--overwrite" {
+            spill = SpillPolicy::Overwrite;
+        } else if let Some(shift) = last
+            .strip_prefix("desplazar=")
+            .or_else(|| last.strip_prefix("shift="))
+            .and_then(SpillPolicy::parse_shift)
+        {
+            spill = shift;
+        } else {
+            break;
+        }
+        values = front.trim_end();
+    }
+    if values.is_empty() {
+        return None;
+    }
+    Some(ExcelCommand::WriteRange(file, target, values.to_string(), spill))
+}
+
+// Separa el destino (Hoja!B2, 'Hoja 1'!B2:C4 o un nombre) del resto de la línea,
+// que no puede faltar
+fn split_target(rest: &str) -> Option<(String, &str)> {
+    split_first_arg(rest).filter(|(_, rest)| !rest.trim().is_empty())
+}
+
+// Parsea `escribir_formula <archivo> <destino> <fórmula> [--sobrescribir]`; la
+// fórmula es el resto de la línea y puede llevar espacios
+fn parse_write_formula(input: &str) -> Option<ExcelCommand> {
+    let (file, rest) = split_first_arg(input.strip_prefix("escribir_formula")?)?;
+    let (target, formula) = split_target(rest)?;
+    let formula = formula.trim();
+    let (formula, overwrite) = match formula.rsplit_once(char::is_whitespace) {
+        Some((front, "--sobrescribir" | "--overwrite")) => (front.trim_end(), true),
+        _ => (formula, false),
+    };
+    if formula.is_empty() {
+        return None;
+    }
+    Some(ExcelCommand::WriteFormula(file, target, formula.to_string(), overwrite))
+}
+
+// Parsea `rellenar <archivo> <destino> <patrón> [paso=<n>] [--sobrescribir]`; el
+// patrón es el resto de la línea, como la fórmula de escribir_formula
+fn parse_fill(input: &str) -> Option<ExcelCommand> {
+    let (file, rest) = split_first_arg(input.strip_prefix("rellenar")?)?;
+    let (target, rest) = split_target(rest)?;
+    let mut pattern = rest.trim();
+    let (mut step, mut overwrite) = (None, false);
+    while let Some((front, last)) = pattern.rsplit_once(char::is_whitespace) {
+        match last {
+            "--sobrescribir" | "--overwrite" => overwrite = true,
+            _ if last.starts_with("paso=") => step = Some(last["paso=".len()..].to_string()),
+            _ => break,
+        }
+        pattern = front.trim_end();
+    }
+    if pattern.is_empty() {
+        return None;
+    }
+    Some(ExcelCommand::Fill(FillOptions {
+        file,
+        target,
+        pattern: pattern.to_string(),
+        step,
+        overwrite,
+    }))
+}
+
+// Parsea `editar <archivo> <hoja> <celda> <valor>`; el archivo y la hoja pueden ir
+// entre comillas y el valor es el resto de la línea
+fn parse_edit_cell(input: &str) -> Option<ExcelCommand> {
+    let (file, rest) = split_first_arg(input.strip_prefix("editar")?)?;
+    let (sheet, rest) = split_first_arg(rest)?;
+    let (cell, rest) = split_first_arg(rest)?;
+    let value = rest.trim();
+    if value.is_empty() {
+        return None;
+    }
+    Some(ExcelCommand::EditCell(file, sheet, cell, value.to_string()))
+}
+
+// Valor de `editar`: entre comillas es texto tal cual ("007"), con = una fórmula
+// y si no se infiere el tipo, reconociendo también fechas como 31/01/2024
+fn parse_cell_input(raw: &str) -> (excel::CellValue, Option<String>) {
+    let quoted = raw
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .or_else(|| raw.strip_prefix('“').and_then(|rest| rest.strip_suffix('”')));
+    if let Some(text) = quoted {
+        return (if text.is_empty() { excel::CellValue::Empty } else { excel::CellValue::Text(text.to_string()) }, None);
+    }
+    if let Some(formula) = raw.strip_prefix('=').filter(|f| !f.trim().is_empty()) {
+        return (excel::CellValue::Empty, Some(formula.trim().to_string()));
+    }
+    match excel::CellValue::infer(raw) {
+        excel::CellValue::Text(text) => match excel::parse_date_text(&text) {
+            Some(serial) => (excel::CellValue::DateTime(serial), None),
+            None => (excel::CellValue::Text(text), None),
+        },
+        value => (value, None),
+    }
+}
+
+// Parsea `escribir_enlace <archivo> <celda> <url> [texto]`; el texto puede ir entre comillas
+fn parse_link_options(input: &str) -> Option<ExcelCommand> {
+    let args = split_quoted(input.strip_prefix("escribir_enlace")?).ok()?;
+    let [file, cell, target, text @ ..] = args.as_slice() else {
+        return None;
+    };
+    Some(ExcelCommand::WriteLink(LinkOptions {
+        file: file.to_string(),
+        cell: cell.to_string(),
+        target: target.to_string(),
+        text: Some(text.join(" ")).filter(|text| !text.trim().is_empty()),
+    }))
+}
+
+// Parsea `nota <archivo> <celda> ["texto"|--quitar]`; sin texto muestra la nota
+fn parse_note_options(input: &str) -> Option<ExcelCommand> {
+    let args = split_quoted(input.strip_prefix("nota")?).ok()?;
+    let [file, cell, text @ ..] = args.as_slice() else {
+        return None;
+    };
+    let action = match text {
+        [] => NoteAction::Show,
+        [flag] if flag == "--quitar" || flag == "--remove" => NoteAction::Remove,
+        text => NoteAction::Write(text.join(" ")),
+    };
+    Some(ExcelCommand::Note(NoteOptions {
+        file: file.to_string(),
+        cell: cell.to_string(),
+        action,
+    }))
+}
+
+// Parsea `crear_tabla <archivo> <hoja> <rango> [estilo] [nombre=<tabla>] [--totales]
+// [--sin-bandas] [--sin-filtros]`
+fn parse_table_options(input: &str) -> Option<ExcelCommand> {
+    let args = split_quoted(input.strip_prefix("crear_tabla")?).ok()?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (positional, options) = split_key_values(&args);
+    let (flags, positional): (Vec<&str>, Vec<&str>) = positional.into_iter().partition(|arg| arg.starts_with("--"));
+    let (file, sheet, range, style) = match positional.as_slice() {
+        [file, sheet, range] => (*file, *sheet, *range, None),
+        [file, sheet, range, style] => (*file, *sheet, *range, Some(style.to_string())),
+        _ => return None,
+    };
+    let mut table = TableOptions {
+        file: file.to_string(),
+        sheet: sheet.to_string(),
+        range: CellRange::parse(range)?,
+        style: style.or_else(|| options.get("estilo").map(|style| style.to_string())),
+        name: options.get("nombre").map(|name| name.to_string()),
+        total_row: false,
+        banded_rows: true,
+        autofilter: true,
+    };
+    for flag in flags {
+        match flag {
+            "--totales" => table.total_row = true,
+            "--sin-bandas" => table.banded_rows = false,
+            "--sin-filtros" => table.autofilter = false,
+            _ => return None,
+        }
+    }
+    Some(ExcelCommand::Table(table))
+}
+
+// Parsea `agrupar <archivo> [filas|columnas] [Hoja!]<5-20|C-F> [--contraer] [resumen=arriba|abajo]`
+// y `desagrupar <archivo> [filas|columnas] [Hoja!]<rango>`
+fn parse_group_options(input: &str, ungroup: bool) -> Option<ExcelCommand> {
+    let command = if ungroup { "desagrupar" } else { "agrupar" };
+    let args = split_quoted(input.strip_prefix(command)?).ok()?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (positional, options) = split_key_values(&args);
+    let (flags, positional): (Vec<&str>, Vec<&str>) = positional.into_iter().partition(|arg| arg.starts_with("--"));
+    let (file, rest) = positional.split_first()?;
+    let (kind, target) = match rest {
+        [kind, target] => (Some(*kind), *target),
+        [target] => (None, *target),
+        _ => return None,
+    };
+    // Con filas o columnas, la hoja va delante: Detalle!filas 5-20
+    let (kind_sheet, kind) = match kind.and_then(|kind| kind.rsplit_once('!')) {
+        Some((sheet, kind)) => (Some(sheet.trim_matches('\'').to_string()), Some(kind)),
+        None => (None, kind),
+    };
+    let (sheet, target) = match target.rsplit_once('!') {
+        Some((sheet, target)) => (Some(sheet.trim_matches('\'').to_string()), target),
+        None => (kind_sheet.or_else(|| options.get("hoja").map(|sheet| sheet.to_string())), target),
+    };
+    let span = match (kind, Span::parse(target)?) {
+        (None, span) => span,
+        (Some("filas" | "rows"), span @ Span::Rows(..)) => span,
+        (Some("columnas" | "columns"), span @ Span::Columns(..)) => span,
+        // columnas 3-5 son C-E
+        (Some("columnas" | "columns"), Span::Rows(first, last)) => Span::Columns(first, last),
+        _ => return None,
+    };
+    let summary_before = match options.get("resumen").map(|value| value.to_lowercase()) {
+        None => None,
+        Some(value) => Some(match (value.as_str(), span) {
+            ("arriba" | "above", Span::Rows(..)) | ("izquierda" | "left", Span::Columns(..)) => true,
+            ("abajo" | "below", Span::Rows(..)) | ("derecha" | "right", Span::Columns(..)) => false,
+            _ => return None,
+        }),
+    };
+    let mut collapse = false;
+    for flag in flags {
+        match flag {
+            "--contraer" | "--collapse" if !ungroup => collapse = true,
+            _ => return None,
+        }
+    }
+    Some(ExcelCommand::Group(GroupOptions {
+        file: file.to_string(),
+        sheet,
+        span,
+        ungroup,
+        collapse,
+        summary_before,
+    }))
+}
+
+// "1,2;3,4" -> filas de celdas; el tipo de cada valor se deduce como al escribir datos
+fn parse_value_block(values: &str) -> Vec<Vec<excel::CellValue>> {
+    values
+        .split(';')
+        .map(|row| row.split(',').map(excel::CellValue::infer).collect())
+        .collect()
+}
+
+// Parsea `preguntar_lote "<pregunta>" <patrón> [salida=<archivo>]`; la pregunta va entre comillas
+fn parse_batch_options(input: &str) -> Option<ExcelCommand> {
+    let args = split_quoted(input.strip_prefix("preguntar_lote")?).ok()?;
+    let (question, args) = args.split_first()?;
+    let question = question.trim();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (positional, options) = split_key_values(&args);
+    if question.is_empty() || positional.len() != 1 {
+        return None;
+    }
+    Some(ExcelCommand::AskBatch(BatchOptions {
+        question: question.to_string(),
+        pattern: positional[0].to_string(),
+        output: options.get("salida").unwrap_or(&"respuestas_lote.xlsx").to_string(),
+    }))
+}
+
+// Parsea `extraer_json <archivo> "<instrucción>" [hoja=] [campos=<a,b:numero>|esquema=<archivo.json>] [salida=]`
+fn parse_extract_options(input: &str) -> Option<ExcelCommand> {
+    let args = split_quoted(input.strip_prefix("extraer_json")?).ok()?;
+    let (file, rest) = args.split_first()?;
+    let (instruction, rest) = rest.split_first()?;
+    let rest: Vec<&str> = rest.iter().map(String::as_str).collect();
+    let (positional, options) = split_key_values(&rest);
+    if !positional.is_empty() || instruction.trim().is_empty() {
+        return None;
+    }
+    Some(ExcelCommand::ExtractJson(ExtractOptions {
+        file: file.to_string(),
+        sheet: options.get("hoja").map(|s| s.to_string()),
+        instruction: instruction.to_string(),
+        fields: options.get("campos").map(|s| s.to_string()),
+        schema_file: options.get("esquema").map(|s| s.to_string()),
+        output: options.get("salida").map(|s| s.to_string()),
+    }))
+}
+
+// Parsea `para_cada_fila <archivo> <hoja> "<plantilla>" [columna=] [concurrencia=] [filas=] [salida=]`
+fn parse_row_prompt_options(input: &str) -> Option<ExcelCommand> {
+    let args = split_quoted(input.strip_prefix("para_cada_fila")?).ok()?;
+    let (file, rest) = args.split_first()?;
+    let (sheet, rest) = rest.split_first()?;
+    let (template, rest) = rest.split_first()?;
+    let rest: Vec<&str> = rest.iter().map(String::as_str).collect();
+    let (positional, options) = split_key_values(&rest);
+    if !positional.is_empty() || template.trim().is_empty() {
+        return None;
+    }
+    let number = |key: &str| match options.get(key) {
+        Some(value) => value.parse::<usize>().ok().filter(|n| *n > 0).map(Some),
+        None => Some(None),
+    };
+    Some(ExcelCommand::RowPrompts(RowPromptOptions {
+        file: file.to_string(),
+        sheet: sheet.to_string(),
+        template: template.to_string(),
+        column: options.get("columna").unwrap_or(&row_prompts::DEFAULT_COLUMN).to_string(),
+        concurrency: number("concurrencia")?.unwrap_or(row_prompts::DEFAULT_CONCURRENCY),
+        output: options.get("salida").map(|s| s.to_string()),
+        limit: number("filas")?,
+    }))
+}
+
+// Parsea `buscar <archivo> <texto|/regex/> [hoja=<nombre>] [max=50] [--contexto]`
+fn parse_search_options(args: &[&str]) -> Option<ExcelCommand> {
+    let (file, rest) = args.split_first()?;
+    let (query, rest) = rest.split_first()?;
+    let add_to_context = rest.contains(&"--contexto");
+    let rest: Vec<&str> = rest.iter().copied().filter(|arg| *arg != "--contexto").collect();
+    let (positional, options) = split_key_values(&rest);
+    if !positional.is_empty() || query.is_empty() {
+        return None;
+    }
+    let max_hits = match options.get("max") {
+        Some(value) => value.parse::<usize>().ok().filter(|n| *n > 0)?,
+        None => search::DEFAULT_MAX_HITS,
+    };
+    Some(ExcelCommand::Search(SearchOptions {
+        file: file.to_string(),
+        query: query.to_string(),
+        sheet: options.get("hoja").map(|s| s.to_string()),
+        max_hits,
+        add_to_context,
+    }))
+}
+
+// Parsea `grafico_texto <archivo> <x> <y> [hoja=<nombre>] [tipo=barras|linea] [max=30]`;
+// las comillas agrupan encabezados con espacios
+fn parse_text_chart_options(input: &str) -> Option<ExcelCommand> {
+    let args = split_quoted(input.strip_prefix("grafico_texto")?).ok()?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (positional, options) = split_key_values(&args);
+    let [file, x, y] = positional.as_slice() else {
+        return None;
+    };
+    Some(ExcelCommand::TextChart(TextChartOptions {
+        file: file.to_string(),
+        sheet: options.get("hoja").map(|s| s.to_string()),
+        x: x.to_string(),
+        y: y.to_string(),
+        kind: match options.get("tipo") {
+            Some(kind) => TextChartKind::parse(kind)?,
+            None => TextChartKind::Bars,
+        },
+        max_bars: match options.get("max") {
+            Some(n) => n.parse().ok().filter(|n| *n > 0)?,
+            None => text_chart::DEFAULT_MAX_BARS,
+        },
+    }))
+}
+
+// Parsea `validar <archivo> <rango> <tipo> <valores...> [mensaje=".."] [error=".."] [vacio=no]`;
+// las comillas agrupan textos con espacios y un valor que empieza por '=' es un rango
+fn parse_validation_options(input: &str) -> Option<ExcelCommand> {
+    let args = split_quoted(input.strip_prefix("validar")?).ok()?;
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    for arg in &args {
+        match arg.split_once('=') {
+            // `>=` es un operador, no una opción
+            Some((key, value)) if !key.is_empty() && key.chars().all(char::is_alphanumeric) => {
+                options.insert(key, value);
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    let (file, rest) = positional.split_first()?;
+    let (target, rule) = rest.split_first()?;
+    Some(ExcelCommand::Validate(ValidationOptions {
+        file: file.to_string(),
+        target: target.to_string(),
+        validation: Validation::parse(rule, &options).ok()?,
+    }))
+}
+
+// Primer argumento (entre comillas si tiene espacios) y el resto del texto sin tocar
+fn split_first_arg(input: &str) -> Option<(String, &str)> {
+    let (mut args, rest) = split_leading(input, 1).ok()?;
+    let first = args.pop().filter(|first| !first.is_empty())?;
+    Some((first, rest))
+}
+
+// Separa por espacios como una shell sencilla: las comillas dobles agrupan y se
+// quitan ("" es un argumento vacío), \" es una comilla literal y \ seguido de un
+// espacio, un espacio. Un argumento que empieza por comilla simple llega hasta la
+// siguiente y la conserva, para referencias como 'Hoja 1'!A1. Las demás barras
+// invertidas se dejan tal cual por las rutas de Windows. Todos los comandos se
+// separan así; unas comillas sin cerrar son un error de uso.
+fn split_quoted(input: &str) -> Result<Vec<String>> {
+    split_leading(input, usize::MAX).map(|(args, _)| args)
+}
+
+// Los `count` primeros argumentos, separados como en split_quoted, y el resto de
+// la línea sin tocar (una fórmula, un JSON o un valor con espacios)
+fn split_leading(input: &str, count: usize) -> Result<(Vec<String>, &str)> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    // El argumento actual existe aunque esté vacío (por unas comillas)
+    let mut started = false;
+    let mut quoted = false;
+    let mut single = false;
+    let mut chars = input.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|(_, next)| *next == '"' || next.is_whitespace()) => {
+                current.extend(chars.next().map(|(_, next)| next));
+                started = true;
+            }
+            '\'' if single => {
+                current.push(c);
+                single = false;
+            }
+            '\'' if !started && !quoted => {
+                current.push(c);
+                started = true;
+                single = true;
+            }
+            '"' | '“' | '”' if !single => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted && !single => {
+                if started {
+                    args.push(std::mem::take(&mut current));
+                    started = false;
+                    if args.len() == count {
+                        return Ok((args, &input[idx..]));
+                    }
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if quoted {
+        bail!("Faltan las comillas de cierre en \"{}; para una comilla literal escribe \\\"", current);
+    }
+    if single {
+        bail!("Falta la comilla simple de cierre en {}", current);
+    }
+    if started {
+        args.push(current);
+    }
+    Ok((args, ""))
+}
+
+// Parsea `cifrar_columna|descifrar_columna <archivo> <hoja> <col>[,<col>...] [salida=<archivo>]`
+fn parse_column_crypto_options(args: &[&str], decrypt: bool) -> Option<ExcelCommand> {
+    let (positional, options) = split_key_values(args);
+    if positional.len() != 3 {
+        return None;
+    }
+    Some(ExcelCommand::ColumnCrypto(ColumnCryptoOptions {
+        file: positional[0].to_string(),
+        sheet: positional[1].to_string(),
+        columns: positional[2].split(',').filter(|c| !c.is_empty()).map(str::to_string).collect(),
+        output: options.get("salida").map(|s| s.to_string()),
+        decrypt,
+    }))
+}
+
+// Parsea `anonimizar <archivo> <col>[,<col>...] [hoja=<nombre>] [modo=seudonimo|hash] [salida=<archivo>]`
+fn parse_anonymize_options(args: &[&str]) -> Option<ExcelCommand> {
+    let (positional, options) = split_key_values(args);
+    if positional.len() != 2 {
+        return None;
+    }
+    Some(ExcelCommand::Anonymize(AnonymizeOptions {
+        file: positional[0].to_string(),
+        columns: positional[1].split(',').filter(|c| !c.is_empty()).map(str::to_string).collect(),
+        sheet: options.get("hoja").map(|s| s.to_string()),
+        method: match options.get("modo") {
+            Some(method) => anonymize::Method::parse(method)?,
+            None => anonymize::Method::Pseudonym,
+        },
+        output: options.get("salida").map(|s| s.to_string()),
+    }))
+}
+
+// Parsea `convertir_fechas <archivo> <hoja> <col>[,<col>...] [orden=dma|mda] [salida=<archivo>]`
+fn parse_date_options(args: &[&str]) -> Option<ExcelCommand> {
+    let (positional, options) = split_key_values(args);
+    if positional.len() != 3 {
+        return None;
+    }
+    Some(ExcelCommand::ConvertDates(DateOptions {
+        file: positional[0].to_string(),
+        sheet: positional[1].to_string(),
+        columns: positional[2].split(',').filter(|c| !c.is_empty()).map(str::to_string).collect(),
+        order: match options.get("orden") {
+            Some(order) => DayOrder::parse(order)?,
+            None => DayOrder::DayMonth,
+        },
+        output: options.get("salida").map(|s| s.to_string()),
+    }))
+}
+
+// Separa argumentos posicionales de opciones clave=valor
+fn split_key_values<'a>(args: &[&'a str]) -> (Vec<&'a str>, HashMap<&'a str, &'a str>) {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    for arg in args {
+        match arg.split_once('=') {
+            Some((key, value)) => {
+                options.insert(key, value);
+            }
+            None => positional.push(*arg),
+        }
+    }
+    (positional, options)
+}
+
+// Quita `flag` de los argumentos; indica si estaba
+fn take_flag<'a>(args: Vec<&'a str>, flag: &str) -> (bool, Vec<&'a str>) {
+    let (flags, rest): (Vec<&str>, Vec<&str>) = args.into_iter().partition(|arg| *arg == flag);
+    (!flags.is_empty(), rest)
+}
+
+// Parsea `cruzar <hoja_a> <clave_a> <hoja_b> <clave_b> [columnas=<cols>] [nombre=<hoja>]`
+fn parse_join_options(args: &[&str]) -> Option<ExcelCommand> {
+    let (positional, options) = split_key_values(args);
+    let [left_sheet, left_key, right_sheet, right_key] = positional.as_slice() else {
+        return None;
+    };
+    Some(ExcelCommand::Join(JoinOptions {
+        left_sheet: left_sheet.to_string(),
+        left_key: left_key.to_string(),
+        right_sheet: right_sheet.to_string(),
+        right_key: right_key.to_string(),
+        columns: options
+            .get("columnas")
+            .map(|cols| cols.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect())
+            .unwrap_or_default(),
+        name: options.get("nombre").map(|s| s.to_string()),
+    }))
+}
+
+// Parsea `duplicados|eliminar_duplicados <archivo> [<col>,<col>...] [hoja=<hoja>]`,
+// con `salida=<archivo>` solo al eliminar
+fn parse_duplicate_options(args: &[&str], remove: bool) -> Option<DuplicateOptions> {
+    let (positional, options) = split_key_values(args);
+    let (file, columns) = match positional.as_slice() {
+        [file] => (file, Vec::new()),
+        [file, columns] => (file, columns.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect()),
+        _ => return None,
+    };
+    if !remove && options.contains_key("salida") {
+        return None;
+    }
+    Some(DuplicateOptions {
+        file: file.to_string(),
+        sheet: options.get("hoja").map(|s| s.to_string()),
+        columns,
+        output: options.get("salida").map(|s| s.to_string()),
+    })
+}
+
+// Parsea `top|bottom <archivo> <hoja> por=<col> [n=10] [agrupado_por=<col>] [salida=<archivo>]`
+fn parse_rank_options(args: &[&str], ascending: bool) -> Option<ExcelCommand> {
+    let (positional, options) = split_key_values(args);
+    if positional.len() != 2 {
+        return None;
+    }
+    Some(ExcelCommand::Rank(RankOptions {
+        file: positional[0].to_string(),
+        sheet: positional[1].to_string(),
+        by: options.get("por")?.to_string(),
+        n: match options.get("n") {
+            Some(n) => n.parse().ok()?,
+            None => 10,
+        },
+        group_by: options.get("agrupado_por").map(|s| s.to_string()),
+        ascending,
+        output: options.get("salida").map(|s| s.to_string()),
+    }))
+}
+
+// Parsea `pareto <archivo> <hoja> por=<col> [agrupado_por=<col>] [corte=80] [grafico=<tipo>] [salida=<archivo>]`
+fn parse_pareto_options(args: &[&str]) -> Option<ExcelCommand> {
+    let (positional, options) = split_key_values(args);
+    if positional.len() != 2 {
+        return None;
+    }
+    Some(ExcelCommand::Pareto(ParetoOptions {
+        file: positional[0].to_string(),
+        sheet: positional[1].to_string(),
+        by: options.get("por")?.to_string(),
+        group_by: options.get("agrupado_por").map(|s| s.to_string()),
+        cut: match options.get("corte") {
+            Some(cut) => cut.trim_end_matches('%').parse().ok()?,
+            None => 80.0,
+        },
+        chart: match options.get("grafico") {
+            Some(kind) => Some(ChartKind::parse(kind)?),
+            None => None,
+        },
+        output: options.get("salida").map(|s| s.to_string()),
+    }))
+}
+
+// Calcula el análisis de Pareto, lo muestra y opcionalmente lo guarda; devuelve el resumen
+fn run_pareto(options: &ParetoOptions) -> Result<String> {
+    let data = read_excel_file(&options.file)?;
+    let sheet = data
+        .require_sheet(&options.file, &options.sheet)?;
+    let result = analysis::pareto(sheet, options)?;
+    let rows = result.sheet.display_rows();
+    table::print_table(&rows[0], &rows[1..]);
+    println!("{}", result.summary);
+    if let Some(output) = &options.output {
+        excel::write_sheet_to_file(output, result.sheet)?;
+        println!("{}", tr!("✅ Resultado guardado en {}", "✅ Result saved to {}", output));
+    } else if options.chart.is_some() {
+        println!(
+            "{}",
+            tr!(
+                "ℹ️  El gráfico solo se genera al guardar con salida=<archivo.xlsx>",
+                "ℹ️  The chart is only generated when saving with output=<file.xlsx>"
+            )
+        );
+    }
+    Ok(result.summary)
+}
+
+// Parsea `cohortes <archivo> fecha_alta=<col> fecha_evento=<col> [valor=<col>] [cliente=<col>] ...`
+fn parse_cohort_options(args: &[&str]) -> Option<ExcelCommand> {
+    let (positional, options) = split_key_values(args);
+    if positional.len() != 1 {
+        return None;
+    }
+    Some(ExcelCommand::Cohorts(CohortOptions {
+        file: positional[0].to_string(),
+        sheet: options.get("hoja").map(|s| s.to_string()),
+        signup: options.get("fecha_alta")?.to_string(),
+        event: options.get("fecha_evento")?.to_string(),
+        value: options.get("valor").map(|s| s.to_string()),
+        customer: options.get("cliente").map(|s| s.to_string()),
+        relative: options
+            .get("relativo")
+            .is_some_and(|v| matches!(*v, "si" | "sí" | "yes" | "true" | "1")),
+        output: options.get("salida").map(|s| s.to_string()),
+    }))
+}
+
+// Parsea `estadisticas <archivo> <col>[,<col>...] [hoja=<nombre>] [salida=<archivo.xlsx>]`
+fn parse_stats_options(args: &[&str]) -> Option<ExcelCommand> {
+    let (positional, options) = split_key_values(args);
+    let [file, columns] = positional.as_slice() else {
+        return None;
+    };
+    let columns: Vec<String> = columns.split(',').filter(|c| !c.is_empty()).map(str::to_string).collect();
+    if columns.is_empty() {
+        return None;
+    }
+    Some(ExcelCommand::Stats(StatsOptions {
+        file: file.to_string(),
+        sheet: options.get("hoja").map(|s| s.to_string()),
+        columns,
+        output: options.get("salida").map(|s| s.to_string()),
+    }))
+}
+
+// Calcula y muestra las estadísticas, y las guarda como hoja con salida=; devuelve el resumen
+fn run_stats(options: &StatsOptions) -> Result<String> {
+    let data = read_excel_file(&options.file)?;
+    let sheet = match &options.sheet {
+        Some(name) => data.require_sheet(&options.file, name)?,
+        None => data.sheets.first().context("El libro no tiene hojas")?,
+    };
+    let result = analysis::statistics(sheet, options)?;
+    let rows = result.sheet.display_rows();
+    table::print_table(&rows[0], &rows[1..]);
+    if let Some(output) = &options.output {
+        let output = outputs::target(output)?;
+        excel::write_sheet_to_file(&output, result.sheet)?;
+        println!("{}", tr!("✅ Estadísticas guardadas en {}", "✅ Statistics saved to {}", output));
+    }
+    Ok(result.summary)
+}
+
+// Genera la hoja de cohortes con escala de colores en un archivo nuevo;
+// devuelve el resumen y el archivo generado
+fn run_cohorts(options: &CohortOptions) -> Result<(String, String)> {
+    let data = read_excel_file(&options.file)?;
+    let sheet = match &options.sheet {
+        Some(name) => data
+            .require_sheet(&options.file, name)?,
+        None => data.sheets.first().context("El libro no tiene hojas")?,
+    };
+    let result = analysis::cohorts(sheet, options)?;
+    let rows = result.sheet.display_rows();
+    table::print_table(&rows[0], &rows[1..]);
+    println!("{}", result.summary);
+
+    let output = match &options.output {
+        Some(output) => output.clone(),
+        None => {
+            let stem = Path::new(&options.file)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("cohortes");
+            Path::new(&options.file)
+                .with_file_name(format!("{}_cohortes.xlsx", stem))
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+    let sheet_name = result.sheet.name.clone();
+    excel::write_sheet_to_file(&output, result.sheet)?;
+    conditional_format::apply(&ConditionalFormatOptions {
+        file: output.clone(),
+        sheet: sheet_name,
+        range: result.matrix_range,
+        rule: ConditionalRule::ColorScale {
+            low: "FFFFFF".to_string(),
+            mid: None,
+            high: "63BE7B".to_string(),
+        },
+    })?;
+    println!("{}", tr!("✅ Matriz de cohortes guardada en {}", "✅ Cohort matrix saved to {}", output));
+    Ok((result.summary, output))
+}
+
+// Calcula el ranking, lo muestra y opcionalmente lo guarda como hoja
+fn run_rank(options: &RankOptions) -> Result<()> {
+    let data = read_excel_file(&options.file)?;
+    let sheet = data
+        .require_sheet(&options.file, &options.sheet)?;
+    let result = analysis::rank(sheet, options)?;
+    let rows = result.text_rows();
+    table::print_table(&rows[0], &rows[1..]);
+    if let Some(output) = &options.output {
+        excel::write_sheet_to_file(output, result)?;
+        println!("{}", tr!("✅ Resultado guardado en {}", "✅ Result saved to {}", output));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(input: &str) -> Vec<String> {
+        split_quoted(input).unwrap()
+    }
+
+    #[test]
+    fn every_documented_command_has_a_handler() {
+        for name in commands::names() {
+            assert!(commands::route(name).is_some(), "{} no llega a ningún comando", name);
+        }
+        // Los ejemplos de la ayuda llegan a su comando en lugar de al modelo
+        for example in commands::examples() {
+            let name = example.split_whitespace().next().unwrap();
+            match commands::route(name) {
+                Some(Route::Excel) => assert!(parse_excel_command(example).is_some(), "{}", example),
+                Some(_) => {}
+                None => panic!("{} no llega a ningún comando", example),
+            }
+        }
+    }
+
+    #[test]
+    fn routes_ignore_the_case_of_the_command() {
+        let route = |input: &str| {
+            let input = i18n::normalize_command(input);
+            commands::route(input.split_whitespace().next().unwrap())
+        };
+        assert_eq!(route("SALIR"), Some(Route::Exit));
+        assert_eq!(route("Ayuda"), Some(Route::Help));
+        assert_eq!(route("usage"), Some(Route::Usage));
+        assert_eq!(route("leer_excel"), Some(Route::Excel));
+        assert_eq!(route("cuántas"), None);
+        // También llega al comando de archivos, con los argumentos tal cual
+        let input = i18n::normalize_command("LEER_EXCEL Datos.xlsx");
+        assert_eq!(input, "leer_excel Datos.xlsx");
+        assert!(matches!(parse_excel_command(&input), Some(ExcelCommand::ReadFile(file, ..)) if file == "Datos.xlsx"));
+        assert!(attempted_command(&i18n::normalize_command("LEER_EXCEL")).is_some());
+        assert_eq!(i18n::normalize_command("Agente resume la hoja"), "agente resume la hoja");
+        assert_eq!(i18n::normalize_command("Cuántas ventas hay"), "Cuántas ventas hay");
+    }
+
+    #[test]
+    fn words_are_split_on_whitespace() {
+        assert_eq!(split("leer_excel  datos.xlsx\t--stream"), ["leer_excel", "datos.xlsx", "--stream"]);
+        assert!(split("   ").is_empty());
+    }
+
+    #[test]
+    fn double_quotes_group_and_are_removed() {
+        assert_eq!(split(r#"leer_excel "Ventas 2024.xlsx""#), ["leer_excel", "Ventas 2024.xlsx"]);
+        assert_eq!(split(r#"nota f.xlsx B2 """#), ["nota", "f.xlsx", "B2", ""]);
+        assert_eq!(split("buscar f.xlsx “dos palabras”"), ["buscar", "f.xlsx", "dos palabras"]);
+        assert_eq!(split(r#"a "x"y"#), ["a", "xy"]);
+    }
+
+    #[test]
+    fn backslashes_escape_quotes_and_spaces_only() {
+        assert_eq!(split(r#"nota f.xlsx B2 "dijo \"hola\"""#), ["nota", "f.xlsx", "B2", r#"dijo "hola""#]);
+        assert_eq!(split(r"leer_excel Ventas\ 2024.xlsx"), ["leer_excel", "Ventas 2024.xlsx"]);
+        assert_eq!(split(r"leer_excel C:\datos\v.xlsx"), ["leer_excel", r"C:\datos\v.xlsx"]);
+    }
+
+    #[test]
+    fn single_quotes_keep_sheet_references_whole() {
+        assert_eq!(split("rellenar f.xlsx 'Hoja 1'!A2:A9 1.."), ["rellenar", "f.xlsx", "'Hoja 1'!A2:A9", "1.."]);
+        // Dentro de una palabra es un apóstrofo
+        assert_eq!(split("buscar f.xlsx O'Brien"), ["buscar", "f.xlsx", "O'Brien"]);
+    }
+
+    #[test]
+    fn unbalanced_quotes_are_an_error() {
+        assert!(split_quoted(r#"leer_excel "Ventas 2024.xlsx"#).is_err());
+        assert!(split_quoted("rellenar f.xlsx 'Hoja 1!A2 1..").is_err());
+        assert!(split_quoted(r#"nota f.xlsx B2 \""#).is_ok());
+    }
+
+    #[test]
+    fn the_rest_of_the_line_is_left_untouched() {
+        let (args, rest) = split_leading(r#"escribir_formula "Ventas 2024.xlsx" C2 =SI(A2="x";1;0)"#, 3).unwrap();
+        assert_eq!(args, ["escribir_formula", "Ventas 2024.xlsx", "C2"]);
+        assert_eq!(rest, r#" =SI(A2="x";1;0)"#);
+        assert_eq!(split_first_arg(r#" "a b" {"Hoja": [[1]]}"#), Some(("a b".to_string(), r#" {"Hoja": [[1]]}"#)));
+        assert_eq!(split_target(" 'Hoja 1'!B2 =A1*2"), Some(("'Hoja 1'!B2".to_string(), " =A1*2")));
+        assert_eq!(split_target(" B2"), None);
+    }
+}
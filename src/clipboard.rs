@@ -0,0 +1,241 @@
+// Portapapeles del sistema (`copiar` y `pegar_datos`), para pasar datos entre el
+// agente y Excel con copiar y pegar. Las tablas se copian como texto separado por
+// tabuladores, que Excel reparte en celdas al pegar, y lo que se copia de Excel
+// llega igual. Se usan las herramientas del sistema: pbcopy/pbpaste en macOS,
+// PowerShell en Windows y wl-copy, xclip o xsel en Linux; IAGENT_CLIPBOARD_COPY
+// e IAGENT_CLIPBOARD_PASTE las sustituyen por otro comando.
+use crate::convert;
+use crate::excel::SheetData;
+use crate::extract::ExtractedTable;
+use crate::profiles;
+use crate::settings;
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub const DEFAULT_SHEET: &str = "Pegado";
+
+// Qué se copia: lo último que se mostró, o una cosa concreta
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CopySource {
+    Last,
+    Answer,
+    // La última tabla mostrada en la terminal
+    Table,
+}
+
+impl CopySource {
+    pub fn parse(value: &str) -> Option<CopySource> {
+        match value {
+            "respuesta" => Some(CopySource::Answer),
+            "tabla" => Some(CopySource::Table),
+            _ => None,
+        }
+    }
+}
+
+// Lo último que ha mostrado un agente, que guarda en sus `Settings`
+#[derive(Default)]
+pub struct Shown {
+    answer: Option<String>,
+    // Encabezados y filas tal como se mostraron en la terminal
+    table: Option<Vec<Vec<String>>>,
+    table_is_last: bool,
+}
+
+pub fn remember_answer(text: &str) {
+    if let Ok(mut shown) = settings::current().shown().lock() {
+        shown.answer = Some(text.to_string());
+        shown.table_is_last = false;
+    }
+}
+
+pub fn remember_table(headers: &[String], rows: &[Vec<String>]) {
+    if let Ok(mut shown) = settings::current().shown().lock() {
+        shown.table = Some(std::iter::once(headers.to_vec()).chain(rows.iter().cloned()).collect());
+        shown.table_is_last = true;
+    }
+}
+
+// Texto para Excel: tabuladores y saltos de línea, con comillas en las celdas
+// que los contienen
+pub fn to_tsv(rows: &[Vec<String>]) -> String {
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| {
+                    if cell.contains(['\t', '\n', '\r', '"']) {
+                        format!("\"{}\"", cell.replace('"', "\"\""))
+                    } else {
+                        cell.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\t")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Copia la respuesta o la tabla pedida; devuelve qué se ha copiado
+pub fn copy(source: CopySource) -> Result<String> {
+    let settings = settings::current();
+    let (text, description) = {
+        let shown = settings.shown().lock().map_err(|_| anyhow::anyhow!("Portapapeles no disponible"))?;
+        if shown.answer.is_none() && shown.table.is_none() && source == CopySource::Last {
+            bail!("Todavía no hay ninguna respuesta ni tabla que copiar");
+        }
+        let table = match source {
+            CopySource::Table => true,
+            CopySource::Answer => false,
+            CopySource::Last => shown.table_is_last || shown.answer.is_none(),
+        };
+        if table {
+            let rows = shown.table.as_ref().context("Todavía no se ha mostrado ninguna tabla")?;
+            let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+            (
+                to_tsv(rows),
+                format!("la última tabla ({} filas x {} columnas)", rows.len().saturating_sub(1), columns),
+            )
+        } else {
+            let answer = shown.answer.as_ref().context("Todavía no hay ninguna respuesta del modelo")?;
+            (answer.clone(), format!("la última respuesta ({} caracteres)", answer.chars().count()))
+        }
+    };
+    write_text(&text)?;
+    Ok(description)
+}
+
+// Hoja con el contenido del portapapeles, con los tipos deducidos como al leer un CSV
+pub fn paste_sheet(name: &str) -> Result<(SheetData, String)> {
+    let text = read_text()?;
+    let text = text.trim_end_matches(['\r', '\n']);
+    if text.trim().is_empty() {
+        bail!("El portapapeles está vacío");
+    }
+    let table = ExtractedTable {
+        source: "portapapeles".to_string(),
+        rows: convert::parse_delimited(text, '\t'),
+    };
+    Ok((table.to_sheet(name), table.describe()))
+}
+
+// (programa, argumentos) en el orden en que se prueban
+fn copy_commands() -> Vec<(String, Vec<String>)> {
+    if let Ok(command) = profiles::var("IAGENT_CLIPBOARD_COPY") {
+        return vec![shell(&command)];
+    }
+    let owned = |program: &str, args: &[&str]| (program.to_string(), args.iter().map(|a| a.to_string()).collect());
+    if cfg!(target_os = "macos") {
+        vec![owned("pbcopy", &[])]
+    } else if cfg!(windows) {
+        vec![owned(
+            "powershell",
+            &["-NoProfile", "-Command", "[Console]::InputEncoding = [Text.Encoding]::UTF8; Set-Clipboard -Value ([Console]::In.ReadToEnd())"],
+        )]
+    } else {
+        let mut commands = vec![owned("xclip", &["-selection", "clipboard"]), owned("xsel", &["--clipboard", "--input"])];
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.insert(0, owned("wl-copy", &[]));
+        }
+        commands
+    }
+}
+
+fn paste_commands() -> Vec<(String, Vec<String>)> {
+    if let Ok(command) = profiles::var("IAGENT_CLIPBOARD_PASTE") {
+        return vec![shell(&command)];
+    }
+    let owned = |program: &str, args: &[&str]| (program.to_string(), args.iter().map(|a| a.to_string()).collect());
+    if cfg!(target_os = "macos") {
+        vec![owned("pbpaste", &[])]
+    } else if cfg!(windows) {
+        vec![owned(
+            "powershell",
+            &["-NoProfile", "-Command", "[Console]::OutputEncoding = [Text.Encoding]::UTF8; Get-Clipboard -Raw"],
+        )]
+    } else {
+        let mut commands = vec![owned("xclip", &["-selection", "clipboard", "-o"]), owned("xsel", &["--clipboard", "--output"])];
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.insert(0, owned("wl-paste", &["--no-newline"]));
+        }
+        commands
+    }
+}
+
+fn shell(command: &str) -> (String, Vec<String>) {
+    if cfg!(windows) {
+        ("cmd".to_string(), vec!["/C".to_string(), command.to_string()])
+    } else {
+        ("sh".to_string(), vec!["-c".to_string(), command.to_string()])
+    }
+}
+
+fn write_text(text: &str) -> Result<()> {
+    for (program, args) in copy_commands() {
+        // Si la herramienta no está instalada se prueba la siguiente. xclip y
+        // wl-copy se quedan en segundo plano sirviendo el texto: con su salida
+        // conectada la espera no terminaría
+        let Ok(mut child) = Command::new(&program).args(&args).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes()).context(format!("No se pudo enviar el texto a {}", program))?;
+        }
+        let status = child.wait().context(format!("{} no terminó", program))?;
+        if !status.success() {
+            bail!("{} falló ({})", program, status);
+        }
+        return Ok(());
+    }
+    bail!("{}", missing_tool_message())
+}
+
+fn read_text() -> Result<String> {
+    for (program, args) in paste_commands() {
+        let Ok(output) = Command::new(&program).args(&args).stdin(Stdio::null()).output() else {
+            continue;
+        };
+        if !output.status.success() {
+            bail!("{} falló: {}", program, String::from_utf8_lossy(&output.stderr).trim());
+        }
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    bail!("{}", missing_tool_message())
+}
+
+fn missing_tool_message() -> &'static str {
+    if cfg!(unix) && !cfg!(target_os = "macos") {
+        "No se encontró ninguna herramienta de portapapeles: instala wl-clipboard, xclip o xsel, o define IAGENT_CLIPBOARD_COPY / IAGENT_CLIPBOARD_PASTE"
+    } else {
+        "No se pudo acceder al portapapeles del sistema; define IAGENT_CLIPBOARD_COPY / IAGENT_CLIPBOARD_PASTE"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::provider::MockProvider;
+    use crate::settings::Settings;
+    use std::sync::Arc;
+
+    #[test]
+    fn each_agent_remembers_what_it_showed() {
+        let agent = || {
+            let config = Config::offline(Arc::new(MockProvider::new(Vec::new())), &crate::paths::test_dir("clipboard"));
+            Settings::new(&config).unwrap()
+        };
+        let (first, second) = (agent(), agent());
+        first.sync_scope(|| remember_answer("respuesta del primero"));
+        second.sync_scope(|| remember_table(&["Zona".to_string()], &[vec!["Norte".to_string()]]));
+        let first_shown = first.shown().lock().unwrap();
+        assert_eq!(first_shown.answer.as_deref(), Some("respuesta del primero"));
+        assert!(first_shown.table.is_none());
+        let second_shown = second.shown().lock().unwrap();
+        assert!(second_shown.answer.is_none());
+        assert!(second_shown.table_is_last);
+    }
+}
@@ -0,0 +1,812 @@
+// Registro de los comandos de la sesión: los nombres que documenta cada entrada,
+// sus argumentos, la descripción en los dos idiomas de la interfaz y ejemplos.
+// De aquí salen la ayuda (`ayuda`), la página de cada comando (`ayuda <comando>`),
+// el uso que se muestra cuando faltan o sobran argumentos, los nombres que se
+// corrigen si están mal escritos y a qué manejador va cada comando; un comando
+// nuevo se documenta solo aquí.
+use crate::i18n::{self, Lang, Msg};
+use std::path::Path;
+
+pub struct CommandHelp {
+    // Comandos, en español, que documenta la entrada
+    pub names: &'static [&'static str],
+    pub route: Route,
+    // (español, inglés)
+    pub usage: (&'static str, &'static str),
+    pub description: (&'static str, &'static str),
+    // Entradas completas con los nombres en español; en inglés se muestran traducidas
+    pub examples: &'static [&'static str],
+}
+
+// Quién atiende los comandos de una entrada: la sesión tiene un manejador para
+// cada uno de los suyos; los de archivos (Excel) los interpreta parse_excel_command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Route {
+    Exit,
+    Usage,
+    Page,
+    Macro,
+    SendResult,
+    Copy,
+    Model,
+    Profile,
+    CompareModels,
+    Help,
+    Cache,
+    Doctor,
+    Tour,
+    Performance,
+    Manifest,
+    ExportSession,
+    Context,
+    Checkpoint,
+    Rewind,
+    Agent,
+    Refresh,
+    Jobs,
+    Resume,
+    Excel,
+}
+
+impl CommandHelp {
+    pub fn usage(&self) -> &'static str {
+        match i18n::lang() {
+            Lang::Es => self.usage.0,
+            Lang::En => self.usage.1,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match i18n::lang() {
+            Lang::Es => self.description.0,
+            Lang::En => self.description.1,
+        }
+    }
+}
+
+// Una línea por entrada: "  uso - descripción"
+pub fn help_lines() -> Vec<String> {
+    COMMANDS.iter().map(|entry| format!("  {} - {}", entry.usage(), entry.description())).collect()
+}
+
+// Líneas de uso de un comando (por su nombre en español), en el idioma de la interfaz
+pub fn usage(command: &str) -> Vec<&'static str> {
+    entries(command).map(CommandHelp::usage).collect()
+}
+
+// Nombres en español de todos los comandos documentados
+pub fn names() -> impl Iterator<Item = &'static str> {
+    COMMANDS.iter().flat_map(|entry| entry.names.iter().copied())
+}
+
+// El comando al que va una primera palabra ya en minúsculas; `None` si no es
+// ninguno de los documentados
+pub fn route(name: &str) -> Option<Route> {
+    COMMANDS.iter().find(|entry| entry.names.contains(&name)).map(|entry| entry.route)
+}
+
+// Nombres de los comandos de archivos, los que interpreta parse_excel_command
+pub fn excel_names() -> impl Iterator<Item = &'static str> {
+    COMMANDS.iter().filter(|entry| entry.route == Route::Excel).flat_map(|entry| entry.names.iter().copied())
+}
+
+// Ejemplos de todas las entradas, con los nombres en español (las pruebas los interpretan todos)
+#[cfg(test)]
+pub fn examples() -> impl Iterator<Item = &'static str> {
+    COMMANDS.iter().flat_map(|entry| entry.examples.iter().copied())
+}
+
+// Palabras de los argumentos que admiten texto libre de varias palabras
+const FREE_TEXT: &[&str] = &["texto", "tarea", "pregunta", "instrucción", "plantilla con", "valor", "argumentos"];
+
+// Si `args` (sin el nombre) encaja con el uso documentado de `command`: cuántos
+// argumentos sueltos lleva y, si el primero es un archivo, que lo parezca. Las
+// opciones (clave=valor, --opción) pueden ir en cualquier orden y no cuentan.
+// Es lo que decide si una entrada con un nombre mal escrito es un comando o una
+// pregunta ("busca los clientes con deuda en v.xlsx").
+pub fn accepts(command: &str, args: &[String]) -> bool {
+    let positional: Vec<&str> = args.iter().map(String::as_str).filter(|arg| !is_option(arg)).collect();
+    entries(command).any(|entry| {
+        let alternatives: Vec<&str> = entry.usage.0.split(" | ").collect();
+        let shape = alternatives
+            .iter()
+            .find(|alternative| alternative.split(' ').next().is_some_and(|name| name.split('|').any(|n| n == command)))
+            .unwrap_or(&alternatives[0]);
+        let placeholders = shape_placeholders(shape);
+        let required = placeholders.iter().filter(|(optional, _)| !optional).count();
+        let variadic = placeholders.iter().any(|(_, text)| text.contains("...") || FREE_TEXT.iter().any(|word| text.contains(word)));
+        if positional.len() < required || (!variadic && positional.len() > placeholders.len()) {
+            return false;
+        }
+        // El primer argumento, si es obligatorio y es un archivo, tiene que parecer una ruta
+        match (placeholders.first(), positional.first()) {
+            (Some((false, text)), Some(arg)) if is_file_placeholder(text) => looks_like_argument(arg),
+            _ => true,
+        }
+    })
+}
+
+// Una ruta, una opción o un rango, no una palabra de una pregunta
+pub fn looks_like_argument(arg: &str) -> bool {
+    arg.contains(['=', '!', '*', '/', '\\'])
+        || arg.starts_with("--")
+        || Path::new(arg)
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| !e.is_empty() && e.len() <= 5 && e.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+// clave=valor o --opción; "=B2*C2" es una fórmula, no una opción
+fn is_option(arg: &str) -> bool {
+    arg.starts_with("--")
+        || arg.split_once('=').is_some_and(|(key, _)| !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_'))
+}
+
+fn is_file_placeholder(text: &str) -> bool {
+    ["archivo", "libro", ".xlsx", ".json"].iter().any(|word| text.contains(word))
+}
+
+// (opcional, texto) de cada argumento suelto de una línea de uso, sin el nombre,
+// las opciones ni lo que va entre paréntesis ("doctor (o ping)")
+fn shape_placeholders(usage: &str) -> Vec<(bool, String)> {
+    let mut tokens = Vec::new();
+    let (mut current, mut depth, mut quoted) = (String::new(), 0i32, false);
+    for c in usage.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '[' | '<' | '{' | '(' if !quoted => depth += 1,
+            ']' | '>' | '}' | ')' if !quoted => depth -= 1,
+            ' ' if depth <= 0 && !quoted => {
+                tokens.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    tokens.push(current);
+    tokens
+        .into_iter()
+        .skip(1)
+        .filter(|token| !token.is_empty() && !token.starts_with('(') && !token.starts_with("[--") && !token.starts_with("--"))
+        .filter(|token| !is_option(token.trim_start_matches('[')))
+        .map(|token| (token.starts_with('[') && token.ends_with(']'), token))
+        .collect()
+}
+
+fn entries(command: &str) -> impl Iterator<Item = &'static CommandHelp> + '_ {
+    COMMANDS.iter().filter(move |entry| entry.names.contains(&command))
+}
+
+// Página de `ayuda <comando>` (en español o inglés): uso y descripción de cada
+// entrada del comando, sus nombres en los dos idiomas y los ejemplos. None si no
+// es un comando.
+pub fn detail(command: &str) -> Option<String> {
+    let name = i18n::spanish_command(command.trim());
+    let entries: Vec<&CommandHelp> = entries(name).collect();
+    if entries.is_empty() {
+        return None;
+    }
+    let mut page = String::new();
+    for entry in &entries {
+        page.push_str(&format!("{}\n  {}\n", entry.usage(), entry.description()));
+    }
+    let mut names = vec![name];
+    names.extend(i18n::english_names(name));
+    page.push_str(&format!("{} {}\n", i18n::text(Msg::HelpNames), names.join(", ")));
+    let examples: Vec<String> = entries
+        .iter()
+        .flat_map(|entry| entry.examples.iter())
+        .map(|example| match i18n::lang() {
+            Lang::Es => example.to_string(),
+            Lang::En => i18n::english_command(example),
+        })
+        .collect();
+    if !examples.is_empty() {
+        page.push_str(i18n::text(Msg::HelpExamples));
+        for example in examples {
+            page.push_str(&format!("\n  {}", example));
+        }
+    }
+    Some(page.trim_end().to_string())
+}
+
+const COMMANDS: &[CommandHelp] = &[
+    CommandHelp {
+        names: &["leer_excel"],
+        route: Route::Excel,
+        usage: ("leer_excel <archivo.xlsx> [--stream] [--evaluar] [--formulas]", "read_excel <file.xlsx> [--stream] [--evaluate] [--formulas]"),
+        description: ("Lee un archivo Excel (los archivos grandes se leen por streaming; --evaluar recalcula las fórmulas habituales; --formulas pasa al modelo el texto de las fórmulas)", "Read an Excel file (large files are streamed; --evaluate recalculates common formulas; --formulas gives the model the text of the formulas)"),
+        examples: &["leer_excel ventas.xlsx", "leer_excel \"Informe 2024.xlsx\" --formulas"],
+    },
+    CommandHelp {
+        names: &["leer_varios"],
+        route: Route::Excel,
+        usage: ("leer_varios <patrón>", "read_many <pattern>"),
+        description: ("Lee en paralelo todos los archivos que coinciden (p. ej. ventas_*.xlsx)", "Read every matching file in parallel (e.g. sales_*.xlsx)"),
+        examples: &["leer_varios ventas_*.xlsx"],
+    },
+    CommandHelp {
+        names: &["mostrar"],
+        route: Route::Excel,
+        usage: ("mostrar [hoja] [n]", "show [sheet] [n]"),
+        description: ("Muestra las primeras n filas de una hoja de los libros leídos, o la hoja entera por páginas", "Show the first n rows of a sheet from the files read, or the whole sheet page by page"),
+        examples: &["mostrar Ventas 20", "mostrar"],
+    },
+    CommandHelp {
+        names: &["siguiente", "anterior", "pagina", "página"],
+        route: Route::Page,
+        usage: ("siguiente | anterior | pagina <n>", "next | previous | page <n>"),
+        description: ("Recorre las páginas del último resultado o de la última hoja mostrada", "Move through the pages of the last result or the last sheet shown"),
+        examples: &["siguiente", "pagina 3"],
+    },
+    CommandHelp {
+        names: &["enviar_resultado"],
+        route: Route::SendResult,
+        usage: ("enviar_resultado", "send_result"),
+        description: ("Añade al contexto del modelo el último resultado completo, no solo la página visible", "Add the whole last result to the model context, not just the visible page"),
+        examples: &[],
+    },
+    CommandHelp {
+        names: &["crear_excel"],
+        route: Route::Excel,
+        usage: ("crear_excel <archivo.xlsx>", "create_excel <file.xlsx>"),
+        description: ("Crea un nuevo archivo Excel", "Create a new Excel file"),
+        examples: &["crear_excel presupuesto.xlsx"],
+    },
+    CommandHelp {
+        names: &["escribir_excel"],
+        route: Route::Excel,
+        usage: ("escribir_excel <archivo.xlsx> [--reescribir] [hoja=<nombre>] <a,b;c,d> | {\"Hoja\": [[..]], ...}", "write_excel <file.xlsx> [--rewrite] [sheet=<name>] <a,b;c,d> | {\"Sheet\": [[..]], ...}"),
+        description: ("Escribe datos en un archivo Excel; con hoja= o un JSON por hoja escribe en esas hojas y conserva las demás, con sus fórmulas. Si el archivo perdería fórmulas o gráficos, solo se escribe con --reescribir", "Write data to an Excel file; with sheet= or one JSON entry per sheet it writes those sheets and keeps the others, with their formulas. If the file would lose formulas or charts, it is only written with --rewrite"),
+        examples: &["escribir_excel notas.xlsx hoja=Resumen Mes,Total;Enero,1200"],
+    },
+    CommandHelp {
+        names: &["escribir_rango"],
+        route: Route::Excel,
+        usage: ("escribir_rango <archivo.xlsx> <nombre|Hoja!A1:B2> <v1,v2;v3,v4> [--sobrescribir|desplazar=abajo|derecha]", "write_range <file.xlsx> <name|Sheet!A1:B2> <v1,v2;v3,v4> [--overwrite|shift=down|right]"),
+        description: ("Escribe en un nombre definido o rango de una plantilla conservando su formato (',' separa celdas y ';' filas); no pisa celdas con datos salvo con --sobrescribir", "Write into a defined name or range of a template keeping its formatting (',' separates cells and ';' rows); cells with data are not replaced unless --overwrite is given"),
+        examples: &["escribir_rango factura.xlsx TotalFactura 1250", "escribir_rango plantilla.xlsx Datos!B2:C3 1,2;3,4 desplazar=abajo"],
+    },
+    CommandHelp {
+        names: &["escribir_formula"],
+        route: Route::Excel,
+        usage: ("escribir_formula <archivo.xlsx> <Hoja!C2[:C20]|nombre> <=fórmula> [--sobrescribir]", "write_formula <file.xlsx> <Sheet!C2[:C20]|name> <=formula> [--overwrite]"),
+        description: ("Comprueba una fórmula (paréntesis, referencias, funciones en inglés) y la escribe, rellenándola en el rango como al arrastrarla", "Check a formula (parentheses, references, English function names) and write it, filling it down the range like dragging it in Excel"),
+        examples: &["escribir_formula ventas.xlsx Ventas!D2:D50 =B2*C2"],
+    },
+    CommandHelp {
+        names: &["formulas"],
+        route: Route::Excel,
+        usage: ("formulas <archivo.xlsx> <hoja>", "formulas <file.xlsx> <sheet>"),
+        description: ("Lista las fórmulas de una hoja con su celda y el valor guardado", "List the formulas of a sheet with their cell and stored value"),
+        examples: &["formulas ventas.xlsx Ventas"],
+    },
+    CommandHelp {
+        names: &["explicar"],
+        route: Route::Excel,
+        usage: ("explicar <archivo.xlsx>", "explain <file.xlsx>"),
+        description: ("Informe de estructura del libro: hojas, dimensiones, encabezados y tipos, fórmulas, nombres definidos y vínculos a otros libros; se añade al contexto", "Structural report of the workbook: sheets, dimensions, headers and types, formulas, defined names and links to other workbooks; added to the context"),
+        examples: &["explicar ventas.xlsx", "explicar \"Presupuesto 2025.xlsx\""],
+    },
+    CommandHelp {
+        names: &["escribir_enlace"],
+        route: Route::Excel,
+        usage: ("escribir_enlace <archivo.xlsx> <celda|Hoja!B2> <url|archivo|#Hoja!A1> [texto]", "write_link <file.xlsx> <cell|Sheet!B2> <url|file|#Sheet!A1> [text]"),
+        description: ("Escribe un hipervínculo en una celda; el texto visible es opcional", "Write a hyperlink into a cell; the visible text is optional"),
+        examples: &["escribir_enlace informe.xlsx Resumen!B2 https://empresa.com/ventas Panel"],
+    },
+    CommandHelp {
+        names: &["nota"],
+        route: Route::Excel,
+        usage: ("nota <archivo.xlsx> <celda|Hoja!B2> [\"texto\"|--quitar]", "note <file.xlsx> <cell|Sheet!B2> [\"text\"|--remove]"),
+        description: ("Muestra, escribe o quita la nota (comentario) de una celda, p. ej. para explicar cómo se calculó un valor", "Show, write or remove the note (comment) of a cell, for instance to explain how a value was computed"),
+        examples: &["nota ventas.xlsx Ventas!E2 \"Suma de Q1 a Q4\"", "nota ventas.xlsx Ventas!E2 --quitar"],
+    },
+    CommandHelp {
+        names: &["agrupar"],
+        route: Route::Excel,
+        usage: ("agrupar <archivo.xlsx> [Hoja!]<filas 5-20|columnas C-F> [--contraer] [resumen=arriba|abajo]", "group <file.xlsx> [Sheet!]<rows 5-20|columns C-F> [--collapse] [summary=above|below]"),
+        description: ("Agrupa filas o columnas en una sección plegable bajo su fila o columna de resumen", "Group rows or columns into a collapsible section under their summary row or column"),
+        examples: &["agrupar ventas.xlsx Detalle!filas 5-20 --contraer"],
+    },
+    CommandHelp {
+        names: &["desagrupar"],
+        route: Route::Excel,
+        usage: ("desagrupar <archivo.xlsx> [Hoja!]<filas 5-20|columnas C-F>", "ungroup <file.xlsx> [Sheet!]<rows 5-20|columns C-F>"),
+        description: ("Quita un nivel de agrupación de esas filas o columnas", "Remove one grouping level from those rows or columns"),
+        examples: &["desagrupar ventas.xlsx Detalle!columnas C-F"],
+    },
+    CommandHelp {
+        names: &["crear_tabla"],
+        route: Route::Excel,
+        usage: ("crear_tabla <archivo.xlsx> <hoja> <A1:D20> [medio2|claro1|oscuro3|ninguno] [nombre=<tabla>] [--totales] [--sin-bandas] [--sin-filtros]", "create_table <file.xlsx> <sheet> <A1:D20> [medium2|light1|dark3|none] [name=<table>] [--totals] [--no-bands] [--no-filters]"),
+        description: ("Convierte el rango en una tabla de Excel con estilo, filas con bandas, filtros en el encabezado y, con --totales, una fila de totales", "Turn the range into an Excel table with a style, banded rows, header filters and, with --totals, a total row"),
+        examples: &["crear_tabla ventas.xlsx Ventas A1:D120 medio2 --totales"],
+    },
+    CommandHelp {
+        names: &["combinar_celdas"],
+        route: Route::Excel,
+        usage: ("combinar_celdas <archivo.xlsx> <Hoja!A1:C1|nombre>", "merge_cells <file.xlsx> <Sheet!A1:C1|name>"),
+        description: ("Combina un rango de celdas (títulos, encabezados agrupados); solo queda el valor de la celda superior izquierda", "Merge a range of cells (titles, grouped headers); only the top-left cell keeps its value"),
+        examples: &["combinar_celdas informe.xlsx Resumen!A1:D1"],
+    },
+    CommandHelp {
+        names: &["convertir"],
+        route: Route::Excel,
+        usage: ("convertir <patrón> --a xlsx|csv|json|parquet [--salida <dir>] [--validar]", "convert <pattern> --to xlsx|csv|json|parquet [--output <dir>] [--validate]"),
+        description: ("Convierte archivos en lote", "Convert files in bulk"),
+        examples: &["convertir datos/*.csv --a xlsx --salida convertidos"],
+    },
+    CommandHelp {
+        names: &["top", "bottom"],
+        route: Route::Excel,
+        usage: ("top|bottom <archivo.xlsx> <hoja> por=<col> [n=10] [agrupado_por=<col>] [salida=<archivo.xlsx>]", "top|bottom <file.xlsx> <sheet> by=<col> [n=10] [group_by=<col>] [output=<file.xlsx>]"),
+        description: ("Ranking calculado localmente", "Ranking computed locally"),
+        examples: &["top ventas.xlsx Ventas por=Importe n=5 agrupado_por=Cliente"],
+    },
+    CommandHelp {
+        names: &["pareto"],
+        route: Route::Excel,
+        usage: ("pareto <archivo.xlsx> <hoja> por=<col> [agrupado_por=<col>] [corte=80] [grafico=columnas|lineas] [salida=<archivo.xlsx>]", "pareto <file.xlsx> <sheet> by=<col> [group_by=<col>] [cut=80] [chart=column|line] [output=<file.xlsx>]"),
+        description: ("% del total y % acumulado", "% of total and cumulative %"),
+        examples: &["pareto ventas.xlsx Ventas por=Importe agrupado_por=Producto corte=80"],
+    },
+    CommandHelp {
+        names: &["cohortes"],
+        route: Route::Excel,
+        usage: ("cohortes <archivo.xlsx> fecha_alta=<col> fecha_evento=<col> [valor=<col>] [cliente=<col>] [hoja=<hoja>] [relativo=si] [salida=<archivo.xlsx>]", "cohorts <file.xlsx> signup_date=<col> event_date=<col> [value=<col>] [customer=<col>] [sheet=<sheet>] [relative=yes] [output=<file.xlsx>]"),
+        description: ("Matriz de cohortes", "Cohort matrix"),
+        examples: &["cohortes pedidos.xlsx fecha_alta=Alta fecha_evento=Pedido cliente=Cliente relativo=si"],
+    },
+    CommandHelp {
+        names: &["estadisticas"],
+        route: Route::Excel,
+        usage: ("estadisticas <archivo.xlsx> <col>[,<col>...] [hoja=<nombre>] [salida=<archivo.xlsx>]", "statistics <file.xlsx> <col>[,<col>...] [sheet=<name>] [output=<file.xlsx>]"),
+        description: ("Recuento, suma, media, mediana, desviación típica y cuartiles calculados localmente, sin gastar tokens", "Count, sum, mean, median, standard deviation and quartiles computed locally, without spending tokens"),
+        examples: &["estadisticas ventas.xlsx Importe,Unidades hoja=Ventas"],
+    },
+    CommandHelp {
+        names: &["formato_condicional"],
+        route: Route::Excel,
+        usage: ("formato_condicional <archivo.xlsx> <hoja> <rango> escala|escala3|barras [colores]", "conditional_format <file.xlsx> <sheet> <range> scale|scale3|bars [colors]"),
+        description: ("Escala de colores o barras de datos", "Color scale or data bars"),
+        examples: &["formato_condicional ventas.xlsx Ventas C2:C100 escala"],
+    },
+    CommandHelp {
+        names: &["formato_condicional"],
+        route: Route::Excel,
+        usage: ("formato_condicional <archivo.xlsx> <hoja> <rango> valor <op> <valor> [valor2] [color]", "conditional_format <file.xlsx> <sheet> <range> value <op> <value> [value2] [color]"),
+        description: ("Resalta celdas (op: > >= < <= = != entre)", "Highlight cells (op: > >= < <= = != between)"),
+        examples: &["formato_condicional ventas.xlsx Ventas C2:C100 valor > 1000"],
+    },
+    CommandHelp {
+        names: &["validar"],
+        route: Route::Excel,
+        usage: ("validar <archivo.xlsx> <Hoja!rango> lista \"Alta,Media,Baja\"|lista =Hoja!A2:A9 [mensaje=\"..\"] [error=\"..\"]", "validate <file.xlsx> <Sheet!range> list \"High,Medium,Low\"|list =Sheet!A2:A9 [message=\"..\"] [error=\"..\"]"),
+        description: ("Añade una lista desplegable a un rango", "Add a dropdown list to a range"),
+        examples: &["validar tareas.xlsx Tareas!C2:C100 lista \"Alta,Media,Baja\""],
+    },
+    CommandHelp {
+        names: &["validar"],
+        route: Route::Excel,
+        usage: ("validar <archivo.xlsx> <Hoja!rango> entero|decimal|fecha|longitud <op> <valor> [valor2] [vacio=no]", "validate <file.xlsx> <Sheet!range> whole|decimal|date|length <op> <value> [value2] [blank=no]"),
+        description: ("Limita los valores admitidos (op: > >= < <= = != entre; fechas AAAA-MM-DD)", "Restrict the accepted values (op: > >= < <= = != between; dates YYYY-MM-DD)"),
+        examples: &["validar pedidos.xlsx Pedidos!D2:D500 entero entre 1 100"],
+    },
+    CommandHelp {
+        names: &["preguntar_lote"],
+        route: Route::Excel,
+        usage: ("preguntar_lote \"<pregunta>\" <patrón> [salida=<archivo.xlsx>]", "ask_batch \"<question>\" <pattern> [output=<file.xlsx>]"),
+        description: ("Hace la misma pregunta sobre cada archivo y consolida las respuestas con sus citas", "Ask the same question about each file and collect the answers with their citations"),
+        examples: &["preguntar_lote \"¿Cuál es el total facturado?\" facturas/*.xlsx salida=totales.xlsx"],
+    },
+    CommandHelp {
+        names: &["para_cada_fila"],
+        route: Route::Excel,
+        usage: ("para_cada_fila <archivo.xlsx> <hoja> \"<plantilla con {Columna}>\" [columna=<nueva>] [concurrencia=4] [filas=<n>] [salida=<archivo>]", "for_each_row <file.xlsx> <sheet> \"<template with {Column}>\" [column=<new>] [concurrency=4] [rows=<n>] [output=<file>]"),
+        description: ("Envía la plantilla al modelo por cada fila y escribe las respuestas en una columna", "Send the template to the model for each row and write the answers into a column"),
+        examples: &["para_cada_fila opiniones.xlsx Opiniones \"Clasifica el tono de: {Comentario}\" columna=Tono"],
+    },
+    CommandHelp {
+        names: &["extraer_json"],
+        route: Route::Excel,
+        usage: ("extraer_json <archivo.xlsx> \"<instrucción>\" [hoja=<nombre>] [campos=<a,b:numero>|esquema=<archivo.json>] [salida=<archivo.json|xlsx>]", "extract_json <file.xlsx> \"<instruction>\" [sheet=<name>] [fields=<a,b:numero>|schema=<file.json>] [output=<file.json|xlsx>]"),
+        description: ("Pide al modelo datos en JSON, comprueba que cumplen el esquema y reintenta si no", "Ask the model for JSON data, check it against the schema and retry if it does not match"),
+        examples: &["extraer_json factura.xlsx \"Datos del emisor\" campos=nombre,nif,total:numero"],
+    },
+    CommandHelp {
+        names: &["grafico_texto"],
+        route: Route::Excel,
+        usage: ("grafico_texto <archivo.xlsx> <x> <y> [hoja=<nombre>] [tipo=barras|linea] [max=30]", "text_chart <file.xlsx> <x> <y> [sheet=<name>] [type=bars|line] [max=30]"),
+        description: ("Dibuja en la terminal barras por categoría o una línea de la columna y, para revisar los datos antes de un gráfico de Excel", "Draw bars per category or a line of column y in the terminal, to check the data before an Excel chart"),
+        examples: &["grafico_texto ventas.xlsx Mes Total tipo=linea"],
+    },
+    CommandHelp {
+        names: &["buscar"],
+        route: Route::Excel,
+        usage: ("buscar <archivo.xlsx> <texto|/regex/[i]> [hoja=<nombre>] [max=50] [--contexto]", "search <file.xlsx> <text|/regex/[i]> [sheet=<name>] [max=50] [--context]"),
+        description: ("Busca un texto (sin distinguir mayúsculas ni tildes) o una expresión regular en todas las hojas e indica la celda de cada coincidencia; --contexto las pasa al modelo", "Search for a text (ignoring case and accents) or a regular expression in every sheet and show the cell of each match; --context passes them to the model"),
+        examples: &["buscar clientes.xlsx jose", "buscar facturas.xlsx /^F-\\d{4}$/ --contexto"],
+    },
+    CommandHelp {
+        names: &["convertir_fechas"],
+        route: Route::Excel,
+        usage: ("convertir_fechas <archivo.xlsx> <hoja> <col>[,<col>...] [orden=dma|mda] [salida=<archivo.xlsx>]", "convert_dates <file.xlsx> <sheet> <col>[,<col>...] [order=dmy|mdy] [output=<file.xlsx>]"),
+        description: ("Convierte a fechas números de serie y textos como 31/01/2024", "Turn serial numbers and texts like 01/31/2024 into dates"),
+        examples: &["convertir_fechas pedidos.xlsx Pedidos Fecha,Entrega orden=dma"],
+    },
+    CommandHelp {
+        names: &["generar_informe"],
+        route: Route::Excel,
+        usage: ("generar_informe <plantilla.json> <datos.xlsx> [salida=<archivo.xlsx>]", "generate_report <template.json> <data.xlsx> [output=<file.xlsx>]"),
+        description: ("Genera un informe con las hojas, columnas, fórmulas, totales y gráficos que describe la plantilla", "Build a report with the sheets, columns, formulas, totals and charts described by the template"),
+        examples: &["generar_informe informe.json ventas.xlsx salida=informe_enero.xlsx"],
+    },
+    CommandHelp {
+        names: &["comparar"],
+        route: Route::Excel,
+        usage: ("comparar <a.xlsx> <b.xlsx> [salida=<archivo.xlsx>]", "compare <a.xlsx> <b.xlsx> [output=<file.xlsx>]"),
+        description: ("Diferencias celda a celda por hoja; con salida= guarda un libro con los cambios resaltados", "Cell-level differences per sheet; with output= saves a workbook with the changes highlighted"),
+        examples: &["comparar enero.xlsx febrero.xlsx salida=cambios.xlsx"],
+    },
+    CommandHelp {
+        names: &["ajustar_hoja"],
+        route: Route::Excel,
+        usage: ("ajustar_hoja <archivo.xlsx> <hoja> [congelar=1|B2] [anchos=A:20,Total:12] [autoajustar] [ocultar=C,D]", "layout <file.xlsx> <sheet> [freeze=1|B2] [widths=A:20,Total:12] [autofit] [hide=C,D]"),
+        description: ("Inmoviliza filas o columnas, fija anchos, autoajusta las columnas u oculta columnas sin tocar los datos", "Freeze rows or columns, set widths, autofit columns or hide columns without touching the data"),
+        examples: &["ajustar_hoja ventas.xlsx Ventas congelar=1 autoajustar"],
+    },
+    CommandHelp {
+        names: &["proteger"],
+        route: Route::Excel,
+        usage: ("proteger <archivo.xlsx> <hoja> [contraseña] [editables=B2:B20,D2]", "protect <file.xlsx> <sheet> [password] [editable=B2:B20,D2]"),
+        description: ("Protege la hoja: bloquea las celdas con fórmula y deja editables las demás (o solo los rangos indicados)", "Protect the sheet: lock cells with formulas and leave the rest editable (or only the given ranges)"),
+        examples: &["proteger plantilla.xlsx Factura editables=B2:B20"],
+    },
+    CommandHelp {
+        names: &["insertar_fila", "eliminar_fila"],
+        route: Route::Excel,
+        usage: ("insertar_fila <hoja> <n> [cantidad=1] | eliminar_fila <hoja> <n>[-m] [archivo=<libro>]", "insert_row <sheet> <n> [count=1] | delete_row <sheet> <n>[-m] [file=<workbook>]"),
+        description: ("Inserta filas vacías antes de la fila n o elimina filas de un libro leído", "Insert empty rows before row n or delete rows of a loaded workbook"),
+        examples: &["insertar_fila Ventas 5 cantidad=2", "eliminar_fila Ventas 10-12"],
+    },
+    CommandHelp {
+        names: &["insertar_columna", "eliminar_columna", "mover_columna"],
+        route: Route::Excel,
+        usage: ("insertar_columna <hoja> <col> [encabezado=<texto>] | eliminar_columna <hoja> <col> | mover_columna <hoja> <col> <destino>", "insert_column <sheet> <col> [header=<text>] | delete_column <sheet> <col> | move_column <sheet> <col> <target>"),
+        description: ("Inserta, elimina o mueve columnas (por letra, número o encabezado) de un libro leído", "Insert, delete or move columns (by letter, number or header) of a loaded workbook"),
+        examples: &["insertar_columna Ventas C encabezado=IVA", "mover_columna Ventas Total A"],
+    },
+    CommandHelp {
+        names: &["duplicados"],
+        route: Route::Excel,
+        usage: ("duplicados <archivo> [<col>,<col>...] [hoja=<hoja>]", "duplicates <file> [<col>,<col>...] [sheet=<sheet>]"),
+        description: ("Lista las filas duplicadas, enteras o por las columnas indicadas", "List duplicate rows, whole or by the given columns"),
+        examples: &["duplicados clientes.xlsx Email"],
+    },
+    CommandHelp {
+        names: &["eliminar_duplicados"],
+        route: Route::Excel,
+        usage: ("eliminar_duplicados <archivo> [<col>,<col>...] [hoja=<hoja>] [salida=<archivo>]", "remove_duplicates <file> [<col>,<col>...] [sheet=<sheet>] [output=<file>]"),
+        description: ("Escribe una copia sin las filas duplicadas y dice cuántas se han quitado", "Write a copy without duplicate rows and tell how many were removed"),
+        examples: &["eliminar_duplicados clientes.xlsx Email salida=clientes_unicos.xlsx"],
+    },
+    CommandHelp {
+        names: &["editar"],
+        route: Route::Excel,
+        usage: ("editar <archivo> <hoja> <celda> <valor>", "edit <file> <sheet> <cell> <value>"),
+        description: ("Cambia una celda en memoria hasta guardar; \"007\" entre comillas es texto y =A1*2 una fórmula", "Change one cell in memory until saved; \"007\" in quotes is text and =A1*2 a formula"),
+        examples: &["editar ventas.xlsx Ventas B2 1500", "editar ventas.xlsx Ventas D2 =B2*C2"],
+    },
+    CommandHelp {
+        names: &["rellenar"],
+        route: Route::Excel,
+        usage: ("rellenar <archivo> <Hoja!A2:A50|nombre> <patrón> [paso=<n>] [--sobrescribir]", "fill <file> <Sheet!A2:A50|name> <pattern> [step=<n>] [--overwrite]"),
+        description: ("Rellena el rango en memoria hasta guardar, sin llamar al modelo: una serie (1.. o 2024-01-31.., con paso=5 o paso=1m), un valor repetido, una fórmula movida como al arrastrarla (=B2*C2) o copiar, que copia la primera fila en las demás", "Fill the range in memory until saved, without calling the model: a series (1.. or 2024-01-31.., with step=5 or step=1m), a repeated value, a formula shifted as when dragging it (=B2*C2) or copy, which copies the first row into the rest"),
+        examples: &["rellenar ventas.xlsx Ventas!A2:A100 1..", "rellenar ventas.xlsx Ventas!B2:B13 2025-01-31.. paso=1m", "rellenar ventas.xlsx Ventas!D2:D100 =B2*C2", "rellenar ventas.xlsx Ventas!E2:E100 copiar"],
+    },
+    CommandHelp {
+        names: &["cruzar"],
+        route: Route::Excel,
+        usage: ("cruzar <hoja_a> <clave_a> <hoja_b> <clave_b> [columnas=<cols>] [nombre=<hoja>]", "join <sheet_a> <key_a> <sheet_b> <key_b> [columns=<cols>] [name=<sheet>]"),
+        description: ("Añade a cada fila de hoja_a las columnas de hoja_b con la misma clave, como BUSCARV, en una hoja nueva", "Add to each row of sheet_a the columns of sheet_b with the same key, like VLOOKUP, in a new sheet"),
+        examples: &["cruzar Pedidos Cliente Clientes Id columnas=Nombre,Ciudad"],
+    },
+    CommandHelp {
+        names: &["transformar"],
+        route: Route::Excel,
+        usage: ("transformar <hoja> renombrar=<a>:<b> ordenar=<cols> quitar=<cols> convertir=<col>:<tipo> recortar sin_duplicados [pasos=<archivo>] [archivo=<libro>]", "transform <sheet> rename=<a>:<b> reorder=<cols> drop=<cols> cast=<col>:<type> trim dedupe [steps=<file>] [file=<workbook>]"),
+        description: ("Limpia una hoja de un libro leído aplicando los pasos en orden", "Clean a sheet of a loaded workbook by applying the steps in order"),
+        examples: &["transformar Ventas renombrar=Imp:Importe quitar=Notas recortar sin_duplicados"],
+    },
+    CommandHelp {
+        names: &["exportar_pdf"],
+        route: Route::Excel,
+        usage: ("exportar_pdf <archivo.xlsx> [hoja=<nombre>] [salida=<archivo.pdf|png>] [conversor=auto|libreoffice|interno]", "export_pdf <file.xlsx> [sheet=<name>] [output=<file.pdf|png>] [converter=auto|libreoffice|interno]"),
+        description: ("Exporta el libro o una hoja a PDF o PNG (con LibreOffice si está instalado, con gráficos y formatos)", "Export the workbook or a sheet to PDF or PNG (through LibreOffice when installed, with charts and formatting)"),
+        examples: &["exportar_pdf informe.xlsx hoja=Resumen salida=resumen.pdf"],
+    },
+    CommandHelp {
+        names: &["aplicar"],
+        route: Route::Excel,
+        usage: ("aplicar <archivo.xlsx> <hoja> [tabla=<n>]", "apply <file.xlsx> <sheet> [table=<n>]"),
+        description: ("Escribe en una hoja una tabla Markdown o un bloque csv de la última respuesta del modelo", "Write a Markdown table or csv block from the last model answer into a sheet"),
+        examples: &["aplicar ventas.xlsx Resumen"],
+    },
+    CommandHelp {
+        names: &["copiar"],
+        route: Route::Copy,
+        usage: ("copiar [respuesta|tabla]", "copy [answer|table]"),
+        description: ("Copia al portapapeles la última respuesta o la última tabla mostrada (por defecto, la más reciente), lista para pegar en Excel", "Copy the last answer or the last table shown (by default, the most recent) to the clipboard, ready to paste into Excel"),
+        examples: &["copiar tabla"],
+    },
+    CommandHelp {
+        names: &["pegar_datos"],
+        route: Route::Excel,
+        usage: ("pegar_datos <archivo.xlsx> [hoja=<nombre>]", "paste_data <file.xlsx> [sheet=<name>]"),
+        description: ("Escribe en una hoja (Pegado por defecto) las celdas copiadas de Excel al portapapeles", "Write the cells copied from Excel to the clipboard into a sheet (Pegado by default)"),
+        examples: &["pegar_datos datos.xlsx hoja=Pegado"],
+    },
+    CommandHelp {
+        names: &["guardar"],
+        route: Route::Excel,
+        usage: ("guardar [archivo] [salida=<archivo>] [--reescribir]", "save [file] [output=<file>] [--rewrite]"),
+        description: ("Escribe los cambios pendientes; si solo son de celdas se escriben en el XML del libro, conservando fórmulas y gráficos. Los cambios de estructura reescriben el libro a partir de los valores: si se perderían fórmulas o gráficos, solo se guarda con --reescribir", "Write the pending changes; cell-only changes go into the workbook XML, keeping formulas and charts. Structural changes rewrite the workbook from the values: if formulas or charts would be lost, it is only saved with --rewrite"),
+        examples: &["guardar ventas.xlsx"],
+    },
+    CommandHelp {
+        names: &["guardar_como"],
+        route: Route::Excel,
+        usage: ("guardar_como [archivo] <destino> [--reescribir]", "save_as [file] <target> [--rewrite]"),
+        description: ("Escribe el libro con sus cambios en otro archivo, que pasa a ser el libro cargado", "Write the workbook with its changes to another file, which becomes the loaded workbook"),
+        examples: &["guardar_como ventas.xlsx ventas_revisado.xlsx"],
+    },
+    CommandHelp {
+        names: &["cifrar_columna", "descifrar_columna"],
+        route: Route::Excel,
+        usage: ("cifrar_columna|descifrar_columna <archivo.xlsx> <hoja> <col>[,<col>...] [salida=<archivo.xlsx>]", "encrypt_column|decrypt_column <file.xlsx> <sheet> <col>[,<col>...] [output=<file.xlsx>]"),
+        description: ("Cifra o descifra columnas con la clave del proyecto", "Encrypt or decrypt columns with the project key"),
+        examples: &["cifrar_columna empleados.xlsx Personal Salario,IBAN"],
+    },
+    CommandHelp {
+        names: &["anonimizar"],
+        route: Route::Excel,
+        usage: ("anonimizar <archivo.xlsx> <col>[,<col>...] [hoja=<nombre>] [modo=seudonimo|hash] [salida=<archivo.xlsx>]", "anonymize <file.xlsx> <col>[,<col>...] [sheet=<name>] [method=pseudonym|hash] [output=<file.xlsx>]"),
+        description: ("Copia del libro con seudónimos o hashes en las columnas con datos personales, para leerla en lugar del original", "Copy of the workbook with pseudonyms or hashes in the personal data columns, to read instead of the original"),
+        examples: &["anonimizar clientes.xlsx Nombre,Email"],
+    },
+    CommandHelp {
+        names: &["desanonimizar"],
+        route: Route::Excel,
+        usage: ("desanonimizar <archivo.xlsx> [salida=<archivo.xlsx>]", "deanonymize <file.xlsx> [output=<file.xlsx>]"),
+        description: ("Devuelve los valores reales a las celdas anonimizadas (la correspondencia se guarda solo en local)", "Put the real values back in anonymized cells (the mapping is only kept locally)"),
+        examples: &["desanonimizar informe_anonimo.xlsx salida=informe.xlsx"],
+    },
+    CommandHelp {
+        names: &["agente"],
+        route: Route::Agent,
+        usage: ("agente <tarea>", "agent <task>"),
+        description: ("El modelo planifica la tarea y la ejecuta paso a paso con herramientas (Ctrl-C la detiene)", "The model plans the task and runs it step by step with tools (Ctrl-C stops it)"),
+        examples: &["agente crea una hoja Resumen con el total por región y un gráfico de barras"],
+    },
+    CommandHelp {
+        names: &["trabajos"],
+        route: Route::Jobs,
+        usage: ("trabajos", "jobs"),
+        description: ("Lista los trabajos interrumpidos de agente y para_cada_fila", "Lists interrupted agent and para_cada_fila jobs"),
+        examples: &[],
+    },
+    CommandHelp {
+        names: &["refrescar"],
+        route: Route::Refresh,
+        usage: ("refrescar <archivo>", "refresh <file>"),
+        description: ("Vuelve a leer la estructura de un libro y la guarda para las próximas sesiones", "Reads a workbook's structure again and keeps it for the next sessions"),
+        examples: &["refrescar ventas.xlsx"],
+    },
+    CommandHelp {
+        names: &["reanudar"],
+        route: Route::Resume,
+        usage: ("reanudar <id>", "resume <id>"),
+        description: ("Sigue un trabajo interrumpido donde se quedó", "Continues an interrupted job where it stopped"),
+        examples: &["reanudar 3"],
+    },
+    CommandHelp {
+        names: &["deshacer"],
+        route: Route::Excel,
+        usage: ("deshacer <archivo>", "undo <file>"),
+        description: ("Restaura la copia de seguridad más reciente (se hace una antes de cada escritura)", "Restore the most recent backup (one is made before every write)"),
+        examples: &["deshacer ventas.xlsx"],
+    },
+    CommandHelp {
+        names: &["contexto"],
+        route: Route::Context,
+        usage: ("contexto crear|usar|borrar <nombre>", "context create|use|delete <name>"),
+        description: ("Conversaciones separadas, cada una con su historial y sus libros cargados (`contexto` las lista)", "Separate conversations, each with its own history and loaded workbooks (`context` lists them)"),
+        examples: &["contexto crear gastos", "contexto usar principal"],
+    },
+    CommandHelp {
+        names: &["contexto"],
+        route: Route::Context,
+        usage: ("contexto ver [n]", "context show [n]"),
+        description: ("Muestra los mensajes que se envían al modelo (también los resúmenes de datos), o el mensaje n completo", "Show the messages sent to the model (including data summaries), or message n in full"),
+        examples: &["contexto ver 3"],
+    },
+    CommandHelp {
+        names: &["contexto"],
+        route: Route::Context,
+        usage: ("contexto quitar <n> | contexto limpiar [datos]", "context remove <n> | context clear [data]"),
+        description: ("Quita un mensaje del contexto, los resúmenes de datos o todo salvo el prompt de sistema, sin reiniciar", "Remove one message from the context, the data summaries or everything but the system prompt, without restarting"),
+        examples: &["contexto limpiar datos"],
+    },
+    CommandHelp {
+        names: &["punto_de_control"],
+        route: Route::Checkpoint,
+        usage: ("punto_de_control [nombre]", "checkpoint [name]"),
+        description: ("Guarda una copia del historial y los libros cargados del contexto activo; sin nombre, lista los puntos de control", "Save a copy of the active context's history and loaded workbooks; without a name, list the checkpoints"),
+        examples: &["punto_de_control antes_de_limpiar"],
+    },
+    CommandHelp {
+        names: &["volver"],
+        route: Route::Rewind,
+        usage: ("volver <nombre>", "rewind <name>"),
+        description: ("Recupera el historial y los libros cargados de un punto de control, para descartar lo hecho desde entonces", "Restore the history and loaded workbooks of a checkpoint, discarding what was done since"),
+        examples: &["volver antes_de_limpiar"],
+    },
+    CommandHelp {
+        names: &["macro"],
+        route: Route::Macro,
+        usage: ("macro [<nombre> <argumentos...>]", "macro [<name> <arguments...>]"),
+        description: ("Ejecuta una macro de macros.toml (comandos y preguntas con parámetros); sin nombre, lista las macros", "Run a macro from macros.toml (commands and prompts with parameters); without a name, list the macros"),
+        examples: &["macro", "macro informe_mensual enero"],
+    },
+    CommandHelp {
+        names: &["exportar_sesion"],
+        route: Route::ExportSession,
+        usage: ("exportar_sesion <archivo> [--con-preguntas]", "export_session <file> [--with-prompts]"),
+        description: ("Guarda los comandos ejecutados como guion reproducible con `ia_agent --guion <archivo>`", "Save the commands run as a script to replay with `ia_agent --guion <file>`"),
+        examples: &["exportar_sesion sesion.txt --con-preguntas"],
+    },
+    CommandHelp {
+        names: &["manifiesto"],
+        route: Route::Manifest,
+        usage: ("manifiesto [archivo.json]", "manifest [file.json]"),
+        description: ("Archivos escritos en la sesión, ya verificados, con sus hojas, filas y SHA-256; con archivo, los guarda en JSON", "Files written in the session, already verified, with their sheets, rows and SHA-256; with a file, save them as JSON"),
+        examples: &["manifiesto entregados.json"],
+    },
+    CommandHelp {
+        names: &[],
+        route: Route::Excel,
+        usage: ("<texto> <<FIN", "<text> <<FIN"),
+        description: ("Sigue el texto en las líneas siguientes hasta una línea con solo FIN", "Continue the text on the next lines until a line with just FIN"),
+        examples: &[],
+    },
+    CommandHelp {
+        names: &["doctor", "ping"],
+        route: Route::Doctor,
+        usage: ("doctor (o ping)", "doctor (or ping)"),
+        description: ("Comprueba la clave, la conexión con la API, el modelo y los permisos de los directorios (también `ia_agent doctor`)", "Check the API key, the connection to the API, the model and directory permissions (also `ia_agent doctor`)"),
+        examples: &[],
+    },
+    CommandHelp {
+        names: &["cache"],
+        route: Route::Cache,
+        usage: ("cache", "cache"),
+        description: ("Muestra el estado de la caché de respuestas (IAGENT_CACHE_TTL, --sin-cache)", "Show the response cache status (IAGENT_CACHE_TTL, --sin-cache)"),
+        examples: &[],
+    },
+    CommandHelp {
+        names: &["cache"],
+        route: Route::Cache,
+        usage: ("cache clear", "cache clear"),
+        description: ("Vacía la caché de respuestas", "Empty the response cache"),
+        examples: &["cache clear"],
+    },
+    CommandHelp {
+        names: &["tour"],
+        route: Route::Tour,
+        usage: ("tour", "tour"),
+        description: ("Recorrido guiado con libros de ejemplo (también `ia_agent tour`)", "Guided tour with sample workbooks (also `ia_agent tour`)"),
+        examples: &[],
+    },
+    CommandHelp {
+        names: &["rendimiento"],
+        route: Route::Performance,
+        usage: ("rendimiento", "performance"),
+        description: ("Muestra las operaciones más lentas de la sesión", "Show the slowest operations of the session"),
+        examples: &[],
+    },
+    CommandHelp {
+        names: &["coste", "usage"],
+        route: Route::Usage,
+        usage: ("coste (o usage)", "cost (or usage)"),
+        description: ("Muestra los tokens consumidos y el coste estimado de la sesión", "Show the tokens used and the estimated cost of the session"),
+        examples: &[],
+    },
+    CommandHelp {
+        names: &["modelo"],
+        route: Route::Model,
+        usage: ("modelo [<nombre> [url=<endpoint>]]", "model [<name> [url=<endpoint>]]"),
+        description: ("Muestra el modelo activo o cambia a otro conservando la conversación", "Show the active model or switch to another one, keeping the conversation"),
+        examples: &["modelo gpt-4o url=https://api.openai.com/v1"],
+    },
+    CommandHelp {
+        names: &["comparar_modelos"],
+        route: Route::CompareModels,
+        usage: ("comparar_modelos \"<pregunta>\" [modelos=<m1>,<m2>[@url]]", "compare_models \"<question>\" [models=<m1>,<m2>[@url]]"),
+        description: ("Hace la misma pregunta, con el contexto actual, a varios modelos a la vez y muestra las respuestas en columnas", "Ask several models the same question, with the current context, at the same time and show the answers side by side"),
+        examples: &["comparar_modelos \"Resume la hoja Ventas\" modelos=deepseek-chat,gpt-4o"],
+    },
+    CommandHelp {
+        names: &["perfil"],
+        route: Route::Profile,
+        usage: ("perfil [<nombre>]", "profile [<name>]"),
+        description: ("Lista los perfiles de iagent.toml o cambia a otro (proveedor, clave, modelo, directorio y prompts) conservando la conversación", "List the profiles in iagent.toml or switch to another one (provider, key, model, directory and prompts), keeping the conversation"),
+        examples: &["perfil trabajo"],
+    },
+    CommandHelp {
+        names: &["ayuda"],
+        route: Route::Help,
+        usage: ("ayuda [comando]", "help [command]"),
+        description: ("Muestra esta información, o la página de un comando con sus opciones y ejemplos", "Show this information, or the page of one command with its options and examples"),
+        examples: &["ayuda leer_excel"],
+    },
+    CommandHelp {
+        names: &["salir"],
+        route: Route::Exit,
+        usage: ("salir", "exit"),
+        description: ("Termina el programa", "Quit the program"),
+        examples: &[],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn arguments_that_fit_the_usage_are_accepted() {
+        assert!(accepts("leer_excel", &args("datos.xlsx")));
+        assert!(accepts("leer_excel", &args("datos.xlsx --stream")));
+        assert!(accepts("buscar", &args("v.xlsx Ana hoja=Clientes")));
+        assert!(accepts("salir", &[]));
+        assert!(accepts("rellenar", &args("v.xlsx Hoja!A2:A9 1.. paso=2")));
+        assert!(accepts("agente", &args("ordena las ventas por mes")));
+        assert!(accepts("pagina", &args("3")));
+        assert!(accepts("ping", &[]));
+    }
+
+    #[test]
+    fn questions_do_not_fit_the_usage() {
+        assert!(!accepts("buscar", &args("los clientes con deuda en v.xlsx")));
+        assert!(!accepts("leer_excel", &args("the file datos.xlsx")));
+        assert!(!accepts("leer_excel", &[]));
+        assert!(!accepts("salir", &args("de la hoja")));
+        assert!(!accepts("explicar", &args("datos.xlsx y otros")));
+        assert!(!accepts("no_existe", &[]));
+    }
+
+    #[test]
+    fn formulas_are_not_options() {
+        assert!(!is_option("=B2*C2"));
+        assert!(is_option("salida=otro.xlsx"));
+        assert!(is_option("--sobrescribir"));
+    }
+
+    #[test]
+    fn every_registry_entry_routes_by_each_of_its_names() {
+        for entry in COMMANDS {
+            for name in entry.names {
+                assert_eq!(route(name), Some(entry.route), "{}", name);
+            }
+        }
+        assert_eq!(route("página"), Some(Route::Page));
+        assert!(excel_names().any(|name| name == "leer_excel"));
+        assert!(!excel_names().any(|name| name == "salir"));
+        assert_eq!(route("cuántas"), None);
+    }
+}
@@ -0,0 +1,327 @@
+// Comparación celda a celda de dos versiones de un libro (`comparar`): qué celdas
+// se han añadido, eliminado o cambiado en cada hoja, con un informe legible y,
+// opcionalmente, un libro con las diferencias resaltadas.
+use crate::conditional_format;
+use crate::convert;
+use crate::excel::{self, CellValue, SheetData, WorkbookData};
+use crate::i18n::{self, tr};
+use crate::outputs;
+use crate::xlsx_patch::XlsxPackage;
+use anyhow::Result;
+use std::fmt;
+use std::path::Path;
+
+// Cambios que se listan por hoja en el informe
+const REPORT_CHANGES_PER_SHEET: usize = 20;
+const CHANGES_SHEET: &str = "Cambios";
+// (relleno, fuente) de cada tipo de cambio en el libro de diferencias
+const CHANGED_COLORS: (&str, &str) = ("FFEB9C", "9C5700");
+const ADDED_COLORS: (&str, &str) = ("C6EFCE", "006100");
+const REMOVED_COLORS: (&str, &str) = ("FFC7CE", "9C0006");
+
+#[derive(Debug, Clone)]
+pub struct CompareOptions {
+    pub left: String,
+    pub right: String,
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl ChangeKind {
+    fn label(self) -> &'static str {
+        match self {
+            ChangeKind::Added => i18n::pick(("añadida", "added")),
+            ChangeKind::Removed => i18n::pick(("eliminada", "removed")),
+            ChangeKind::Changed => i18n::pick(("cambiada", "changed")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CellChange {
+    // Índices desde 0
+    pub row: usize,
+    pub col: usize,
+    pub kind: ChangeKind,
+    pub old: CellValue,
+    pub new: CellValue,
+}
+
+impl CellChange {
+    fn reference(&self) -> String {
+        format!("{}{}", excel::column_letters(self.col), self.row + 1)
+    }
+
+    // Antes y después tal como se muestran; si se muestran igual pero son de otro
+    // tipo (el texto "20" y el número 20), cada uno con su tipo entre paréntesis
+    fn display(&self) -> (String, String) {
+        let (old, new) = (display_value(&self.old), display_value(&self.new));
+        if old != new {
+            return (old, new);
+        }
+        (format!("{} ({})", old, type_label(&self.old)), format!("{} ({})", new, type_label(&self.new)))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SheetDiff {
+    pub sheet: String,
+    pub changes: Vec<CellChange>,
+}
+
+impl SheetDiff {
+    fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|change| change.kind == kind).count()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct WorkbookDiff {
+    pub left: String,
+    pub right: String,
+    // Hojas presentes en los dos libros
+    pub sheets: Vec<SheetDiff>,
+    // Hojas que solo están en el segundo o en el primero
+    pub added_sheets: Vec<String>,
+    pub removed_sheets: Vec<String>,
+}
+
+impl WorkbookDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_sheets.is_empty()
+            && self.removed_sheets.is_empty()
+            && self.sheets.iter().all(|sheet| sheet.changes.is_empty())
+    }
+}
+
+impl fmt::Display for WorkbookDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = vec![tr!("Comparación de {} → {}", "Comparison of {} → {}", self.left, self.right)];
+        if self.is_empty() {
+            lines.push(i18n::pick(("Los libros tienen los mismos valores", "The workbooks have the same values")).to_string());
+        }
+        lines.extend(self.added_sheets.iter().map(|name| tr!("Hoja nueva: {}", "New sheet: {}", name)));
+        lines.extend(self.removed_sheets.iter().map(|name| tr!("Hoja eliminada: {}", "Removed sheet: {}", name)));
+        for sheet in self.sheets.iter().filter(|sheet| !sheet.changes.is_empty()) {
+            lines.push(tr!(
+                "Hoja {}: {} cambiadas, {} añadidas, {} eliminadas",
+                "Sheet {}: {} changed, {} added, {} removed",
+                sheet.sheet,
+                sheet.count(ChangeKind::Changed),
+                sheet.count(ChangeKind::Added),
+                sheet.count(ChangeKind::Removed)
+            ));
+            for change in sheet.changes.iter().take(REPORT_CHANGES_PER_SHEET) {
+                let (old, new) = change.display();
+                lines.push(format!("  {}: {} → {}", change.reference(), old, new));
+            }
+            if sheet.changes.len() > REPORT_CHANGES_PER_SHEET {
+                lines.push(tr!("  … y {} más", "  … and {} more", sheet.changes.len() - REPORT_CHANGES_PER_SHEET));
+            }
+        }
+        let unchanged: Vec<&str> = self
+            .sheets
+            .iter()
+            .filter(|sheet| sheet.changes.is_empty())
+            .map(|sheet| sheet.sheet.as_str())
+            .collect();
+        if !unchanged.is_empty() && !self.is_empty() {
+            lines.push(tr!("Sin cambios: {}", "Unchanged: {}", unchanged.join(", ")));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+fn display_value(value: &CellValue) -> String {
+    match value {
+        CellValue::Empty => i18n::pick(("(vacía)", "(empty)")).to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn type_label(value: &CellValue) -> &'static str {
+    i18n::pick(match value {
+        CellValue::Empty => ("vacía", "empty"),
+        CellValue::Bool(_) => ("booleano", "boolean"),
+        CellValue::Number(_) => ("número", "number"),
+        CellValue::DateTime(_) => ("fecha", "date"),
+        CellValue::Text(_) => ("texto", "text"),
+        CellValue::Error(_) => ("error", "error"),
+    })
+}
+
+// Compara los dos archivos (xlsx, csv o json) y escribe el libro de diferencias si se pide
+pub fn run(options: &CompareOptions) -> Result<WorkbookDiff> {
+    let left = convert::read_any(Path::new(&options.left))?;
+    let right = convert::read_any(Path::new(&options.right))?;
+    let mut diff = diff_workbooks(&left, &right);
+    diff.left = options.left.clone();
+    diff.right = options.right.clone();
+    if let Some(output) = &options.output {
+        write_diff_workbook(Path::new(&outputs::target(output)?), &right, &diff)?;
+    }
+    Ok(diff)
+}
+
+pub fn diff_workbooks(left: &WorkbookData, right: &WorkbookData) -> WorkbookDiff {
+    let mut diff = WorkbookDiff::default();
+    for sheet in &left.sheets {
+        match right.sheet(&sheet.name) {
+            Some(other) => diff.sheets.push(SheetDiff {
+                sheet: sheet.name.clone(),
+                changes: diff_sheets(sheet, other),
+            }),
+            None => diff.removed_sheets.push(sheet.name.clone()),
+        }
+    }
+    diff.added_sheets = right
+        .sheets
+        .iter()
+        .filter(|sheet| left.sheet(&sheet.name).is_none())
+        .map(|sheet| sheet.name.clone())
+        .collect();
+    diff
+}
+
+// Cambios por posición, recorriendo la hoja por filas
+fn diff_sheets(left: &SheetData, right: &SheetData) -> Vec<CellChange> {
+    let rows = left.rows.len().max(right.rows.len());
+    let mut changes = Vec::new();
+    for row in 0..rows {
+        let old_row = left.rows.get(row).map(Vec::as_slice).unwrap_or_default();
+        let new_row = right.rows.get(row).map(Vec::as_slice).unwrap_or_default();
+        for col in 0..old_row.len().max(new_row.len()) {
+            let old = old_row.get(col).cloned().unwrap_or(CellValue::Empty);
+            let new = new_row.get(col).cloned().unwrap_or(CellValue::Empty);
+            let kind = match (is_blank(&old), is_blank(&new)) {
+                (true, true) => continue,
+                (true, false) => ChangeKind::Added,
+                (false, true) => ChangeKind::Removed,
+                (false, false) if same_value(&old, &new) => continue,
+                (false, false) => ChangeKind::Changed,
+            };
+            changes.push(CellChange { row, col, kind, old, new });
+        }
+    }
+    changes
+}
+
+fn is_blank(value: &CellValue) -> bool {
+    match value {
+        CellValue::Empty => true,
+        CellValue::Text(text) => text.is_empty(),
+        _ => false,
+    }
+}
+
+// Los números se comparan con tolerancia para no marcar diferencias de redondeo
+// (p. ej. 0.1 + 0.2 guardado por otra herramienta)
+fn same_value(old: &CellValue, new: &CellValue) -> bool {
+    match (old.as_number(), new.as_number()) {
+        (Some(a), Some(b)) => (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0),
+        _ => old == new,
+    }
+}
+
+// Libro con una hoja de cambios y cada hoja comparada con los valores nuevos;
+// las celdas cambiadas muestran "antes → después" y se colorean según el tipo
+fn write_diff_workbook(path: &Path, right: &WorkbookData, diff: &WorkbookDiff) -> Result<()> {
+    let mut summary = SheetData::new(CHANGES_SHEET);
+    summary.rows.push(
+        [("Hoja", "Sheet"), ("Celda", "Cell"), ("Tipo", "Type"), ("Antes", "Before"), ("Después", "After")]
+            .into_iter()
+            .map(|header| CellValue::Text(i18n::pick(header).to_string()))
+            .collect(),
+    );
+    for (names, label) in [(&diff.added_sheets, ("hoja nueva", "new sheet")), (&diff.removed_sheets, ("hoja eliminada", "removed sheet"))] {
+        for name in names {
+            summary.rows.push(vec![CellValue::Text(name.clone()), CellValue::Empty, CellValue::Text(i18n::pick(label).to_string())]);
+        }
+    }
+
+    let mut output = WorkbookData::default();
+    for sheet_diff in &diff.sheets {
+        for change in &sheet_diff.changes {
+            summary.rows.push(vec![
+                CellValue::Text(sheet_diff.sheet.clone()),
+                CellValue::Text(change.reference()),
+                CellValue::Text(change.kind.label().to_string()),
+                change.old.clone(),
+                change.new.clone(),
+            ]);
+        }
+    }
+    output.sheets.push(summary);
+
+    for sheet_diff in diff.sheets.iter().filter(|sheet| !sheet.changes.is_empty()) {
+        let Some(new) = right.sheet(&sheet_diff.sheet) else { continue };
+        // Una hoja llamada como la de cambios se renombraría al guardar
+        if new.name.eq_ignore_ascii_case(CHANGES_SHEET) {
+            continue;
+        }
+        let mut marked = new.clone();
+        marked.charts.clear();
+        for change in &sheet_diff.changes {
+            while marked.rows.len() <= change.row {
+                marked.rows.push(Vec::new());
+            }
+            let row = &mut marked.rows[change.row];
+            if row.len() <= change.col {
+                row.resize(change.col + 1, CellValue::Empty);
+            }
+            row[change.col] = match change.kind {
+                ChangeKind::Changed => {
+                    let (old, new) = change.display();
+                    CellValue::Text(format!("{} → {}", old, new))
+                }
+                ChangeKind::Added => change.new.clone(),
+                ChangeKind::Removed => change.old.clone(),
+            };
+        }
+        output.sheets.push(marked);
+    }
+    excel::save_workbook(path, &output)?;
+
+    let mut package = XlsxPackage::open(path)?;
+    for sheet_diff in &diff.sheets {
+        if output.sheet(&sheet_diff.sheet).is_none() || sheet_diff.sheet.eq_ignore_ascii_case(CHANGES_SHEET) {
+            continue;
+        }
+        for (kind, (fill, font)) in [
+            (ChangeKind::Changed, CHANGED_COLORS),
+            (ChangeKind::Added, ADDED_COLORS),
+            (ChangeKind::Removed, REMOVED_COLORS),
+        ] {
+            let cells: Vec<(usize, usize)> = sheet_diff
+                .changes
+                .iter()
+                .filter(|change| change.kind == kind)
+                .map(|change| (change.row, change.col))
+                .collect();
+            conditional_format::highlight_cells(&mut package, &sheet_diff.sheet, &cells, fill, font)?;
+        }
+    }
+    package.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_change_of_type_alone_shows_both_types() {
+        let left = WorkbookData { sheets: vec![SheetData::from_rows("Datos", &[&["Id", "Importe"], &["1", "20"], &["2", "35"]])] };
+        let mut right = left.clone();
+        right.sheets[0].rows[1][1] = CellValue::Text("20".to_string());
+        right.sheets[0].rows[2][1] = CellValue::Number(36.0);
+        let report = diff_workbooks(&left, &right).to_string();
+        assert!(report.contains("B2: 20 (número) → 20 (texto)"), "{}", report);
+        assert!(report.contains("B3: 35 → 36"), "{}", report);
+    }
+}
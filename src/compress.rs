@@ -0,0 +1,134 @@
+// Compresión del historial: cuando la conversación supera un umbral de tokens,
+// los turnos antiguos se resumen con una llamada al modelo (IAGENT_SUMMARY_MODEL,
+// uno barato si se configura) y se sustituyen por el resumen. Los últimos turnos
+// se conservan tal cual, y también los datos de archivos insertados como contexto,
+// que ya tienen su propio presupuesto.
+use crate::budget;
+use crate::config::Config;
+use crate::llm::{self, Message};
+use crate::profiles;
+use crate::untrusted;
+use crate::usage::UsageTracker;
+use anyhow::{bail, Result};
+use reqwest::Client;
+
+const DEFAULT_THRESHOLD_TOKENS: usize = 12_000;
+// Mensajes recientes que nunca se resumen
+const DEFAULT_KEEP_MESSAGES: usize = 6;
+// Lo que se envía de cada mensaje antiguo para resumirlo
+const MAX_MESSAGE_TOKENS: usize = 400;
+const SUMMARY_PREFIX: &str = "Resumen de la conversación anterior:";
+
+const SUMMARY_INSTRUCTIONS: &str = "Resume la conversación siguiente entre un usuario y un asistente que trabaja con archivos Excel. Conserva los archivos, hojas y columnas mencionados, las cifras y conclusiones obtenidas, los cambios hechos en los archivos y las preguntas pendientes. Responde solo con el resumen, en viñetas breves.";
+
+#[derive(Debug, Clone, Copy)]
+pub struct HistoryCompression {
+    // Tokens estimados de todo el historial a partir de los que se resume; 0 lo desactiva
+    pub threshold: usize,
+    pub keep_messages: usize,
+}
+
+impl HistoryCompression {
+    // IAGENT_HISTORY_TOKENS / IAGENT_HISTORY_KEEP
+    pub fn from_env() -> HistoryCompression {
+        let read = |name: &str, default: usize| {
+            profiles::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        };
+        HistoryCompression {
+            threshold: read("IAGENT_HISTORY_TOKENS", DEFAULT_THRESHOLD_TOKENS),
+            keep_messages: read("IAGENT_HISTORY_KEEP", DEFAULT_KEEP_MESSAGES).max(1),
+        }
+    }
+}
+
+fn history_tokens(history: &[Message]) -> usize {
+    history.iter().map(|message| budget::estimate_tokens(&message.content)).sum()
+}
+
+// Resultado de una compresión: mensajes resumidos y tokens antes y después
+pub struct Compressed {
+    pub messages: usize,
+    pub before: usize,
+    pub after: usize,
+}
+
+// Resume los turnos antiguos si el historial supera el umbral; None si no hacía falta
+pub async fn maybe_compress(
+    client: &Client,
+    config: &Config,
+    history: &mut Vec<Message>,
+    usage_tracker: &mut UsageTracker,
+) -> Result<Option<Compressed>> {
+    let settings = config.history_compression;
+    let before = history_tokens(history);
+    if settings.threshold == 0 || before <= settings.threshold {
+        return Ok(None);
+    }
+    let Some(split) = split_point(history, settings.keep_messages) else {
+        return Ok(None);
+    };
+
+    // Los datos insertados (salvo resúmenes anteriores) se quedan; el resto se resume
+    let (old, recent) = (history[1..split].to_vec(), history[split..].to_vec());
+    let (kept, summarized): (Vec<Message>, Vec<Message>) = old
+        .into_iter()
+        .partition(|message| {
+            (message.role == "system" && !message.content.starts_with(SUMMARY_PREFIX)) || untrusted::is_file_content(message)
+        });
+    if summarized.is_empty() {
+        return Ok(None);
+    }
+
+    let transcript = summarized.iter().map(transcript_line).collect::<Vec<_>>().join("\n");
+    let messages = vec![Message::new("system", SUMMARY_INSTRUCTIONS), Message::new("user", transcript)];
+    let mut summary_config = config.for_command("resumen");
+    if let Some(model) = &config.summary_model {
+        summary_config.model = model.clone();
+    }
+    let completion = llm::get_deepseek_response(client, &summary_config, &messages, None).await?;
+    usage_tracker.record_completion(&summary_config.provider_name(), &summary_config.model, &completion);
+    let summary = completion.message.content.unwrap_or_default();
+    if summary.trim().is_empty() {
+        bail!("El modelo devolvió un resumen vacío");
+    }
+
+    let mut compressed = vec![history[0].clone()];
+    compressed.extend(kept);
+    compressed.push(Message::new("system", format!("{}\n{}", SUMMARY_PREFIX, summary.trim())));
+    compressed.extend(recent);
+    *history = compressed;
+    Ok(Some(Compressed {
+        messages: summarized.len(),
+        before,
+        after: history_tokens(history),
+    }))
+}
+
+// Primer mensaje que se conserva: una pregunta del usuario, para no separar una
+// llamada a herramientas de sus resultados
+fn split_point(history: &[Message], keep: usize) -> Option<usize> {
+    let limit = history.len().saturating_sub(keep);
+    let candidates = || history.iter().enumerate().skip(2).filter(|(_, m)| m.role == "user" && !untrusted::is_file_content(m)).map(|(idx, _)| idx);
+    candidates()
+        .rfind(|idx| *idx <= limit)
+        .or_else(|| candidates().next())
+}
+
+fn transcript_line(message: &Message) -> String {
+    let who = match message.role.as_str() {
+        "user" => "Usuario",
+        "assistant" => "Asistente",
+        "tool" => "Resultado de herramienta",
+        _ => "Contexto",
+    };
+    let mut content = budget::truncate_to_tokens(&message.content, MAX_MESSAGE_TOKENS);
+    if let Some(calls) = &message.tool_calls {
+        for call in calls {
+            content.push_str(&format!(" [llama a {} {}]", call.function.name, call.function.arguments));
+        }
+    }
+    format!("{}: {}", who, content)
+}
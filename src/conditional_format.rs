@@ -0,0 +1,372 @@
+use crate::excel::CellRange;
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::path::Path;
+
+const DEFAULT_LOW_COLOR: &str = "F8696B";
+const DEFAULT_MID_COLOR: &str = "FFEB84";
+const DEFAULT_HIGH_COLOR: &str = "63BE7B";
+const DEFAULT_BAR_COLOR: &str = "638EC6";
+const DEFAULT_FILL_COLOR: &str = "FFC7CE";
+const DEFAULT_FONT_COLOR: &str = "9C0006";
+
+// Regla de formato condicional sobre un rango
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionalRule {
+    // Escala de 2 o 3 colores (mínimo, [medio], máximo)
+    ColorScale {
+        low: String,
+        mid: Option<String>,
+        high: String,
+    },
+    DataBar {
+        color: String,
+    },
+    // Resalta celdas cuyo valor cumple la comparación
+    CellValue {
+        operator: ComparisonOperator,
+        value: String,
+        second_value: Option<String>,
+        fill: String,
+        font: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComparisonOperator {
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    Equal,
+    NotEqual,
+    Between,
+}
+
+impl ComparisonOperator {
+    pub fn parse(value: &str) -> Option<ComparisonOperator> {
+        match value {
+            ">" | "mayor" => Some(ComparisonOperator::GreaterThan),
+            ">=" | "mayor_igual" => Some(ComparisonOperator::GreaterThanOrEqual),
+            "<" | "menor" => Some(ComparisonOperator::LessThan),
+            "<=" | "menor_igual" => Some(ComparisonOperator::LessThanOrEqual),
+            "=" | "==" | "igual" => Some(ComparisonOperator::Equal),
+            "!=" | "<>" | "distinto" => Some(ComparisonOperator::NotEqual),
+            "entre" | "between" => Some(ComparisonOperator::Between),
+            _ => None,
+        }
+    }
+
+    // Mismos nombres en las reglas de formato y en la validación de datos
+    pub fn xml_name(&self) -> &'static str {
+        match self {
+            ComparisonOperator::GreaterThan => "greaterThan",
+            ComparisonOperator::GreaterThanOrEqual => "greaterThanOrEqual",
+            ComparisonOperator::LessThan => "lessThan",
+            ComparisonOperator::LessThanOrEqual => "lessThanOrEqual",
+            ComparisonOperator::Equal => "equal",
+            ComparisonOperator::NotEqual => "notEqual",
+            ComparisonOperator::Between => "between",
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ComparisonOperator::GreaterThan => ">",
+            ComparisonOperator::GreaterThanOrEqual => ">=",
+            ComparisonOperator::LessThan => "<",
+            ComparisonOperator::LessThanOrEqual => "<=",
+            ComparisonOperator::Equal => "=",
+            ComparisonOperator::NotEqual => "!=",
+            ComparisonOperator::Between => "entre",
+        }
+    }
+}
+
+// Opciones del comando `formato_condicional`
+#[derive(Debug, Clone)]
+pub struct ConditionalFormatOptions {
+    pub file: String,
+    pub sheet: String,
+    pub range: CellRange,
+    pub rule: ConditionalRule,
+}
+
+// Normaliza un color: acepta "FF0000", "#FF0000" o algunos nombres en español
+pub fn parse_color(value: &str) -> Result<String> {
+    let named = match value.to_lowercase().as_str() {
+        "rojo" => Some("FF0000"),
+        "verde" => Some("00B050"),
+        "amarillo" => Some("FFFF00"),
+        "azul" => Some("0070C0"),
+        "naranja" => Some("FFC000"),
+        "gris" => Some("BFBFBF"),
+        _ => None,
+    };
+    let hex = named.unwrap_or_else(|| value.trim_start_matches('#'));
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Color no válido: {} (usa RRGGBB o un nombre)", value);
+    }
+    Ok(hex.to_uppercase())
+}
+
+// Valor literal para la fórmula de la regla: números tal cual, texto entre comillas
+fn formula_literal(value: &str) -> String {
+    if value.parse::<f64>().is_ok() || value.starts_with('$') || value.starts_with('=') {
+        value.trim_start_matches('=').to_string()
+    } else {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+}
+
+// Parsea los argumentos de regla del comando:
+//   escala [min] [max] | escala3 [min] [medio] [max] | barras [color] | valor <op> <v> [v2] [relleno]
+pub fn parse_rule(args: &[&str]) -> Result<ConditionalRule> {
+    let kind = args.first().context("Falta el tipo de regla (escala, escala3, barras, valor)")?;
+    let rest = &args[1..];
+    let color_at = |idx: usize, default: &str| -> Result<String> {
+        rest.get(idx).map(|c| parse_color(c)).unwrap_or(Ok(default.to_string()))
+    };
+    match *kind {
+        "escala" => Ok(ConditionalRule::ColorScale {
+            low: color_at(0, DEFAULT_LOW_COLOR)?,
+            mid: None,
+            high: color_at(1, DEFAULT_HIGH_COLOR)?,
+        }),
+        "escala3" => Ok(ConditionalRule::ColorScale {
+            low: color_at(0, DEFAULT_LOW_COLOR)?,
+            mid: Some(color_at(1, DEFAULT_MID_COLOR)?),
+            high: color_at(2, DEFAULT_HIGH_COLOR)?,
+        }),
+        "barras" => Ok(ConditionalRule::DataBar {
+            color: color_at(0, DEFAULT_BAR_COLOR)?,
+        }),
+        "valor" => {
+            let operator = rest
+                .first()
+                .and_then(|op| ComparisonOperator::parse(op))
+                .context("Operador no válido (usa >, >=, <, <=, =, != o entre)")?;
+            let value = rest.get(1).context("Falta el valor de comparación")?.to_string();
+            let (second_value, fill_idx) = if operator == ComparisonOperator::Between {
+                (Some(rest.get(2).context("'entre' necesita dos valores")?.to_string()), 3)
+            } else {
+                (None, 2)
+            };
+            Ok(ConditionalRule::CellValue {
+                operator,
+                value,
+                second_value,
+                fill: color_at(fill_idx, DEFAULT_FILL_COLOR)?,
+                font: DEFAULT_FONT_COLOR.to_string(),
+            })
+        }
+        other => bail!("Tipo de regla desconocido: {}", other),
+    }
+}
+
+// Construye la regla desde los argumentos JSON de una llamada a herramienta
+pub fn rule_from_json(args: &Value) -> Result<ConditionalRule> {
+    let text = |key: &str| args.get(key).and_then(Value::as_str).map(str::to_string);
+    let kind = text("tipo").context("Falta 'tipo'")?;
+    let mut parts: Vec<String> = vec![kind.clone()];
+    match kind.as_str() {
+        "escala" | "escala3" | "barras" => {
+            if let Some(Value::Array(colors)) = args.get("colores") {
+                parts.extend(colors.iter().filter_map(Value::as_str).map(str::to_string));
+            }
+        }
+        "valor" => {
+            parts.push(text("operador").context("Falta 'operador'")?);
+            parts.push(json_scalar(args.get("valor")).context("Falta 'valor'")?);
+            if let Some(second) = json_scalar(args.get("valor2")) {
+                parts.push(second);
+            }
+            if let Some(color) = text("color") {
+                parts.push(color);
+            }
+        }
+        _ => {}
+    }
+    let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+    parse_rule(&parts)
+}
+
+fn json_scalar(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+// Añade la regla directamente al XML de la hoja, conservando el resto del libro
+pub fn apply(options: &ConditionalFormatOptions) -> Result<()> {
+    let path = Path::new(&options.file);
+    let mut package = XlsxPackage::open(path)?;
+
+    let dxf_id = match &options.rule {
+        ConditionalRule::CellValue { fill, font, .. } => Some(add_dxf(&mut package, fill, font)?),
+        _ => None,
+    };
+
+    let range = options.range.to_string();
+    let rule = options.rule.clone();
+    package.edit_sheet(&options.sheet, |xml| {
+        let priority = next_priority(&xml);
+        let element = format!(
+            "<conditionalFormatting sqref=\"{}\">{}</conditionalFormatting>",
+            range,
+            rule_xml(&rule, priority, dxf_id)
+        );
+        xlsx_patch::insert_worksheet_element(&xml, "conditionalFormatting", &element)
+    })?;
+    package.save(path)
+}
+
+// Resalta celdas sueltas (p. ej. las diferencias de `comparar`) con una regla que
+// siempre se cumple; `cells` son (fila, columna) desde 0 y las contiguas de una
+// fila se agrupan en un solo rango
+pub fn highlight_cells(
+    package: &mut XlsxPackage,
+    sheet: &str,
+    cells: &[(usize, usize)],
+    fill: &str,
+    font: &str,
+) -> Result<()> {
+    if cells.is_empty() {
+        return Ok(());
+    }
+    let mut sorted = cells.to_vec();
+    sorted.sort_unstable();
+    let mut ranges: Vec<CellRange> = Vec::new();
+    for (row, col) in sorted {
+        match ranges.last_mut() {
+            Some(range) if range.first_row == row && range.last_col + 1 == col => range.last_col = col,
+            _ => ranges.push(CellRange {
+                first_row: row,
+                first_col: col,
+                last_row: row,
+                last_col: col,
+            }),
+        }
+    }
+    let sqref: Vec<String> = ranges.iter().map(ToString::to_string).collect();
+    let dxf_id = add_dxf(package, fill, font)?;
+    package.edit_sheet(sheet, |xml| {
+        let element = format!(
+            "<conditionalFormatting sqref=\"{}\"><cfRule type=\"expression\" dxfId=\"{}\" priority=\"{}\"><formula>TRUE</formula></cfRule></conditionalFormatting>",
+            sqref.join(" "),
+            dxf_id,
+            next_priority(&xml)
+        );
+        xlsx_patch::insert_worksheet_element(&xml, "conditionalFormatting", &element)
+    })
+}
+
+fn rule_xml(rule: &ConditionalRule, priority: u32, dxf_id: Option<usize>) -> String {
+    match rule {
+        ConditionalRule::ColorScale { low, mid, high } => {
+            let mut cfvo = String::from("<cfvo type=\"min\"/>");
+            let mut colors = format!("<color rgb=\"FF{}\"/>", low);
+            if let Some(mid) = mid {
+                cfvo.push_str("<cfvo type=\"percentile\" val=\"50\"/>");
+                colors.push_str(&format!("<color rgb=\"FF{}\"/>", mid));
+            }
+            cfvo.push_str("<cfvo type=\"max\"/>");
+            colors.push_str(&format!("<color rgb=\"FF{}\"/>", high));
+            format!(
+                "<cfRule type=\"colorScale\" priority=\"{}\"><colorScale>{}{}</colorScale></cfRule>",
+                priority, cfvo, colors
+            )
+        }
+        ConditionalRule::DataBar { color } => format!(
+            "<cfRule type=\"dataBar\" priority=\"{}\"><dataBar><cfvo type=\"min\"/><cfvo type=\"max\"/><color rgb=\"FF{}\"/></dataBar></cfRule>",
+            priority, color
+        ),
+        ConditionalRule::CellValue {
+            operator,
+            value,
+            second_value,
+            ..
+        } => {
+            let mut formulas = format!("<formula>{}</formula>", xlsx_patch::xml_escape(&formula_literal(value)));
+            if let Some(second) = second_value {
+                formulas.push_str(&format!(
+                    "<formula>{}</formula>",
+                    xlsx_patch::xml_escape(&formula_literal(second))
+                ));
+            }
+            format!(
+                "<cfRule type=\"cellIs\" dxfId=\"{}\" priority=\"{}\" operator=\"{}\">{}</cfRule>",
+                dxf_id.unwrap_or(0),
+                priority,
+                operator.xml_name(),
+                formulas
+            )
+        }
+    }
+}
+
+// Las prioridades deben ser únicas dentro de la hoja
+fn next_priority(xml: &str) -> u32 {
+    xlsx_patch::find_tags(xml, "cfRule")
+        .iter()
+        .filter_map(|tag| xlsx_patch::xml_attr(tag, "priority")?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+// Registra un estilo diferencial (relleno + fuente) en styles.xml y devuelve su índice
+fn add_dxf(package: &mut XlsxPackage, fill: &str, font: &str) -> Result<usize> {
+    let styles = package
+        .read_part("xl/styles.xml")
+        .context("El libro no tiene xl/styles.xml")?;
+    let dxf = format!(
+        "<dxf><font><color rgb=\"FF{}\"/></font><fill><patternFill><bgColor rgb=\"FF{}\"/></patternFill></fill></dxf>",
+        font, fill
+    );
+
+    let (updated, index) = match xlsx_patch::find_element_start(&styles, "dxfs") {
+        Some(start) => {
+            let tag_end = start + styles[start..].find('>').context("styles.xml no válido")?;
+            let count: usize = xlsx_patch::xml_attr(&styles[start..=tag_end], "count")
+                .and_then(|c| c.parse().ok())
+                .unwrap_or(0);
+            let new_open = format!("<dxfs count=\"{}\">", count + 1);
+            let updated = if styles[..tag_end].ends_with('/') {
+                format!("{}{}{}</dxfs>{}", &styles[..start], new_open, dxf, &styles[tag_end + 1..])
+            } else {
+                let close = start + styles[start..].find("</dxfs>").context("styles.xml no válido")?;
+                format!(
+                    "{}{}{}{}{}",
+                    &styles[..start],
+                    new_open,
+                    &styles[tag_end + 1..close],
+                    dxf,
+                    &styles[close..]
+                )
+            };
+            (updated, count)
+        }
+        None => {
+            // dxfs va justo después de cellStyles
+            let insert_at = ["tableStyles", "colors", "extLst"]
+                .iter()
+                .filter_map(|e| xlsx_patch::find_element_start(&styles, e))
+                .min()
+                .or_else(|| styles.rfind("</styleSheet>"))
+                .context("styles.xml no válido")?;
+            let updated = format!(
+                "{}<dxfs count=\"1\">{}</dxfs>{}",
+                &styles[..insert_at],
+                dxf,
+                &styles[insert_at..]
+            );
+            (updated, 0)
+        }
+    };
+    package.write_part("xl/styles.xml", updated);
+    Ok(index)
+}
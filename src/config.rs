@@ -0,0 +1,672 @@
+use crate::azure::AzureSettings;
+use crate::budget::TokenBudget;
+use crate::compress::HistoryCompression;
+use crate::error::IAgentError;
+use crate::i18n::Lang;
+use crate::limits::Limits;
+use crate::outputs::{Naming, OutputPolicy};
+use crate::pager;
+use crate::profiles;
+use crate::provider::{MockProvider, Provider, ReplayProvider};
+use crate::registry::ToolRegistry;
+use crate::retrieval::{self, Embedder};
+use crate::sampling::Sampling;
+use crate::sandbox::Workspace;
+use crate::secrets;
+use crate::structured::JsonSettings;
+use crate::throttle::RateLimits;
+use crate::untrusted::ToolConfirmation;
+use crate::watch::WatchSettings;
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_BASE_URL: &str = "https://api.deepseek.com/v1";
+const DEFAULT_MODEL: &str = "deepseek-coder";
+const DEFAULT_SERVE_PORT: u16 = 8080;
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Las respuestas largas con herramientas pueden tardar; más allá se considera colgado
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Configuración del agente a partir del entorno y de los argumentos de línea de comandos
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Vacía si la autenticación va en una cabecera adicional
+    pub api_key: String,
+    // Endpoint completo de chat completions, sin parámetros de consulta
+    pub api_url: String,
+    // Parámetros de consulta añadidos a cada petición (p. ej. api-version)
+    pub query_params: Vec<(String, String)>,
+    // Cabeceras adicionales para pasarelas (LiteLLM, Azure, proxies corporativos)
+    pub extra_headers: Vec<(String, String)>,
+    // Azure OpenAI: la URL sale del despliegue de cada modelo y la clave va en `api-key`
+    pub azure: Option<AzureSettings>,
+    pub model: String,
+    pub persona: Option<String>,
+    // Añadir una hoja "Léeme" a los libros generados
+    pub readme_sheet: bool,
+    // Mostrar el tiempo de cada comando y herramienta
+    pub verbose: bool,
+    pub context_budget: TokenBudget,
+    // Cuándo se resumen los turnos antiguos de la conversación
+    pub history_compression: HistoryCompression,
+    // Modelo para esos resúmenes (IAGENT_SUMMARY_MODEL); por defecto el mismo
+    pub summary_model: Option<String>,
+    // Validez de la caché de respuestas (IAGENT_CACHE_TTL); None, lo predeterminado, la desactiva
+    pub cache_ttl: Option<Duration>,
+    // Clave del proyecto para cifrar y descifrar columnas sensibles
+    pub project_key: Option<String>,
+    // Columnas (por encabezado) que se cifran en todos los libros generados
+    pub encrypt_columns: Vec<String>,
+    // Idioma de la interfaz (--lang, IAGENT_LANG o LANG)
+    pub lang: Lang,
+    // Idioma de las respuestas fijado con IAGENT_REPLY_LANG (`idioma` en un
+    // perfil); sin él se detecta en cada pregunta
+    pub reply_lang: Option<Lang>,
+    // Empezar con el recorrido guiado (`ia_agent tour`)
+    pub start_tour: bool,
+    // Recalcular las fórmulas al leer libros, como `leer_excel ... --evaluar`
+    pub evaluate_formulas: bool,
+    // Directorio al que se limitan las herramientas del modelo
+    pub workspace: Workspace,
+    // Dónde se escriben los archivos generados y qué hacer si ya existen
+    pub outputs: OutputPolicy,
+    // Vectores para la búsqueda por similitud en hojas grandes
+    pub embeddings: Embedder,
+    // Filas de datos a partir de las que se indexa una hoja; 0 lo desactiva
+    pub retrieval_rows: usize,
+    // Guion a ejecutar sin interacción (`--guion <archivo>`)
+    pub script: Option<PathBuf>,
+    // Dirección del modo servidor (`ia_agent serve --port 8080`)
+    pub serve: Option<SocketAddr>,
+    // Token que deben enviar los clientes del servidor (`Authorization: Bearer ...`)
+    pub serve_token: Option<String>,
+    // Directorio y tarea del modo vigilancia (`ia_agent watch <dir> --tarea <tarea.json>`)
+    pub watch: Option<WatchSettings>,
+    // Solo diagnosticar la configuración (`ia_agent doctor`)
+    pub doctor: bool,
+    // Modelo sin red (IAGENT_MOCK o IAGENT_REPLAY) en lugar de la API
+    pub provider: Option<Arc<dyn Provider>>,
+    // Directorio donde se graban las respuestas de la API (IAGENT_RECORD)
+    pub record_dir: Option<PathBuf>,
+    pub http: HttpSettings,
+    // Herramientas del modelo: las de Excel y las del manifiesto IAGENT_TOOLS
+    pub tools: Arc<ToolRegistry>,
+    // Respuestas en JSON (extraer_json): modo del proveedor y reintentos
+    pub json: JsonSettings,
+    // Temperatura, top_p y semilla de las peticiones (IAGENT_TEMPERATURE, IAGENT_TOP_P, IAGENT_SEED)
+    pub sampling: Sampling,
+    // Cuándo se confirman las llamadas a herramientas (IAGENT_CONFIRM_TOOLS)
+    pub confirm_tools: ToolConfirmation,
+    // Tamaño de archivo, filas, celdas y tiempo máximos de las operaciones con libros
+    pub limits: Limits,
+    // Peticiones y tokens por minuto a la API (IAGENT_REQUESTS_PER_MINUTE, IAGENT_TOKENS_PER_MINUTE)
+    pub rate_limits: RateLimits,
+    // Recordar la estructura de los libros leídos entre sesiones (IAGENT_SCHEMA_MEMORY)
+    pub schema_memory: bool,
+    // Filas por página de los resultados (--page-size o IAGENT_PAGE_SIZE); 0 no pagina
+    pub page_size: usize,
+    // Archivo en el que se registran los eventos de la sesión (IAGENT_EVENTS_LOG)
+    pub events_log: Option<PathBuf>,
+    // Registro de auditoría de los archivos escritos y verificados (IAGENT_AUDIT_LOG)
+    pub audit_log: Option<PathBuf>,
+    // Perfil de iagent.toml aplicado (--profile, IAGENT_PROFILE o `perfil`)
+    pub profile: Option<String>,
+}
+
+// Conexión con la API: tiempos máximos, proxy y certificados de la red corporativa
+#[derive(Debug, Clone)]
+pub struct HttpSettings {
+    pub connect_timeout: Duration,
+    // Tiempo máximo de cada petición completa; None espera indefinidamente
+    pub timeout: Option<Duration>,
+    // Proxy para todas las peticiones; sin él se usan HTTP_PROXY, HTTPS_PROXY y NO_PROXY
+    pub proxy: Option<String>,
+    // Archivo PEM con certificados raíz adicionales
+    pub ca_cert: Option<PathBuf>,
+}
+
+impl HttpSettings {
+    fn from_env() -> Result<HttpSettings> {
+        let connect_timeout = match profiles::var("IAGENT_CONNECT_TIMEOUT") {
+            Ok(value) => parse_duration("IAGENT_CONNECT_TIMEOUT", &value)?,
+            Err(_) => DEFAULT_CONNECT_TIMEOUT,
+        };
+        let timeout = match profiles::var("IAGENT_TIMEOUT") {
+            Ok(value) => Some(parse_duration("IAGENT_TIMEOUT", &value)?).filter(|timeout| !timeout.is_zero()),
+            Err(_) => Some(DEFAULT_TIMEOUT),
+        };
+        let ca_cert = profiles::var("IAGENT_CA_CERT").ok().filter(|path| !path.trim().is_empty()).map(PathBuf::from);
+        if let Some(path) = &ca_cert {
+            if !path.is_file() {
+                bail!("No existe el certificado de IAGENT_CA_CERT: {}", path.display());
+            }
+        }
+        Ok(HttpSettings {
+            connect_timeout,
+            timeout,
+            proxy: profiles::var("IAGENT_PROXY").ok().filter(|proxy| !proxy.trim().is_empty()),
+            ca_cert,
+        })
+    }
+}
+
+impl Config {
+    // Los fallos de configuración llegan como IAgentError::Config
+    pub fn load() -> Result<Config> {
+        Config::from_env().map_err(|e| IAgentError::Config(format!("{:#}", e)).into())
+    }
+
+    // Copia con la temperatura propia de un comando (IAGENT_TEMPERATURES)
+    pub fn for_command(&self, command: &str) -> Config {
+        let mut config = self.clone();
+        config.sampling = self.sampling.for_command(command);
+        config
+    }
+
+    // Endpoint de chat completions del modelo activo: con Azure, el de su despliegue
+    pub fn completions_url(&self) -> String {
+        match &self.azure {
+            Some(azure) => azure.completions_url(&self.model),
+            None => self.api_url.clone(),
+        }
+    }
+
+    // Proveedor de la API, elegido como el endpoint: Azure, o el dominio de la URL
+    // (api.deepseek.com → deepseek, api.openai.com → openai, localhost). Separa las
+    // tarifas, el consumo y las entradas de la caché de cada proveedor.
+    pub fn provider_name(&self) -> String {
+        match &self.azure {
+            Some(_) => "azure".to_string(),
+            None => provider_from_url(&self.api_url),
+        }
+    }
+
+    fn from_env() -> Result<Config> {
+        let args = CliArgs::parse(env::args().skip(1))?;
+        // El perfil escribe sus variables antes de leerlas; si ya hay uno aplicado
+        // (`perfil <nombre>` en la sesión) se conserva
+        let profile = match profiles::active() {
+            Some(name) => Some(name),
+            None => profiles::activate(args.profile.as_deref())?,
+        };
+        let serve = args.serve_address()?;
+        let watch = args.watch_settings()?;
+        let extra_headers = match profiles::var("IAGENT_EXTRA_HEADERS") {
+            Ok(value) => parse_headers(&value)?,
+            Err(_) => Vec::new(),
+        };
+        let provider: Option<Arc<dyn Provider>> = match (profiles::var("IAGENT_MOCK"), profiles::var("IAGENT_REPLAY")) {
+            (Ok(_), Ok(_)) => bail!("IAGENT_MOCK e IAGENT_REPLAY no se pueden usar a la vez"),
+            (Ok(file), _) => Some(Arc::new(MockProvider::from_file(Path::new(&file))?)),
+            (_, Ok(dir)) => Some(Arc::new(ReplayProvider::new(Path::new(&dir))?)),
+            _ => None,
+        };
+        let record_dir = profiles::var("IAGENT_RECORD").ok().filter(|dir| !dir.is_empty()).map(PathBuf::from);
+        if record_dir.is_some() && provider.is_some() {
+            bail!("IAGENT_RECORD graba respuestas de la API; no se combina con IAGENT_MOCK ni IAGENT_REPLAY");
+        }
+        let azure = AzureSettings::from_env()?;
+        let header_auth = extra_headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("authorization") || name.eq_ignore_ascii_case("api-key"));
+        // `ia_agent clave` vuelve a pedirla aunque ya haya una
+        let api_key = if args.store_key {
+            secrets::prompt_and_store("Nueva clave de la API")?.context("`ia_agent clave` necesita una terminal")?
+        } else {
+            // Con Azure vale también la variable de sus propias herramientas
+            let azure_key = azure.as_ref().and_then(|_| profiles::var("AZURE_OPENAI_API_KEY").ok()).filter(|key| !key.is_empty());
+            let found = match azure_key {
+                Some(key) => Some(key),
+                None => secrets::find_api_key()?,
+            };
+            match found {
+                Some(key) => key,
+                // Sin red tampoco hace falta clave, y el diagnóstico informa de que falta
+                None if header_auth || provider.is_some() || args.doctor => String::new(),
+                None => secrets::prompt_and_store(
+                    "No se encontró la clave de la API (DEEPSEEK_API_KEY, IAGENT_API_KEY_CMD ni el llavero)",
+                )?
+                .context("No se encontró DEEPSEEK_API_KEY en el entorno")?,
+            }
+        };
+        // DEEPSEEK_API_URL es el endpoint completo; si no, se construye desde la URL base
+        let api_url = match profiles::var("DEEPSEEK_API_URL") {
+            Ok(url) => url,
+            Err(_) => completions_url(
+                &profiles::var("IAGENT_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+                profiles::var("IAGENT_DEPLOYMENT").ok().as_deref(),
+            ),
+        };
+        let mut query_params = match profiles::var("IAGENT_QUERY_PARAMS") {
+            Ok(value) => parse_query(&value)?,
+            Err(_) => Vec::new(),
+        };
+        if let Some(azure) = &azure {
+            if !query_params.iter().any(|(name, _)| name == "api-version") {
+                query_params.push(("api-version".to_string(), azure.api_version.clone()));
+            }
+        }
+        if let Ok(version) = profiles::var("IAGENT_API_VERSION") {
+            query_params.retain(|(name, _)| name != "api-version");
+            query_params.push(("api-version".to_string(), version));
+        }
+        // La caché de respuestas se activa con IAGENT_CACHE_TTL (p. ej. 1d): con una
+        // temperatura mayor que 0, repetir una pregunta debería poder dar otra respuesta.
+        // 0 o --sin-cache la desactivan.
+        let cache_ttl = match profiles::var("IAGENT_CACHE_TTL") {
+            _ if args.no_cache => None,
+            Ok(value) => Some(parse_duration("IAGENT_CACHE_TTL", &value)?).filter(|ttl| !ttl.is_zero()),
+            Err(_) => None,
+        };
+        let project_key = match (profiles::var("IAGENT_PROJECT_KEY"), profiles::var("IAGENT_PROJECT_KEY_FILE")) {
+            (Ok(key), _) => Some(key),
+            (Err(_), Ok(file)) => Some(
+                std::fs::read_to_string(&file)
+                    .context(format!("No se pudo leer la clave del proyecto de {}", file))?
+                    .trim()
+                    .to_string(),
+            ),
+            _ => None,
+        }
+        .filter(|key| !key.is_empty());
+        let encrypt_columns: Vec<String> = args
+            .encrypt_columns
+            .or_else(|| profiles::var("IAGENT_ENCRYPT_COLUMNS").ok())
+            .map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !encrypt_columns.is_empty() && project_key.is_none() {
+            bail!("Para cifrar columnas define la clave del proyecto en IAGENT_PROJECT_KEY o IAGENT_PROJECT_KEY_FILE");
+        }
+        // Por defecto, el directorio desde el que se lanza el agente
+        let workspace_dir = args
+            .workspace
+            .or_else(|| profiles::var("IAGENT_WORKSPACE").ok().map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("."));
+        let workspace = Workspace::new(&workspace_dir)?;
+        let naming = match profiles::var("IAGENT_OUTPUT_NAMING") {
+            Ok(value) => Naming::parse(&value).context(format!(
+                "Valor no válido en IAGENT_OUTPUT_NAMING: '{}' (usa sobrescribir, sufijo o fecha)",
+                value
+            ))?,
+            Err(_) => Naming::default(),
+        };
+        let outputs = OutputPolicy {
+            dir: args
+                .output_dir
+                .or_else(|| profiles::var("IAGENT_OUTPUT_DIR").ok().filter(|dir| !dir.trim().is_empty()).map(PathBuf::from)),
+            naming,
+        };
+        // Con IAGENT_EMBEDDINGS_MODEL se usa la API de embeddings del mismo proveedor
+        // (o IAGENT_EMBEDDINGS_URL); si no, vectores locales
+        let embeddings = match profiles::var("IAGENT_EMBEDDINGS_MODEL") {
+            Ok(model) if !model.trim().is_empty() => Embedder::Api {
+                url: profiles::var("IAGENT_EMBEDDINGS_URL").unwrap_or_else(|_| match &azure {
+                    Some(azure) => azure.embeddings_url(model.trim()),
+                    None => embeddings_url(&api_url),
+                }),
+                model: model.trim().to_string(),
+            },
+            _ => Embedder::Local,
+        };
+        let retrieval_rows = match profiles::var("IAGENT_RETRIEVAL_ROWS") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .context(format!("Valor no válido en IAGENT_RETRIEVAL_ROWS: '{}'", value))?,
+            Err(_) => retrieval::DEFAULT_MIN_ROWS,
+        };
+        let model = profiles::var("DEEPSEEK_MODEL").unwrap_or_else(|_| DEFAULT_MODEL.to_string());
+        let http = HttpSettings::from_env()?;
+        let page_size = match (args.page_size, profiles::var("IAGENT_PAGE_SIZE")) {
+            (Some(size), _) => size,
+            (None, Ok(value)) => value
+                .trim()
+                .parse()
+                .context(format!("Valor no válido en IAGENT_PAGE_SIZE: '{}'", value))?,
+            (None, Err(_)) => pager::DEFAULT_PAGE_SIZE,
+        };
+        let tools = Arc::new(ToolRegistry::from_env(&http)?);
+
+        Ok(Config {
+            api_key,
+            api_url,
+            query_params,
+            extra_headers,
+            azure,
+            model,
+            persona: args.persona.or_else(|| profiles::var("IAGENT_PERSONA").ok()),
+            readme_sheet: args.readme_sheet
+                || profiles::var("IAGENT_README_SHEET").is_ok_and(|v| is_enabled(&v)),
+            verbose: args.verbose || profiles::var("IAGENT_VERBOSE").is_ok_and(|v| is_enabled(&v)),
+            context_budget: TokenBudget::from_env(),
+            history_compression: HistoryCompression::from_env(),
+            summary_model: profiles::var("IAGENT_SUMMARY_MODEL").ok().filter(|model| !model.trim().is_empty()),
+            cache_ttl,
+            project_key,
+            encrypt_columns,
+            lang: args.lang.unwrap_or_else(Lang::from_env),
+            reply_lang: profiles::var("IAGENT_REPLY_LANG").ok().and_then(|value| Lang::parse(&value)),
+            start_tour: args.tour,
+            evaluate_formulas: profiles::var("IAGENT_EVALUATE_FORMULAS").is_ok_and(|v| is_enabled(&v)),
+            workspace,
+            outputs,
+            embeddings,
+            retrieval_rows,
+            script: args.script,
+            serve,
+            serve_token: profiles::var("IAGENT_SERVE_TOKEN").ok().filter(|token| !token.is_empty()),
+            watch,
+            doctor: args.doctor,
+            provider,
+            record_dir,
+            http,
+            tools,
+            json: JsonSettings::from_env()?,
+            sampling: Sampling::from_env()?,
+            confirm_tools: ToolConfirmation::from_env()?,
+            limits: Limits::from_env()?,
+            rate_limits: RateLimits::from_env()?,
+            schema_memory: !profiles::var("IAGENT_SCHEMA_MEMORY").is_ok_and(|v| matches!(v.trim(), "0" | "no" | "false")),
+            page_size,
+            events_log: profiles::var("IAGENT_EVENTS_LOG").ok().filter(|path| !path.trim().is_empty()).map(PathBuf::from),
+            audit_log: profiles::var("IAGENT_AUDIT_LOG").ok().filter(|path| !path.trim().is_empty()).map(PathBuf::from),
+            profile,
+        })
+    }
+}
+
+// `{base}/chat/completions`, o `{base}/deployments/{nombre}/chat/completions`
+// para pasarelas que enrutan por despliegue
+fn completions_url(base: &str, deployment: Option<&str>) -> String {
+    let base = base.trim_end_matches('/');
+    match deployment {
+        Some(deployment) if !deployment.is_empty() => {
+            format!("{}/deployments/{}/chat/completions", base, deployment)
+        }
+        _ => format!("{}/chat/completions", base),
+    }
+}
+
+// Endpoint de embeddings junto al de chat completions
+fn provider_from_url(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?']).next().unwrap_or(rest);
+    let host = host.rsplit_once(':').filter(|(_, port)| port.parse::<u16>().is_ok()).map_or(host, |(host, _)| host);
+    let host = host.to_lowercase();
+    // Una IP se queda como está; de un nombre, el dominio sin el sufijo
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return host;
+    }
+    match host.rsplit('.').nth(1) {
+        Some(domain) => domain.to_string(),
+        None => host,
+    }
+}
+
+fn embeddings_url(api_url: &str) -> String {
+    match api_url.strip_suffix("/chat/completions") {
+        Some(base) => format!("{}/embeddings", base),
+        None => format!("{}/embeddings", api_url.trim_end_matches('/')),
+    }
+}
+
+// "Nombre: valor; Otro: valor"
+fn parse_headers(value: &str) -> Result<Vec<(String, String)>> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .map(|header| {
+            let (name, value) = header
+                .split_once(':')
+                .context(format!("Cabecera no válida en IAGENT_EXTRA_HEADERS: '{}' (usa Nombre: valor)", header))?;
+            let name = name.trim();
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .context(format!("Nombre de cabecera no válido: '{}'", name))?;
+            reqwest::header::HeaderValue::from_str(value.trim())
+                .context(format!("Valor no válido para la cabecera '{}'", name))?;
+            Ok((name.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+// "clave=valor&otra=valor"
+fn parse_query(value: &str) -> Result<Vec<(String, String)>> {
+    value
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair
+                .split_once('=')
+                .context(format!("Parámetro no válido en IAGENT_QUERY_PARAMS: '{}'", pair))?;
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+// Segundos, o un número con sufijo s, m, h o d ("90", "30m", "12h", "7d")
+pub fn parse_duration(variable: &str, value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => value.split_at(idx),
+        None => (value, "s"),
+    };
+    let multiplier = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => bail!("Duración no válida en {}: '{}' (usa p. ej. 3600, 30m, 12h o 7d)", variable, value),
+    };
+    let number: u64 = number
+        .parse()
+        .context(format!("Duración no válida en {}: '{}'", variable, value))?;
+    Ok(Duration::from_secs(number * multiplier))
+}
+
+fn is_enabled(value: &str) -> bool {
+    matches!(value, "1" | "si" | "sí" | "true")
+}
+
+// Argumentos reconocidos en la línea de comandos
+#[derive(Debug, Default)]
+struct CliArgs {
+    persona: Option<String>,
+    readme_sheet: bool,
+    verbose: bool,
+    no_cache: bool,
+    encrypt_columns: Option<String>,
+    lang: Option<Lang>,
+    tour: bool,
+    workspace: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    script: Option<PathBuf>,
+    serve: bool,
+    port: Option<u16>,
+    host: Option<String>,
+    store_key: bool,
+    doctor: bool,
+    watch_dir: Option<PathBuf>,
+    watch_task: Option<PathBuf>,
+    page_size: Option<usize>,
+    profile: Option<String>,
+}
+
+impl CliArgs {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<CliArgs> {
+        let mut parsed = CliArgs::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--persona" => {
+                    parsed.persona = Some(args.next().context("--persona requiere un nombre")?);
+                }
+                "--leeme" => parsed.readme_sheet = true,
+                "--verbose" | "-v" => parsed.verbose = true,
+                "--sin-cache" => parsed.no_cache = true,
+                "tour" => parsed.tour = true,
+                "clave" | "api-key" => parsed.store_key = true,
+                "doctor" | "ping" => parsed.doctor = true,
+                "serve" | "servir" => parsed.serve = true,
+                "--port" | "--puerto" => {
+                    let value = args.next().context("--port requiere un número de puerto")?;
+                    parsed.port = Some(value.parse().context(format!("Puerto no válido: {}", value))?);
+                }
+                "watch" | "vigilar" => {
+                    parsed.watch_dir = Some(PathBuf::from(args.next().context("watch requiere un directorio")?));
+                }
+                "--tarea" | "--task" => {
+                    parsed.watch_task = Some(PathBuf::from(args.next().context("--tarea requiere un archivo JSON")?));
+                }
+                "--host" => parsed.host = Some(args.next().context("--host requiere una dirección")?),
+                "--lang" => {
+                    let value = args.next().context("--lang requiere un idioma (es o en)")?;
+                    parsed.lang = Some(Lang::parse(&value).context(format!("Idioma no soportado: {} (usa es o en)", value))?);
+                }
+                "--espacio-trabajo" => {
+                    parsed.workspace = Some(PathBuf::from(
+                        args.next().context("--espacio-trabajo requiere un directorio")?,
+                    ));
+                }
+                "--dir-salida" | "--output-dir" => {
+                    parsed.output_dir = Some(PathBuf::from(args.next().context("--dir-salida requiere un directorio")?));
+                }
+                "--profile" | "--perfil" => {
+                    parsed.profile = Some(args.next().context("--profile requiere el nombre de un perfil de iagent.toml")?);
+                }
+                "--guion" => {
+                    parsed.script = Some(PathBuf::from(args.next().context("--guion requiere un archivo")?));
+                }
+                "--page-size" | "--por-pagina" => {
+                    let value = args.next().context("--page-size requiere un número de filas")?;
+                    parsed.page_size = Some(value.parse().context(format!("Número de filas no válido: {}", value))?);
+                }
+                "--cifrar" => {
+                    parsed.encrypt_columns =
+                        Some(args.next().context("--cifrar requiere una lista de columnas")?);
+                }
+                other => bail!("Argumento desconocido: {}", other),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn watch_settings(&self) -> Result<Option<WatchSettings>> {
+        match (&self.watch_dir, &self.watch_task) {
+            (Some(dir), Some(task)) => Ok(Some(WatchSettings {
+                dir: dir.clone(),
+                task: task.clone(),
+            })),
+            (Some(_), None) => bail!("`ia_agent watch <dir>` requiere --tarea <tarea.json>"),
+            (None, Some(_)) => bail!("--tarea solo se usa con `ia_agent watch <dir>`"),
+            (None, None) => Ok(None),
+        }
+    }
+
+    // Por defecto solo escucha en la máquina local
+    fn serve_address(&self) -> Result<Option<SocketAddr>> {
+        if !self.serve {
+            if self.port.is_some() || self.host.is_some() {
+                bail!("--port y --host solo se usan con `ia_agent serve`");
+            }
+            return Ok(None);
+        }
+        let host = self.host.as_deref().unwrap_or("127.0.0.1");
+        let address = format!("{}:{}", host, self.port.unwrap_or(DEFAULT_SERVE_PORT));
+        let parsed = address
+            .to_socket_addrs()
+            .context(format!("Dirección no válida: {}", address))?
+            .next()
+            .context(format!("Dirección no válida: {}", address))?;
+        Ok(Some(parsed))
+    }
+}
+
+// Configuración de las pruebas del bucle de herramientas: el modelo es `provider`,
+// las herramientas trabajan en `workspace` y nada se lee del entorno ni de la red
+#[cfg(test)]
+impl Config {
+    pub fn offline(provider: Arc<dyn Provider>, workspace: &Path) -> Config {
+        let http = HttpSettings {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: Some(DEFAULT_TIMEOUT),
+            proxy: None,
+            ca_cert: None,
+        };
+        Config {
+            api_key: String::new(),
+            api_url: completions_url(DEFAULT_BASE_URL, None),
+            query_params: Vec::new(),
+            extra_headers: Vec::new(),
+            azure: None,
+            model: DEFAULT_MODEL.to_string(),
+            persona: None,
+            readme_sheet: false,
+            verbose: false,
+            context_budget: TokenBudget {
+                per_item: 1_500,
+                total: 8_000,
+            },
+            history_compression: HistoryCompression {
+                threshold: 0,
+                keep_messages: 0,
+            },
+            summary_model: None,
+            cache_ttl: None,
+            project_key: None,
+            encrypt_columns: Vec::new(),
+            lang: Lang::Es,
+            reply_lang: None,
+            start_tour: false,
+            evaluate_formulas: false,
+            workspace: Workspace::new(workspace).expect("espacio de trabajo de la prueba"),
+            outputs: OutputPolicy::default(),
+            embeddings: Embedder::Local,
+            retrieval_rows: retrieval::DEFAULT_MIN_ROWS,
+            script: None,
+            serve: None,
+            serve_token: None,
+            watch: None,
+            doctor: false,
+            provider: Some(provider),
+            record_dir: None,
+            http,
+            tools: Arc::new(ToolRegistry::default()),
+            json: JsonSettings {
+                mode: Default::default(),
+                retries: 0,
+            },
+            sampling: Sampling::default(),
+            confirm_tools: ToolConfirmation::Never,
+            limits: Limits::default(),
+            rate_limits: RateLimits::default(),
+            schema_memory: false,
+            page_size: 0,
+            events_log: None,
+            audit_log: None,
+            profile: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provider_comes_from_the_endpoint_domain() {
+        assert_eq!(provider_from_url("https://api.deepseek.com/v1/chat/completions"), "deepseek");
+        assert_eq!(provider_from_url("https://api.openai.com/v1/chat/completions"), "openai");
+        assert_eq!(provider_from_url("http://localhost:4000/chat/completions"), "localhost");
+        assert_eq!(provider_from_url("http://127.0.0.1:8765/v1/chat/completions"), "127.0.0.1");
+    }
+}
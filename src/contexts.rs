@@ -0,0 +1,374 @@
+// Contextos con nombre (`contexto crear|usar|borrar|lista`): cada uno tiene su
+// propio historial, libros cargados e índices de búsqueda, para trabajar con
+// hojas sin relación entre sí sin que una conversación contamine a la otra.
+// `contexto ver|limpiar|quitar` muestran y recortan el historial del activo, que
+// es lo que se envía al modelo en cada pregunta. `punto_de_control <nombre>` guarda
+// una copia del contexto activo y `volver <nombre>` la recupera, para probar un
+// camino del análisis y deshacerlo si no lleva a nada.
+use crate::budget;
+use crate::llm::Message;
+use crate::retrieval::Retriever;
+use crate::untrusted;
+use crate::workbook_cache::WorkbookCache;
+use anyhow::{bail, Context, Result};
+
+pub const DEFAULT_CONTEXT: &str = "principal";
+// Caracteres de cada mensaje en `contexto ver`
+const PREVIEW_CHARS: usize = 90;
+
+// Estado de una conversación
+#[derive(Clone)]
+pub struct Conversation {
+    pub history: Vec<Message>,
+    pub workbooks: WorkbookCache,
+    pub retriever: Retriever,
+}
+
+pub enum ContextCommand {
+    List,
+    Create(String),
+    Use(String),
+    Delete(String),
+    // Mensajes del historial activo, o el texto completo de uno
+    Show(Option<usize>),
+    // Todo salvo el prompt de sistema, o solo los datos de archivos
+    Clear { data_only: bool },
+    Remove(usize),
+}
+
+impl ContextCommand {
+    // `contexto [lista|crear|usar|borrar] [nombre]`, `contexto ver [n]`,
+    // `contexto limpiar [datos]` y `contexto quitar <n>`, también con los verbos en inglés
+    // (los argumentos ya separados, sin `contexto`)
+    pub fn parse(args: &[&str]) -> Option<ContextCommand> {
+        let mut words = args.iter().copied();
+        let verb = words.next();
+        let name = words.next().map(str::to_string);
+        if words.next().is_some() {
+            return None;
+        }
+        match (verb, name) {
+            (None | Some("lista" | "list"), None) => Some(ContextCommand::List),
+            (Some("crear" | "create" | "new"), Some(name)) => Some(ContextCommand::Create(name)),
+            (Some("usar" | "use" | "switch"), Some(name)) => Some(ContextCommand::Use(name)),
+            (Some("borrar" | "delete"), Some(name)) => Some(ContextCommand::Delete(name)),
+            (Some("ver" | "show"), None) => Some(ContextCommand::Show(None)),
+            (Some("ver" | "show"), Some(n)) => n.parse().ok().map(|n| ContextCommand::Show(Some(n))),
+            (Some("limpiar" | "clear"), None) => Some(ContextCommand::Clear { data_only: false }),
+            (Some("limpiar" | "clear"), Some(what)) if matches!(what.as_str(), "datos" | "data") => {
+                Some(ContextCommand::Clear { data_only: true })
+            }
+            (Some("quitar" | "remove"), Some(n)) => n.parse().ok().map(ContextCommand::Remove),
+            _ => None,
+        }
+    }
+}
+
+// Resultado de `Contexts::switch`, con la conversación que queda activa
+pub enum Switch {
+    Switched(Conversation),
+    AlreadyActive(Conversation),
+    Missing(Conversation),
+}
+
+// Copia de un contexto en un momento de la sesión
+struct Checkpoint {
+    context: String,
+    name: String,
+    conversation: Conversation,
+}
+
+// Contextos guardados; el activo vive en las variables de la sesión y no está aquí
+pub struct Contexts {
+    active: String,
+    stored: Vec<(String, Conversation)>,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl Default for Contexts {
+    fn default() -> Self {
+        Contexts {
+            active: DEFAULT_CONTEXT.to_string(),
+            stored: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+}
+
+impl Contexts {
+    pub fn active(&self) -> &str {
+        &self.active
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.active.eq_ignore_ascii_case(name) || self.stored.iter().any(|(stored, _)| stored.eq_ignore_ascii_case(name))
+    }
+
+    pub fn create(&mut self, name: &str, conversation: Conversation) -> Result<()> {
+        if self.exists(name) {
+            bail!("Ya existe el contexto '{}'", name);
+        }
+        self.stored.push((name.to_string(), conversation));
+        Ok(())
+    }
+
+    // Guarda el contexto activo y devuelve el pedido, que pasa a ser el activo; si
+    // ya lo es o no existe, devuelve el actual sin cambiar nada
+    pub fn switch(&mut self, name: &str, current: Conversation) -> Switch {
+        if self.active.eq_ignore_ascii_case(name) {
+            return Switch::AlreadyActive(current);
+        }
+        let Some(idx) = self.stored.iter().position(|(stored, _)| stored.eq_ignore_ascii_case(name)) else {
+            return Switch::Missing(current);
+        };
+        let (name, next) = self.stored.remove(idx);
+        let previous = std::mem::replace(&mut self.active, name);
+        self.stored.push((previous, current));
+        Switch::Switched(next)
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<()> {
+        if self.active.eq_ignore_ascii_case(name) {
+            bail!("No se puede borrar el contexto activo; cambia antes a otro con contexto usar <nombre>");
+        }
+        let before = self.stored.len();
+        self.stored.retain(|(stored, _)| !stored.eq_ignore_ascii_case(name));
+        if self.stored.len() == before {
+            bail!("No existe el contexto '{}'", name);
+        }
+        self.checkpoints.retain(|checkpoint| !checkpoint.context.eq_ignore_ascii_case(name));
+        Ok(())
+    }
+
+    // Guarda una copia del contexto activo con el nombre `name`, que sustituye a la
+    // que tuviera; devuelve si ya existía
+    pub fn checkpoint(&mut self, name: &str, conversation: Conversation) -> bool {
+        let before = self.checkpoints.len();
+        let active = &self.active;
+        self.checkpoints.retain(|c| !(c.context == *active && c.name.eq_ignore_ascii_case(name)));
+        let replaced = self.checkpoints.len() < before;
+        self.checkpoints.push(Checkpoint {
+            context: self.active.clone(),
+            name: name.to_string(),
+            conversation,
+        });
+        replaced
+    }
+
+    // Copia del punto de control `name` del contexto activo; el punto se conserva,
+    // así se puede volver a él más de una vez
+    pub fn rewind(&self, name: &str) -> Result<Conversation> {
+        let checkpoint = self
+            .checkpoints
+            .iter()
+            .find(|c| c.context == self.active && c.name.eq_ignore_ascii_case(name));
+        match checkpoint {
+            Some(checkpoint) => Ok(checkpoint.conversation.clone()),
+            None if self.checkpoint_names().is_empty() => {
+                bail!("No hay puntos de control en el contexto '{}'; crea uno con punto_de_control <nombre>", self.active)
+            }
+            None => bail!(
+                "No existe el punto de control '{}' (puntos de control: {})",
+                name,
+                self.checkpoint_names().join(", ")
+            ),
+        }
+    }
+
+    // Una línea por punto de control del contexto activo, del más antiguo al más reciente
+    pub fn checkpoints(&self) -> Vec<String> {
+        self.checkpoints
+            .iter()
+            .filter(|c| c.context == self.active)
+            .map(|c| describe(&c.name, &c.conversation.history, &c.conversation.workbooks, false))
+            .collect()
+    }
+
+    fn checkpoint_names(&self) -> Vec<&str> {
+        self.checkpoints.iter().filter(|c| c.context == self.active).map(|c| c.name.as_str()).collect()
+    }
+
+    // Una línea por contexto (nombre, mensajes y libros cargados), empezando por el activo
+    pub fn list(&self, history: &[Message], workbooks: &WorkbookCache) -> Vec<String> {
+        let mut lines = vec![describe(&self.active, history, workbooks, true)];
+        lines.extend(
+            self.stored
+                .iter()
+                .map(|(name, conversation)| describe(name, &conversation.history, &conversation.workbooks, false)),
+        );
+        lines
+    }
+}
+
+// Una línea por mensaje del historial, numerados desde 0 (el prompt de sistema),
+// con su tipo, los tokens estimados y el principio del texto
+pub fn inspect(history: &[Message]) -> Vec<String> {
+    let mut lines: Vec<String> = history
+        .iter()
+        .enumerate()
+        .map(|(idx, message)| {
+            let preview = match &message.tool_calls {
+                Some(calls) if message.content.trim().is_empty() => {
+                    let names: Vec<&str> = calls.iter().map(|call| call.function.name.as_str()).collect();
+                    format!("llama a {}", names.join(", "))
+                }
+                _ => preview(body(message)),
+            };
+            format!("[{}] {} (~{} tokens): {}", idx, kind(message), budget::estimate_tokens(&message.content), preview)
+        })
+        .collect();
+    let total: usize = history.iter().map(|message| budget::estimate_tokens(&message.content)).sum();
+    lines.push(format!("Total: {} mensajes, ~{} tokens", history.len(), total));
+    lines
+}
+
+// Texto completo de un mensaje para `contexto ver <n>`
+pub fn message_text(history: &[Message], idx: usize) -> Result<String> {
+    let message = history.get(idx).context(format!("No hay mensaje {} (el historial tiene {})", idx, history.len()))?;
+    let mut text = format!("[{}] {}\n{}", idx, kind(message), message.content);
+    for call in message.tool_calls.iter().flatten() {
+        text.push_str(&format!("\n→ {}({})", call.function.name, call.function.arguments));
+    }
+    Ok(text)
+}
+
+// Quita el mensaje `idx`; una llamada a herramientas se lleva sus respuestas, que
+// sin ella la API rechaza. Devuelve cuántos mensajes se quitaron.
+pub fn remove_message(history: &mut Vec<Message>, idx: usize) -> Result<usize> {
+    let message = history.get(idx).context(format!("No hay mensaje {} (el historial tiene {})", idx, history.len()))?;
+    if idx == 0 {
+        bail!("El mensaje 0 es el prompt de sistema y no se puede quitar");
+    }
+    if message.role == "tool" {
+        let call = history[..idx].iter().rposition(|m| m.tool_calls.is_some()).unwrap_or(idx);
+        bail!("El mensaje {} es la respuesta de una herramienta; quita la llamada, el mensaje {}", idx, call);
+    }
+    let ids: Vec<String> = message.tool_calls.iter().flatten().map(|call| call.id.clone()).collect();
+    let before = history.len();
+    history.remove(idx);
+    while history
+        .get(idx)
+        .is_some_and(|next| next.role == "tool" && next.tool_call_id.as_ref().is_some_and(|id| ids.contains(id)))
+    {
+        history.remove(idx);
+    }
+    Ok(before - history.len())
+}
+
+// Vacía el historial salvo el prompt de sistema, o quita solo los datos de
+// archivos. Devuelve cuántos mensajes se quitaron.
+pub fn clear(history: &mut Vec<Message>, data_only: bool) -> usize {
+    let before = history.len();
+    if data_only {
+        let mut idx = 0;
+        history.retain(|message| {
+            idx += 1;
+            idx == 1 || !untrusted::is_file_content(message)
+        });
+    } else {
+        history.truncate(1);
+    }
+    before - history.len()
+}
+
+// Los datos de un libro entregados como llamada y resultado de herramienta se
+// muestran como tales, con la marca de que son datos de archivo
+fn kind(message: &Message) -> &'static str {
+    let calls = message.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty());
+    match (message.role.as_str(), untrusted::is_file_content(message)) {
+        ("system", _) => "sistema",
+        ("user", true) => "datos de archivo",
+        ("user", false) => "usuario",
+        ("assistant", true) => "llamada a herramienta (datos de archivo)",
+        ("assistant", false) if calls => "llamada a herramienta",
+        ("assistant", false) => "asistente",
+        ("tool", true) => "resultado de herramienta (datos de archivo)",
+        ("tool", false) => "resultado de herramienta",
+        _ => "otro",
+    }
+}
+
+// El contenido de un archivo sin el aviso ni el delimitador que lo preceden
+fn body(message: &Message) -> &str {
+    if !untrusted::is_file_content(message) {
+        return &message.content;
+    }
+    let mut rest = message.content.as_str();
+    for _ in 0..2 {
+        rest = rest.split_once('\n').map_or("", |(_, rest)| rest);
+    }
+    rest
+}
+
+fn preview(text: &str) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match flat.char_indices().nth(PREVIEW_CHARS) {
+        Some((cut, _)) => format!("{}…", &flat[..cut]),
+        None => flat,
+    }
+}
+
+fn describe(name: &str, history: &[Message], workbooks: &WorkbookCache, active: bool) -> String {
+    let messages = history.iter().filter(|m| m.role != "system" && !untrusted::is_file_content(m)).count();
+    let files: Vec<String> = workbooks.sheet_lists().into_iter().map(|(path, _)| path).collect();
+    format!(
+        "{} {}: {} mensajes, libros: {}",
+        if active { "*" } else { " " },
+        name,
+        messages,
+        if files.is_empty() { "ninguno".to_string() } else { files.join(", ") }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{FunctionCall, ToolCall};
+
+    #[test]
+    fn tool_messages_are_labelled_as_such() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            kind: "function".to_string(),
+            function: FunctionCall { name: "agregar".to_string(), arguments: "{}".to_string() },
+        };
+        let mut history = vec![Message::new("system", "prompt"), Message::new("user", "¿total?")];
+        history.push(Message::tool_request(String::new(), vec![call]));
+        history.push(Message::tool_result("call_1", "42"));
+        history.extend(untrusted::file_tool_messages("leer_excel", "{}", "A1: 1", 1));
+        history.push(untrusted::file_message("resumen"));
+        history.push(Message::new("assistant", "El total es 42"));
+
+        let kinds: Vec<&str> = history.iter().map(kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                "sistema",
+                "usuario",
+                "llamada a herramienta",
+                "resultado de herramienta",
+                "llamada a herramienta (datos de archivo)",
+                "resultado de herramienta (datos de archivo)",
+                "datos de archivo",
+                "asistente",
+            ]
+        );
+        assert!(inspect(&history)[2].contains("llamada a herramienta (~0 tokens): llama a agregar"));
+    }
+
+    #[test]
+    fn switching_to_the_active_context_changes_nothing() {
+        let conversation = |text: &str| Conversation {
+            history: vec![Message::new("user", text)],
+            workbooks: WorkbookCache::new(100),
+            retriever: Retriever::default(),
+        };
+        let mut contexts = Contexts::default();
+        contexts.create("ventas", conversation("ventas")).unwrap();
+        assert!(matches!(contexts.switch("PRINCIPAL", conversation("principal")), Switch::AlreadyActive(c) if c.history[0].content == "principal"));
+        assert!(matches!(contexts.switch("stock", conversation("principal")), Switch::Missing(_)));
+        assert!(matches!(contexts.switch("Ventas", conversation("principal")), Switch::Switched(c) if c.history[0].content == "ventas"));
+        assert_eq!(contexts.active(), "ventas");
+        assert!(matches!(contexts.switch("ventas", conversation("ventas")), Switch::AlreadyActive(_)));
+    }
+}
@@ -0,0 +1,440 @@
+use crate::backup;
+use crate::crypto;
+use crate::dates;
+use crate::excel::{self, CellValue, SheetData, WorkbookData};
+use crate::files;
+use crate::header;
+use crate::limits;
+use crate::outputs;
+use crate::parquet;
+use crate::verify;
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Formatos de destino admitidos por `convertir`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    Xlsx,
+    Csv,
+    Json,
+    Parquet,
+}
+
+impl FileFormat {
+    pub fn parse(value: &str) -> Option<FileFormat> {
+        match value.trim_start_matches('.').to_lowercase().as_str() {
+            "xlsx" => Some(FileFormat::Xlsx),
+            "csv" => Some(FileFormat::Csv),
+            "json" => Some(FileFormat::Json),
+            "parquet" => Some(FileFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<FileFormat> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(FileFormat::parse)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FileFormat::Xlsx => "xlsx",
+            FileFormat::Csv => "csv",
+            FileFormat::Json => "json",
+            FileFormat::Parquet => "parquet",
+        }
+    }
+}
+
+// Opciones del comando `convertir`
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    pub pattern: String,
+    pub format: FileFormat,
+    pub output_dir: String,
+    pub validate: bool,
+}
+
+// Resultado de convertir un archivo concreto
+pub struct ConversionOutcome {
+    pub source: PathBuf,
+    pub result: Result<Vec<PathBuf>>,
+}
+
+// Convierte todos los archivos que coinciden con el patrón; los errores se reportan por archivo
+pub fn convert_files(options: &ConvertOptions) -> Result<Vec<ConversionOutcome>> {
+    let sources = files::expand_pattern(&options.pattern)?;
+    if sources.is_empty() {
+        bail!("Ningún archivo coincide con {}", options.pattern);
+    }
+    fs::create_dir_all(&options.output_dir)
+        .context(format!("No se pudo crear el directorio {}", options.output_dir))?;
+
+    Ok(sources
+        .into_iter()
+        .map(|source| {
+            let result = convert_file(&source, options);
+            ConversionOutcome { source, result }
+        })
+        .collect())
+}
+
+fn convert_file(source: &Path, options: &ConvertOptions) -> Result<Vec<PathBuf>> {
+    let data = read_any(source)?;
+    // Las columnas sensibles se cifran también en las salidas csv y json
+    let data = crypto::protect_outputs(&data)?.unwrap_or(data);
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .context("Nombre de archivo no válido")?;
+    let out_dir = Path::new(&options.output_dir);
+
+    // csv y Parquet guardan una sola hoja: un archivo por hoja
+    let targets = match options.format {
+        FileFormat::Csv | FileFormat::Parquet if data.sheets.len() > 1 => data
+            .sheets
+            .iter()
+            .map(|sheet| {
+                let name = format!("{}_{}.{}", stem, sheet.name, options.format.extension());
+                (out_dir.join(outputs::safe_file_name(&name)), Some(sheet))
+            })
+            .collect(),
+        _ => vec![(out_dir.join(format!("{}.{}", stem, options.format.extension())), None)],
+    };
+
+    let mut written = Vec::new();
+    for (target, sheet) in targets {
+        let target = outputs::unique(target);
+        if same_file(source, &target) {
+            bail!("el destino {} coincide con el origen", target.display());
+        }
+        match (options.format, sheet) {
+            (FileFormat::Xlsx, _) => excel::save_workbook(&target, &data)?,
+            (FileFormat::Csv, Some(sheet)) => write_csv(&target, sheet)?,
+            (FileFormat::Csv, None) => write_csv(&target, data.sheets.first().unwrap_or(&SheetData::new(stem)))?,
+            (FileFormat::Json, _) => write_json(&target, &data)?,
+            (FileFormat::Parquet, Some(sheet)) => parquet::write(&target, sheet)?,
+            (FileFormat::Parquet, None) => parquet::write(&target, data.sheets.first().unwrap_or(&SheetData::new(stem)))?,
+        }
+        // Una salida que no supera la validación no se deja: el archivo cuenta como
+        // error y lo ya escrito de él se borra
+        if options.validate {
+            if let Err(e) = validate_output(&target, &data, sheet) {
+                for path in written.iter().chain([&target]) {
+                    let _ = fs::remove_file(path);
+                }
+                return Err(e.context(format!("la validación de {} falló y no se ha guardado", target.display())));
+            }
+        }
+        written.push(target);
+    }
+    Ok(written)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Lee un libro en cualquiera de los formatos admitidos según su extensión
+pub fn read_any(path: &Path) -> Result<WorkbookData> {
+    limits::check_file_size(path)?;
+    match FileFormat::from_path(path) {
+        Some(FileFormat::Xlsx) => excel::read_excel_file(&path.to_string_lossy()),
+        Some(FileFormat::Csv) => {
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Hoja1");
+            Ok(WorkbookData {
+                sheets: vec![read_csv(path, name)?],
+            })
+        }
+        Some(FileFormat::Json) => read_json(path),
+        Some(FileFormat::Parquet) => {
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Hoja1");
+            Ok(WorkbookData {
+                sheets: vec![parquet::read(path, name)?],
+            })
+        }
+        None => bail!("Formato no reconocido: {}", path.display()),
+    }
+}
+
+// Escribe un libro en el formato que indica la extensión (csv y Parquet solo con una hoja).
+// Como en la conversión, las columnas sensibles se cifran también en csv y json.
+pub fn write_any(path: &Path, data: &WorkbookData) -> Result<()> {
+    let protected = || -> Result<WorkbookData> { Ok(crypto::protect_outputs(data)?.unwrap_or_else(|| data.clone())) };
+    match FileFormat::from_path(path) {
+        Some(FileFormat::Xlsx) => excel::save_workbook(path, data),
+        Some(FileFormat::Csv) => match protected()?.sheets.as_slice() {
+            [sheet] => write_csv(path, sheet),
+            _ => bail!("un csv solo admite una hoja; guarda el libro como xlsx o json"),
+        },
+        Some(FileFormat::Json) => write_json(path, &protected()?),
+        Some(FileFormat::Parquet) => match protected()?.sheets.as_slice() {
+            [sheet] => parquet::write(path, sheet),
+            _ => bail!("un Parquet solo admite una hoja; guarda el libro como xlsx o json"),
+        },
+        None => bail!("Formato no reconocido: {}", path.display()),
+    }
+}
+
+// Vuelve a leer el archivo generado y compara celda a celda con el original. Un
+// csv no guarda tipos: de él se compara solo el texto de cada celda.
+fn validate_output(target: &Path, expected: &WorkbookData, sheet: Option<&SheetData>) -> Result<()> {
+    let format = FileFormat::from_path(target);
+    let actual = match format {
+        Some(FileFormat::Csv) => {
+            let content = fs::read_to_string(target).context(format!("No se pudo leer {}", target.display()))?;
+            let mut sheet = SheetData::new("csv");
+            sheet.rows = parse_csv(content.trim_start_matches('\u{feff}'))
+                .into_iter()
+                .map(|row| row.into_iter().map(CellValue::Text).collect())
+                .collect();
+            WorkbookData { sheets: vec![sheet] }
+        }
+        _ => read_any(target)?,
+    };
+    let expected_sheets: Vec<Cow<SheetData>> = match (format, sheet) {
+        (_, Some(sheet)) => vec![Cow::Borrowed(sheet)],
+        (Some(FileFormat::Csv | FileFormat::Parquet), None) => expected.sheets.iter().take(1).map(Cow::Borrowed).collect(),
+        _ => expected.sheets.iter().map(Cow::Borrowed).collect(),
+    };
+    // Parquet guarda los encabezados como nombres de columna y las columnas con
+    // tipos mezclados como texto: se compara con eso
+    let expected_sheets: Vec<Cow<SheetData>> = match format {
+        Some(FileFormat::Parquet) => expected_sheets.iter().map(|sheet| Cow::Owned(parquet::as_stored(sheet))).collect(),
+        _ => expected_sheets,
+    };
+    if actual.sheets.len() != expected_sheets.len() {
+        bail!(
+            "se esperaban {} hojas y se encontraron {}",
+            expected_sheets.len(),
+            actual.sheets.len()
+        );
+    }
+    for (want, got) in expected_sheets.iter().zip(&actual.sheets) {
+        let rows = want.rows.len().max(got.rows.len());
+        for r in 0..rows {
+            let want_row = want.rows.get(r).map(Vec::as_slice).unwrap_or(&[]);
+            let got_row = got.rows.get(r).map(Vec::as_slice).unwrap_or(&[]);
+            for c in 0..want_row.len().max(got_row.len()) {
+                let a = want_row.get(c).unwrap_or(&CellValue::Empty);
+                let b = got_row.get(c).unwrap_or(&CellValue::Empty);
+                let equivalent = match format {
+                    Some(FileFormat::Csv) => a.to_string() == b.to_string(),
+                    _ => cells_equivalent(a, b),
+                };
+                if !equivalent {
+                    bail!(
+                        "hoja '{}', fila {}, columna {}: '{}' ({}) != '{}' ({})",
+                        want.name,
+                        r + 1,
+                        c + 1,
+                        a,
+                        a.type_name(),
+                        b,
+                        b.type_name()
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cells_equivalent(a: &CellValue, b: &CellValue) -> bool {
+    match (a, b) {
+        (CellValue::Number(x), CellValue::Number(y)) => (x - y).abs() <= 1e-9 * x.abs().max(1.0),
+        // Las fechas se serializan al segundo en CSV/JSON
+        (CellValue::DateTime(_), CellValue::DateTime(_)) => a.to_string() == b.to_string(),
+        // Los errores de Excel se escriben como texto
+        (CellValue::Error(x), CellValue::Text(y)) | (CellValue::Text(y), CellValue::Error(x)) => x == y,
+        _ => a == b,
+    }
+}
+
+pub fn write_csv(path: &Path, sheet: &SheetData) -> Result<()> {
+    let mut output = String::new();
+    for row in &sheet.rows {
+        let fields: Vec<String> = row.iter().map(|cell| csv_escape(&cell.to_string())).collect();
+        output.push_str(&fields.join(","));
+        output.push('\n');
+    }
+    backup::before_write(path)?;
+    fs::write(path, output).context(format!("No se pudo escribir {}", path.display()))?;
+    verify::after_write(path)?;
+    Ok(())
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn read_csv(path: &Path, sheet_name: &str) -> Result<SheetData> {
+    let content = fs::read_to_string(path).context(format!("No se pudo leer {}", path.display()))?;
+    let mut sheet = SheetData::new(sheet_name);
+    sheet.rows = parse_csv(content.trim_start_matches('\u{feff}'))
+        .into_iter()
+        .map(|row| row.iter().map(|field| CellValue::infer(field)).collect())
+        .collect();
+    sheet.headerless = !header::detect(&sheet.rows);
+    dates::detect_date_columns(&mut sheet);
+    Ok(sheet)
+}
+
+// Parser CSV (RFC 4180): comillas dobles, comillas escapadas y saltos de línea dentro de campos
+pub fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    parse_delimited(content, ',')
+}
+
+// Lo mismo con otro separador; Excel copia las celdas al portapapeles como texto
+// separado por tabuladores con estas mismas comillas
+pub fn parse_delimited(content: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+// JSON: un objeto con una matriz de filas por hoja, p. ej. {"Hoja1": [[1, "a", null]]}
+pub fn write_json(path: &Path, data: &WorkbookData) -> Result<()> {
+    let mut sheets = Map::new();
+    for sheet in &data.sheets {
+        let rows: Vec<Value> = sheet
+            .rows
+            .iter()
+            .map(|row| Value::Array(row.iter().map(cell_to_json).collect()))
+            .collect();
+        sheets.insert(sheet.name.clone(), Value::Array(rows));
+    }
+    let content = serde_json::to_string_pretty(&Value::Object(sheets))?;
+    backup::before_write(path)?;
+    fs::write(path, content).context(format!("No se pudo escribir {}", path.display()))?;
+    verify::after_write(path)?;
+    Ok(())
+}
+
+pub fn read_json(path: &Path) -> Result<WorkbookData> {
+    let content = fs::read_to_string(path).context(format!("No se pudo leer {}", path.display()))?;
+    let value: Value = serde_json::from_str(&content).context("JSON no válido")?;
+    workbook_from_json(&value)
+}
+
+// Construye un libro a partir de un objeto JSON {"Hoja": [[...], ...]}
+pub fn workbook_from_json(value: &Value) -> Result<WorkbookData> {
+    let Value::Object(sheets) = value else {
+        bail!("se esperaba un objeto con una matriz de filas por hoja");
+    };
+    let mut data = WorkbookData::default();
+    for (name, rows) in sheets {
+        let Value::Array(rows) = rows else {
+            bail!("la hoja '{}' debe ser una matriz de filas", name);
+        };
+        let mut sheet = SheetData::new(name);
+        for row in rows {
+            let cells = match row {
+                Value::Array(cells) => cells.iter().map(cell_from_json).collect(),
+                other => vec![cell_from_json(other)],
+            };
+            sheet.rows.push(cells);
+        }
+        data.sheets.push(sheet);
+    }
+    Ok(data)
+}
+
+pub fn cell_to_json(cell: &CellValue) -> Value {
+    match cell {
+        CellValue::Empty => Value::Null,
+        CellValue::Bool(b) => Value::Bool(*b),
+        CellValue::Number(n) if n.fract() == 0.0 && n.abs() < 9e15 => Value::from(*n as i64),
+        CellValue::Number(n) => serde_json::Number::from_f64(*n)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        other => Value::String(other.to_string()),
+    }
+}
+
+pub fn cell_from_json(value: &Value) -> CellValue {
+    match value {
+        Value::Null => CellValue::Empty,
+        Value::Bool(b) => CellValue::Bool(*b),
+        Value::Number(n) => n.as_f64().map(CellValue::Number).unwrap_or(CellValue::Empty),
+        Value::String(s) => match excel::parse_iso_datetime(s) {
+            Some(serial) => CellValue::DateTime(serial),
+            None => CellValue::Text(s.clone()),
+        },
+        other => CellValue::Text(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(name: &str, data: WorkbookData, format: FileFormat) -> (PathBuf, Result<Vec<PathBuf>>) {
+        let dir = crate::paths::test_dir(name);
+        let source = dir.join("t.xlsx");
+        excel::save_workbook(&source, &data).unwrap();
+        let options = ConvertOptions {
+            pattern: source.to_string_lossy().into_owned(),
+            format,
+            output_dir: dir.join("salida").to_string_lossy().into_owned(),
+            validate: true,
+        };
+        let mut outcomes = convert_files(&options).unwrap();
+        (dir.join("salida"), outcomes.remove(0).result)
+    }
+
+    #[test]
+    fn csv_output_is_validated_by_its_text_only() {
+        let sheet = SheetData::from_rows("Datos", &[&["Código", "Importe"], &["12", "12"], &["0012", "1,5"]]);
+        let mut text_numbers = sheet.clone();
+        text_numbers.rows[1][0] = CellValue::Text("12".to_string());
+        let (_, result) = convert("csv_output_is_validated_by_its_text_only", WorkbookData { sheets: vec![text_numbers] }, FileFormat::Csv);
+        let written = result.unwrap();
+        assert_eq!(fs::read_to_string(&written[0]).unwrap(), "Código,Importe\n12,12\n0012,\"1,5\"\n");
+    }
+
+    #[test]
+    fn an_output_that_fails_validation_is_removed() {
+        // El csv se lee sin la marca de orden de bytes del principio: no coincide
+        let sheet = SheetData::from_rows("Datos", &[&["\u{feff}Código"], &["a"]]);
+        let (dir, result) = convert("an_output_that_fails_validation_is_removed", WorkbookData { sheets: vec![sheet] }, FileFormat::Csv);
+        let error = result.unwrap_err();
+        assert!(format!("{:#}", error).contains("no se ha guardado"), "{:#}", error);
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 0);
+    }
+}
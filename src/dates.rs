@@ -0,0 +1,291 @@
+// Fechas: conversión explícita de columnas (`convertir_fechas`) y detección de
+// columnas de fecha que llegan como números de serie sin formato de fecha
+// (p. ej. 45321 en una columna "Fecha" exportada por otra herramienta).
+use crate::analysis;
+use crate::excel::{self, CellValue, SheetData};
+use anyhow::Result;
+use std::path::Path;
+
+// Último número de serie válido en Excel (9999-12-31)
+const MAX_SERIAL: f64 = 2_958_465.0;
+// Rango plausible para detectar fechas sin formato: 1950-01-01 a 2100-01-01
+const DETECT_MIN_SERIAL: f64 = 18_264.0;
+const DETECT_MAX_SERIAL: f64 = 73_051.0;
+// Encabezados que indican una columna de fechas
+const DATE_HEADER_WORDS: &[&str] = &["fecha", "date", "día", "dia", "alta", "vencimiento", "nacimiento"];
+
+// Orden de día y mes en fechas de texto ambiguas como 03/04/2024
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DayOrder {
+    DayMonth,
+    MonthDay,
+}
+
+impl DayOrder {
+    pub fn parse(value: &str) -> Option<DayOrder> {
+        match value.to_lowercase().as_str() {
+            "dma" | "dmy" | "dd/mm" => Some(DayOrder::DayMonth),
+            "mda" | "mdy" | "mm/dd" => Some(DayOrder::MonthDay),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DateOptions {
+    pub file: String,
+    pub sheet: String,
+    pub columns: Vec<String>,
+    pub order: DayOrder,
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct DateReport {
+    pub converted: usize,
+    // Celdas que ya eran fechas
+    pub unchanged: usize,
+    // Celdas no vacías que no se pudieron interpretar (se dejan como estaban)
+    pub failed: Vec<String>,
+    pub output: String,
+}
+
+// Convierte las columnas indicadas a fechas y guarda el libro
+pub fn apply(options: &DateOptions) -> Result<DateReport> {
+    let mut data = excel::read_excel_file(&options.file)?;
+    let mut sheet = data
+        .require_sheet(&options.file, &options.sheet)?
+        .clone();
+    let columns = options
+        .columns
+        .iter()
+        .map(|spec| analysis::require_column(&sheet, spec))
+        .collect::<Result<Vec<usize>>>()?;
+
+    let mut report = DateReport::default();
+    for (row_idx, row) in sheet.rows.iter_mut().enumerate().skip(1) {
+        for col in &columns {
+            let Some(cell) = row.get_mut(*col) else { continue };
+            match cell {
+                CellValue::Empty => {}
+                CellValue::DateTime(_) => report.unchanged += 1,
+                _ => match coerce(cell, options.order) {
+                    Some(date) => {
+                        *cell = date;
+                        report.converted += 1;
+                    }
+                    None => report
+                        .failed
+                        .push(format!("{}{} ('{}')", excel::column_letters(*col), row_idx + 1, cell)),
+                },
+            }
+        }
+    }
+    data.upsert_sheet(sheet);
+    report.output = options.output.clone().unwrap_or_else(|| options.file.clone());
+    excel::save_workbook(Path::new(&report.output), &data)?;
+    Ok(report)
+}
+
+// Fecha equivalente a la celda: números de serie y textos con fecha
+pub fn coerce(cell: &CellValue, order: DayOrder) -> Option<CellValue> {
+    match cell {
+        CellValue::DateTime(serial) => Some(CellValue::DateTime(*serial)),
+        CellValue::Number(serial) if (1.0..=MAX_SERIAL).contains(serial) => Some(CellValue::DateTime(*serial)),
+        CellValue::Text(text) => parse_date(text, order).map(CellValue::DateTime),
+        _ => None,
+    }
+}
+
+// "2024-01-31", "31/01/2024", "01/31/24 10:30", "2024/01/31"...
+pub fn parse_date(text: &str, order: DayOrder) -> Option<f64> {
+    let text = text.trim();
+    if let Some(serial) = excel::parse_iso_datetime(text) {
+        return Some(serial);
+    }
+    let (date, time) = match text.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time.trim())),
+        None => (text, None),
+    };
+    let parts: Vec<&str> = date.split(['/', '-', '.']).collect();
+    let [first, second, third] = parts.as_slice() else { return None };
+    let (year, month, day) = if first.len() == 4 {
+        (*first, *second, *third)
+    } else {
+        match order {
+            DayOrder::DayMonth => (*third, *second, *first),
+            DayOrder::MonthDay => (*third, *first, *second),
+        }
+    };
+    let year: i64 = match year.len() {
+        4 => year.parse().ok()?,
+        // Años de dos cifras: 00-69 son 2000-2069 y 70-99 son 1970-1999, como en Excel
+        2 => {
+            let short: i64 = year.parse().ok()?;
+            if short < 70 {
+                2000 + short
+            } else {
+                1900 + short
+            }
+        }
+        _ => return None,
+    };
+    let month: u32 = month.parse().ok()?;
+    let day: u32 = day.parse().ok()?;
+    let time = match time {
+        // HH:MM se completa con los segundos y H:MM con el cero inicial
+        Some(time) if time.matches(':').count() == 1 => format!(" {:0>5}:00", time),
+        Some(time) => format!(" {:0>8}", time),
+        None => String::new(),
+    };
+    excel::parse_iso_datetime(&format!("{:04}-{:02}-{:02}{}", year, month, day, time))
+}
+
+// Marca como fechas las columnas numéricas cuyo encabezado indica una fecha y
+// cuyos valores son todos números de serie plausibles; devuelve sus índices
+pub fn detect_date_columns(sheet: &mut SheetData) -> Vec<usize> {
+    let headers = sheet.headers();
+    let candidates: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .filter(|(_, header)| {
+            let header = header.to_lowercase();
+            header
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|word| DATE_HEADER_WORDS.contains(&word))
+        })
+        .map(|(col, _)| col)
+        .collect();
+
+    let start = sheet.data_start();
+    let mut detected = Vec::new();
+    for col in candidates {
+        let mut numbers = 0;
+        let plausible = sheet.rows.iter().skip(start).all(|row| match row.get(col) {
+            None | Some(CellValue::Empty) | Some(CellValue::DateTime(_)) => true,
+            Some(CellValue::Number(n)) => {
+                numbers += 1;
+                (DETECT_MIN_SERIAL..=DETECT_MAX_SERIAL).contains(n)
+            }
+            Some(_) => false,
+        });
+        if plausible && numbers > 0 {
+            for row in sheet.rows.iter_mut().skip(start) {
+                if let Some(cell @ CellValue::Number(_)) = row.get_mut(col) {
+                    *cell = CellValue::DateTime(cell.as_number().unwrap_or_default());
+                }
+            }
+            detected.push(col);
+        }
+    }
+    detected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serial(text: &str) -> f64 {
+        excel::parse_iso_datetime(text).unwrap()
+    }
+
+    #[test]
+    fn ambiguous_dates_follow_the_day_order() {
+        assert_eq!(parse_date("03/04/2024", DayOrder::DayMonth), Some(serial("2024-04-03")));
+        assert_eq!(parse_date("03/04/2024", DayOrder::MonthDay), Some(serial("2024-03-04")));
+        // Con el año delante no hay ambigüedad
+        assert_eq!(parse_date("2024/04/03", DayOrder::MonthDay), Some(serial("2024-04-03")));
+        assert_eq!(parse_date("31.12.23", DayOrder::DayMonth), Some(serial("2023-12-31")));
+        assert_eq!(parse_date("01/02/75", DayOrder::DayMonth), Some(serial("1975-02-01")));
+        assert_eq!(parse_date("01/31/24 9:30", DayOrder::MonthDay), Some(serial("2024-01-31 09:30:00")));
+        assert_eq!(parse_date("31/02/2024", DayOrder::DayMonth), None);
+        assert_eq!(parse_date("pronto", DayOrder::DayMonth), None);
+        assert_eq!(DayOrder::parse("MDY"), Some(DayOrder::MonthDay));
+    }
+
+    #[test]
+    fn serial_numbers_and_date_text_coerce_to_dates() {
+        assert_eq!(coerce(&CellValue::Number(45321.0), DayOrder::DayMonth), Some(CellValue::DateTime(45321.0)));
+        assert_eq!(coerce(&CellValue::Number(-3.0), DayOrder::DayMonth), None);
+        assert_eq!(coerce(&CellValue::Text("15/02/2024".to_string()), DayOrder::DayMonth), Some(CellValue::DateTime(serial("2024-02-15"))));
+        assert_eq!(coerce(&CellValue::Bool(true), DayOrder::DayMonth), None);
+    }
+
+    #[test]
+    fn only_date_headers_with_plausible_serials_are_detected() {
+        let mut sheet = SheetData {
+            name: "Altas".to_string(),
+            rows: vec![
+                vec!["Fecha de alta", "Importe", "Vencimiento", "Día"].into_iter().map(|h| CellValue::Text(h.to_string())).collect(),
+                vec![CellValue::Number(45321.0), CellValue::Number(45321.0), CellValue::Number(12.0), CellValue::Number(45000.0)],
+                vec![CellValue::Empty, CellValue::Number(45400.0), CellValue::Number(45400.0), CellValue::Text("lunes".to_string())],
+            ],
+            ..Default::default()
+        };
+        // Importe no tiene un encabezado de fecha; Vencimiento tiene un 12 y Día un texto
+        assert_eq!(detect_date_columns(&mut sheet), [0]);
+        assert_eq!(sheet.rows[1][0], CellValue::DateTime(45321.0));
+        assert_eq!(sheet.rows[1][1], CellValue::Number(45321.0));
+    }
+
+    #[test]
+    fn converting_a_column_reports_the_cells_it_could_not_read() {
+        let dir = crate::paths::test_dir("converting_a_column_reports_the_cells_it_could_not_read");
+        let file = dir.join("altas.xlsx");
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Altas").unwrap();
+        sheet.write_string(0, 0, "Cliente").unwrap();
+        sheet.write_string(0, 1, "Alta").unwrap();
+        for (row, (customer, signup)) in [("Ana", "15/02/2024"), ("Luis", "sin fecha"), ("Eva", "2024-03-01")].iter().enumerate() {
+            sheet.write_string(row as u32 + 1, 0, *customer).unwrap();
+            sheet.write_string(row as u32 + 1, 1, *signup).unwrap();
+        }
+        workbook.save(&file).unwrap();
+
+        let output = dir.join("altas_fechas.xlsx").display().to_string();
+        let report = apply(&DateOptions {
+            file: file.display().to_string(),
+            sheet: "Altas".to_string(),
+            columns: vec!["Alta".to_string()],
+            order: DayOrder::DayMonth,
+            output: Some(output.clone()),
+        })
+        .unwrap();
+        assert_eq!(report.converted, 2);
+        assert_eq!(report.failed, ["B3 ('sin fecha')"]);
+        let written = excel::read_excel_file(&output).unwrap();
+        let altas = written.sheet("Altas").unwrap();
+        assert_eq!(altas.rows[1][1], CellValue::DateTime(serial("2024-02-15")));
+        assert_eq!(altas.rows[2][1], CellValue::Text("sin fecha".to_string()));
+    }
+
+    #[test]
+    fn a_workbook_with_formulas_is_not_rewritten_in_place() {
+        let dir = crate::paths::test_dir("a_workbook_with_formulas_is_not_rewritten_in_place");
+        let file = dir.join("altas.xlsx");
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Altas").unwrap();
+        sheet.write_string(0, 0, "Alta").unwrap();
+        sheet.write_string(1, 0, "15/02/2024").unwrap();
+        let totals = workbook.add_worksheet();
+        totals.set_name("Totales").unwrap();
+        totals.write_formula(0, 0, "=COUNTA(Altas!A:A)").unwrap();
+        workbook.save(&file).unwrap();
+        let before = std::fs::read(&file).unwrap();
+
+        let options = |output: Option<String>| DateOptions {
+            file: file.display().to_string(),
+            sheet: "Altas".to_string(),
+            columns: vec!["Alta".to_string()],
+            order: DayOrder::DayMonth,
+            output,
+        };
+        let error = apply(&options(None)).unwrap_err();
+        assert!(error.to_string().contains("se perderían 1 fórmula(s)"), "{:#}", error);
+        assert_eq!(std::fs::read(&file).unwrap(), before);
+        // En otro archivo no hay nada que perder
+        assert!(apply(&options(Some(dir.join("altas_fechas.xlsx").display().to_string()))).is_ok());
+    }
+}
@@ -0,0 +1,248 @@
+// Diagnóstico de la instalación (`doctor` / `ping`, también `ia_agent doctor`):
+// comprueba la clave, que el endpoint responde, que el modelo existe y que se
+// puede escribir en los directorios que usa el agente, con una indicación de qué
+// cambiar en cada fallo. Evita descubrir la configuración rota con la primera
+// pregunta.
+use crate::config::Config;
+use crate::error::{ApiFailure, IAgentError};
+use crate::llm::{self, Message};
+use crate::paths;
+use reqwest::Client;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Failed,
+    Info,
+}
+
+struct Check {
+    status: Status,
+    name: &'static str,
+    detail: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn new(status: Status, name: &'static str, detail: impl Into<String>) -> Check {
+        Check {
+            status,
+            name,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Check {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    fn hint_if_failed(self, hint: &str) -> Check {
+        if self.status == Status::Failed {
+            self.hint(hint)
+        } else {
+            self
+        }
+    }
+
+    fn print(&self) {
+        let icon = match self.status {
+            Status::Ok => "✅",
+            Status::Warning => "⚠️ ",
+            Status::Failed => "❌",
+            Status::Info => "ℹ️ ",
+        };
+        println!("{} {}: {}", icon, self.name, self.detail);
+        if let Some(hint) = &self.hint {
+            println!("   → {}", hint);
+        }
+    }
+}
+
+// Ejecuta las comprobaciones e imprime el resultado; devuelve el número de fallos
+pub async fn run(client: &Client, config: &Config) -> usize {
+    println!("🩺 Diagnóstico de IAgent");
+    let mut checks = Vec::new();
+    let mut report = |check: Check| {
+        check.print();
+        checks.push(check.status);
+    };
+
+    match &config.provider {
+        Some(provider) => report(Check::new(
+            Status::Info,
+            "Modelo",
+            format!("sin red ({}); no se comprueba la API", provider.name()),
+        )),
+        None => {
+            let key = check_key(config);
+            let has_key = key.status != Status::Failed;
+            report(key);
+            if has_key {
+                let (endpoint, reachable) = check_endpoint(client, config).await;
+                report(endpoint);
+                if reachable {
+                    report(check_models(client, config).await);
+                }
+            }
+        }
+    }
+
+    if let Some(source) = config.tools.source() {
+        report(Check::new(
+            Status::Info,
+            "Herramientas propias",
+            format!("{} de {}", config.tools.custom_names().join(", "), source.display()),
+        ));
+    }
+    report(check_writable("Configuración", &paths::config_dir(), "plantillas, herramientas y clave cifrada")
+        .hint_if_failed("Usa IAGENT_HOME para elegir otro directorio"));
+    report(check_writable("Directorio de datos", &paths::data_dir(), "copias de seguridad, trabajos y registros")
+        .hint_if_failed("Usa IAGENT_HOME para elegir otro directorio"));
+    report(check_writable("Caché", &paths::cache_dir(), "respuestas del modelo")
+        .hint_if_failed("Usa IAGENT_HOME para elegir otro directorio"));
+    report(check_writable("Espacio de trabajo", config.workspace.root(), "archivos de las herramientas del modelo")
+        .hint_if_failed("Usa --espacio-trabajo o IAGENT_WORKSPACE para elegir otro directorio"));
+    if let Some(dir) = &config.outputs.dir {
+        report(check_writable("Directorio de salida", dir, "archivos generados")
+            .hint_if_failed("Revisa IAGENT_OUTPUT_DIR o --dir-salida"));
+    }
+
+    let failed = checks.iter().filter(|status| **status == Status::Failed).count();
+    let warnings = checks.iter().filter(|status| **status == Status::Warning).count();
+    match (failed, warnings) {
+        (0, 0) => println!("Todo correcto"),
+        (0, warnings) => println!("Sin errores, {} aviso(s)", warnings),
+        (failed, warnings) => println!("{} problema(s), {} aviso(s)", failed, warnings),
+    }
+    failed
+}
+
+fn check_key(config: &Config) -> Check {
+    let header_auth = config
+        .extra_headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("authorization") || name.eq_ignore_ascii_case("api-key"));
+    if let Some((name, _)) = header_auth {
+        return Check::new(Status::Ok, "Clave de la API", format!("cabecera {} de IAGENT_EXTRA_HEADERS", name));
+    }
+    if config.api_key.trim().is_empty() {
+        return Check::new(Status::Failed, "Clave de la API", "no se encontró ninguna").hint(
+            "Define DEEPSEEK_API_KEY, usa IAGENT_API_KEY_CMD o guárdala con `ia_agent clave`",
+        );
+    }
+    if config.api_key.trim() != config.api_key || config.api_key.contains(char::is_whitespace) {
+        return Check::new(Status::Warning, "Clave de la API", "contiene espacios o saltos de línea")
+            .hint("Revisa que la clave se copió entera y sin espacios");
+    }
+    Check::new(Status::Ok, "Clave de la API", format!("configurada ({})", mask(&config.api_key)))
+}
+
+// Muestra solo el final de la clave
+fn mask(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    format!("…{}", chars[chars.len() - 4..].iter().collect::<String>())
+}
+
+// Una petición mínima al modelo; sin caché para comprobar la API de verdad.
+// Devuelve también si el endpoint contestó, aunque fuera con un error
+async fn check_endpoint(client: &Client, config: &Config) -> (Check, bool) {
+    let mut config = config.clone();
+    config.cache_ttl = None;
+    config.record_dir = None;
+    let messages = vec![Message::new("user", "Responde solo: ok")];
+    let started = Instant::now();
+    let error = match llm::get_deepseek_response(client, &config, &messages, None).await {
+        Ok(_) => {
+            let detail = format!(
+                "{} responde con el modelo {} en {} ms",
+                config.completions_url(),
+                config.model,
+                started.elapsed().as_millis()
+            );
+            return (Check::new(Status::Ok, "API", detail), true);
+        }
+        Err(e) => e,
+    };
+    let check = match IAgentError::find(&error) {
+        Some(api_error @ IAgentError::Api { status, .. }) => {
+            let check = match api_error.api_failure().unwrap_or(ApiFailure::Other) {
+                ApiFailure::InvalidKey => Check::new(Status::Failed, "API", format!("la clave no es válida ({})", status))
+                    .hint("Revisa DEEPSEEK_API_KEY o vuelve a guardarla con `ia_agent clave`"),
+                ApiFailure::NoBalance => Check::new(Status::Failed, "API", "la cuenta no tiene saldo (402)")
+                    .hint("Recarga el saldo en la consola del proveedor"),
+                ApiFailure::NotFound => Check::new(Status::Failed, "API", format!("{} no existe (404)", config.completions_url()))
+                    .hint(match config.azure {
+                        Some(_) => "Revisa IAGENT_AZURE_RESOURCE / IAGENT_AZURE_DEPLOYMENTS: el despliegue debe existir en el recurso",
+                        None => "Revisa DEEPSEEK_API_URL / IAGENT_BASE_URL / IAGENT_DEPLOYMENT",
+                    }),
+                ApiFailure::ModelNotFound => {
+                    Check::new(Status::Failed, "API", format!("el modelo {} no está disponible", config.model))
+                        .hint("Elige otro con DEEPSEEK_MODEL")
+                }
+                ApiFailure::RateLimit => Check::new(Status::Warning, "API", "límite de peticiones alcanzado (429)")
+                    .hint("La configuración es correcta; espera un momento antes de usarla"),
+                ApiFailure::Server => Check::new(Status::Warning, "API", format!("el servicio falló ({})", status))
+                    .hint("Vuelve a intentarlo más tarde"),
+                ApiFailure::ContextLength | ApiFailure::Other => Check::new(Status::Failed, "API", format!("{:#}", error)),
+            };
+            return (check, true);
+        }
+        _ => Check::new(Status::Failed, "API", short_error(&error)).hint(
+            "Revisa la URL, IAGENT_PROXY (o HTTPS_PROXY), IAGENT_CA_CERT en redes que inspeccionan TLS e IAGENT_CONNECT_TIMEOUT",
+        ),
+    };
+    (check, false)
+}
+
+async fn check_models(client: &Client, config: &Config) -> Check {
+    // Azure lista los modelos base, no los despliegues: la petición anterior ya probó el del modelo
+    if let Some(azure) = &config.azure {
+        return Check::new(Status::Info, "Modelo", format!("{} ({})", config.model, azure.describe(&config.model)));
+    }
+    match llm::list_models(client, config).await {
+        Ok(models) if models.contains(&config.model) => {
+            Check::new(Status::Ok, "Modelo", format!("{} disponible ({} modelos en total)", config.model, models.len()))
+        }
+        Ok(models) if models.is_empty() => Check::new(Status::Info, "Modelo", "el endpoint no lista modelos"),
+        Ok(models) => Check::new(
+            Status::Warning,
+            "Modelo",
+            format!("{} no aparece en la lista del endpoint", config.model),
+        )
+        .hint(format!("Modelos disponibles: {} (cámbialo con DEEPSEEK_MODEL)", models.join(", "))),
+        // Muchas pasarelas no implementan /models; la petición anterior ya probó el modelo
+        Err(_) => Check::new(Status::Info, "Modelo", "el endpoint no publica la lista de modelos"),
+    }
+}
+
+// El mensaje principal y la causa de fondo, sin la cadena intermedia de reqwest
+fn short_error(error: &anyhow::Error) -> String {
+    let root = error.root_cause().to_string();
+    let top = error.to_string();
+    if top == root {
+        top
+    } else {
+        format!("{} ({})", top, root)
+    }
+}
+
+// Crea el directorio si falta y escribe y borra un archivo de prueba
+fn check_writable(name: &'static str, dir: &Path, purpose: &str) -> Check {
+    let probe = dir.join(".iagent_doctor");
+    let result = fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check::new(Status::Ok, name, format!("{} ({})", dir.display(), purpose)),
+        Err(e) => Check::new(Status::Failed, name, format!("no se puede escribir en {}: {}", dir.display(), e)),
+    }
+}
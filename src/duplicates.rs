@@ -0,0 +1,223 @@
+// Filas duplicadas (`duplicados`, `eliminar_duplicados` y la herramienta
+// buscar_duplicados). Dos filas son duplicadas si coinciden en todas sus celdas o,
+// con columnas, solo en esas. La primera aparición se conserva y las siguientes
+// se marcan; las filas vacías no cuentan.
+use crate::analysis;
+use crate::convert;
+use crate::excel::{SheetData, WorkbookData};
+use crate::outputs;
+use crate::structure;
+use anyhow::{Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+// Grupos que se listan en el informe
+const REPORT_GROUPS: usize = 20;
+
+#[derive(Debug, Clone)]
+pub struct DuplicateOptions {
+    pub file: String,
+    // Sin hoja, la primera del libro
+    pub sheet: Option<String>,
+    // Columnas que forman la clave; vacío, la fila entera
+    pub columns: Vec<String>,
+    // Solo eliminar_duplicados; por defecto <archivo>_sin_duplicados
+    pub output: Option<String>,
+}
+
+pub struct DuplicateGroup {
+    // Valores de la clave, como se muestran
+    pub key: Vec<String>,
+    // Filas como en Excel (desde 1); la primera es la que se conserva
+    pub rows: Vec<usize>,
+}
+
+pub struct DuplicateReport {
+    pub sheet: String,
+    pub data_rows: usize,
+    // Encabezados de las columnas de la clave; vacío si es la fila entera
+    pub key_columns: Vec<String>,
+    pub groups: Vec<DuplicateGroup>,
+    // Índices (desde 0) de las filas que sobran: todas menos la primera de cada grupo
+    pub extra_rows: BTreeSet<usize>,
+}
+
+pub fn find(sheet: &SheetData, columns: &[String]) -> Result<DuplicateReport> {
+    let keys = columns
+        .iter()
+        .map(|spec| analysis::require_data_column(sheet, spec))
+        .collect::<Result<Vec<usize>>>()?;
+    let headers = sheet.headers();
+    let mut first_seen: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    let mut extra_rows = BTreeSet::new();
+    for (idx, row) in sheet.rows.iter().enumerate().skip(sheet.data_start()) {
+        let key: Vec<String> = if keys.is_empty() {
+            let mut values: Vec<String> = row.iter().map(|cell| cell.to_string()).collect();
+            while values.last().is_some_and(|v| v.is_empty()) {
+                values.pop();
+            }
+            values
+        } else {
+            keys.iter().map(|col| row.get(*col).map(|cell| cell.to_string()).unwrap_or_default()).collect()
+        };
+        if key.iter().all(|value| value.is_empty()) {
+            continue;
+        }
+        match first_seen.get(&key) {
+            Some(&group) => {
+                groups[group].rows.push(idx + 1);
+                extra_rows.insert(idx);
+            }
+            None => {
+                first_seen.insert(key.clone(), groups.len());
+                groups.push(DuplicateGroup { key, rows: vec![idx + 1] });
+            }
+        }
+    }
+    groups.retain(|group| group.rows.len() > 1);
+    Ok(DuplicateReport {
+        sheet: sheet.name.clone(),
+        data_rows: sheet.data_rows().len(),
+        key_columns: keys.iter().map(|col| headers.get(*col).cloned().unwrap_or_default()).collect(),
+        groups,
+        extra_rows,
+    })
+}
+
+impl DuplicateReport {
+    // Una línea de resumen y, si hay duplicados, los primeros grupos
+    pub fn describe(&self) -> String {
+        let compared = if self.key_columns.is_empty() {
+            "la fila entera".to_string()
+        } else {
+            self.key_columns.join(", ")
+        };
+        if self.groups.is_empty() {
+            return format!(
+                "Hoja {}: ninguna fila duplicada entre {} filas (comparando {})",
+                self.sheet, self.data_rows, compared
+            );
+        }
+        let mut text = format!(
+            "Hoja {}: {} fila(s) duplicada(s) en {} grupo(s) entre {} filas (comparando {})",
+            self.sheet,
+            self.extra_rows.len(),
+            self.groups.len(),
+            self.data_rows,
+            compared
+        );
+        for group in self.groups.iter().take(REPORT_GROUPS) {
+            let rows: Vec<String> = group.rows.iter().map(|row| row.to_string()).collect();
+            text.push_str(&format!("\n  filas {}: {}", rows.join(", "), group.key.join(" | ")));
+        }
+        if self.groups.len() > REPORT_GROUPS {
+            text.push_str(&format!("\n  ... y {} grupo(s) más", self.groups.len() - REPORT_GROUPS));
+        }
+        text
+    }
+}
+
+fn read_sheet(options: &DuplicateOptions) -> Result<(WorkbookData, usize)> {
+    let data = convert::read_any(Path::new(&options.file)).context(format!("No se pudo leer {}", options.file))?;
+    let idx = match &options.sheet {
+        Some(name) => {
+            data.require_sheet(&options.file, name)?;
+            data.sheets.iter().position(|s| s.name.eq_ignore_ascii_case(name)).unwrap_or(0)
+        }
+        None => {
+            data.sheets.first().context("El libro no tiene hojas")?;
+            0
+        }
+    };
+    Ok((data, idx))
+}
+
+pub fn report(options: &DuplicateOptions) -> Result<DuplicateReport> {
+    let (data, idx) = read_sheet(options)?;
+    find(&data.sheets[idx], &options.columns)
+}
+
+// Escribe una copia sin las filas duplicadas; el resto de hojas se conserva.
+// Devuelve el informe y el archivo escrito.
+pub fn remove(options: &DuplicateOptions) -> Result<(DuplicateReport, String)> {
+    let (mut data, idx) = read_sheet(options)?;
+    let report = find(&data.sheets[idx], &options.columns)?;
+    structure::delete_rows(&mut data.sheets[idx], &report.extra_rows);
+    let output = match &options.output {
+        Some(output) => outputs::target(output)?,
+        None => {
+            let path = Path::new(&options.file);
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("datos");
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("xlsx");
+            outputs::derived(path, &format!("{}_sin_duplicados.{}", stem, extension))?
+        }
+    };
+    convert::write_any(Path::new(&output), &data).context(format!("No se pudo guardar {}", output))?;
+    Ok((report, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn customers() -> SheetData {
+        SheetData::from_rows(
+            "Clientes",
+            &[
+                &["Cliente", "Ciudad", "Importe"],
+                &["Ana", "Madrid", "10"],
+                &["Luis", "Sevilla", "20"],
+                &["Ana", "Madrid", "10"],
+                &["", "", ""],
+                &["", "", ""],
+                &["Ana", "Bilbao", "30"],
+                &["Luis", "Sevilla", "20", ""],
+            ],
+        )
+    }
+
+    #[test]
+    fn whole_rows_are_compared_and_empty_rows_do_not_count() {
+        let report = find(&customers(), &[]).unwrap();
+        // Las filas como en Excel: la primera de cada grupo se conserva
+        let groups: Vec<&[usize]> = report.groups.iter().map(|g| g.rows.as_slice()).collect();
+        assert_eq!(groups, [&[2, 4][..], &[3, 8][..]]);
+        assert_eq!(report.extra_rows.iter().copied().collect::<Vec<_>>(), [3, 7]);
+        let described = report.describe();
+        assert!(described.starts_with("Hoja Clientes: 2 fila(s) duplicada(s) en 2 grupo(s) entre 7 filas (comparando la fila entera)"), "{}", described);
+        assert!(described.contains("\n  filas 2, 4: Ana | Madrid | 10"), "{}", described);
+    }
+
+    #[test]
+    fn key_columns_group_rows_that_differ_elsewhere() {
+        let report = find(&customers(), &["Cliente".to_string()]).unwrap();
+        assert_eq!(report.key_columns, ["Cliente"]);
+        assert_eq!(report.groups[0].rows, [2, 4, 7]);
+        assert_eq!(report.extra_rows.len(), 3);
+        assert!(find(&customers(), &["Teléfono".to_string()]).is_err());
+
+        let unique = SheetData::from_rows("Clientes", &[&["Cliente"], &["Ana"], &["Luis"]]);
+        assert_eq!(find(&unique, &[]).unwrap().describe(), "Hoja Clientes: ninguna fila duplicada entre 2 filas (comparando la fila entera)");
+    }
+
+    #[test]
+    fn removing_duplicates_writes_a_copy_next_to_the_original() {
+        let dir = crate::paths::test_dir("removing_duplicates_writes_a_copy_next_to_the_original");
+        let file = dir.join("clientes.csv");
+        std::fs::write(&file, "Cliente,Ciudad\nAna,Madrid\nLuis,Sevilla\nAna,Madrid\n").unwrap();
+        let options = DuplicateOptions {
+            file: file.display().to_string(),
+            sheet: None,
+            columns: Vec::new(),
+            output: None,
+        };
+        let (report, output) = remove(&options).unwrap();
+        assert_eq!(report.extra_rows.len(), 1);
+        assert_eq!(Path::new(&output), dir.join("clientes_sin_duplicados.csv"));
+        let written = convert::read_any(Path::new(&output)).unwrap();
+        assert_eq!(written.sheets[0].data_rows().len(), 2);
+        // El original no se toca
+        assert_eq!(convert::read_any(&file).unwrap().sheets[0].data_rows().len(), 3);
+    }
+}
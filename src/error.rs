@@ -0,0 +1,238 @@
+// Errores con tipo del agente. Dentro del crate las funciones devuelven
+// anyhow::Result, pero en los puntos donde el tipo de fallo importa (la API del
+// modelo, la configuración, los libros y las referencias) se crea un IAgentError,
+// que se distingue con `IAgentError::find(&e)` sin comparar textos. La API de la
+// biblioteca (`Agent`) solo devuelve IAgentError: `classify` convierte ahí los
+// fallos sin tipo.
+use serde_json::Value;
+use std::fmt;
+
+// Texto de la respuesta de la API que se conserva en el error
+const MAX_BODY_CHARS: usize = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IAgentError {
+    // La API del modelo respondió con un código de error; `detail` es el error
+    // que explica el cuerpo, si es un JSON con el formato habitual
+    Api {
+        status: u16,
+        body: String,
+        detail: Option<ApiErrorDetail>,
+    },
+    // Un libro no se pudo abrir, leer o escribir; hoja y celda si se conocen
+    Excel {
+        file: String,
+        sheet: Option<String>,
+        cell: Option<String>,
+        message: String,
+    },
+    // Una entrada del usuario (referencia, plantilla, valor) no se entiende
+    Parse { input: String, message: String },
+    // Variables de entorno o argumentos no válidos
+    Config(String),
+    // La pregunta al modelo no obtuvo respuesta por otro motivo: sin conexión, una
+    // respuesta que no se entiende o demasiadas rondas de herramientas
+    Model(String),
+    // Una herramienta ejecutada directamente falló
+    Tool { name: String, message: String },
+}
+
+impl IAgentError {
+    pub fn api(status: u16, body: &str) -> IAgentError {
+        let detail = ApiErrorDetail::parse(body);
+        // Las páginas de error HTML de los proxies ocupan muchas líneas
+        let body = body.split_whitespace().collect::<Vec<_>>().join(" ");
+        let body = match body.char_indices().nth(MAX_BODY_CHARS) {
+            Some((end, _)) => format!("{}…", &body[..end]),
+            None => body,
+        };
+        IAgentError::Api { status, body, detail }
+    }
+
+    pub fn excel(file: &str, message: impl Into<String>) -> IAgentError {
+        IAgentError::Excel {
+            file: file.to_string(),
+            sheet: None,
+            cell: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn parse(input: &str, message: impl Into<String>) -> IAgentError {
+        IAgentError::Parse {
+            input: input.to_string(),
+            message: message.into(),
+        }
+    }
+
+    // El IAgentError de la cadena de un error de anyhow, aunque se le haya añadido contexto
+    pub(crate) fn find(error: &anyhow::Error) -> Option<&IAgentError> {
+        error.chain().find_map(|cause| cause.downcast_ref::<IAgentError>())
+    }
+
+    // El IAgentError de la cadena o, si el fallo no tiene tipo, el de `untyped` con
+    // el mensaje completo
+    pub(crate) fn classify(error: anyhow::Error, untyped: impl FnOnce(String) -> IAgentError) -> IAgentError {
+        match IAgentError::find(&error) {
+            Some(typed) => typed.clone(),
+            None => untyped(format!("{:#}", error)),
+        }
+    }
+
+    // Qué ha fallado en la API, por el código HTTP y el error del cuerpo
+    pub fn api_failure(&self) -> Option<ApiFailure> {
+        let IAgentError::Api { status, body, detail } = self else {
+            return None;
+        };
+        // Sin un JSON reconocible se busca en el cuerpo tal cual
+        let text = match detail {
+            Some(detail) => format!("{} {} {}", detail.code.as_deref().unwrap_or(""), detail.kind.as_deref().unwrap_or(""), detail.message),
+            None => body.clone(),
+        }
+        .to_lowercase();
+        let mentions = |needles: &[&str]| needles.iter().any(|needle| text.contains(needle));
+        let failure = if mentions(&["context_length", "context length", "maximum context", "too many tokens", "prompt is too long"]) {
+            ApiFailure::ContextLength
+        } else if *status == 401 || mentions(&["invalid_api_key", "authentication", "api key", "api_key", "unauthorized"]) {
+            ApiFailure::InvalidKey
+        } else if *status == 402 || mentions(&["insufficient balance", "insufficient_quota", "billing"]) {
+            ApiFailure::NoBalance
+        } else if mentions(&["model_not_found", "deploymentnotfound"])
+            || (mentions(&["model"]) && mentions(&["not exist", "not found", "does not exist", "no existe", "unknown", "invalid model"]))
+        {
+            ApiFailure::ModelNotFound
+        } else if *status == 403 {
+            ApiFailure::InvalidKey
+        } else if *status == 404 {
+            ApiFailure::NotFound
+        } else if *status == 429 || mentions(&["rate limit", "rate_limit"]) {
+            ApiFailure::RateLimit
+        } else if *status >= 500 {
+            ApiFailure::Server
+        } else {
+            ApiFailure::Other
+        };
+        Some(failure)
+    }
+
+    // Sugerencia para el usuario según el tipo de fallo
+    pub fn hint(&self) -> Option<&'static str> {
+        match self.api_failure()? {
+            ApiFailure::InvalidKey => Some("Revisa DEEPSEEK_API_KEY (o la cabecera de autenticación en IAGENT_EXTRA_HEADERS); `doctor` comprueba la clave"),
+            ApiFailure::NoBalance => Some("La cuenta no tiene saldo; recárgalo en la consola del proveedor"),
+            ApiFailure::ModelNotFound => {
+                Some("El modelo no existe o no está disponible con esta clave; revisa DEEPSEEK_MODEL o cámbialo con `modelo <nombre>`")
+            }
+            ApiFailure::ContextLength => Some(
+                "La petición no cabe en el contexto del modelo: empieza otra conversación con `contexto crear <nombre>`, baja IAGENT_HISTORY_TOKENS o usa un modelo con más contexto",
+            ),
+            ApiFailure::NotFound => Some("Revisa DEEPSEEK_API_URL / IAGENT_BASE_URL y el nombre del modelo"),
+            ApiFailure::RateLimit => Some("Límite de peticiones alcanzado; espera un momento y repite"),
+            ApiFailure::Server => Some("El servicio del modelo falló; vuelve a intentarlo más tarde"),
+            ApiFailure::Other => None,
+        }
+    }
+}
+
+impl fmt::Display for IAgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IAgentError::Api {
+                status,
+                detail: Some(detail),
+                ..
+            } => {
+                write!(f, "La API respondió {}", status)?;
+                let mut labels: Vec<&str> = detail.kind.as_deref().into_iter().collect();
+                if let Some(code) = detail.code.as_deref().filter(|code| !labels.contains(code)) {
+                    labels.push(code);
+                }
+                if !labels.is_empty() {
+                    write!(f, " ({})", labels.join(", "))?;
+                }
+                write!(f, ": {}", detail.message)
+            }
+            IAgentError::Api { status, body, .. } if body.is_empty() => write!(f, "La API respondió {}", status),
+            IAgentError::Api { status, body, .. } => write!(f, "La API respondió {}: {}", status, body),
+            IAgentError::Excel {
+                file,
+                sheet,
+                cell,
+                message,
+            } => {
+                write!(f, "{} ({}", message, file)?;
+                if let Some(sheet) = sheet {
+                    write!(f, ", hoja {}", sheet)?;
+                }
+                if let Some(cell) = cell {
+                    write!(f, ", celda {}", cell)?;
+                }
+                write!(f, ")")
+            }
+            IAgentError::Parse { input, message } => write!(f, "{}: '{}'", message, input),
+            IAgentError::Config(message) | IAgentError::Model(message) => write!(f, "{}", message),
+            IAgentError::Tool { name, message } => write!(f, "La herramienta {} falló: {}", name, message),
+        }
+    }
+}
+
+impl std::error::Error for IAgentError {}
+
+// Error que devuelve la API en el cuerpo: {"error": {"message", "type", "code"}}
+// en DeepSeek y OpenAI, o {"message"}, {"detail"} o {"error": "..."} en otros
+// proveedores y pasarelas
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiErrorDetail {
+    pub message: String,
+    // `type` del error (invalid_request_error, authentication_error...)
+    pub kind: Option<String>,
+    // Texto o número según el proveedor
+    pub code: Option<String>,
+}
+
+impl ApiErrorDetail {
+    pub fn parse(body: &str) -> Option<ApiErrorDetail> {
+        let value: Value = serde_json::from_str(body.trim()).ok()?;
+        let error = match value.get("error") {
+            Some(error @ Value::Object(_)) => error,
+            Some(Value::String(message)) => {
+                return Some(ApiErrorDetail {
+                    message: message.clone(),
+                    kind: None,
+                    code: value.get("code").and_then(label),
+                })
+            }
+            _ => &value,
+        };
+        let message = ["message", "detail", "msg"]
+            .iter()
+            .find_map(|key| error.get(*key).and_then(label))
+            .filter(|message| !message.trim().is_empty())?;
+        Some(ApiErrorDetail {
+            message: message.trim().to_string(),
+            kind: error.get("type").and_then(label),
+            code: error.get("code").and_then(label),
+        })
+    }
+}
+
+fn label(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) if !text.is_empty() => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiFailure {
+    InvalidKey,
+    NoBalance,
+    ModelNotFound,
+    ContextLength,
+    // La URL no existe (404 sin más detalle)
+    NotFound,
+    RateLimit,
+    Server,
+    Other,
+}
@@ -0,0 +1,134 @@
+// Eventos del agente para las aplicaciones que lo usan como biblioteca: métricas,
+// registro de auditoría o una interfaz propia sin leer lo que se escribe en la
+// terminal. Un observador se suscribe a los ajustes de un agente (`Settings::subscribe`)
+// y recibe cada evento de ese agente en el hilo que lo produce, así que debe volver
+// enseguida. IAGENT_EVENTS_LOG escribe
+// además los eventos de la sesión en un archivo, uno por línea en JSON.
+use crate::settings;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "evento")]
+pub enum AgentEvent {
+    // Petición al modelo, antes de enviarla (también si la responde la caché)
+    #[serde(rename = "peticion")]
+    PromptSent {
+        #[serde(rename = "modelo")]
+        model: String,
+        #[serde(rename = "mensajes")]
+        messages: usize,
+        #[serde(rename = "herramientas")]
+        tools: usize,
+    },
+    #[serde(rename = "tokens")]
+    TokensUsed {
+        #[serde(rename = "modelo")]
+        model: String,
+        #[serde(rename = "entrada")]
+        prompt_tokens: u64,
+        #[serde(rename = "salida")]
+        completion_tokens: u64,
+        // Respuesta de la caché local: no ha costado nada
+        #[serde(rename = "cache")]
+        cached: bool,
+    },
+    #[serde(rename = "herramienta")]
+    ToolInvoked {
+        #[serde(rename = "nombre")]
+        name: String,
+        #[serde(rename = "correcto")]
+        ok: bool,
+        #[serde(rename = "milisegundos")]
+        millis: u64,
+    },
+    #[serde(rename = "archivo_escrito")]
+    FileWritten {
+        #[serde(rename = "archivo")]
+        path: String,
+    },
+    Error {
+        // "modelo" o "herramienta <nombre>"
+        #[serde(rename = "origen")]
+        source: String,
+        #[serde(rename = "mensaje")]
+        message: String,
+    },
+    // Lo que la terminal muestra mientras el agente trabaja: herramientas que se
+    // ejecutan, esperas por los límites de la API, avisos sobre el contenido...
+    #[serde(rename = "aviso")]
+    Notice {
+        #[serde(rename = "mensaje")]
+        message: String,
+    },
+}
+
+pub trait AgentObserver: Send + Sync {
+    fn on_event(&self, event: &AgentEvent);
+}
+
+// Un cierre sirve de observador: settings.subscribe(Arc::new(|event: &AgentEvent| ...))
+impl<F> AgentObserver for F
+where
+    F: Fn(&AgentEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &AgentEvent) {
+        self(event)
+    }
+}
+
+// Avisa a los observadores del agente en curso
+pub fn emit(event: AgentEvent) {
+    settings::current().emit(event);
+}
+
+// Un aviso del agente: a los observadores como `AgentEvent::Notice` y, si el
+// agente es el de la terminal, también en pantalla
+pub fn notice(message: impl Into<String>) {
+    let message = message.into();
+    let settings = settings::current();
+    if settings.terminal() {
+        println!("{}", message);
+    }
+    settings.emit(AgentEvent::Notice { message });
+}
+
+pub fn file_written(path: &Path) {
+    emit(AgentEvent::FileWritten {
+        path: path.display().to_string(),
+    });
+}
+
+// Registro de IAGENT_EVENTS_LOG: {"hora": <ms desde 1970>, "evento": "tokens", ...}
+pub struct EventLog {
+    file: Mutex<File>,
+}
+
+impl EventLog {
+    // Añade al final del archivo, creándolo si no existe
+    pub fn open(path: &Path) -> Result<EventLog> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!("No se pudo abrir el registro de eventos {}", path.display()))?;
+        Ok(EventLog { file: Mutex::new(file) })
+    }
+}
+
+impl AgentObserver for EventLog {
+    fn on_event(&self, event: &AgentEvent) {
+        let Ok(mut line) = serde_json::to_value(event) else { return };
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        line["hora"] = millis.into();
+        // Un registro que no se puede escribir no debe interrumpir la sesión
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
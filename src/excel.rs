@@ -0,0 +1,1434 @@
+use crate::backup;
+use crate::convert;
+use crate::crypto;
+use crate::dates;
+use crate::error::IAgentError;
+use crate::formats::{self, CellStyle, Styles};
+use crate::formulas;
+use crate::header;
+use crate::hyperlinks;
+use crate::layout::{self, SheetLayout};
+use crate::limits;
+use crate::sampling;
+use crate::settings;
+use crate::verify;
+use crate::xlsx_patch::{self, find_element_start, find_tags, xml_attr, xml_unescape};
+use anyhow::{bail, Context, Result};
+use calamine::{open_workbook, DataType, Reader, Xlsx};
+use crate::merges;
+use crate::notes::{self, Note};
+use crate::outline::{self, Outline};
+use crate::protection::{self, SheetProtection};
+use crate::tables::{self, TableSpec};
+use rust_xlsxwriter::{Chart, ChartType, DocProperties, Format, FormatAlign, Url, Workbook, Worksheet};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+use zip::ZipArchive;
+
+// Días entre 1899-12-30 (época de Excel) y 1970-01-01
+pub const EXCEL_EPOCH_OFFSET: i64 = 25569;
+// A partir de este tamaño `leer_excel` lee por streaming
+pub const STREAMING_THRESHOLD_BYTES: u64 = 20 * 1024 * 1024;
+// Filas decodificadas que pueden esperar en el canal del lector por streaming
+const STREAM_BUFFER_ROWS: usize = 256;
+const STREAM_CHUNK_BYTES: usize = 64 * 1024;
+// Hilos que leen hojas a la vez; cada uno abre el libro y carga sus textos compartidos
+const MAX_READ_THREADS: usize = 8;
+
+// Valor tipado de una celda
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellValue {
+    Empty,
+    Bool(bool),
+    Number(f64),
+    // Fecha/hora como número de serie de Excel
+    DateTime(f64),
+    Text(String),
+    Error(String),
+}
+
+impl CellValue {
+    // Infiere el tipo de un valor escrito como texto (CSV, comandos, etc.)
+    pub fn infer(raw: &str) -> CellValue {
+        let value = raw.trim();
+        if value.is_empty() {
+            return CellValue::Empty;
+        }
+        if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("verdadero") {
+            return CellValue::Bool(true);
+        }
+        if value.eq_ignore_ascii_case("false") || value.eq_ignore_ascii_case("falso") {
+            return CellValue::Bool(false);
+        }
+        if looks_numeric(value) {
+            if let Ok(number) = value.parse::<f64>() {
+                return CellValue::Number(number);
+            }
+        }
+        if let Some(serial) = parse_iso_datetime(value) {
+            return CellValue::DateTime(serial);
+        }
+        CellValue::Text(value.to_string())
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            CellValue::Number(n) | CellValue::DateTime(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CellValue::Empty => "vacío",
+            CellValue::Bool(_) => "booleano",
+            CellValue::Number(_) => "número",
+            CellValue::DateTime(_) => "fecha",
+            CellValue::Text(_) => "texto",
+            CellValue::Error(_) => "error",
+        }
+    }
+}
+
+impl From<&DataType> for CellValue {
+    fn from(value: &DataType) -> Self {
+        match value {
+            DataType::Int(i) => CellValue::Number(*i as f64),
+            DataType::Float(f) => CellValue::Number(*f),
+            DataType::String(s) => CellValue::Text(s.clone()),
+            DataType::Bool(b) => CellValue::Bool(*b),
+            DataType::DateTime(serial) => CellValue::DateTime(*serial),
+            DataType::Duration(d) => CellValue::Number(*d),
+            DataType::DateTimeIso(s) => match parse_iso_datetime(s) {
+                Some(serial) => CellValue::DateTime(serial),
+                None => CellValue::Text(s.clone()),
+            },
+            DataType::DurationIso(s) => CellValue::Text(s.clone()),
+            DataType::Error(e) => CellValue::Error(e.to_string()),
+            DataType::Empty => CellValue::Empty,
+        }
+    }
+}
+
+impl fmt::Display for CellValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CellValue::Empty => Ok(()),
+            CellValue::Bool(b) => write!(f, "{}", b),
+            CellValue::Number(n) => write!(f, "{}", n),
+            CellValue::DateTime(serial) => write!(f, "{}", excel_serial_to_iso(*serial)),
+            CellValue::Text(s) => write!(f, "{}", s),
+            CellValue::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// Tipos de gráfico que se pueden insertar al guardar una hoja
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartKind {
+    Column,
+    Bar,
+    Line,
+    Pie,
+    Area,
+}
+
+impl ChartKind {
+    pub fn parse(value: &str) -> Option<ChartKind> {
+        match value.to_lowercase().as_str() {
+            "columnas" | "column" => Some(ChartKind::Column),
+            "barras" | "bar" => Some(ChartKind::Bar),
+            "lineas" | "líneas" | "line" => Some(ChartKind::Line),
+            "circular" | "pie" => Some(ChartKind::Pie),
+            "area" | "área" => Some(ChartKind::Area),
+            _ => None,
+        }
+    }
+
+    fn chart_type(&self) -> ChartType {
+        match self {
+            ChartKind::Column => ChartType::Column,
+            ChartKind::Bar => ChartType::Bar,
+            ChartKind::Line => ChartType::Line,
+            ChartKind::Pie => ChartType::Pie,
+            ChartKind::Area => ChartType::Area,
+        }
+    }
+}
+
+// Gráfico sobre columnas de la propia hoja; la fila 0 da los nombres de las series
+#[derive(Debug, Clone)]
+pub struct ChartSpec {
+    pub kind: ChartKind,
+    pub title: String,
+    pub category_col: usize,
+    pub value_cols: Vec<usize>,
+    pub first_row: usize,
+    pub last_row: usize,
+    // Celda superior izquierda donde se ancla el gráfico
+    pub anchor: (usize, usize),
+}
+
+// Una hoja con sus filas, empezando siempre en A1
+#[derive(Debug, Clone, Default)]
+pub struct SheetData {
+    pub name: String,
+    pub rows: Vec<Vec<CellValue>>,
+    // Formato numérico por columna (p. ej. "0.0%"), aplicado al guardar
+    pub column_formats: BTreeMap<usize, String>,
+    pub charts: Vec<ChartSpec>,
+    // Fórmulas por (fila, columna), sin '='; la celda guarda su último resultado
+    pub formulas: BTreeMap<(usize, usize), String>,
+    // Paneles inmovilizados, anchos y columnas ocultas, aplicados al guardar
+    pub layout: SheetLayout,
+    // La primera fila ya es de datos (ver `header::detect`); las columnas se
+    // llaman entonces "Columna A", "Columna B"...
+    pub headerless: bool,
+    // Rangos de celdas combinadas; el valor está en la celda superior izquierda
+    pub merges: Vec<CellRange>,
+    // Destino del enlace por (fila, columna); la celda guarda el texto visible
+    pub hyperlinks: BTreeMap<(usize, usize), String>,
+    // Filas y columnas agrupadas (esquema)
+    pub outline: Outline,
+    // Tablas de Excel (Insertar > Tabla) de la hoja
+    pub tables: Vec<TableSpec>,
+    // Nota de celda por (fila, columna)
+    pub notes: BTreeMap<(usize, usize), Note>,
+    // Fragmentos (negrita, texto) de las celdas con texto enriquecido
+    pub rich_text: BTreeMap<(usize, usize), Vec<(bool, String)>>,
+    // Formato original por (fila, columna) de un libro leído, que se vuelve a aplicar al guardar
+    pub styles: BTreeMap<(usize, usize), CellStyle>,
+    // Hoja protegida: solo se editan las celdas con estilo desbloqueado
+    pub protection: Option<SheetProtection>,
+}
+
+impl SheetData {
+    pub fn new(name: &str) -> Self {
+        SheetData {
+            name: name.to_string(),
+            ..SheetData::default()
+        }
+    }
+
+    // Filas como texto aplicando los formatos de porcentaje de columna
+    pub fn display_rows(&self) -> Vec<Vec<String>> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(row_idx, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(col_idx, cell)| match (cell, self.column_formats.get(&col_idx)) {
+                        (CellValue::Number(n), Some(format)) if row_idx >= self.data_start() && format.ends_with('%') => {
+                            let decimals = format.split('.').nth(1).map(|d| d.len() - 1).unwrap_or(0);
+                            format!("{:.*}%", decimals, n * 100.0)
+                        }
+                        _ => cell.to_string(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn headers(&self) -> Vec<String> {
+        if self.headerless {
+            let width = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+            return (0..width).map(|idx| format!("Columna {}", column_letters(idx))).collect();
+        }
+        // Una celda combinada con la de su izquierda sigue vacía: el rango combinado
+        // se informa aparte y no presta su texto a las columnas que abarca
+        self.rows
+            .first()
+            .map(|row| row.iter().enumerate().map(|(idx, cell)| header_name(idx, &cell.to_string())).collect())
+            .unwrap_or_default()
+    }
+
+    // Resuelve una columna por nombre de encabezado, letra (B) o número (2)
+    pub fn column_index(&self, spec: &str) -> Option<usize> {
+        let spec = spec.trim();
+        if let Some(idx) = self
+            .headers()
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(spec))
+        {
+            return Some(idx);
+        }
+        if let Ok(number) = spec.parse::<usize>() {
+            return number.checked_sub(1);
+        }
+        column_from_letters(spec)
+    }
+
+    // Índice de la primera fila de datos: 1, o 0 si la hoja no tiene encabezado
+    pub fn data_start(&self) -> usize {
+        if self.headerless {
+            0
+        } else {
+            1
+        }
+    }
+
+    // Escribe una celda (una fórmula deja el valor vacío hasta que Excel la
+    // calcule) y devuelve lo que había, como texto
+    pub fn set_cell(&mut self, row: usize, col: usize, value: CellValue, formula: Option<String>) -> String {
+        if self.rows.len() <= row {
+            self.rows.resize(row + 1, Vec::new());
+        }
+        if self.rows[row].len() <= col {
+            self.rows[row].resize(col + 1, CellValue::Empty);
+        }
+        let previous = match self.formulas.get(&(row, col)) {
+            Some(formula) => format!("={}", formula),
+            None => self.rows[row][col].to_string(),
+        };
+        self.rows[row][col] = value;
+        match formula {
+            Some(formula) => self.formulas.insert((row, col), formula),
+            None => self.formulas.remove(&(row, col)),
+        };
+        self.rich_text.remove(&(row, col));
+        previous
+    }
+
+    // Valor de una celda; vacío fuera de los datos
+    pub fn cell(&self, row: usize, col: usize) -> &CellValue {
+        static EMPTY: CellValue = CellValue::Empty;
+        self.rows.get(row).and_then(|cells| cells.get(col)).unwrap_or(&EMPTY)
+    }
+
+    pub fn data_rows(&self) -> &[Vec<CellValue>] {
+        self.rows.get(self.data_start()..).unwrap_or_default()
+    }
+
+    // Filas como texto, útil para resúmenes y salida por terminal
+    pub fn text_rows(&self) -> Vec<Vec<String>> {
+        self.rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect()
+    }
+}
+
+// Hoja para las pruebas, con cada celda interpretada como al leer un CSV ("10" es
+// un número, "2024-01-10" una fecha y "" una celda vacía)
+#[cfg(test)]
+impl SheetData {
+    pub fn from_rows(name: &str, rows: &[&[&str]]) -> SheetData {
+        SheetData {
+            name: name.to_string(),
+            rows: rows.iter().map(|row| row.iter().map(|cell| CellValue::infer(cell)).collect()).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+// Libro completo con las hojas en el orden original
+#[derive(Debug, Clone, Default)]
+pub struct WorkbookData {
+    pub sheets: Vec<SheetData>,
+}
+
+impl WorkbookData {
+    pub fn sheet(&self, name: &str) -> Option<&SheetData> {
+        self.sheets
+            .iter()
+            .find(|s| s.name.eq_ignore_ascii_case(name))
+    }
+
+    // Como `sheet`, con un error que indica el archivo y la hoja que faltan
+    pub fn require_sheet(&self, file: &str, name: &str) -> Result<&SheetData, IAgentError> {
+        self.sheet(name).ok_or_else(|| IAgentError::Excel {
+            file: file.to_string(),
+            sheet: Some(name.to_string()),
+            cell: None,
+            message: "No existe la hoja".to_string(),
+        })
+    }
+
+    // Sustituye la hoja con el mismo nombre o la añade al final
+    pub fn upsert_sheet(&mut self, sheet: SheetData) {
+        match self.sheets.iter_mut().find(|s| s.name == sheet.name) {
+            Some(existing) => *existing = sheet,
+            None => self.sheets.push(sheet),
+        }
+    }
+}
+
+// Convierte letras de columna (A, Z, AA...) en índice desde 0
+pub fn column_from_letters(letters: &str) -> Option<usize> {
+    if letters.is_empty() || letters.len() > 3 || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let mut index = 0usize;
+    for c in letters.to_ascii_uppercase().chars() {
+        index = index * 26 + (c as usize - 'A' as usize + 1);
+    }
+    Some(index - 1)
+}
+
+// Función para leer un archivo Excel
+pub fn read_excel_file(filename: &str) -> Result<WorkbookData> {
+    read_excel_file_timed(filename).map(|(data, _)| data)
+}
+
+// Como `read_excel_file`, con lo que tardó cada hoja. Las hojas se leen en
+// paralelo: calamine necesita el libro en exclusiva para leer una hoja, así que
+// cada hilo abre el suyo y va tomando la siguiente hoja pendiente.
+pub fn read_excel_file_timed(filename: &str) -> Result<(WorkbookData, Vec<(String, Duration)>)> {
+    let path = Path::new(filename);
+    limits::check_file_size(path)?;
+    ensure_not_encrypted(path)?;
+    let open = || -> Result<Xlsx<BufReader<File>>> {
+        open_workbook(path).map_err(|e| IAgentError::excel(filename, format!("No se pudo abrir el archivo: {}", e)).into())
+    };
+    let workbook = open()?;
+    let names = workbook.sheet_names().to_owned();
+    let styles = xlsx_patch::read_part_from(path, "xl/styles.xml", None)?
+        .map(|xml| Styles::parse(&xml))
+        .unwrap_or_default();
+
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_READ_THREADS)
+        .min(names.len())
+        .max(1);
+    let next = AtomicUsize::new(0);
+    let mut first = Some(workbook);
+    let mut parsed = thread::scope(|scope| -> Result<Vec<(usize, SheetData, Duration)>> {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                let opened = first.take();
+                let (names, styles, next, open) = (&names, &styles, &next, &open);
+                scope.spawn(settings::inherit_blocking(move || -> Result<Vec<(usize, SheetData, Duration)>> {
+                    let mut workbook = match opened {
+                        Some(workbook) => workbook,
+                        None => open()?,
+                    };
+                    // Lo que calamine no expone se lee del XML de cada hoja, solo
+                    // cuando le toca a esa hoja
+                    let mut extras = xlsx_patch::SheetXmlReader::open(path)?;
+                    let mut parsed = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(name) = names.get(idx) else { break };
+                        let started = Instant::now();
+                        let extra = extras.as_mut().and_then(|reader| reader.sheet(name));
+                        let sheet = parse_sheet(&mut workbook, filename, name, extra.as_ref(), styles)?;
+                        parsed.push((idx, sheet, started.elapsed()));
+                    }
+                    Ok(parsed)
+                }))
+            })
+            .collect();
+        let mut parsed = Vec::new();
+        for handle in handles {
+            parsed.extend(handle.join().map_err(|_| anyhow::anyhow!("La lectura de una hoja de {} falló", filename))??);
+        }
+        Ok(parsed)
+    })?;
+
+    parsed.sort_by_key(|(idx, ..)| *idx);
+    let timings = parsed.iter().map(|(_, sheet, elapsed)| (sheet.name.clone(), *elapsed)).collect();
+    let result = WorkbookData {
+        sheets: parsed.into_iter().map(|(_, sheet, _)| sheet).collect(),
+    };
+    Ok((result, timings))
+}
+
+// Una hoja con lo que se lee de su XML; si calamine no puede leerla el error
+// dice qué hoja es, en vez de quitarla del libro sin avisar
+fn parse_sheet(
+    workbook: &mut Xlsx<BufReader<File>>,
+    filename: &str,
+    sheet_name: &str,
+    extra: Option<&xlsx_patch::SheetXml>,
+    styles: &Styles,
+) -> Result<SheetData> {
+    let failed = |message: String| IAgentError::Excel {
+        file: filename.to_string(),
+        sheet: Some(sheet_name.to_string()),
+        cell: None,
+        message,
+    };
+    let range = match workbook.worksheet_range(sheet_name) {
+        Some(Ok(range)) => range,
+        Some(Err(e)) => return Err(failed(format!("No se pudo leer la hoja: {}", e)).into()),
+        None => return Err(failed("No se encontró la hoja en el libro".to_string()).into()),
+    };
+    let mut sheet = SheetData::new(sheet_name);
+    // calamine devuelve el rango desde la primera celda usada
+    let (start_row, start_col) = range.start().unwrap_or((0, 0));
+    sheet.rows.resize(start_row as usize, Vec::new());
+    for row in range.rows() {
+        let mut row_data = vec![CellValue::Empty; start_col as usize];
+        row_data.extend(row.iter().map(CellValue::from));
+        sheet.rows.push(row_data);
+    }
+    if let Some(part) = extra {
+        sheet.merges = merges::parse(&part.xml);
+        sheet.hyperlinks = hyperlinks::parse(&part.xml, part.rels.as_deref());
+        sheet.notes = part.comments.as_deref().map(notes::parse).unwrap_or_default();
+        sheet.outline = outline::parse(&part.xml);
+        sheet.tables = part.tables.iter().filter_map(|xml| tables::parse(xml)).collect();
+        sheet.styles = styles.cell_styles(&part.xml);
+        sheet.layout = formats::sheet_layout(&part.xml);
+        sheet.protection = protection::parse(&part.xml);
+    }
+    sheet.headerless = !header::detect(&sheet.rows);
+    dates::detect_date_columns(&mut sheet);
+    Ok(sheet)
+}
+
+pub fn create_excel_file(filename: &str) -> Result<()> {
+    let mut workbook = Workbook::new();
+    let _worksheet = workbook.add_worksheet();
+
+    backup::before_write(Path::new(filename))?;
+    workbook.save(filename)?;
+    verify::after_write(Path::new(filename))?;
+    Ok(())
+}
+
+// Función para escribir datos en un archivo Excel. Formatos de `data`:
+//   - simple: filas separadas por punto y coma, columnas por coma, en la hoja Sheet1
+//     (el archivo se reemplaza); con `hoja=<nombre>` delante, en esa hoja
+//   - JSON: {"Resumen": [[..]], "Detalle": [[..]]} para varias hojas a la vez
+// Con hojas con nombre se conservan las demás hojas del archivo, con sus fórmulas. Si
+// aun así se perderían fórmulas (las de las hojas reemplazadas) o gráficos, solo se
+// escribe con `rewrite`. Devuelve las hojas escritas.
+pub fn write_excel_data(filename: &str, data: &str, rewrite: bool) -> Result<Vec<String>> {
+    let data = data.trim();
+    let (sheets, keep_others) = if data.starts_with('{') {
+        let value: serde_json::Value =
+            serde_json::from_str(data).map_err(|e| IAgentError::parse(data, format!("JSON no válido ({})", e)))?;
+        let workbook = convert::workbook_from_json(&value)?;
+        if workbook.sheets.is_empty() {
+            bail!("El JSON no contiene ninguna hoja");
+        }
+        (workbook.sheets, true)
+    } else {
+        let (name, rows) = match data.strip_prefix("hoja=") {
+            Some(rest) => {
+                let (name, rows) = rest.split_once(char::is_whitespace).context("Faltan los datos después de hoja=")?;
+                (name, rows.trim())
+            }
+            None => ("Sheet1", data),
+        };
+        // Parseamos los datos (formato simple: filas separadas por punto y coma, columnas por coma)
+        let mut sheet = SheetData::new(name);
+        for line in rows.split(';') {
+            sheet.rows.push(line.split(',').map(|value| CellValue::Text(value.trim().to_string())).collect());
+        }
+        (vec![sheet], data.starts_with("hoja="))
+    };
+
+    let names: Vec<String> = sheets.iter().map(|sheet| sheet.name.clone()).collect();
+    let path = Path::new(filename);
+    let mut workbook = if keep_others && path.exists() {
+        let mut existing = read_excel_file(filename)?;
+        formulas::read(filename, &mut existing)?;
+        existing
+    } else {
+        WorkbookData::default()
+    };
+    for mut sheet in sheets {
+        hyperlinks::apply_bold_markup(&mut sheet);
+        workbook.upsert_sheet(sheet);
+    }
+    let lost = lost_on_rewrite(path, &workbook);
+    if !lost.is_empty() && !rewrite {
+        bail!(
+            "Escribir en {} lo reescribe a partir de los valores y se perderían {} del archivo; añade --reescribir para escribirlo igualmente (deshacer lo recupera)",
+            filename,
+            lost.join(" y ")
+        );
+    }
+    overwrite_workbook(path, &workbook)?;
+    Ok(names)
+}
+
+// Guarda un libro completo conservando el tipo de cada celda; las columnas
+// sensibles configuradas (--cifrar) se cifran antes de escribir. Un xlsx que ya
+// existe no se reescribe si se perderían fórmulas o gráficos suyos.
+pub fn save_workbook(path: &Path, data: &WorkbookData) -> Result<()> {
+    check_rewrite(path, data)?;
+    overwrite_workbook(path, data)
+}
+
+// Como save_workbook pero sin comprobar lo que se pierde del archivo anterior:
+// para guardar y escribir_excel con --reescribir, que ya lo han avisado
+pub fn overwrite_workbook(path: &Path, data: &WorkbookData) -> Result<()> {
+    match crypto::protect_outputs(data)? {
+        Some(protected) => write_workbook(path, &protected),
+        None => write_workbook(path, data),
+    }
+}
+
+// Lo que tiene el xlsx de `path` y no conserva reescribirlo a partir de `data`:
+// las fórmulas de las hojas que no se leyeron con ellas y los gráficos, que no
+// se leen nunca. Vacío si el archivo no existe o no es un xlsx.
+pub fn lost_on_rewrite(path: &Path, data: &WorkbookData) -> Vec<String> {
+    if !path.extension().is_some_and(|e| e.eq_ignore_ascii_case("xlsx")) {
+        return Vec::new();
+    }
+    let Ok(Some(mut reader)) = xlsx_patch::SheetXmlReader::open(path) else {
+        return Vec::new();
+    };
+    let without_formulas: Vec<String> = reader
+        .sheet_names()
+        .into_iter()
+        .filter(|name| !data.sheets.iter().any(|sheet| sheet.name == *name && !sheet.formulas.is_empty()))
+        .collect();
+    let formulas: usize = without_formulas
+        .iter()
+        .filter_map(|name| reader.sheet(name))
+        .map(|sheet| find_tags(&sheet.xml, "f").len())
+        .sum();
+    let charts = reader
+        .part_names()
+        .iter()
+        .filter(|part| part.starts_with("xl/charts/chart") && part.ends_with(".xml"))
+        .count();
+    let mut lost = Vec::new();
+    if formulas > 0 {
+        lost.push(format!("{} fórmula(s)", formulas));
+    }
+    if charts > 0 {
+        lost.push(format!("{} gráfico(s)", charts));
+    }
+    lost
+}
+
+fn check_rewrite(path: &Path, data: &WorkbookData) -> Result<()> {
+    let lost = lost_on_rewrite(path, data);
+    if !lost.is_empty() {
+        bail!(
+            "No se reescribe {}: se guardaría a partir de los valores y se perderían {} del original; guarda el resultado en otro archivo",
+            path.display(),
+            lost.join(" y ")
+        );
+    }
+    Ok(())
+}
+
+// Propiedades del documento con los parámetros del modelo que intervino en la
+// sesión, para saber con qué se generó el libro (Archivo > Propiedades)
+fn generation_properties(params: &sampling::ModelParams) -> DocProperties {
+    let mut properties = DocProperties::new()
+        .set_comment(format!("Generado por IAgent con {}", params.describe()))
+        .set_custom_property("IAgent modelo", params.model.as_str());
+    if let Some(temperature) = params.temperature {
+        properties = properties.set_custom_property("IAgent temperatura", temperature);
+    }
+    if let Some(top_p) = params.top_p {
+        properties = properties.set_custom_property("IAgent top_p", top_p);
+    }
+    if let Some(seed) = params.seed {
+        // Como texto: una semilla puede no caber en un entero de Excel
+        properties = properties.set_custom_property("IAgent semilla", seed.to_string());
+    }
+    properties
+}
+
+// Guarda sin el cifrado automático de columnas (lo usa descifrar_columna)
+pub fn save_workbook_unprotected(path: &Path, data: &WorkbookData) -> Result<()> {
+    check_rewrite(path, data)?;
+    write_workbook(path, data)
+}
+
+fn write_workbook(path: &Path, data: &WorkbookData) -> Result<()> {
+    limits::check_write_cells(data.sheets.iter().flat_map(|sheet| &sheet.rows).map(Vec::len).sum())?;
+    let mut workbook = Workbook::new();
+    if let Some(params) = sampling::last() {
+        workbook.set_properties(&generation_properties(&params));
+    }
+    let (plain_format, bold_format) = (Format::new(), Format::new().set_bold());
+
+    for sheet in &data.sheets {
+        let worksheet = workbook.add_worksheet();
+        worksheet
+            .set_name(&sheet.name)
+            .context(format!("Nombre de hoja no válido: {}", sheet.name))?;
+        let column_formats: BTreeMap<usize, Format> = sheet
+            .column_formats
+            .iter()
+            .map(|(col, num_format)| (*col, Format::new().set_num_format(num_format)))
+            .collect();
+        for (row_idx, row) in sheet.rows.iter().enumerate() {
+            for (col_idx, cell) in row.iter().enumerate() {
+                let (r, c) = (row_idx as u32, col_idx as u16);
+                // Las celdas combinadas se escriben después con merge_range, y los enlaces con write_url
+                if sheet.merges.iter().any(|m| m.contains(row_idx, col_idx))
+                    || sheet.hyperlinks.contains_key(&(row_idx, col_idx))
+                {
+                    continue;
+                }
+                if let (CellValue::Text(_), Some(runs)) = (cell, sheet.rich_text.get(&(row_idx, col_idx))) {
+                    let segments: Vec<(&Format, &str)> = runs
+                        .iter()
+                        .map(|(bold, text)| (if *bold { &bold_format } else { &plain_format }, text.as_str()))
+                        .collect();
+                    worksheet.write_rich_string(r, c, &segments)?;
+                    continue;
+                }
+                let column_format = sheet.column_formats.get(&col_idx).map(String::as_str);
+                match (cell, cell_format(sheet, row_idx, col_idx, None, None)) {
+                    // Una celda vacía solo se escribe si tenía formato (un relleno, un borde de tabla)
+                    (CellValue::Empty, Some(format)) => {
+                        worksheet.write_blank(r, c, &format)?;
+                    }
+                    (CellValue::Empty, None) => {}
+                    (CellValue::Bool(b), Some(format)) => {
+                        worksheet.write_boolean_with_format(r, c, *b, &format)?;
+                    }
+                    (CellValue::Bool(b), None) => {
+                        worksheet.write_boolean(r, c, *b)?;
+                    }
+                    (CellValue::Number(n), _) => match cell_format(sheet, row_idx, col_idx, column_format, None) {
+                        Some(format) => {
+                            worksheet.write_number_with_format(r, c, *n, &format)?;
+                        }
+                        None => {
+                            worksheet.write_number(r, c, *n)?;
+                        }
+                    },
+                    // Sin formato original, las fechas sin hora se guardan con formato de solo fecha
+                    (CellValue::DateTime(serial), _) => {
+                        let fallback = if serial.fract() == 0.0 { "yyyy-mm-dd" } else { "yyyy-mm-dd hh:mm:ss" };
+                        let format = cell_format(sheet, row_idx, col_idx, None, Some(fallback)).unwrap_or_default();
+                        worksheet.write_number_with_format(r, c, *serial, &format)?;
+                    }
+                    (CellValue::Text(s) | CellValue::Error(s), Some(format)) => {
+                        worksheet.write_string_with_format(r, c, s, &format)?;
+                    }
+                    (CellValue::Text(s) | CellValue::Error(s), None) => {
+                        worksheet.write_string(r, c, s)?;
+                    }
+                }
+            }
+        }
+        write_merges(worksheet, sheet, &column_formats)?;
+        for ((row_idx, col_idx), target) in &sheet.hyperlinks {
+            // Una celda tapada por otra combinada no puede llevar enlace
+            if sheet.merges.iter().any(|m| m.contains(*row_idx, *col_idx) && (m.first_row, m.first_col) != (*row_idx, *col_idx)) {
+                continue;
+            }
+            let text = sheet
+                .rows
+                .get(*row_idx)
+                .and_then(|row| row.get(*col_idx))
+                .map(|cell| cell.to_string())
+                .filter(|text| !text.is_empty())
+                .unwrap_or_else(|| target.clone());
+            worksheet
+                .write_url_with_text(*row_idx as u32, *col_idx as u16, Url::new(hyperlinks::writer_url(target)), text)
+                .context(format!("Enlace no válido en {}: {}", sheet.name, target))?;
+        }
+        for ((row_idx, col_idx), formula) in &sheet.formulas {
+            let (r, c) = (*row_idx as u32, *col_idx as u16);
+            let column_format = sheet.column_formats.get(col_idx).map(String::as_str);
+            match cell_format(sheet, *row_idx, *col_idx, column_format, None) {
+                Some(format) => worksheet.write_formula_with_format(r, c, format!("={}", formula).as_str(), &format)?,
+                None => worksheet.write_formula(r, c, format!("={}", formula).as_str())?,
+            };
+            // El resultado en caché permite leer el valor sin recalcular en Excel
+            if let Some(cell) = sheet.rows.get(*row_idx).and_then(|row| row.get(*col_idx)) {
+                if *cell != CellValue::Empty {
+                    worksheet.set_formula_result(r, c, cell.to_string());
+                }
+            }
+        }
+        for spec in &sheet.tables {
+            let range = &spec.range;
+            worksheet
+                .add_table(range.first_row as u32, range.first_col as u16, range.last_row as u32, range.last_col as u16, &tables::writer_table(sheet, spec))
+                .context(format!("No se pudo escribir la tabla {} de {}", spec.name, sheet.name))?;
+            // add_table escribe el encabezado sin formato: se recupera el original
+            for col in range.first_col..=range.last_col {
+                if let (true, Some(format)) = (spec.header_row, cell_format(sheet, range.first_row, col, None, None)) {
+                    let header = sheet.cell(range.first_row, col).to_string();
+                    if !header.trim().is_empty() {
+                        worksheet.write_string_with_format(range.first_row as u32, col as u16, header.trim(), &format)?;
+                    }
+                }
+            }
+        }
+        apply_layout(worksheet, sheet)?;
+        if let Some(protection) = &sheet.protection {
+            match &protection.password {
+                Some(password) => worksheet.protect_with_password(password),
+                None => worksheet.protect(),
+            };
+        }
+        for spec in &sheet.charts {
+            let mut chart = Chart::new(spec.kind.chart_type());
+            chart.title().set_name(&spec.title);
+            let (first, last) = (spec.first_row as u32, spec.last_row as u32);
+            for col in &spec.value_cols {
+                let col = *col as u16;
+                chart
+                    .add_series()
+                    .set_name((sheet.name.as_str(), 0, col))
+                    .set_categories((sheet.name.as_str(), first, spec.category_col as u16, last, spec.category_col as u16))
+                    .set_values((sheet.name.as_str(), first, col, last, col));
+            }
+            worksheet.insert_chart(spec.anchor.0 as u32, spec.anchor.1 as u16, &chart)?;
+        }
+    }
+
+    // Un libro sin hojas no es válido para Excel
+    if data.sheets.is_empty() {
+        workbook.add_worksheet();
+    }
+
+    backup::before_write(path)?;
+    workbook
+        .save(path)
+        .context(format!("No se pudo guardar {}", path.display()))?;
+    protection::restore_passwords(path, &data.sheets)?;
+    notes::restore(path, &data.sheets)?;
+    outline::restore(path, &data.sheets)?;
+    verify::after_write(path)?;
+    Ok(())
+}
+
+// Formato de una celda al guardar: el estilo original con, como formato numérico,
+// el de columna puesto en la sesión, si no el original y si no `fallback`
+fn cell_format(sheet: &SheetData, row: usize, col: usize, column: Option<&str>, fallback: Option<&str>) -> Option<Format> {
+    match sheet.styles.get(&(row, col)) {
+        Some(style) => Some(style.to_format(column.or(fallback.filter(|_| style.num_format.is_none())))),
+        None => column.or(fallback).map(|code| Format::new().set_num_format(code)),
+    }
+}
+
+// Combina los rangos y escribe en cada uno el valor de su celda superior izquierda
+fn write_merges(worksheet: &mut Worksheet, sheet: &SheetData, column_formats: &BTreeMap<usize, Format>) -> Result<()> {
+    let centered = Format::new().set_align(FormatAlign::Center).set_align(FormatAlign::VerticalCenter);
+    for merge in &sheet.merges {
+        let (r, c) = (merge.first_row as u32, merge.first_col as u16);
+        let cell = sheet.rows.get(merge.first_row).and_then(|row| row.get(merge.first_col));
+        let text = match cell {
+            Some(CellValue::Text(s)) => s.as_str(),
+            _ => "",
+        };
+        worksheet
+            .merge_range(r, c, merge.last_row as u32, merge.last_col as u16, text, &centered)
+            .context(format!("No se pueden combinar las celdas {} de {}", merge, sheet.name))?;
+        match cell {
+            Some(CellValue::Number(n)) => match column_formats.get(&merge.first_col) {
+                Some(format) => worksheet.write_number_with_format(r, c, *n, format)?,
+                None => worksheet.write_number(r, c, *n)?,
+            },
+            Some(CellValue::DateTime(serial)) => {
+                let format = if serial.fract() == 0.0 { "yyyy-mm-dd" } else { "yyyy-mm-dd hh:mm:ss" };
+                worksheet.write_number_with_format(r, c, *serial, &Format::new().set_num_format(format))?
+            }
+            Some(CellValue::Bool(b)) => worksheet.write_boolean(r, c, *b)?,
+            _ => worksheet,
+        };
+    }
+    Ok(())
+}
+
+fn apply_layout(worksheet: &mut Worksheet, sheet: &SheetData) -> Result<()> {
+    let layout = &sheet.layout;
+    let mut widths = if layout.autofit { layout::autofit_widths(sheet) } else { BTreeMap::new() };
+    widths.extend(layout.widths.iter().map(|(col, width)| (*col, *width)));
+    for (col, width) in widths {
+        worksheet.set_column_width(col as u16, width)?;
+    }
+    for col in &layout.hidden {
+        worksheet.set_column_hidden(*col as u16)?;
+    }
+    if layout.freeze_rows > 0 || layout.freeze_cols > 0 {
+        worksheet.set_freeze_panes(layout.freeze_rows as u32, layout.freeze_cols as u16)?;
+    }
+    Ok(())
+}
+
+// Nombre de la columna `index` con el encabezado `text`: su letra si está vacío,
+// para no mostrar nunca un encabezado en blanco
+pub fn header_name(index: usize, text: &str) -> String {
+    if text.trim().is_empty() {
+        column_letters(index)
+    } else {
+        text.to_string()
+    }
+}
+
+// Convierte un índice de columna desde 0 en letras (0 -> A, 27 -> AB)
+pub fn column_letters(mut index: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push((b'A' + (index % 26) as u8) as char);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    letters.iter().rev().collect()
+}
+
+// Referencia de celda A1 -> (fila, columna) desde 0; admite $ de referencias absolutas
+pub fn parse_cell_ref(reference: &str) -> Option<(usize, usize)> {
+    let reference = reference.trim().replace('$', "");
+    let split = reference.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = reference.split_at(split);
+    let col = column_from_letters(letters)?;
+    let row: usize = digits.parse().ok()?;
+    row.checked_sub(1).map(|row| (row, col))
+}
+
+// Rango rectangular de celdas (índices desde 0, inclusivos)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellRange {
+    pub first_row: usize,
+    pub first_col: usize,
+    pub last_row: usize,
+    pub last_col: usize,
+}
+
+impl CellRange {
+    // Acepta "B2:D10" o una sola celda "B2"
+    pub fn parse(range: &str) -> Option<CellRange> {
+        let (start, end) = range.split_once(':').unwrap_or((range, range));
+        let (r1, c1) = parse_cell_ref(start)?;
+        let (r2, c2) = parse_cell_ref(end)?;
+        Some(CellRange {
+            first_row: r1.min(r2),
+            first_col: c1.min(c2),
+            last_row: r1.max(r2),
+            last_col: c1.max(c2),
+        })
+    }
+}
+
+impl CellRange {
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        (self.first_row..=self.last_row).contains(&row) && (self.first_col..=self.last_col).contains(&col)
+    }
+}
+
+impl fmt::Display for CellRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let start = format!("{}{}", column_letters(self.first_col), self.first_row + 1);
+        let end = format!("{}{}", column_letters(self.last_col), self.last_row + 1);
+        if start == end {
+            write!(f, "{}", start)
+        } else {
+            write!(f, "{}:{}", start, end)
+        }
+    }
+}
+
+// Añade (o reemplaza) una hoja en un archivo, creándolo si no existe
+pub fn write_sheet_to_file(filename: &str, sheet: SheetData) -> Result<()> {
+    let path = Path::new(filename);
+    let mut data = if path.exists() {
+        read_excel_file(filename)?
+    } else {
+        WorkbookData::default()
+    };
+    data.upsert_sheet(sheet);
+    save_workbook(path, &data)
+}
+
+// Evita tratar como número valores como "00123" o "+34 600..."
+fn looks_numeric(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return false;
+    }
+    !(digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0."))
+}
+
+// Convierte un número de serie de Excel a "AAAA-MM-DD" o "AAAA-MM-DD HH:MM:SS"
+// Fecha y hora actual (UTC) como número de serie de Excel
+pub fn now_serial() -> f64 {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    (seconds / 60 * 60) as f64 / 86_400.0 + EXCEL_EPOCH_OFFSET as f64
+}
+
+pub fn excel_serial_to_iso(serial: f64) -> String {
+    let mut days = serial.floor() as i64;
+    let mut seconds = ((serial - serial.floor()) * 86_400.0).round() as i64;
+    if seconds == 86_400 {
+        days += 1;
+        seconds = 0;
+    }
+    let (year, month, day) = serial_day_to_civil(days);
+    if seconds == 0 {
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    } else {
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            (seconds % 3600) / 60,
+            seconds % 60
+        )
+    }
+}
+
+// Fecha (año, mes, día) de un número de serie de Excel, ignorando la hora
+pub fn serial_to_date(serial: f64) -> (i64, u32, u32) {
+    serial_day_to_civil(serial.floor() as i64)
+}
+
+fn serial_day_to_civil(mut days: i64) -> (i64, u32, u32) {
+    // Excel considera 1900 bisiesto: el día 60 no existe
+    if days < 61 {
+        days += 1;
+    }
+    civil_from_days(days - EXCEL_EPOCH_OFFSET)
+}
+
+// Fecha escrita como texto: ISO o dd/mm/aaaa
+pub fn parse_date_text(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if let Some(serial) = parse_iso_datetime(value) {
+        return Some(serial);
+    }
+    let mut parts = value.split(['/', '-', '.']);
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let year_text = parts.next()?;
+    if parts.next().is_some() || year_text.len() != 4 {
+        return None;
+    }
+    parse_iso_datetime(&format!("{}-{:02}-{:02}", year_text, month, day))
+}
+
+// Interpreta fechas ISO ("2024-01-31", "2024-01-31 10:00:00", "2024-01-31T10:00")
+pub fn parse_iso_datetime(value: &str) -> Option<f64> {
+    let (date_part, time_part) = match value.find(['T', ' ']) {
+        Some(idx) => (&value[..idx], Some(&value[idx + 1..])),
+        None => (value, None),
+    };
+    let mut date = date_part.split('-');
+    let year: i64 = parse_fixed(date.next()?, 4)?;
+    let month: u32 = parse_fixed(date.next()?, 2)?;
+    let day: u32 = parse_fixed(date.next()?, 2)?;
+    if date.next().is_some() || !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let mut seconds = 0i64;
+    if let Some(time) = time_part {
+        let time = time.trim_end_matches('Z');
+        let mut pieces = time.split(':');
+        let hours: i64 = parse_fixed(pieces.next()?, 2)?;
+        let minutes: i64 = parse_fixed(pieces.next()?, 2)?;
+        let secs: f64 = pieces.next().map(|s| s.parse().ok()).unwrap_or(Some(0.0))?;
+        if pieces.next().is_some() || hours > 23 || minutes > 59 || !(0.0..60.0).contains(&secs) {
+            return None;
+        }
+        seconds = hours * 3600 + minutes * 60 + secs.round() as i64;
+    }
+
+    let mut days = days_from_civil(year, month, day) + EXCEL_EPOCH_OFFSET;
+    if days < 61 {
+        days -= 1;
+    }
+    Some(days as f64 + seconds as f64 / 86_400.0)
+}
+
+fn parse_fixed<T: std::str::FromStr>(value: &str, len: usize) -> Option<T> {
+    if value.len() != len || !value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Algoritmos de calendario civil de Howard Hinnant (días desde 1970-01-01)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Los xlsx cifrados con contraseña no son zip sino contenedores OLE (como los .xls)
+// con un flujo EncryptedPackage; se detectan para dar un error claro
+pub fn ensure_not_encrypted(path: &Path) -> Result<()> {
+    const OLE_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+    let Ok(mut file) = File::open(path) else {
+        return Ok(());
+    };
+    let mut header = [0u8; 8];
+    if file.read_exact(&mut header).is_err() || header != OLE_SIGNATURE {
+        return Ok(());
+    }
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    let marker: Vec<u8> = "EncryptedPackage".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    if content.windows(marker.len()).any(|window| window == marker) {
+        bail!(
+            "{} está protegido con contraseña. Ábrelo en Excel y guárdalo sin contraseña (Archivo > Información > Proteger libro) para poder leerlo",
+            path.display()
+        );
+    }
+    bail!(
+        "{} es un libro en el formato binario antiguo (.xls); guárdalo como .xlsx para poder leerlo",
+        path.display()
+    )
+}
+
+// Nombres de las hojas en orden, sin cargar sus datos
+pub fn sheet_names(filename: &str) -> Result<Vec<String>> {
+    let mut archive = open_archive(filename)?;
+    let workbook = read_zip_text(&mut archive, "xl/workbook.xml")?.context("Falta xl/workbook.xml")?;
+    Ok(find_tags(&workbook, "sheet")
+        .iter()
+        .filter_map(|tag| xml_attr(tag, "name"))
+        .map(|name| xml_unescape(&name))
+        .collect())
+}
+
+// Filas de una hoja leídas por streaming. La hoja se descomprime y decodifica en
+// un hilo aparte y las filas llegan por un canal acotado, de modo que nunca está
+// entera en memoria. Igual que `read_excel_file`, las filas empiezan en A1.
+pub struct SheetRows {
+    receiver: Receiver<Result<Vec<CellValue>>>,
+}
+
+impl Iterator for SheetRows {
+    type Item = Result<Vec<CellValue>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+pub fn stream_sheet(filename: &str, sheet_name: &str) -> Result<SheetRows> {
+    let mut archive = open_archive(filename)?;
+    let part = sheet_part_path(&mut archive, sheet_name)?;
+    let shared_strings = read_shared_strings(&mut archive)?;
+    let date_styles = read_date_styles(&mut archive)?;
+    let (sender, receiver) = mpsc::sync_channel(STREAM_BUFFER_ROWS);
+    thread::spawn(settings::inherit_blocking(move || {
+        let context = StreamContext {
+            shared_strings,
+            date_styles,
+        };
+        if let Err(e) = stream_rows(&mut archive, &part, &context, &sender) {
+            let _ = sender.send(Err(e));
+        }
+    }));
+    Ok(SheetRows { receiver })
+}
+
+struct StreamContext {
+    shared_strings: Vec<String>,
+    // Índices de estilo (cellXfs) con formato de fecha
+    date_styles: HashSet<usize>,
+}
+
+fn open_archive(filename: &str) -> Result<ZipArchive<File>> {
+    let path = Path::new(filename);
+    ensure_not_encrypted(path)?;
+    let file = File::open(path).context(format!("No se pudo abrir el archivo {}", filename))?;
+    ZipArchive::new(file).context(format!("{} no es un archivo xlsx válido", filename))
+}
+
+fn read_zip_text(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<String>> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    Ok(Some(content))
+}
+
+fn sheet_part_path(archive: &mut ZipArchive<File>, sheet_name: &str) -> Result<String> {
+    let workbook = read_zip_text(archive, "xl/workbook.xml")?.context("Falta xl/workbook.xml")?;
+    let rels = read_zip_text(archive, "xl/_rels/workbook.xml.rels")?
+        .context("Falta xl/_rels/workbook.xml.rels")?;
+    xlsx_patch::sheet_part_in(&workbook, &rels, sheet_name)
+}
+
+fn read_shared_strings(archive: &mut ZipArchive<File>) -> Result<Vec<String>> {
+    let Some(xml) = read_zip_text(archive, "xl/sharedStrings.xml")? else {
+        return Ok(Vec::new());
+    };
+    let mut strings = Vec::new();
+    let mut rest = xml.as_str();
+    while let Some(start) = find_element_start(rest, "si") {
+        let Some(end) = rest[start..].find("</si>") else { break };
+        strings.push(inline_text(&rest[start..start + end]));
+        rest = &rest[start + end + "</si>".len()..];
+    }
+    Ok(strings)
+}
+
+// Texto de un <si> o <is>: concatena los <t> de todos los fragmentos, sin la fonética
+fn inline_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut rest = xml;
+    loop {
+        let next_t = find_element_start(rest, "t");
+        let next_phonetic = find_element_start(rest, "rPh");
+        match (next_t, next_phonetic) {
+            (Some(_), Some(ph)) if next_t > Some(ph) => {
+                let Some(end) = rest[ph..].find("</rPh>") else { break };
+                rest = &rest[ph + end + "</rPh>".len()..];
+            }
+            (Some(t), _) => {
+                let Some(open_end) = rest[t..].find('>') else { break };
+                let content_start = t + open_end + 1;
+                if rest[..content_start].ends_with("/>") {
+                    rest = &rest[content_start..];
+                    continue;
+                }
+                let Some(close) = rest[content_start..].find("</t>") else { break };
+                text.push_str(&xml_unescape(&rest[content_start..content_start + close]));
+                rest = &rest[content_start + close + "</t>".len()..];
+            }
+            (None, _) => break,
+        }
+    }
+    text
+}
+
+fn read_date_styles(archive: &mut ZipArchive<File>) -> Result<HashSet<usize>> {
+    let mut dates = HashSet::new();
+    let Some(xml) = read_zip_text(archive, "xl/styles.xml")? else {
+        return Ok(dates);
+    };
+    let custom_dates: HashSet<u32> = find_tags(&xml, "numFmt")
+        .iter()
+        .filter(|tag| xml_attr(tag, "formatCode").is_some_and(|code| is_date_format(&xml_unescape(&code))))
+        .filter_map(|tag| xml_attr(tag, "numFmtId")?.parse().ok())
+        .collect();
+    let Some(start) = find_element_start(&xml, "cellXfs") else {
+        return Ok(dates);
+    };
+    let end = xml[start..].find("</cellXfs>").map_or(xml.len(), |e| start + e);
+    for (index, tag) in find_tags(&xml[start..end], "xf").iter().enumerate() {
+        let id: u32 = xml_attr(tag, "numFmtId").and_then(|id| id.parse().ok()).unwrap_or(0);
+        if matches!(id, 14..=22 | 27..=36 | 45..=47 | 50..=58) || custom_dates.contains(&id) {
+            dates.insert(index);
+        }
+    }
+    Ok(dates)
+}
+
+// Un formato es de fecha si usa d, m, y, h o s fuera de literales y corchetes
+fn is_date_format(code: &str) -> bool {
+    let mut in_quotes = false;
+    let mut in_brackets = false;
+    let mut escaped = false;
+    for c in code.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '[' if !in_quotes => in_brackets = true,
+            ']' if !in_quotes => in_brackets = false,
+            'd' | 'm' | 'y' | 'h' | 's' | 'D' | 'M' | 'Y' | 'H' | 'S' if !in_quotes && !in_brackets => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+// Descomprime la hoja por bloques y envía cada fila en cuanto está completa
+fn stream_rows(
+    archive: &mut ZipArchive<File>,
+    part: &str,
+    context: &StreamContext,
+    sender: &SyncSender<Result<Vec<CellValue>>>,
+) -> Result<()> {
+    let mut entry = archive.by_name(part).context(format!("Falta la parte {}", part))?;
+    let mut chunk = vec![0u8; STREAM_CHUNK_BYTES];
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buffer = String::new();
+    let mut next_row = 0;
+    loop {
+        let read = entry.read(&mut chunk)?;
+        pending.extend_from_slice(&chunk[..read]);
+        // Un carácter UTF-8 puede quedar partido entre dos bloques
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        buffer.push_str(std::str::from_utf8(&pending[..valid]).unwrap_or_default());
+        pending.drain(..valid);
+
+        let mut consumed = 0;
+        while let Some((row_xml, end)) = next_row_element(&buffer[consumed..]) {
+            let (number, cells) = parse_row(row_xml, context);
+            let number = number.unwrap_or(next_row);
+            // Filas vacías intermedias para que el índice coincida con la fila de Excel
+            while next_row < number {
+                if sender.send(Ok(Vec::new())).is_err() {
+                    return Ok(());
+                }
+                next_row += 1;
+            }
+            if sender.send(Ok(cells)).is_err() {
+                return Ok(());
+            }
+            next_row = number + 1;
+            consumed += end;
+        }
+        buffer.drain(..consumed);
+        if read == 0 {
+            return Ok(());
+        }
+    }
+}
+
+// Devuelve el XML de la siguiente fila completa y la posición tras ella
+fn next_row_element(xml: &str) -> Option<(&str, usize)> {
+    let start = find_element_start(xml, "row")?;
+    let open_end = start + xml[start..].find('>')? + 1;
+    if xml[..open_end].ends_with("/>") {
+        return Some((&xml[start..open_end], open_end));
+    }
+    let close = open_end + xml[open_end..].find("</row>")? + "</row>".len();
+    Some((&xml[start..close], close))
+}
+
+// Número de fila (desde 0) y celdas, con huecos para las columnas sin valor
+fn parse_row(row_xml: &str, context: &StreamContext) -> (Option<usize>, Vec<CellValue>) {
+    let open_end = row_xml.find('>').unwrap_or(row_xml.len());
+    let number = xml_attr(&row_xml[..open_end], "r")
+        .and_then(|r| r.parse::<usize>().ok())
+        .map(|r| r.saturating_sub(1));
+    let mut cells = Vec::new();
+    let mut rest = &row_xml[open_end.min(row_xml.len())..];
+    while let Some(start) = find_element_start(rest, "c") {
+        let Some(tag_len) = rest[start..].find('>') else { break };
+        let tag = &rest[start..start + tag_len + 1];
+        let (content, next) = if tag.ends_with("/>") {
+            ("", start + tag.len())
+        } else {
+            let content_start = start + tag.len();
+            let close = rest[content_start..].find("</c>").map_or(rest.len(), |c| content_start + c);
+            (&rest[content_start..close], (close + "</c>".len()).min(rest.len()))
+        };
+        let column = xml_attr(tag, "r")
+            .and_then(|r| parse_cell_ref(&r))
+            .map_or(cells.len(), |(_, col)| col);
+        if column >= cells.len() {
+            cells.resize(column, CellValue::Empty);
+            cells.push(parse_cell(tag, content, context));
+        }
+        rest = &rest[next..];
+    }
+    (number, cells)
+}
+
+fn parse_cell(tag: &str, content: &str, context: &StreamContext) -> CellValue {
+    let kind = xml_attr(tag, "t").unwrap_or_default();
+    if kind == "inlineStr" {
+        return CellValue::Text(inline_text(content));
+    }
+    let value = find_element_start(content, "v")
+        .and_then(|start| {
+            let open_end = start + content[start..].find('>')? + 1;
+            let close = open_end + content[open_end..].find("</v>")?;
+            Some(xml_unescape(&content[open_end..close]))
+        })
+        .unwrap_or_default();
+    if value.is_empty() {
+        return CellValue::Empty;
+    }
+    match kind.as_str() {
+        "s" => value
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| context.shared_strings.get(index))
+            .map_or(CellValue::Empty, |text| CellValue::Text(text.clone())),
+        "str" => CellValue::Text(value),
+        "b" => CellValue::Bool(value == "1"),
+        "e" => CellValue::Error(value),
+        "d" => parse_iso_datetime(&value).map_or(CellValue::Text(value), CellValue::DateTime),
+        _ => match value.parse::<f64>() {
+            Ok(number) => {
+                let style = xml_attr(tag, "s").and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
+                if context.date_styles.contains(&style) {
+                    CellValue::DateTime(number)
+                } else {
+                    CellValue::Number(number)
+                }
+            }
+            Err(_) => CellValue::Text(value),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writing_one_sheet_keeps_the_formulas_of_the_others() {
+        let file = crate::paths::test_dir("writing_one_sheet_keeps_the_formulas_of_the_others").join("f.xlsx");
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("A").unwrap();
+        sheet.write_number(0, 0, 2.0).unwrap();
+        sheet.write_formula(0, 1, "=A1*3").unwrap();
+        let other = workbook.add_worksheet();
+        other.set_name("B").unwrap();
+        other.write_formula(0, 0, "=A!A1+1").unwrap();
+        workbook.save(&file).unwrap();
+        let filename = file.display().to_string();
+
+        assert_eq!(write_excel_data(&filename, "hoja=C z;9", false).unwrap(), ["C"]);
+        let mut written = read_excel_file(&filename).unwrap();
+        formulas::read(&filename, &mut written).unwrap();
+        assert_eq!(written.sheet("A").unwrap().formulas.get(&(0, 1)).map(String::as_str), Some("A1*3"));
+        assert_eq!(written.sheet("C").unwrap().rows[1][0], CellValue::Text("9".to_string()));
+
+        // Reemplazar la hoja B perdería su fórmula
+        let error = write_excel_data(&filename, "hoja=B z;9", false).unwrap_err();
+        assert!(error.to_string().contains("1 fórmula(s)"), "{:#}", error);
+        write_excel_data(&filename, "hoja=B z;9", true).unwrap();
+        let mut written = read_excel_file(&filename).unwrap();
+        formulas::read(&filename, &mut written).unwrap();
+        assert!(written.sheet("B").unwrap().formulas.is_empty());
+        assert_eq!(written.sheet("A").unwrap().formulas.len(), 1);
+    }
+}
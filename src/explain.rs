@@ -0,0 +1,276 @@
+// Informe de estructura de un libro (`explicar <archivo>`): hojas y si están
+// ocultas, rango usado, encabezados con el tipo de cada columna, fórmulas,
+// tablas, nombres definidos y vínculos a otros libros. Es la ficha con la que
+// empezar a trabajar con un libro desconocido; el resumen de leer_excel se
+// centra en los valores, esto en cómo está montado. El mismo texto se muestra en
+// la terminal y se añade al contexto del modelo. No incluye valores de celdas,
+// solo nombres, tipos y recuentos.
+use crate::convert::{self, FileFormat};
+use crate::excel::{CellRange, CellValue, SheetData, WorkbookData};
+use crate::formulas;
+use crate::metadata::WorkbookMetadata;
+use crate::named_ranges::DefinedName;
+use crate::schemas;
+use crate::xlsx_patch;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+// Columnas que se describen por hoja
+const MAX_COLUMNS: usize = 40;
+
+const EXTERNAL_LINK_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/externalLink";
+
+pub struct WorkbookReport {
+    pub file: String,
+    pub bytes: u64,
+    // (descripción, valor) de las propiedades del documento
+    pub properties: Vec<(String, String)>,
+    pub sheets: Vec<SheetReport>,
+    pub names: Vec<DefinedName>,
+    pub external_links: Vec<ExternalLink>,
+}
+
+pub struct SheetReport {
+    pub name: String,
+    pub hidden: bool,
+    // Rango usado; `None` si la hoja está vacía
+    pub range: Option<CellRange>,
+    pub headerless: bool,
+    pub data_rows: usize,
+    // (encabezado, tipo, celdas vacías en las filas de datos)
+    pub columns: Vec<(String, &'static str, usize)>,
+    pub formula_count: usize,
+    // Fórmulas agrupadas como en leer_excel --formulas
+    pub formulas: String,
+    pub tables: Vec<String>,
+    pub merges: usize,
+    pub notes: usize,
+    pub hyperlinks: usize,
+}
+
+// Libro enlazado: sus referencias aparecen en las fórmulas como [1]Hoja!A1
+pub struct ExternalLink {
+    pub index: usize,
+    pub target: String,
+    pub formulas: usize,
+}
+
+pub fn explain(file: &str) -> Result<WorkbookReport> {
+    let path = Path::new(file);
+    let bytes = fs::metadata(path).context(format!("No se pudo abrir {}", file))?.len();
+    let xlsx = FileFormat::from_path(path) == Some(FileFormat::Xlsx);
+    let mut data = convert::read_any(path)?;
+    let mut report = WorkbookReport {
+        file: file.to_string(),
+        bytes,
+        properties: Vec::new(),
+        sheets: Vec::new(),
+        names: Vec::new(),
+        external_links: Vec::new(),
+    };
+    // Un csv o un json no tiene fórmulas, nombres ni propiedades
+    let mut hidden = Vec::new();
+    if xlsx {
+        formulas::read(file, &mut data)?;
+        let metadata = WorkbookMetadata::read(file, Some(&data))?;
+        report.properties = metadata.properties;
+        report.names = metadata.names;
+        let workbook = xlsx_patch::read_part_from(path, "xl/workbook.xml", None)?.unwrap_or_default();
+        hidden = hidden_sheets(&workbook);
+        report.external_links = external_links(path, &workbook, &data)?;
+    }
+    for sheet in &data.sheets {
+        report.sheets.push(sheet_report(sheet, hidden.contains(&sheet.name)));
+    }
+    Ok(report)
+}
+
+fn sheet_report(sheet: &SheetData, hidden: bool) -> SheetReport {
+    let rows = sheet.data_rows();
+    let columns = sheet
+        .headers()
+        .into_iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            let empty = rows.iter().filter(|row| matches!(row.get(idx), None | Some(CellValue::Empty))).count();
+            (name, schemas::column_kind(rows, idx), empty)
+        })
+        .collect();
+    SheetReport {
+        name: sheet.name.clone(),
+        hidden,
+        range: used_range(sheet),
+        headerless: sheet.headerless,
+        data_rows: rows.len(),
+        columns,
+        formula_count: sheet.formulas.len(),
+        formulas: formulas::describe(&sheet.formulas),
+        tables: sheet.tables.iter().map(|table| table.name.clone()).collect(),
+        merges: sheet.merges.len(),
+        notes: sheet.notes.len(),
+        hyperlinks: sheet.hyperlinks.len(),
+    }
+}
+
+// Rango de las celdas leídas; el <dimension> que declara la hoja puede estar
+// desfasado si otro programa la escribió sin actualizarlo
+fn used_range(sheet: &SheetData) -> Option<CellRange> {
+    let cols = sheet.rows.iter().map(Vec::len).max().unwrap_or(0);
+    (cols > 0 && !sheet.rows.is_empty()).then(|| CellRange {
+        first_row: 0,
+        first_col: 0,
+        last_row: sheet.rows.len() - 1,
+        last_col: cols - 1,
+    })
+}
+
+// Hojas con state="hidden" o "veryHidden" en xl/workbook.xml
+fn hidden_sheets(workbook: &str) -> Vec<String> {
+    xlsx_patch::find_tags(workbook, "sheet")
+        .into_iter()
+        .filter(|tag| xlsx_patch::xml_attr(tag, "state").is_some_and(|state| state != "visible"))
+        .filter_map(|tag| xlsx_patch::xml_attr(&tag, "name"))
+        .map(|name| xlsx_patch::xml_unescape(&name))
+        .collect()
+}
+
+// Libros enlazados en el orden de <externalReferences>, que da su número en las
+// fórmulas, con la ruta de cada uno y cuántas fórmulas lo usan
+fn external_links(path: &Path, workbook: &str, data: &WorkbookData) -> Result<Vec<ExternalLink>> {
+    let references = xlsx_patch::find_tags(workbook, "externalReference");
+    if references.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rels = xlsx_patch::read_part_from(path, "xl/_rels/workbook.xml.rels", None)?.unwrap_or_default();
+    let relationships = xlsx_patch::find_tags(&rels, "Relationship");
+    let mut links = Vec::new();
+    for (idx, reference) in references.iter().enumerate() {
+        let index = idx + 1;
+        let part = xlsx_patch::xml_attr(reference, "r:id").and_then(|id| {
+            relationships
+                .iter()
+                .find(|rel| {
+                    xlsx_patch::xml_attr(rel, "Id").as_deref() == Some(id.as_str())
+                        && xlsx_patch::xml_attr(rel, "Type").as_deref() == Some(EXTERNAL_LINK_RELATIONSHIP)
+                })
+                .and_then(|rel| xlsx_patch::xml_attr(rel, "Target"))
+                .map(|target| xlsx_patch::resolve_part("xl", &xlsx_patch::xml_unescape(&target)))
+        });
+        // La ruta del libro enlazado está en las relaciones de xl/externalLinks/externalLinkN.xml
+        let target = part
+            .and_then(|part| {
+                let (dir, name) = part.rsplit_once('/')?;
+                xlsx_patch::read_part_from(path, &format!("{}/_rels/{}.rels", dir, name), None).ok().flatten()
+            })
+            .and_then(|rels| {
+                xlsx_patch::find_tags(&rels, "Relationship").into_iter().find_map(|tag| xlsx_patch::xml_attr(&tag, "Target"))
+            })
+            .map(|target| xlsx_patch::xml_unescape(&target))
+            .unwrap_or_else(|| "(ruta desconocida)".to_string());
+        let marker = format!("[{}]", index);
+        let formulas = data
+            .sheets
+            .iter()
+            .flat_map(|sheet| sheet.formulas.values())
+            .filter(|formula| formula.contains(&marker))
+            .count();
+        links.push(ExternalLink { index, target, formulas });
+    }
+    Ok(links)
+}
+
+impl WorkbookReport {
+    // Texto para la terminal y el contexto del modelo
+    pub fn render(&self) -> String {
+        let size = if self.bytes >= 1024 { format!("{:.1} KB", self.bytes as f64 / 1024.0) } else { format!("{} bytes", self.bytes) };
+        let sheets = match self.sheets.len() {
+            1 => "1 hoja".to_string(),
+            count => format!("{} hojas", count),
+        };
+        let mut lines = vec![format!("Estructura de {} ({}, {}):", self.file, size, sheets)];
+        if !self.properties.is_empty() {
+            let properties: Vec<String> = self.properties.iter().map(|(label, value)| format!("{}: {}", label, value)).collect();
+            lines.push(format!("Propiedades: {}", properties.join(", ")));
+        }
+        for sheet in &self.sheets {
+            lines.extend(sheet.render());
+        }
+        if self.names.is_empty() {
+            lines.push("Nombres definidos: ninguno".to_string());
+        } else {
+            let names: Vec<String> =
+                self.names.iter().map(|defined| format!("{} = {}", defined.display_name(), defined.reference)).collect();
+            lines.push(format!("Nombres definidos: {}", names.join("; ")));
+        }
+        if self.external_links.is_empty() {
+            lines.push("Vínculos a otros libros: ninguno".to_string());
+        } else {
+            let links: Vec<String> = self
+                .external_links
+                .iter()
+                .map(|link| match link.formulas {
+                    1 => format!("[{}] {} (en 1 fórmula)", link.index, link.target),
+                    count => format!("[{}] {} (en {} fórmulas)", link.index, link.target, count),
+                })
+                .collect();
+            lines.push(format!("Vínculos a otros libros (los valores se guardaron al abrirlo por última vez): {}", links.join("; ")));
+        }
+        lines.join("\n")
+    }
+}
+
+impl SheetReport {
+    // "Hoja Ventas: A1:E121 (121 filas x 5 columnas), encabezados en la fila 1, 120 filas de datos"
+    fn render(&self) -> Vec<String> {
+        let hidden = if self.hidden { " (oculta)" } else { "" };
+        let Some(range) = &self.range else {
+            return vec![format!("Hoja {}{}: vacía", self.name, hidden)];
+        };
+        let header = if self.headerless { "sin encabezados" } else { "encabezados en la fila 1" };
+        let mut lines = vec![format!(
+            "Hoja {}{}: {} ({} filas x {} columnas), {}, {} filas de datos",
+            self.name,
+            hidden,
+            range,
+            range.last_row - range.first_row + 1,
+            range.last_col - range.first_col + 1,
+            header,
+            self.data_rows
+        )];
+        let mut columns: Vec<String> = self
+            .columns
+            .iter()
+            .take(MAX_COLUMNS)
+            .map(|(name, kind, empty)| match empty {
+                0 => format!("{} ({})", name, kind),
+                _ if *kind == "vacía" => format!("{} (vacía)", name),
+                1 => format!("{} ({}, 1 vacía)", name, kind),
+                empty => format!("{} ({}, {} vacías)", name, kind, empty),
+            })
+            .collect();
+        if self.columns.len() > MAX_COLUMNS {
+            columns.push(format!("y {} más", self.columns.len() - MAX_COLUMNS));
+        }
+        lines.push(format!("  Columnas: {}", columns.join(", ")));
+        if self.formula_count > 0 {
+            lines.push(format!("  Fórmulas ({}): {}", self.formula_count, self.formulas));
+        } else {
+            lines.push("  Fórmulas: ninguna (solo valores)".to_string());
+        }
+        let mut extras = Vec::new();
+        if !self.tables.is_empty() {
+            extras.push(format!("tablas {}", self.tables.join(", ")));
+        }
+        for (count, label) in [(self.merges, "rangos combinados"), (self.notes, "notas"), (self.hyperlinks, "hipervínculos")] {
+            if count > 0 {
+                extras.push(format!("{} {}", count, label));
+            }
+        }
+        if !extras.is_empty() {
+            lines.push(format!("  Además: {}", extras.join(", ")));
+        }
+        lines
+    }
+}
@@ -0,0 +1,478 @@
+// Exportación de libros a PDF o PNG (`exportar_pdf`) para adjuntarlos a un correo
+// o a unas diapositivas. Si LibreOffice está instalado (soffice, o la ruta de
+// IAGENT_SOFFICE) convierte el libro tal como se imprimiría, con formatos y
+// gráficos; si no, un PDF propio dibuja las hojas como tablas, sin gráficos.
+use crate::backup;
+use crate::crypto;
+use crate::excel::{self, CellValue, SheetData};
+use crate::outputs;
+use crate::profiles;
+use crate::verify;
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// A4 apaisado, en puntos
+const PAGE_WIDTH: f64 = 842.0;
+const PAGE_HEIGHT: f64 = 595.0;
+const MARGIN: f64 = 36.0;
+const FONT_SIZE: f64 = 8.0;
+const TITLE_SIZE: f64 = 11.0;
+const ROW_HEIGHT: f64 = 12.0;
+// Ancho medio de un carácter de Helvetica respecto al tamaño de letra
+const CHAR_WIDTH: f64 = 0.52;
+const MAX_CELL_CHARS: usize = 40;
+const CELL_PADDING: f64 = 6.0;
+// Caracteres sin representación que se citan en la nota del PDF propio
+const MAX_REPLACED_SHOWN: usize = 10;
+
+// Número de la exportación con LibreOffice dentro del proceso, para su directorio de trabajo
+static NEXT_EXPORT: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Pdf,
+    Png,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Png => "png",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Converter {
+    // LibreOffice si está disponible; si no, el PDF propio
+    Auto,
+    LibreOffice,
+    Internal,
+}
+
+impl Converter {
+    pub fn parse(value: &str) -> Option<Converter> {
+        match value {
+            "auto" => Some(Converter::Auto),
+            "libreoffice" | "soffice" => Some(Converter::LibreOffice),
+            "interno" => Some(Converter::Internal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub file: String,
+    // Sin hoja se exporta el libro completo
+    pub sheet: Option<String>,
+    // Destino .pdf o .png; por defecto, junto al libro con extensión .pdf
+    pub output: Option<String>,
+    pub converter: Converter,
+}
+
+pub struct ExportOutcome {
+    pub output: String,
+    pub converter: &'static str,
+    pub pages: Option<usize>,
+    pub notes: Vec<String>,
+}
+
+pub fn export(options: &ExportOptions) -> Result<ExportOutcome> {
+    let source = Path::new(&options.file);
+    if !source.is_file() {
+        bail!("No existe el archivo {}", options.file);
+    }
+    let output = PathBuf::from(match &options.output {
+        Some(output) => outputs::target(output)?,
+        None => {
+            let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("libro");
+            outputs::derived(source, &format!("{}.pdf", stem))?
+        }
+    });
+    let format = match output.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+        Some("pdf") => ExportFormat::Pdf,
+        Some("png") => ExportFormat::Png,
+        _ => bail!("El destino debe ser un archivo .pdf o .png: {}", output.display()),
+    };
+    let soffice = match options.converter {
+        Converter::Internal => None,
+        Converter::Auto => find_soffice(),
+        Converter::LibreOffice => Some(find_soffice().context(
+            "No se encontró LibreOffice (soffice); instálalo o indica su ruta en IAGENT_SOFFICE",
+        )?),
+    };
+    match soffice {
+        Some(soffice) => export_with_soffice(&soffice, source, options.sheet.as_deref(), &output, format),
+        None if format == ExportFormat::Png => {
+            bail!("Exportar a PNG requiere LibreOffice (soffice); instálalo, indica su ruta en IAGENT_SOFFICE o exporta a PDF")
+        }
+        None => export_internal(source, options.sheet.as_deref(), &output),
+    }
+}
+
+fn find_soffice() -> Option<String> {
+    let candidates = match profiles::var("IAGENT_SOFFICE") {
+        Ok(path) => vec![path],
+        Err(_) => vec!["soffice".to_string(), "libreoffice".to_string()],
+    };
+    candidates.into_iter().find(|program| {
+        Command::new(program)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    })
+}
+
+fn export_with_soffice(
+    soffice: &str,
+    source: &Path,
+    sheet: Option<&str>,
+    output: &Path,
+    format: ExportFormat,
+) -> Result<ExportOutcome> {
+    // LibreOffice escribe en un directorio con el nombre del archivo de entrada; cada
+    // exportación tiene el suyo para que dos a la vez (el servidor) no se pisen
+    let work_dir = env::temp_dir().join(format!(
+        "iagent_export_{}_{}",
+        std::process::id(),
+        NEXT_EXPORT.fetch_add(1, Ordering::Relaxed)
+    ));
+    // Uno que dejó un proceso anterior con el mismo pid no se mezcla con este
+    let _ = fs::remove_dir_all(&work_dir);
+    fs::create_dir_all(&work_dir).context(format!("No se pudo crear {}", work_dir.display()))?;
+    let result = (|| {
+        let input = work_dir.join(source.file_name().context("Ruta de archivo no válida")?);
+        match sheet {
+            Some(sheet) => only_sheet(source, sheet, &input)?,
+            None => {
+                fs::copy(source, &input).context(format!("No se pudo copiar {}", source.display()))?;
+            }
+        }
+        let run = Command::new(soffice)
+            .args(["--headless", "--convert-to", format.extension(), "--outdir"])
+            .arg(&work_dir)
+            .arg(&input)
+            .stdout(Stdio::null())
+            .output()
+            .context(format!("No se pudo ejecutar {}", soffice))?;
+        let converted = input.with_extension(format.extension());
+        if !run.status.success() || !converted.is_file() {
+            bail!(
+                "LibreOffice no pudo convertir el libro: {}",
+                String::from_utf8_lossy(&run.stderr).trim()
+            );
+        }
+        backup::before_write(output)?;
+        fs::copy(&converted, output).context(format!("No se pudo escribir {}", output.display()))?;
+        verify::after_write(output)?;
+        Ok(())
+    })();
+    let _ = fs::remove_dir_all(&work_dir);
+    result?;
+    let mut notes = Vec::new();
+    if format == ExportFormat::Png {
+        notes.push("El PNG contiene la primera página de la hoja".to_string());
+    }
+    Ok(ExportOutcome {
+        output: output.display().to_string(),
+        converter: "LibreOffice",
+        pages: None,
+        notes,
+    })
+}
+
+// Copia del libro con las demás hojas ocultas, que LibreOffice no imprime
+fn only_sheet(source: &Path, sheet: &str, target: &Path) -> Result<()> {
+    let mut package = XlsxPackage::open(source)?;
+    let workbook = package.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+    let tags = xlsx_patch::find_tags(&workbook, "sheet");
+    let index = tags
+        .iter()
+        .position(|tag| xlsx_patch::xml_attr(tag, "name").is_some_and(|name| xlsx_patch::xml_unescape(&name) == sheet))
+        .context(format!("No existe la hoja '{}' en {}", sheet, source.display()))?;
+    let mut edited = workbook.clone();
+    for (idx, tag) in tags.iter().enumerate() {
+        let without_state = tag.replace(" state=\"hidden\"", "").replace(" state=\"veryHidden\"", "");
+        let replacement = if idx == index {
+            without_state
+        } else {
+            without_state.replacen("<sheet ", "<sheet state=\"hidden\" ", 1)
+        };
+        edited = edited.replacen(tag.as_str(), &replacement, 1);
+    }
+    // La hoja activa debe ser visible
+    if let Some(view) = xlsx_patch::find_tags(&edited, "workbookView").first() {
+        let active = match xlsx_patch::xml_attr(view, "activeTab") {
+            Some(value) => view.replace(&format!("activeTab=\"{}\"", value), &format!("activeTab=\"{}\"", index)),
+            None => view.replacen("<workbookView", &format!("<workbookView activeTab=\"{}\"", index), 1),
+        };
+        edited = edited.replacen(view.as_str(), &active, 1);
+    }
+    package.write_part("xl/workbook.xml", edited);
+    package.save(target)
+}
+
+fn export_internal(source: &Path, sheet: Option<&str>, output: &Path) -> Result<ExportOutcome> {
+    let file = source.display().to_string();
+    let data = excel::read_excel_file(&file)?;
+    // Las columnas que se cifran al escribir tampoco salen en claro en el PDF
+    let data = crypto::protect_outputs(&data)?.unwrap_or(data);
+    let sheets: Vec<&SheetData> = match sheet {
+        Some(name) => vec![data.require_sheet(&file, name)?],
+        None => data.sheets.iter().collect(),
+    };
+    let title = source.file_name().and_then(|n| n.to_str()).unwrap_or(&file);
+    let mut pages = Vec::new();
+    let mut replaced = unsupported_chars(title);
+    for sheet in sheets {
+        pages.extend(sheet_pages(title, sheet));
+        for c in unsupported_chars(&sheet.name)
+            .into_iter()
+            .chain(sheet.rows.iter().flatten().flat_map(|value| unsupported_chars(&cell_text(value))))
+        {
+            if !replaced.contains(&c) {
+                replaced.push(c);
+            }
+        }
+    }
+    let pdf = render_pdf(&pages);
+    backup::before_write(output)?;
+    fs::write(output, pdf).context(format!("No se pudo escribir {}", output.display()))?;
+    verify::after_write(output)?;
+    let mut notes = vec!["Sin LibreOffice el PDF muestra los datos como tablas, sin gráficos ni formatos".to_string()];
+    if !replaced.is_empty() {
+        let shown: String = replaced.iter().take(MAX_REPLACED_SHOWN).map(|c| format!(" {}", c)).collect();
+        notes.push(format!(
+            "{} carácter(es) que la fuente estándar del PDF no tiene se han cambiado por '?' ({}{}); con LibreOffice se conservan",
+            replaced.len(),
+            shown.trim_start(),
+            if replaced.len() > MAX_REPLACED_SHOWN { " …" } else { "" }
+        ));
+    }
+    Ok(ExportOutcome {
+        output: output.display().to_string(),
+        converter: "el conversor interno",
+        pages: Some(pages.len()),
+        notes,
+    })
+}
+
+fn cell_text(value: &CellValue) -> String {
+    let text = match value {
+        CellValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => format!("{}", *n as i64),
+        CellValue::Number(n) => {
+            let text = format!("{:.2}", n);
+            text.trim_end_matches('0').trim_end_matches('.').to_string()
+        }
+        other => other.to_string(),
+    };
+    let text = text.replace(['\n', '\r', '\t'], " ");
+    if text.chars().count() > MAX_CELL_CHARS {
+        let mut cut: String = text.chars().take(MAX_CELL_CHARS - 1).collect();
+        cut.push('…');
+        cut
+    } else {
+        text
+    }
+}
+
+fn text_width(text: &str, size: f64) -> f64 {
+    text.chars().count() as f64 * size * CHAR_WIDTH
+}
+
+// Contenido de cada página del PDF
+fn sheet_pages(title: &str, sheet: &SheetData) -> Vec<String> {
+    let rows: Vec<Vec<String>> = sheet.rows.iter().map(|row| row.iter().map(cell_text).collect()).collect();
+    let numeric: Vec<Vec<bool>> = sheet
+        .rows
+        .iter()
+        .map(|row| row.iter().map(|cell| matches!(cell, CellValue::Number(_))).collect())
+        .collect();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<f64> = (0..columns)
+        .map(|col| {
+            let longest = rows
+                .iter()
+                .filter_map(|row| row.get(col))
+                .map(|text| text_width(text, FONT_SIZE))
+                .fold(0.0, f64::max);
+            longest + CELL_PADDING
+        })
+        .collect();
+
+    // Las columnas que no caben pasan a otra página, como al imprimir en Excel
+    let available = PAGE_WIDTH - 2.0 * MARGIN;
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut used = f64::INFINITY;
+    for (col, width) in widths.iter().enumerate() {
+        if used + width > available {
+            groups.push(Vec::new());
+            used = 0.0;
+        }
+        if let Some(group) = groups.last_mut() {
+            group.push(col);
+        }
+        used += width;
+    }
+    if groups.is_empty() {
+        groups.push(Vec::new());
+    }
+
+    let top = PAGE_HEIGHT - MARGIN - TITLE_SIZE - ROW_HEIGHT;
+    let rows_per_page = (((top - MARGIN) / ROW_HEIGHT).floor() as usize).max(2);
+    let (header, body) = match rows.split_first() {
+        Some((header, body)) => (Some(header), body),
+        None => (None, &rows[..]),
+    };
+    // El encabezado se repite en cada página
+    let body_per_page = rows_per_page - usize::from(header.is_some());
+    let chunks: Vec<(usize, &[Vec<String>])> = if body.is_empty() {
+        vec![(1, body)]
+    } else {
+        body.chunks(body_per_page)
+            .enumerate()
+            .map(|(idx, chunk)| (1 + idx * body_per_page, chunk))
+            .collect()
+    };
+    let total = groups.len() * chunks.len();
+    let mut pages = Vec::new();
+    for group in &groups {
+        for (first_row, chunk) in &chunks {
+            let mut content = String::new();
+            let heading = format!("{} - {} ({}/{})", title, sheet.name, pages.len() + 1, total);
+            content.push_str(&text_op("F2", TITLE_SIZE, MARGIN, PAGE_HEIGHT - MARGIN - TITLE_SIZE, &heading));
+            let mut y = top;
+            if let Some(header) = header {
+                draw_row(&mut content, header, &[], group, &widths, y, "F2");
+                // Línea bajo el encabezado
+                let line_y = y - 3.0;
+                let right = MARGIN + group.iter().map(|col| widths[*col]).sum::<f64>();
+                content.push_str(&format!("0.5 w {:.1} {:.1} m {:.1} {:.1} l S\n", MARGIN, line_y, right, line_y));
+                y -= ROW_HEIGHT;
+            }
+            for (offset, row) in chunk.iter().enumerate() {
+                let kinds = numeric.get(first_row + offset).map(Vec::as_slice).unwrap_or(&[]);
+                draw_row(&mut content, row, kinds, group, &widths, y, "F1");
+                y -= ROW_HEIGHT;
+            }
+            pages.push(content);
+        }
+    }
+    pages
+}
+
+fn draw_row(content: &mut String, row: &[String], numeric: &[bool], group: &[usize], widths: &[f64], y: f64, font: &str) {
+    let mut x = MARGIN;
+    for col in group {
+        if let Some(text) = row.get(*col).filter(|text| !text.is_empty()) {
+            // Los números se alinean a la derecha
+            let left = if numeric.get(*col) == Some(&true) {
+                x + widths[*col] - CELL_PADDING / 2.0 - text_width(text, FONT_SIZE)
+            } else {
+                x + CELL_PADDING / 2.0
+            };
+            content.push_str(&text_op(font, FONT_SIZE, left, y, text));
+        }
+        x += widths[*col];
+    }
+}
+
+fn text_op(font: &str, size: f64, x: f64, y: f64, text: &str) -> String {
+    format!("BT /{} {} Tf {:.1} {:.1} Td ({}) Tj ET\n", font, size, x, y, pdf_string(text))
+}
+
+// Byte de un carácter en WinAnsiEncoding (la de las fuentes estándar)
+fn win_ansi(c: char) -> Option<u8> {
+    Some(match c {
+        '€' => 0x80,
+        '…' => 0x85,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '–' => 0x96,
+        '—' => 0x97,
+        c if (c as u32) < 0x80 || (0xA0..=0xFF).contains(&(c as u32)) => c as u32 as u8,
+        _ => return None,
+    })
+}
+
+// Caracteres distintos de `text` que pdf_string convierte en '?'
+fn unsupported_chars(text: &str) -> Vec<char> {
+    let mut chars: Vec<char> = text.chars().filter(|c| win_ansi(*c).is_none()).collect();
+    chars.sort_unstable();
+    chars.dedup();
+    chars
+}
+
+// Texto en WinAnsiEncoding; lo que no cabe se cambia por '?'
+fn pdf_string(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        let byte = win_ansi(c).unwrap_or(b'?');
+        match byte {
+            b'(' | b')' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            0x20..=0x7E => out.push(byte as char),
+            _ => out.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    out
+}
+
+// PDF 1.4 mínimo: catálogo, árbol de páginas, dos fuentes estándar y una página por contenido
+fn render_pdf(pages: &[String]) -> Vec<u8> {
+    let page_count = pages.len().max(1);
+    let first_page = 5;
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..page_count)
+                .map(|idx| format!("{} 0 R", first_page + idx * 2))
+                .collect::<Vec<_>>()
+                .join(" "),
+            page_count
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+    ];
+    let empty = String::new();
+    for idx in 0..page_count {
+        let content = pages.get(idx).unwrap_or(&empty);
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            first_page + idx * 2 + 1
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (idx, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", idx + 1, object).as_bytes());
+    }
+    let xref = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes(),
+    );
+    pdf
+}
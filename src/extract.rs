@@ -0,0 +1,133 @@
+// Tablas en las respuestas del modelo: tablas Markdown y bloques ```csv / ```tsv.
+// `aplicar <archivo> <hoja>` escribe la última respuesta en un libro sin tener
+// que copiarla a mano en escribir_excel.
+use crate::convert;
+use crate::dates;
+use crate::excel::{CellValue, SheetData};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedTable {
+    // "tabla Markdown", "bloque csv"...
+    pub source: String,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl ExtractedTable {
+    // Hoja con los tipos deducidos como al leer un CSV
+    pub fn to_sheet(&self, name: &str) -> SheetData {
+        let mut sheet = SheetData::new(name);
+        sheet.rows = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|cell| CellValue::infer(cell)).collect())
+            .collect();
+        dates::detect_date_columns(&mut sheet);
+        sheet
+    }
+
+    pub fn describe(&self) -> String {
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        format!("{} de {} filas x {} columnas", self.source, self.rows.len(), columns)
+    }
+}
+
+// Tablas de la respuesta en el orden en que aparecen; las de una sola fila no cuentan
+pub fn tables(text: &str) -> Vec<ExtractedTable> {
+    let mut tables = Vec::new();
+    let lines: Vec<&str> = text.lines().collect();
+    let mut idx = 0;
+    while idx < lines.len() {
+        let line = lines[idx].trim();
+        if let Some(info) = line.strip_prefix("```") {
+            let language = info.trim().to_lowercase();
+            let end = lines[idx + 1..]
+                .iter()
+                .position(|l| l.trim_start().starts_with("```"))
+                .map_or(lines.len(), |offset| idx + 1 + offset);
+            let body = lines[idx + 1..end].join("\n");
+            let rows = match language.as_str() {
+                "csv" => convert::parse_csv(&body),
+                "tsv" => convert::parse_delimited(&body, '\t'),
+                // Un bloque sin lenguaje puede contener una tabla Markdown
+                "" | "markdown" | "md" => markdown_rows(&lines[idx + 1..end]),
+                _ => Vec::new(),
+            };
+            let source = match language.as_str() {
+                "csv" | "tsv" => format!("bloque {}", language),
+                _ => "tabla Markdown".to_string(),
+            };
+            push_table(&mut tables, source, rows);
+            idx = end + 1;
+        } else if line.starts_with('|') {
+            let end = lines[idx..]
+                .iter()
+                .position(|l| !l.trim().starts_with('|'))
+                .map_or(lines.len(), |offset| idx + offset);
+            push_table(&mut tables, "tabla Markdown".to_string(), markdown_rows(&lines[idx..end]));
+            idx = end;
+        } else {
+            idx += 1;
+        }
+    }
+    tables
+}
+
+fn push_table(tables: &mut Vec<ExtractedTable>, source: String, rows: Vec<Vec<String>>) {
+    let rows: Vec<Vec<String>> = rows
+        .into_iter()
+        .filter(|row| row.iter().any(|cell| !cell.trim().is_empty()))
+        .collect();
+    if rows.len() >= 2 {
+        tables.push(ExtractedTable { source, rows });
+    }
+}
+
+// Filas de una tabla Markdown sin la línea separadora |---|:-:|
+fn markdown_rows(lines: &[&str]) -> Vec<Vec<String>> {
+    lines
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| line.starts_with('|'))
+        .map(split_markdown_row)
+        .filter(|cells| !is_separator(cells))
+        .collect()
+}
+
+fn split_markdown_row(line: &str) -> Vec<String> {
+    let inner = line.strip_prefix('|').unwrap_or(line);
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(clean_cell(&std::mem::take(&mut cell))),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(clean_cell(&cell));
+    cells
+}
+
+// Quita el énfasis (**Total**, `código`) que el modelo añade a las celdas
+fn clean_cell(cell: &str) -> String {
+    let cell = cell.trim();
+    for marker in ["**", "__", "`"] {
+        if let Some(inner) = cell.strip_prefix(marker).and_then(|c| c.strip_suffix(marker)) {
+            if !inner.is_empty() {
+                return inner.trim().to_string();
+            }
+        }
+    }
+    cell.to_string()
+}
+
+fn is_separator(cells: &[String]) -> bool {
+    cells
+        .iter()
+        .all(|cell| !cell.is_empty() && cell.chars().all(|c| matches!(c, '-' | ':' | ' ')) && cell.contains('-'))
+}
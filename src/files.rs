@@ -0,0 +1,66 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Expande un patrón con comodines (* y ?) en el nombre de archivo
+pub fn expand_pattern(pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(pattern);
+    let file_pattern = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context(format!("Patrón no válido: {}", pattern))?;
+
+    if !has_wildcards(file_pattern) {
+        if path.is_file() {
+            return Ok(vec![path.to_path_buf()]);
+        }
+        bail!("No existe el archivo {}", pattern);
+    }
+
+    // Sin directorio explícito se buscan (y devuelven) rutas relativas al actual
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+    let dir = parent.unwrap_or(Path::new("."));
+    let mut matches = Vec::new();
+    for entry in fs::read_dir(dir).context(format!("No se pudo leer el directorio {}", dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else { continue };
+        if entry.path().is_file() && wildcard_match(file_pattern, name) {
+            matches.push(match parent {
+                Some(parent) => parent.join(name),
+                None => PathBuf::from(name),
+            });
+        }
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+pub fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+// Coincidencia de comodines estilo shell, sin distinguir mayúsculas
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
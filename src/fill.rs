@@ -0,0 +1,359 @@
+// Relleno de rangos sin pasar por el modelo (`rellenar <archivo> <rango> <patrón>`):
+// series de números o de fechas, un valor repetido o una fórmula copiada hacia
+// abajo con sus referencias relativas movidas, como al arrastrar el controlador
+// de relleno en Excel. Los cambios se hacen en la copia cargada, como con
+// `editar`, y se escriben con `guardar`.
+use crate::excel::{self, CellValue, WorkbookData};
+use crate::formula_check;
+use crate::formulas;
+use crate::limits;
+use crate::named_ranges;
+use crate::xlsx_patch;
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+// Valores de ejemplo que se muestran al terminar
+const PREVIEW_CELLS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateStep {
+    Days(i64),
+    // Mismo día de cada mes; si no existe, el último del mes (31/01 → 29/02)
+    Months(i64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FillPattern {
+    // Fórmula (sin '=') escrita en la primera celda y movida en las demás
+    Formula(String),
+    // El contenido de la primera fila del rango, copiado en el resto
+    Copy,
+    Numbers { start: f64, step: f64 },
+    Dates { start: f64, step: DateStep },
+    Repeat(CellValue),
+}
+
+#[derive(Debug, Clone)]
+pub struct FillOptions {
+    pub file: String,
+    // Hoja!A2:A50 o un nombre definido
+    pub target: String,
+    pub pattern: String,
+    // paso= de las series: un número, o con fechas 7d, 2s, 1m, 1a
+    pub step: Option<String>,
+    pub overwrite: bool,
+}
+
+#[derive(Debug)]
+pub struct FillPlan {
+    pub sheet: String,
+    pub description: String,
+    // (fila, columna, valor, fórmula sin '=') de cada celda que se escribe
+    pub cells: Vec<(usize, usize, CellValue, Option<String>)>,
+}
+
+impl FillPattern {
+    // "=B2*C2", "copiar", "1..", "2024-01-31..", "Pendiente" o "\"007\""
+    pub fn parse(pattern: &str, step: Option<&str>) -> Result<FillPattern> {
+        let pattern = pattern.trim();
+        if let Some(start) = pattern.strip_suffix("..") {
+            let start = start.trim();
+            if let Some(start) = parse_number(start) {
+                let step = match step {
+                    Some(step) => parse_number(step).context(format!("Paso no válido: '{}' (usa un número, p. ej. paso=5)", step))?,
+                    None => 1.0,
+                };
+                return Ok(FillPattern::Numbers { start, step });
+            }
+            if let Some(start) = excel::parse_date_text(start) {
+                let step = match step {
+                    Some(step) => parse_date_step(step)?,
+                    None => DateStep::Days(1),
+                };
+                return Ok(FillPattern::Dates { start, step });
+            }
+            bail!("Inicio de serie no válido: '{}' (usa un número o una fecha, p. ej. 1.. o 2024-01-31..)", start);
+        }
+        if let Some(step) = step {
+            bail!("paso={} solo vale con una serie (<inicio>..)", step);
+        }
+        if let Some(formula) = pattern.strip_prefix('=') {
+            let formula = formula.trim();
+            if formula.is_empty() {
+                bail!("Falta la fórmula después de '='");
+            }
+            return Ok(FillPattern::Formula(formula.to_string()));
+        }
+        if pattern.eq_ignore_ascii_case("copiar") || pattern.eq_ignore_ascii_case("copy") {
+            return Ok(FillPattern::Copy);
+        }
+        // Como en editar: entre comillas es texto tal cual y una fecha se reconoce
+        let quoted = pattern
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+            .or_else(|| pattern.strip_prefix('“').and_then(|rest| rest.strip_suffix('”')));
+        let value = match quoted {
+            Some(text) => CellValue::Text(text.to_string()),
+            None => match CellValue::infer(pattern) {
+                CellValue::Text(text) => excel::parse_date_text(&text).map_or(CellValue::Text(text), CellValue::DateTime),
+                value => value,
+            },
+        };
+        Ok(FillPattern::Repeat(value))
+    }
+
+    // Valor de la celda `index` (desde 0) de una serie
+    fn series_value(&self, index: usize) -> Option<CellValue> {
+        match self {
+            FillPattern::Numbers { start, step } => Some(CellValue::Number(round(start + step * index as f64))),
+            FillPattern::Dates { start, step: DateStep::Days(days) } => {
+                Some(CellValue::DateTime(start + (days * index as i64) as f64))
+            }
+            FillPattern::Dates { start, step: DateStep::Months(months) } => {
+                add_months(*start, months * index as i64).map(CellValue::DateTime)
+            }
+            _ => None,
+        }
+    }
+}
+
+// Las celdas que se escriben en el rango de `options` de la copia cargada `data`
+pub fn plan(options: &FillOptions, data: &WorkbookData) -> Result<FillPlan> {
+    let pattern = FillPattern::parse(&options.pattern, options.step.as_deref())?;
+    let names = match xlsx_patch::read_part_from(Path::new(&options.file), "xl/workbook.xml", None) {
+        Ok(Some(workbook)) => named_ranges::defined_names(&workbook),
+        _ => Vec::new(),
+    };
+    let target = named_ranges::resolve_target(&names, &options.target)?;
+    let sheet = data.require_sheet(&options.file, &target.sheet)?;
+    let range = target.range;
+    let sheets: Vec<String> = data.sheets.iter().map(|s| s.name.clone()).collect();
+    let name_list: Vec<String> = names.iter().map(|defined| defined.name.clone()).collect();
+
+    // Con copiar la primera fila es el origen y no se escribe
+    let first_row = match pattern {
+        FillPattern::Copy if range.first_row == range.last_row => {
+            bail!("{} solo tiene una fila: con copiar, la primera fila del rango es la que se copia", target.description)
+        }
+        FillPattern::Copy => range.first_row + 1,
+        _ => range.first_row,
+    };
+    limits::check_write_cells((range.last_row - first_row + 1) * (range.last_col - range.first_col + 1))?;
+
+    let sources: Vec<(Option<String>, CellValue)> = match &pattern {
+        FillPattern::Formula(text) => {
+            formula_check::check(text, &sheets, &name_list).context(format!("La fórmula ={} no es válida", text))?;
+            Vec::new()
+        }
+        FillPattern::Copy => {
+            // La copia cargada solo tiene las fórmulas del archivo si se leyó con --formulas
+            let stored = if Path::new(&options.file).extension().is_some_and(|e| e.eq_ignore_ascii_case("xlsx")) {
+                formulas::list(&options.file, &sheet.name).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            (range.first_col..=range.last_col)
+                .map(|col| {
+                    let reference = format!("{}{}", excel::column_letters(col), range.first_row + 1);
+                    let formula = sheet.formulas.get(&(range.first_row, col)).cloned().or_else(|| {
+                        stored
+                            .iter()
+                            .find(|row| row[0] == reference)
+                            .map(|row| row[1].trim_start_matches('=').to_string())
+                    });
+                    let value = sheet.cell(range.first_row, col).clone();
+                    if formula.is_none() && value == CellValue::Empty {
+                        bail!("{} está vacía: copiar rellena el rango con el contenido de su primera fila", reference);
+                    }
+                    Ok((formula, value))
+                })
+                .collect::<Result<_>>()?
+        }
+        _ => Vec::new(),
+    };
+
+    if !options.overwrite {
+        let mut taken = Vec::new();
+        for row in first_row..=range.last_row {
+            for col in range.first_col..=range.last_col {
+                if sheet.cell(row, col) != &CellValue::Empty || sheet.formulas.contains_key(&(row, col)) {
+                    taken.push(format!("{}{}", excel::column_letters(col), row + 1));
+                }
+            }
+        }
+        if !taken.is_empty() {
+            let shown: Vec<&str> = taken.iter().take(5).map(String::as_str).collect();
+            bail!(
+                "{} tiene {} celda(s) con datos ({}{}); usa --sobrescribir para reemplazarlas o elige celdas vacías",
+                target.description,
+                taken.len(),
+                shown.join(", "),
+                if taken.len() > shown.len() { ", …" } else { "" }
+            );
+        }
+    }
+
+    let mut cells = Vec::new();
+    for row in first_row..=range.last_row {
+        for col in range.first_col..=range.last_col {
+            let (rows, cols) = (row - range.first_row, col - range.first_col);
+            let cell = match &pattern {
+                FillPattern::Formula(text) => (CellValue::Empty, Some(formula_check::shift(text, &sheets, &name_list, rows, cols)?)),
+                FillPattern::Copy => match &sources[cols] {
+                    (Some(formula), _) => (CellValue::Empty, Some(formula_check::shift(formula, &sheets, &name_list, rows, 0)?)),
+                    (None, value) => (value.clone(), None),
+                },
+                FillPattern::Repeat(value) => (value.clone(), None),
+                series => (series.series_value(rows).context("La serie se sale de las fechas de Excel")?, None),
+            };
+            cells.push((row, col, cell.0, cell.1));
+        }
+    }
+    Ok(FillPlan {
+        sheet: sheet.name.clone(),
+        description: target.description,
+        cells,
+    })
+}
+
+impl FillPlan {
+    // "1, 2, 3 … 49" o "=B2*C2, =B3*C3, =B4*C4 … =B50*C50"
+    pub fn preview(&self) -> String {
+        let shown = |(_, _, value, formula): &(usize, usize, CellValue, Option<String>)| match formula {
+            Some(formula) => format!("={}", formula),
+            None => value.to_string(),
+        };
+        let first: Vec<String> = self.cells.iter().take(PREVIEW_CELLS).map(shown).collect();
+        match self.cells.last() {
+            Some(last) if self.cells.len() > PREVIEW_CELLS => format!("{} … {}", first.join(", "), shown(last)),
+            _ => first.join(", "),
+        }
+    }
+}
+
+// "1,5" también vale: el separador decimal de la hoja
+fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    text.parse::<f64>().ok().or_else(|| text.replace(',', ".").parse().ok()).filter(|n: &f64| n.is_finite())
+}
+
+// "7d" (días), "2s" (semanas), "1m" (meses), "1a" (años); sin unidad, días
+fn parse_date_step(step: &str) -> Result<DateStep> {
+    let step = step.trim().to_lowercase();
+    let split = step.find(|c: char| !(c.is_ascii_digit() || c == '-' || c == '+')).unwrap_or(step.len());
+    let (number, unit) = step.split_at(split);
+    let count: i64 = match number {
+        "" => 1,
+        number => number
+            .parse()
+            .ok()
+            .context(format!("Paso no válido: '{}' (usa p. ej. paso=7d, paso=1m o paso=1a)", step))?,
+    };
+    Ok(match unit {
+        "" | "d" | "dia" | "día" | "dias" | "días" | "day" | "days" => DateStep::Days(count),
+        "s" | "w" | "semana" | "semanas" | "week" | "weeks" => DateStep::Days(count * 7),
+        "m" | "mes" | "meses" | "month" | "months" => DateStep::Months(count),
+        "a" | "y" | "año" | "años" | "year" | "years" => DateStep::Months(count * 12),
+        _ => bail!("Unidad de paso no válida: '{}' (d, s, m o a)", unit),
+    })
+}
+
+// Fecha `months` meses después, con la misma hora
+fn add_months(serial: f64, months: i64) -> Option<f64> {
+    let (year, month, day) = excel::serial_to_date(serial);
+    let total = year * 12 + i64::from(month) - 1 + months;
+    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) + 1);
+    let time = serial - serial.floor();
+    (day.min(28)..=day)
+        .rev()
+        .find_map(|day| excel::parse_iso_datetime(&format!("{:04}-{:02}-{:02}", year, month, day)))
+        .map(|date| date + time)
+}
+
+// Quita el ruido de coma flotante de pasos como 0.1
+fn round(value: f64) -> f64 {
+    (value * 1e10).round() / 1e10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::excel::SheetData;
+
+    // Precio y unidades en B2:C4; las demás columnas vacías
+    fn data() -> WorkbookData {
+        WorkbookData {
+            sheets: vec![SheetData::from_rows(
+                "Datos",
+                &[&["Producto", "Precio", "Unidades"], &["Lápiz", "1,5", "10"], &["Goma", "0,8", "4"], &["Regla", "2", "1"]],
+            )],
+        }
+    }
+
+    fn fill(target: &str, pattern: &str, step: Option<&str>, overwrite: bool) -> Result<FillPlan> {
+        let options = FillOptions {
+            // Sin archivo: no hay nombres definidos ni fórmulas guardadas
+            file: crate::paths::test_dir("fill").join("ventas.xlsx").display().to_string(),
+            target: target.to_string(),
+            pattern: pattern.to_string(),
+            step: step.map(str::to_string),
+            overwrite,
+        };
+        plan(&options, &data())
+    }
+
+    fn date(text: &str) -> CellValue {
+        CellValue::DateTime(excel::parse_iso_datetime(text).unwrap())
+    }
+
+    #[test]
+    fn patterns_are_told_apart_by_their_shape() {
+        assert_eq!(FillPattern::parse("1..", None).unwrap(), FillPattern::Numbers { start: 1.0, step: 1.0 });
+        assert_eq!(FillPattern::parse("0,5..", Some("0,25")).unwrap(), FillPattern::Numbers { start: 0.5, step: 0.25 });
+        assert_eq!(
+            FillPattern::parse("2024-01-31..", Some("2s")).unwrap(),
+            FillPattern::Dates { start: excel::parse_iso_datetime("2024-01-31").unwrap(), step: DateStep::Days(14) }
+        );
+        assert_eq!(FillPattern::parse("=B2*C2", None).unwrap(), FillPattern::Formula("B2*C2".to_string()));
+        assert_eq!(FillPattern::parse("Copiar", None).unwrap(), FillPattern::Copy);
+        assert_eq!(FillPattern::parse("\"007\"", None).unwrap(), FillPattern::Repeat(CellValue::Text("007".to_string())));
+        assert_eq!(FillPattern::parse("42", None).unwrap(), FillPattern::Repeat(CellValue::Number(42.0)));
+        assert_eq!(FillPattern::parse("15/02/2024", None).unwrap(), FillPattern::Repeat(date("2024-02-15")));
+        assert!(FillPattern::parse("x..", None).is_err());
+        assert!(FillPattern::parse("Pendiente", Some("2")).is_err());
+        assert!(FillPattern::parse("2024-01-31..", Some("3q")).is_err());
+    }
+
+    #[test]
+    fn series_step_without_float_noise_and_months_clamp_to_the_month_end() {
+        let numbers = fill("Datos!D2:D4", "0,1..", Some("0,1"), false).unwrap();
+        assert_eq!(numbers.preview(), "0.1, 0.2, 0.3");
+        assert_eq!(numbers.sheet, "Datos");
+
+        let months = fill("Datos!E2:E5", "31/01/2024..", Some("1m"), false).unwrap();
+        let values: Vec<CellValue> = months.cells.iter().map(|(_, _, value, _)| value.clone()).collect();
+        assert_eq!(values, [date("2024-01-31"), date("2024-02-29"), date("2024-03-31"), date("2024-04-30")]);
+    }
+
+    #[test]
+    fn a_formula_moves_its_relative_references_down_the_range() {
+        let plan = fill("Datos!D2:D4", "=B2*C2*$F$1", None, false).unwrap();
+        assert_eq!(plan.preview(), "=B2*C2*$F$1, =B3*C3*$F$1, =B4*C4*$F$1");
+        assert_eq!(plan.cells[2].0, 3);
+        let error = fill("Datos!D2:D4", "=SUMA(B2:B4)", None, false).unwrap_err();
+        assert!(format!("{:#}", error).contains("SUMA es el nombre en español"), "{:#}", error);
+    }
+
+    #[test]
+    fn filled_cells_are_kept_unless_overwritten() {
+        let error = fill("Datos!B2:B3", "0", None, false).unwrap_err();
+        assert!(error.to_string().contains("2 celda(s) con datos (B2, B3)"), "{}", error);
+        assert_eq!(fill("Datos!B2:B3", "0", None, true).unwrap().cells.len(), 2);
+
+        // Copiar toma la primera fila del rango y no la reescribe
+        let copied = fill("Datos!C2:C4", "copiar", None, true).unwrap();
+        assert_eq!(copied.preview(), "10, 10");
+        assert!(fill("Datos!C2", "copiar", None, true).is_err());
+        assert!(fill("Datos!D2:D3", "copiar", None, true).is_err());
+    }
+}
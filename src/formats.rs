@@ -0,0 +1,216 @@
+// Formatos de un libro existente, para que reescribirlo (guardar, transformar_hoja,
+// cruzar_hojas...) no los pierda: calamine solo da los valores y rust_xlsxwriter
+// escribe un libro nuevo. Al leer se toman de styles.xml el formato numérico
+// (moneda, porcentaje, fecha), la negrita, la cursiva, la alineación y los
+// colores de fuente y relleno de cada celda, y del XML de la hoja los anchos, las
+// columnas ocultas y los paneles inmovilizados. Al guardar se vuelven a aplicar;
+// un formato de columna puesto en la sesión (`SheetData::column_formats`)
+// sustituye al formato numérico original.
+use crate::excel;
+use crate::layout::SheetLayout;
+use crate::xlsx_patch::{find_elements, find_tags, xml_attr, xml_unescape};
+use rust_xlsxwriter::{Color, Format, FormatAlign};
+use std::collections::BTreeMap;
+
+// Un <col> puede llegar hasta la última columna (XFD); más allá de estas no se
+// conservan su ancho ni si está oculta
+pub const MAX_LAYOUT_COLUMNS: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NumFormat {
+    // Formato integrado por su número (14 es la fecha corta de la configuración regional)
+    Builtin(u8),
+    Code(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CellStyle {
+    pub num_format: Option<NumFormat>,
+    pub bold: bool,
+    pub italic: bool,
+    // "left", "center" o "right"
+    pub align: Option<&'static str>,
+    // Colores RGB (0xRRGGBB)
+    pub font_color: Option<u32>,
+    pub fill: Option<u32>,
+    // <protection locked="0"/>: editable aunque la hoja esté protegida
+    pub unlocked: bool,
+}
+
+impl CellStyle {
+    fn is_default(&self) -> bool {
+        *self == CellStyle::default()
+    }
+
+    // Formato de rust_xlsxwriter; `num_format` sustituye al formato numérico propio
+    pub fn to_format(&self, num_format: Option<&str>) -> Format {
+        let mut format = Format::new();
+        format = match (num_format, &self.num_format) {
+            (Some(code), _) => format.set_num_format(code),
+            (None, Some(NumFormat::Builtin(id))) => format.set_num_format_index(*id),
+            (None, Some(NumFormat::Code(code))) => format.set_num_format(code),
+            (None, None) => format,
+        };
+        if self.bold {
+            format = format.set_bold();
+        }
+        if self.italic {
+            format = format.set_italic();
+        }
+        format = match self.align {
+            Some("left") => format.set_align(FormatAlign::Left),
+            Some("center") => format.set_align(FormatAlign::Center),
+            Some("right") => format.set_align(FormatAlign::Right),
+            _ => format,
+        };
+        if let Some(color) = self.font_color {
+            format = format.set_font_color(Color::RGB(color));
+        }
+        if let Some(color) = self.fill {
+            format = format.set_background_color(Color::RGB(color));
+        }
+        if self.unlocked {
+            format = format.set_unlocked();
+        }
+        format
+    }
+}
+
+// Estilos de celda de styles.xml en el orden de <cellXfs>: el atributo s de
+// cada celda es su posición
+#[derive(Debug, Default)]
+pub struct Styles {
+    cell_styles: Vec<CellStyle>,
+}
+
+// Contenido del primer elemento `name`, o vacío
+fn section(xml: &str, name: &str) -> String {
+    find_elements(xml, name).into_iter().next().map(|(_, content)| content).unwrap_or_default()
+}
+
+// "FFFF0000" (ARGB) -> 0xFF0000; los colores de tema e indexados no se conservan
+fn rgb(element: &str, tag: &str) -> Option<u32> {
+    let tag = find_tags(element, tag).into_iter().next()?;
+    let value = xml_attr(&tag, "rgb")?;
+    u32::from_str_radix(&value[value.len().saturating_sub(6)..], 16).ok()
+}
+
+// Un <b/> o <b val="1"/>; <b val="0"/> anula la negrita
+fn flag(element: &str, tag: &str) -> bool {
+    find_tags(element, tag)
+        .first()
+        .is_some_and(|tag| !matches!(xml_attr(tag, "val").as_deref(), Some("0" | "false")))
+}
+
+impl Styles {
+    pub fn parse(styles_xml: &str) -> Styles {
+        let custom: BTreeMap<u32, String> = find_tags(styles_xml, "numFmt")
+            .iter()
+            .filter_map(|tag| Some((xml_attr(tag, "numFmtId")?.parse().ok()?, xml_unescape(&xml_attr(tag, "formatCode")?))))
+            .collect();
+        // Fuentes como (negrita, cursiva, color); rellenos como color sólido
+        let fonts: Vec<(bool, bool, Option<u32>)> = find_elements(&section(styles_xml, "fonts"), "font")
+            .iter()
+            .map(|(_, font)| (flag(font, "b"), flag(font, "i"), rgb(font, "color")))
+            .collect();
+        let fills: Vec<Option<u32>> = find_elements(&section(styles_xml, "fills"), "fill")
+            .iter()
+            .map(|(_, fill)| {
+                let solid = find_tags(fill, "patternFill")
+                    .first()
+                    .is_some_and(|tag| xml_attr(tag, "patternType").as_deref() == Some("solid"));
+                if solid {
+                    rgb(fill, "fgColor")
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let cell_styles = find_elements(&section(styles_xml, "cellXfs"), "xf")
+            .iter()
+            .map(|(tag, content)| {
+                let index = |name: &str| xml_attr(tag, name).and_then(|id| id.parse::<usize>().ok()).unwrap_or(0);
+                let num_format = match index("numFmtId") {
+                    0 => None,
+                    id => match custom.get(&(id as u32)) {
+                        Some(code) => Some(NumFormat::Code(code.clone())),
+                        None => u8::try_from(id).ok().map(NumFormat::Builtin),
+                    },
+                };
+                let font = fonts.get(index("fontId")).copied().unwrap_or_default();
+                let align = find_tags(content, "alignment")
+                    .first()
+                    .and_then(|tag| xml_attr(tag, "horizontal"))
+                    .and_then(|align| ["left", "center", "right"].into_iter().find(|known| *known == align));
+                CellStyle {
+                    num_format,
+                    bold: font.0,
+                    italic: font.1,
+                    align,
+                    // El negro de la fuente por defecto no hace falta repetirlo
+                    font_color: font.2.filter(|color| *color != 0),
+                    fill: fills.get(index("fillId")).copied().flatten(),
+                    unlocked: find_tags(content, "protection")
+                        .first()
+                        .is_some_and(|tag| matches!(xml_attr(tag, "locked").as_deref(), Some("0" | "false"))),
+                }
+            })
+            .collect();
+        Styles { cell_styles }
+    }
+
+    // Estilo (fila, columna) -> formato de las celdas de una hoja que no tienen el
+    // estilo por defecto
+    pub fn cell_styles(&self, sheet_xml: &str) -> BTreeMap<(usize, usize), CellStyle> {
+        let mut styles = BTreeMap::new();
+        for tag in find_tags(sheet_xml, "c") {
+            let Some(style) = xml_attr(&tag, "s")
+                .and_then(|s| s.parse::<usize>().ok())
+                .and_then(|s| self.cell_styles.get(s))
+                .filter(|style| !style.is_default())
+            else {
+                continue;
+            };
+            if let Some(cell) = xml_attr(&tag, "r").and_then(|r| excel::parse_cell_ref(&r)) {
+                styles.insert(cell, style.clone());
+            }
+        }
+        styles
+    }
+}
+
+// Anchos, columnas ocultas y paneles inmovilizados del XML de una hoja
+pub fn sheet_layout(sheet_xml: &str) -> SheetLayout {
+    let mut layout = SheetLayout::default();
+    for tag in find_tags(&section(sheet_xml, "cols"), "col") {
+        let (Some(min), Some(max)) = (
+            xml_attr(&tag, "min").and_then(|v| v.parse::<usize>().ok()),
+            xml_attr(&tag, "max").and_then(|v| v.parse::<usize>().ok()),
+        ) else {
+            continue;
+        };
+        // El XML guarda el ancho con el margen de la celda (5 píxeles de 7 por
+        // carácter); set_column_width lo espera sin él
+        let width = xml_attr(&tag, "width")
+            .and_then(|v| v.parse::<f64>().ok())
+            .map(|width| if width > 1.0 { ((width * 7.0 - 5.0) / 7.0 * 100.0).round() / 100.0 } else { width });
+        let custom = matches!(xml_attr(&tag, "customWidth").as_deref(), Some("1" | "true"));
+        let hidden = matches!(xml_attr(&tag, "hidden").as_deref(), Some("1" | "true"));
+        for col in min.saturating_sub(1)..max.min(MAX_LAYOUT_COLUMNS) {
+            if let Some(width) = width.filter(|_| custom) {
+                layout.widths.insert(col, width);
+            }
+            if hidden {
+                layout.hidden.insert(col);
+            }
+        }
+    }
+    if let Some(pane) = find_tags(sheet_xml, "pane").first() {
+        if matches!(xml_attr(pane, "state").as_deref(), Some("frozen" | "frozenSplit")) {
+            let split = |name: &str| xml_attr(pane, name).and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0) as usize;
+            layout.freeze_rows = split("ySplit");
+            layout.freeze_cols = split("xSplit");
+        }
+    }
+    layout
+}
@@ -0,0 +1,966 @@
+// Evaluación de fórmulas habituales sobre los datos leídos, para no depender de
+// los valores que Excel guardó la última vez (pueden faltar o estar obsoletos).
+// Se reconocen referencias (A1, $B$2, Hoja!A1, 'Mi hoja'!A1:B9, columnas enteras
+// como A:A o $B:$D), operadores
+// aritméticos, de comparación y &, y las funciones de `Evaluator::call`; una
+// fórmula con cualquier otra cosa conserva el valor guardado.
+use crate::excel::{self, CellValue, WorkbookData};
+use crate::formulas;
+use crate::xlsx_patch;
+use anyhow::{Context, Result};
+use calamine::{open_workbook, Xlsx};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+// Resultado de evaluar las fórmulas de un libro
+#[derive(Debug, Default, Clone)]
+pub struct EvalReport {
+    pub evaluated: usize,
+    // Fórmulas cuyo valor calculado no coincide con el guardado en el archivo
+    pub changed: Vec<String>,
+    // Fórmulas con funciones o sintaxis no soportadas (se deja el valor guardado)
+    pub unsupported: usize,
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} fórmulas evaluadas, {} con un valor distinto del guardado, {} no soportadas",
+            self.evaluated,
+            self.changed.len(),
+            self.unsupported
+        )?;
+        if !self.changed.is_empty() {
+            let shown: Vec<&str> = self.changed.iter().take(10).map(String::as_str).collect();
+            write!(f, " (recalculadas: {}{})", shown.join(", "), if self.changed.len() > 10 { ", …" } else { "" })?;
+        }
+        Ok(())
+    }
+}
+
+// Lee las fórmulas del archivo y sustituye en `data` los valores de las que se pueden calcular
+pub fn evaluate_file(filename: &str, data: &mut WorkbookData) -> Result<EvalReport> {
+    let path = Path::new(filename);
+    let mut workbook: Xlsx<_> = open_workbook(path).context(format!("No se pudo abrir el archivo {}", filename))?;
+    let parts = xlsx_patch::read_sheet_xml(path)?;
+    let sheets: Vec<String> = data.sheets.iter().map(|s| s.name.clone()).collect();
+    let mut formulas = HashMap::new();
+    for (sheet_idx, sheet) in sheets.iter().enumerate() {
+        let part = parts.iter().find(|part| part.name == *sheet);
+        for ((row, col), text) in formulas::sheet_formulas(&mut workbook, sheet, part, &sheets) {
+            formulas.insert((sheet_idx, row, col), text);
+        }
+    }
+    Ok(evaluate(data, &formulas))
+}
+
+// Evalúa las fórmulas indicadas por (hoja, fila, columna) y actualiza `data`
+pub fn evaluate(data: &mut WorkbookData, formulas: &HashMap<(usize, usize, usize), String>) -> EvalReport {
+    let mut report = EvalReport::default();
+    let sheet_names: Vec<String> = data.sheets.iter().map(|s| s.name.clone()).collect();
+    let mut parsed = HashMap::new();
+    for (key, text) in formulas {
+        match Parser::new(text, key.0, &sheet_names).parse_formula() {
+            Some(expr) => {
+                parsed.insert(*key, expr);
+            }
+            None => report.unsupported += 1,
+        }
+    }
+
+    let mut evaluator = Evaluator {
+        data,
+        formulas: &parsed,
+        results: HashMap::new(),
+        in_progress: HashSet::new(),
+    };
+    let mut keys: Vec<&(usize, usize, usize)> = parsed.keys().collect();
+    keys.sort();
+    let mut updates = Vec::new();
+    for key in keys {
+        match evaluator.cell(*key) {
+            Ok(value) => {
+                report.evaluated += 1;
+                updates.push((*key, value));
+            }
+            Err(Unsupported) => report.unsupported += 1,
+        }
+    }
+
+    for ((sheet_idx, row, col), value) in updates {
+        let sheet = &mut data.sheets[sheet_idx];
+        if sheet.rows.len() <= row {
+            sheet.rows.resize(row + 1, Vec::new());
+        }
+        let cells = &mut sheet.rows[row];
+        if cells.len() <= col {
+            cells.resize(col + 1, CellValue::Empty);
+        }
+        let computed = value.into_cell(&cells[col]);
+        if !same_value(&cells[col], &computed) {
+            report
+                .changed
+                .push(format!("{}!{}{}", sheet.name, excel::column_letters(col), row + 1));
+            cells[col] = computed;
+        }
+    }
+    report
+}
+
+fn same_value(a: &CellValue, b: &CellValue) -> bool {
+    match (a.as_number(), b.as_number()) {
+        (Some(x), Some(y)) => (x - y).abs() <= 1e-9 * x.abs().max(y.abs()).max(1.0),
+        _ => a == b,
+    }
+}
+
+// --- Análisis sintáctico ---
+
+// Última fila de una hoja de Excel (desde 0), el final de A:A
+const LAST_ROW: usize = 1_048_575;
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    // (hoja, fila, columna) desde 0
+    Cell(usize, usize, usize),
+    // (hoja, fila inicial, columna inicial, fila final, columna final)
+    Range(usize, usize, usize, usize, usize),
+    Negate(Box<Expr>),
+    Percent(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    // Comparaciones: = <> < > <= >=
+    Compare(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    sheet: usize,
+    sheet_names: &'a [String],
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &str, sheet: usize, sheet_names: &'a [String]) -> Parser<'a> {
+        Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+            sheet,
+            sheet_names,
+        }
+    }
+
+    fn parse_formula(&mut self) -> Option<Expr> {
+        let expr = self.comparison()?;
+        self.skip_spaces();
+        (self.pos == self.chars.len()).then_some(expr)
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_spaces();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        self.skip_spaces();
+        let matches = expected.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            self.pos += expected.chars().count();
+        }
+        matches
+    }
+
+    fn comparison(&mut self) -> Option<Expr> {
+        let mut left = self.concat()?;
+        loop {
+            let op = ["<>", "<=", ">=", "=", "<", ">"].into_iter().find(|op| self.eat(op));
+            let Some(op) = op else { return Some(left) };
+            let right = self.concat()?;
+            left = Expr::Compare(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn concat(&mut self) -> Option<Expr> {
+        let mut left = self.additive()?;
+        while self.eat("&") {
+            left = Expr::Binary('&', Box::new(left), Box::new(self.additive()?));
+        }
+        Some(left)
+    }
+
+    fn additive(&mut self) -> Option<Expr> {
+        let mut left = self.multiplicative()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+        Some(left)
+    }
+
+    fn multiplicative(&mut self) -> Option<Expr> {
+        let mut left = self.power()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.power()?));
+        }
+        Some(left)
+    }
+
+    // Como en Excel, el signo negativo se aplica antes que la potencia (-2^2 = 4)
+    fn power(&mut self) -> Option<Expr> {
+        let mut left = self.unary()?;
+        while self.eat("^") {
+            left = Expr::Binary('^', Box::new(left), Box::new(self.unary()?));
+        }
+        Some(left)
+    }
+
+    fn unary(&mut self) -> Option<Expr> {
+        if self.eat("-") {
+            return Some(Expr::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat("+") {
+            return self.unary();
+        }
+        let mut expr = self.primary()?;
+        while self.eat("%") {
+            expr = Expr::Percent(Box::new(expr));
+        }
+        Some(expr)
+    }
+
+    fn primary(&mut self) -> Option<Expr> {
+        match self.peek()? {
+            '(' => {
+                self.pos += 1;
+                let expr = self.comparison()?;
+                self.eat(")").then_some(expr)
+            }
+            '"' => self.string(),
+            c if c.is_ascii_digit() || c == '.' => self.number(),
+            '\'' => {
+                let sheet = self.quoted_sheet()?;
+                self.reference(sheet)
+            }
+            _ => self.name_or_reference(),
+        }
+    }
+
+    fn string(&mut self) -> Option<Expr> {
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.chars.get(self.pos)? {
+                '"' if self.chars.get(self.pos + 1) == Some(&'"') => {
+                    text.push('"');
+                    self.pos += 2;
+                }
+                '"' => {
+                    self.pos += 1;
+                    return Some(Expr::Text(text));
+                }
+                c => {
+                    text.push(*c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<Expr> {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+            self.pos += 1;
+        }
+        if matches!(self.chars.get(self.pos), Some('e' | 'E')) {
+            let mark = self.pos;
+            self.pos += 1;
+            if matches!(self.chars.get(self.pos), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            if !self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+                self.pos = mark;
+            }
+            while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1;
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().ok().map(Expr::Number)
+    }
+
+    fn quoted_sheet(&mut self) -> Option<usize> {
+        self.pos += 1;
+        let mut name = String::new();
+        loop {
+            match self.chars.get(self.pos)? {
+                '\'' if self.chars.get(self.pos + 1) == Some(&'\'') => {
+                    name.push('\'');
+                    self.pos += 2;
+                }
+                '\'' => {
+                    self.pos += 1;
+                    break;
+                }
+                c => {
+                    name.push(*c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if self.chars.get(self.pos) != Some(&'!') {
+            return None;
+        }
+        self.pos += 1;
+        self.sheet_index(&name)
+    }
+
+    fn sheet_index(&self, name: &str) -> Option<usize> {
+        self.sheet_names.iter().position(|s| s.eq_ignore_ascii_case(name))
+    }
+
+    // Nombre de función, TRUE/FALSE, hoja sin comillas o referencia de celda
+    fn name_or_reference(&mut self) -> Option<Expr> {
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '$'))
+        {
+            self.pos += 1;
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        if word.is_empty() {
+            return None;
+        }
+        match self.chars.get(self.pos) {
+            Some('(') => {
+                self.pos += 1;
+                let name = word.to_uppercase();
+                let name = name.strip_prefix("_XLFN.").unwrap_or(&name).to_string();
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.comparison()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        if !self.eat(",") {
+                            return None;
+                        }
+                    }
+                }
+                Some(Expr::Call(name, args))
+            }
+            Some('!') => {
+                self.pos += 1;
+                let sheet = self.sheet_index(&word)?;
+                self.reference(sheet)
+            }
+            _ if word.eq_ignore_ascii_case("TRUE") => Some(Expr::Bool(true)),
+            _ if word.eq_ignore_ascii_case("FALSE") => Some(Expr::Bool(false)),
+            _ => {
+                self.pos = start;
+                self.reference(self.sheet)
+            }
+        }
+    }
+
+    fn reference(&mut self, sheet: usize) -> Option<Expr> {
+        let start = self.pos;
+        let Some((row, col)) = self.cell_ref() else {
+            // Columnas enteras (A:A, $B:$D); al evaluar llegan hasta la última fila de la hoja
+            self.pos = start;
+            let first = self.column_ref()?;
+            if self.chars.get(self.pos) != Some(&':') {
+                return None;
+            }
+            self.pos += 1;
+            let last = self.column_ref()?;
+            return Some(Expr::Range(sheet, 0, first.min(last), LAST_ROW, first.max(last)));
+        };
+        if self.chars.get(self.pos) == Some(&':') {
+            self.pos += 1;
+            let (row2, col2) = self.cell_ref()?;
+            return Some(Expr::Range(sheet, row.min(row2), col.min(col2), row.max(row2), col.max(col2)));
+        }
+        Some(Expr::Cell(sheet, row, col))
+    }
+
+    fn column_ref(&mut self) -> Option<usize> {
+        self.eat("$");
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        let letters: String = self.chars[start..self.pos].iter().collect();
+        excel::column_from_letters(&letters)
+    }
+
+    fn cell_ref(&mut self) -> Option<(usize, usize)> {
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '$')
+        {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().filter(|c| **c != '$').collect();
+        excel::parse_cell_ref(&text)
+    }
+}
+
+// --- Evaluación ---
+
+// Fórmula que usa algo que el evaluador no conoce
+struct Unsupported;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Empty,
+    // Código de error de Excel (#DIV/0!, #N/A, ...)
+    Error(&'static str),
+}
+
+impl Value {
+    fn from_cell(cell: &CellValue) -> Value {
+        match cell {
+            CellValue::Empty => Value::Empty,
+            CellValue::Bool(b) => Value::Bool(*b),
+            CellValue::Number(n) | CellValue::DateTime(n) => Value::Number(*n),
+            CellValue::Text(s) => Value::Text(s.clone()),
+            CellValue::Error(code) => Value::Error(error_code(code)),
+        }
+    }
+
+    // Conserva el tipo fecha si la celda original lo era
+    fn into_cell(self, original: &CellValue) -> CellValue {
+        match self {
+            Value::Number(n) if matches!(original, CellValue::DateTime(_)) => CellValue::DateTime(n),
+            Value::Number(n) => CellValue::Number(n),
+            Value::Text(s) => CellValue::Text(s),
+            Value::Bool(b) => CellValue::Bool(b),
+            Value::Empty => CellValue::Number(0.0),
+            Value::Error(code) => CellValue::Error(code.to_string()),
+        }
+    }
+
+    fn number(&self) -> std::result::Result<f64, &'static str> {
+        match self {
+            Value::Number(n) => Ok(*n),
+            Value::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            Value::Empty => Ok(0.0),
+            Value::Text(s) => s.trim().parse().map_err(|_| "#VALUE!"),
+            Value::Error(code) => Err(code),
+        }
+    }
+
+    fn text(&self) -> std::result::Result<String, &'static str> {
+        match self {
+            Value::Number(n) => Ok(CellValue::Number(*n).to_string()),
+            Value::Bool(b) => Ok(if *b { "TRUE" } else { "FALSE" }.to_string()),
+            Value::Empty => Ok(String::new()),
+            Value::Text(s) => Ok(s.clone()),
+            Value::Error(code) => Err(code),
+        }
+    }
+
+    fn truthy(&self) -> std::result::Result<bool, &'static str> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            Value::Text(s) if s.eq_ignore_ascii_case("TRUE") => Ok(true),
+            Value::Text(s) if s.eq_ignore_ascii_case("FALSE") => Ok(false),
+            Value::Text(_) => Err("#VALUE!"),
+            other => other.number().map(|n| n != 0.0),
+        }
+    }
+}
+
+// Argumento de función: un valor o las celdas de un rango
+enum Arg {
+    Value(Value),
+    Range(Vec<Vec<Value>>),
+}
+
+impl Arg {
+    fn values(&self) -> Vec<&Value> {
+        match self {
+            Arg::Value(value) => vec![value],
+            Arg::Range(rows) => rows.iter().flatten().collect(),
+        }
+    }
+
+    fn scalar(self) -> Value {
+        match self {
+            Arg::Value(value) => value,
+            // Un rango donde se espera un valor: su primera celda
+            Arg::Range(rows) => rows.into_iter().flatten().next().unwrap_or(Value::Empty),
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    data: &'a WorkbookData,
+    formulas: &'a HashMap<(usize, usize, usize), Expr>,
+    results: HashMap<(usize, usize, usize), Value>,
+    in_progress: HashSet<(usize, usize, usize)>,
+}
+
+type Eval<T> = std::result::Result<T, Unsupported>;
+
+// Propaga un error de Excel como valor
+macro_rules! excel_try {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(code) => return Ok(Value::Error(code)),
+        }
+    };
+}
+
+impl Evaluator<'_> {
+    // Valor de una celda: calculado si tiene fórmula, el leído si no
+    fn cell(&mut self, key: (usize, usize, usize)) -> Eval<Value> {
+        if let Some(value) = self.results.get(&key) {
+            return Ok(value.clone());
+        }
+        let Some(expr) = self.formulas.get(&key) else {
+            let (sheet, row, col) = key;
+            return Ok(self
+                .data
+                .sheets
+                .get(sheet)
+                .and_then(|s| s.rows.get(row))
+                .and_then(|r| r.get(col))
+                .map(Value::from_cell)
+                .unwrap_or(Value::Empty));
+        };
+        if !self.in_progress.insert(key) {
+            // Referencia circular
+            return Ok(Value::Error("#REF!"));
+        }
+        let value = self.expr(expr).map(Arg::scalar);
+        self.in_progress.remove(&key);
+        let value = value?;
+        self.results.insert(key, value.clone());
+        Ok(value)
+    }
+
+    fn range(&mut self, sheet: usize, r1: usize, c1: usize, r2: usize, c2: usize) -> Eval<Vec<Vec<Value>>> {
+        // Los rangos de columna entera se limitan a las filas que tiene la hoja
+        let last_row = self.data.sheets.get(sheet).map_or(0, |s| s.rows.len().saturating_sub(1));
+        let r2 = r2.min(last_row.max(r1));
+        (r1..=r2)
+            .map(|row| (c1..=c2).map(|col| self.cell((sheet, row, col))).collect())
+            .collect()
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Eval<Arg> {
+        let value = match expr {
+            Expr::Number(n) => Value::Number(*n),
+            Expr::Text(s) => Value::Text(s.clone()),
+            Expr::Bool(b) => Value::Bool(*b),
+            Expr::Cell(sheet, row, col) => self.cell((*sheet, *row, *col))?,
+            Expr::Range(sheet, r1, c1, r2, c2) => return Ok(Arg::Range(self.range(*sheet, *r1, *c1, *r2, *c2)?)),
+            Expr::Negate(inner) => {
+                let value = self.scalar(inner)?;
+                numeric(value.number().map(|n| -n))
+            }
+            Expr::Percent(inner) => {
+                let value = self.scalar(inner)?;
+                numeric(value.number().map(|n| n / 100.0))
+            }
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (self.scalar(left)?, self.scalar(right)?);
+                binary(*op, &left, &right)
+            }
+            Expr::Compare(op, left, right) => {
+                let (left, right) = (self.scalar(left)?, self.scalar(right)?);
+                match (&left, &right) {
+                    (Value::Error(code), _) | (_, Value::Error(code)) => Value::Error(code),
+                    _ => Value::Bool(compare(op, &left, &right)),
+                }
+            }
+            Expr::Call(name, args) => self.call(name, args)?,
+        };
+        Ok(Arg::Value(value))
+    }
+
+    fn scalar(&mut self, expr: &Expr) -> Eval<Value> {
+        self.expr(expr).map(Arg::scalar)
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr]) -> Eval<Value> {
+        // IF e IFERROR solo evalúan la rama necesaria
+        match (name, args.len()) {
+            ("IF", 2 | 3) => {
+                let condition = self.scalar(&args[0])?;
+                let branch = excel_try!(condition.truthy());
+                return match (branch, args.get(2)) {
+                    (true, _) => self.scalar(&args[1]),
+                    (false, Some(otherwise)) => self.scalar(otherwise),
+                    (false, None) => Ok(Value::Bool(false)),
+                };
+            }
+            ("IFERROR", 2) => {
+                let value = self.scalar(&args[0])?;
+                return match value {
+                    Value::Error(_) => self.scalar(&args[1]),
+                    value => Ok(value),
+                };
+            }
+            _ => {}
+        }
+
+        let args = args.iter().map(|arg| self.expr(arg)).collect::<Eval<Vec<Arg>>>()?;
+        Ok(match (name, args.len()) {
+            // COUNT no falla con errores: solo cuenta los números
+            ("COUNT", _) => Value::Number(
+                args.iter()
+                    .map(|arg| match arg {
+                        Arg::Value(value) => usize::from(value.number().is_ok() && *value != Value::Empty),
+                        Arg::Range(rows) => rows.iter().flatten().filter(|value| matches!(value, Value::Number(_))).count(),
+                    })
+                    .sum::<usize>() as f64,
+            ),
+            ("SUM" | "AVERAGE" | "MIN" | "MAX", _) if !args.is_empty() => {
+                let numbers = excel_try!(collect_numbers(&args));
+                match name {
+                    "SUM" => Value::Number(numbers.iter().sum()),
+                    _ if numbers.is_empty() && name == "AVERAGE" => Value::Error("#DIV/0!"),
+                    _ if numbers.is_empty() => Value::Number(0.0),
+                    "AVERAGE" => Value::Number(numbers.iter().sum::<f64>() / numbers.len() as f64),
+                    "MIN" => Value::Number(numbers.iter().copied().fold(f64::INFINITY, f64::min)),
+                    _ => Value::Number(numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+                }
+            }
+            ("COUNTA", _) => Value::Number(
+                args.iter()
+                    .flat_map(Arg::values)
+                    .filter(|v| !matches!(v, Value::Empty))
+                    .count() as f64,
+            ),
+            ("AND" | "OR", _) if !args.is_empty() => {
+                let mut flags = Vec::new();
+                for arg in &args {
+                    for value in arg.values() {
+                        if !matches!(value, Value::Empty) {
+                            flags.push(excel_try!(value.truthy()));
+                        }
+                    }
+                }
+                Value::Bool(if name == "AND" { flags.iter().all(|f| *f) } else { flags.iter().any(|f| *f) })
+            }
+            ("NOT", 1) => Value::Bool(!excel_try!(args.into_iter().next().map(Arg::scalar).unwrap_or(Value::Empty).truthy())),
+            ("ABS", 1) => numeric(args.into_iter().next().map(Arg::scalar).unwrap_or(Value::Empty).number().map(f64::abs)),
+            ("ROUND", 2) => {
+                let mut args = args.into_iter().map(Arg::scalar);
+                let value = excel_try!(args.next().unwrap_or(Value::Empty).number());
+                let digits = excel_try!(args.next().unwrap_or(Value::Empty).number()).trunc() as i32;
+                let factor = 10f64.powi(digits);
+                Value::Number((value * factor).round() / factor)
+            }
+            ("CONCATENATE" | "CONCAT", _) => {
+                let mut text = String::new();
+                for arg in &args {
+                    for value in arg.values() {
+                        text.push_str(&excel_try!(value.text()));
+                    }
+                }
+                Value::Text(text)
+            }
+            ("VLOOKUP", 3 | 4) => {
+                let mut args = args.into_iter();
+                let key = args.next().map(Arg::scalar).unwrap_or(Value::Empty);
+                let Some(Arg::Range(table)) = args.next() else { return Ok(Value::Error("#VALUE!")) };
+                let column = excel_try!(args.next().map(Arg::scalar).unwrap_or(Value::Empty).number()) as usize;
+                let approximate = match args.next() {
+                    Some(arg) => excel_try!(arg.scalar().truthy()),
+                    None => true,
+                };
+                vlookup(&key, &table, column, approximate)
+            }
+            ("SUMIF" | "COUNTIF", _) if (2..=3).contains(&args.len()) && (name == "SUMIF" || args.len() == 2) => {
+                let mut args = args.into_iter();
+                let Some(Arg::Range(criteria_range)) = args.next() else { return Ok(Value::Error("#VALUE!")) };
+                let criteria = args.next().map(Arg::scalar).unwrap_or(Value::Empty);
+                let sum_range = match args.next() {
+                    Some(Arg::Range(rows)) => rows,
+                    Some(Arg::Value(_)) => return Ok(Value::Error("#VALUE!")),
+                    None => criteria_range.clone(),
+                };
+                let (op, target) = split_criteria(&criteria);
+                let mut count = 0.0;
+                let mut sum = 0.0;
+                for (row_idx, row) in criteria_range.iter().enumerate() {
+                    for (col_idx, value) in row.iter().enumerate() {
+                        if compare(op, value, &target) || (op == "=" && wildcard_eq(value, &target)) {
+                            count += 1.0;
+                            if let Some(Value::Number(n)) = sum_range.get(row_idx).and_then(|r| r.get(col_idx)) {
+                                sum += n;
+                            }
+                        }
+                    }
+                }
+                Value::Number(if name == "SUMIF" { sum } else { count })
+            }
+            _ => return Err(Unsupported),
+        })
+    }
+}
+
+fn numeric(result: std::result::Result<f64, &'static str>) -> Value {
+    match result {
+        Ok(n) if n.is_finite() => Value::Number(n),
+        Ok(_) => Value::Error("#NUM!"),
+        Err(code) => Value::Error(code),
+    }
+}
+
+fn binary(op: char, left: &Value, right: &Value) -> Value {
+    if op == '&' {
+        return match (left.text(), right.text()) {
+            (Ok(a), Ok(b)) => Value::Text(a + &b),
+            (Err(code), _) | (_, Err(code)) => Value::Error(code),
+        };
+    }
+    let (a, b) = match (left.number(), right.number()) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(code), _) | (_, Err(code)) => return Value::Error(code),
+    };
+    match op {
+        '+' => numeric(Ok(a + b)),
+        '-' => numeric(Ok(a - b)),
+        '*' => numeric(Ok(a * b)),
+        '/' if b == 0.0 => Value::Error("#DIV/0!"),
+        '/' => numeric(Ok(a / b)),
+        _ => numeric(Ok(a.powf(b))),
+    }
+}
+
+// Código de un error leído de una celda; los desconocidos, como #VALUE!
+fn error_code(code: &str) -> &'static str {
+    const CODES: [&str; 9] = ["#NULL!", "#DIV/0!", "#VALUE!", "#REF!", "#NAME?", "#NUM!", "#N/A", "#SPILL!", "#CALC!"];
+    CODES.into_iter().find(|known| known.eq_ignore_ascii_case(code.trim())).unwrap_or("#VALUE!")
+}
+
+// Números de los argumentos: en rangos se ignoran textos y vacíos, como en Excel
+fn collect_numbers(args: &[Arg]) -> std::result::Result<Vec<f64>, &'static str> {
+    let mut numbers = Vec::new();
+    for arg in args {
+        match arg {
+            Arg::Value(Value::Empty) => {}
+            Arg::Value(value) => numbers.push(value.number()?),
+            Arg::Range(rows) => {
+                for value in rows.iter().flatten() {
+                    match value {
+                        Value::Number(n) => numbers.push(*n),
+                        Value::Error(code) => return Err(code),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+// Orden de Excel al comparar: números < textos < lógicos; los textos sin distinguir mayúsculas
+fn compare(op: &str, left: &Value, right: &Value) -> bool {
+    let rank = |v: &Value| match v {
+        Value::Number(_) | Value::Empty => 0,
+        Value::Text(_) => 1,
+        Value::Bool(_) => 2,
+        Value::Error(_) => 3,
+    };
+    let normalize = |v: &Value, other: &Value| match (v, other) {
+        (Value::Empty, Value::Text(_)) => Value::Text(String::new()),
+        (Value::Empty, Value::Bool(_)) => Value::Bool(false),
+        (Value::Empty, _) => Value::Number(0.0),
+        _ => v.clone(),
+    };
+    let (a, b) = (normalize(left, right), normalize(right, left));
+    let ordering = match (&a, &b) {
+        (Value::Number(x), Value::Number(y)) => x.partial_cmp(y),
+        (Value::Text(x), Value::Text(y)) => Some(x.to_lowercase().cmp(&y.to_lowercase())),
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        _ => Some(rank(&a).cmp(&rank(&b))),
+    };
+    let Some(ordering) = ordering else { return false };
+    match op {
+        "=" => ordering.is_eq(),
+        "<>" => ordering.is_ne(),
+        "<" => ordering.is_lt(),
+        ">" => ordering.is_gt(),
+        "<=" => ordering.is_le(),
+        _ => ordering.is_ge(),
+    }
+}
+
+// Criterio de SUMIF/COUNTIF: ">10", "<>x", "=abc" o un valor a igualar
+fn split_criteria(criteria: &Value) -> (&'static str, Value) {
+    let Value::Text(text) = criteria else { return ("=", criteria.clone()) };
+    let (op, rest) = ["<>", "<=", ">=", "=", "<", ">"]
+        .into_iter()
+        .find_map(|op| text.strip_prefix(op).map(|rest| (op, rest)))
+        .unwrap_or(("=", text.as_str()));
+    let target = match rest.trim().parse::<f64>() {
+        Ok(n) => Value::Number(n),
+        Err(_) => Value::Text(rest.to_string()),
+    };
+    (op, target)
+}
+
+// Igualdad con comodines * y ? en criterios de texto
+fn wildcard_eq(value: &Value, target: &Value) -> bool {
+    match (value, target) {
+        (Value::Text(text), Value::Text(pattern)) if pattern.contains(['*', '?']) => {
+            crate::files::wildcard_match(pattern, text)
+        }
+        _ => false,
+    }
+}
+
+fn vlookup(key: &Value, table: &[Vec<Value>], column: usize, approximate: bool) -> Value {
+    if column == 0 || table.first().is_some_and(|row| column > row.len()) {
+        return Value::Error("#REF!");
+    }
+    let row = if approximate {
+        // Tabla ordenada: la última fila cuya clave no supera la buscada
+        table
+            .iter()
+            .take_while(|row| row.first().is_some_and(|first| compare("<=", first, key)))
+            .last()
+    } else {
+        table.iter().find(|row| {
+            row.first()
+                .is_some_and(|first| compare("=", first, key) || wildcard_eq(first, key))
+        })
+    };
+    match row.and_then(|row| row.get(column - 1)) {
+        Some(value) => value.clone(),
+        None => Value::Error("#N/A"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::excel::SheetData;
+
+    // Hoja "Datos" con A1:C4 = (Importe, Cliente, Nota), 10/20/30 y una fórmula en E1
+    fn data() -> WorkbookData {
+        let mut sheet = SheetData::new("Datos");
+        let text = |s: &str| CellValue::Text(s.to_string());
+        sheet.rows = vec![
+            vec![text("Importe"), text("Cliente"), text("Nota")],
+            vec![CellValue::Number(10.0), text("Ana"), text("5")],
+            vec![CellValue::Number(20.0), text("Luis"), CellValue::Bool(true)],
+            vec![CellValue::Number(30.0), text("ana"), CellValue::Error("#N/A".to_string())],
+        ];
+        WorkbookData { sheets: vec![sheet, SheetData::new("Otra hoja")] }
+    }
+
+    fn eval(formula: &str) -> CellValue {
+        let mut data = data();
+        let formulas = HashMap::from([((0, 0, 4), formula.to_string())]);
+        let report = evaluate(&mut data, &formulas);
+        assert_eq!(report.unsupported, 0, "{} no se pudo evaluar", formula);
+        data.sheets[0].rows[0][4].clone()
+    }
+
+    fn number(formula: &str) -> f64 {
+        match eval(formula) {
+            CellValue::Number(n) => n,
+            other => panic!("{} dio {:?}", formula, other),
+        }
+    }
+
+    #[test]
+    fn operators_follow_excel_precedence() {
+        assert_eq!(number("2+3*4"), 14.0);
+        assert_eq!(number("(2+3)*4"), 20.0);
+        assert_eq!(number("2^3^2"), 64.0);
+        assert_eq!(number("-2^2"), 4.0);
+        assert_eq!(number("10-4-3"), 3.0);
+        assert_eq!(number("50%*A2"), 5.0);
+        assert_eq!(eval("1+1&\"x\""), CellValue::Text("2x".to_string()));
+        assert_eq!(eval("1+2=3"), CellValue::Bool(true));
+    }
+
+    #[test]
+    fn ranges_and_references() {
+        assert_eq!(number("SUM(A2:A4)"), 60.0);
+        assert_eq!(number("SUM(A4:A2)"), 60.0);
+        assert_eq!(number("$A$2*2"), 20.0);
+        assert_eq!(number("Datos!A3+'Otra hoja'!A1"), 20.0);
+        assert_eq!(number("COUNTA(A1:C2)"), 6.0);
+        assert_eq!(number("SUMIF(B2:B4,\"ana\",A2:A4)"), 40.0);
+        assert_eq!(eval("VLOOKUP(\"Luis\",B2:C4,1,FALSE)"), CellValue::Text("Luis".to_string()));
+    }
+
+    #[test]
+    fn whole_columns_stop_at_the_last_row() {
+        assert_eq!(number("AVERAGE(A:A)"), 20.0);
+        assert_eq!(number("SUM($A:$A)"), 60.0);
+        assert_eq!(number("COUNT(A:C)"), 3.0);
+        assert_eq!(number("COUNTIF(B:B,\"a*\")"), 2.0);
+    }
+
+    #[test]
+    fn text_and_numbers_are_coerced_like_excel() {
+        assert_eq!(number("\"2\"+3"), 5.0);
+        assert_eq!(number("TRUE+1"), 2.0);
+        assert_eq!(number("C2*2"), 10.0);
+        // En un rango los textos se ignoran; como argumento suelto se convierten
+        assert_eq!(number("SUM(C2:C3)"), 0.0);
+        assert_eq!(number("SUM(\"5\",1)"), 6.0);
+        assert_eq!(eval("A2&\"€\""), CellValue::Text("10€".to_string()));
+        assert_eq!(eval("B2=\"ANA\""), CellValue::Bool(true));
+        assert_eq!(eval("1<\"a\""), CellValue::Bool(true));
+    }
+
+    #[test]
+    fn errors_propagate_as_values() {
+        assert_eq!(eval("1/0"), CellValue::Error("#DIV/0!".to_string()));
+        assert_eq!(eval("\"a\"+1"), CellValue::Error("#VALUE!".to_string()));
+        assert_eq!(eval("SUM(C2:C4)"), CellValue::Error("#N/A".to_string()));
+        assert_eq!(eval("C4"), CellValue::Error("#N/A".to_string()));
+        assert_eq!(number("COUNT(C2:C4,1,\"x\")"), 1.0);
+        assert_eq!(eval("AVERAGE(B2:B4)"), CellValue::Error("#DIV/0!".to_string()));
+        assert_eq!(eval("IFERROR(1/0,\"sin dato\")"), CellValue::Text("sin dato".to_string()));
+        assert_eq!(eval("IF(A2>15,\"alto\",\"bajo\")"), CellValue::Text("bajo".to_string()));
+    }
+
+    #[test]
+    fn unknown_functions_keep_the_stored_value() {
+        let mut data = data();
+        let formulas = HashMap::from([((0, 1, 0), "XLOOKUP(1,B:B,A:A)".to_string()), ((0, 2, 0), "A1:".to_string())]);
+        let report = evaluate(&mut data, &formulas);
+        assert_eq!(report.unsupported, 2);
+        assert_eq!(data.sheets[0].rows[1][0], CellValue::Number(10.0));
+    }
+
+    #[test]
+    fn circular_references_are_errors() {
+        let mut data = data();
+        let formulas = HashMap::from([((0, 0, 4), "F1+1".to_string()), ((0, 0, 5), "E1+1".to_string())]);
+        evaluate(&mut data, &formulas);
+        assert_eq!(data.sheets[0].rows[0][4], CellValue::Error("#REF!".to_string()));
+    }
+}
@@ -0,0 +1,779 @@
+// Fórmulas que compone el modelo (`escribir_formula`): antes de guardarlas se
+// comprueba la sintaxis (paréntesis y comillas cerradas, operadores con sus dos
+// valores), que las referencias A1 existan y apunten a hojas del libro y que las
+// funciones sean de Excel. El error dice qué corregir y dónde, para que el modelo
+// lo repita bien en lugar de dejar un #¿NOMBRE? en el libro. Con un rango como
+// destino la fórmula se rellena como al arrastrarla en Excel.
+use crate::excel::{self, CellValue};
+use crate::formula;
+use crate::limits;
+use crate::named_ranges::{self, Target};
+use crate::suggest::levenshtein;
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+// Límites de una hoja de Excel (XFD1048576)
+const MAX_ROWS: usize = 1_048_576;
+const MAX_COLS: usize = 16_384;
+
+// Funciones que se aceptan, por su nombre en inglés (el que se guarda en el archivo)
+const FUNCTIONS: &[&str] = &[
+    "ABS", "ACOS", "ADDRESS", "AGGREGATE", "AND", "ASIN", "ATAN", "ATAN2", "AVERAGE", "AVERAGEA", "AVERAGEIF",
+    "AVERAGEIFS", "BYROW", "BYCOL", "CEILING", "CEILING.MATH", "CELL", "CHAR", "CHOOSE", "CHOOSECOLS",
+    "CHOOSEROWS", "CLEAN", "CODE", "COLUMN", "COLUMNS", "CONCAT", "CONCATENATE", "CORREL", "COS", "COUNT",
+    "COUNTA", "COUNTBLANK", "COUNTIF", "COUNTIFS", "COVARIANCE.S", "DATE", "DATEDIF", "DATEVALUE", "DAY", "DAYS",
+    "DEGREES", "DROP", "EDATE", "EOMONTH", "EXACT", "EXP", "EXPAND", "FALSE", "FILTER", "FIND", "FIXED", "FLOOR",
+    "FLOOR.MATH", "FORECAST", "FORECAST.LINEAR", "FV", "GEOMEAN", "HLOOKUP", "HOUR", "HSTACK", "HYPERLINK", "IF",
+    "IFERROR", "IFNA", "IFS", "INDEX", "INDIRECT", "INT", "IPMT", "IRR", "ISBLANK", "ISERR", "ISERROR", "ISEVEN",
+    "ISFORMULA", "ISLOGICAL", "ISNA", "ISNONTEXT", "ISNUMBER", "ISODD", "ISOWEEKNUM", "ISTEXT", "LAMBDA", "LARGE",
+    "LEFT", "LEN", "LET", "LN", "LOG", "LOG10", "LOOKUP", "LOWER", "MAP", "MATCH", "MAX", "MAXA", "MAXIFS",
+    "MEDIAN", "MID", "MIN", "MINA", "MINIFS", "MINUTE", "MOD", "MODE", "MODE.SNGL", "MONTH", "MROUND",
+    "NETWORKDAYS", "NETWORKDAYS.INTL", "NOT", "NOW", "NPER", "NPV", "NUMBERVALUE", "OFFSET", "OR", "PERCENTILE",
+    "PERCENTILE.INC", "PERCENTRANK", "PI", "PMT", "POWER", "PPMT", "PRODUCT", "PROPER", "PV", "QUARTILE",
+    "QUARTILE.INC", "RADIANS", "RAND", "RANDARRAY", "RANDBETWEEN", "RANK", "RANK.AVG", "RANK.EQ", "RATE",
+    "REDUCE", "REPLACE", "REPT", "RIGHT", "ROUND", "ROUNDDOWN", "ROUNDUP", "ROW", "ROWS", "SCAN", "SEARCH",
+    "SECOND", "SEQUENCE", "SIGN", "SIN", "SLOPE", "SMALL", "SORT", "SORTBY", "SQRT", "STDEV", "STDEV.P", "STDEV.S",
+    "SUBSTITUTE", "SUBTOTAL", "SUM", "SUMIF", "SUMIFS", "SUMPRODUCT", "SWITCH", "T", "TAKE", "TAN", "TEXT",
+    "TEXTAFTER", "TEXTBEFORE", "TEXTJOIN", "TEXTSPLIT", "TIME", "TIMEVALUE", "TOCOL", "TODAY", "TOROW",
+    "TRANSPOSE", "TREND", "TRIM", "TRUE", "TRUNC", "TYPE", "UNIQUE", "UPPER", "VALUE", "VAR", "VAR.P", "VAR.S",
+    "VLOOKUP", "VSTACK", "WEEKDAY", "WEEKNUM", "WORKDAY", "WORKDAY.INTL", "WRAPCOLS", "WRAPROWS", "XIRR",
+    "XLOOKUP", "XMATCH", "XNPV", "YEAR", "YEARFRAC",
+];
+
+// Nombres de la versión española de Excel, que el archivo no entiende
+const SPANISH_FUNCTIONS: &[(&str, &str)] = &[
+    ("AHORA", "NOW"),
+    ("AÑO", "YEAR"),
+    ("BUSCAR", "LOOKUP"),
+    ("BUSCARH", "HLOOKUP"),
+    ("BUSCARV", "VLOOKUP"),
+    ("BUSCARX", "XLOOKUP"),
+    ("COINCIDIR", "MATCH"),
+    ("COLUMNA", "COLUMN"),
+    ("CONCATENAR", "CONCATENATE"),
+    ("CONTAR", "COUNT"),
+    ("CONTAR.BLANCO", "COUNTBLANK"),
+    ("CONTAR.SI", "COUNTIF"),
+    ("CONTAR.SI.CONJUNTO", "COUNTIFS"),
+    ("CONTARA", "COUNTA"),
+    ("DERECHA", "RIGHT"),
+    ("DIA", "DAY"),
+    ("DIAS", "DAYS"),
+    ("ELEGIR", "CHOOSE"),
+    ("ENCONTRAR", "FIND"),
+    ("ENTERO", "INT"),
+    ("ESBLANCO", "ISBLANK"),
+    ("ESERROR", "ISERROR"),
+    ("ESNUMERO", "ISNUMBER"),
+    ("ESPACIOS", "TRIM"),
+    ("ESTEXTO", "ISTEXT"),
+    ("EXTRAE", "MID"),
+    ("FECHA", "DATE"),
+    ("FILA", "ROW"),
+    ("FILTRAR", "FILTER"),
+    ("HOY", "TODAY"),
+    ("INDICE", "INDEX"),
+    ("IZQUIERDA", "LEFT"),
+    ("LARGO", "LEN"),
+    ("MAYUSC", "UPPER"),
+    ("MES", "MONTH"),
+    ("MINUSC", "LOWER"),
+    ("NO", "NOT"),
+    ("O", "OR"),
+    ("ORDENAR", "SORT"),
+    ("POTENCIA", "POWER"),
+    ("PROMEDIO", "AVERAGE"),
+    ("PROMEDIO.SI", "AVERAGEIF"),
+    ("PROMEDIO.SI.CONJUNTO", "AVERAGEIFS"),
+    ("PRODUCTO", "PRODUCT"),
+    ("RAIZ", "SQRT"),
+    ("REDONDEAR", "ROUND"),
+    ("REDONDEAR.MAS", "ROUNDUP"),
+    ("REDONDEAR.MENOS", "ROUNDDOWN"),
+    ("RESIDUO", "MOD"),
+    ("SI", "IF"),
+    ("SI.CONJUNTO", "IFS"),
+    ("SI.ERROR", "IFERROR"),
+    ("SUMA", "SUM"),
+    ("SUMAPRODUCTO", "SUMPRODUCT"),
+    ("SUMAR.SI", "SUMIF"),
+    ("SUMAR.SI.CONJUNTO", "SUMIFS"),
+    ("SUSTITUIR", "SUBSTITUTE"),
+    ("TEXTO", "TEXT"),
+    ("UNICOS", "UNIQUE"),
+    ("VALOR", "VALUE"),
+    ("Y", "AND"),
+];
+
+const ERROR_VALUES: &[&str] = &["#N/A", "#DIV/0!", "#VALUE!", "#REF!", "#NAME?", "#NUM!", "#NULL!"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    // Número, texto, TRUE/FALSE, valor de error o constante matricial {1,2;3,4}
+    Literal,
+    // Referencia (A1, $B$2:C9, A:A, 3:3); `start` apunta a la referencia, detrás
+    // del "Hoja!" si lo lleva
+    Reference { start: usize },
+    // Nombre definido del libro
+    Name,
+    // Nombre de función, con el '(' que la abre
+    Function,
+    Open,
+    Close,
+    Comma,
+    Operator(&'static str),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    // Posición en bytes, para reescribir referencias al rellenar
+    start: usize,
+    end: usize,
+}
+
+// Posición para los mensajes: carácter desde 1
+fn position(text: &str, byte: usize) -> usize {
+    text[..byte].chars().count() + 1
+}
+
+fn excerpt(text: &str, byte: usize) -> String {
+    let mut excerpt: String = text[byte..].chars().take(15).collect();
+    if text[byte..].chars().count() > 15 {
+        excerpt.push('…');
+    }
+    excerpt
+}
+
+fn tokenize(text: &str, sheets: &[String], names: &[String]) -> Result<Vec<Token>> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    let word_end = |from: usize| {
+        text[from..]
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '$' | '\\')))
+            .map_or(text.len(), |end| from + end)
+    };
+    while pos < text.len() {
+        let c = text[pos..].chars().next().unwrap_or_default();
+        let start = pos;
+        let kind = match c {
+            _ if c.is_whitespace() => {
+                pos += c.len_utf8();
+                continue;
+            }
+            '"' => {
+                pos += 1;
+                loop {
+                    match text[pos..].find('"') {
+                        Some(quote) if bytes.get(pos + quote + 1) == Some(&b'"') => pos += quote + 2,
+                        Some(quote) => {
+                            pos += quote + 1;
+                            break;
+                        }
+                        None => bail!(
+                            "Falta cerrar el texto que empieza en la posición {} ({}); dentro de un texto las comillas se escriben \"\"",
+                            position(text, start),
+                            excerpt(text, start)
+                        ),
+                    }
+                }
+                Kind::Literal
+            }
+            '{' => {
+                let Some(close) = text[pos..].find('}') else {
+                    bail!("Falta cerrar con '}}' la matriz que empieza en la posición {}", position(text, start));
+                };
+                pos += close + 1;
+                Kind::Literal
+            }
+            '#' => {
+                let Some(error) = ERROR_VALUES.iter().find(|e| text[pos..].to_uppercase().starts_with(*e)) else {
+                    bail!(
+                        "Valor de error no válido en la posición {} ({}); los de Excel son {}",
+                        position(text, start),
+                        excerpt(text, start),
+                        ERROR_VALUES.join(" ")
+                    );
+                };
+                pos += error.len();
+                Kind::Literal
+            }
+            '\'' => {
+                pos += 1;
+                loop {
+                    match text[pos..].find('\'') {
+                        Some(quote) if bytes.get(pos + quote + 1) == Some(&b'\'') => pos += quote + 2,
+                        Some(quote) => {
+                            pos += quote + 1;
+                            break;
+                        }
+                        None => bail!(
+                            "Falta cerrar la comilla del nombre de hoja que empieza en la posición {}",
+                            position(text, start)
+                        ),
+                    }
+                }
+                if bytes.get(pos) != Some(&b'!') {
+                    bail!(
+                        "Detrás de la hoja {} falta '!' y la celda (p. ej. {}!A1)",
+                        &text[start..pos],
+                        &text[start..pos]
+                    );
+                }
+                let sheet = text[start + 1..pos - 1].replace("''", "'");
+                pos += 1;
+                let reference = pos;
+                pos = range_end(text, pos, word_end);
+                check_sheet_reference(text, &sheet, reference, pos, sheets, names)?;
+                Kind::Reference { start: reference }
+            }
+            _ if c.is_ascii_digit() || (c == '.' && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit)) => {
+                let end = word_end(pos);
+                // Filas enteras: 3:3
+                if bytes.get(end) == Some(&b':') {
+                    pos = range_end(text, pos, word_end);
+                    check_reference(text, start, pos)?;
+                    Kind::Reference { start }
+                } else {
+                    let mut end = pos;
+                    while bytes.get(end).is_some_and(|b| b.is_ascii_digit() || *b == b'.') {
+                        end += 1;
+                    }
+                    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+                        let mut exponent = end + 1;
+                        if matches!(bytes.get(exponent), Some(b'+' | b'-')) {
+                            exponent += 1;
+                        }
+                        if bytes.get(exponent).is_some_and(u8::is_ascii_digit) {
+                            end = exponent;
+                            while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+                                end += 1;
+                            }
+                        }
+                    }
+                    if text[pos..end].parse::<f64>().is_err() {
+                        bail!("Número no válido en la posición {} ({})", position(text, start), &text[pos..end]);
+                    }
+                    if word_end(end) > end {
+                        bail!(
+                            "'{}' en la posición {} no es un número ni una referencia",
+                            &text[start..word_end(end)],
+                            position(text, start)
+                        );
+                    }
+                    pos = end;
+                    Kind::Literal
+                }
+            }
+            _ if c.is_alphabetic() || matches!(c, '_' | '$' | '\\') => {
+                let end = word_end(pos);
+                let word = &text[pos..end];
+                match bytes.get(end) {
+                    Some(b'(') => {
+                        pos = end + 1;
+                        check_function(text, start, word)?;
+                        Kind::Function
+                    }
+                    Some(b'!') => {
+                        let reference = end + 1;
+                        pos = range_end(text, reference, word_end);
+                        check_sheet_reference(text, word, reference, pos, sheets, names)?;
+                        Kind::Reference { start: reference }
+                    }
+                    _ if word.eq_ignore_ascii_case("TRUE") || word.eq_ignore_ascii_case("FALSE") => {
+                        pos = end;
+                        Kind::Literal
+                    }
+                    _ if word.eq_ignore_ascii_case("VERDADERO") || word.eq_ignore_ascii_case("FALSO") => bail!(
+                        "{} es el nombre en español; en la fórmula se escribe {}",
+                        word,
+                        if word.eq_ignore_ascii_case("FALSO") { "FALSE" } else { "TRUE" }
+                    ),
+                    _ => {
+                        pos = range_end(text, pos, word_end);
+                        if pos == end && names.iter().any(|n| n.eq_ignore_ascii_case(word)) {
+                            Kind::Name
+                        } else if pos == end && !matches!(split_reference(word), Some((RefPart::Cell(..), _))) {
+                            let hint = match SPANISH_FUNCTIONS.iter().find(|(es, _)| es.eq_ignore_ascii_case(word)) {
+                                Some((_, en)) => format!("; si es una función, escríbela con paréntesis y en inglés: {}(…)", en),
+                                None if names.is_empty() => String::new(),
+                                None => format!(" (nombres definidos: {})", names.join(", ")),
+                            };
+                            bail!(
+                                "'{}' en la posición {} no es una referencia de celda ni un nombre definido del libro; los textos van entre comillas dobles{}",
+                                word,
+                                position(text, start),
+                                hint
+                            );
+                        } else {
+                            check_reference(text, start, pos)?;
+                            Kind::Reference { start }
+                        }
+                    }
+                }
+            }
+            '(' => {
+                pos += 1;
+                Kind::Open
+            }
+            ')' => {
+                pos += 1;
+                Kind::Close
+            }
+            ',' => {
+                pos += 1;
+                Kind::Comma
+            }
+            ';' => bail!(
+                "Usa ',' para separar argumentos (posición {}); ';' es el separador de Excel en español, pero el archivo guarda ','",
+                position(text, start)
+            ),
+            _ => {
+                let operator = ["<>", "<=", ">=", "+", "-", "*", "/", "^", "&", "%", "=", "<", ">"]
+                    .into_iter()
+                    .find(|op| text[pos..].starts_with(op));
+                let Some(operator) = operator else {
+                    bail!("Carácter no válido '{}' en la posición {}", c, position(text, start));
+                };
+                pos += operator.len();
+                Kind::Operator(operator)
+            }
+        };
+        tokens.push(Token { kind, start, end: pos });
+    }
+    Ok(tokens)
+}
+
+// Fin de una referencia que empieza en `from`, con su segunda mitad si es un rango (A1:B9)
+fn range_end(text: &str, from: usize, word_end: impl Fn(usize) -> usize) -> usize {
+    let end = word_end(from);
+    if text.as_bytes().get(end) == Some(&b':') {
+        word_end(end + 1)
+    } else {
+        end
+    }
+}
+
+// Una mitad de referencia: celda (fila, columna), columna entera o fila entera
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RefPart {
+    Cell(usize, usize),
+    Column(usize),
+    Row(usize),
+}
+
+fn parse_part(part: &str) -> Option<RefPart> {
+    let plain = part.replace('$', "");
+    if plain.is_empty() {
+        return None;
+    }
+    if plain.chars().all(|c| c.is_ascii_alphabetic()) {
+        return excel::column_from_letters(&plain).filter(|col| *col < MAX_COLS).map(RefPart::Column);
+    }
+    if plain.chars().all(|c| c.is_ascii_digit()) {
+        return plain.parse::<usize>().ok().filter(|row| (1..=MAX_ROWS).contains(row)).map(|row| RefPart::Row(row - 1));
+    }
+    let (row, col) = excel::parse_cell_ref(&plain)?;
+    (row < MAX_ROWS && col < MAX_COLS).then_some(RefPart::Cell(row, col))
+}
+
+// Las dos mitades de A1:B9 (la misma dos veces si es una celda), si son coherentes
+fn split_reference(reference: &str) -> Option<(RefPart, RefPart)> {
+    let (first, second) = reference.split_once(':').unwrap_or((reference, reference));
+    let (first, second) = (parse_part(first)?, parse_part(second)?);
+    match (first, second) {
+        (RefPart::Cell(..), RefPart::Cell(..)) | (RefPart::Column(_), RefPart::Column(_)) | (RefPart::Row(_), RefPart::Row(_)) => {
+            Some((first, second))
+        }
+        _ => None,
+    }
+}
+
+fn check_reference(text: &str, start: usize, end: usize) -> Result<()> {
+    let reference = &text[start..end];
+    let single = !reference.contains(':');
+    match split_reference(reference) {
+        Some((RefPart::Column(_), _) | (RefPart::Row(_), _)) if single => bail!(
+            "'{}' en la posición {} no es una celda; una columna o fila entera se escribe como rango (A:A, 3:3)",
+            reference,
+            position(text, start)
+        ),
+        Some(_) => Ok(()),
+        None => bail!(
+            "Referencia no válida '{}' en la posición {}; usa celdas como B2 o $B$2, rangos como A1:C9 (hasta XFD1048576), columnas como A:A o filas como 3:3",
+            reference,
+            position(text, start)
+        ),
+    }
+}
+
+fn check_sheet_reference(text: &str, sheet: &str, start: usize, end: usize, sheets: &[String], names: &[String]) -> Result<()> {
+    if !sheets.iter().any(|s| s.eq_ignore_ascii_case(sheet)) {
+        bail!(
+            "La hoja '{}' no existe en el libro (hojas: {}); los nombres con espacios van entre comillas simples: 'Mi hoja'!A1",
+            sheet,
+            sheets.join(", ")
+        );
+    }
+    if start == end {
+        bail!("Falta la celda detrás de {}! en la posición {}", sheet, position(text, start));
+    }
+    if names.iter().any(|n| n.eq_ignore_ascii_case(&text[start..end])) {
+        return Ok(());
+    }
+    check_reference(text, start, end)
+}
+
+// Error si no es una función de Excel, con el nombre correcto si se parece a una
+fn check_function(text: &str, start: usize, word: &str) -> Result<()> {
+    let name = word.to_uppercase();
+    let bare = name.strip_prefix("_XLFN.").unwrap_or(&name);
+    if FUNCTIONS.contains(&bare) {
+        return Ok(());
+    }
+    if let Some((_, english)) = SPANISH_FUNCTIONS.iter().find(|(spanish, _)| *spanish == bare) {
+        bail!("{} es el nombre en español; en la fórmula se escribe {}", word, english);
+    }
+    let closest = FUNCTIONS
+        .iter()
+        .map(|f| (levenshtein(bare, f), *f))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance);
+    match closest {
+        Some((_, similar)) => bail!("Función desconocida {} en la posición {} (¿{}?)", word, position(text, start), similar),
+        None => bail!(
+            "Función desconocida {} en la posición {}; usa el nombre en inglés de una función de Excel",
+            word,
+            position(text, start)
+        ),
+    }
+}
+
+// Comprueba una fórmula (sin el '=' inicial) frente a las hojas y los nombres
+// definidos del libro; el error explica qué hay que corregir
+pub fn check(text: &str, sheets: &[String], names: &[String]) -> Result<()> {
+    if text.trim().is_empty() {
+        bail!("La fórmula está vacía");
+    }
+    let tokens = tokenize(text, sheets, names)?;
+    // Paréntesis abiertos: (posición, es de una función)
+    let mut open: Vec<(usize, bool)> = Vec::new();
+    let mut expect_value = true;
+    let mut previous: Option<&Kind> = None;
+    for token in &tokens {
+        let at = position(text, token.start);
+        match &token.kind {
+            Kind::Literal | Kind::Reference { .. } | Kind::Name | Kind::Function | Kind::Open => {
+                if !expect_value {
+                    bail!(
+                        "Falta un operador o una ',' antes de '{}' en la posición {}",
+                        excerpt(text, token.start),
+                        at
+                    );
+                }
+                match token.kind {
+                    Kind::Function => open.push((token.start, true)),
+                    Kind::Open => open.push((token.start, false)),
+                    _ => {}
+                }
+                expect_value = matches!(token.kind, Kind::Function | Kind::Open);
+            }
+            Kind::Close => {
+                let Some((_, function)) = open.pop() else {
+                    bail!("Sobra un ')' en la posición {}", at);
+                };
+                let empty_call = function && matches!(previous, Some(Kind::Function | Kind::Comma));
+                if expect_value && !empty_call {
+                    bail!("Falta un valor antes del ')' de la posición {}", at);
+                }
+                expect_value = false;
+            }
+            Kind::Comma => {
+                if !open.last().is_some_and(|(_, function)| *function) {
+                    bail!("La ',' de la posición {} solo puede separar argumentos de una función", at);
+                }
+                expect_value = true;
+            }
+            Kind::Operator("%") => {
+                if expect_value {
+                    bail!("Falta un valor antes del '%' de la posición {}", at);
+                }
+            }
+            // Signo de un valor: -A1, =+B2
+            Kind::Operator("+" | "-") if expect_value => {}
+            Kind::Operator(operator) => {
+                if expect_value {
+                    bail!("Falta un valor antes del '{}' de la posición {}", operator, at);
+                }
+                expect_value = true;
+            }
+        }
+        previous = Some(&token.kind);
+    }
+    if let Some((start, _)) = open.last() {
+        bail!(
+            "Falta{} {} ')': el último paréntesis sin cerrar está en la posición {} ({})",
+            if open.len() == 1 { "" } else { "n" },
+            open.len(),
+            position(text, *start),
+            excerpt(text, *start)
+        );
+    }
+    if expect_value {
+        bail!("La fórmula termina en un operador; falta el último valor");
+    }
+    Ok(())
+}
+
+// La fórmula copiada `rows` filas más abajo y `cols` columnas a la derecha: las
+// referencias sin $ se mueven, como al arrastrarla en Excel
+pub fn shift(text: &str, sheets: &[String], names: &[String], rows: usize, cols: usize) -> Result<String> {
+    if rows == 0 && cols == 0 {
+        return Ok(text.to_string());
+    }
+    let mut output = String::new();
+    let mut copied = 0;
+    for token in tokenize(text, sheets, names)? {
+        let Kind::Reference { start, .. } = token.kind else { continue };
+        let reference = &text[start..token.end];
+        if names.iter().any(|n| n.eq_ignore_ascii_case(reference)) {
+            continue;
+        }
+        output.push_str(&text[copied..start]);
+        let moved: Vec<String> = reference.split(':').map(|part| shift_part(part, rows, cols)).collect();
+        output.push_str(&moved.join(":"));
+        copied = token.end;
+    }
+    output.push_str(&text[copied..]);
+    Ok(output)
+}
+
+fn shift_part(part: &str, rows: usize, cols: usize) -> String {
+    let split = part.find(|c: char| c.is_ascii_digit()).unwrap_or(part.len());
+    let (letters, digits) = part.split_at(split);
+    // $ delante de las letras fija la columna; delante de los números, la fila
+    let (letters, digits) = match letters.strip_suffix('$') {
+        Some(letters) if !digits.is_empty() => (letters, format!("${}", digits)),
+        _ => (letters, digits.to_string()),
+    };
+    let column = match letters.strip_prefix('$') {
+        Some(fixed) => format!("${}", fixed),
+        None if letters.is_empty() => String::new(),
+        None => excel::column_from_letters(letters)
+            .map(|col| excel::column_letters(col + cols))
+            .unwrap_or_else(|| letters.to_string()),
+    };
+    let row = match digits.strip_prefix('$') {
+        Some(_) => digits.clone(),
+        None if digits.is_empty() => String::new(),
+        None => digits.parse::<usize>().map(|row| (row + rows).to_string()).unwrap_or(digits.clone()),
+    };
+    format!("{}{}", column, row)
+}
+
+#[derive(Debug, Clone)]
+pub struct FormulaWrite {
+    // Destino escrito, p. ej. Ventas!C2:C20
+    pub description: String,
+    pub cells: usize,
+    // Fórmula de la primera celda, sin '='
+    pub formula: String,
+    // Valor calculado de la primera celda, si el evaluador conoce sus funciones
+    pub value: Option<CellValue>,
+}
+
+// Escribe la fórmula en el destino (Hoja!C2, Hoja!C2:C20 o un nombre definido),
+// rellenándola si es un rango. No pisa celdas con datos salvo con `overwrite`.
+pub fn write_formula(file: &str, target: &str, formula_text: &str, overwrite: bool) -> Result<FormulaWrite> {
+    let text = formula_text.trim();
+    let text = text.strip_prefix('=').unwrap_or(text).trim();
+    let path = Path::new(file);
+    let mut package = XlsxPackage::open(path)?;
+    let workbook = package.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+    let sheets = named_ranges::sheet_names(&workbook);
+    let defined = named_ranges::defined_names(&workbook);
+    let names: Vec<String> = defined.iter().map(|d| d.name.clone()).collect();
+    check(text, &sheets, &names).context(format!("La fórmula ={} no es válida", text))?;
+
+    let Target {
+        sheet,
+        range,
+        description,
+        ..
+    } = named_ranges::resolve_target(&defined, target)?;
+    let count = (range.last_row - range.first_row + 1) * (range.last_col - range.first_col + 1);
+    limits::check_write_cells(count)?;
+    if !overwrite {
+        let part = package.sheet_part(&sheet)?;
+        let xml = package.read_part(&part).context(format!("Falta {}", part))?;
+        let taken: Vec<String> = xlsx_patch::filled_cells(&xml)
+            .into_iter()
+            .filter(|(row, col)| range.contains(*row, *col))
+            .map(|(row, col)| format!("{}{}", excel::column_letters(col), row + 1))
+            .collect();
+        if !taken.is_empty() {
+            let shown: Vec<&str> = taken.iter().take(5).map(String::as_str).collect();
+            bail!(
+                "{} tiene {} celda(s) con datos ({}{}); usa --sobrescribir para reemplazarlas o elige celdas vacías",
+                description,
+                taken.len(),
+                shown.join(", "),
+                if taken.len() > shown.len() { ", …" } else { "" }
+            );
+        }
+    }
+
+    let mut formulas = Vec::new();
+    for row in range.first_row..=range.last_row {
+        for col in range.first_col..=range.last_col {
+            let shifted = shift(text, &sheets, &names, row - range.first_row, col - range.first_col)?;
+            formulas.push((row, col, shifted));
+        }
+    }
+    // El valor calculado va como valor guardado (<v>), para que lo vean quienes leen
+    // el libro sin recalcularlo; las que el evaluador no sabe calcular van sin él
+    let mut values = evaluate_all(file, &sheet, &formulas);
+    let cells: Vec<(usize, usize, String)> = formulas
+        .iter()
+        .map(|(row, col, shifted)| {
+            let value = values.get(&(*row, *col)).unwrap_or(&CellValue::Empty);
+            (*row, *col, xlsx_patch::formula_content(shifted, value))
+        })
+        .collect();
+    package.write_cells(&sheet, &cells)?;
+    package.write_part("xl/workbook.xml", named_ranges::with_full_calc_on_load(&workbook));
+    package.save(path)?;
+
+    let (row, col, _) = &formulas[0];
+    Ok(FormulaWrite {
+        description,
+        cells: cells.len(),
+        formula: text.to_string(),
+        value: values.remove(&(*row, *col)),
+    })
+}
+
+// Valores de las fórmulas que se van a escribir, calculados sobre los datos del
+// libro; faltan las que usan funciones que el evaluador no conoce
+fn evaluate_all(file: &str, sheet: &str, formulas: &[(usize, usize, String)]) -> HashMap<(usize, usize), CellValue> {
+    let Ok(mut data) = excel::read_excel_file(file) else { return HashMap::new() };
+    let Some(sheet_idx) = data.sheets.iter().position(|s| s.name == sheet) else { return HashMap::new() };
+    // Se vacían antes los destinos: lo que tengan después lo ha calculado el evaluador
+    let rows = &mut data.sheets[sheet_idx].rows;
+    for (row, col, _) in formulas {
+        if let Some(cell) = rows.get_mut(*row).and_then(|cells| cells.get_mut(*col)) {
+            *cell = CellValue::Empty;
+        }
+    }
+    let pending = formulas
+        .iter()
+        .map(|(row, col, text)| ((sheet_idx, *row, *col), text.clone()))
+        .collect();
+    formula::evaluate(&mut data, &pending);
+    let rows = &data.sheets[sheet_idx].rows;
+    formulas
+        .iter()
+        .filter_map(|(row, col, _)| {
+            let value = rows.get(*row)?.get(*col)?;
+            (*value != CellValue::Empty).then(|| ((*row, *col), value.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::Reader;
+
+    fn sheets() -> Vec<String> {
+        vec!["Ventas".to_string(), "Mi hoja".to_string()]
+    }
+
+    fn error(text: &str) -> String {
+        check(text, &sheets(), &["Tipo_IVA".to_string()]).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn valid_formulas_pass() {
+        let names = ["Tipo_IVA".to_string()];
+        for text in [
+            "SUM(B2:B9)*Tipo_IVA",
+            "IF(A1>=10,\"Alto \"\"sí\"\"\",\"Bajo\")",
+            "-A1+'Mi hoja'!$B$2%",
+            "VLOOKUP(A2,Ventas!A:C,3,FALSE)",
+            "SUM(3:3)/COUNT({1,2;3,4})",
+            "_xlfn.XLOOKUP(A2,B:B,C:C)",
+            "TODAY()",
+        ] {
+            assert!(check(text, &sheets(), &names).is_ok(), "{}: {:?}", text, check(text, &sheets(), &names));
+        }
+    }
+
+    #[test]
+    fn errors_say_what_to_fix_and_where() {
+        assert!(error("SUMA(B2:B9)").contains("SUMA es el nombre en español; en la fórmula se escribe SUM"));
+        assert!(error("SUMM(B2:B9)").contains("(¿SUM?)"));
+        assert!(error("SUM(B2:B9").contains("Falta 1 ')'"));
+        assert!(error("SUM(B2;B9)").contains("Usa ',' para separar argumentos (posición 7)"));
+        assert!(error("Clientes!A1").contains("La hoja 'Clientes' no existe"));
+        assert!(error("A1+").contains("termina en un operador"));
+        assert!(error("A1 B1").contains("Falta un operador"));
+        assert!(error("Pendiente").contains("los textos van entre comillas dobles"));
+        assert!(error("SI(A1>1,1,0)").contains("SI es el nombre en español; en la fórmula se escribe IF"));
+        // Sin paréntesis parece un nombre: se sugiere la función
+        assert!(error("SI").contains("escríbela con paréntesis y en inglés: IF(…)"));
+        assert!(error("XFE1").contains("(nombres definidos: Tipo_IVA)"));
+        assert!(error("A1:3").contains("Referencia no válida 'A1:3'"));
+    }
+
+    #[test]
+    fn shifting_moves_only_relative_parts() {
+        let names = ["Tipo_IVA".to_string()];
+        let shift = |text: &str, rows: usize, cols: usize| super::shift(text, &sheets(), &names, rows, cols).unwrap();
+        assert_eq!(shift("B2*C2", 2, 0), "B4*C4");
+        assert_eq!(shift("$B2*C$2+$D$1", 1, 1), "$B3*D$2+$D$1");
+        assert_eq!(shift("SUM(Ventas!A1:A9)*Tipo_IVA", 1, 0), "SUM(Ventas!A2:A10)*Tipo_IVA");
+        assert_eq!(shift("'Mi hoja'!Z1&\"A1\"", 0, 1), "'Mi hoja'!AA1&\"A1\"");
+        assert_eq!(shift("SUM(A:A)", 0, 2), "SUM(C:C)");
+    }
+
+    #[test]
+    fn a_formula_is_filled_down_the_target_range_without_replacing_data() {
+        let dir = crate::paths::test_dir("a_formula_is_filled_down_the_target_range_without_replacing_data");
+        let file = dir.join("ventas.xlsx");
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Ventas").unwrap();
+        sheet.write_string(0, 0, "Precio").unwrap();
+        sheet.write_string(0, 1, "Unidades").unwrap();
+        for (row, (price, units)) in [(1, (2.0, 3.0)), (2, (4.0, 5.0))] {
+            sheet.write_number(row, 0, price).unwrap();
+            sheet.write_number(row, 1, units).unwrap();
+        }
+        workbook.save(&file).unwrap();
+        let file = file.display().to_string();
+
+        assert!(write_formula(&file, "Ventas!B2:B3", "=A2*2", false).is_err());
+        let written = write_formula(&file, "Ventas!C2:C3", "=A2*B2", false).unwrap();
+        assert_eq!((written.cells, written.formula.as_str()), (2, "A2*B2"));
+        assert_eq!(written.value, Some(CellValue::Number(6.0)));
+        let mut workbook: calamine::Xlsx<_> = calamine::open_workbook(&file).unwrap();
+        let stored = workbook.worksheet_formula("Ventas").unwrap().unwrap();
+        assert_eq!(stored.get_value((2, 2)).map(String::as_str), Some("A3*B3"));
+
+        // Los valores calculados quedan guardados para quien lee el libro sin recalcularlo
+        let cached = |file: &str| {
+            let data = excel::read_excel_file(file).unwrap();
+            data.sheets[0].rows[1..3].iter().map(|row| row[2].clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(cached(&file), vec![CellValue::Number(6.0), CellValue::Number(20.0)]);
+
+        let error = write_formula(&file, "Ventas!C2:C3", "=A2+1", false).unwrap_err();
+        assert!(error.to_string().contains("2 celda(s) con datos (C2, C3)"), "{}", error);
+        write_formula(&file, "Ventas!C2:C3", "=A2+1", true).unwrap();
+        assert_eq!(cached(&file), vec![CellValue::Number(3.0), CellValue::Number(5.0)]);
+    }
+}
@@ -0,0 +1,221 @@
+// Texto de las fórmulas de un libro existente (`leer_excel ... --formulas` y
+// `formulas <archivo> <hoja>`), para que el modelo explique, revise o reescriba
+// sus cálculos en lugar de ver solo los valores guardados. calamine deja vacías
+// las celdas de una fórmula compartida (una columna rellenada en Excel guarda la
+// fórmula solo en la primera); se reconstruyen desplazando la de la primera
+// celda con sus `si` del XML de la hoja.
+use crate::excel::{self, CellValue, WorkbookData};
+use crate::formula_check;
+use crate::xlsx_patch::{self, SheetXml};
+use anyhow::{Context, Result};
+use calamine::{open_workbook, Reader, Xlsx};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Grupos de fórmulas que se describen en el resumen de una hoja
+const MAX_LISTED: usize = 20;
+// Caracteres de cada fórmula en el resumen
+const LISTED_CHARS: usize = 80;
+
+// Fórmulas (sin '=') de cada hoja de `data`, por (fila, columna) desde 0; devuelve cuántas hay
+pub fn read(filename: &str, data: &mut WorkbookData) -> Result<usize> {
+    let path = Path::new(filename);
+    let mut workbook: Xlsx<_> = open_workbook(path).context(format!("No se pudo abrir el archivo {}", filename))?;
+    let parts = xlsx_patch::read_sheet_xml(path)?;
+    let sheets: Vec<String> = data.sheets.iter().map(|sheet| sheet.name.clone()).collect();
+    let mut total = 0;
+    for sheet in &mut data.sheets {
+        let part = parts.iter().find(|part| part.name == sheet.name);
+        sheet.formulas = sheet_formulas(&mut workbook, &sheet.name, part, &sheets);
+        total += sheet.formulas.len();
+    }
+    Ok(total)
+}
+
+// Fórmulas de una hoja con las compartidas ya desplazadas a cada celda
+pub fn sheet_formulas(
+    workbook: &mut Xlsx<BufReader<File>>,
+    sheet: &str,
+    part: Option<&SheetXml>,
+    sheets: &[String],
+) -> BTreeMap<(usize, usize), String> {
+    let mut formulas = BTreeMap::new();
+    let Some(Ok(range)) = workbook.worksheet_formula(sheet) else {
+        return formulas;
+    };
+    let (start_row, start_col) = range.start().unwrap_or((0, 0));
+    for (row, col, text) in range.used_cells() {
+        if !text.trim().is_empty() {
+            let key = (start_row as usize + row, start_col as usize + col);
+            formulas.insert(key, text.trim_start_matches('=').to_string());
+        }
+    }
+    let Some(part) = part else { return formulas };
+    let names: Vec<String> = workbook.defined_names().iter().map(|(name, _)| name.clone()).collect();
+    // Primera celda de cada fórmula compartida, por su `si`
+    let mut masters: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut followers = Vec::new();
+    for (tag, content) in xlsx_patch::find_elements(&part.xml, "c") {
+        let Some(cell) = xlsx_patch::xml_attr(&tag, "r").and_then(|r| excel::parse_cell_ref(&r)) else {
+            continue;
+        };
+        let Some(formula) = xlsx_patch::find_tags(&content, "f").into_iter().next() else {
+            continue;
+        };
+        if xlsx_patch::xml_attr(&formula, "t").as_deref() != Some("shared") {
+            continue;
+        }
+        let Some(index) = xlsx_patch::xml_attr(&formula, "si") else { continue };
+        if xlsx_patch::xml_attr(&formula, "ref").is_some() {
+            masters.insert(index, cell);
+        } else if !formulas.contains_key(&cell) {
+            followers.push((cell, index));
+        }
+    }
+    for ((row, col), index) in followers {
+        let Some(&(master_row, master_col)) = masters.get(&index) else { continue };
+        let Some(text) = formulas.get(&(master_row, master_col)) else { continue };
+        let (Some(rows), Some(cols)) = (row.checked_sub(master_row), col.checked_sub(master_col)) else {
+            continue;
+        };
+        if let Ok(shifted) = formula_check::shift(text, sheets, &names, rows, cols) {
+            formulas.insert((row, col), shifted);
+        }
+    }
+    formulas
+}
+
+// Filas de `formulas <archivo> <hoja>`: celda, fórmula y valor guardado
+pub fn list(filename: &str, sheet: &str) -> Result<Vec<Vec<String>>> {
+    let path = Path::new(filename);
+    let mut workbook: Xlsx<_> = open_workbook(path).context(format!("No se pudo abrir el archivo {}", filename))?;
+    let sheets = workbook.sheet_names().to_vec();
+    let Some(sheet) = sheets.iter().find(|name| name.eq_ignore_ascii_case(sheet)).cloned() else {
+        anyhow::bail!("No existe la hoja '{}' en {} (hojas: {})", sheet, filename, sheets.join(", "));
+    };
+    let parts = xlsx_patch::read_sheet_xml(path)?;
+    let formulas = sheet_formulas(&mut workbook, &sheet, parts.iter().find(|part| part.name == sheet), &sheets);
+    let values = workbook.worksheet_range(&sheet).and_then(Result::ok);
+    Ok(formulas
+        .iter()
+        .map(|(&(row, col), text)| {
+            let value = values
+                .as_ref()
+                .and_then(|range| range.get_value((row as u32, col as u32)))
+                .map(CellValue::from)
+                .unwrap_or(CellValue::Empty);
+            vec![format!("{}{}", excel::column_letters(col), row + 1), format!("={}", text), value.to_string()]
+        })
+        .collect())
+}
+
+// Fórmulas de una hoja en una línea, con las rellenadas hacia abajo juntas:
+// "C2:C6 =A2*B2 (rellenada); D7 =SUM(D2:D6)"
+pub fn describe(formulas: &BTreeMap<(usize, usize), String>) -> String {
+    // Por columnas, para que las celdas de una columna rellenada queden seguidas
+    let mut cells: Vec<(usize, usize, &str)> = formulas.iter().map(|(&(row, col), text)| (col, row, text.as_str())).collect();
+    cells.sort();
+    let mut groups: Vec<(usize, usize, usize, &str, String)> = Vec::new();
+    for (col, row, text) in cells {
+        let shape = relative_shape(text, row, col);
+        match groups.last_mut() {
+            Some((group_col, _, last, _, group_shape)) if *group_col == col && *last + 1 == row && *group_shape == shape => {
+                *last = row;
+            }
+            _ => groups.push((col, row, row, text, shape)),
+        }
+    }
+    let mut listed: Vec<String> = groups
+        .iter()
+        .take(MAX_LISTED)
+        .map(|&(col, first, last, text, _)| {
+            let letters = excel::column_letters(col);
+            if first == last {
+                format!("{}{} ={}", letters, first + 1, shorten(text))
+            } else {
+                format!("{}{}:{}{} ={} (rellenada)", letters, first + 1, letters, last + 1, shorten(text))
+            }
+        })
+        .collect();
+    if groups.len() > MAX_LISTED {
+        listed.push(format!("y {} más", groups.len() - MAX_LISTED));
+    }
+    listed.join("; ")
+}
+
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= LISTED_CHARS {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(LISTED_CHARS).collect();
+    short.push('…');
+    short
+}
+
+// La fórmula con las referencias relativas escritas como desplazamiento desde
+// su celda: dos celdas con la misma forma son la misma fórmula arrastrada
+fn relative_shape(text: &str, row: usize, col: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut shape = String::new();
+    let mut quoted = false;
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        let starts_word = idx == 0 || !(chars[idx - 1].is_alphanumeric() || chars[idx - 1] == '_' || chars[idx - 1] == '.');
+        if c == '"' {
+            quoted = !quoted;
+        }
+        if quoted || !starts_word || !(c == '$' || c.is_ascii_alphabetic()) {
+            shape.push(c);
+            idx += 1;
+            continue;
+        }
+        let (end, reference) = read_reference(&chars, idx);
+        match reference {
+            Some((fixed_col, ref_col, fixed_row, ref_row)) => {
+                let col_part = if fixed_col { format!("C{}", ref_col) } else { format!("C[{}]", ref_col as isize - col as isize) };
+                let row_part = if fixed_row { format!("R{}", ref_row) } else { format!("R[{}]", ref_row as isize - row as isize) };
+                shape.push_str(&row_part);
+                shape.push_str(&col_part);
+            }
+            None => shape.extend(&chars[idx..end]),
+        }
+        idx = end;
+    }
+    shape
+}
+
+// Referencia A1 (con $ opcionales) que empieza en `start`: (fin, (columna fija,
+// columna, fila fija, fila)). Sin referencia, el fin de la palabra.
+fn read_reference(chars: &[char], start: usize) -> (usize, Option<(bool, usize, bool, usize)>) {
+    let mut idx = start;
+    let fixed_col = chars.get(idx) == Some(&'$');
+    idx += usize::from(fixed_col);
+    let letters_start = idx;
+    while chars.get(idx).is_some_and(|c| c.is_ascii_alphabetic()) {
+        idx += 1;
+    }
+    let letters: String = chars[letters_start..idx].iter().collect();
+    let fixed_row = chars.get(idx) == Some(&'$');
+    let digits_start = idx + usize::from(fixed_row);
+    let mut end = digits_start;
+    while chars.get(end).is_some_and(|c| c.is_ascii_digit()) {
+        end += 1;
+    }
+    let word_continues = chars.get(end).is_some_and(|c| c.is_alphanumeric() || *c == '_' || *c == '(');
+    let column = excel::column_from_letters(&letters);
+    let row: Option<usize> = chars[digits_start..end].iter().collect::<String>().parse().ok();
+    match (column, row) {
+        (Some(column), Some(row)) if !word_continues => (end, Some((fixed_col, column, fixed_row, row))),
+        _ => {
+            // El resto de la palabra se copia tal cual (funciones, nombres)
+            let mut word_end = idx.max(start + 1);
+            while chars.get(word_end).is_some_and(|c| c.is_alphanumeric() || *c == '_' || *c == '.') {
+                word_end += 1;
+            }
+            (word_end, None)
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Detección de encabezados: decide si la primera fila de una hoja son los nombres
+// de las columnas o ya es una fila de datos (exportaciones sin encabezado). Se
+// compara cada celda de la primera fila con el tipo que domina en su columna: un
+// texto sobre números o fechas apunta a encabezado; un valor del mismo tipo que
+// el resto, o repetido en la columna, apunta a datos. Ante la duda se mantiene
+// la primera fila como encabezado, que es lo habitual.
+use crate::excel::CellValue;
+use std::collections::HashSet;
+
+// Filas de datos que se miran para conocer el tipo de cada columna
+pub const SAMPLE_ROWS: usize = 50;
+// Parte de las celdas de una columna que debe tener un tipo para dominarla
+const DOMINANT_SHARE: f64 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    Number,
+    Date,
+    Bool,
+}
+
+fn kind(cell: &CellValue) -> Option<Kind> {
+    match cell {
+        CellValue::Empty | CellValue::Error(_) => None,
+        CellValue::Text(text) if text.trim().is_empty() => None,
+        CellValue::Text(_) => Some(Kind::Text),
+        CellValue::Number(_) => Some(Kind::Number),
+        CellValue::DateTime(_) => Some(Kind::Date),
+        CellValue::Bool(_) => Some(Kind::Bool),
+    }
+}
+
+// `true` si la primera fila parece un encabezado
+pub fn detect(rows: &[Vec<CellValue>]) -> bool {
+    let Some((first, body)) = rows.split_first() else {
+        return true;
+    };
+    let body = &body[..body.len().min(SAMPLE_ROWS)];
+    let filled: Vec<(usize, &CellValue, Kind)> = first
+        .iter()
+        .enumerate()
+        .filter_map(|(col, cell)| kind(cell).map(|k| (col, cell, k)))
+        .collect();
+    if filled.is_empty() {
+        return true;
+    }
+    // Sin filas debajo solo cuenta el tipo: un encabezado es texto
+    if body.is_empty() {
+        return filled.iter().all(|(_, _, k)| *k == Kind::Text);
+    }
+
+    let mut score = 0i32;
+    let mut seen = HashSet::new();
+    for (col, cell, first_kind) in filled {
+        let text = cell.to_string().trim().to_lowercase();
+        // Dos columnas con el mismo nombre son raras en un encabezado
+        if !seen.insert(text.clone()) {
+            score -= 1;
+        }
+        let kinds: Vec<Kind> = body.iter().filter_map(|row| row.get(col).and_then(kind)).collect();
+        let Some(dominant) = dominant(&kinds) else {
+            continue;
+        };
+        score += match (first_kind, dominant) {
+            (Kind::Text, Kind::Text) => {
+                let repeated = body
+                    .iter()
+                    .filter_map(|row| row.get(col))
+                    .any(|other| other.to_string().trim().to_lowercase() == text);
+                if repeated {
+                    -1
+                } else {
+                    0
+                }
+            }
+            (Kind::Text, _) => 1,
+            // Años como encabezado de columna (2023, 2024) sobre importes
+            (Kind::Number, Kind::Number) if is_year(cell) => 0,
+            _ => -1,
+        };
+    }
+    score >= 0
+}
+
+fn dominant(kinds: &[Kind]) -> Option<Kind> {
+    [Kind::Text, Kind::Number, Kind::Date, Kind::Bool]
+        .into_iter()
+        .find(|wanted| kinds.iter().filter(|k| *k == wanted).count() as f64 >= kinds.len() as f64 * DOMINANT_SHARE)
+        .filter(|_| !kinds.is_empty())
+}
+
+fn is_year(cell: &CellValue) -> bool {
+    matches!(cell, CellValue::Number(n) if n.fract() == 0.0 && (1900.0..=2100.0).contains(n))
+}
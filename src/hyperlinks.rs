@@ -0,0 +1,162 @@
+// Hipervínculos y texto enriquecido. Los enlaces de un libro se leen del
+// <hyperlinks> de cada hoja (calamine no los expone) y se guardan en
+// `SheetData::hyperlinks`, de modo que se muestran con `leer_excel` y se
+// conservan al reescribir la hoja. `escribir_enlace` añade uno a una celda.
+// El destino es una URL (https://, mailto:), una celda del libro (#Hoja!A1) o la
+// ruta de un archivo, p. ej. el libro de origen de un cuadro de mando.
+//
+// El texto enriquecido se limita a fragmentos en negrita, marcados con **...**
+// en los textos que se escriben con escribir_excel o la herramienta escribir_hoja.
+use crate::excel::{self, CellValue, SheetData, WorkbookData};
+use crate::xlsx_patch;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Enlaces que se muestran por hoja en `leer_excel` y en el resumen del modelo
+pub const MAX_LISTED: usize = 10;
+
+// Enlaces (fila, columna) -> destino declarados en el XML de una hoja
+pub fn parse(sheet_xml: &str, rels: Option<&str>) -> BTreeMap<(usize, usize), String> {
+    let relationships = rels.map(|rels| xlsx_patch::find_tags(rels, "Relationship")).unwrap_or_default();
+    let mut links = BTreeMap::new();
+    for tag in xlsx_patch::find_tags(sheet_xml, "hyperlink") {
+        let Some(cell) = xlsx_patch::xml_attr(&tag, "ref").and_then(|r| excel::CellRange::parse(&r)) else {
+            continue;
+        };
+        let location = xlsx_patch::xml_attr(&tag, "location").map(|l| xlsx_patch::xml_unescape(&l));
+        let external = xlsx_patch::xml_attr(&tag, "r:id").and_then(|id| {
+            relationships
+                .iter()
+                .find(|rel| xlsx_patch::xml_attr(rel, "Id").as_deref() == Some(id.as_str()))
+                .and_then(|rel| xlsx_patch::xml_attr(rel, "Target"))
+                .map(|target| xlsx_patch::xml_unescape(&target))
+        });
+        let target = match (external, location) {
+            (Some(target), Some(location)) => format!("{}#{}", target, location),
+            (Some(target), None) => target,
+            (None, Some(location)) => format!("#{}", location),
+            (None, None) => continue,
+        };
+        links.insert((cell.first_row, cell.first_col), target);
+    }
+    links
+}
+
+// Destino en el formato de rust_xlsxwriter: las celdas del libro llevan
+// "internal:" y las rutas de archivo "file:///"
+pub fn writer_url(target: &str) -> String {
+    let lower = target.to_lowercase();
+    if let Some(location) = target.strip_prefix('#') {
+        format!("internal:{}", location)
+    } else if ["http://", "https://", "ftp://", "ftps://", "mailto:", "file://"]
+        .iter()
+        .any(|scheme| lower.starts_with(scheme))
+    {
+        target.to_string()
+    } else if lower.starts_with("www.") {
+        format!("https://{}", target)
+    } else {
+        format!("file:///{}", target)
+    }
+}
+
+// Enlaces de una hoja como "A2 → https://...", para mostrarlos
+pub fn describe(links: &BTreeMap<(usize, usize), String>) -> String {
+    let mut listed: Vec<String> = links
+        .iter()
+        .take(MAX_LISTED)
+        .map(|((row, col), target)| format!("{}{} → {}", excel::column_letters(*col), row + 1, target))
+        .collect();
+    if links.len() > MAX_LISTED {
+        listed.push(format!("y {} más", links.len() - MAX_LISTED));
+    }
+    listed.join(", ")
+}
+
+// Fragmentos (negrita, texto) de un texto con **negrita**; `None` si no hay
+// ningún fragmento marcado completo
+pub fn parse_bold(text: &str) -> Option<Vec<(bool, String)>> {
+    let pieces: Vec<&str> = text.split("**").collect();
+    // Un número par de trozos es un ** sin cerrar
+    if pieces.len() < 3 || pieces.len().is_multiple_of(2) {
+        return None;
+    }
+    let runs: Vec<(bool, String)> = pieces
+        .iter()
+        .enumerate()
+        .filter(|(_, piece)| !piece.is_empty())
+        .map(|(idx, piece)| (idx % 2 == 1, piece.to_string()))
+        .collect();
+    runs.iter().any(|(bold, _)| *bold).then_some(runs)
+}
+
+// Convierte las marcas **...** de los textos de la hoja en texto enriquecido:
+// la celda guarda el texto sin marcas y los fragmentos van a `rich_text`
+pub fn apply_bold_markup(sheet: &mut SheetData) {
+    for (row_idx, row) in sheet.rows.iter_mut().enumerate() {
+        for (col_idx, cell) in row.iter_mut().enumerate() {
+            let CellValue::Text(text) = cell else { continue };
+            let Some(runs) = parse_bold(text) else { continue };
+            *text = runs.iter().map(|(_, piece)| piece.as_str()).collect();
+            sheet.rich_text.insert((row_idx, col_idx), runs);
+        }
+    }
+}
+
+pub struct LinkOptions {
+    pub file: String,
+    // "B2" (primera hoja) o "Hoja!B2"
+    pub cell: String,
+    pub target: String,
+    pub text: Option<String>,
+}
+
+// `escribir_enlace <archivo> <celda> <url> [texto]`; crea el archivo si no existe.
+// Devuelve la celda con su hoja, para los mensajes.
+pub fn write_link(options: &LinkOptions) -> Result<String> {
+    let target = options.target.trim();
+    if target.is_empty() || target == "#" {
+        bail!("Falta el destino del enlace");
+    }
+    let (sheet_name, cell) = match options.cell.rsplit_once('!') {
+        Some((sheet, cell)) => (Some(sheet.trim_matches('\'').to_string()), cell),
+        None => (None, options.cell.as_str()),
+    };
+    let (row, col) = excel::parse_cell_ref(cell).context(format!("Celda no válida: {}", options.cell))?;
+
+    let path = Path::new(&options.file);
+    let mut data = if path.exists() {
+        excel::read_excel_file(&options.file)?
+    } else {
+        WorkbookData::default()
+    };
+    let sheet_name = match sheet_name {
+        Some(name) => name,
+        None => data.sheets.first().map(|s| s.name.clone()).unwrap_or_else(|| "Sheet1".to_string()),
+    };
+    let mut sheet = data
+        .sheet(&sheet_name)
+        .cloned()
+        .unwrap_or_else(|| SheetData::new(&sheet_name));
+    if sheet.rows.len() <= row {
+        sheet.rows.resize(row + 1, Vec::new());
+    }
+    let cells = &mut sheet.rows[row];
+    if cells.len() <= col {
+        cells.resize(col + 1, CellValue::Empty);
+    }
+    // Sin texto se conserva el de la celda o, si está vacía, se muestra el destino
+    match &options.text {
+        Some(text) => cells[col] = CellValue::Text(text.clone()),
+        None if cells[col] == CellValue::Empty => cells[col] = CellValue::Text(target.to_string()),
+        None => {}
+    }
+    sheet.formulas.remove(&(row, col));
+    sheet.rich_text.remove(&(row, col));
+    sheet.hyperlinks.insert((row, col), target.to_string());
+    let description = format!("{}!{}{}", sheet.name, excel::column_letters(col), row + 1);
+    data.upsert_sheet(sheet);
+    excel::save_workbook(path, &data)?;
+    Ok(description)
+}
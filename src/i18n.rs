@@ -0,0 +1,487 @@
+// Idioma de la interfaz (--lang, IAGENT_LANG o LANG) y alias en inglés de los
+// comandos. Los comandos en español siguen funcionando en cualquier idioma:
+// la entrada se normaliza a su forma española antes de interpretarla. Se
+// traducen la ayuda, los avisos y los mensajes de resultado de la terminal y los
+// encabezados de las tablas que generan los comandos; el detalle de los errores
+// sigue en español.
+use crate::commands;
+use crate::profiles;
+use crate::settings;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    Es,
+    En,
+}
+
+impl Lang {
+    // Acepta códigos como "en", "en_US.UTF-8", "es-ES" o "english"
+    pub fn parse(value: &str) -> Option<Lang> {
+        let code = value.trim().to_lowercase();
+        let code = code.split(['_', '-', '.']).next().unwrap_or_default();
+        match code {
+            "es" | "spa" | "español" | "espanol" | "spanish" => Some(Lang::Es),
+            "en" | "eng" | "english" | "inglés" | "ingles" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    // IAGENT_LANG (también desde un perfil) y si no LANG del sistema; por defecto
+    // español, también con un LANG de otro idioma o sin idioma (C, POSIX)
+    pub fn from_env() -> Lang {
+        profiles::var("IAGENT_LANG")
+            .ok()
+            .and_then(|value| Lang::parse(&value))
+            .or_else(|| std::env::var("LANG").ok().and_then(|value| Lang::parse(&value)))
+            .unwrap_or(Lang::Es)
+    }
+}
+
+// Idioma de la interfaz del agente en curso
+pub fn lang() -> Lang {
+    settings::current().lang()
+}
+
+// El texto (español, inglés) del idioma de la interfaz
+pub fn pick(text: (&'static str, &'static str)) -> &'static str {
+    match lang() {
+        Lang::Es => text.0,
+        Lang::En => text.1,
+    }
+}
+
+// `pick` con formato: tr!("{} filas", "{} rows", n). Solo se evalúan los
+// argumentos del idioma elegido, una vez
+macro_rules! tr {
+    ($es:literal, $en:literal $(, $arg:expr)* $(,)?) => {
+        match $crate::i18n::lang() {
+            $crate::i18n::Lang::Es => format!($es $(, $arg)*),
+            $crate::i18n::Lang::En => format!($en $(, $arg)*),
+        }
+    };
+}
+pub(crate) use tr;
+
+// Textos fijos de la interfaz
+#[derive(Debug, Clone, Copy)]
+pub enum Msg {
+    Title,
+    HelpHint,
+    ExitHint,
+    Goodbye,
+    HelpHeader,
+    HelpFooter,
+    ReloadPrompt,
+    MissingFile,
+    Reloaded,
+    ModelError,
+    Cancelled,
+    Usage,
+    CommandCorrected,
+    NotACommand,
+    CommandHint,
+    HelpNames,
+    HelpExamples,
+    UnknownCommand,
+}
+
+pub fn text(msg: Msg) -> &'static str {
+    match (lang(), msg) {
+        (Lang::Es, Msg::Title) => "=== Agente de IA con Deepseek para Excel ===",
+        (Lang::En, Msg::Title) => "=== Deepseek AI agent for Excel ===",
+        (Lang::Es, Msg::HelpHint) => "Escribe 'ayuda' para ver comandos disponibles",
+        (Lang::En, Msg::HelpHint) => "Type 'help' to see the available commands",
+        (Lang::Es, Msg::ExitHint) => "Escribe 'salir' para terminar",
+        (Lang::En, Msg::ExitHint) => "Type 'exit' to quit",
+        (Lang::Es, Msg::Goodbye) => "Adiós!",
+        (Lang::En, Msg::Goodbye) => "Goodbye!",
+        (Lang::Es, Msg::HelpHeader) => "Comandos disponibles:",
+        (Lang::En, Msg::HelpHeader) => "Available commands:",
+        (Lang::Es, Msg::HelpFooter) => "También puedes hacer preguntas sobre manipulación de Excel o solicitar ayuda.",
+        (Lang::En, Msg::HelpFooter) => "You can also ask questions about working with Excel files or ask for help.",
+        (Lang::Es, Msg::ReloadPrompt) => "ha cambiado desde que se leyó. ¿Recargarlo? (s/n)",
+        (Lang::En, Msg::ReloadPrompt) => "has changed since it was read. Reload it? (y/n)",
+        (Lang::Es, Msg::MissingFile) => "ya no existe; el modelo solo conoce el resumen que se leyó antes",
+        (Lang::En, Msg::MissingFile) => "no longer exists; the model only knows the summary read earlier",
+        (Lang::Es, Msg::Reloaded) => "recargado",
+        (Lang::En, Msg::Reloaded) => "reloaded",
+        (Lang::Es, Msg::ModelError) => "Error al comunicarse con Deepseek",
+        (Lang::En, Msg::ModelError) => "Error talking to Deepseek",
+        (Lang::Es, Msg::Cancelled) => "⏹ Petición cancelada; la sesión y los libros cargados se conservan",
+        (Lang::En, Msg::Cancelled) => "⏹ Request cancelled; the session and loaded workbooks are kept",
+        (Lang::Es, Msg::Usage) => "Faltan o sobran argumentos; uso:",
+        (Lang::En, Msg::Usage) => "Missing or unexpected arguments; usage:",
+        (Lang::Es, Msg::CommandCorrected) => "Comando corregido",
+        (Lang::En, Msg::CommandCorrected) => "Corrected command",
+        (Lang::Es, Msg::NotACommand) => "no es un comando; ¿quisiste decir",
+        (Lang::En, Msg::NotACommand) => "is not a command; did you mean",
+        (Lang::Es, Msg::CommandHint) => "se envía como pregunta; si era un comando, prueba con",
+        (Lang::En, Msg::CommandHint) => "is sent as a question; if you meant a command, try",
+        (Lang::Es, Msg::HelpNames) => "Nombres:",
+        (Lang::En, Msg::HelpNames) => "Names:",
+        (Lang::Es, Msg::HelpExamples) => "Ejemplos:",
+        (Lang::En, Msg::HelpExamples) => "Examples:",
+        (Lang::Es, Msg::UnknownCommand) => "no es un comando; `ayuda` los lista todos",
+        (Lang::En, Msg::UnknownCommand) => "is not a command; `help` lists them all",
+    }
+}
+
+// Instrucción añadida al prompt de sistema para que el modelo responda en el idioma
+// elegido; el prompt de sistema ya está en español
+pub fn reply_instruction(lang: Lang) -> Option<&'static str> {
+    match lang {
+        Lang::Es => None,
+        Lang::En => Some("Reply to the user in English."),
+    }
+}
+
+// El prompt de sistema con la instrucción de `lang`, si la necesita
+pub fn with_reply_instruction(mut template: String, lang: Option<Lang>) -> String {
+    if let Some(instruction) = lang.and_then(reply_instruction) {
+        template.push_str("\n\n");
+        template.push_str(instruction);
+    }
+    template
+}
+
+// Idioma en que se responde a `question`: el fijado (IAGENT_REPLY_LANG o `idioma`
+// en un perfil) o, si no hay, el que se detecta en la pregunta; `previous` si la
+// pregunta no lo deja claro
+pub fn reply_lang(fixed: Option<Lang>, question: &str, previous: Lang) -> Lang {
+    fixed.or_else(|| detect(question)).unwrap_or(previous)
+}
+
+// Palabras frecuentes que solo aparecen en uno de los dos idiomas
+const SPANISH_WORDS: &[&str] = &[
+    "el", "la", "los", "las", "de", "del", "que", "qué", "en", "por", "para", "con", "una", "es", "son", "hay", "y",
+    "cuál", "cual", "cuáles", "cuántos", "cuántas", "cuanto", "cuantos", "cómo", "como", "dame", "muestra", "dime",
+    "hoja", "hojas", "columna", "columnas", "fila", "filas", "suma", "promedio", "mayor", "menor", "cada", "más",
+    "mas", "tiene", "puedes", "quiero", "entre", "sin", "este", "esta", "libro", "ventas", "clientes", "gracias",
+];
+const ENGLISH_WORDS: &[&str] = &[
+    "the", "of", "and", "is", "are", "what", "which", "how", "many", "much", "show", "give", "tell", "sheet",
+    "sheets", "column", "columns", "row", "rows", "sum", "average", "highest", "lowest", "each", "per", "more",
+    "have", "can", "you", "want", "please", "between", "without", "this", "that", "in", "for", "with",
+    "workbook", "sales", "customers", "thanks", "does", "do", "by",
+];
+
+// Idioma de un texto del usuario por sus palabras frecuentes y sus acentos;
+// `None` si el texto es demasiado corto o ambiguo para decidir
+pub fn detect(text: &str) -> Option<Lang> {
+    let lower = text.to_lowercase();
+    let mut spanish = 0;
+    let mut english = 0;
+    if lower.contains(['ñ', '¿', '¡']) || lower.contains(['á', 'é', 'í', 'ó', 'ú']) {
+        spanish += 2;
+    }
+    for word in lower.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        if SPANISH_WORDS.contains(&word) {
+            spanish += 1;
+        }
+        if ENGLISH_WORDS.contains(&word) {
+            english += 1;
+        }
+    }
+    match spanish.cmp(&english) {
+        Ordering::Greater if spanish >= 2 => Some(Lang::Es),
+        Ordering::Less if english >= 2 => Some(Lang::En),
+        _ => None,
+    }
+}
+
+// Alias en inglés de los comandos (inglés, español)
+const COMMAND_ALIASES: &[(&str, &str)] = &[
+    ("read_excel", "leer_excel"),
+    ("read_many", "leer_varios"),
+    ("explain", "explicar"),
+    ("fill", "rellenar"),
+    ("show", "mostrar"),
+    ("create_excel", "crear_excel"),
+    ("write_excel", "escribir_excel"),
+    ("write_range", "escribir_rango"),
+    ("write_formula", "escribir_formula"),
+    ("merge_cells", "combinar_celdas"),
+    ("write_link", "escribir_enlace"),
+    ("note", "nota"),
+    ("group", "agrupar"),
+    ("ungroup", "desagrupar"),
+    ("create_table", "crear_tabla"),
+    ("convert", "convertir"),
+    ("cohorts", "cohortes"),
+    ("conditional_format", "formato_condicional"),
+    ("ask_batch", "preguntar_lote"),
+    ("for_each_row", "para_cada_fila"),
+    ("search", "buscar"),
+    ("extract_json", "extraer_json"),
+    ("statistics", "estadisticas"),
+    ("stats", "estadisticas"),
+    ("text_chart", "grafico_texto"),
+    ("encrypt_column", "cifrar_columna"),
+    ("convert_dates", "convertir_fechas"),
+    ("compare", "comparar"),
+    ("generate_report", "generar_informe"),
+    ("layout", "ajustar_hoja"),
+    ("protect", "proteger"),
+    ("insert_row", "insertar_fila"),
+    ("delete_row", "eliminar_fila"),
+    ("insert_column", "insertar_columna"),
+    ("delete_column", "eliminar_columna"),
+    ("move_column", "mover_columna"),
+    ("transform", "transformar"),
+    ("join", "cruzar"),
+    ("edit", "editar"),
+    ("duplicates", "duplicados"),
+    ("remove_duplicates", "eliminar_duplicados"),
+    ("save", "guardar"),
+    ("save_as", "guardar_como"),
+    ("validate", "validar"),
+    ("apply", "aplicar"),
+    ("copy", "copiar"),
+    ("next", "siguiente"),
+    ("previous", "anterior"),
+    ("prev", "anterior"),
+    ("page", "pagina"),
+    ("send_result", "enviar_resultado"),
+    ("paste_data", "pegar_datos"),
+    ("export_pdf", "exportar_pdf"),
+    ("decrypt_column", "descifrar_columna"),
+    ("anonymize", "anonimizar"),
+    ("deanonymize", "desanonimizar"),
+    ("agent", "agente"),
+    ("resume", "reanudar"),
+    ("jobs", "trabajos"),
+    ("refresh", "refrescar"),
+    ("undo", "deshacer"),
+    ("export_session", "exportar_sesion"),
+    ("manifest", "manifiesto"),
+    ("macros", "macro"),
+    ("context", "contexto"),
+    ("checkpoint", "punto_de_control"),
+    ("rewind", "volver"),
+    ("rollback", "volver"),
+    ("performance", "rendimiento"),
+    ("cost", "coste"),
+    ("model", "modelo"),
+    ("compare_models", "comparar_modelos"),
+    ("profile", "perfil"),
+    ("help", "ayuda"),
+    ("exit", "salir"),
+    ("quit", "salir"),
+];
+
+// Alias de las opciones clave=valor y --opciones
+const OPTION_ALIASES: &[(&str, &str)] = &[
+    ("by", "por"),
+    ("group_by", "agrupado_por"),
+    ("output", "salida"),
+    ("method", "modo"),
+    ("mode", "modo"),
+    ("cut", "corte"),
+    ("chart", "grafico"),
+    ("signup_date", "fecha_alta"),
+    ("event_date", "fecha_evento"),
+    ("value", "valor"),
+    ("customer", "cliente"),
+    ("sheet", "hoja"),
+    ("relative", "relativo"),
+    ("order", "orden"),
+    ("freeze", "congelar"),
+    ("widths", "anchos"),
+    ("hide", "ocultar"),
+    ("editable", "editables"),
+    ("count", "cantidad"),
+    ("header", "encabezado"),
+    ("file", "archivo"),
+    ("message", "mensaje"),
+    ("blank", "vacio"),
+    ("table", "tabla"),
+    ("answer", "respuesta"),
+    ("converter", "conversor"),
+    ("column", "columna"),
+    ("concurrency", "concurrencia"),
+    ("rows", "filas"),
+    ("fields", "campos"),
+    ("schema", "esquema"),
+    ("rename", "renombrar"),
+    ("reorder", "ordenar"),
+    ("drop", "quitar"),
+    ("cast", "convertir"),
+    ("trim", "recortar"),
+    ("dedupe", "sin_duplicados"),
+    ("steps", "pasos"),
+    ("step", "paso"),
+    ("columns", "columnas"),
+    ("name", "nombre"),
+    ("type", "tipo"),
+    ("summary", "resumen"),
+    ("style", "estilo"),
+    ("--to", "--a"),
+    ("--output", "--salida"),
+    ("--validate", "--validar"),
+    ("--evaluate", "--evaluar"),
+    ("--with-prompts", "--con-preguntas"),
+    ("--context", "--contexto"),
+    ("--collapse", "--contraer"),
+    ("--totals", "--totales"),
+    ("--no-bands", "--sin-bandas"),
+    ("--no-filters", "--sin-filtros"),
+    ("--rewrite", "--reescribir"),
+];
+
+// Alias de los tipos de regla y operadores de formato_condicional
+const RULE_ALIASES: &[(&str, &str)] = &[
+    ("scale", "escala"),
+    ("scale3", "escala3"),
+    ("bars", "barras"),
+    ("value", "valor"),
+    ("between", "entre"),
+];
+
+// Alias de los tipos de validar
+const VALIDATION_ALIASES: &[(&str, &str)] = &[
+    ("list", "lista"),
+    ("whole", "entero"),
+    ("date", "fecha"),
+    ("length", "longitud"),
+    ("between", "entre"),
+];
+
+fn alias<'a>(table: &[(&str, &'a str)], word: &str) -> Option<&'a str> {
+    table
+        .iter()
+        .find(|(english, _)| english.eq_ignore_ascii_case(word))
+        .map(|(_, spanish)| *spanish)
+}
+
+// Nombres de los comandos en español y sus alias en inglés, para sugerir el más
+// parecido a uno mal escrito
+pub fn command_names() -> impl Iterator<Item = &'static str> {
+    COMMAND_ALIASES
+        .iter()
+        .flat_map(|(english, spanish)| [*english, *spanish])
+        .chain(commands::names())
+}
+
+// Alias en inglés de un comando en español, en el orden de la tabla
+pub fn english_names(spanish: &str) -> Vec<&'static str> {
+    COMMAND_ALIASES.iter().filter(|(_, name)| *name == spanish).map(|(english, _)| *english).collect()
+}
+
+// Forma española de un nombre de comando (el propio nombre si ya lo es)
+pub fn spanish_command(name: &str) -> &str {
+    alias(COMMAND_ALIASES, name).unwrap_or(name)
+}
+
+// Traduce a español el comando y sus opciones, sin tocar el texto entre comillas
+// ni los valores; una entrada que no es un comando se devuelve tal cual
+pub fn normalize_command(input: &str) -> String {
+    let mut words = input.split_whitespace();
+    let Some(typed) = words.next() else { return input.to_string() };
+    let rest = &input.trim_start()[typed.len()..];
+    // El nombre del comando no distingue mayúsculas (LEER_EXCEL es leer_excel); los
+    // argumentos se quedan como están
+    let lower = typed.to_lowercase();
+    let first = if commands::route(&lower).is_some() { lower.as_str() } else { typed };
+    let command = alias(COMMAND_ALIASES, first).unwrap_or(first);
+    let known = command != first
+        || COMMAND_ALIASES.iter().any(|(_, spanish)| *spanish == first)
+        || matches!(first, "top" | "bottom" | "pareto");
+    if !known {
+        return format!("{}{}", first, rest);
+    }
+    // La tarea del agente, la pregunta de comparar_modelos, los datos de escribir_excel y
+    // escribir_rango, la fórmula y el valor de editar y el texto de nota son texto libre
+    if matches!(command, "agente" | "comparar_modelos" | "escribir_excel" | "escribir_rango" | "escribir_formula" | "editar" | "nota") {
+        return format!("{}{}", command, rest);
+    }
+
+    let mut output = command.to_string();
+    let mut quoted = false;
+    let mut position = 0;
+    for piece in rest.split_inclusive(char::is_whitespace) {
+        let word = piece.trim_end();
+        let spacing = &piece[word.len()..];
+        if word.is_empty() {
+            output.push_str(piece);
+            continue;
+        }
+        let translated = if quoted || word.starts_with('"') {
+            None
+        } else if let Some((key, value)) = word.split_once('=') {
+            alias(OPTION_ALIASES, key).map(|key| format!("{}={}", key, value))
+        } else if word.starts_with("--") {
+            alias(OPTION_ALIASES, word).map(str::to_string)
+        } else if command == "formato_condicional" && position >= 3 {
+            alias(RULE_ALIASES, word).map(str::to_string)
+        } else if command == "transformar" && position >= 1 {
+            // Pasos sin valor: trim, dedupe
+            alias(OPTION_ALIASES, word).map(str::to_string)
+        } else if command == "copiar" {
+            alias(OPTION_ALIASES, word).map(str::to_string)
+        } else if command == "validar" && position >= 2 {
+            alias(VALIDATION_ALIASES, word).map(str::to_string)
+        } else {
+            None
+        };
+        if word.matches('"').count() % 2 == 1 {
+            quoted = !quoted;
+        }
+        output.push_str(translated.as_deref().unwrap_or(word));
+        output.push_str(spacing);
+        position += 1;
+    }
+    output
+}
+
+// Lo contrario de normalize_command para los ejemplos de la ayuda en inglés: el
+// comando y las claves de las opciones pasan a su primer alias en inglés; los
+// valores y el texto entre comillas se quedan como están
+pub fn english_command(input: &str) -> String {
+    let english = |table: &[(&'static str, &str)], word: &str| -> Option<&'static str> {
+        table.iter().find(|(_, spanish)| *spanish == word).map(|(english, _)| *english)
+    };
+    let mut quoted = false;
+    let mut output = String::new();
+    for (idx, piece) in input.split_inclusive(char::is_whitespace).enumerate() {
+        let word = piece.trim_end();
+        let translated = if quoted {
+            None
+        } else if idx == 0 {
+            english(COMMAND_ALIASES, word).map(str::to_string)
+        } else if word.starts_with("--") {
+            english(OPTION_ALIASES, word).map(str::to_string)
+        } else {
+            word.split_once('=')
+                .and_then(|(key, value)| english(OPTION_ALIASES, key).map(|key| format!("{}={}", key, value)))
+        };
+        if word.matches('"').count() % 2 == 1 {
+            quoted = !quoted;
+        }
+        output.push_str(translated.as_deref().unwrap_or(word));
+        output.push_str(&piece[word.len()..]);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_reply_language_is_the_fixed_one_or_the_question_one() {
+        assert_eq!(reply_lang(Some(Lang::Es), "how many rows does the sheet have", Lang::En), Lang::Es);
+        assert_eq!(reply_lang(None, "how many rows does the sheet have", Lang::Es), Lang::En);
+        assert_eq!(reply_lang(None, "¿cuántas filas tiene la hoja?", Lang::En), Lang::Es);
+        // Demasiado corta para decidir: se queda el idioma anterior
+        assert_eq!(reply_lang(None, "ok", Lang::En), Lang::En);
+    }
+
+    #[test]
+    fn the_reply_instruction_is_added_only_when_needed() {
+        assert_eq!(with_reply_instruction("Prompt".to_string(), Some(Lang::Es)), "Prompt");
+        assert_eq!(with_reply_instruction("Prompt".to_string(), None), "Prompt");
+        assert_eq!(with_reply_instruction("Prompt".to_string(), Some(Lang::En)), "Prompt\n\nReply to the user in English.");
+    }
+}
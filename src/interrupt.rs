@@ -0,0 +1,39 @@
+// Manejo de Ctrl-C: fuera de las secciones interrumpibles termina el programa
+// como siempre; dentro de ellas solo cancela la operación en curso.
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::Notify;
+
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+fn notifier() -> &'static Notify {
+    static NOTIFY: OnceLock<Notify> = OnceLock::new();
+    NOTIFY.get_or_init(Notify::new)
+}
+
+// Único receptor de la señal; decide entre avisar a la sección activa o salir
+pub fn install() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if CAPTURING.load(Ordering::SeqCst) {
+                notifier().notify_waiters();
+            } else {
+                println!();
+                std::process::exit(130);
+            }
+        }
+    });
+}
+
+// Ejecuta el future; devuelve None si el usuario pulsa Ctrl-C antes de que termine
+pub async fn interruptible<F: Future>(future: F) -> Option<F::Output> {
+    let interrupted = notifier().notified();
+    CAPTURING.store(true, Ordering::SeqCst);
+    let output = tokio::select! {
+        output = future => Some(output),
+        _ = interrupted => None,
+    };
+    CAPTURING.store(false, Ordering::SeqCst);
+    output
+}
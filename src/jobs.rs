@@ -0,0 +1,141 @@
+// Trabajos largos reanudables (`para_cada_fila` y `agente`): su estado se guarda
+// en trabajos/<id>.json, dentro del directorio de datos, mientras avanzan. Si se
+// interrumpen (Ctrl-C, un fallo o el límite de peticiones de la API), `reanudar <id>`
+// sigue donde se quedaron sin repetir las peticiones ya pagadas. Al terminar sin errores el
+// archivo se borra; `trabajos` lista los pendientes.
+use crate::excel;
+use crate::llm::Message;
+use crate::paths;
+use crate::row_prompts::RowPromptOptions;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    #[serde(rename = "creado")]
+    pub created: String,
+    #[serde(flatten)]
+    pub kind: JobKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "tipo")]
+pub enum JobKind {
+    #[serde(rename = "filas")]
+    Rows(RowsJob),
+    #[serde(rename = "agente")]
+    Agent(AgentJob),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RowsJob {
+    #[serde(rename = "opciones")]
+    pub options: RowPromptOptions,
+    // Respuestas ya obtenidas por fila (índice desde 0); las que fallaron no se
+    // guardan y se vuelven a pedir al reanudar
+    #[serde(rename = "respuestas", default)]
+    pub answers: BTreeMap<usize, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgentJob {
+    #[serde(rename = "tarea")]
+    pub task: String,
+    #[serde(rename = "pasos")]
+    pub steps: Vec<String>,
+    // Pasos terminados; el siguiente es el primero que se ejecuta al reanudar
+    #[serde(rename = "completados", default)]
+    pub completed: usize,
+    // Conversación tras el último paso terminado
+    #[serde(rename = "historial", default)]
+    pub history: Vec<Message>,
+}
+
+impl Job {
+    // Crea el trabajo con el primer número libre y lo guarda
+    pub fn create(kind: JobKind) -> Result<Job> {
+        let next = list()?.iter().filter_map(|job| job.id.parse::<u64>().ok()).max().unwrap_or(0) + 1;
+        let job = Job {
+            id: next.to_string(),
+            created: excel::excel_serial_to_iso(excel::now_serial()),
+            kind,
+        };
+        job.save()?;
+        Ok(job)
+    }
+
+    pub fn load(id: &str) -> Result<Job> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+            bail!("'{}' no es un número de trabajo (usa `trabajos` para ver los pendientes)", id);
+        }
+        let path = job_path(id);
+        let text = fs::read_to_string(&path).context(format!(
+            "No existe el trabajo '{}' (usa `trabajos` para ver los pendientes)",
+            id
+        ))?;
+        serde_json::from_str(&text).context(format!("El trabajo {} está dañado ({})", id, path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = job_path(&self.id);
+        fs::create_dir_all(paths::jobs_dir())?;
+        // Se escribe aparte y se renombra para no dejar un archivo a medias si el proceso muere
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, serde_json::to_string(self)?)
+            .context(format!("No se pudo guardar el trabajo en {}", partial.display()))?;
+        fs::rename(&partial, &path).context(format!("No se pudo guardar el trabajo en {}", path.display()))
+    }
+
+    // Trabajo terminado: ya no hay nada que reanudar
+    pub fn finish(&self) -> Result<()> {
+        let path = job_path(&self.id);
+        if path.exists() {
+            fs::remove_file(&path).context(format!("No se pudo borrar {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            JobKind::Rows(rows) => format!(
+                "{} · para_cada_fila {}!{} · {} respuestas guardadas · {}",
+                self.id,
+                rows.options.file,
+                rows.options.sheet,
+                rows.answers.len(),
+                self.created
+            ),
+            JobKind::Agent(agent) => format!(
+                "{} · agente \"{}\" · {} de {} pasos · {}",
+                self.id,
+                agent.task,
+                agent.completed,
+                agent.steps.len(),
+                self.created
+            ),
+        }
+    }
+}
+
+// Trabajos pendientes, de más antiguo a más reciente
+pub fn list() -> Result<Vec<Job>> {
+    let Ok(entries) = fs::read_dir(paths::jobs_dir()) else {
+        return Ok(Vec::new());
+    };
+    let mut jobs: Vec<Job> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| serde_json::from_str(&fs::read_to_string(path).ok()?).ok())
+        .collect();
+    jobs.sort_by_key(|job| job.id.parse::<u64>().unwrap_or(u64::MAX));
+    Ok(jobs)
+}
+
+fn job_path(id: &str) -> PathBuf {
+    paths::jobs_dir().join(format!("{}.json", id))
+}
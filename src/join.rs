@@ -0,0 +1,235 @@
+// Cruce de dos hojas al estilo BUSCARV (`cruzar` y la herramienta cruzar_hojas):
+// a cada fila de la hoja A se le añaden las columnas de la fila de la hoja B con
+// la misma clave (un left join). Las filas de A sin pareja se conservan con esas
+// columnas vacías. Si una clave se repite en B se usa su primera fila, como hace
+// BUSCARV. Las claves se comparan sin distinguir mayúsculas ni espacios de los
+// extremos, y un número escrito como texto coincide con el número.
+use crate::analysis;
+use crate::excel::{CellValue, SheetData};
+use anyhow::{bail, Result};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+// Longitud máxima de un nombre de hoja en Excel
+const MAX_SHEET_NAME: usize = 31;
+
+#[derive(Debug, Clone)]
+pub struct JoinOptions {
+    pub left_sheet: String,
+    pub left_key: String,
+    pub right_sheet: String,
+    pub right_key: String,
+    // Columnas de B que se añaden; vacío, todas menos la clave
+    pub columns: Vec<String>,
+    // Nombre de la hoja resultante; por defecto "<A>+<B>"
+    pub name: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct JoinStats {
+    pub rows: usize,
+    // Filas de A que encontraron su clave en B
+    pub matched: usize,
+    // Claves que aparecen más de una vez en B
+    pub repeated_keys: usize,
+    // Columnas añadidas, con el nombre que tienen en el resultado
+    pub added: Vec<String>,
+}
+
+impl JoinStats {
+    pub fn describe(&self, right_sheet: &str) -> String {
+        let mut text = format!(
+            "{} filas, {} con pareja en {} y {} sin ella; columnas añadidas: {}",
+            self.rows,
+            self.matched,
+            right_sheet,
+            self.rows - self.matched,
+            self.added.join(", ")
+        );
+        if self.repeated_keys > 0 {
+            text.push_str(&format!(
+                ". {} clave(s) se repiten en {}; se ha usado la primera fila de cada una",
+                self.repeated_keys, right_sheet
+            ));
+        }
+        text
+    }
+}
+
+// Forma de comparar una clave; None si la celda está vacía y no cruza con nada
+fn key(cell: &CellValue) -> Option<String> {
+    match cell {
+        CellValue::Empty => None,
+        CellValue::Text(text) => match CellValue::infer(text) {
+            CellValue::Empty => None,
+            CellValue::Number(n) => Some(n.to_string()),
+            _ => Some(text.trim().to_lowercase()),
+        },
+        CellValue::Number(n) => Some(n.to_string()),
+        other => Some(other.to_string().to_lowercase()),
+    }
+}
+
+fn width(sheet: &SheetData) -> usize {
+    sheet.rows.iter().map(Vec::len).max().unwrap_or(0)
+}
+
+pub fn left_join(left: &SheetData, right: &SheetData, options: &JoinOptions) -> Result<(SheetData, JoinStats)> {
+    let left_key = analysis::require_data_column(left, &options.left_key)?;
+    let right_key = analysis::require_data_column(right, &options.right_key)?;
+    let right_headers = right.headers();
+    let brought: Vec<usize> = if options.columns.is_empty() {
+        (0..width(right)).filter(|&col| col != right_key).collect()
+    } else {
+        options
+            .columns
+            .iter()
+            .map(|spec| analysis::require_data_column(right, spec))
+            .collect::<Result<_>>()?
+    };
+    if brought.is_empty() {
+        bail!("La hoja '{}' no tiene más columnas que la clave", right.name);
+    }
+    let name = options
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("{}+{}", left.name, right.name).chars().take(MAX_SHEET_NAME).collect());
+    if name.chars().count() > MAX_SHEET_NAME {
+        bail!("El nombre de hoja '{}' tiene más de {} caracteres", name, MAX_SHEET_NAME);
+    }
+    if name.eq_ignore_ascii_case(&left.name) || name.eq_ignore_ascii_case(&right.name) {
+        bail!("El resultado no puede llamarse como una de las hojas que se cruzan ('{}')", name);
+    }
+
+    // Primera fila de B para cada clave
+    let mut index: HashMap<String, &Vec<CellValue>> = HashMap::new();
+    let mut repeated = HashSet::new();
+    for row in right.data_rows() {
+        let Some(key) = row.get(right_key).and_then(key) else {
+            continue;
+        };
+        match index.entry(key) {
+            Entry::Occupied(first) => {
+                repeated.insert(first.key().clone());
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(row);
+            }
+        }
+    }
+
+    let mut headers = left.headers();
+    let left_width = headers.len().max(width(left));
+    headers.resize(left_width, String::new());
+    let mut added = Vec::new();
+    for &col in &brought {
+        let header = right_headers.get(col).filter(|h| !h.is_empty()).cloned().unwrap_or_else(|| format!("Columna {}", col + 1));
+        let header = if headers.iter().any(|h| h.eq_ignore_ascii_case(&header)) {
+            format!("{} ({})", header, right.name)
+        } else {
+            header
+        };
+        headers.push(header.clone());
+        added.push(header);
+    }
+
+    let mut result = SheetData::new(&name);
+    result.rows.push(headers.into_iter().map(CellValue::Text).collect());
+    let mut matched = 0;
+    for row in left.data_rows() {
+        let mut joined = row.clone();
+        joined.resize(left_width, CellValue::Empty);
+        let found = row.get(left_key).and_then(key).and_then(|key| index.get(&key));
+        if found.is_some() {
+            matched += 1;
+        }
+        joined.extend(
+            brought
+                .iter()
+                .map(|&col| found.and_then(|r| r.get(col)).cloned().unwrap_or(CellValue::Empty)),
+        );
+        result.rows.push(joined);
+    }
+    // Los formatos de columna (porcentajes, fechas) siguen a sus columnas
+    result.column_formats = left.column_formats.iter().filter(|(col, _)| **col < left_width).map(|(c, f)| (*c, f.clone())).collect();
+    for (offset, col) in brought.iter().enumerate() {
+        if let Some(format) = right.column_formats.get(col) {
+            result.column_formats.insert(left_width + offset, format.clone());
+        }
+    }
+    let stats = JoinStats {
+        rows: result.rows.len() - 1,
+        matched,
+        repeated_keys: repeated.len(),
+        added,
+    };
+    Ok((result, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // " ana " y "7" como texto: la clave del otro lado es "ANA" y el número 7
+    fn orders() -> SheetData {
+        let mut sheet = SheetData::from_rows(
+            "Pedidos",
+            &[&["Pedido", "Cliente", "Importe"], &["P1", "", "10"], &["P2", "", "20"], &["P3", "Eva", "30"], &["P4", "", "40"]],
+        );
+        sheet.rows[1][1] = CellValue::Text(" ana ".to_string());
+        sheet.rows[2][1] = CellValue::Text("7".to_string());
+        sheet
+    }
+
+    fn customers() -> SheetData {
+        SheetData::from_rows(
+            "Clientes",
+            &[&["Cliente", "Zona", "Importe"], &["ANA", "Norte", "1"], &["7", "Sur", "2"], &["Ana", "Este", "3"]],
+        )
+    }
+
+    fn options(columns: &[&str], name: Option<&str>) -> JoinOptions {
+        JoinOptions {
+            left_sheet: "Pedidos".to_string(),
+            left_key: "Cliente".to_string(),
+            right_sheet: "Clientes".to_string(),
+            right_key: "Cliente".to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            name: name.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn every_left_row_is_kept_and_keys_ignore_case_spaces_and_number_text() {
+        let (result, stats) = left_join(&orders(), &customers(), &options(&[], None)).unwrap();
+        assert_eq!(result.name, "Pedidos+Clientes");
+        // Importe ya existe en Pedidos: la de Clientes lleva el nombre de su hoja
+        assert_eq!(result.headers(), ["Pedido", "Cliente", "Importe", "Zona", "Importe (Clientes)"]);
+        let zones: Vec<String> = result.data_rows().iter().map(|row| row[3].to_string()).collect();
+        // La clave repetida usa la primera fila, como BUSCARV; sin pareja o sin clave queda vacía
+        assert_eq!(zones, ["Norte", "Sur", "", ""]);
+        assert_eq!((stats.rows, stats.matched, stats.repeated_keys), (4, 2, 1));
+        let described = stats.describe("Clientes");
+        assert!(described.starts_with("4 filas, 2 con pareja en Clientes y 2 sin ella"), "{}", described);
+        assert!(described.contains("1 clave(s) se repiten"), "{}", described);
+    }
+
+    #[test]
+    fn only_the_requested_columns_are_brought() {
+        let (result, stats) = left_join(&orders(), &customers(), &options(&["Zona"], Some("Cruce"))).unwrap();
+        assert_eq!(result.name, "Cruce");
+        assert_eq!(result.headers(), ["Pedido", "Cliente", "Importe", "Zona"]);
+        assert_eq!(stats.added, ["Zona"]);
+        assert!(left_join(&orders(), &customers(), &options(&["Precio"], None)).is_err());
+    }
+
+    #[test]
+    fn the_result_cannot_replace_either_sheet() {
+        let error = left_join(&orders(), &customers(), &options(&[], Some("clientes"))).unwrap_err();
+        assert!(error.to_string().contains("no puede llamarse como una de las hojas"), "{}", error);
+        let long = "Una hoja con un nombre demasiado largo";
+        assert!(left_join(&orders(), &customers(), &options(&[], Some(long))).is_err());
+        let key_only = SheetData::from_rows("Claves", &[&["Cliente"], &["Ana"]]);
+        assert!(left_join(&orders(), &key_only, &options(&[], None)).is_err());
+    }
+}
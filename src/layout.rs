@@ -0,0 +1,417 @@
+// Diseño de las hojas (`ajustar_hoja`): inmovilizar filas o columnas, anchos de
+// columna, autoajuste al contenido y columnas ocultas. Los libros generados lo
+// aplican al guardarse; en un libro existente se edita el XML de la hoja para
+// conservar el resto (formatos, fórmulas, gráficos).
+use crate::excel::{self, SheetData};
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+// Anchos en caracteres del autoajuste, como los de Excel
+const MIN_AUTOFIT_WIDTH: f64 = 6.0;
+const MAX_AUTOFIT_WIDTH: f64 = 60.0;
+const MAX_COLUMN_WIDTH: f64 = 255.0;
+
+// Diseño tal como lo escribe el usuario; las columnas pueden ser letras,
+// números o encabezados y se resuelven contra la hoja
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayoutSpec {
+    // "1" (filas), "B2" (primera celda que se desplaza) o "no"
+    pub freeze: Option<String>,
+    pub widths: Vec<(String, f64)>,
+    pub autofit: bool,
+    pub hidden: Vec<String>,
+}
+
+// Diseño resuelto; columnas desde 0
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SheetLayout {
+    pub freeze_rows: usize,
+    pub freeze_cols: usize,
+    pub widths: BTreeMap<usize, f64>,
+    pub autofit: bool,
+    pub hidden: BTreeSet<usize>,
+}
+
+impl SheetLayout {
+    // Inmoviliza la fila de encabezados y ajusta las columnas al contenido
+    pub fn readable() -> SheetLayout {
+        SheetLayout {
+            freeze_rows: 1,
+            autofit: true,
+            ..SheetLayout::default()
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        match (self.freeze_rows, self.freeze_cols) {
+            (0, 0) => {}
+            (rows, 0) => parts.push(format!("{} fila(s) inmovilizada(s)", rows)),
+            (0, cols) => parts.push(format!("{} columna(s) inmovilizada(s)", cols)),
+            (rows, cols) => parts.push(format!("{} fila(s) y {} columna(s) inmovilizadas", rows, cols)),
+        }
+        if self.autofit {
+            parts.push("columnas autoajustadas".to_string());
+        }
+        if !self.widths.is_empty() {
+            let widths: Vec<String> = self
+                .widths
+                .iter()
+                .map(|(col, width)| format!("{}={}", excel::column_letters(*col), width))
+                .collect();
+            parts.push(format!("anchos {}", widths.join(", ")));
+        }
+        if !self.hidden.is_empty() {
+            let hidden: Vec<String> = self.hidden.iter().map(|col| excel::column_letters(*col)).collect();
+            parts.push(format!("ocultas {}", hidden.join(", ")));
+        }
+        if parts.is_empty() {
+            "sin cambios de diseño".to_string()
+        } else {
+            parts.join("; ")
+        }
+    }
+}
+
+impl LayoutSpec {
+    pub fn is_empty(&self) -> bool {
+        *self == LayoutSpec::default()
+    }
+
+    // Opciones de la línea de comandos: congelar=, anchos=A:20,Total:12, autoajustar, ocultar=C,D
+    pub fn parse_options(flags: &[&str], options: &HashMap<&str, &str>) -> Result<LayoutSpec> {
+        let mut spec = LayoutSpec {
+            freeze: options.get("congelar").map(|value| value.to_string()),
+            ..LayoutSpec::default()
+        };
+        for flag in flags {
+            match *flag {
+                "autoajustar" | "autofit" => spec.autofit = true,
+                other => bail!("Opción de diseño desconocida: {}", other),
+            }
+        }
+        if let Some(value) = options.get("autoajustar") {
+            spec.autofit = matches!(*value, "si" | "sí" | "1" | "true");
+        }
+        if let Some(value) = options.get("anchos") {
+            for pair in value.split(',').filter(|pair| !pair.trim().is_empty()) {
+                let (column, width) = pair
+                    .rsplit_once(':')
+                    .context(format!("Ancho no válido: '{}' (usa columna:ancho, p. ej. A:20)", pair))?;
+                spec.widths.push((column.trim().to_string(), parse_width(width)?));
+            }
+        }
+        if let Some(value) = options.get("ocultar") {
+            spec.hidden = value
+                .split(',')
+                .map(str::trim)
+                .filter(|column| !column.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        Ok(spec)
+    }
+
+    // Objeto JSON de las herramientas y plantillas:
+    // {"congelar": "B2" | 1, "anchos": {"A": 20}, "autoajustar": true, "ocultar": ["C"]}
+    pub fn from_json(value: &Value) -> Result<LayoutSpec> {
+        let object = value.as_object().context("El diseño debe ser un objeto JSON")?;
+        let mut spec = LayoutSpec {
+            freeze: match object.get("congelar") {
+                Some(Value::String(cell)) => Some(cell.clone()),
+                Some(Value::Number(rows)) => Some(rows.to_string()),
+                Some(Value::Bool(false)) | Some(Value::Null) | None => None,
+                Some(Value::Bool(true)) => Some("1".to_string()),
+                Some(other) => bail!("Valor no válido para 'congelar': {}", other),
+            },
+            autofit: object.get("autoajustar").and_then(Value::as_bool).unwrap_or(false),
+            ..LayoutSpec::default()
+        };
+        if let Some(widths) = object.get("anchos") {
+            let widths = widths.as_object().context("'anchos' debe ser un objeto {\"columna\": ancho}")?;
+            for (column, width) in widths {
+                let width = width.as_f64().context(format!("El ancho de '{}' debe ser un número", column))?;
+                spec.widths.push((column.clone(), check_width(width)?));
+            }
+        }
+        if let Some(hidden) = object.get("ocultar") {
+            spec.hidden = hidden
+                .as_array()
+                .context("'ocultar' debe ser una lista de columnas")?
+                .iter()
+                .map(|column| match column {
+                    Value::String(name) => Ok(name.clone()),
+                    Value::Number(number) => Ok(number.to_string()),
+                    other => bail!("Columna no válida en 'ocultar': {}", other),
+                })
+                .collect::<Result<_>>()?;
+        }
+        Ok(spec)
+    }
+
+    // Resuelve las columnas por encabezado, letra o número en la hoja dada
+    pub fn resolve(&self, sheet: &SheetData) -> Result<SheetLayout> {
+        let column = |spec: &str| {
+            sheet
+                .column_index(spec)
+                .context(format!("No existe la columna '{}' en la hoja {}", spec, sheet.name))
+        };
+        let (freeze_rows, freeze_cols) = match self.freeze.as_deref().map(str::trim) {
+            None | Some("no" | "0" | "") => (0, 0),
+            Some(value) => match value.parse::<usize>() {
+                Ok(rows) => (rows, 0),
+                Err(_) => excel::parse_cell_ref(value)
+                    .context(format!("Valor no válido para congelar: '{}' (usa un número de filas o una celda como B2)", value))?,
+            },
+        };
+        let mut layout = SheetLayout {
+            freeze_rows,
+            freeze_cols,
+            autofit: self.autofit,
+            ..SheetLayout::default()
+        };
+        for (spec, width) in &self.widths {
+            layout.widths.insert(column(spec)?, *width);
+        }
+        for spec in &self.hidden {
+            layout.hidden.insert(column(spec)?);
+        }
+        Ok(layout)
+    }
+}
+
+fn parse_width(value: &str) -> Result<f64> {
+    let width: f64 = value
+        .trim()
+        .replace(',', ".")
+        .parse()
+        .context(format!("Ancho no válido: '{}'", value))?;
+    check_width(width)
+}
+
+fn check_width(width: f64) -> Result<f64> {
+    if !(0.0..=MAX_COLUMN_WIDTH).contains(&width) {
+        bail!("El ancho debe estar entre 0 y {} caracteres (es {})", MAX_COLUMN_WIDTH, width);
+    }
+    Ok(width)
+}
+
+// Ancho de cada columna según su texto más largo (encabezado incluido)
+pub fn autofit_widths(sheet: &SheetData) -> BTreeMap<usize, f64> {
+    let mut widths = BTreeMap::new();
+    for row in sheet.display_rows() {
+        for (col, text) in row.iter().enumerate() {
+            let longest = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+            if longest == 0 {
+                continue;
+            }
+            let width = (longest as f64 * 1.1 + 2.0).clamp(MIN_AUTOFIT_WIDTH, MAX_AUTOFIT_WIDTH);
+            let entry = widths.entry(col).or_insert(MIN_AUTOFIT_WIDTH);
+            if width > *entry {
+                *entry = width;
+            }
+        }
+    }
+    widths
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutOptions {
+    pub file: String,
+    pub sheet: String,
+    pub spec: LayoutSpec,
+}
+
+// Aplica el diseño a una hoja de un libro existente, sin reescribir el resto
+pub fn apply(options: &LayoutOptions) -> Result<SheetLayout> {
+    if options.spec.is_empty() {
+        bail!("Indica qué cambiar: congelar=, anchos=, autoajustar u ocultar=");
+    }
+    let data = excel::read_excel_file(&options.file)?;
+    let sheet = data.require_sheet(&options.file, &options.sheet)?;
+    let layout = options.spec.resolve(sheet)?;
+    let mut widths = if layout.autofit { autofit_widths(sheet) } else { BTreeMap::new() };
+    widths.extend(layout.widths.iter().map(|(col, width)| (*col, *width)));
+
+    let path = Path::new(&options.file);
+    let mut package = XlsxPackage::open(path)?;
+    package.edit_sheet(&sheet.name, |xml| {
+        let mut xml = xml;
+        if options.spec.freeze.is_some() {
+            xml = set_frozen_pane(&xml, layout.freeze_rows, layout.freeze_cols)?;
+        }
+        set_columns(&xml, &widths, &layout.hidden)
+    })?;
+    package.save(path)?;
+    Ok(layout)
+}
+
+// Sustituye el panel de la primera vista de la hoja; (0, 0) lo quita
+fn set_frozen_pane(xml: &str, rows: usize, cols: usize) -> Result<String> {
+    let pane = if rows == 0 && cols == 0 {
+        String::new()
+    } else {
+        let active = match (rows, cols) {
+            (0, _) => "topRight",
+            (_, 0) => "bottomLeft",
+            _ => "bottomRight",
+        };
+        let mut pane = String::from("<pane");
+        if cols > 0 {
+            pane.push_str(&format!(" xSplit=\"{}\"", cols));
+        }
+        if rows > 0 {
+            pane.push_str(&format!(" ySplit=\"{}\"", rows));
+        }
+        pane.push_str(&format!(
+            " topLeftCell=\"{}{}\" activePane=\"{}\" state=\"frozen\"/>",
+            excel::column_letters(cols),
+            rows + 1,
+            active
+        ));
+        pane
+    };
+
+    let Some(start) = xlsx_patch::find_element_start(xml, "sheetView") else {
+        let views = format!("<sheetViews><sheetView workbookViewId=\"0\">{}</sheetView></sheetViews>", pane);
+        return xlsx_patch::insert_worksheet_element(xml, "sheetViews", &views);
+    };
+    let tag_end = start + xml[start..].find('>').context("XML de hoja no válido")?;
+    let (open_tag, body_end, close_end) = if xml[..tag_end].ends_with('/') {
+        (xml[start..tag_end - 1].trim_end().to_string() + ">", tag_end + 1, tag_end + 1)
+    } else {
+        let close = tag_end + xml[tag_end..].find("</sheetView>").context("XML de hoja no válido")?;
+        (xml[start..=tag_end].to_string(), close, close + "</sheetView>".len())
+    };
+    let body = if body_end > tag_end + 1 { &xml[tag_end + 1..body_end] } else { "" };
+    // El panel y las selecciones anteriores dejan de valer con la nueva división
+    let rest = remove_elements(&remove_elements(body, "pane"), "selection");
+    Ok(format!(
+        "{}{}{}{}</sheetView>{}",
+        &xml[..start],
+        open_tag,
+        pane,
+        rest,
+        &xml[close_end..]
+    ))
+}
+
+fn remove_elements(xml: &str, name: &str) -> String {
+    let mut output = xml.to_string();
+    while let Some(start) = xlsx_patch::find_element_start(&output, name) {
+        let Some(end) = output[start..].find("/>").map(|end| start + end + 2) else { break };
+        output.replace_range(start..end, "");
+    }
+    output
+}
+
+// Elemento <col> de un intervalo de columnas (desde 1, como en el XML)
+#[derive(Debug, Clone)]
+pub struct ColumnRange {
+    min: usize,
+    max: usize,
+    attrs: Vec<(String, String)>,
+}
+
+impl ColumnRange {
+    pub fn set(&mut self, name: &str, value: String) {
+        match self.attrs.iter_mut().find(|(key, _)| key == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.attrs.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.attrs.retain(|(key, _)| key != name);
+    }
+
+    fn to_xml(&self) -> String {
+        let mut xml = format!("<col min=\"{}\" max=\"{}\"", self.min, self.max);
+        for (key, value) in &self.attrs {
+            xml.push_str(&format!(" {}=\"{}\"", key, value));
+        }
+        xml.push_str("/>");
+        xml
+    }
+}
+
+// Cambia el ancho u oculta columnas en <cols>
+fn set_columns(xml: &str, widths: &BTreeMap<usize, f64>, hidden: &BTreeSet<usize>) -> Result<String> {
+    let targets: BTreeSet<usize> = widths.keys().chain(hidden.iter()).copied().collect();
+    edit_columns(xml, &targets, |column, range| {
+        if let Some(width) = widths.get(&column) {
+            range.set("width", format!("{:.2}", width));
+            range.set("customWidth", "1".to_string());
+        }
+        if hidden.contains(&column) {
+            range.set("hidden", "1".to_string());
+        }
+    })
+}
+
+// Edita el <col> de cada columna de `columns` (desde 0), dividiendo los intervalos
+// existentes para conservar el estilo y el ancho del resto de columnas
+pub fn edit_columns<F>(xml: &str, columns: &BTreeSet<usize>, mut edit: F) -> Result<String>
+where
+    F: FnMut(usize, &mut ColumnRange),
+{
+    if columns.is_empty() {
+        return Ok(xml.to_string());
+    }
+    let mut ranges: Vec<ColumnRange> = xlsx_patch::find_tags(xml, "col")
+        .iter()
+        .filter_map(|tag| {
+            let min = xlsx_patch::xml_attr(tag, "min")?.parse().ok()?;
+            let max = xlsx_patch::xml_attr(tag, "max")?.parse().ok()?;
+            let attrs = xlsx_patch::xml_attrs(tag).into_iter().filter(|(key, _)| key != "min" && key != "max").collect();
+            Some(ColumnRange { min, max, attrs })
+        })
+        .collect();
+
+    for column in columns.iter().map(|col| col + 1) {
+        let mut range = match ranges.iter().position(|range| range.min <= column && column <= range.max) {
+            Some(idx) => {
+                let existing = ranges.remove(idx);
+                if existing.min < column {
+                    ranges.push(ColumnRange {
+                        max: column - 1,
+                        ..existing.clone()
+                    });
+                }
+                if column < existing.max {
+                    ranges.push(ColumnRange {
+                        min: column + 1,
+                        ..existing.clone()
+                    });
+                }
+                ColumnRange {
+                    min: column,
+                    max: column,
+                    attrs: existing.attrs,
+                }
+            }
+            None => ColumnRange {
+                min: column,
+                max: column,
+                attrs: Vec::new(),
+            },
+        };
+        edit(column - 1, &mut range);
+        if !range.attrs.iter().any(|(key, _)| key == "width") {
+            // Excel exige un ancho en cada <col>
+            range.set("width", "8.43".to_string());
+        }
+        ranges.push(range);
+    }
+    ranges.sort_by_key(|range| range.min);
+
+    let cols: String = ranges.iter().map(ColumnRange::to_xml).collect();
+    let element = format!("<cols>{}</cols>", cols);
+    match (xlsx_patch::find_element_start(xml, "cols"), xml.find("</cols>")) {
+        (Some(start), Some(end)) => Ok(format!("{}{}{}", &xml[..start], element, &xml[end + "</cols>".len()..])),
+        _ => xlsx_patch::insert_worksheet_element(xml, "cols", &element),
+    }
+}
@@ -0,0 +1,106 @@
+// Biblioteca del agente para integrarlo en otras aplicaciones: `Agent` pregunta
+// al modelo, ejecuta las herramientas y lee y escribe libros, y avisa de lo que
+// hace a sus observadores (`AgentEvent`). Los módulos son internos.
+pub use agent::Agent;
+pub use error::{ApiErrorDetail, ApiFailure, IAgentError};
+pub use events::{AgentEvent, AgentObserver};
+pub use excel::{CellValue, SheetData, WorkbookData};
+pub use untrusted::ConfirmTool;
+
+// La terminal del binario `ia_agent`
+#[doc(hidden)]
+pub mod cli;
+
+mod agent;
+mod analysis;
+mod anonymize;
+mod azure;
+mod backup;
+mod batch;
+mod cache;
+mod chunks;
+mod commands;
+mod compare;
+mod compress;
+mod budget;
+mod clipboard;
+mod conditional_format;
+mod config;
+mod contexts;
+mod convert;
+mod crypto;
+mod dates;
+mod doctor;
+mod duplicates;
+mod error;
+mod events;
+mod excel;
+mod explain;
+mod export;
+mod extract;
+mod files;
+mod fill;
+mod formats;
+mod formula;
+mod formula_check;
+mod formulas;
+mod header;
+mod hyperlinks;
+mod i18n;
+mod interrupt;
+mod jobs;
+mod join;
+mod layout;
+mod limits;
+mod llm;
+mod macros;
+mod manifest;
+mod merges;
+mod metadata;
+mod model_compare;
+mod models;
+mod named_ranges;
+mod notes;
+mod outline;
+mod parquet;
+mod outputs;
+mod pager;
+mod paths;
+mod progress;
+mod protection;
+mod provider;
+mod profiles;
+mod prompts;
+mod readme;
+mod registry;
+mod report;
+mod retrieval;
+mod row_prompts;
+mod sampling;
+mod sandbox;
+mod schemas;
+mod script;
+mod search;
+mod secrets;
+mod server;
+mod settings;
+mod structure;
+mod structured;
+mod suggest;
+mod summary;
+mod table;
+mod tables;
+mod template;
+mod text_chart;
+mod throttle;
+mod timing;
+mod tour;
+mod transform;
+mod tools;
+mod untrusted;
+mod usage;
+mod validation;
+mod verify;
+mod watch;
+mod workbook_cache;
+mod xlsx_patch;
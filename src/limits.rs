@@ -0,0 +1,124 @@
+// Límites de las operaciones con libros, para que un archivo enorme no deje el
+// agente colgado: tamaño máximo del archivo que se carga, filas que se recorren
+// para resumir una hoja, celdas que se escriben de una vez y tiempo máximo de
+// cada lectura y herramienta de Excel. Se configuran con IAGENT_MAX_FILE_MB,
+// IAGENT_MAX_SUMMARY_ROWS, IAGENT_MAX_WRITE_CELLS e IAGENT_EXCEL_TIMEOUT; un 0
+// desactiva el límite correspondiente.
+use crate::config;
+use crate::error::IAgentError;
+use crate::profiles;
+use crate::settings;
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::time::Duration;
+
+const DEFAULT_MAX_FILE_MB: u64 = 200;
+const DEFAULT_MAX_SUMMARY_ROWS: usize = 1_000_000;
+const DEFAULT_MAX_WRITE_CELLS: usize = 5_000_000;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_file_bytes: Option<u64>,
+    // Filas por hoja que se recorren para las estadísticas y la muestra del resumen
+    pub max_summary_rows: Option<usize>,
+    pub max_write_cells: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_file_bytes: Some(DEFAULT_MAX_FILE_MB * 1024 * 1024),
+            max_summary_rows: Some(DEFAULT_MAX_SUMMARY_ROWS),
+            max_write_cells: Some(DEFAULT_MAX_WRITE_CELLS),
+            timeout: Some(DEFAULT_TIMEOUT),
+        }
+    }
+}
+
+impl Limits {
+    pub fn from_env() -> Result<Limits> {
+        let defaults = Limits::default();
+        let timeout = match profiles::var("IAGENT_EXCEL_TIMEOUT") {
+            Ok(value) => Some(config::parse_duration("IAGENT_EXCEL_TIMEOUT", &value)?).filter(|t| !t.is_zero()),
+            Err(_) => defaults.timeout,
+        };
+        Ok(Limits {
+            max_file_bytes: match number("IAGENT_MAX_FILE_MB")? {
+                Some(mb) => Some(mb as u64 * 1024 * 1024).filter(|bytes| *bytes > 0),
+                None => defaults.max_file_bytes,
+            },
+            max_summary_rows: number("IAGENT_MAX_SUMMARY_ROWS")?.map_or(defaults.max_summary_rows, |n| Some(n).filter(|n| *n > 0)),
+            max_write_cells: number("IAGENT_MAX_WRITE_CELLS")?.map_or(defaults.max_write_cells, |n| Some(n).filter(|n| *n > 0)),
+            timeout,
+        })
+    }
+}
+
+fn number(variable: &str) -> Result<Option<usize>> {
+    match profiles::var(variable) {
+        Ok(value) => Ok(Some(
+            value.trim().parse().context(format!("Valor no válido en {}: '{}'", variable, value))?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+// Límites del agente en curso
+pub fn get() -> Limits {
+    settings::current().limits()
+}
+
+// Falla antes de abrir un libro más grande que IAGENT_MAX_FILE_MB
+pub fn check_file_size(path: &Path) -> Result<()> {
+    let (Some(max), Ok(metadata)) = (get().max_file_bytes, path.metadata()) else {
+        return Ok(());
+    };
+    if metadata.len() > max {
+        return Err(IAgentError::excel(
+            &path.display().to_string(),
+            format!(
+                "El archivo ocupa {} MB y el máximo es {} MB; divídelo o sube el límite con IAGENT_MAX_FILE_MB",
+                metadata.len().div_ceil(1024 * 1024),
+                max / (1024 * 1024)
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// Falla antes de escribir más celdas de las que permite IAGENT_MAX_WRITE_CELLS
+pub fn check_write_cells(cells: usize) -> Result<()> {
+    match get().max_write_cells {
+        Some(max) if cells > max => bail!(
+            "Se iban a escribir {} celdas y el máximo es {}; escribe los datos por partes o sube el límite con IAGENT_MAX_WRITE_CELLS",
+            cells,
+            max
+        ),
+        _ => Ok(()),
+    }
+}
+
+// Ejecuta una operación de Excel en el pool de tareas bloqueantes con el tiempo
+// máximo de IAGENT_EXCEL_TIMEOUT. Si se agota, la operación sigue en segundo
+// plano hasta terminar, pero el agente vuelve a responder.
+pub async fn run_blocking<T: Send + 'static>(
+    what: &str,
+    operation: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let task = tokio::task::spawn_blocking(settings::inherit_blocking(operation));
+    let result = match get().timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, task).await {
+            Ok(result) => result,
+            Err(_) => bail!(
+                "{} superó el tiempo máximo de {} s (IAGENT_EXCEL_TIMEOUT)",
+                what,
+                timeout.as_secs()
+            ),
+        },
+        None => task.await,
+    };
+    result.context(format!("{} terminó de forma inesperada", what))?
+}
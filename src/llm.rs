@@ -0,0 +1,395 @@
+use crate::budget;
+use crate::cache;
+use crate::config::{Config, HttpSettings};
+use crate::error::{ApiErrorDetail, IAgentError};
+use crate::events::{self, AgentEvent};
+use crate::models;
+use crate::progress::Spinner;
+use crate::provider;
+use crate::sampling;
+use crate::throttle;
+use crate::usage::Usage;
+use anyhow::{bail, Context, Result};
+use reqwest::{Certificate, Client, Proxy, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+
+// Estructuras para la API de Deepseek
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl Message {
+    pub fn new(role: &str, content: impl Into<String>) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.into(),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+
+    // Mensaje del asistente que solicita ejecutar herramientas
+    pub fn tool_request(content: String, calls: Vec<ToolCall>) -> Message {
+        Message {
+            tool_calls: Some(calls),
+            ..Message::new("assistant", content)
+        }
+    }
+
+    // Resultado de una herramienta, ligado a la llamada que lo pidió
+    pub fn tool_result(call_id: &str, content: impl Into<String>) -> Message {
+        Message {
+            tool_call_id: Some(call_id.to_string()),
+            ..Message::new("tool", content)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: String,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+#[derive(Deserialize, Debug)]
+struct DeepseekResponse {
+    choices: Vec<DeepseekChoice>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DeepseekChoice {
+    message: DeepseekMessage,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeepseekMessage {
+    pub content: Option<String>,
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+// Respuesta del modelo junto con el consumo de tokens
+pub struct Completion {
+    pub message: DeepseekMessage,
+    pub usage: Option<Usage>,
+    // Servida desde la caché en disco, sin llamar a la API
+    pub cached: bool,
+}
+
+// Cliente HTTP con los tiempos máximos, el proxy y los certificados configurados
+pub fn build_client(settings: &HttpSettings) -> Result<Client> {
+    let mut builder = Client::builder().connect_timeout(settings.connect_timeout);
+    if let Some(timeout) = settings.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(proxy) = &settings.proxy {
+        let proxy = Proxy::all(proxy)
+            .map_err(|e| IAgentError::Config(format!("Proxy no válido en IAGENT_PROXY ({}): {}", proxy, e)))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &settings.ca_cert {
+        let pem = fs::read(path).context(format!("No se pudo leer el certificado {}", path.display()))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| IAgentError::Config(format!("Certificado no válido en {}: {}", path.display(), e)))?;
+        if certificates.is_empty() {
+            return Err(IAgentError::Config(format!("{} no contiene certificados PEM", path.display())).into());
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    builder.build().context("No se pudo crear el cliente HTTP")
+}
+
+// Un fallo de conexión o un tiempo de espera agotado, con la variable que lo controla
+fn send_error(error: reqwest::Error, settings: &HttpSettings) -> anyhow::Error {
+    if error.is_timeout() {
+        let waited = if error.is_connect() { Some(settings.connect_timeout) } else { settings.timeout };
+        let variable = if error.is_connect() { "IAGENT_CONNECT_TIMEOUT" } else { "IAGENT_TIMEOUT" };
+        if let Some(waited) = waited {
+            return anyhow::anyhow!("La API no respondió en {} s (se puede cambiar con {})", waited.as_secs(), variable);
+        }
+    }
+    if error.is_connect() {
+        return anyhow::Error::new(error)
+            .context("No se pudo conectar con la API (revisa la URL, el proxy y los certificados)");
+    }
+    error.into()
+}
+
+// Función para obtener una respuesta de Deepseek
+pub async fn get_deepseek_response(
+    client: &Client,
+    config: &Config,
+    messages: &[Message],
+    tools: Option<&Value>,
+) -> Result<Completion> {
+    let profile = models::profile(&config.model);
+    let mut request_body = json!({
+        "model": config.model,
+        "messages": models::adapt(messages, &profile),
+        "max_tokens": 500
+    });
+    config.sampling.apply(&mut request_body, 0.7);
+    if let Some(tools) = tools.filter(|_| profile.tools) {
+        request_body["tools"] = tools.clone();
+    }
+    send_completion(client, config, request_body).await
+}
+
+// Respuesta en JSON con el modo de salida estructurada del proveedor
+// (`response_format`); más tokens porque una extracción puede ser larga y un
+// JSON cortado no sirve
+pub async fn get_json_response(
+    client: &Client,
+    config: &Config,
+    messages: &[Message],
+    response_format: Option<&Value>,
+) -> Result<Completion> {
+    let mut request_body = json!({
+        "model": config.model,
+        "messages": models::adapt(messages, &models::profile(&config.model)),
+        "max_tokens": JSON_MAX_TOKENS
+    });
+    // La temperatura general no se aplica a las extracciones: solo la propia de extraer_json
+    let mut sampling = config.sampling.clone();
+    sampling.temperature = config.sampling.override_for("extraer_json");
+    sampling.apply(&mut request_body, 0.0);
+    if let Some(format) = response_format {
+        request_body["response_format"] = format.clone();
+    }
+    send_completion(client, config, request_body).await
+}
+
+const JSON_MAX_TOKENS: u32 = 4000;
+
+// Envía la petición y avisa a los observadores de `events`
+async fn send_completion(client: &Client, config: &Config, request_body: Value) -> Result<Completion> {
+    let count = |key: &str| request_body.get(key).and_then(Value::as_array).map_or(0, Vec::len);
+    events::emit(AgentEvent::PromptSent {
+        model: config.model.clone(),
+        messages: count("messages"),
+        tools: count("tools"),
+    });
+    let result = request_completion(client, config, request_body).await;
+    match &result {
+        Ok(completion) => {
+            let usage = completion.usage.unwrap_or_default();
+            events::emit(AgentEvent::TokensUsed {
+                model: config.model.clone(),
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+                cached: completion.cached,
+            });
+        }
+        Err(e) => events::emit(AgentEvent::Error {
+            source: "modelo".to_string(),
+            message: e.to_string(),
+        }),
+    }
+    result
+}
+
+async fn request_completion(client: &Client, config: &Config, request_body: Value) -> Result<Completion> {
+
+    let cache_key = cache::key(&config.provider_name(), &config.completions_url(), &config.model, &request_body);
+    // Los proveedores sin red no pasan por la caché: cada petición consume su respuesta
+    if let Some(provider) = &config.provider {
+        let (message, usage) = provider.complete(&cache_key)?;
+        sampling::record(&request_body);
+        return Ok(Completion {
+            message,
+            usage,
+            cached: false,
+        });
+    }
+    if let Some(ttl) = config.cache_ttl {
+        if let Some((message, usage)) = cache::lookup(&cache_key, ttl) {
+            sampling::record(&request_body);
+            return Ok(Completion {
+                message,
+                usage,
+                cached: true,
+            });
+        }
+    }
+
+    // Lo que se espera que consuma: los mensajes enviados y la respuesta más larga posible
+    let estimated = budget::estimate_tokens(&request_body["messages"].to_string())
+        + request_body["max_tokens"].as_u64().unwrap_or_default() as usize;
+    let ticket = throttle::acquire(&config.rate_limits, &config.completions_url(), estimated).await;
+    let spinner = Spinner::start("Esperando al modelo");
+    let response = authorized_post(client, config, &config.completions_url())
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| send_error(e, &config.http))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(IAgentError::api(status.as_u16(), &body).into());
+    }
+    let body = response.text().await.context("No se pudo leer la respuesta de la API")?;
+    drop(spinner);
+    let response_data: DeepseekResponse = match serde_json::from_str(&body) {
+        Ok(data) => data,
+        // Algunas pasarelas devuelven el error con código 200
+        Err(_) if ApiErrorDetail::parse(&body).is_some() => return Err(IAgentError::api(status.as_u16(), &body).into()),
+        Err(e) => bail!("La API devolvió una respuesta que no es del formato de chat/completions ({}): {}", e, preview(&body)),
+    };
+    if let Some(usage) = response_data.usage {
+        throttle::settle(ticket, (usage.prompt_tokens + usage.completion_tokens) as usize);
+    }
+    if let Some(choice) = response_data.choices.into_iter().next() {
+        if config.cache_ttl.is_some() {
+            // La caché es una optimización: si no se puede escribir se sigue sin ella
+            let _ = cache::store(&cache_key, &choice.message, response_data.usage);
+        }
+        if let Some(dir) = &config.record_dir {
+            provider::record(dir, &cache_key, &request_body, &choice.message, response_data.usage)?;
+        }
+        sampling::record(&request_body);
+        return Ok(Completion {
+            message: choice.message,
+            usage: response_data.usage,
+            cached: false,
+        });
+    }
+
+    bail!("La API no devolvió ninguna respuesta (lista `choices` vacía): {}", preview(&body))
+}
+
+// Comienzo del cuerpo de una respuesta, para los mensajes de error
+fn preview(body: &str) -> String {
+    let body = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    match body.char_indices().nth(200) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None if body.is_empty() => "(cuerpo vacío)".to_string(),
+        None => body,
+    }
+}
+
+// Petición POST con los parámetros de consulta y la autenticación configurados
+fn authorized_post(client: &Client, config: &Config, url: &str) -> RequestBuilder {
+    authorize(client.post(url).header("Content-Type", "application/json"), config)
+}
+
+fn authorize(request: RequestBuilder, config: &Config) -> RequestBuilder {
+    let mut request = request.query(&config.query_params);
+    let custom_auth = config
+        .extra_headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("authorization") || name.eq_ignore_ascii_case("api-key"));
+    if !config.api_key.is_empty() && !custom_auth {
+        request = match config.azure {
+            // Azure OpenAI no acepta la clave como Bearer
+            Some(_) => request.header("api-key", config.api_key.as_str()),
+            None => request.header("Authorization", format!("Bearer {}", config.api_key)),
+        };
+    }
+    for (name, value) in &config.extra_headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    request
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelsResponse {
+    data: Vec<ModelItem>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelItem {
+    id: String,
+}
+
+// Modelos que publica el endpoint (GET /models de la API compatible con OpenAI)
+pub async fn list_models(client: &Client, config: &Config) -> Result<Vec<String>> {
+    let url = match &config.azure {
+        Some(azure) => format!("{}/openai/models", azure.endpoint),
+        None => match config.api_url.strip_suffix("/chat/completions") {
+            Some(base) => format!("{}/models", base),
+            None => format!("{}/models", config.api_url.trim_end_matches('/')),
+        },
+    };
+    let response = authorize(client.get(&url), config)
+        .send()
+        .await
+        .map_err(|e| send_error(e, &config.http))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(IAgentError::api(status.as_u16(), &body).into());
+    }
+    let models = response
+        .json::<ModelsResponse>()
+        .await
+        .context("Lista de modelos no válida")?;
+    Ok(models.data.into_iter().map(|model| model.id).collect())
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingItem>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbeddingItem {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+// Vectores de una API de embeddings compatible con OpenAI, en el orden de `inputs`
+pub async fn get_embeddings(
+    client: &Client,
+    config: &Config,
+    url: &str,
+    model: &str,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>> {
+    let request_body = json!({ "model": model, "input": inputs });
+    let tokens = inputs.iter().map(|input| budget::estimate_tokens(input)).sum();
+    throttle::acquire(&config.rate_limits, url, tokens).await;
+    let response = authorized_post(client, config, url)
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| send_error(e, &config.http))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow::Error::new(IAgentError::api(status.as_u16(), &body)).context("Error en la API de embeddings"));
+    }
+    let mut items = response
+        .json::<EmbeddingsResponse>()
+        .await
+        .context("Respuesta de embeddings no válida")?
+        .data;
+    if items.len() != inputs.len() {
+        bail!("La API de embeddings devolvió {} vectores para {} textos", items.len(), inputs.len());
+    }
+    items.sort_by_key(|item| item.index);
+    Ok(items.into_iter().map(|item| item.embedding).collect())
+}
@@ -0,0 +1,219 @@
+// Macros: secuencias de comandos y preguntas con parámetros, para repetir una
+// tarea con otros archivos sin volver a escribirla. Se declaran en macros.toml,
+// en el directorio de configuración (o en el archivo de IAGENT_MACROS):
+//
+// [[macro]]
+// nombre = "informe_mensual"
+// descripcion = "Estadísticas y resumen de las ventas de un mes"
+// parametros = ["archivo", "hoja=Ventas"]
+// pasos = [
+//   "leer_excel {archivo}",
+//   "estadisticas {archivo} Importe hoja={hoja}",
+//   "¿Qué tres clientes suman más importe en {archivo}?",
+// ]
+//
+// `macro informe_mensual ventas_enero.xlsx` ejecuta los pasos como si se hubieran
+// escrito en el prompt, con {archivo} sustituido. Los argumentos van en el orden de
+// `parametros` o como nombre=valor; un parámetro con `=` tiene ese valor por
+// defecto. Solo se sustituyen los parámetros declarados, así que las plantillas de
+// para_cada_fila ({Comentario}) quedan igual.
+use crate::manifest;
+use crate::paths;
+use crate::profiles;
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MACROS_FILE: &str = "macros.toml";
+// Pasos que puede encadenar una macro, contando las que llama: evita que una que
+// se llama a sí misma no termine nunca
+pub const MAX_STEPS: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub name: String,
+    pub description: Option<String>,
+    // Nombre y valor por defecto, en el orden de los argumentos
+    pub parameters: Vec<(String, Option<String>)>,
+    pub steps: Vec<String>,
+}
+
+impl Macro {
+    fn parse(entry: &Value) -> Result<Macro> {
+        let text = |keys: &[&str]| keys.iter().find_map(|key| entry.get(*key).and_then(Value::as_str)).map(str::to_string);
+        let name = text(&["nombre", "name"]).context("Falta 'nombre'")?;
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            bail!("El nombre '{}' solo puede tener letras sin tildes, números, '_' y '-'", name);
+        }
+        let strings = |keys: &[&str], what: &str| -> Result<Vec<String>> {
+            match keys.iter().find_map(|key| entry.get(*key)) {
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string).context(format!("'{}' debe ser una lista de textos", what)))
+                    .collect(),
+                Some(_) => bail!("'{}' debe ser una lista de textos", what),
+                None => Ok(Vec::new()),
+            }
+        };
+        let parameters = strings(&["parametros", "parameters"], "parametros")?
+            .into_iter()
+            .map(|parameter| match parameter.split_once('=') {
+                Some((name, default)) => (name.trim().to_string(), Some(default.trim().to_string())),
+                None => (parameter.trim().to_string(), None),
+            })
+            .collect::<Vec<_>>();
+        for (idx, (parameter, _)) in parameters.iter().enumerate() {
+            if parameter.is_empty() || parameters[..idx].iter().any(|(other, _)| other == parameter) {
+                bail!("Parámetro '{}' vacío o repetido en '{}'", parameter, name);
+            }
+        }
+        let steps: Vec<String> = strings(&["pasos", "steps"], "pasos")?
+            .into_iter()
+            .map(|step| step.trim().to_string())
+            .filter(|step| !step.is_empty())
+            .collect();
+        if steps.is_empty() {
+            bail!("La macro '{}' no tiene pasos", name);
+        }
+        Ok(Macro {
+            name,
+            description: text(&["descripcion", "description"]),
+            parameters,
+            steps,
+        })
+    }
+
+    // informe_mensual(archivo, hoja=Ventas)
+    pub fn signature(&self) -> String {
+        let parameters: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|(name, default)| match default {
+                Some(default) => format!("{}={}", name, default),
+                None => name.clone(),
+            })
+            .collect();
+        format!("{}({})", self.name, parameters.join(", "))
+    }
+
+    // Los pasos con los argumentos sustituidos
+    pub fn expand(&self, args: &[String]) -> Result<Vec<String>> {
+        let mut values: Vec<Option<String>> = self.parameters.iter().map(|(_, default)| default.clone()).collect();
+        let mut next_positional = 0;
+        for arg in args {
+            let named = arg
+                .split_once('=')
+                .and_then(|(key, value)| Some((self.parameters.iter().position(|(name, _)| name == key)?, value)));
+            match named {
+                Some((idx, value)) => values[idx] = Some(value.to_string()),
+                None => {
+                    if next_positional >= self.parameters.len() {
+                        bail!("Demasiados argumentos para {}", self.signature());
+                    }
+                    values[next_positional] = Some(arg.clone());
+                    next_positional += 1;
+                }
+            }
+        }
+        let missing: Vec<&str> = self
+            .parameters
+            .iter()
+            .zip(&values)
+            .filter(|(_, value)| value.is_none())
+            .map(|((name, _), _)| name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            bail!("Falta {} en {}", missing.join(", "), self.signature());
+        }
+        Ok(self
+            .steps
+            .iter()
+            .map(|step| {
+                self.parameters.iter().zip(&values).fold(step.clone(), |step, ((name, _), value)| {
+                    step.replace(&format!("{{{}}}", name), &quote_if_needed(value.as_deref().unwrap_or_default()))
+                })
+            })
+            .collect())
+    }
+}
+
+// Un valor con espacios se pasa entre comillas, para que siga siendo un solo argumento
+fn quote_if_needed(value: &str) -> String {
+    if value.contains(char::is_whitespace) && !value.contains('"') {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MacroLibrary {
+    macros: Vec<Macro>,
+    // Archivo del que se cargaron, para los mensajes
+    source: Option<PathBuf>,
+}
+
+impl MacroLibrary {
+    // IAGENT_MACROS=<archivo.toml>, o macros.toml en el directorio de configuración
+    pub fn from_env() -> Result<MacroLibrary> {
+        let path = match profiles::var("IAGENT_MACROS") {
+            Ok(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+            _ => {
+                let path = paths::config_dir().join(MACROS_FILE);
+                if !path.exists() {
+                    return Ok(MacroLibrary::default());
+                }
+                path
+            }
+        };
+        MacroLibrary::load(&path)
+    }
+
+    pub fn load(path: &Path) -> Result<MacroLibrary> {
+        let text = fs::read_to_string(path).context(format!("No se pudo leer el archivo de macros {}", path.display()))?;
+        let document = manifest::parse(&text).context(format!("Archivo de macros {} no válido", path.display()))?;
+        let entries = match document.get("macro") {
+            Some(Value::Array(entries)) => entries.clone(),
+            Some(_) => bail!("{}: usa [[macro]] para cada macro", path.display()),
+            None => Vec::new(),
+        };
+        let mut macros: Vec<Macro> = Vec::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            let parsed = Macro::parse(entry).context(format!("{}: macro {} no válida", path.display(), idx + 1))?;
+            if macros.iter().any(|m| m.name == parsed.name) {
+                bail!("{}: la macro '{}' está declarada dos veces", path.display(), parsed.name);
+            }
+            macros.push(parsed);
+        }
+        Ok(MacroLibrary {
+            macros,
+            source: Some(path.to_path_buf()),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Macro> {
+        self.macros.iter().find(|m| m.name.eq_ignore_ascii_case(name))
+    }
+
+    // Lista para `macro` sin argumentos
+    pub fn describe(&self) -> String {
+        if self.macros.is_empty() {
+            return format!(
+                "ℹ️  No hay macros; decláralas con [[macro]] en {}",
+                self.source.clone().unwrap_or_else(|| paths::config_dir().join(MACROS_FILE)).display()
+            );
+        }
+        let mut text = format!(
+            "📚 Macros de {}:",
+            self.source.as_deref().map(|p| p.display().to_string()).unwrap_or_default()
+        );
+        for m in &self.macros {
+            text.push_str(&format!("\n  - {} ({} pasos)", m.signature(), m.steps.len()));
+            if let Some(description) = &m.description {
+                text.push_str(&format!(": {}", description));
+            }
+        }
+        text
+    }
+}
@@ -0,0 +1,408 @@
+// Lectura de manifiestos en TOML (las herramientas propias de IAGENT_TOOLS), sin
+// dependencias. Se admite el subconjunto que usan estos archivos: comentarios,
+// tablas [a.b] y listas de tablas [[a]], claves simples o entre comillas, textos
+// "básicos" (con \n, \t, \" y \\), 'literales' y multilínea (""" y '''), enteros,
+// decimales, booleanos, listas (también en varias líneas) y tablas en línea
+// { a = 1 }. No hay fechas ni claves con puntos (a.b = 1). El resultado es un
+// objeto JSON para leerlo con las mismas funciones que el resto de archivos.
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Number, Value};
+
+pub fn parse(text: &str) -> Result<Value> {
+    let mut root = Map::new();
+    // Ruta de la tabla en la que se escriben las claves; `true` si es el último
+    // elemento de una lista de tablas
+    let mut current: Vec<(String, bool)> = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut parser = Parser { chars: &chars, pos: 0, line: 1 };
+    loop {
+        parser.skip_blank_lines();
+        let Some(c) = parser.peek() else { break };
+        let line = parser.line;
+        if c == '[' {
+            let array = parser.chars[parser.pos..].starts_with(&['[', '[']);
+            parser.pos += if array { 2 } else { 1 };
+            let mut path = Vec::new();
+            loop {
+                parser.skip_spaces();
+                path.push(parser.key()?);
+                parser.skip_spaces();
+                if parser.peek() == Some('.') {
+                    parser.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            let close = if array { "]]" } else { "]" };
+            if !parser.eat(close) {
+                bail!("Línea {}: falta '{}'", line, close);
+            }
+            parser.end_of_line()?;
+            let last = path.len() - 1;
+            current = path.into_iter().enumerate().map(|(idx, key)| (key, array && idx == last)).collect();
+            open_table(&mut root, &current, true).context(format!("Línea {}", line))?;
+        } else {
+            let key = parser.key()?;
+            parser.skip_spaces();
+            if !parser.eat("=") {
+                bail!("Línea {}: se esperaba '=' tras '{}'", line, key);
+            }
+            parser.skip_spaces();
+            let value = parser.value().context(format!("Línea {}", line))?;
+            parser.end_of_line()?;
+            let table = open_table(&mut root, &current, false).context(format!("Línea {}", line))?;
+            if table.insert(key.clone(), value).is_some() {
+                bail!("Línea {}: la clave '{}' está repetida", line, key);
+            }
+        }
+    }
+    Ok(Value::Object(root))
+}
+
+// Tabla de `path`, creándola si falta; `new_entry` añade un elemento nuevo a la
+// lista de tablas final ([[a]] otra vez)
+fn open_table<'a>(root: &'a mut Map<String, Value>, path: &[(String, bool)], new_entry: bool) -> Result<&'a mut Map<String, Value>> {
+    let mut table = root;
+    for (idx, (key, array)) in path.iter().enumerate() {
+        let last = idx == path.len() - 1;
+        let entry = table.entry(key.clone()).or_insert_with(|| {
+            if *array {
+                Value::Array(Vec::new())
+            } else {
+                Value::Object(Map::new())
+            }
+        });
+        table = match entry {
+            Value::Array(items) if *array || !items.is_empty() => {
+                if *array && last && new_entry {
+                    items.push(Value::Object(Map::new()));
+                }
+                match items.last_mut() {
+                    Some(Value::Object(item)) => item,
+                    _ => bail!("'{}' no es una lista de tablas", key),
+                }
+            }
+            Value::Object(item) if !*array => item,
+            _ => bail!("'{}' ya tiene otro tipo de valor", key),
+        };
+    }
+    Ok(table)
+}
+
+struct Parser<'a> {
+    chars: &'a [char],
+    pos: usize,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        let wanted: Vec<char> = text.chars().collect();
+        if self.chars[self.pos..].starts_with(&wanted) {
+            self.pos += wanted.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    // Espacios, comentarios y saltos de línea (también dentro de listas)
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') => {
+                    self.next();
+                }
+                Some('\r') => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.peek() == Some('\r') {
+            self.pos += 1;
+        }
+        match self.next() {
+            None | Some('\n') => Ok(()),
+            Some(c) => bail!("Línea {}: sobra '{}' al final de la línea", self.line, c),
+        }
+    }
+
+    fn key(&mut self) -> Result<String> {
+        match self.peek() {
+            Some('"') => {
+                self.pos += 1;
+                self.basic_string()
+            }
+            Some('\'') => {
+                self.pos += 1;
+                self.literal_string()
+            }
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+                    self.pos += 1;
+                }
+                if start == self.pos {
+                    bail!("Línea {}: falta el nombre de la clave", self.line);
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        if self.eat("\"\"\"") {
+            return self.multiline_string("\"\"\"", true).map(Value::String);
+        }
+        if self.eat("'''") {
+            return self.multiline_string("'''", false).map(Value::String);
+        }
+        match self.peek() {
+            Some('"') => {
+                self.pos += 1;
+                self.basic_string().map(Value::String)
+            }
+            Some('\'') => {
+                self.pos += 1;
+                self.literal_string().map(Value::String)
+            }
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank_lines();
+                    if self.eat("]") {
+                        break;
+                    }
+                    items.push(self.value()?);
+                    self.skip_blank_lines();
+                    if self.eat(",") {
+                        continue;
+                    }
+                    if !self.eat("]") {
+                        bail!("falta ']' o ',' en la lista");
+                    }
+                    break;
+                }
+                Ok(Value::Array(items))
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut table = Map::new();
+                loop {
+                    self.skip_spaces();
+                    if self.eat("}") {
+                        break;
+                    }
+                    let key = self.key()?;
+                    self.skip_spaces();
+                    if !self.eat("=") {
+                        bail!("se esperaba '=' tras '{}' en la tabla", key);
+                    }
+                    self.skip_spaces();
+                    let value = self.value()?;
+                    table.insert(key, value);
+                    self.skip_spaces();
+                    if self.eat(",") {
+                        continue;
+                    }
+                    if !self.eat("}") {
+                        bail!("falta '}}' o ',' en la tabla");
+                    }
+                    break;
+                }
+                Ok(Value::Object(table))
+            }
+            _ => self.bare_value(),
+        }
+    }
+
+    // Booleanos y números
+    fn bare_value(&mut self) -> Result<Value> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !matches!(c, ',' | ']' | '}' | '#' | '\n' | '\r' | ' ' | '\t')) {
+            self.pos += 1;
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        let digits = word.replace('_', "");
+        match word.as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "" => bail!("falta el valor"),
+            _ => {
+                if let Ok(n) = digits.parse::<i64>() {
+                    return Ok(Value::from(n));
+                }
+                match digits.parse::<f64>().ok().and_then(Number::from_f64) {
+                    Some(n) => Ok(Value::Number(n)),
+                    None => bail!("valor no válido: {} (los textos van entre comillas)", word),
+                }
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => bail!("falta la comilla de cierre"),
+                Some('"') => return Ok(text),
+                Some('\\') => text.push(self.escape()?),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => bail!("falta la comilla de cierre"),
+                Some('\'') => return Ok(text),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn multiline_string(&mut self, close: &str, escapes: bool) -> Result<String> {
+        // El salto de línea justo tras la apertura no forma parte del texto
+        if self.peek() == Some('\r') {
+            self.pos += 1;
+        }
+        if self.peek() == Some('\n') {
+            self.next();
+        }
+        let mut text = String::new();
+        loop {
+            if self.eat(close) {
+                return Ok(text);
+            }
+            match self.next() {
+                None => bail!("falta el cierre {}", close),
+                Some('\\') if escapes => text.push(self.escape()?),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char> {
+        Ok(match self.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('u') => {
+                let hex: String = (0..4).filter_map(|_| self.next()).collect();
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .context(format!("escape \\u{} no válido", hex))?
+            }
+            Some(c) => bail!("escape \\{} no válido", c),
+            None => bail!("falta la comilla de cierre"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn a_tools_manifest_becomes_the_equivalent_json() {
+        let text = r#"
+# Herramientas de la empresa
+version = 2
+[ajustes]
+reintentos = 1_000
+factor = 0.5
+activa = true
+
+[[herramienta]]
+nombre = "tipo_cambio"   # comentario al final
+descripcion = "Cambio \"oficial\"\tdel día"
+parametros = { moneda = "EUR", decimales = 2 }
+monedas = [
+  "EUR",
+  'USD', # en varias líneas
+]
+
+[[herramienta]]
+nombre = 'C:\ruta\sin\escapes'
+plantilla = """
+Hola
+\u00e1"""
+"#;
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({
+                "version": 2,
+                "ajustes": {"reintentos": 1000, "factor": 0.5, "activa": true},
+                "herramienta": [
+                    {
+                        "nombre": "tipo_cambio",
+                        "descripcion": "Cambio \"oficial\"\tdel día",
+                        "parametros": {"moneda": "EUR", "decimales": 2},
+                        "monedas": ["EUR", "USD"]
+                    },
+                    {"nombre": "C:\\ruta\\sin\\escapes", "plantilla": "Hola\ná"}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn nested_tables_attach_to_the_last_list_entry() {
+        let text = "[[herramienta]]\nnombre = \"a\"\n[herramienta.http]\nurl = \"https://a\"\n[[herramienta]]\nnombre = \"b\"\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({"herramienta": [{"nombre": "a", "http": {"url": "https://a"}}, {"nombre": "b"}]})
+        );
+    }
+
+    #[test]
+    fn errors_point_to_the_line() {
+        let error = |text: &str| format!("{:#}", parse(text).unwrap_err());
+        assert_eq!(error("a = 1\na = 2\n"), "Línea 2: la clave 'a' está repetida");
+        assert!(error("a = 1\nb = texto\n").starts_with("Línea 2: valor no válido: texto"));
+        assert!(error("a = \"sin cerrar\n").starts_with("Línea 1: falta la comilla de cierre"));
+        assert_eq!(error("[tabla\n"), "Línea 1: falta ']'");
+        assert_eq!(error("a = 1 2\n"), "Línea 1: sobra '2' al final de la línea");
+        assert!(error("a = 1\n[a]\n").starts_with("Línea 2: 'a' ya tiene otro tipo de valor"));
+    }
+}
@@ -0,0 +1,87 @@
+// Celdas combinadas. calamine no las expone: se leen del <mergeCells> de cada
+// hoja y se guardan en `SheetData::merges`, y `combinar_celdas` las añade
+// editando el XML para conservar el resto del libro (formatos, fórmulas...).
+// Excel solo conserva el valor de la celda superior izquierda de un rango
+// combinado, así que las demás se vacían al combinar.
+use crate::excel::{self, CellRange, CellValue};
+use crate::named_ranges::{self, Target};
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+// Rangos combinados declarados en el XML de una hoja
+pub fn parse(sheet_xml: &str) -> Vec<CellRange> {
+    xlsx_patch::find_tags(sheet_xml, "mergeCell")
+        .iter()
+        .filter_map(|tag| xlsx_patch::xml_attr(tag, "ref"))
+        .filter_map(|reference| CellRange::parse(&reference))
+        .collect()
+}
+
+pub fn overlaps(a: &CellRange, b: &CellRange) -> bool {
+    a.first_row <= b.last_row && b.first_row <= a.last_row && a.first_col <= b.last_col && b.first_col <= a.last_col
+}
+
+pub fn describe(merges: &[CellRange]) -> String {
+    merges.iter().map(|range| range.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+pub struct MergeOutcome {
+    pub description: String,
+    // Celdas con valor que se han vaciado por quedar dentro del rango
+    pub cleared: usize,
+}
+
+// `combinar_celdas <archivo> <rango>`: el rango es Hoja!A1:C1 o un nombre definido
+pub fn merge_cells(file: &str, target: &str) -> Result<MergeOutcome> {
+    let path = Path::new(file);
+    let mut package = XlsxPackage::open(path)?;
+    let workbook = package.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+    let Target {
+        sheet,
+        range,
+        description,
+        ..
+    } = named_ranges::resolve_target(&named_ranges::defined_names(&workbook), target)?;
+    if range.first_row == range.last_row && range.first_col == range.last_col {
+        bail!("{} es una sola celda; indica un rango como A1:C1", description);
+    }
+
+    let part = package.sheet_part(&sheet)?;
+    let xml = package.read_part(&part).context(format!("Falta la parte {}", part))?;
+    if let Some(existing) = parse(&xml).into_iter().find(|existing| overlaps(existing, &range)) {
+        if existing == range {
+            bail!("{} ya está combinado", description);
+        }
+        bail!("{} se solapa con el rango combinado {}", description, existing);
+    }
+
+    // Las celdas con valor fuera de la esquina superior izquierda se vacían, como hace Excel
+    let data = excel::read_excel_file(file)?;
+    let rows = data.sheet(&sheet).map(|s| s.rows.as_slice()).unwrap_or_default();
+    let cleared: Vec<(usize, usize, String)> = (range.first_row..=range.last_row)
+        .flat_map(|row| (range.first_col..=range.last_col).map(move |col| (row, col)))
+        .filter(|&(row, col)| (row, col) != (range.first_row, range.first_col))
+        .filter(|&(row, col)| rows.get(row).and_then(|r| r.get(col)).is_some_and(|cell| *cell != CellValue::Empty))
+        .map(|(row, col)| (row, col, String::new()))
+        .collect();
+    let mut xml = xlsx_patch::set_cell_values(&xml, &cleared)?;
+
+    let element = format!("<mergeCell ref=\"{}\"/>", range);
+    xml = match xlsx_patch::find_tags(&xml, "mergeCells").into_iter().next() {
+        Some(tag) if tag.ends_with("/>") => xml.replacen(&tag, &format!("<mergeCells count=\"1\">{}</mergeCells>", element), 1),
+        Some(tag) => {
+            let count = parse(&xml).len() + 1;
+            let close = xml.find("</mergeCells>").context("XML de hoja no válido")?;
+            let xml = format!("{}{}{}", &xml[..close], element, &xml[close..]);
+            xml.replacen(&tag, &format!("<mergeCells count=\"{}\">", count), 1)
+        }
+        None => xlsx_patch::insert_worksheet_element(&xml, "mergeCells", &format!("<mergeCells count=\"1\">{}</mergeCells>", element))?,
+    };
+    package.write_part(&part, xml);
+    package.save(path)?;
+    Ok(MergeOutcome {
+        description,
+        cleared: cleared.len(),
+    })
+}
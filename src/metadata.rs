@@ -0,0 +1,137 @@
+// Metadatos de un libro al leerlo: propiedades del documento (autor, fechas,
+// título), dimensiones de cada hoja y nombres definidos, para que el modelo sepa
+// qué celdas de una plantilla tienen nombre y pueda escribir en ellas.
+use crate::excel::{CellRange, WorkbookData};
+use crate::named_ranges::{self, DefinedName};
+use crate::xlsx_patch;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+// La etiqueta <dimension> está al principio de la hoja: basta con leer su comienzo
+const SHEET_HEAD_BYTES: u64 = 4_096;
+
+// (etiqueta en docProps/core.xml, descripción)
+const CORE_PROPERTIES: &[(&str, &str)] = &[
+    ("dc:title", "título"),
+    ("dc:subject", "asunto"),
+    ("dc:creator", "autor"),
+    ("dc:description", "comentarios"),
+    ("dcterms:created", "creado"),
+    ("cp:lastModifiedBy", "modificado por"),
+    ("dcterms:modified", "modificado"),
+];
+
+#[derive(Debug, Default)]
+pub struct WorkbookMetadata {
+    // (descripción, valor) de las propiedades presentes
+    pub properties: Vec<(String, String)>,
+    // (hoja, rango usado)
+    pub dimensions: Vec<(String, Option<CellRange>)>,
+    pub names: Vec<DefinedName>,
+}
+
+impl WorkbookMetadata {
+    // Lee los metadatos sin cargar las celdas; con `data` se completan las
+    // dimensiones de las hojas que no declaran <dimension>
+    pub fn read(path: &str, data: Option<&WorkbookData>) -> Result<WorkbookMetadata> {
+        let path = Path::new(path);
+        let workbook =
+            xlsx_patch::read_part_from(path, "xl/workbook.xml", None)?.context("Falta xl/workbook.xml")?;
+        let rels = xlsx_patch::read_part_from(path, "xl/_rels/workbook.xml.rels", None)?
+            .context("Falta xl/_rels/workbook.xml.rels")?;
+
+        let mut metadata = WorkbookMetadata {
+            names: named_ranges::defined_names(&workbook),
+            ..WorkbookMetadata::default()
+        };
+        if let Some(core) = xlsx_patch::read_part_from(path, "docProps/core.xml", None)? {
+            for (tag, label) in CORE_PROPERTIES {
+                if let Some(value) = first_text(&core, tag) {
+                    metadata.properties.push((label.to_string(), display_date(&value)));
+                }
+            }
+        }
+        if let Some(company) = xlsx_patch::read_part_from(path, "docProps/app.xml", None)?
+            .and_then(|app| first_text(&app, "Company"))
+        {
+            metadata.properties.push(("empresa".to_string(), company));
+        }
+
+        for tag in xlsx_patch::find_tags(&workbook, "sheet") {
+            let Some(name) = xlsx_patch::xml_attr(&tag, "name").map(|n| xlsx_patch::xml_unescape(&n)) else {
+                continue;
+            };
+            let declared = xlsx_patch::sheet_part_in(&workbook, &rels, &name)
+                .ok()
+                .and_then(|part| xlsx_patch::read_part_from(path, &part, Some(SHEET_HEAD_BYTES)).ok().flatten())
+                .and_then(|head| xlsx_patch::find_tags(&head, "dimension").into_iter().next())
+                .and_then(|tag| xlsx_patch::xml_attr(&tag, "ref"))
+                .and_then(|reference| CellRange::parse(&reference));
+            let computed = || {
+                let sheet = data?.sheet(&name)?;
+                let cols = sheet.rows.iter().map(Vec::len).max().unwrap_or(0);
+                (cols > 0).then(|| CellRange {
+                    first_row: 0,
+                    first_col: 0,
+                    last_row: sheet.rows.len() - 1,
+                    last_col: cols - 1,
+                })
+            };
+            let range = declared.or_else(computed);
+            metadata.dimensions.push((name, range));
+        }
+        Ok(metadata)
+    }
+
+    // Texto para la terminal y el contexto del modelo
+    pub fn render(&self) -> String {
+        let mut lines = Vec::new();
+        if !self.properties.is_empty() {
+            let properties: Vec<String> =
+                self.properties.iter().map(|(label, value)| format!("{}: {}", label, value)).collect();
+            lines.push(format!("Propiedades: {}", properties.join(", ")));
+        }
+        let dimensions: Vec<String> = self
+            .dimensions
+            .iter()
+            .map(|(sheet, range)| match range {
+                Some(range) => format!(
+                    "{} {} ({} filas x {} columnas)",
+                    sheet,
+                    range,
+                    range.last_row - range.first_row + 1,
+                    range.last_col - range.first_col + 1
+                ),
+                None => format!("{} (vacía)", sheet),
+            })
+            .collect();
+        lines.push(format!("Dimensiones: {}", dimensions.join("; ")));
+        if !self.names.is_empty() {
+            let names: Vec<String> = self
+                .names
+                .iter()
+                .map(|defined| format!("{} = {}", defined.display_name(), defined.reference))
+                .collect();
+            lines.push(format!(
+                "Nombres definidos (se puede escribir en ellos con escribir_rango): {}",
+                names.join("; ")
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+fn first_text(xml: &str, tag: &str) -> Option<String> {
+    xlsx_patch::find_elements(xml, tag)
+        .into_iter()
+        .map(|(_, content)| xlsx_patch::xml_unescape(content.trim()))
+        .find(|value| !value.is_empty())
+}
+
+// "2024-01-05T10:30:00Z" -> "2024-01-05 10:30:00"
+fn display_date(value: &str) -> String {
+    match value.split_once('T') {
+        Some((date, time)) if date.len() == 10 => format!("{} {}", date, time.trim_end_matches('Z')),
+        _ => value.to_string(),
+    }
+}
@@ -0,0 +1,233 @@
+// Comparación de modelos (`comparar_modelos "pregunta"`): la misma pregunta, con
+// la conversación y los libros cargados, se envía a la vez a varios modelos y las
+// respuestas se muestran en columnas para juzgar cuál responde mejor. Los modelos
+// salen de modelos=a,b o de IAGENT_COMPARE_MODELS; cada uno puede llevar su
+// endpoint (modelo@url), como `modelo <nombre> url=`. Responden sin herramientas,
+// porque varios a la vez podrían escribir en los mismos archivos, y la
+// conversación no cambia.
+use crate::config::Config;
+use crate::llm::{self, Completion, Message};
+use crate::models;
+use crate::profiles;
+use crate::settings;
+use crate::table;
+use crate::usage::UsageTracker;
+use anyhow::{bail, Result};
+use reqwest::Client;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+// Modelos que se comparan como mucho, para que las columnas se puedan leer
+pub const MAX_MODELS: usize = 4;
+// Por debajo de este ancho de columna las respuestas se muestran una debajo de otra
+const MIN_COLUMN_WIDTH: usize = 28;
+const COLUMN_SEPARATOR: &str = " │ ";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contender {
+    pub model: String,
+    // Endpoint propio; sin él se usa el de la configuración
+    pub url: Option<String>,
+}
+
+impl Contender {
+    fn parse(entry: &str) -> Option<Contender> {
+        let (model, url) = match entry.trim().split_once('@') {
+            Some((model, url)) => (model.trim(), Some(url.trim().to_string()).filter(|url| !url.is_empty())),
+            None => (entry.trim(), None),
+        };
+        (!model.is_empty()).then(|| Contender {
+            model: model.to_string(),
+            url,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.url {
+            Some(url) => format!("{} ({})", self.model, url),
+            None => self.model.clone(),
+        }
+    }
+}
+
+// "deepseek-chat, gpt-4o@https://..." -> modelos; con uno solo se compara con el
+// modelo activo
+pub fn contenders(list: &str, current: &str) -> Result<Vec<Contender>> {
+    let mut contenders: Vec<Contender> = list.split(',').filter_map(Contender::parse).collect();
+    if contenders.len() == 1 && contenders[0].model != current {
+        contenders.insert(
+            0,
+            Contender {
+                model: current.to_string(),
+                url: None,
+            },
+        );
+    }
+    if contenders.len() < 2 {
+        bail!("Indica al menos dos modelos con modelos=a,b o IAGENT_COMPARE_MODELS");
+    }
+    if contenders.len() > MAX_MODELS {
+        bail!("Se comparan como mucho {} modelos a la vez ({} indicados)", MAX_MODELS, contenders.len());
+    }
+    Ok(contenders)
+}
+
+// Lista de IAGENT_COMPARE_MODELS, si está definida
+pub fn from_env() -> Option<String> {
+    profiles::var("IAGENT_COMPARE_MODELS").ok().filter(|list| !list.trim().is_empty())
+}
+
+#[derive(Debug)]
+pub struct Answer {
+    pub contender: Contender,
+    pub reply: Result<String>,
+    pub elapsed: Duration,
+    // Tokens de la petición y de la respuesta, si la API los informa
+    pub tokens: Option<(u64, u64)>,
+}
+
+// Pregunta a todos los modelos a la vez; las respuestas vuelven en el orden de `contenders`
+pub async fn ask_all(
+    client: &Client,
+    config: &Config,
+    history: &[Message],
+    prompt: &str,
+    contenders: &[Contender],
+    usage_tracker: &mut UsageTracker,
+) -> Vec<Answer> {
+    let mut running: JoinSet<(usize, Result<Completion>, Duration)> = JoinSet::new();
+    // Proveedor de cada modelo, para las tarifas: con @url puede ser otro
+    let mut providers = Vec::new();
+    for (idx, contender) in contenders.iter().enumerate() {
+        let mut config = config.clone();
+        config.model = contender.model.clone();
+        if let Some(url) = &contender.url {
+            config.api_url = url.clone();
+            config.azure = None;
+        }
+        providers.push(config.provider_name());
+        // Cada modelo recibe el historial recortado a su propia ventana
+        let mut messages = history.to_vec();
+        messages.push(Message::new("user", prompt));
+        models::fit_window(&mut messages, &models::profile(&config.model));
+        let client = client.clone();
+        running.spawn(settings::inherit(async move {
+            let started = Instant::now();
+            let completion = llm::get_deepseek_response(&client, &config, &messages, None).await;
+            (idx, completion, started.elapsed())
+        }));
+    }
+
+    let mut answers: Vec<Option<Answer>> = contenders.iter().map(|_| None).collect();
+    while let Some(joined) = running.join_next().await {
+        let Ok((idx, completion, elapsed)) = joined else { continue };
+        let contender = contenders[idx].clone();
+        let answer = match completion {
+            Ok(completion) => {
+                usage_tracker.record_completion(&providers[idx], &contender.model, &completion);
+                Answer {
+                    tokens: completion.usage.map(|usage| (usage.prompt_tokens, usage.completion_tokens)),
+                    reply: Ok(completion.message.content.unwrap_or_default()),
+                    contender,
+                    elapsed,
+                }
+            }
+            Err(e) => Answer {
+                contender,
+                reply: Err(e),
+                elapsed,
+                tokens: None,
+            },
+        };
+        answers[idx] = Some(answer);
+    }
+    answers.into_iter().flatten().collect()
+}
+
+// Respuestas en columnas, o una debajo de otra si la terminal es estrecha
+pub fn render(answers: &[Answer]) -> String {
+    let width = table::terminal_width();
+    let separators = COLUMN_SEPARATOR.chars().count() * answers.len().saturating_sub(1);
+    let column_width = width.saturating_sub(separators) / answers.len().max(1);
+    let columns: Vec<(String, String, String)> = answers
+        .iter()
+        .map(|answer| {
+            let mut status = format!("{:.1} s", answer.elapsed.as_secs_f64());
+            if let Some((prompt, completion)) = answer.tokens {
+                status.push_str(&format!(" · {} + {} tokens", prompt, completion));
+            }
+            let body = match &answer.reply {
+                Ok(text) if text.trim().is_empty() => "(respuesta vacía)".to_string(),
+                Ok(text) => text.trim().to_string(),
+                Err(e) => format!("❌ {:#}", e),
+            };
+            (answer.contender.describe(), status, body)
+        })
+        .collect();
+
+    if column_width < MIN_COLUMN_WIDTH {
+        return columns
+            .iter()
+            .map(|(name, status, body)| format!("🤖 {} ({})\n{}", name, status, body))
+            .collect::<Vec<_>>()
+            .join(&format!("\n{}\n", "─".repeat(width.min(60))));
+    }
+
+    let wrapped: Vec<Vec<String>> = columns
+        .iter()
+        .map(|(name, status, body)| {
+            let mut lines = wrap(&format!("🤖 {}", name), column_width);
+            lines.extend(wrap(status, column_width));
+            lines.push("─".repeat(column_width));
+            lines.extend(wrap(body, column_width));
+            lines
+        })
+        .collect();
+    let height = wrapped.iter().map(Vec::len).max().unwrap_or(0);
+    let mut output = String::new();
+    for line in 0..height {
+        let cells: Vec<String> = wrapped
+            .iter()
+            .map(|lines| pad(lines.get(line).map(String::as_str).unwrap_or(""), column_width))
+            .collect();
+        output.push_str(cells.join(COLUMN_SEPARATOR).trim_end());
+        output.push('\n');
+    }
+    output.trim_end().to_string()
+}
+
+// Líneas de como mucho `width` caracteres, cortando por palabras
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.to_string();
+            // Las palabras más largas que la columna se parten
+            while word.chars().count() > width {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                let split = word.char_indices().nth(width).map_or(word.len(), |(pos, _)| pos);
+                lines.push(word[..split].to_string());
+                word = word[split..].to_string();
+            }
+            if word.is_empty() {
+                continue;
+            }
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.chars().count())))
+}
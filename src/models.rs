@@ -0,0 +1,138 @@
+// Perfiles de los modelos: tamaño de la ventana de contexto, si admiten
+// herramientas y con qué rol reciben las instrucciones de sistema. Cada petición
+// adapta el historial al modelo activo, de modo que `modelo <nombre>` puede
+// cambiar de modelo a mitad de sesión conservando la conversación; al cambiar,
+// el historial se recorta a la ventana del nuevo modelo.
+use crate::budget;
+use crate::llm::Message;
+use crate::profiles;
+use crate::untrusted;
+
+const DEFAULT_CONTEXT_TOKENS: usize = 32_000;
+// Lo que se deja libre en la ventana para la respuesta y las definiciones de herramientas
+const RESERVED_TOKENS: usize = 4_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelProfile {
+    pub context_tokens: usize,
+    pub tools: bool,
+    // "system", "developer" (modelos o1/o3 de OpenAI) o "user" si no hay rol de sistema
+    pub system_role: &'static str,
+}
+
+// Perfil según el nombre del modelo, también con prefijo de pasarela ("openai/gpt-4o");
+// IAGENT_CONTEXT_WINDOW sustituye la ventana
+pub fn profile(model: &str) -> ModelProfile {
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    let (context_tokens, tools, system_role) = if name.starts_with("deepseek-reasoner") {
+        (64_000, false, "system")
+    } else if name.starts_with("deepseek") {
+        (64_000, true, "system")
+    } else if name.starts_with("o1-mini") || name.starts_with("o1-preview") {
+        (128_000, false, "user")
+    } else if ["o1", "o3", "o4"].iter().any(|prefix| name.starts_with(prefix)) {
+        (200_000, true, "developer")
+    } else if name.starts_with("gpt-4o") || name.starts_with("gpt-4-turbo") {
+        (128_000, true, "system")
+    } else if name.starts_with("gpt-4.1") {
+        (1_000_000, true, "system")
+    } else if name.starts_with("claude") {
+        (200_000, true, "system")
+    } else {
+        (DEFAULT_CONTEXT_TOKENS, true, "system")
+    };
+    let context_tokens = profiles::var("IAGENT_CONTEXT_WINDOW")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|tokens: &usize| *tokens > 0)
+        .unwrap_or(context_tokens);
+    ModelProfile {
+        context_tokens,
+        tools,
+        system_role,
+    }
+}
+
+impl ModelProfile {
+    pub fn describe(&self) -> String {
+        format!(
+            "ventana de {} tokens, {}",
+            self.context_tokens,
+            if self.tools { "con herramientas" } else { "sin herramientas" }
+        )
+    }
+}
+
+// Historial tal como se envía al modelo: el rol de sistema se renombra y, si el
+// modelo no admite herramientas, las llamadas y sus resultados pasan a texto.
+// Esos modelos exigen además que los roles se alternen, así que los mensajes
+// seguidos del mismo rol se unen.
+pub fn adapt(messages: &[Message], profile: &ModelProfile) -> Vec<Message> {
+    let mut adapted: Vec<Message> = Vec::with_capacity(messages.len());
+    for message in messages {
+        let mut message = message.clone();
+        if message.role == "system" {
+            message.role = profile.system_role.to_string();
+        }
+        if !profile.tools {
+            if let Some(calls) = message.tool_calls.take() {
+                let names: Vec<&str> = calls.iter().map(|call| call.function.name.as_str()).collect();
+                let used = format!("(Herramientas usadas: {})", names.join(", "));
+                message.content = if message.content.trim().is_empty() { used } else { format!("{}\n{}", message.content, used) };
+            }
+            if message.role == "tool" {
+                message.role = "user".to_string();
+                message.tool_call_id = None;
+                message.content = format!("Resultado de la herramienta:\n{}", message.content);
+            }
+            if let Some(previous) = adapted.last_mut().filter(|previous| previous.role == message.role) {
+                previous.content.push_str("\n\n");
+                previous.content.push_str(&message.content);
+                continue;
+            }
+        }
+        adapted.push(message);
+    }
+    adapted
+}
+
+// Mensajes y tokens quitados al recortar el historial
+pub struct Trimmed {
+    pub messages: usize,
+    pub tokens: usize,
+}
+
+// Quita los mensajes más antiguos hasta que el historial cabe en la ventana del
+// modelo. Primero los turnos de la conversación (una pregunta con sus respuestas
+// y llamadas a herramientas) y solo después los datos de archivos; el prompt de
+// sistema y el último turno se conservan siempre.
+pub fn fit_window(history: &mut Vec<Message>, profile: &ModelProfile) -> Trimmed {
+    let limit = profile.context_tokens.saturating_sub(RESERVED_TOKENS);
+    let tokens = |history: &[Message]| -> usize { history.iter().map(|m| budget::estimate_tokens(&m.content)).sum() };
+    let before = tokens(history);
+    let mut removed = 0;
+    for keep_files in [true, false] {
+        while tokens(history) > limit {
+            let Some(idx) = (1..history.len()).find(|&idx| !(keep_files && untrusted::is_file_content(&history[idx]))) else {
+                break;
+            };
+            let end = if untrusted::is_file_content(&history[idx]) {
+                // Una llamada con datos se lleva sus resultados
+                idx + 1 + history[idx + 1..].iter().take_while(|m| m.role == "tool" && untrusted::is_file_content(m)).count()
+            } else {
+                (idx + 1..history.len())
+                    .find(|&next| history[next].role == "user" && !untrusted::is_file_content(&history[next]))
+                    .unwrap_or(history.len())
+            };
+            if end == history.len() {
+                break;
+            }
+            removed += end - idx;
+            history.drain(idx..end);
+        }
+    }
+    Trimmed {
+        messages: removed,
+        tokens: before - tokens(history),
+    }
+}
@@ -0,0 +1,328 @@
+// Nombres definidos del libro (Administrador de nombres de Excel) y escritura en
+// rangos por nombre o por referencia Hoja!A1:B2. Las plantillas suelen marcar sus
+// celdas importantes con nombres; se escribe directamente en el XML de la hoja
+// para conservar formatos, fórmulas y el resto del libro.
+use crate::error::IAgentError;
+use crate::excel::{self, CellRange, CellValue};
+use crate::limits;
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct DefinedName {
+    pub name: String,
+    // Hoja a la que se limita el nombre (localSheetId); `None` si es de todo el libro
+    pub scope: Option<String>,
+    // Referencia tal como está en el libro, p. ej. 'Hoja 1'!$B$2:$C$4
+    pub reference: String,
+}
+
+impl DefinedName {
+    // Hoja y rango a los que apunta; `None` para constantes, fórmulas o varias áreas
+    pub fn target(&self) -> Option<(String, CellRange)> {
+        parse_reference(&self.reference)
+    }
+
+    pub fn display_name(&self) -> String {
+        match &self.scope {
+            Some(sheet) => format!("{}!{}", quote_sheet(sheet), self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+// Nombres de xl/workbook.xml, sin los ocultos ni los internos de Excel (_xlnm.Print_Area...)
+pub fn defined_names(workbook_xml: &str) -> Vec<DefinedName> {
+    let sheets = sheet_names(workbook_xml);
+    xlsx_patch::find_elements(workbook_xml, "definedName")
+        .into_iter()
+        .filter_map(|(tag, content)| {
+            let name = xlsx_patch::xml_unescape(&xlsx_patch::xml_attr(&tag, "name")?);
+            let hidden = xlsx_patch::xml_attr(&tag, "hidden").is_some_and(|h| h == "1" || h == "true");
+            if hidden || name.starts_with("_xlnm.") {
+                return None;
+            }
+            let scope = xlsx_patch::xml_attr(&tag, "localSheetId")
+                .and_then(|id| id.parse::<usize>().ok())
+                .and_then(|id| sheets.get(id).cloned());
+            Some(DefinedName {
+                name,
+                scope,
+                reference: xlsx_patch::xml_unescape(content.trim()),
+            })
+        })
+        .collect()
+}
+
+// Hojas de xl/workbook.xml, en el orden del libro
+pub fn sheet_names(workbook_xml: &str) -> Vec<String> {
+    xlsx_patch::find_tags(workbook_xml, "sheet")
+        .iter()
+        .filter_map(|tag| xlsx_patch::xml_attr(tag, "name"))
+        .map(|name| xlsx_patch::xml_unescape(&name))
+        .collect()
+}
+
+// Nombre exacto de la hoja pedida (sin distinguir mayúsculas), o la primera
+pub fn find_sheet(workbook_xml: &str, name: Option<&str>) -> Result<String> {
+    let sheets = sheet_names(workbook_xml);
+    match name {
+        Some(name) => sheets
+            .iter()
+            .find(|sheet| sheet.eq_ignore_ascii_case(name))
+            .cloned()
+            .context(format!("No existe la hoja '{}' (hojas: {})", name, sheets.join(", "))),
+        None => sheets.first().cloned().context("El libro no tiene hojas"),
+    }
+}
+
+// "'Hoja 1'!$B$2:$C$4" o "Datos!B2" -> (hoja, rango)
+pub fn parse_reference(reference: &str) -> Option<(String, CellRange)> {
+    let reference = reference.trim().trim_start_matches('=');
+    let (sheet, range) = reference.rsplit_once('!')?;
+    if range.contains(',') {
+        return None;
+    }
+    let sheet = match sheet.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        Some(quoted) => quoted.replace("''", "'"),
+        None => sheet.to_string(),
+    };
+    Some((sheet, CellRange::parse(range)?))
+}
+
+pub fn quote_sheet(sheet: &str) -> String {
+    if sheet.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        sheet.to_string()
+    } else {
+        format!("'{}'", sheet.replace('\'', "''"))
+    }
+}
+
+// Destino de una escritura ya resuelto
+pub struct Target {
+    pub sheet: String,
+    pub range: CellRange,
+    // Nombre y referencia, para los mensajes
+    pub description: String,
+    pub named: bool,
+}
+
+// Destino de una escritura: un nombre definido (global o "Hoja!Nombre") o una referencia Hoja!A1:B2
+pub fn resolve_target(names: &[DefinedName], target: &str) -> Result<Target> {
+    let target = target.trim();
+    let (scope, name) = match target.rsplit_once('!') {
+        Some((sheet, name)) => (Some(sheet.trim_matches('\'')), name),
+        None => (None, target),
+    };
+    let matching = names.iter().find(|defined| {
+        defined.name.eq_ignore_ascii_case(name)
+            && match scope {
+                Some(sheet) => defined.scope.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(sheet)),
+                None => defined.scope.is_none(),
+            }
+    });
+    // Sin hoja se acepta también un nombre local si es el único con ese nombre
+    let matching = matching.or_else(|| {
+        let mut local = names.iter().filter(|d| scope.is_none() && d.name.eq_ignore_ascii_case(name));
+        local.next().filter(|_| local.next().is_none())
+    });
+    if let Some(defined) = matching {
+        let (sheet, range) = defined.target().context(format!(
+            "El nombre {} no apunta a un rango de celdas ({})",
+            defined.display_name(),
+            defined.reference
+        ))?;
+        return Ok(Target {
+            sheet,
+            range,
+            description: format!("{} ({})", defined.display_name(), defined.reference),
+            named: true,
+        });
+    }
+    match parse_reference(target) {
+        Some((sheet, range)) => Ok(Target {
+            description: format!("{}!{}", quote_sheet(&sheet), range),
+            sheet,
+            range,
+            named: false,
+        }),
+        None if scope.is_none() => Err(IAgentError::parse(
+            target,
+            "No existe ese nombre en el libro; usa un nombre definido o una referencia Hoja!A1",
+        )
+        .into()),
+        None => Err(IAgentError::parse(target, "Referencia no válida").into()),
+    }
+}
+
+// Qué hacer si el bloque cae sobre celdas con datos. Los nombres definidos no se
+// comprueban: en una plantilla marcan justo las celdas que hay que rellenar
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpillPolicy {
+    // No escribir y explicar dónde cabría
+    #[default]
+    Refuse,
+    Overwrite,
+    // Bajar o mover a la derecha el bloque hasta la primera posición libre
+    ShiftDown,
+    ShiftRight,
+}
+
+impl SpillPolicy {
+    // Valor de desplazar=
+    pub fn parse_shift(value: &str) -> Option<SpillPolicy> {
+        match value.to_lowercase().as_str() {
+            "abajo" | "down" => Some(SpillPolicy::ShiftDown),
+            "derecha" | "right" => Some(SpillPolicy::ShiftRight),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RangeWrite {
+    // Destino escrito (Hoja!B12:D20 tras desplazar)
+    pub description: String,
+    pub cells: usize,
+    // Celdas con datos que se reemplazaron (con SpillPolicy::Overwrite)
+    pub overwritten: usize,
+    // Destino pedido, si se desplazó para no pisar datos
+    pub shifted_from: Option<String>,
+}
+
+// Escribe un bloque de valores (filas de celdas) desde la esquina superior
+// izquierda del destino. Los valores deben caber en él, salvo cuando el destino
+// es una referencia a una sola celda (Hoja!B2): entonces el bloque se extiende desde ella.
+// Si el bloque cae sobre celdas con datos, `spill` decide si se escribe encima,
+// se desplaza o no se escribe.
+pub fn write_range(file: &str, target: &str, values: &[Vec<CellValue>], spill: SpillPolicy) -> Result<RangeWrite> {
+    let path = Path::new(file);
+    let mut package = XlsxPackage::open(path)?;
+    let workbook = package.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+    let Target {
+        sheet,
+        range,
+        description,
+        named,
+    } = resolve_target(&defined_names(&workbook), target)?;
+
+    let rows = values.len();
+    let cols = values.iter().map(Vec::len).max().unwrap_or(0);
+    if rows == 0 || cols == 0 {
+        bail!("No hay valores que escribir");
+    }
+    limits::check_write_cells(rows * cols)?;
+    let single_cell = range.first_row == range.last_row && range.first_col == range.last_col;
+    let (range_rows, range_cols) = (range.last_row - range.first_row + 1, range.last_col - range.first_col + 1);
+    if (named || !single_cell) && (rows > range_rows || cols > range_cols) {
+        bail!(
+            "{} filas x {} columnas no caben en {} ({} x {})",
+            rows,
+            cols,
+            description,
+            range_rows,
+            range_cols
+        );
+    }
+
+    let mut origin = (range.first_row, range.first_col);
+    let mut outcome = RangeWrite {
+        description,
+        cells: 0,
+        overwritten: 0,
+        shifted_from: None,
+    };
+    if !named {
+        let part = package.sheet_part(&sheet)?;
+        let xml = package.read_part(&part).context(format!("Falta {}", part))?;
+        let filled = xlsx_patch::filled_cells(&xml);
+        let block = |(row, col): (usize, usize)| CellRange {
+            first_row: row,
+            first_col: col,
+            last_row: row + rows - 1,
+            last_col: col + cols - 1,
+        };
+        let overlap = |at: (usize, usize)| -> Vec<(usize, usize)> {
+            let area = block(at);
+            filled.iter().copied().filter(|(row, col)| area.contains(*row, *col)).collect()
+        };
+        // Primera posición libre bajando (o yendo a la derecha) desde el destino
+        let free = |down: bool| {
+            let mut at = origin;
+            loop {
+                let hits = overlap(at);
+                let Some(last) = hits.iter().map(|(r, c)| if down { *r } else { *c }).max() else {
+                    return at;
+                };
+                at = if down { (last + 1, at.1) } else { (at.0, last + 1) };
+            }
+        };
+        let hits = overlap(origin);
+        if !hits.is_empty() {
+            let at_sheet = |at: (usize, usize)| format!("{}!{}", quote_sheet(&sheet), block(at));
+            match spill {
+                SpillPolicy::Overwrite => outcome.overwritten = hits.len(),
+                SpillPolicy::ShiftDown | SpillPolicy::ShiftRight => {
+                    outcome.shifted_from = Some(at_sheet(origin));
+                    origin = free(spill == SpillPolicy::ShiftDown);
+                    outcome.description = at_sheet(origin);
+                }
+                SpillPolicy::Refuse => {
+                    let mut shown: Vec<String> = hits
+                        .iter()
+                        .take(5)
+                        .map(|(r, c)| format!("{}{}", excel::column_letters(*c), r + 1))
+                        .collect();
+                    if hits.len() > shown.len() {
+                        shown.push("…".to_string());
+                    }
+                    bail!(
+                        "El bloque {} pisaría {} celda(s) con datos ({}). Usa --sobrescribir para reemplazarlas, \
+                         desplazar=abajo para escribir en {} o desplazar=derecha para escribir en {}",
+                        at_sheet(origin),
+                        hits.len(),
+                        shown.join(", "),
+                        at_sheet(free(true)),
+                        at_sheet(free(false))
+                    );
+                }
+            }
+        }
+    }
+
+    let mut cells = Vec::new();
+    for (row_offset, row) in values.iter().enumerate() {
+        for (col_offset, value) in row.iter().enumerate() {
+            let content = xlsx_patch::cell_content(value)
+                .context(format!("No se puede escribir el valor de error '{}'", value))?;
+            cells.push((origin.0 + row_offset, origin.1 + col_offset, content));
+        }
+    }
+    outcome.cells = cells.len();
+    package.edit_sheet(&sheet, |xml| xlsx_patch::set_cell_values(&xml, &cells))?;
+    // Las fórmulas que dependen de las celdas escritas guardan su valor anterior:
+    // se pide a Excel que recalcule al abrir el libro
+    package.write_part("xl/workbook.xml", with_full_calc_on_load(&workbook));
+    package.save(path)?;
+    Ok(outcome)
+}
+
+pub fn with_full_calc_on_load(workbook: &str) -> String {
+    match xlsx_patch::find_tags(workbook, "calcPr").first() {
+        Some(tag) if tag.contains("fullCalcOnLoad") => workbook.to_string(),
+        Some(tag) => workbook.replacen(tag, &tag.replacen("<calcPr", "<calcPr fullCalcOnLoad=\"1\"", 1), 1),
+        None => {
+            // calcPr va después de definedNames y antes del resto de elementos opcionales
+            let insert_at = ["oleSize", "customWorkbookViews", "pivotCaches", "smartTagPr", "extLst"]
+                .iter()
+                .filter_map(|later| xlsx_patch::find_element_start(workbook, later))
+                .min()
+                .or_else(|| workbook.rfind("</workbook>"));
+            match insert_at {
+                Some(at) => format!("{}<calcPr fullCalcOnLoad=\"1\"/>{}", &workbook[..at], &workbook[at..]),
+                None => workbook.to_string(),
+            }
+        }
+    }
+}
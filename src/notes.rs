@@ -0,0 +1,362 @@
+// Notas de celda (los comentarios clásicos de Excel). Se leen de la parte
+// xl/commentsN.xml de cada hoja (calamine no las expone) y se guardan en
+// `SheetData::notes`, de modo que se muestran con `leer_excel`, llegan al modelo
+// en el resumen y se conservan al reescribir la hoja. `nota <archivo> <celda>
+// "texto"` y la herramienta escribir_nota añaden una, p. ej. para explicar cómo
+// se calculó un valor generado; se escriben en el XML del libro, junto con el
+// dibujo VML que Excel usa para mostrarlas, sin tocar el resto.
+use crate::excel::{self, SheetData};
+use crate::named_ranges;
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Notas que se muestran por hoja en `leer_excel` y en el resumen del modelo
+pub const MAX_LISTED: usize = 10;
+// Caracteres de cada nota en esos listados
+const LISTED_CHARS: usize = 120;
+// Autor de las notas escritas por el agente
+pub const AUTHOR: &str = "IAgent";
+
+const VML_RELATIONSHIP: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/vmlDrawing";
+const COMMENTS_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.comments+xml";
+const VML_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.vmlDrawing";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub text: String,
+    pub author: String,
+}
+
+impl Note {
+    pub fn new(text: &str) -> Note {
+        Note {
+            text: text.trim().to_string(),
+            author: AUTHOR.to_string(),
+        }
+    }
+}
+
+// Notas (fila, columna) de la parte de comentarios de una hoja
+pub fn parse(comments_xml: &str) -> BTreeMap<(usize, usize), Note> {
+    let authors: Vec<String> = xlsx_patch::find_elements(comments_xml, "author")
+        .into_iter()
+        .map(|(_, author)| xlsx_patch::xml_unescape(&author))
+        .collect();
+    let mut notes = BTreeMap::new();
+    for (tag, content) in xlsx_patch::find_elements(comments_xml, "comment") {
+        let Some(cell) = xlsx_patch::xml_attr(&tag, "ref").and_then(|r| excel::parse_cell_ref(&r)) else {
+            continue;
+        };
+        let author = xlsx_patch::xml_attr(&tag, "authorId")
+            .and_then(|id| id.parse::<usize>().ok())
+            .and_then(|id| authors.get(id).cloned())
+            .unwrap_or_default();
+        let mut text: String = xlsx_patch::find_elements(&content, "t")
+            .iter()
+            .map(|(_, text)| xlsx_patch::xml_unescape(text))
+            .collect();
+        // Excel empieza las notas con "Autor:" en negrita
+        if let Some(rest) = text.strip_prefix(&format!("{}:", author)).filter(|_| !author.is_empty()) {
+            text = rest.to_string();
+        }
+        notes.insert(
+            cell,
+            Note {
+                text: text.trim().to_string(),
+                author,
+            },
+        );
+    }
+    notes
+}
+
+// Notas de una hoja como "B2: calculado como...", para mostrarlas
+pub fn describe(notes: &BTreeMap<(usize, usize), Note>) -> String {
+    let mut listed: Vec<String> = notes
+        .iter()
+        .take(MAX_LISTED)
+        .map(|((row, col), note)| format!("{}{}: {}", excel::column_letters(*col), row + 1, shorten(&note.text)))
+        .collect();
+    if notes.len() > MAX_LISTED {
+        listed.push(format!("y {} más", notes.len() - MAX_LISTED));
+    }
+    listed.join("; ")
+}
+
+fn shorten(text: &str) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= LISTED_CHARS {
+        return flat;
+    }
+    let mut short: String = flat.chars().take(LISTED_CHARS).collect();
+    short.push('…');
+    short
+}
+
+// Partes de las notas de una hoja: (hoja, relaciones, comentarios, dibujo VML)
+struct NoteParts {
+    sheet: String,
+    rels: String,
+    comments: Option<String>,
+    vml: Option<String>,
+}
+
+fn note_parts(package: &XlsxPackage, sheet_name: &str) -> Result<NoteParts> {
+    let sheet = package.sheet_part(sheet_name)?;
+    let (dir, file) = sheet.rsplit_once('/').context(format!("Ruta de hoja no válida: {}", sheet))?;
+    let rels = format!("{}/_rels/{}.rels", dir, file);
+    let rels_xml = package.read_part(&rels).unwrap_or_default();
+    let comments = xlsx_patch::relationship_target(&rels_xml, xlsx_patch::COMMENTS_RELATIONSHIP)
+        .map(|target| xlsx_patch::resolve_part(dir, &target));
+    let xml = package.read_part(&sheet).context(format!("Falta la parte {}", sheet))?;
+    let vml = xlsx_patch::find_tags(&xml, "legacyDrawing")
+        .first()
+        .and_then(|tag| xlsx_patch::xml_attr(tag, "r:id"))
+        .and_then(|id| relationship(&rels_xml, &id))
+        .and_then(|tag| xlsx_patch::xml_attr(&tag, "Target"))
+        .map(|target| xlsx_patch::resolve_part(dir, &xlsx_patch::xml_unescape(&target)));
+    Ok(NoteParts {
+        sheet,
+        rels,
+        comments,
+        vml,
+    })
+}
+
+// Etiqueta <Relationship> con ese Id
+fn relationship(rels: &str, id: &str) -> Option<String> {
+    xlsx_patch::find_tags(rels, "Relationship")
+        .into_iter()
+        .find(|tag| xlsx_patch::xml_attr(tag, "Id").as_deref() == Some(id))
+}
+
+// Notas actuales de una hoja del paquete
+fn read_sheet_notes(package: &XlsxPackage, sheet_name: &str) -> Result<BTreeMap<(usize, usize), Note>> {
+    let parts = note_parts(package, sheet_name)?;
+    Ok(parts
+        .comments
+        .and_then(|comments| package.read_part(&comments))
+        .map(|xml| parse(&xml))
+        .unwrap_or_default())
+}
+
+// Sustituye las notas de una hoja del paquete; sin notas se quitan sus partes.
+// El dibujo VML de la hoja se rehace entero: solo contiene las notas.
+pub fn write_sheet_notes(package: &mut XlsxPackage, sheet_name: &str, notes: &BTreeMap<(usize, usize), Note>) -> Result<()> {
+    let parts = note_parts(package, sheet_name)?;
+    let mut xml = package.read_part(&parts.sheet).context(format!("Falta la parte {}", parts.sheet))?;
+    let mut rels = package.read_part(&parts.rels).unwrap_or_else(|| xlsx_patch::EMPTY_RELS.to_string());
+    let mut types = package.read_part("[Content_Types].xml").context("Falta [Content_Types].xml")?;
+
+    if notes.is_empty() {
+        if let Some(comments) = &parts.comments {
+            package.remove_part(comments);
+            types = remove_tag(&types, "Override", "PartName", &format!("/{}", comments));
+            rels = remove_tag(&rels, "Relationship", "Type", xlsx_patch::COMMENTS_RELATIONSHIP);
+        }
+        if let Some(vml) = &parts.vml {
+            package.remove_part(vml);
+            if let Some(id) = xlsx_patch::find_tags(&xml, "legacyDrawing").first().and_then(|tag| xlsx_patch::xml_attr(tag, "r:id")) {
+                rels = remove_tag(&rels, "Relationship", "Id", &id);
+            }
+            xml = remove_tag(&xml, "legacyDrawing", "r:id", "");
+        }
+        package.write_part(&parts.sheet, xml);
+        package.write_part(&parts.rels, rels);
+        package.write_part("[Content_Types].xml", types);
+        return Ok(());
+    }
+
+    let comments = match parts.comments {
+        Some(comments) => comments,
+        None => {
+            let comments = package.free_part("xl/comments", "xml");
+            (rels, _) = xlsx_patch::add_relationship(&rels, xlsx_patch::COMMENTS_RELATIONSHIP, &comments);
+            types = types.replacen(
+                "</Types>",
+                &format!("<Override PartName=\"/{}\" ContentType=\"{}\"/></Types>", comments, COMMENTS_CONTENT_TYPE),
+                1,
+            );
+            comments
+        }
+    };
+    let vml = match parts.vml {
+        Some(vml) => vml,
+        None => {
+            let vml = package.free_part("xl/drawings/vmlDrawing", "vml");
+            let id;
+            (rels, id) = xlsx_patch::add_relationship(&rels, VML_RELATIONSHIP, &vml);
+            xml = xlsx_patch::insert_worksheet_element(&xml, "legacyDrawing", &format!("<legacyDrawing r:id=\"{}\"/>", id))?;
+            xml = xlsx_patch::with_relationships_namespace(&xml);
+            vml
+        }
+    };
+    if !types.contains("Extension=\"vml\"") {
+        types = types.replacen("<Override", &format!("<Default Extension=\"vml\" ContentType=\"{}\"/><Override", VML_CONTENT_TYPE), 1);
+    }
+    // Cada dibujo VML necesita su propio bloque de identificadores de forma
+    let block: usize = vml
+        .trim_end_matches(".vml")
+        .rsplit(|c: char| !c.is_ascii_digit())
+        .next()
+        .and_then(|digits| digits.parse().ok())
+        .unwrap_or(1);
+
+    package.write_part(&comments, comments_xml(notes));
+    package.write_part(&vml, vml_xml(notes, block));
+    package.write_part(&parts.sheet, xml);
+    package.write_part(&parts.rels, rels);
+    package.write_part("[Content_Types].xml", types);
+    Ok(())
+}
+
+// Quita la etiqueta vacía `<name .../>` cuyo atributo tiene ese valor (con
+// `value` vacío, la primera)
+fn remove_tag(xml: &str, name: &str, attr: &str, value: &str) -> String {
+    let found = xlsx_patch::find_tags(xml, name).into_iter().find(|tag| {
+        value.is_empty() || xlsx_patch::xml_attr(tag, attr).map(|v| xlsx_patch::xml_unescape(&v)).as_deref() == Some(value)
+    });
+    match found {
+        Some(tag) => xml.replacen(&tag, "", 1),
+        None => xml.to_string(),
+    }
+}
+
+fn comments_xml(notes: &BTreeMap<(usize, usize), Note>) -> String {
+    let mut authors: Vec<&str> = Vec::new();
+    for note in notes.values() {
+        let author = if note.author.is_empty() { AUTHOR } else { note.author.as_str() };
+        if !authors.contains(&author) {
+            authors.push(author);
+        }
+    }
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<comments xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\"><authors>",
+    );
+    for author in &authors {
+        xml.push_str(&format!("<author>{}</author>", xlsx_patch::xml_escape(author)));
+    }
+    xml.push_str("</authors><commentList>");
+    for ((row, col), note) in notes {
+        let author = if note.author.is_empty() { AUTHOR } else { note.author.as_str() };
+        let author_id = authors.iter().position(|a| *a == author).unwrap_or(0);
+        xml.push_str(&format!(
+            "<comment ref=\"{}{}\" authorId=\"{}\"><text><t xml:space=\"preserve\">{}</t></text></comment>",
+            excel::column_letters(*col),
+            row + 1,
+            author_id,
+            xlsx_patch::xml_escape(&note.text)
+        ));
+    }
+    xml.push_str("</commentList></comments>");
+    xml
+}
+
+// Cuadros ocultos junto a cada celda, como los crea Excel al insertar una nota
+fn vml_xml(notes: &BTreeMap<(usize, usize), Note>, block: usize) -> String {
+    let mut xml = format!(
+        "<xml xmlns:v=\"urn:schemas-microsoft-com:vml\" xmlns:o=\"urn:schemas-microsoft-com:office:office\" xmlns:x=\"urn:schemas-microsoft-com:office:excel\">\
+<o:shapelayout v:ext=\"edit\"><o:idmap v:ext=\"edit\" data=\"{}\"/></o:shapelayout>\
+<v:shapetype id=\"_x0000_t202\" coordsize=\"21600,21600\" o:spt=\"202\" path=\"m,l,21600r21600,l21600,xe\">\
+<v:stroke joinstyle=\"miter\"/><v:path gradientshapeok=\"t\" o:connecttype=\"rect\"/></v:shapetype>",
+        block
+    );
+    for (idx, (row, col)) in notes.keys().enumerate() {
+        xml.push_str(&format!(
+            "<v:shape id=\"_x0000_s{}\" type=\"#_x0000_t202\" style=\"position:absolute;margin-left:59.25pt;margin-top:1.5pt;width:108pt;height:59.25pt;z-index:{};visibility:hidden\" fillcolor=\"#ffffe1\" o:insetmode=\"auto\">\
+<v:fill color2=\"#ffffe1\"/><v:shadow on=\"t\" color=\"black\" obscured=\"t\"/><v:path o:connecttype=\"none\"/>\
+<v:textbox style=\"mso-direction-alt:auto\"><div style=\"text-align:left\"></div></v:textbox>\
+<x:ClientData ObjectType=\"Note\"><x:MoveWithCells/><x:SizeWithCells/>\
+<x:Anchor>{}, 15, {}, 10, {}, 15, {}, 4</x:Anchor><x:AutoFill>False</x:AutoFill><x:Row>{}</x:Row><x:Column>{}</x:Column></x:ClientData></v:shape>",
+            block * 1024 + idx + 1,
+            idx + 1,
+            col + 1,
+            row.saturating_sub(1),
+            col + 3,
+            row + 3,
+            row,
+            col
+        ));
+    }
+    xml.push_str("</xml>");
+    xml
+}
+
+// Vuelve a escribir tras guardar las notas de las hojas de un libro, que
+// rust_xlsxwriter no sabe escribir
+pub fn restore(path: &Path, sheets: &[SheetData]) -> Result<()> {
+    let pending: Vec<&SheetData> = sheets.iter().filter(|sheet| !sheet.notes.is_empty()).collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let mut package = XlsxPackage::open(path)?;
+    for sheet in pending {
+        write_sheet_notes(&mut package, &sheet.name, &sheet.notes)?;
+    }
+    package.save(path)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NoteAction {
+    Show,
+    Write(String),
+    Remove,
+}
+
+pub struct NoteOptions {
+    pub file: String,
+    // "B2" (primera hoja) o "Hoja!B2"
+    pub cell: String,
+    pub action: NoteAction,
+}
+
+pub struct NoteOutcome {
+    // Celda con su hoja, para los mensajes
+    pub cell: String,
+    // Nota que tenía la celda antes del cambio
+    pub previous: Option<Note>,
+}
+
+// `nota <archivo> <celda> ["texto"|--quitar]`: muestra, escribe o quita la nota
+// de una celda de un libro existente
+pub fn apply(options: &NoteOptions) -> Result<NoteOutcome> {
+    let path = Path::new(&options.file);
+    if !path.exists() {
+        bail!("No existe {}", options.file);
+    }
+    let (sheet_name, cell) = match options.cell.rsplit_once('!') {
+        Some((sheet, cell)) => (Some(sheet.trim_matches('\'').to_string()), cell),
+        None => (None, options.cell.as_str()),
+    };
+    let (row, col) = excel::parse_cell_ref(cell).context(format!("Celda no válida: {}", options.cell))?;
+
+    let mut package = XlsxPackage::open(path)?;
+    let workbook = package.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+    let sheet_name = named_ranges::find_sheet(&workbook, sheet_name.as_deref())?;
+    let mut notes = read_sheet_notes(&package, &sheet_name)?;
+    let description = format!("{}!{}{}", sheet_name, excel::column_letters(col), row + 1);
+    let previous = notes.get(&(row, col)).cloned();
+    match &options.action {
+        NoteAction::Show => {}
+        NoteAction::Write(text) => {
+            if text.trim().is_empty() {
+                bail!("Falta el texto de la nota");
+            }
+            notes.insert((row, col), Note::new(text));
+            write_sheet_notes(&mut package, &sheet_name, &notes)?;
+            package.save(path)?;
+        }
+        NoteAction::Remove => {
+            if notes.remove(&(row, col)).is_some() {
+                write_sheet_notes(&mut package, &sheet_name, &notes)?;
+                package.save(path)?;
+            }
+        }
+    }
+    Ok(NoteOutcome {
+        cell: description,
+        previous,
+    })
+}
@@ -0,0 +1,434 @@
+// Esquema de filas y columnas (Datos > Agrupar en Excel): secciones de detalle
+// que se pliegan con los botones +/- bajo su fila de resumen. rust_xlsxwriter no
+// lo expone, así que se lee del XML de cada hoja a `SheetData::outline`, se
+// vuelve a escribir después de guardar un libro y `agrupar <archivo> 5-20` lo
+// edita directamente en un libro existente. Las plantillas de informe lo usan
+// con "detalle": las filas de cada grupo quedan plegadas bajo su fila de resumen.
+use crate::error::IAgentError;
+use crate::excel;
+use crate::excel::SheetData;
+use crate::formats;
+use crate::layout;
+use crate::named_ranges;
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+// Niveles de esquema que admite Excel
+pub const MAX_LEVEL: u8 = 7;
+
+// Filas o columnas (desde 0, ambos extremos incluidos)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Span {
+    Rows(usize, usize),
+    Columns(usize, usize),
+}
+
+impl Span {
+    // "5-20" o "5:20" son filas; "C-F" o "C:F", columnas
+    pub fn parse(text: &str) -> Option<Span> {
+        let text = text.trim();
+        let (first, last) = text.split_once(['-', ':']).unwrap_or((text, text));
+        let (first, last) = (first.trim(), last.trim());
+        if let (Ok(first), Ok(last)) = (first.parse::<usize>(), last.parse::<usize>()) {
+            if first == 0 || last == 0 {
+                return None;
+            }
+            return Some(Span::Rows(first.min(last) - 1, first.max(last) - 1));
+        }
+        let (first, last) = (excel::column_from_letters(first)?, excel::column_from_letters(last)?);
+        Some(Span::Columns(first.min(last), first.max(last)))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Span::Rows(first, last) if first == last => format!("fila {}", first + 1),
+            Span::Rows(first, last) => format!("filas {}-{}", first + 1, last + 1),
+            Span::Columns(first, last) if first == last => format!("columna {}", excel::column_letters(*first)),
+            Span::Columns(first, last) => {
+                format!("columnas {}-{}", excel::column_letters(*first), excel::column_letters(*last))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outline {
+    // Nivel de esquema (1 a 7) de cada fila y columna agrupada
+    pub rows: BTreeMap<usize, u8>,
+    pub cols: BTreeMap<usize, u8>,
+    // Filas ocultas (las de un grupo contraído, o por otro motivo) y columnas
+    // ocultas por estar en un grupo contraído
+    pub hidden_rows: BTreeSet<usize>,
+    pub hidden_cols: BTreeSet<usize>,
+    // La fila de resumen va encima del detalle y la columna de resumen a su
+    // izquierda; Excel por defecto las pone debajo y a la derecha
+    pub summary_above: bool,
+    pub summary_left: bool,
+}
+
+impl Outline {
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty() && self.cols.is_empty() && self.hidden_rows.is_empty()
+    }
+
+    // Agrupa un nivel más las filas o columnas; `collapse` las oculta bajo su resumen
+    pub fn group(&mut self, span: Span, collapse: bool) -> Result<(), IAgentError> {
+        let (levels, hidden, first, last) = self.parts(span);
+        if let Some(idx) = (first..=last).find(|idx| levels.get(idx).copied().unwrap_or(0) >= MAX_LEVEL) {
+            let full = match span {
+                Span::Rows(..) => Span::Rows(idx, idx),
+                Span::Columns(..) => Span::Columns(idx, idx),
+            };
+            return Err(IAgentError::parse(
+                &full.describe(),
+                format!("Ya tiene {} niveles de esquema, el máximo de Excel", MAX_LEVEL),
+            ));
+        }
+        for idx in first..=last {
+            *levels.entry(idx).or_insert(0) += 1;
+            if collapse {
+                hidden.insert(idx);
+            }
+        }
+        Ok(())
+    }
+
+    // Quita un nivel; las que dejan de estar agrupadas vuelven a verse.
+    // Devuelve cuántas estaban agrupadas.
+    pub fn ungroup(&mut self, span: Span) -> usize {
+        let (levels, hidden, first, last) = self.parts(span);
+        let mut changed = 0;
+        for idx in first..=last {
+            let Some(level) = levels.get_mut(&idx) else { continue };
+            changed += 1;
+            *level -= 1;
+            if *level == 0 {
+                levels.remove(&idx);
+                hidden.remove(&idx);
+            }
+        }
+        changed
+    }
+
+    fn parts(&mut self, span: Span) -> (&mut BTreeMap<usize, u8>, &mut BTreeSet<usize>, usize, usize) {
+        match span {
+            Span::Rows(first, last) => (&mut self.rows, &mut self.hidden_rows, first, last),
+            Span::Columns(first, last) => (&mut self.cols, &mut self.hidden_cols, first, last),
+        }
+    }
+
+    // "filas 5-20 (contraídas), columnas C-F", para `leer_excel` y el resumen
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        for (levels, hidden, rows) in [(&self.rows, &self.hidden_rows, true), (&self.cols, &self.hidden_cols, false)] {
+            for (first, last) in runs(levels, 1) {
+                let span = if rows { Span::Rows(first, last) } else { Span::Columns(first, last) };
+                let mut text = span.describe();
+                let depth = (first..=last).filter_map(|idx| levels.get(&idx)).max().copied().unwrap_or(1);
+                if depth > 1 {
+                    text.push_str(&format!(" ({} niveles)", depth));
+                }
+                if (first..=last).all(|idx| hidden.contains(&idx)) {
+                    text.push_str(" (contraídas)");
+                }
+                parts.push(text);
+            }
+        }
+        parts.join(", ")
+    }
+}
+
+// Intervalos de índices consecutivos con nivel `level` o más
+fn runs(levels: &BTreeMap<usize, u8>, level: u8) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (idx, _) in levels.iter().filter(|(_, l)| **l >= level) {
+        match runs.last_mut() {
+            Some((_, last)) if *last + 1 == *idx => *last = *idx,
+            _ => runs.push((*idx, *idx)),
+        }
+    }
+    runs
+}
+
+fn is_true(value: Option<String>) -> bool {
+    matches!(value.as_deref(), Some("1" | "true"))
+}
+
+// Esquema declarado en el XML de una hoja
+pub fn parse(sheet_xml: &str) -> Outline {
+    let mut outline = Outline::default();
+    for tag in xlsx_patch::find_tags(sheet_xml, "row") {
+        let Some(row) = xlsx_patch::xml_attr(&tag, "r").and_then(|r| r.parse::<usize>().ok()).filter(|r| *r > 0) else {
+            continue;
+        };
+        if let Some(level) = xlsx_patch::xml_attr(&tag, "outlineLevel").and_then(|l| l.parse::<u8>().ok()).filter(|l| *l > 0) {
+            outline.rows.insert(row - 1, level.min(MAX_LEVEL));
+        }
+        if is_true(xlsx_patch::xml_attr(&tag, "hidden")) {
+            outline.hidden_rows.insert(row - 1);
+        }
+    }
+    for tag in xlsx_patch::find_tags(sheet_xml, "col") {
+        let (Some(min), Some(max), Some(level)) = (
+            xlsx_patch::xml_attr(&tag, "min").and_then(|v| v.parse::<usize>().ok()).filter(|v| *v > 0),
+            xlsx_patch::xml_attr(&tag, "max").and_then(|v| v.parse::<usize>().ok()),
+            xlsx_patch::xml_attr(&tag, "outlineLevel").and_then(|l| l.parse::<u8>().ok()).filter(|l| *l > 0),
+        ) else {
+            continue;
+        };
+        let hidden = is_true(xlsx_patch::xml_attr(&tag, "hidden"));
+        for col in min - 1..max.min(formats::MAX_LAYOUT_COLUMNS) {
+            outline.cols.insert(col, level.min(MAX_LEVEL));
+            if hidden {
+                outline.hidden_cols.insert(col);
+            }
+        }
+    }
+    if let Some(tag) = xlsx_patch::find_tags(sheet_xml, "outlinePr").first() {
+        outline.summary_above = xlsx_patch::xml_attr(tag, "summaryBelow").as_deref() == Some("0");
+        outline.summary_left = xlsx_patch::xml_attr(tag, "summaryRight").as_deref() == Some("0");
+    }
+    outline
+}
+
+// Escribe el esquema en el XML de una hoja, sustituyendo el que tuviera
+pub fn apply_xml(xml: &str, outline: &Outline) -> Result<String> {
+    let before = parse(xml);
+    if before == *outline {
+        return Ok(xml.to_string());
+    }
+
+    // La fila o columna de resumen de un grupo contraído lleva collapsed="1"
+    let collapsed = |levels: &BTreeMap<usize, u8>, hidden: &BTreeSet<usize>, before_first: bool| -> BTreeSet<usize> {
+        let depth = levels.values().max().copied().unwrap_or(0);
+        (1..=depth)
+            .flat_map(|level| runs(levels, level))
+            .filter(|(first, last)| (*first..=*last).all(|idx| hidden.contains(&idx)))
+            .filter_map(|(first, last)| if before_first { first.checked_sub(1) } else { Some(last + 1) })
+            .collect()
+    };
+    let collapsed_rows = collapsed(&outline.rows, &outline.hidden_rows, outline.summary_above);
+    let collapsed_cols = collapsed(&outline.cols, &outline.hidden_cols, outline.summary_left);
+
+    let row_targets: BTreeSet<usize> = [&before.rows, &outline.rows]
+        .iter()
+        .flat_map(|levels| levels.keys().copied())
+        .chain(before.hidden_rows.iter().chain(&outline.hidden_rows).chain(&collapsed_rows).copied())
+        .collect();
+    let mut xml = set_rows(xml, &row_targets, |row| {
+        let mut attrs = String::new();
+        if let Some(level) = outline.rows.get(&row) {
+            attrs.push_str(&format!(" outlineLevel=\"{}\"", level));
+        }
+        if outline.hidden_rows.contains(&row) {
+            attrs.push_str(" hidden=\"1\"");
+        }
+        if collapsed_rows.contains(&row) {
+            attrs.push_str(" collapsed=\"1\"");
+        }
+        attrs
+    })?;
+
+    let col_targets: BTreeSet<usize> = [&before.cols, &outline.cols]
+        .iter()
+        .flat_map(|levels| levels.keys().copied())
+        .chain(before.hidden_cols.iter().chain(&outline.hidden_cols).chain(&collapsed_cols).copied())
+        .collect();
+    xml = layout::edit_columns(&xml, &col_targets, |col, range| {
+        range.remove("outlineLevel");
+        range.remove("collapsed");
+        if let Some(level) = outline.cols.get(&col) {
+            range.set("outlineLevel", level.to_string());
+        }
+        if outline.hidden_cols.contains(&col) {
+            range.set("hidden", "1".to_string());
+        } else if before.hidden_cols.contains(&col) {
+            range.remove("hidden");
+        }
+        if collapsed_cols.contains(&col) {
+            range.set("collapsed", "1".to_string());
+        }
+    })?;
+
+    // Excel reserva el margen de los botones según el nivel más alto
+    let row_depth = outline.rows.values().max().copied().unwrap_or(0);
+    let col_depth = outline.cols.values().max().copied().unwrap_or(0);
+    let mut levels = String::new();
+    if row_depth > 0 {
+        levels.push_str(&format!(" outlineLevelRow=\"{}\"", row_depth));
+    }
+    if col_depth > 0 {
+        levels.push_str(&format!(" outlineLevelCol=\"{}\"", col_depth));
+    }
+    xml = match xlsx_patch::find_tags(&xml, "sheetFormatPr").first() {
+        Some(tag) => {
+            let close = if tag.ends_with("/>") { "/>" } else { ">" };
+            let body = tag.trim_end_matches(close).trim_end();
+            let body = xlsx_patch::strip_attr(&xlsx_patch::strip_attr(body, "outlineLevelRow"), "outlineLevelCol");
+            xml.replacen(tag.as_str(), &format!("{}{}{}", body, levels, close), 1)
+        }
+        None if !levels.is_empty() => {
+            xlsx_patch::insert_worksheet_element(&xml, "sheetFormatPr", &format!("<sheetFormatPr defaultRowHeight=\"15\"{}/>", levels))?
+        }
+        None => xml,
+    };
+
+    set_summary_position(&xml, outline.summary_above, outline.summary_left)
+}
+
+// Reescribe la etiqueta <row> de las filas de `targets` con los atributos de
+// esquema de `attrs`, creando las que no existen
+fn set_rows<F>(xml: &str, targets: &BTreeSet<usize>, attrs: F) -> Result<String>
+where
+    F: Fn(usize) -> String,
+{
+    if targets.is_empty() {
+        return Ok(xml.to_string());
+    }
+    let xml = match xlsx_patch::find_tags(xml, "sheetData").first() {
+        Some(tag) if tag.ends_with("/>") => xml.replacen(tag.as_str(), "<sheetData></sheetData>", 1),
+        _ => xml.to_string(),
+    };
+    let data_start = xlsx_patch::find_element_start(&xml, "sheetData").context("La hoja no tiene sheetData")?;
+    let open_end = data_start + xml[data_start..].find('>').context("XML de hoja no válido")? + 1;
+    let data_end = open_end + xml[open_end..].find("</sheetData>").context("XML de hoja no válido")?;
+
+    let new_row = |row: usize| {
+        let attrs = attrs(row);
+        (!attrs.is_empty()).then(|| format!("<row r=\"{}\"{}/>", row + 1, attrs))
+    };
+    let mut pending = targets.iter().copied().peekable();
+    let mut output = String::with_capacity(xml.len() + targets.len() * 40);
+    output.push_str(&xml[..open_end]);
+    let mut offset = open_end;
+    while let Some(found) = xlsx_patch::find_element_start(&xml[offset..data_end], "row") {
+        let start = offset + found;
+        let tag_end = start + xml[start..].find('>').context("XML de hoja no válido")? + 1;
+        let tag = &xml[start..tag_end];
+        let row = xlsx_patch::xml_attr(tag, "r").and_then(|r| r.parse::<usize>().ok()).unwrap_or(1).saturating_sub(1);
+        output.push_str(&xml[offset..start]);
+        while let Some(missing) = pending.next_if(|target| *target < row) {
+            output.extend(new_row(missing));
+        }
+        if pending.next_if_eq(&row).is_some() {
+            let close = if tag.ends_with("/>") { "/>" } else { ">" };
+            let mut body = tag.trim_end_matches(close).trim_end().to_string();
+            for name in ["outlineLevel", "hidden", "collapsed"] {
+                body = xlsx_patch::strip_attr(&body, name);
+            }
+            output.push_str(&format!("{}{}{}", body, attrs(row), close));
+        } else {
+            output.push_str(tag);
+        }
+        offset = tag_end;
+    }
+    output.push_str(&xml[offset..data_end]);
+    for missing in pending {
+        output.extend(new_row(missing));
+    }
+    output.push_str(&xml[data_end..]);
+    Ok(output)
+}
+
+// Posición de las filas y columnas de resumen, en <sheetPr><outlinePr>
+fn set_summary_position(xml: &str, above: bool, left: bool) -> Result<String> {
+    let mut xml = match xlsx_patch::find_tags(xml, "outlinePr").first() {
+        Some(tag) => xml.replacen(tag.as_str(), "", 1),
+        None => xml.to_string(),
+    };
+    if !above && !left {
+        return Ok(xml);
+    }
+    let mut element = String::from("<outlinePr");
+    if above {
+        element.push_str(" summaryBelow=\"0\"");
+    }
+    if left {
+        element.push_str(" summaryRight=\"0\"");
+    }
+    element.push_str("/>");
+    let Some(tag) = xlsx_patch::find_tags(&xml, "sheetPr").first().cloned() else {
+        return xlsx_patch::insert_worksheet_element(&xml, "sheetPr", &format!("<sheetPr>{}</sheetPr>", element));
+    };
+    if tag.ends_with("/>") {
+        let open = tag.trim_end_matches("/>").trim_end();
+        xml = xml.replacen(&tag, &format!("{}>{}</sheetPr>", open, element), 1);
+        return Ok(xml);
+    }
+    // <outlinePr> va después de <tabColor>, si lo hay
+    let start = xlsx_patch::find_element_start(&xml, "sheetPr").context("XML de hoja no válido")?;
+    let mut insert_at = start + tag.len();
+    if xml[insert_at..].starts_with("<tabColor") {
+        insert_at += xml[insert_at..].find('>').map_or(0, |end| end + 1);
+    }
+    xml.insert_str(insert_at, &element);
+    Ok(xml)
+}
+
+// Vuelve a escribir tras guardar el esquema de las hojas de un libro, que
+// rust_xlsxwriter no sabe escribir
+pub fn restore(path: &Path, sheets: &[SheetData]) -> Result<()> {
+    let pending: Vec<&SheetData> = sheets.iter().filter(|sheet| !sheet.outline.is_empty()).collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let mut package = XlsxPackage::open(path)?;
+    for sheet in pending {
+        package.edit_sheet(&sheet.name, |xml| apply_xml(&xml, &sheet.outline))?;
+    }
+    package.save(path)
+}
+
+#[derive(Debug, Clone)]
+pub struct GroupOptions {
+    pub file: String,
+    pub sheet: Option<String>,
+    pub span: Span,
+    pub ungroup: bool,
+    pub collapse: bool,
+    // resumen=arriba|abajo (filas) o izquierda|derecha (columnas); por defecto,
+    // en una hoja sin esquema, el resumen va antes que el detalle
+    pub summary_before: Option<bool>,
+}
+
+pub struct GroupOutcome {
+    pub sheet: String,
+    pub outline: Outline,
+    // Filas o columnas que estaban agrupadas, al desagrupar
+    pub ungrouped: usize,
+}
+
+// `agrupar <archivo> [Hoja!]5-20` / `desagrupar ...`, sin reescribir el resto del libro
+pub fn apply(options: &GroupOptions) -> Result<GroupOutcome> {
+    let path = Path::new(&options.file);
+    let mut package = XlsxPackage::open(path)?;
+    let workbook = package.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+    let sheet = named_ranges::find_sheet(&workbook, options.sheet.as_deref())?;
+    let part = package.sheet_part(&sheet)?;
+    let xml = package.read_part(&part).context(format!("Falta la parte {}", part))?;
+    let mut outline = parse(&xml);
+
+    let mut ungrouped = 0;
+    if options.ungroup {
+        ungrouped = outline.ungroup(options.span);
+        if ungrouped == 0 {
+            bail!("{} de la hoja {} no están agrupadas", options.span.describe(), sheet);
+        }
+    } else {
+        let rows = matches!(options.span, Span::Rows(..));
+        let fresh = if rows { outline.rows.is_empty() } else { outline.cols.is_empty() };
+        if let Some(before) = options.summary_before.or(fresh.then_some(true)) {
+            if rows {
+                outline.summary_above = before;
+            } else {
+                outline.summary_left = before;
+            }
+        }
+        outline.group(options.span, options.collapse)?;
+    }
+    package.write_part(&part, apply_xml(&xml, &outline)?);
+    package.save(path)?;
+    Ok(GroupOutcome { sheet, outline, ungrouped })
+}
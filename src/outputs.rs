@@ -0,0 +1,146 @@
+// Destino de los archivos que genera el agente (informes, exportaciones, respuestas
+// en lote, comparaciones, estadísticas, conversiones y sesiones exportadas). Con un directorio de
+// salida (IAGENT_OUTPUT_DIR o --dir-salida) las rutas relativas van a parar a él en
+// lugar de al directorio actual, y IAGENT_OUTPUT_NAMING decide qué hacer si el
+// archivo ya existe:
+//   - sobrescribir (por defecto): se reemplaza, como siempre
+//   - sufijo: informe.xlsx → informe (2).xlsx, informe (3).xlsx...
+//   - fecha: siempre se añade la fecha y hora, informe_20261014-153000.xlsx
+// Los comandos que modifican un libro existente no pasan por aquí.
+use crate::excel;
+use crate::settings;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Naming {
+    #[default]
+    Overwrite,
+    Suffix,
+    Timestamp,
+}
+
+impl Naming {
+    pub fn parse(value: &str) -> Option<Naming> {
+        match value.trim().to_lowercase().as_str() {
+            "sobrescribir" | "overwrite" => Some(Naming::Overwrite),
+            "sufijo" | "suffix" => Some(Naming::Suffix),
+            "fecha" | "timestamp" => Some(Naming::Timestamp),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OutputPolicy {
+    pub dir: Option<PathBuf>,
+    pub naming: Naming,
+}
+
+fn policy() -> OutputPolicy {
+    settings::current().outputs()
+}
+
+// Ruta final de un archivo generado que el usuario ha nombrado (o uno por defecto)
+pub fn target(requested: &str) -> Result<String> {
+    let requested = requested.trim();
+    if requested.is_empty() {
+        bail!("La ruta de salida está vacía");
+    }
+    let policy = policy();
+    let path = match &policy.dir {
+        Some(dir) if Path::new(requested).is_relative() => dir.join(requested),
+        _ => PathBuf::from(requested),
+    };
+    finish(path, policy.naming)
+}
+
+// Ruta de un archivo generado a partir de otro (ventas.xlsx → ventas.pdf): junto
+// al original, o en el directorio de salida si hay uno
+pub fn derived(source: &Path, file_name: &str) -> Result<String> {
+    let policy = policy();
+    let file_name = safe_file_name(file_name);
+    let path = match &policy.dir {
+        Some(dir) => dir.join(file_name),
+        None => source.with_file_name(file_name),
+    };
+    finish(path, policy.naming)
+}
+
+// Directorio para varios archivos generados (convertir --salida)
+pub fn target_dir(requested: Option<&str>) -> String {
+    match (requested, policy().dir) {
+        (Some(dir), Some(base)) if Path::new(dir).is_relative() => base.join(dir).to_string_lossy().into_owned(),
+        (Some(dir), _) => dir.to_string(),
+        (None, Some(base)) => base.to_string_lossy().into_owned(),
+        (None, None) => ".".to_string(),
+    }
+}
+
+// Aplica la política de nombres a una ruta ya decidida (p. ej. cada archivo de convertir)
+pub fn unique(path: PathBuf) -> PathBuf {
+    available(path, policy().naming)
+}
+
+fn finish(path: PathBuf, naming: Naming) -> Result<String> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).context(format!("No se pudo crear el directorio {}", parent.display()))?;
+    }
+    Ok(available(path, naming).to_string_lossy().into_owned())
+}
+
+fn available(path: PathBuf, naming: Naming) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let with_name = |name: &str| path.with_file_name(format!("{}{}", name, extension));
+    let base = match naming {
+        Naming::Overwrite => return path,
+        Naming::Suffix => stem,
+        Naming::Timestamp => format!("{}_{}", stem, timestamp()),
+    };
+    let first = with_name(&base);
+    if !first.exists() {
+        return first;
+    }
+    // Dos ejecuciones en el mismo segundo también reciben un sufijo
+    (2..)
+        .map(|n| with_name(&format!("{} ({})", base, n)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(first)
+}
+
+// AAAAMMDD-HHMMSS (UTC)
+fn timestamp() -> String {
+    excel::excel_serial_to_iso(excel::now_serial())
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | ':' => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+// Nombre de archivo válido en cualquier sistema a partir de un texto (p. ej. el
+// nombre de una hoja); conserva espacios y acentos
+pub fn safe_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    // Windows no admite nombres terminados en punto o espacio
+    let cleaned = cleaned.trim().trim_end_matches('.').to_string();
+    if cleaned.is_empty() {
+        "salida".to_string()
+    } else {
+        cleaned
+    }
+}
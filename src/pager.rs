@@ -0,0 +1,119 @@
+// Resultados largos por páginas: las tablas de resultados y `mostrar` enseñan
+// una página (IAGENT_PAGE_SIZE o --page-size filas, 20 por defecto) y se recorren
+// con `siguiente`, `anterior` y `pagina <n>`, en lugar de llenar la terminal o
+// cortarse sin avisar. El resultado completo se conserva: `copiar tabla` lo copia
+// entero y `enviar_resultado` se lo pasa al modelo.
+use crate::clipboard;
+use crate::settings;
+use crate::table;
+use anyhow::{bail, Result};
+use std::sync::Mutex;
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+
+// Filas por página del agente en curso; 0 muestra los resultados enteros
+fn page_size() -> usize {
+    match settings::current().page_size().unwrap_or(DEFAULT_PAGE_SIZE) {
+        0 => usize::MAX,
+        size => size,
+    }
+}
+
+struct Paged {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    // Celdas recortadas y columnas ajustadas a la terminal, como en `mostrar`
+    fitted: bool,
+    // Filas de la hoja de la que sale, si el resultado es solo una parte
+    source_rows: Option<usize>,
+    page: usize,
+}
+
+impl Paged {
+    fn pages(&self) -> usize {
+        self.rows.len().div_ceil(page_size()).max(1)
+    }
+
+    fn render(&self) -> String {
+        let size = page_size();
+        let start = (self.page * size).min(self.rows.len());
+        let end = start.saturating_add(size).min(self.rows.len());
+        let rows = &self.rows[start..end];
+        let (mut output, hidden) = if self.fitted {
+            let (output, visible, total) = table::render_fitted(&self.headers, rows);
+            (output, (visible < total).then(|| format!(", {} de {} columnas", visible, total)))
+        } else {
+            (table::render_table(&self.headers, rows), None)
+        };
+        let total = match self.source_rows {
+            Some(source) if source > self.rows.len() => format!("{} de las {} filas de la hoja", self.rows.len(), source),
+            _ => format!("{} filas", self.rows.len()),
+        };
+        if self.pages() > 1 {
+            output.push_str(&format!(
+                "\n(filas {}-{} de {}{}; página {} de {}: siguiente, anterior o pagina <n>)",
+                start + 1,
+                end,
+                total,
+                hidden.unwrap_or_default(),
+                self.page + 1,
+                self.pages()
+            ));
+        } else if self.fitted {
+            output.push_str(&format!("\n({}{})", total, hidden.unwrap_or_default()));
+        }
+        output
+    }
+}
+
+static CURRENT: Mutex<Option<Paged>> = Mutex::new(None);
+
+// Primera página de un resultado, que pasa a ser el que recorren `siguiente` y `anterior`
+pub fn show(headers: &[String], rows: &[Vec<String>], fitted: bool, source_rows: Option<usize>) -> String {
+    clipboard::remember_table(headers, rows);
+    let paged = Paged {
+        headers: headers.to_vec(),
+        rows: rows.to_vec(),
+        fitted,
+        source_rows,
+        page: 0,
+    };
+    let output = paged.render();
+    if let Ok(mut current) = CURRENT.lock() {
+        *current = Some(paged);
+    }
+    output
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Move {
+    Next,
+    Previous,
+    // Desde 1
+    Page(usize),
+}
+
+pub fn turn(movement: Move) -> Result<String> {
+    let mut current = CURRENT.lock().map_err(|_| anyhow::anyhow!("Resultado no disponible"))?;
+    let Some(paged) = current.as_mut() else {
+        bail!("No hay ningún resultado que recorrer; usa mostrar o un comando de análisis");
+    };
+    let pages = paged.pages();
+    let page = match movement {
+        Move::Next if paged.page + 1 >= pages => bail!("Ya estás en la última página ({} de {})", pages, pages),
+        Move::Next => paged.page + 1,
+        Move::Previous if paged.page == 0 => bail!("Ya estás en la primera página"),
+        Move::Previous => paged.page - 1,
+        Move::Page(n) if n == 0 || n > pages => bail!("El resultado tiene {} página(s)", pages),
+        Move::Page(n) => n - 1,
+    };
+    paged.page = page;
+    Ok(paged.render())
+}
+
+// El resultado entero como tabla, para el historial; None si no hay ninguno
+pub fn full_result() -> Option<(String, usize)> {
+    let current = CURRENT.lock().ok()?;
+    let paged = current.as_ref()?;
+    Some((table::render_table(&paged.headers, &paged.rows), paged.rows.len()))
+}
@@ -0,0 +1,1222 @@
+// Lectura y escritura de Parquet para `convertir`, sin dependencias externas. Una
+// hoja es un archivo con un único grupo de filas: los encabezados son los nombres
+// de las columnas y cada columna es opcional (las celdas vacías son nulos).
+//
+// Se escribe en codificación PLAIN, sin comprimir y en páginas de datos v1, con el
+// tipo de cada columna: número (DOUBLE), booleano (BOOLEAN), fecha (INT64 con
+// TIMESTAMP_MILLIS) o texto (BYTE_ARRAY con UTF8). Una columna que mezcla tipos se
+// guarda como texto. Al leer se admite además lo que suelen escribir otras
+// herramientas: diccionarios, páginas v2, compresión SNAPPY o GZIP y los tipos
+// enteros, decimales y de fecha habituales. Los esquemas anidados no.
+use crate::backup;
+use crate::events;
+use crate::excel::{self, CellValue, SheetData};
+use crate::verify;
+use anyhow::{bail, Context, Result};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+const MAGIC: &[u8] = b"PAR1";
+// Serie de Excel del 1970-01-01, origen de las fechas de Parquet
+const UNIX_EPOCH_SERIAL: f64 = 25569.0;
+const MILLIS_PER_DAY: f64 = 86_400_000.0;
+// Anidamiento máximo de los metadatos, para no desbordar la pila con un archivo dañado
+const MAX_DEPTH: usize = 32;
+
+// Tipos físicos
+const TYPE_BOOLEAN: i64 = 0;
+const TYPE_INT32: i64 = 1;
+const TYPE_INT64: i64 = 2;
+const TYPE_INT96: i64 = 3;
+const TYPE_FLOAT: i64 = 4;
+const TYPE_DOUBLE: i64 = 5;
+const TYPE_BYTE_ARRAY: i64 = 6;
+const TYPE_FIXED_LEN_BYTE_ARRAY: i64 = 7;
+
+// Tipos convertidos (las anotaciones antiguas, que entienden todos los lectores)
+const CONVERTED_UTF8: i64 = 0;
+const CONVERTED_ENUM: i64 = 4;
+const CONVERTED_DECIMAL: i64 = 5;
+const CONVERTED_DATE: i64 = 6;
+const CONVERTED_TIMESTAMP_MILLIS: i64 = 9;
+const CONVERTED_TIMESTAMP_MICROS: i64 = 10;
+const CONVERTED_JSON: i64 = 19;
+
+const ENCODING_PLAIN: i64 = 0;
+const ENCODING_PLAIN_DICTIONARY: i64 = 2;
+const ENCODING_RLE: i64 = 3;
+const ENCODING_RLE_DICTIONARY: i64 = 8;
+
+const CODEC_UNCOMPRESSED: i64 = 0;
+const CODEC_SNAPPY: i64 = 1;
+const CODEC_GZIP: i64 = 2;
+
+const PAGE_DATA: i64 = 0;
+const PAGE_DICTIONARY: i64 = 2;
+const PAGE_DATA_V2: i64 = 3;
+
+const REPETITION_REQUIRED: i64 = 0;
+const REPETITION_OPTIONAL: i64 = 1;
+
+// Valor del protocolo compacto de Thrift, en el que van los metadatos y las
+// cabeceras de página
+#[derive(Debug, Clone, PartialEq)]
+enum Thrift {
+    Bool(bool),
+    I32(i32),
+    I64(i64),
+    Double(f64),
+    Binary(Vec<u8>),
+    List(Vec<Thrift>),
+    Struct(BTreeMap<i16, Thrift>),
+}
+
+fn fields(entries: Vec<(i16, Thrift)>) -> Thrift {
+    Thrift::Struct(entries.into_iter().collect())
+}
+
+fn text(value: &str) -> Thrift {
+    Thrift::Binary(value.as_bytes().to_vec())
+}
+
+impl Thrift {
+    fn type_code(&self) -> u8 {
+        match self {
+            Thrift::Bool(true) => 1,
+            Thrift::Bool(false) => 2,
+            Thrift::I32(_) => 5,
+            Thrift::I64(_) => 6,
+            Thrift::Double(_) => 7,
+            Thrift::Binary(_) => 8,
+            Thrift::List(_) => 9,
+            Thrift::Struct(_) => 12,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            // Solo dentro de listas: en un campo el valor va en el tipo
+            Thrift::Bool(value) => out.push(if *value { 1 } else { 2 }),
+            Thrift::I32(value) => write_varint(out, zigzag(i64::from(*value))),
+            Thrift::I64(value) => write_varint(out, zigzag(*value)),
+            Thrift::Double(value) => out.extend_from_slice(&value.to_le_bytes()),
+            Thrift::Binary(bytes) => {
+                write_varint(out, bytes.len() as u64);
+                out.extend_from_slice(bytes);
+            }
+            Thrift::List(items) => {
+                // Los booleanos de una lista llevan el tipo 1
+                let code = match items.first() {
+                    Some(Thrift::Bool(_)) => 1,
+                    Some(item) => item.type_code(),
+                    None => 12,
+                };
+                if items.len() < 15 {
+                    out.push((items.len() as u8) << 4 | code);
+                } else {
+                    out.push(0xf0 | code);
+                    write_varint(out, items.len() as u64);
+                }
+                for item in items {
+                    item.write(out);
+                }
+            }
+            Thrift::Struct(entries) => {
+                let mut last = 0;
+                for (id, value) in entries {
+                    let code = value.type_code();
+                    match id - last {
+                        delta @ 1..=15 => out.push((delta as u8) << 4 | code),
+                        _ => {
+                            out.push(code);
+                            write_varint(out, zigzag(i64::from(*id)));
+                        }
+                    }
+                    if !matches!(value, Thrift::Bool(_)) {
+                        value.write(out);
+                    }
+                    last = *id;
+                }
+                out.push(0);
+            }
+        }
+    }
+
+    fn field(&self, id: i16) -> Option<&Thrift> {
+        match self {
+            Thrift::Struct(entries) => entries.get(&id),
+            _ => None,
+        }
+    }
+
+    fn int(&self, id: i16) -> Option<i64> {
+        match self.field(id)? {
+            Thrift::I32(value) => Some(i64::from(*value)),
+            Thrift::I64(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn boolean(&self, id: i16) -> Option<bool> {
+        match self.field(id)? {
+            Thrift::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn string(&self, id: i16) -> Option<String> {
+        match self.field(id)? {
+            Thrift::Binary(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            _ => None,
+        }
+    }
+
+    fn list(&self, id: i16) -> &[Thrift] {
+        match self.field(id) {
+            Some(Thrift::List(items)) => items,
+            _ => &[],
+        }
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+// Lectura secuencial de bytes: metadatos, cabeceras y valores de las páginas
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        ByteReader { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(count).filter(|end| *end <= self.bytes.len());
+        let Some(end) = end else {
+            bail!("datos cortados (se esperaban {} bytes más)", count);
+        };
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("entero variable demasiado largo")
+    }
+
+    fn zigzag(&mut self) -> Result<i64> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn thrift(&mut self, code: u8, depth: usize) -> Result<Thrift> {
+        if depth > MAX_DEPTH {
+            bail!("metadatos anidados en exceso");
+        }
+        Ok(match code {
+            1 => Thrift::Bool(true),
+            2 => Thrift::Bool(false),
+            3 => Thrift::I32(i32::from(self.byte()? as i8)),
+            4 | 5 => Thrift::I32(self.zigzag()? as i32),
+            6 => Thrift::I64(self.zigzag()?),
+            7 => Thrift::Double(f64::from_le_bytes(self.take(8)?.try_into()?)),
+            8 => {
+                let length = self.varint()? as usize;
+                Thrift::Binary(self.take(length)?.to_vec())
+            }
+            9 | 10 => {
+                let header = self.byte()?;
+                let size = match header >> 4 {
+                    15 => self.varint()? as usize,
+                    size => size as usize,
+                };
+                let element = header & 0x0f;
+                let mut items = Vec::new();
+                for _ in 0..size {
+                    items.push(match element {
+                        1 | 2 => Thrift::Bool(self.byte()? == 1),
+                        _ => self.thrift(element, depth + 1)?,
+                    });
+                }
+                Thrift::List(items)
+            }
+            11 => {
+                // Parquet no usa mapas: se leen para saltarlos
+                let size = self.varint()? as usize;
+                let mut items = Vec::new();
+                if size > 0 {
+                    let types = self.byte()?;
+                    for _ in 0..size {
+                        items.push(self.thrift(types >> 4, depth + 1)?);
+                        items.push(self.thrift(types & 0x0f, depth + 1)?);
+                    }
+                }
+                Thrift::List(items)
+            }
+            12 => {
+                let mut entries = BTreeMap::new();
+                let mut last: i16 = 0;
+                loop {
+                    let header = self.byte()?;
+                    if header == 0 {
+                        break;
+                    }
+                    let id = match header >> 4 {
+                        0 => self.zigzag()? as i16,
+                        delta => last.wrapping_add(i16::from(delta)),
+                    };
+                    entries.insert(id, self.thrift(header & 0x0f, depth + 1)?);
+                    last = id;
+                }
+                Thrift::Struct(entries)
+            }
+            other => bail!("tipo Thrift {} desconocido", other),
+        })
+    }
+}
+
+// Tipo con el que se guarda cada columna de la hoja
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind {
+    Number,
+    Bool,
+    DateTime,
+    Text,
+}
+
+impl ColumnKind {
+    fn physical(self) -> i64 {
+        match self {
+            ColumnKind::Number => TYPE_DOUBLE,
+            ColumnKind::Bool => TYPE_BOOLEAN,
+            ColumnKind::DateTime => TYPE_INT64,
+            ColumnKind::Text => TYPE_BYTE_ARRAY,
+        }
+    }
+
+    fn converted(self) -> Option<i64> {
+        match self {
+            ColumnKind::DateTime => Some(CONVERTED_TIMESTAMP_MILLIS),
+            ColumnKind::Text => Some(CONVERTED_UTF8),
+            _ => None,
+        }
+    }
+
+    // Un único tipo en todas las celdas con valor; si no, texto
+    fn of(cells: &[&CellValue]) -> ColumnKind {
+        let mut kinds = cells.iter().filter_map(|cell| match cell {
+            CellValue::Empty => None,
+            CellValue::Number(_) => Some(ColumnKind::Number),
+            CellValue::Bool(_) => Some(ColumnKind::Bool),
+            CellValue::DateTime(_) => Some(ColumnKind::DateTime),
+            CellValue::Text(_) | CellValue::Error(_) => Some(ColumnKind::Text),
+        });
+        match kinds.next() {
+            Some(first) if kinds.all(|kind| kind == first) => first,
+            _ => ColumnKind::Text,
+        }
+    }
+}
+
+// Las columnas de una hoja tal como se guardan: nombre, tipo y celdas de datos
+struct Columns<'a> {
+    names: Vec<String>,
+    kinds: Vec<ColumnKind>,
+    cells: Vec<Vec<&'a CellValue>>,
+}
+
+impl<'a> Columns<'a> {
+    fn of(sheet: &'a SheetData) -> Columns<'a> {
+        let rows = &sheet.rows[sheet.data_start().min(sheet.rows.len())..];
+        let width = sheet.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut headers = sheet.headers();
+        headers.resize_with(width, String::new);
+        // Los nombres vacíos toman la letra de la columna y los repetidos un sufijo
+        let mut names: Vec<String> = Vec::with_capacity(width);
+        for (index, header) in headers.iter().enumerate() {
+            let base = match (header.trim().is_empty(), sheet.headerless) {
+                (true, true) => format!("Columna {}", excel::column_letters(index)),
+                (true, false) => excel::column_letters(index),
+                _ => header.clone(),
+            };
+            let mut name = base.clone();
+            let mut copy = 2;
+            while names.contains(&name) {
+                name = format!("{} ({})", base, copy);
+                copy += 1;
+            }
+            names.push(name);
+        }
+        let cells: Vec<Vec<&CellValue>> = (0..width)
+            .map(|column| rows.iter().map(|row| row.get(column).unwrap_or(&CellValue::Empty)).collect())
+            .collect();
+        let kinds = cells.iter().map(|column| ColumnKind::of(column)).collect();
+        Columns { names, kinds, cells }
+    }
+
+    // Columnas que se guardan como texto aunque tengan números, fechas o booleanos
+    fn mixed(&self) -> Vec<&str> {
+        self.names
+            .iter()
+            .zip(&self.kinds)
+            .zip(&self.cells)
+            .filter(|((_, kind), cells)| {
+                **kind == ColumnKind::Text
+                    && cells.iter().any(|cell| !matches!(cell, CellValue::Empty | CellValue::Text(_) | CellValue::Error(_)))
+            })
+            .map(|((name, _), _)| name.as_str())
+            .collect()
+    }
+}
+
+// Lo que devuelve la lectura del Parquet de una hoja: los nombres de columna
+// como primera fila (salvo en una hoja sin encabezados) y las columnas con tipos
+// mezclados como texto. Sirve para validar la conversión celda a celda.
+pub fn as_stored(sheet: &SheetData) -> SheetData {
+    let columns = Columns::of(sheet);
+    let mut stored = SheetData::new(&sheet.name);
+    stored.headerless = sheet.headerless;
+    if !sheet.headerless {
+        stored.rows.push(columns.names.iter().map(|name| CellValue::Text(name.clone())).collect());
+    }
+    let height = columns.cells.first().map_or(0, Vec::len);
+    for row in 0..height {
+        stored.rows.push(
+            columns
+                .cells
+                .iter()
+                .zip(&columns.kinds)
+                .map(|(cells, kind)| match (kind, cells[row]) {
+                    (_, CellValue::Empty) => CellValue::Empty,
+                    (ColumnKind::Text, cell) => CellValue::Text(cell.to_string()),
+                    (_, cell) => cell.clone(),
+                })
+                .collect(),
+        );
+    }
+    stored
+}
+
+pub fn write(path: &Path, sheet: &SheetData) -> Result<()> {
+    let columns = Columns::of(sheet);
+    let mixed = columns.mixed();
+    if !mixed.is_empty() {
+        events::notice(format!(
+            "⚠️  {}: {} mezcla{} tipos y se guarda{} como texto",
+            path.display(),
+            mixed.join(", "),
+            if mixed.len() > 1 { "n" } else { "" },
+            if mixed.len() > 1 { "n" } else { "" }
+        ));
+    }
+    let bytes = encode(&sheet.name, &columns)?;
+    backup::before_write(path)?;
+    fs::write(path, bytes).context(format!("No se pudo escribir {}", path.display()))?;
+    verify::after_write(path)?;
+    Ok(())
+}
+
+fn encode(sheet_name: &str, columns: &Columns) -> Result<Vec<u8>> {
+    if columns.names.is_empty() {
+        bail!("la hoja '{}' está vacía y un Parquet necesita al menos una columna", sheet_name);
+    }
+    let num_rows = columns.cells[0].len() as i64;
+    let mut out = MAGIC.to_vec();
+    let mut chunks = Vec::new();
+    let mut schema = vec![fields(vec![(4, text("schema")), (5, Thrift::I32(columns.names.len() as i32))])];
+    let mut total_size = 0;
+    for ((name, kind), cells) in columns.names.iter().zip(&columns.kinds).zip(&columns.cells) {
+        let page = encode_page(cells, *kind);
+        let header = fields(vec![
+            (1, Thrift::I32(PAGE_DATA as i32)),
+            (2, Thrift::I32(page.len() as i32)),
+            (3, Thrift::I32(page.len() as i32)),
+            (
+                5,
+                fields(vec![
+                    (1, Thrift::I32(cells.len() as i32)),
+                    (2, Thrift::I32(ENCODING_PLAIN as i32)),
+                    (3, Thrift::I32(ENCODING_RLE as i32)),
+                    (4, Thrift::I32(ENCODING_RLE as i32)),
+                ]),
+            ),
+        ]);
+        let offset = out.len() as i64;
+        header.write(&mut out);
+        out.extend_from_slice(&page);
+        // Los tamaños de una columna incluyen las cabeceras de sus páginas
+        let size = out.len() as i64 - offset;
+        total_size += size;
+        let metadata = fields(vec![
+            (1, Thrift::I32(kind.physical() as i32)),
+            (2, Thrift::List(vec![Thrift::I32(ENCODING_PLAIN as i32), Thrift::I32(ENCODING_RLE as i32)])),
+            (3, Thrift::List(vec![text(name)])),
+            (4, Thrift::I32(CODEC_UNCOMPRESSED as i32)),
+            (5, Thrift::I64(cells.len() as i64)),
+            (6, Thrift::I64(size)),
+            (7, Thrift::I64(size)),
+            (9, Thrift::I64(offset)),
+        ]);
+        chunks.push(fields(vec![(2, Thrift::I64(offset)), (3, metadata)]));
+
+        let mut element = vec![
+            (1, Thrift::I32(kind.physical() as i32)),
+            (3, Thrift::I32(REPETITION_OPTIONAL as i32)),
+            (4, text(name)),
+        ];
+        if let Some(converted) = kind.converted() {
+            element.push((6, Thrift::I32(converted as i32)));
+        }
+        schema.push(fields(element));
+    }
+    let row_group = fields(vec![
+        (1, Thrift::List(chunks)),
+        (2, Thrift::I64(total_size)),
+        (3, Thrift::I64(num_rows)),
+    ]);
+    let metadata = fields(vec![
+        (1, Thrift::I32(1)),
+        (2, Thrift::List(schema)),
+        (3, Thrift::I64(num_rows)),
+        (4, Thrift::List(vec![row_group])),
+        (6, text(&format!("ia_agent version {}", env!("CARGO_PKG_VERSION")))),
+    ]);
+    let start = out.len();
+    metadata.write(&mut out);
+    let length = (out.len() - start) as u32;
+    out.extend_from_slice(&length.to_le_bytes());
+    out.extend_from_slice(MAGIC);
+    Ok(out)
+}
+
+// Página de datos v1: niveles de definición (1 = hay valor) y los valores no nulos
+fn encode_page(cells: &[&CellValue], kind: ColumnKind) -> Vec<u8> {
+    let defined: Vec<bool> = cells.iter().map(|cell| !matches!(cell, CellValue::Empty)).collect();
+    let levels = encode_levels(&defined);
+    let mut page = Vec::new();
+    page.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+    page.extend_from_slice(&levels);
+    let values = cells.iter().filter(|cell| !matches!(cell, CellValue::Empty));
+    match kind {
+        ColumnKind::Number => {
+            for cell in values {
+                if let CellValue::Number(number) = cell {
+                    page.extend_from_slice(&number.to_le_bytes());
+                }
+            }
+        }
+        ColumnKind::DateTime => {
+            for cell in values {
+                if let CellValue::DateTime(serial) = cell {
+                    let millis = ((serial - UNIX_EPOCH_SERIAL) * MILLIS_PER_DAY).round() as i64;
+                    page.extend_from_slice(&millis.to_le_bytes());
+                }
+            }
+        }
+        // Un bit por valor, empezando por el menos significativo
+        ColumnKind::Bool => {
+            let bits: Vec<bool> = values.map(|cell| matches!(cell, CellValue::Bool(true))).collect();
+            for byte in bits.chunks(8) {
+                page.push(byte.iter().enumerate().fold(0u8, |acc, (bit, set)| acc | (u8::from(*set) << bit)));
+            }
+        }
+        ColumnKind::Text => {
+            for cell in values {
+                let value = cell.to_string();
+                page.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                page.extend_from_slice(value.as_bytes());
+            }
+        }
+    }
+    page
+}
+
+// Niveles de definición en RLE: cada tramo de niveles iguales es su longitud y el nivel
+fn encode_levels(defined: &[bool]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    while start < defined.len() {
+        let run = defined[start..].iter().take_while(|level| **level == defined[start]).count();
+        write_varint(&mut out, (run as u64) << 1);
+        out.push(u8::from(defined[start]));
+        start += run;
+    }
+    out
+}
+
+pub fn read(path: &Path, sheet_name: &str) -> Result<SheetData> {
+    let bytes = fs::read(path).context(format!("No se pudo leer {}", path.display()))?;
+    decode(&bytes, sheet_name).context(format!("No se pudo leer el Parquet {}", path.display()))
+}
+
+// Anotación de una columna que cambia cómo se interpretan sus valores
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Annotation {
+    None,
+    Text,
+    Date,
+    // Unidades del valor por día
+    Timestamp(f64),
+    // Dígitos decimales
+    Decimal(i32),
+}
+
+struct Column {
+    name: String,
+    physical: i64,
+    annotation: Annotation,
+    optional: bool,
+    type_length: usize,
+}
+
+impl Column {
+    fn from_schema(element: &Thrift) -> Result<Column> {
+        let name = element.string(4).unwrap_or_default();
+        if element.int(5).unwrap_or(0) > 0 || !matches!(element.int(3), Some(REPETITION_REQUIRED | REPETITION_OPTIONAL)) {
+            bail!("la columna '{}' es anidada o repetida y solo se admiten tablas planas", name);
+        }
+        Ok(Column {
+            physical: element.int(1).context(format!("la columna '{}' no tiene tipo", name))?,
+            annotation: annotation(element),
+            optional: element.int(3) == Some(REPETITION_OPTIONAL),
+            type_length: element.int(2).unwrap_or(0).max(0) as usize,
+            name,
+        })
+    }
+
+    fn int_value(&self, value: i64) -> CellValue {
+        match self.annotation {
+            Annotation::Date => CellValue::DateTime(value as f64 + UNIX_EPOCH_SERIAL),
+            Annotation::Timestamp(per_day) => CellValue::DateTime(value as f64 / per_day + UNIX_EPOCH_SERIAL),
+            Annotation::Decimal(scale) => CellValue::Number(value as f64 / 10f64.powi(scale)),
+            _ => CellValue::Number(value as f64),
+        }
+    }
+
+    fn bytes_value(&self, bytes: &[u8]) -> Result<CellValue> {
+        match self.annotation {
+            // Entero con signo en big-endian
+            Annotation::Decimal(scale) => {
+                if bytes.len() > 16 {
+                    bail!("decimal de {} bytes en la columna '{}'", bytes.len(), self.name);
+                }
+                let negative = bytes.first().is_some_and(|byte| byte & 0x80 != 0);
+                let unscaled = bytes
+                    .iter()
+                    .fold(if negative { -1i128 } else { 0 }, |acc, byte| (acc << 8) | i128::from(*byte));
+                Ok(CellValue::Number(unscaled as f64 / 10f64.powi(scale)))
+            }
+            _ => Ok(CellValue::Text(String::from_utf8_lossy(bytes).into_owned())),
+        }
+    }
+}
+
+fn annotation(element: &Thrift) -> Annotation {
+    if let Some(logical) = element.field(10) {
+        if logical.field(1).is_some() || logical.field(4).is_some() || logical.field(12).is_some() {
+            return Annotation::Text;
+        }
+        if let Some(decimal) = logical.field(5) {
+            return Annotation::Decimal(decimal.int(1).unwrap_or(0) as i32);
+        }
+        if logical.field(6).is_some() {
+            return Annotation::Date;
+        }
+        if let Some(unit) = logical.field(8).and_then(|timestamp| timestamp.field(2)) {
+            let per_day = if unit.field(1).is_some() {
+                MILLIS_PER_DAY
+            } else if unit.field(2).is_some() {
+                MILLIS_PER_DAY * 1e3
+            } else {
+                MILLIS_PER_DAY * 1e6
+            };
+            return Annotation::Timestamp(per_day);
+        }
+    }
+    match element.int(6) {
+        Some(CONVERTED_UTF8 | CONVERTED_ENUM | CONVERTED_JSON) => Annotation::Text,
+        Some(CONVERTED_DECIMAL) => Annotation::Decimal(element.int(7).unwrap_or(0) as i32),
+        Some(CONVERTED_DATE) => Annotation::Date,
+        Some(CONVERTED_TIMESTAMP_MILLIS) => Annotation::Timestamp(MILLIS_PER_DAY),
+        Some(CONVERTED_TIMESTAMP_MICROS) => Annotation::Timestamp(MILLIS_PER_DAY * 1e3),
+        _ => Annotation::None,
+    }
+}
+
+fn decode(bytes: &[u8], sheet_name: &str) -> Result<SheetData> {
+    if bytes.len() < 12 || !bytes.starts_with(MAGIC) || !bytes.ends_with(MAGIC) {
+        bail!("no empieza y acaba con la marca PAR1");
+    }
+    let footer = bytes.len() - 8;
+    let length = u32::from_le_bytes(bytes[footer..footer + 4].try_into()?) as usize;
+    let start = footer
+        .checked_sub(length)
+        .filter(|start| *start >= MAGIC.len())
+        .context("la longitud de los metadatos no es válida")?;
+    let metadata = ByteReader::new(&bytes[start..footer]).thrift(12, 0).context("metadatos no válidos")?;
+
+    let Some((root, leaves)) = metadata.list(2).split_first() else {
+        bail!("no tiene esquema");
+    };
+    let columns: Vec<Column> = leaves.iter().map(Column::from_schema).collect::<Result<_>>()?;
+    if root.int(5).unwrap_or(0) as usize != columns.len() {
+        bail!("el esquema tiene columnas anidadas y solo se admiten tablas planas");
+    }
+    let mut values: Vec<Vec<CellValue>> = vec![Vec::new(); columns.len()];
+    for group in metadata.list(4) {
+        let chunks = group.list(1);
+        if chunks.len() != columns.len() {
+            bail!("un grupo de filas tiene {} columnas y el esquema {}", chunks.len(), columns.len());
+        }
+        for ((chunk, column), values) in chunks.iter().zip(&columns).zip(values.iter_mut()) {
+            if chunk.string(1).is_some() {
+                bail!("la columna '{}' está en otro archivo", column.name);
+            }
+            let chunk = chunk.field(3).context(format!("la columna '{}' no tiene metadatos", column.name))?;
+            values.extend(read_chunk(bytes, chunk, column).context(format!("columna '{}'", column.name))?);
+        }
+    }
+    let height = values.first().map_or(0, Vec::len);
+    if values.iter().any(|column| column.len() != height) {
+        bail!("las columnas no tienen el mismo número de filas");
+    }
+
+    let mut sheet = SheetData::new(sheet_name);
+    // Los nombres "Columna A", "Columna B"... son los de una hoja sin encabezados
+    sheet.headerless = !columns.is_empty()
+        && columns
+            .iter()
+            .enumerate()
+            .all(|(index, column)| column.name == format!("Columna {}", excel::column_letters(index)));
+    if !sheet.headerless {
+        sheet.rows.push(columns.iter().map(|column| CellValue::Text(column.name.clone())).collect());
+    }
+    let mut columns: Vec<_> = values.into_iter().map(Vec::into_iter).collect();
+    for _ in 0..height {
+        sheet.rows.push(columns.iter_mut().filter_map(Iterator::next).collect());
+    }
+    Ok(sheet)
+}
+
+// Todas las páginas de una columna de un grupo de filas
+fn read_chunk(bytes: &[u8], chunk: &Thrift, column: &Column) -> Result<Vec<CellValue>> {
+    let codec = chunk.int(4).unwrap_or(CODEC_UNCOMPRESSED);
+    let num_values = chunk.int(5).context("falta el número de valores")?.max(0) as usize;
+    let data_offset = chunk.int(9).context("falta la posición de los datos")?;
+    // El diccionario, si lo hay, va antes de las páginas de datos
+    let start = chunk.int(11).filter(|offset| *offset > 0).map_or(data_offset, |offset| offset.min(data_offset));
+    let mut reader = ByteReader::new(bytes);
+    reader.pos = usize::try_from(start).ok().filter(|start| *start < bytes.len()).context("posición de página no válida")?;
+
+    let mut dictionary: Option<Vec<CellValue>> = None;
+    let mut cells = Vec::new();
+    while cells.len() < num_values {
+        let header = reader.thrift(12, 0).context("cabecera de página no válida")?;
+        let size = header.int(3).context("falta el tamaño de la página")?.max(0) as usize;
+        let body = reader.take(size).context("página cortada")?;
+        match header.int(1) {
+            Some(PAGE_DICTIONARY) => {
+                let count = header.field(7).and_then(|page| page.int(1)).unwrap_or(0).max(0) as usize;
+                let page = decompress(codec, body)?;
+                dictionary = Some(plain_values(&page, count, column)?);
+            }
+            Some(PAGE_DATA) => {
+                let page_header = header.field(5).context("falta la cabecera de la página de datos")?;
+                let count = page_header.int(1).unwrap_or(0).max(0) as usize;
+                let page = decompress(codec, body)?;
+                let mut page = ByteReader::new(&page);
+                let defined = match column.optional {
+                    true => {
+                        let length = page.u32()? as usize;
+                        levels(page.take(length)?, count)?
+                    }
+                    false => vec![true; count],
+                };
+                let encoding = page_header.int(2).unwrap_or(ENCODING_PLAIN);
+                let present = defined.iter().filter(|defined| **defined).count();
+                let values = decode_values(&page.bytes[page.pos..], encoding, present, column, dictionary.as_deref())?;
+                cells.extend(with_nulls(&defined, values));
+            }
+            Some(PAGE_DATA_V2) => {
+                let page_header = header.field(8).context("falta la cabecera de la página de datos")?;
+                let count = page_header.int(1).unwrap_or(0).max(0) as usize;
+                let repetition_length = page_header.int(6).unwrap_or(0).max(0) as usize;
+                let definition_length = page_header.int(5).unwrap_or(0).max(0) as usize;
+                if repetition_length > 0 {
+                    bail!("tiene valores repetidos y solo se admiten tablas planas");
+                }
+                let mut page = ByteReader::new(body);
+                let definition = page.take(definition_length)?;
+                let defined = match column.optional {
+                    true => levels(definition, count)?,
+                    false => vec![true; count],
+                };
+                // En v2 los niveles nunca van comprimidos y los valores pueden no estarlo
+                let raw = &body[page.pos..];
+                let values = match page_header.boolean(7).unwrap_or(true) {
+                    true => Cow::Owned(decompress(codec, raw)?),
+                    false => Cow::Borrowed(raw),
+                };
+                let encoding = page_header.int(4).unwrap_or(ENCODING_PLAIN);
+                let present = defined.iter().filter(|defined| **defined).count();
+                let values = decode_values(&values, encoding, present, column, dictionary.as_deref())?;
+                cells.extend(with_nulls(&defined, values));
+            }
+            // Las páginas de índice no tienen valores
+            _ => {}
+        }
+    }
+    cells.truncate(num_values);
+    Ok(cells)
+}
+
+fn with_nulls(defined: &[bool], values: Vec<CellValue>) -> Vec<CellValue> {
+    let mut values = values.into_iter();
+    defined
+        .iter()
+        .map(|defined| match defined {
+            true => values.next().unwrap_or(CellValue::Empty),
+            false => CellValue::Empty,
+        })
+        .collect()
+}
+
+// Niveles de definición de una columna opcional (ancho de 1 bit)
+fn levels(data: &[u8], count: usize) -> Result<Vec<bool>> {
+    Ok(hybrid(data, 1, count)?.into_iter().map(|level| level > 0).collect())
+}
+
+// Codificación híbrida RLE / empaquetado de bits de niveles e índices de diccionario
+fn hybrid(data: &[u8], bit_width: usize, count: usize) -> Result<Vec<u32>> {
+    if bit_width > 32 {
+        bail!("ancho de bits {} no válido", bit_width);
+    }
+    let mut reader = ByteReader::new(data);
+    let mut out = Vec::new();
+    while out.len() < count {
+        let header = reader.varint().context("niveles o índices cortados")?;
+        let run = (header >> 1) as usize;
+        if header & 1 == 1 {
+            // Grupos de 8 valores empaquetados, empezando por el bit menos significativo
+            let packed = reader.take(run.saturating_mul(bit_width))?;
+            for index in 0..run * 8 {
+                if out.len() == count {
+                    break;
+                }
+                let value = (0..bit_width).fold(0u32, |acc, bit| {
+                    let position = index * bit_width + bit;
+                    acc | (u32::from(packed[position / 8] >> (position % 8) & 1) << bit)
+                });
+                out.push(value);
+            }
+        } else {
+            let raw = reader.take(bit_width.div_ceil(8))?;
+            let value = raw.iter().rev().fold(0u32, |acc, byte| (acc << 8) | u32::from(*byte));
+            out.extend(std::iter::repeat_n(value, run.min(count - out.len())));
+        }
+    }
+    Ok(out)
+}
+
+fn decode_values(
+    data: &[u8],
+    encoding: i64,
+    count: usize,
+    column: &Column,
+    dictionary: Option<&[CellValue]>,
+) -> Result<Vec<CellValue>> {
+    match encoding {
+        ENCODING_PLAIN => plain_values(data, count, column),
+        ENCODING_PLAIN_DICTIONARY | ENCODING_RLE_DICTIONARY => {
+            let dictionary = dictionary.context("la página usa un diccionario que no está")?;
+            let Some((bit_width, indices)) = data.split_first() else {
+                return Ok(vec![CellValue::Empty; count]);
+            };
+            hybrid(indices, usize::from(*bit_width), count)?
+                .into_iter()
+                .map(|index| dictionary.get(index as usize).cloned().context("índice de diccionario fuera de rango"))
+                .collect()
+        }
+        other => bail!("codificación {} no admitida (solo PLAIN y diccionario)", other),
+    }
+}
+
+fn plain_values(data: &[u8], count: usize, column: &Column) -> Result<Vec<CellValue>> {
+    let mut reader = ByteReader::new(data);
+    let mut values = Vec::new();
+    for index in 0..count {
+        let value = match column.physical {
+            TYPE_BOOLEAN => {
+                let byte = *data.get(index / 8).context("datos cortados")?;
+                CellValue::Bool(byte >> (index % 8) & 1 == 1)
+            }
+            TYPE_INT32 => column.int_value(i64::from(i32::from_le_bytes(reader.take(4)?.try_into()?))),
+            TYPE_INT64 => column.int_value(i64::from_le_bytes(reader.take(8)?.try_into()?)),
+            // Nanosegundos del día y día juliano
+            TYPE_INT96 => {
+                let nanos = u64::from_le_bytes(reader.take(8)?.try_into()?);
+                let julian = u32::from_le_bytes(reader.take(4)?.try_into()?);
+                CellValue::DateTime(f64::from(julian) - 2_440_588.0 + UNIX_EPOCH_SERIAL + nanos as f64 / (MILLIS_PER_DAY * 1e6))
+            }
+            TYPE_FLOAT => CellValue::Number(f64::from(f32::from_le_bytes(reader.take(4)?.try_into()?))),
+            TYPE_DOUBLE => CellValue::Number(f64::from_le_bytes(reader.take(8)?.try_into()?)),
+            TYPE_BYTE_ARRAY => {
+                let length = reader.u32()? as usize;
+                column.bytes_value(reader.take(length)?)?
+            }
+            TYPE_FIXED_LEN_BYTE_ARRAY => column.bytes_value(reader.take(column.type_length)?)?,
+            other => bail!("tipo físico {} desconocido", other),
+        };
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn decompress(codec: i64, data: &[u8]) -> Result<Vec<u8>> {
+    match codec {
+        CODEC_UNCOMPRESSED => Ok(data.to_vec()),
+        CODEC_SNAPPY => snappy(data),
+        CODEC_GZIP => {
+            let mut out = Vec::new();
+            flate2::read::GzDecoder::new(data)
+                .read_to_end(&mut out)
+                .context("bloque GZIP no válido")?;
+            Ok(out)
+        }
+        other => {
+            let name = match other {
+                3 => "LZO",
+                4 => "BROTLI",
+                5 | 7 => "LZ4",
+                6 => "ZSTD",
+                _ => "desconocida",
+            };
+            bail!("compresión {} no admitida (solo sin comprimir, SNAPPY y GZIP)", name)
+        }
+    }
+}
+
+// Bloque SNAPPY sin tramas: longitud final y una serie de literales y copias de
+// lo ya descomprimido
+fn snappy(data: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = ByteReader::new(data);
+    let length = reader.varint()? as usize;
+    let little_endian = |bytes: &[u8]| bytes.iter().rev().fold(0usize, |acc, byte| (acc << 8) | usize::from(*byte));
+    let mut out: Vec<u8> = Vec::new();
+    while reader.pos < data.len() {
+        let tag = reader.byte()?;
+        let (copy_length, offset) = match tag & 3 {
+            0 => {
+                let literal = match usize::from(tag >> 2) {
+                    small @ 0..=59 => small,
+                    long => little_endian(reader.take(long - 59)?),
+                };
+                out.extend_from_slice(reader.take(literal + 1)?);
+                continue;
+            }
+            1 => (usize::from((tag >> 2) & 7) + 4, usize::from(tag >> 5) << 8 | usize::from(reader.byte()?)),
+            2 => (usize::from(tag >> 2) + 1, little_endian(reader.take(2)?)),
+            _ => (usize::from(tag >> 2) + 1, little_endian(reader.take(4)?)),
+        };
+        if offset == 0 || offset > out.len() {
+            bail!("bloque SNAPPY no válido");
+        }
+        // La copia puede solaparse con lo que va escribiendo
+        let start = out.len() - offset;
+        for index in 0..copy_length {
+            out.push(out[start + index]);
+        }
+    }
+    if out.len() != length {
+        bail!("bloque SNAPPY incompleto ({} de {} bytes)", out.len(), length);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{self, ConvertOptions, FileFormat};
+    use crate::excel::WorkbookData;
+    use std::io::Write;
+
+    fn datos() -> SheetData {
+        let mut sheet = SheetData::new("Datos");
+        sheet.rows = vec![
+            ["Cliente", "Importe", "Pagado", "Alta", "Nota"].map(|h| CellValue::Text(h.to_string())).to_vec(),
+            vec![
+                CellValue::Text("Ana".to_string()),
+                CellValue::Number(10.5),
+                CellValue::Bool(true),
+                CellValue::DateTime(45292.5),
+                CellValue::Text("ñandú".to_string()),
+            ],
+            vec![CellValue::Text("Luis".to_string()), CellValue::Empty, CellValue::Bool(false), CellValue::DateTime(45293.0)],
+            vec![CellValue::Empty, CellValue::Number(-3.0), CellValue::Empty, CellValue::Empty, CellValue::Error("#N/A".to_string())],
+        ];
+        sheet
+    }
+
+    fn round_trip(sheet: &SheetData) -> SheetData {
+        decode(&encode(&sheet.name, &Columns::of(sheet)).unwrap(), &sheet.name).unwrap()
+    }
+
+    #[test]
+    fn column_types_and_empty_cells_survive_the_round_trip() {
+        let sheet = datos();
+        let columns = Columns::of(&sheet);
+        assert_eq!(
+            columns.kinds,
+            [ColumnKind::Text, ColumnKind::Number, ColumnKind::Bool, ColumnKind::DateTime, ColumnKind::Text]
+        );
+        let read = round_trip(&sheet);
+        assert!(!read.headerless);
+        assert_eq!(read.rows, as_stored(&sheet).rows);
+        assert_eq!(read.rows[1][1], CellValue::Number(10.5));
+        assert_eq!(read.rows[2][1], CellValue::Empty);
+        assert_eq!(read.rows[2][3], CellValue::DateTime(45293.0));
+        // Los errores de Excel quedan como su texto
+        assert_eq!(read.rows[3][4], CellValue::Text("#N/A".to_string()));
+    }
+
+    #[test]
+    fn mixed_columns_become_text_and_names_are_never_repeated() {
+        let mut sheet = SheetData::new("Datos");
+        sheet.rows = vec![
+            vec![CellValue::Text("Id".to_string()), CellValue::Empty, CellValue::Text("Id".to_string())],
+            vec![CellValue::Number(1.0), CellValue::Text("a".to_string()), CellValue::Number(2.0)],
+            vec![CellValue::Text("x".to_string()), CellValue::Empty, CellValue::Number(3.0), CellValue::Bool(true)],
+        ];
+        let columns = Columns::of(&sheet);
+        assert_eq!(columns.names, ["Id", "B", "Id (2)", "D"]);
+        assert_eq!(columns.mixed(), ["Id"]);
+        let read = round_trip(&sheet);
+        assert_eq!(read.rows[1][0], CellValue::Text("1".to_string()));
+        assert_eq!(read.rows[1][2], CellValue::Number(2.0));
+        assert_eq!(read.rows, as_stored(&sheet).rows);
+    }
+
+    #[test]
+    fn a_sheet_without_headers_is_read_back_without_them() {
+        let mut sheet = SheetData::new("Datos");
+        sheet.headerless = true;
+        sheet.rows = vec![vec![CellValue::Number(1.0), CellValue::Number(2.0)], vec![CellValue::Number(3.0)]];
+        let read = round_trip(&sheet);
+        assert!(read.headerless);
+        assert_eq!(read.rows, [vec![CellValue::Number(1.0), CellValue::Number(2.0)], vec![CellValue::Number(3.0), CellValue::Empty]]);
+    }
+
+    #[test]
+    fn an_empty_sheet_cannot_be_written() {
+        let error = encode("Vacía", &Columns::of(&SheetData::new("Vacía"))).unwrap_err();
+        assert_eq!(error.to_string(), "la hoja 'Vacía' está vacía y un Parquet necesita al menos una columna");
+    }
+
+    #[test]
+    fn snappy_copies_can_overlap_what_they_copy() {
+        assert_eq!(snappy(&[12, 0x08, b'a', b'b', b'c', 0x15, 3]).unwrap(), b"abcabcabcabc");
+        assert!(snappy(&[4, 0x05, 1]).is_err());
+    }
+
+    // Bloque SNAPPY con un único literal, suficiente para las pruebas
+    fn snappy_literal(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_varint(&mut out, data.len() as u64);
+        out.push(60 << 2);
+        out.push((data.len() - 1) as u8);
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn page(out: &mut Vec<u8>, header: Vec<(i16, Thrift)>, body: &[u8]) {
+        fields(header).write(out);
+        out.extend_from_slice(body);
+    }
+
+    // Un archivo como los que escriben otras herramientas: una columna obligatoria de
+    // fechas con diccionario, página v2 y SNAPPY, y otra opcional de texto con GZIP y
+    // niveles empaquetados en bits
+    #[test]
+    fn dictionaries_v2_pages_and_compressed_pages_are_read() {
+        let mut file = MAGIC.to_vec();
+
+        let fechas_offset = file.len() as i64;
+        let dictionary = snappy_literal(&[19723i32.to_le_bytes(), 19724i32.to_le_bytes()].concat());
+        page(&mut file, vec![(1, Thrift::I32(2)), (2, Thrift::I32(8)), (3, Thrift::I32(dictionary.len() as i32)), (7, fields(vec![(1, Thrift::I32(2)), (2, Thrift::I32(0))]))], &dictionary);
+        let data_offset = file.len() as i64;
+        // Ancho de 1 bit y tres índices empaquetados: 1, 0, 1
+        let indices = snappy_literal(&[1, 0x03, 0b101]);
+        let v2 = vec![(1, Thrift::I32(3)), (2, Thrift::I32(0)), (3, Thrift::I32(3)), (4, Thrift::I32(8)), (5, Thrift::I32(0)), (6, Thrift::I32(0))];
+        page(&mut file, vec![(1, Thrift::I32(3)), (2, Thrift::I32(3)), (3, Thrift::I32(indices.len() as i32)), (8, fields(v2))], &indices);
+
+        let nombres_offset = file.len() as i64;
+        let mut raw = Vec::new();
+        // Niveles 1, 0, 1 empaquetados y dos textos
+        raw.extend_from_slice(&2u32.to_le_bytes());
+        raw.extend_from_slice(&[0x03, 0b101]);
+        for name in ["Eva", "Íñigo"] {
+            raw.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            raw.extend_from_slice(name.as_bytes());
+        }
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&raw).unwrap();
+        let compressed = gzip.finish().unwrap();
+        let v1 = vec![(1, Thrift::I32(3)), (2, Thrift::I32(0)), (3, Thrift::I32(3)), (4, Thrift::I32(3))];
+        page(&mut file, vec![(1, Thrift::I32(0)), (2, Thrift::I32(raw.len() as i32)), (3, Thrift::I32(compressed.len() as i32)), (5, fields(v1))], &compressed);
+
+        let chunk = |kind: i64, codec: i64, offset: i64, dictionary: Option<i64>| {
+            let mut meta = vec![(1, Thrift::I32(kind as i32)), (4, Thrift::I32(codec as i32)), (5, Thrift::I64(3)), (9, Thrift::I64(offset))];
+            if let Some(dictionary) = dictionary {
+                meta.push((11, Thrift::I64(dictionary)));
+            }
+            fields(vec![(2, Thrift::I64(offset)), (3, fields(meta))])
+        };
+        let metadata = fields(vec![
+            (1, Thrift::I32(2)),
+            (
+                2,
+                Thrift::List(vec![
+                    fields(vec![(4, text("schema")), (5, Thrift::I32(2))]),
+                    fields(vec![(1, Thrift::I32(1)), (3, Thrift::I32(0)), (4, text("Fecha")), (10, fields(vec![(6, fields(vec![]))]))]),
+                    fields(vec![(1, Thrift::I32(6)), (3, Thrift::I32(1)), (4, text("Nombre")), (10, fields(vec![(1, fields(vec![]))]))]),
+                ]),
+            ),
+            (3, Thrift::I64(3)),
+            (
+                4,
+                Thrift::List(vec![fields(vec![
+                    (1, Thrift::List(vec![chunk(1, 1, data_offset, Some(fechas_offset)), chunk(6, 2, nombres_offset, None)])),
+                    (2, Thrift::I64(0)),
+                    (3, Thrift::I64(3)),
+                ])]),
+            ),
+        ]);
+        let start = file.len();
+        metadata.write(&mut file);
+        let length = (file.len() - start) as u32;
+        file.extend_from_slice(&length.to_le_bytes());
+        file.extend_from_slice(MAGIC);
+
+        let sheet = decode(&file, "Datos").unwrap();
+        assert_eq!(
+            sheet.rows,
+            [
+                vec![CellValue::Text("Fecha".to_string()), CellValue::Text("Nombre".to_string())],
+                vec![CellValue::DateTime(45293.0), CellValue::Text("Eva".to_string())],
+                vec![CellValue::DateTime(45292.0), CellValue::Empty],
+                vec![CellValue::DateTime(45293.0), CellValue::Text("Íñigo".to_string())],
+            ]
+        );
+    }
+
+    // Lo que el escritor integrado no usa, en un archivo de testdata/parquet/generar.py
+    // (un codificador aparte del lector, no otra herramienta): columnas opcionales
+    // con diccionario y SNAPPY, tipos lógicos y dos grupos de filas
+    #[test]
+    fn dictionary_snappy_logical_types_and_row_groups_are_read() {
+        let sheet = decode(include_bytes!("../testdata/parquet/ventas.parquet"), "Ventas").unwrap();
+        let text = |value: &str| CellValue::Text(value.to_string());
+        let row = |cliente: &str, nota: &str, importe: f64, unidades: f64, pagado: Option<bool>, alta: f64, registro: f64, precio: f64| {
+            vec![
+                text(cliente),
+                text(nota),
+                CellValue::Number(importe),
+                CellValue::Number(unidades),
+                pagado.map_or(CellValue::Empty, CellValue::Bool),
+                CellValue::DateTime(alta),
+                CellValue::DateTime(registro),
+                CellValue::Number(precio),
+            ]
+        };
+        assert!(!sheet.headerless);
+        assert_eq!(
+            sheet.rows,
+            [
+                ["Cliente", "Nota", "Importe", "Unidades", "Pagado", "Alta", "Registro", "Precio"].map(text).to_vec(),
+                row("Ana López", "pedido urgente", 10.5, 3.0, Some(true), 45292.0, 45292.5, 12.34),
+                row("Luis", "pedido normal", 20.0, 1.0, Some(false), 45293.0, 45293.25, 5.0),
+                row("Ana López", "pedido urgente", 10.5, 3.0, Some(true), 45292.0, 45292.5, 12.34),
+                vec![CellValue::Empty; 8],
+                row("Eva", "pedido urgente revisado", -3.25, 7.0, Some(true), 45351.0, 45351.75, -0.5),
+                row("Íñigo", "pedido normal", 0.0, 0.0, None, 45291.0, 45291.0, 1_000_000.0),
+                row("Ana López", "pedido normal", 10.5, 2.0, Some(false), 45352.0, 45352.125, 12.34),
+            ]
+        );
+    }
+
+    #[test]
+    fn files_that_are_not_parquet_or_not_flat_are_rejected() {
+        let error = decode(b"Cliente,Importe\nAna,10\n", "Datos").unwrap_err();
+        assert_eq!(error.to_string(), "no empieza y acaba con la marca PAR1");
+
+        let mut file = MAGIC.to_vec();
+        let metadata = fields(vec![
+            (2, Thrift::List(vec![
+                fields(vec![(4, text("schema")), (5, Thrift::I32(1))]),
+                fields(vec![(3, Thrift::I32(2)), (4, text("lista")), (5, Thrift::I32(1))]),
+                fields(vec![(1, Thrift::I32(1)), (3, Thrift::I32(1)), (4, text("valor"))]),
+            ])),
+        ]);
+        let start = file.len();
+        metadata.write(&mut file);
+        let length = (file.len() - start) as u32;
+        file.extend_from_slice(&length.to_le_bytes());
+        file.extend_from_slice(MAGIC);
+        let error = decode(&file, "Datos").unwrap_err();
+        assert_eq!(error.to_string(), "la columna 'lista' es anidada o repetida y solo se admiten tablas planas");
+    }
+
+    #[test]
+    fn convert_writes_one_parquet_per_sheet_and_validates_it() {
+        let dir = crate::paths::test_dir("convert_writes_one_parquet_per_sheet_and_validates_it");
+        let source = dir.join("ventas.xlsx");
+        let mut otra = SheetData::new("Otra");
+        otra.rows = vec![vec![CellValue::Text("Valor".to_string())], vec![CellValue::Number(1.0)], vec![CellValue::Text("dos".to_string())]];
+        excel::save_workbook(&source, &WorkbookData { sheets: vec![datos(), otra] }).unwrap();
+
+        let options = ConvertOptions {
+            pattern: source.to_string_lossy().into_owned(),
+            format: FileFormat::Parquet,
+            output_dir: dir.join("salida").to_string_lossy().into_owned(),
+            validate: true,
+        };
+        let outcomes = convert::convert_files(&options).unwrap();
+        let written = outcomes[0].result.as_ref().unwrap();
+        let names: Vec<String> = written.iter().map(|path| path.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["ventas_Datos.parquet", "ventas_Otra.parquet"]);
+        let read = convert::read_any(&written[0]).unwrap();
+        assert_eq!(read.sheets[0].name, "ventas_Datos");
+        assert_eq!(read.sheets[0].rows[1][3], CellValue::DateTime(45292.5));
+    }
+}
@@ -0,0 +1,166 @@
+// Directorios del agente según las convenciones de cada sistema:
+//   Linux:   $XDG_CONFIG_HOME/iagent, $XDG_DATA_HOME/iagent y $XDG_CACHE_HOME/iagent
+//            (~/.config, ~/.local/share y ~/.cache si no están definidas)
+//   macOS:   ~/Library/Application Support/IAgent y ~/Library/Caches/IAgent
+//   Windows: %APPDATA%\IAgent\config, %APPDATA%\IAgent\data y %LOCALAPPDATA%\IAgent\cache
+// Con IAGENT_HOME todo va a ese directorio, como en el antiguo ~/.iagent.
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const APP_NAME: &str = "IAgent";
+const LEGACY_DIR: &str = ".iagent";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    // Lo que escribe el usuario: plantillas, manifiesto de herramientas y clave
+    Config,
+    // Lo que genera el agente y hay que conservar: copias, trabajos y registros
+    Data,
+    // Lo que se puede borrar sin perder nada
+    Cache,
+}
+
+// Contenido del antiguo ~/.iagent y el directorio al que se traslada
+const LEGACY_ENTRIES: &[(&str, Kind)] = &[
+    ("prompts", Kind::Config),
+    ("herramientas.toml", Kind::Config),
+    ("clave_api.enc", Kind::Config),
+    ("backups", Kind::Data),
+    ("trabajos", Kind::Data),
+    ("archivos.log", Kind::Data),
+    ("cache", Kind::Cache),
+];
+
+pub fn home() -> PathBuf {
+    let home = env::var("HOME")
+        .or_else(|_| env::var("USERPROFILE"))
+        .unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home)
+}
+
+fn env_dir(name: &str) -> Option<PathBuf> {
+    env::var(name).ok().filter(|dir| !dir.trim().is_empty()).map(PathBuf::from)
+}
+
+fn platform_dir(kind: Kind) -> PathBuf {
+    if let Some(dir) = env_dir("IAGENT_HOME") {
+        return match kind {
+            Kind::Cache => dir.join("cache"),
+            Kind::Config | Kind::Data => dir,
+        };
+    }
+    if cfg!(target_os = "windows") {
+        let roaming = env_dir("APPDATA").unwrap_or_else(|| home().join("AppData").join("Roaming"));
+        return match kind {
+            Kind::Config => roaming.join(APP_NAME).join("config"),
+            Kind::Data => roaming.join(APP_NAME).join("data"),
+            Kind::Cache => env_dir("LOCALAPPDATA")
+                .unwrap_or_else(|| home().join("AppData").join("Local"))
+                .join(APP_NAME)
+                .join("cache"),
+        };
+    }
+    if cfg!(target_os = "macos") {
+        let library = home().join("Library");
+        return match kind {
+            Kind::Config | Kind::Data => library.join("Application Support").join(APP_NAME),
+            Kind::Cache => library.join("Caches").join(APP_NAME),
+        };
+    }
+    let (variable, fallback) = match kind {
+        Kind::Config => ("XDG_CONFIG_HOME", home().join(".config")),
+        Kind::Data => ("XDG_DATA_HOME", home().join(".local").join("share")),
+        Kind::Cache => ("XDG_CACHE_HOME", home().join(".cache")),
+    };
+    // La especificación XDG solo admite rutas absolutas
+    env_dir(variable)
+        .filter(|dir| dir.is_absolute())
+        .unwrap_or(fallback)
+        .join(APP_NAME.to_lowercase())
+}
+
+// Configuración del usuario (plantillas, herramientas.toml, clave cifrada)
+pub fn config_dir() -> PathBuf {
+    platform_dir(Kind::Config)
+}
+
+// Datos del agente (copias de seguridad, trabajos, registro de archivos)
+pub fn data_dir() -> PathBuf {
+    platform_dir(Kind::Data)
+}
+
+pub fn cache_dir() -> PathBuf {
+    platform_dir(Kind::Cache)
+}
+
+pub fn prompts_dir() -> PathBuf {
+    config_dir().join("prompts")
+}
+
+pub fn backups_dir() -> PathBuf {
+    data_dir().join("backups")
+}
+
+// Clave de la API cifrada con una frase de paso
+pub fn api_key_file() -> PathBuf {
+    config_dir().join("clave_api.enc")
+}
+
+// Estado de los trabajos reanudables (`reanudar <id>`)
+pub fn jobs_dir() -> PathBuf {
+    data_dir().join("trabajos")
+}
+
+// Traslada lo que quede en el antiguo ~/.iagent a los directorios del sistema.
+// Nunca se sobrescribe nada: si el destino ya existe, la entrada se deja donde
+// está. Devuelve un aviso por cada entrada trasladada o que no se pudo trasladar.
+pub fn migrate_legacy() -> Vec<String> {
+    if env_dir("IAGENT_HOME").is_some() {
+        return Vec::new();
+    }
+    let legacy = home().join(LEGACY_DIR);
+    if !legacy.is_dir() {
+        return Vec::new();
+    }
+    let mut notes = Vec::new();
+    for (name, kind) in LEGACY_ENTRIES {
+        let from = legacy.join(name);
+        let to = match kind {
+            // La caché antigua era una subcarpeta; ahora es el directorio entero
+            Kind::Cache => cache_dir(),
+            Kind::Config => config_dir().join(name),
+            Kind::Data => data_dir().join(name),
+        };
+        if !from.exists() || to.exists() {
+            continue;
+        }
+        let moved = to
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::rename(&from, &to));
+        notes.push(match moved {
+            Ok(()) => format!("ℹ️  {} se ha trasladado a {}", from.display(), to.display()),
+            Err(e) => format!("⚠️  No se pudo trasladar {} a {}: {}", from.display(), to.display(), e),
+        });
+    }
+    // Solo se borra si ha quedado vacío
+    let _ = fs::remove_dir(&legacy);
+    notes
+}
+
+// Carpeta vacía para los archivos de una prueba. IAGENT_HOME apunta a una
+// carpeta temporal para que las copias de seguridad no vayan al usuario.
+#[cfg(test)]
+pub fn test_dir(name: &str) -> PathBuf {
+    static HOME: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+    let home = HOME.get_or_init(|| {
+        let home = env::temp_dir().join(format!("iagent_pruebas_{}", std::process::id()));
+        env::set_var("IAGENT_HOME", &home);
+        home
+    });
+    let dir = home.join("archivos").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("carpeta de la prueba");
+    dir
+}
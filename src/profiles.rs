@@ -0,0 +1,328 @@
+// Perfiles de configuración en iagent.toml (en el directorio de configuración, o
+// el archivo de IAGENT_CONFIG), como alternativa a mantener varios .env:
+//
+// perfil = "trabajo"            # el que se usa si no se elige otro
+//
+// [profiles.trabajo]
+// url = "https://pasarela.empresa.com/v1/chat/completions"
+// modelo = "gpt-4o"
+// clave_env = "EMPRESA_API_KEY"  # o clave_cmd = "pass show empresa/ia"
+// directorio = "~/trabajo/informes"
+// persona = "analyst"
+//
+// [profiles.trabajo.entorno]
+// IAGENT_CONFIRM_TOOLS = "siempre"
+//
+// Cada opción es una de las variables de entorno de la configuración. El entorno
+// del proceso no se toca: la configuración se lee con `var`, que antes de mirar el
+// entorno busca la variable en el perfil activo, así que el perfil manda sobre el
+// .env. Se elige con --profile, IAGENT_PROFILE o `perfil <nombre>` en la sesión;
+// al cambiar, las variables que no fija el perfil nuevo vuelven a las del entorno.
+// No se lee un iagent.toml del directorio de trabajo: clave_cmd ejecuta un comando.
+use crate::manifest;
+use crate::paths;
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const CONFIG_FILE: &str = "iagent.toml";
+
+// Opción del perfil, sus alias y la variable que escribe
+const OPTIONS: &[(&[&str], &str)] = &[
+    (&["url", "api_url"], "DEEPSEEK_API_URL"),
+    (&["url_base", "base_url"], "IAGENT_BASE_URL"),
+    (&["modelo", "model"], "DEEPSEEK_MODEL"),
+    (&["clave_cmd", "key_cmd"], "IAGENT_API_KEY_CMD"),
+    (&["directorio", "workspace"], "IAGENT_WORKSPACE"),
+    (&["persona"], "IAGENT_PERSONA"),
+    (&["prompt_sistema", "system_prompt"], "IAGENT_SYSTEM_PROMPT"),
+    (&["prompt_sistema_archivo", "system_prompt_file"], "IAGENT_SYSTEM_PROMPT_FILE"),
+    (&["idioma", "reply_lang"], "IAGENT_REPLY_LANG"),
+    (&["idioma_interfaz", "lang"], "IAGENT_LANG"),
+    (&["peticiones_por_minuto", "requests_per_minute"], "IAGENT_REQUESTS_PER_MINUTE"),
+    (&["tokens_por_minuto", "tokens_per_minute"], "IAGENT_TOKENS_PER_MINUTE"),
+];
+
+// Variables de la clave: un perfil con su propia fuente de clave las sustituye
+const KEY_VARIABLES: &[&str] = &["DEEPSEEK_API_KEY", "IAGENT_API_KEY", "IAGENT_API_KEY_CMD"];
+
+// Perfil aplicado con el valor de cada una de sus variables (`None` si la quita)
+struct Applied {
+    name: String,
+    values: Vec<(String, Option<String>)>,
+}
+
+static ACTIVE: Mutex<Option<Applied>> = Mutex::new(None);
+
+// Valor que un perfil da a una variable
+#[derive(Debug, Clone, PartialEq)]
+pub enum Setting {
+    Value(String),
+    // El de otra variable, leída al aplicar el perfil (clave_env)
+    FromVariable(String),
+    Remove,
+}
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub variables: Vec<(String, Setting)>,
+}
+
+impl Profile {
+    fn parse(name: &str, table: &Map<String, Value>) -> Result<Profile> {
+        let text = |value: &Value, key: &str| -> Result<String> {
+            match value {
+                Value::String(text) => Ok(text.clone()),
+                Value::Number(_) | Value::Bool(_) => Ok(value.to_string()),
+                _ => bail!("'{}' debe ser un texto", key),
+            }
+        };
+        let mut variables: Vec<(String, Setting)> = Vec::new();
+        let mut set = |variable: &str, value: Setting| {
+            variables.retain(|(existing, _)| existing != variable);
+            variables.push((variable.to_string(), value));
+        };
+        for (key, value) in table {
+            if let Some((_, variable)) = OPTIONS.iter().find(|(aliases, _)| aliases.contains(&key.as_str())) {
+                let mut value = text(value, key)?;
+                if *variable == "IAGENT_WORKSPACE" {
+                    value = expand_home(&value);
+                }
+                // Una URL base no debe quedar tapada por el endpoint completo del .env
+                if *variable == "IAGENT_BASE_URL" {
+                    set("DEEPSEEK_API_URL", Setting::Remove);
+                }
+                if *variable == "IAGENT_API_KEY_CMD" {
+                    KEY_VARIABLES.iter().for_each(|key| set(key, Setting::Remove));
+                }
+                set(variable, Setting::Value(value));
+                continue;
+            }
+            match key.as_str() {
+                "clave_env" | "key_env" => {
+                    KEY_VARIABLES.iter().for_each(|key| set(key, Setting::Remove));
+                    set("DEEPSEEK_API_KEY", Setting::FromVariable(text(value, key)?));
+                }
+                "clave" | "key" | "api_key" => {
+                    bail!("No guardes la clave en {}; usa clave_env (una variable) o clave_cmd (un comando)", CONFIG_FILE)
+                }
+                "entorno" | "env" => {
+                    let Value::Object(extra) = value else {
+                        bail!("'{}' debe ser una tabla [profiles.{}.{}]", key, name, key);
+                    };
+                    for (variable, value) in extra {
+                        set(variable, Setting::Value(text(value, variable)?));
+                    }
+                }
+                other => bail!("Opción desconocida '{}' en el perfil '{}'", other, name),
+            }
+        }
+        Ok(Profile {
+            name: name.to_string(),
+            variables,
+        })
+    }
+
+    // Opciones del perfil para `perfil`; de la clave solo se muestra de dónde sale
+    pub fn describe(&self) -> String {
+        let shown: Vec<String> = self
+            .variables
+            .iter()
+            .filter_map(|(variable, setting)| match setting {
+                Setting::Value(value) => Some(format!("{}={}", variable, value)),
+                Setting::FromVariable(source) => Some(format!("{}=${}", variable, source)),
+                Setting::Remove => None,
+            })
+            .collect();
+        shown.join(", ")
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some(rest) => paths::home().join(rest).display().to_string(),
+        None => path.to_string(),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ProfileFile {
+    pub path: Option<PathBuf>,
+    pub profiles: Vec<Profile>,
+    // Perfil de `perfil = "..."`
+    pub default: Option<String>,
+}
+
+impl ProfileFile {
+    // IAGENT_CONFIG=<archivo.toml>, o iagent.toml en el directorio de configuración
+    pub fn load() -> Result<ProfileFile> {
+        let path = match env::var("IAGENT_CONFIG") {
+            Ok(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+            _ => {
+                let path = paths::config_dir().join(CONFIG_FILE);
+                if !path.exists() {
+                    return Ok(ProfileFile::default());
+                }
+                path
+            }
+        };
+        let text = fs::read_to_string(&path).context(format!("No se pudo leer {}", path.display()))?;
+        let document = manifest::parse(&text).context(format!("{} no es válido", path.display()))?;
+        let mut profiles = Vec::new();
+        for key in ["profiles", "perfiles"] {
+            match document.get(key) {
+                Some(Value::Object(tables)) => {
+                    for (name, table) in tables {
+                        let Value::Object(table) = table else {
+                            bail!("{}: usa [{}.{}] para el perfil", path.display(), key, name);
+                        };
+                        profiles.push(Profile::parse(name, table).context(format!("{}: perfil '{}'", path.display(), name))?);
+                    }
+                }
+                Some(_) => bail!("{}: '{}' debe contener tablas [{}.<nombre>]", path.display(), key, key),
+                None => {}
+            }
+        }
+        let default = ["perfil", "profile"]
+            .iter()
+            .find_map(|key| document.get(*key).and_then(Value::as_str))
+            .map(str::to_string);
+        Ok(ProfileFile {
+            path: Some(path),
+            profiles,
+            default,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    // Lista para `perfil` sin argumentos
+    pub fn describe(&self) -> String {
+        let Some(path) = &self.path else {
+            return format!(
+                "ℹ️  No hay perfiles; decláralos con [profiles.<nombre>] en {}",
+                paths::config_dir().join(CONFIG_FILE).display()
+            );
+        };
+        let current = active();
+        let mut text = format!("👤 Perfiles de {}:", path.display());
+        for profile in &self.profiles {
+            let marker = if current.as_deref() == Some(profile.name.as_str()) { " (activo)" } else { "" };
+            text.push_str(&format!("\n  - {}{}: {}", profile.name, marker, profile.describe()));
+        }
+        text
+    }
+}
+
+// Nombre del perfil aplicado
+pub fn active() -> Option<String> {
+    ACTIVE.lock().ok()?.as_ref().map(|applied| applied.name.clone())
+}
+
+// Aplica el perfil `name`, o el de IAGENT_PROFILE o el de `perfil =` en el archivo.
+// Devuelve el nombre del perfil aplicado, `None` si no hay ninguno que aplicar.
+pub fn activate(name: Option<&str>) -> Result<Option<String>> {
+    let file = ProfileFile::load()?;
+    let requested = name
+        .map(str::to_string)
+        .or_else(|| env::var("IAGENT_PROFILE").ok().filter(|name| !name.trim().is_empty()))
+        .or_else(|| file.default.clone());
+    let Some(requested) = requested else { return Ok(None) };
+    let profile = file.get(&requested).with_context(|| {
+        let names: Vec<&str> = file.profiles.iter().map(|p| p.name.as_str()).collect();
+        match &file.path {
+            Some(path) if !names.is_empty() => {
+                format!("No existe el perfil '{}' en {} (perfiles: {})", requested, path.display(), names.join(", "))
+            }
+            Some(path) => format!("{} no declara perfiles [profiles.<nombre>]", path.display()),
+            None => format!(
+                "No existe el perfil '{}': no hay {} en {}",
+                requested,
+                CONFIG_FILE,
+                paths::config_dir().display()
+            ),
+        }
+    })?;
+
+    // Las claves de clave_env se leen antes de tocar nada, por si falta alguna
+    let mut values: Vec<(String, Option<String>)> = Vec::new();
+    for (variable, setting) in &profile.variables {
+        values.push((
+            variable.clone(),
+            match setting {
+                Setting::Value(value) => Some(value.clone()),
+                Setting::FromVariable(source) => Some(
+                    env::var(source)
+                        .ok()
+                        .filter(|value| !value.trim().is_empty())
+                        .context(format!("Perfil '{}': la variable {} de clave_env no está definida", profile.name, source))?,
+                ),
+                Setting::Remove => None,
+            },
+        ));
+    }
+
+    let mut active = ACTIVE.lock().map_err(|_| anyhow::anyhow!("Estado de perfiles no disponible"))?;
+    *active = Some(Applied {
+        name: profile.name.clone(),
+        values,
+    });
+    Ok(Some(profile.name.clone()))
+}
+
+// Deja de aplicar el perfil: sus variables vuelven a leerse del entorno
+pub fn deactivate() {
+    if let Ok(mut active) = ACTIVE.lock() {
+        active.take();
+    }
+}
+
+// Variable de la configuración: la del perfil activo si la fija (o un error si la
+// quita), si no la del entorno. Se usa en lugar de env::var para todo lo que un
+// perfil puede cambiar.
+pub fn var(name: &str) -> Result<String, env::VarError> {
+    let from_profile = ACTIVE.lock().ok().and_then(|active| {
+        let applied = active.as_ref()?;
+        applied.values.iter().find(|(variable, _)| variable == name).map(|(_, value)| value.clone())
+    });
+    match from_profile {
+        Some(Some(value)) => Ok(value),
+        Some(None) => Err(env::VarError::NotPresent),
+        None => env::var(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_active_profile_is_read_without_touching_the_environment() {
+        let Value::Object(table) = manifest::parse("modelo = \"gpt-4o\"\nclave_env = \"EMPRESA_API_KEY\"\n").unwrap() else {
+            panic!("no es una tabla");
+        };
+        let profile = Profile::parse("prueba", &table).unwrap();
+        assert!(profile.variables.contains(&("DEEPSEEK_MODEL".to_string(), Setting::Value("gpt-4o".to_string()))));
+        assert!(profile.variables.contains(&("IAGENT_API_KEY".to_string(), Setting::Remove)));
+
+        // Se aplica a mano para no depender de un iagent.toml; PATH lo quita el perfil
+        *ACTIVE.lock().unwrap() = Some(Applied {
+            name: profile.name.clone(),
+            values: vec![("IAGENT_PRUEBA_PERFIL".to_string(), Some("1".to_string())), ("PATH".to_string(), None)],
+        });
+        assert_eq!(var("IAGENT_PRUEBA_PERFIL").as_deref(), Ok("1"));
+        assert!(var("PATH").is_err());
+        assert!(env::var("IAGENT_PRUEBA_PERFIL").is_err());
+        assert!(env::var("PATH").is_ok());
+
+        deactivate();
+        assert!(var("IAGENT_PRUEBA_PERFIL").is_err());
+        assert!(var("PATH").is_ok());
+    }
+}
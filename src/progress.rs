@@ -0,0 +1,170 @@
+// Indicadores de progreso en stderr: una barra cuando se conoce el total (varios
+// archivos, lotes de preguntas o de embeddings) y un spinner mientras se espera al
+// modelo o a una lectura larga. Solo se dibujan para el agente de la terminal y si
+// stderr es una terminal, así que los guiones, las salidas redirigidas y las
+// aplicaciones que usan la biblioteca no cambian.
+use crate::settings::{self, Settings};
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 24;
+const SPINNER_FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+// Las operaciones más cortas no llegan a mostrar el spinner
+const SPINNER_DELAY: Duration = Duration::from_millis(400);
+
+fn enabled() -> bool {
+    settings::current().terminal() && io::stderr().is_terminal()
+}
+
+fn draw(line: &str) {
+    let mut stderr = io::stderr().lock();
+    let _ = write!(stderr, "\r\x1b[2K{}", line);
+    let _ = stderr.flush();
+}
+
+// Texto de la barra activa del agente, para que el spinner la mantenga a su izquierda
+fn bar_line(settings: &Settings) -> String {
+    settings.bar_line().lock().map(|line| line.clone()).unwrap_or_default()
+}
+
+fn set_bar_line(settings: &Settings, line: String) {
+    if let Ok(mut current) = settings.bar_line().lock() {
+        *current = line;
+    }
+}
+
+// Barra de progreso de una serie de pasos conocidos
+pub struct ProgressBar {
+    label: String,
+    total: usize,
+    done: usize,
+    started: Instant,
+    enabled: bool,
+    settings: Arc<Settings>,
+}
+
+impl ProgressBar {
+    pub fn new(label: &str, total: usize) -> ProgressBar {
+        let bar = ProgressBar {
+            label: label.to_string(),
+            total,
+            done: 0,
+            started: Instant::now(),
+            enabled: enabled() && total > 1,
+            settings: settings::current(),
+        };
+        bar.redraw("");
+        bar
+    }
+
+    // Un paso más; `item` es lo que se acaba de terminar
+    pub fn inc(&mut self, item: &str) {
+        self.done = (self.done + 1).min(self.total);
+        self.redraw(item);
+    }
+
+    // Escribe una línea en stdout sin romper la barra
+    pub fn println(&self, text: &str) {
+        if self.enabled {
+            draw("");
+        }
+        println!("{}", text);
+        if self.enabled {
+            draw(&bar_line(&self.settings));
+        }
+    }
+
+    fn redraw(&self, item: &str) {
+        if !self.enabled {
+            return;
+        }
+        let filled = BAR_WIDTH * self.done / self.total.max(1);
+        let mut line = format!(
+            "{} [{}{}] {}/{}",
+            self.label,
+            "█".repeat(filled),
+            "░".repeat(BAR_WIDTH - filled),
+            self.done,
+            self.total
+        );
+        // El tiempo restante se estima con la media de los pasos terminados
+        if self.done > 0 && self.done < self.total {
+            let remaining = self.started.elapsed().as_secs_f64() / self.done as f64 * (self.total - self.done) as f64;
+            line.push_str(&format!(" · ~{:.0} s", remaining.ceil()));
+        }
+        if !item.is_empty() {
+            line.push_str(&format!(" · {}", item));
+        }
+        set_bar_line(&self.settings, line.clone());
+        draw(&line);
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        if self.enabled {
+            set_bar_line(&self.settings, String::new());
+            draw("");
+        }
+    }
+}
+
+// Spinner con el tiempo transcurrido (y la barra activa delante); se detiene al soltarlo
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub fn start(message: impl Into<String>) -> Spinner {
+        let stop = Arc::new(AtomicBool::new(false));
+        if !enabled() {
+            return Spinner { stop, handle: None };
+        }
+        let message = message.into();
+        let flag = Arc::clone(&stop);
+        let settings = settings::current();
+        let handle = thread::spawn(move || {
+            let started = Instant::now();
+            let mut frame = 0;
+            while !flag.load(Ordering::SeqCst) {
+                thread::park_timeout(SPINNER_INTERVAL);
+                if flag.load(Ordering::SeqCst) || started.elapsed() < SPINNER_DELAY {
+                    continue;
+                }
+                let prefix = bar_line(&settings);
+                let separator = if prefix.is_empty() { "" } else { " " };
+                draw(&format!(
+                    "{}{}{} {} ({} s)",
+                    prefix,
+                    separator,
+                    SPINNER_FRAMES[frame % SPINNER_FRAMES.len()],
+                    message,
+                    started.elapsed().as_secs()
+                ));
+                frame += 1;
+            }
+            // Deja la línea libre para lo que se imprima a continuación; la
+            // barra activa se vuelve a dibujar en su siguiente paso
+            draw("");
+        });
+        Spinner {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
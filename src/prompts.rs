@@ -0,0 +1,96 @@
+use crate::paths;
+use crate::profiles;
+use crate::untrusted;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+
+pub const DEFAULT_SYSTEM_PROMPT: &str = "Eres un asistente especializado en manipular archivos Excel. Puedes analizar datos, crear gráficos, realizar cálculos y generar informes basados en datos de Excel. Responde de manera concisa y enfocada en la tarea solicitada.";
+
+// Personas incluidas; un archivo prompts/<nombre>.txt en el directorio de
+// configuración tiene prioridad
+const BUILTIN_PERSONAS: &[(&str, &str)] = &[
+    (
+        "analyst",
+        "Eres un analista de datos experto en Excel. Trabajas con {filename} (hojas: {sheets}). Explica tendencias, valores atípicos y conclusiones con cifras concretas, e indica qué cálculos has hecho.",
+    ),
+    (
+        "formatter",
+        "Eres un especialista en presentación de hojas de cálculo. Trabajas con {filename}. Propón formatos, anchos de columna, encabezados y estilos claros, sin alterar los datos.",
+    ),
+];
+
+// Obtiene la plantilla del prompt de sistema: persona, archivo o variable de entorno
+pub fn load_system_template(persona: Option<&str>) -> Result<String> {
+    if let Some(name) = persona {
+        return load_persona(name);
+    }
+    if let Ok(path) = profiles::var("IAGENT_SYSTEM_PROMPT_FILE") {
+        return fs::read_to_string(&path)
+            .context(format!("No se pudo leer el prompt de sistema {}", path));
+    }
+    if let Ok(prompt) = profiles::var("IAGENT_SYSTEM_PROMPT") {
+        return Ok(prompt);
+    }
+    Ok(DEFAULT_SYSTEM_PROMPT.to_string())
+}
+
+fn load_persona(name: &str) -> Result<String> {
+    let dir = paths::prompts_dir();
+    for extension in ["txt", "md"] {
+        let path = dir.join(format!("{}.{}", name, extension));
+        if path.is_file() {
+            return fs::read_to_string(&path)
+                .context(format!("No se pudo leer la persona {}", path.display()));
+        }
+    }
+    match BUILTIN_PERSONAS.iter().find(|(persona, _)| *persona == name) {
+        Some((_, template)) => Ok(template.to_string()),
+        None => bail!(
+            "No existe la persona '{}' (búscala en {})",
+            name,
+            dir.display()
+        ),
+    }
+}
+
+// Sustituye {variable} por su valor; las variables desconocidas se dejan intactas
+pub fn render(template: &str, vars: &HashMap<&str, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) if vars.contains_key(&after[..end]) => {
+                output.push_str(&vars[&after[..end]]);
+                rest = &after[end + 1..];
+            }
+            _ => {
+                output.push('{');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+// Variables disponibles a partir de los libros cargados (el último es el activo)
+pub fn workbook_vars(loaded: &[(String, Vec<String>)]) -> HashMap<&'static str, String> {
+    let mut vars = HashMap::new();
+    match loaded.last() {
+        Some((filename, sheets)) => {
+            // Los nombres vienen del archivo y van al prompt de sistema
+            vars.insert("filename", untrusted::sanitize(filename).text);
+            vars.insert("sheets", untrusted::sanitize(&sheets.join(", ")).text);
+        }
+        None => {
+            vars.insert("filename", "ningún archivo cargado todavía".to_string());
+            vars.insert("sheets", "-".to_string());
+        }
+    }
+    let all: Vec<&str> = loaded.iter().map(|(name, _)| name.as_str()).collect();
+    vars.insert("filenames", if all.is_empty() { "-".to_string() } else { all.join(", ") });
+    vars
+}
@@ -0,0 +1,313 @@
+// Protección de hojas (`proteger`): en una plantilla las celdas con fórmula
+// quedan bloqueadas y las de entrada siguen editables. Excel solo aplica el
+// bloqueo de cada celda (Formato de celdas > Proteger) cuando la hoja está
+// protegida, y por defecto todas están bloqueadas: proteger una hoja es
+// desbloquear las celdas de entrada y activar la protección. En un libro
+// existente se edita el XML (styles.xml y la hoja) para conservar fórmulas y
+// gráficos; los libros que genera el agente usan la protección de rust_xlsxwriter.
+use crate::excel::{self, CellRange, SheetData};
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+// Celdas vacías que se pueden desbloquear de una vez con editables=
+const MAX_EDITABLE_CELLS: usize = 10_000;
+
+// Atributos de <sheetProtection> con la contraseña (cifrada) de la hoja
+const PASSWORD_ATTRS: &[&str] = &["password", "algorithmName", "hashValue", "saltValue", "spinCount"];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SheetProtection {
+    // Contraseña puesta en la sesión
+    pub password: Option<String>,
+    // Contraseña cifrada de un libro leído, que se vuelve a poner al guardarlo
+    pub stored_password: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProtectOptions {
+    pub file: String,
+    pub sheet: String,
+    pub password: Option<String>,
+    // Celdas que quedan editables; vacío, todas las que no tienen fórmula
+    pub editable: Vec<CellRange>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProtectOutcome {
+    pub formulas: usize,
+    pub unlocked: usize,
+}
+
+// Protección declarada en el XML de una hoja
+pub fn parse(sheet_xml: &str) -> Option<SheetProtection> {
+    let tag = xlsx_patch::find_tags(sheet_xml, "sheetProtection").into_iter().next()?;
+    if !matches!(xlsx_patch::xml_attr(&tag, "sheet").as_deref(), Some("1" | "true")) {
+        return None;
+    }
+    Some(SheetProtection {
+        password: None,
+        stored_password: PASSWORD_ATTRS
+            .iter()
+            .filter_map(|name| Some((name.to_string(), xlsx_patch::xml_attr(&tag, name)?)))
+            .collect(),
+    })
+}
+
+// Rangos de editables=: "B2:B20,D2"
+pub fn parse_ranges(value: &str) -> Result<Vec<CellRange>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|range| !range.is_empty())
+        .map(|range| CellRange::parse(range).context(format!("Rango no válido: '{}' (usa p. ej. B2:B20)", range)))
+        .collect()
+}
+
+// Clave de 16 bits de la protección de hojas de Excel, la misma que calcula
+// rust_xlsxwriter con protect_with_password
+fn hash_password(password: &str) -> u16 {
+    if password.is_empty() {
+        return 0;
+    }
+    let mut hash: u16 = 0;
+    for byte in password.as_bytes().iter().rev() {
+        hash = ((hash >> 14) & 0x01) | ((hash << 1) & 0x7fff);
+        hash ^= u16::from(*byte);
+    }
+    hash = ((hash >> 14) & 0x01) | ((hash << 1) & 0x7fff);
+    hash ^= password.len() as u16;
+    hash ^ 0xCE4B
+}
+
+// Desbloquea en una hoja generada las celdas de entrada, para guardarla protegida
+pub fn lock_formulas(sheet: &mut SheetData, protection: SheetProtection) {
+    for (row_idx, row) in sheet.rows.iter().enumerate() {
+        for col_idx in 0..row.len() {
+            let unlocked = !sheet.formulas.contains_key(&(row_idx, col_idx));
+            if unlocked || sheet.styles.contains_key(&(row_idx, col_idx)) {
+                sheet.styles.entry((row_idx, col_idx)).or_default().unlocked = unlocked;
+            }
+        }
+    }
+    sheet.protection = Some(protection);
+}
+
+// Vuelve a poner tras guardar la contraseña cifrada de las hojas de un libro leído
+pub fn restore_passwords(path: &Path, sheets: &[SheetData]) -> Result<()> {
+    let pending: Vec<&SheetData> = sheets
+        .iter()
+        .filter(|sheet| sheet.protection.as_ref().is_some_and(|p| p.password.is_none() && !p.stored_password.is_empty()))
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let mut package = XlsxPackage::open(path)?;
+    for sheet in pending {
+        let Some(protection) = &sheet.protection else { continue };
+        package.edit_sheet(&sheet.name, |xml| {
+            let attrs: String = protection
+                .stored_password
+                .iter()
+                .map(|(key, value)| format!(" {}=\"{}\"", key, value))
+                .collect();
+            Ok(xml.replacen("<sheetProtection", &format!("<sheetProtection{}", attrs), 1))
+        })?;
+    }
+    package.save(path)
+}
+
+// Protege una hoja de un libro existente sin reescribir el resto
+pub fn apply(options: &ProtectOptions) -> Result<ProtectOutcome> {
+    let path = Path::new(&options.file);
+    let mut package = XlsxPackage::open(path)?;
+    let part = package.sheet_part(&options.sheet)?;
+    let mut xml = package.read_part(&part).context(format!("Falta la parte {}", part))?;
+    let styles_xml = package.read_part("xl/styles.xml").context("Falta xl/styles.xml")?;
+    let mut styles = CellXfs::parse(&styles_xml)?;
+
+    // Las celdas vacías de los rangos editables no existen en el XML: se crean
+    // para poder darles un estilo desbloqueado
+    if !options.editable.is_empty() {
+        let present: HashSet<(usize, usize)> = xlsx_patch::find_tags(&xml, "c")
+            .iter()
+            .filter_map(|tag| xlsx_patch::xml_attr(tag, "r"))
+            .filter_map(|reference| excel::parse_cell_ref(&reference))
+            .collect();
+        let missing: Vec<(usize, usize, String)> = options
+            .editable
+            .iter()
+            .flat_map(|range| (range.first_row..=range.last_row).flat_map(move |row| (range.first_col..=range.last_col).map(move |col| (row, col))))
+            .filter(|cell| !present.contains(cell))
+            .map(|(row, col)| (row, col, String::new()))
+            .collect();
+        if missing.len() > MAX_EDITABLE_CELLS {
+            bail!("Los rangos editables tienen demasiadas celdas vacías ({}; como mucho {})", missing.len(), MAX_EDITABLE_CELLS);
+        }
+        xml = xlsx_patch::set_cell_values(&xml, &missing)?;
+    }
+
+    let mut outcome = ProtectOutcome::default();
+    let mut output = String::with_capacity(xml.len());
+    let mut rest = xml.as_str();
+    while let Some(start) = xlsx_patch::find_element_start(rest, "c") {
+        let tag_end = start + rest[start..].find('>').context("XML de hoja no válido")?;
+        let end = if rest[start..=tag_end].ends_with("/>") {
+            tag_end + 1
+        } else {
+            tag_end + rest[tag_end..].find("</c>").context("XML de hoja no válido")? + "</c>".len()
+        };
+        let tag = &rest[start..=tag_end];
+        let formula = rest[tag_end..end].contains("<f");
+        let cell = xlsx_patch::xml_attr(tag, "r").and_then(|reference| excel::parse_cell_ref(&reference));
+        let editable = !formula
+            && match cell {
+                Some((row, col)) => options.editable.is_empty() || options.editable.iter().any(|range| range.contains(row, col)),
+                None => false,
+            };
+        outcome.formulas += usize::from(formula);
+        outcome.unlocked += usize::from(editable);
+        let style = xlsx_patch::xml_attr(tag, "s").and_then(|s| s.parse().ok()).unwrap_or(0);
+        let new_style = styles.with_lock(style, !editable)?;
+        output.push_str(&rest[..start]);
+        output.push_str(&set_style(tag, style, new_style));
+        rest = &rest[tag_end + 1..];
+    }
+    output.push_str(rest);
+
+    let hash = options.password.as_deref().map(hash_password).filter(|hash| *hash != 0);
+    let element = format!(
+        "<sheetProtection{} sheet=\"1\" objects=\"1\" scenarios=\"1\"/>",
+        hash.map(|hash| format!(" password=\"{:04X}\"", hash)).unwrap_or_default()
+    );
+    let xml = match xlsx_patch::find_element_start(&output, "sheetProtection") {
+        Some(start) => {
+            let end = start + output[start..].find("/>").context("XML de hoja no válido")? + 2;
+            format!("{}{}{}", &output[..start], element, &output[end..])
+        }
+        None => xlsx_patch::insert_worksheet_element(&output, "sheetProtection", &element)?,
+    };
+    package.write_part(&part, xml);
+    package.write_part("xl/styles.xml", styles.to_xml(&styles_xml)?);
+    package.save(path)?;
+    Ok(outcome)
+}
+
+// Cambia (o quita, si es el 0 por defecto) el atributo s de una etiqueta <c>
+fn set_style(tag: &str, old: usize, new: usize) -> String {
+    if old == new {
+        return tag.to_string();
+    }
+    let without = match xlsx_patch::xml_attr(tag, "s") {
+        Some(value) => tag.replacen(&format!(" s=\"{}\"", value), "", 1),
+        None => tag.to_string(),
+    };
+    if new == 0 {
+        return without;
+    }
+    let close = if without.ends_with("/>") { without.len() - 2 } else { without.len() - 1 };
+    format!("{} s=\"{}\"{}", without[..close].trim_end(), new, &without[close..])
+}
+
+// Estilos de celda (<cellXfs>) de styles.xml, con las copias bloqueadas o
+// desbloqueadas que se van necesitando
+struct CellXfs {
+    xfs: Vec<String>,
+    // (estilo original, bloqueado) -> estilo con ese bloqueo
+    variants: HashMap<(usize, bool), usize>,
+}
+
+impl CellXfs {
+    fn parse(styles_xml: &str) -> Result<CellXfs> {
+        let start = xlsx_patch::find_element_start(styles_xml, "cellXfs").context("styles.xml no tiene cellXfs")?;
+        let end = styles_xml[start..].find("</cellXfs>").map(|end| start + end).context("styles.xml no válido")?;
+        let body = &styles_xml[start..end];
+        let mut xfs = Vec::new();
+        let mut offset = body.find('>').context("styles.xml no válido")? + 1;
+        while let Some(found) = xlsx_patch::find_element_start(&body[offset..], "xf") {
+            let xf_start = offset + found;
+            let tag_end = xf_start + body[xf_start..].find('>').context("styles.xml no válido")?;
+            let xf_end = if body[xf_start..=tag_end].ends_with("/>") {
+                tag_end + 1
+            } else {
+                tag_end + body[tag_end..].find("</xf>").context("styles.xml no válido")? + "</xf>".len()
+            };
+            xfs.push(body[xf_start..xf_end].to_string());
+            offset = xf_end;
+        }
+        if xfs.is_empty() {
+            bail!("styles.xml no tiene estilos de celda");
+        }
+        Ok(CellXfs {
+            xfs,
+            variants: HashMap::new(),
+        })
+    }
+
+    fn is_locked(xf: &str) -> bool {
+        !xlsx_patch::find_tags(xf, "protection")
+            .first()
+            .is_some_and(|tag| matches!(xlsx_patch::xml_attr(tag, "locked").as_deref(), Some("0" | "false")))
+    }
+
+    // Índice de un estilo igual a `style` pero con el bloqueo pedido
+    fn with_lock(&mut self, style: usize, locked: bool) -> Result<usize> {
+        let xf = self.xfs.get(style).context(format!("La hoja usa un estilo que no existe ({})", style))?;
+        if Self::is_locked(xf) == locked {
+            return Ok(style);
+        }
+        if let Some(variant) = self.variants.get(&(style, locked)) {
+            return Ok(*variant);
+        }
+        let variant = set_xf_lock(xf, locked);
+        let index = match self.xfs.iter().position(|existing| *existing == variant) {
+            Some(index) => index,
+            None => {
+                self.xfs.push(variant);
+                self.xfs.len() - 1
+            }
+        };
+        self.variants.insert((style, locked), index);
+        Ok(index)
+    }
+
+    fn to_xml(&self, styles_xml: &str) -> Result<String> {
+        let start = xlsx_patch::find_element_start(styles_xml, "cellXfs").context("styles.xml no tiene cellXfs")?;
+        let end = styles_xml[start..].find("</cellXfs>").map(|end| start + end).context("styles.xml no válido")?;
+        Ok(format!(
+            "{}<cellXfs count=\"{}\">{}{}",
+            &styles_xml[..start],
+            self.xfs.len(),
+            self.xfs.concat(),
+            &styles_xml[end..]
+        ))
+    }
+}
+
+// Copia de un <xf> con <protection locked="0"/> (o sin ella, bloqueado)
+fn set_xf_lock(xf: &str, locked: bool) -> String {
+    let tag_end = xf.find('>').unwrap_or(xf.len() - 1);
+    let self_closing = xf[..=tag_end].ends_with("/>");
+    let open = xf[..tag_end].trim_end_matches('/').trim_end();
+    let open = match xlsx_patch::xml_attr(open, "applyProtection") {
+        Some(value) => open.replacen(&format!(" applyProtection=\"{}\"", value), "", 1),
+        None => open.to_string(),
+    };
+    let mut body = if self_closing { String::new() } else { xf[tag_end + 1..xf.len() - "</xf>".len()].to_string() };
+    if let Some(start) = xlsx_patch::find_element_start(&body, "protection") {
+        let end = body[start..].find("/>").map_or(body.len(), |end| start + end + 2);
+        body.replace_range(start..end, "");
+    }
+    if !locked {
+        // <protection> va tras <alignment> y antes de <extLst>
+        let at = xlsx_patch::find_element_start(&body, "extLst").unwrap_or(body.len());
+        body.insert_str(at, "<protection locked=\"0\"/>");
+    }
+    let apply = if locked { "" } else { " applyProtection=\"1\"" };
+    if body.is_empty() {
+        format!("{}{}/>", open, apply)
+    } else {
+        format!("{}{}>{}</xf>", open, apply, body)
+    }
+}
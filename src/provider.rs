@@ -0,0 +1,234 @@
+// Proveedores del modelo que no usan la red, para probar el bucle de herramientas,
+// el despacho de herramientas y el historial sin llamar a la API:
+//   - IAGENT_MOCK=<respuestas.json>: MockProvider devuelve respuestas preparadas en orden
+//   - IAGENT_REPLAY=<dir>: ReplayProvider devuelve las respuestas grabadas con IAGENT_RECORD
+// IAGENT_RECORD=<dir> sigue llamando a la API y guarda cada respuesta como fixture,
+// con la misma clave que la caché (proveedor, modelo y cuerpo de la petición), así
+// una grabación vale para cualquier sesión que repita las mismas peticiones.
+//
+// Formato de respuestas.json:
+// [
+//   {"herramienta": "leer_excel", "argumentos": {"archivo": "ventas.xlsx"}},
+//   "Las ventas de enero suman 1.200 €",
+//   {"error": "Servicio no disponible", "estado": 503}
+// ]
+use crate::error::IAgentError;
+use crate::llm::{DeepseekMessage, FunctionCall, ToolCall};
+use crate::usage::Usage;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Respuesta de un proveedor: el mensaje del modelo y su consumo, si lo informa
+pub type Reply = (DeepseekMessage, Option<Usage>);
+
+pub trait Provider: fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+    // `key` es la clave de caché de la petición
+    fn complete(&self, key: &str) -> Result<Reply>;
+}
+
+// Respuestas preparadas que se entregan una por petición
+#[derive(Debug)]
+pub struct MockProvider {
+    replies: Mutex<VecDeque<MockReply>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum MockReply {
+    Text(String),
+    Tool { name: String, arguments: Value },
+    Error { status: u16, message: String },
+}
+
+impl MockProvider {
+    pub fn new(replies: Vec<MockReply>) -> MockProvider {
+        MockProvider {
+            replies: Mutex::new(replies.into()),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<MockProvider> {
+        let text = fs::read_to_string(path).context(format!("No se pudo leer {}", path.display()))?;
+        let value: Value = serde_json::from_str(&text).map_err(|e| {
+            IAgentError::parse(&path.display().to_string(), format!("Respuestas simuladas no válidas ({})", e))
+        })?;
+        let items = value
+            .as_array()
+            .context(format!("{} debe contener una lista de respuestas", path.display()))?;
+        let replies = items
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| MockReply::from_json(item).context(format!("Respuesta simulada {}", idx + 1)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(MockProvider::new(replies))
+    }
+}
+
+impl MockReply {
+    fn from_json(value: &Value) -> Result<MockReply> {
+        if let Some(text) = value.as_str() {
+            return Ok(MockReply::Text(text.to_string()));
+        }
+        if let Some(name) = value.get("herramienta").and_then(Value::as_str) {
+            return Ok(MockReply::Tool {
+                name: name.to_string(),
+                arguments: value.get("argumentos").cloned().unwrap_or_else(|| Value::Object(Default::default())),
+            });
+        }
+        if let Some(message) = value.get("error").and_then(Value::as_str) {
+            let status = value.get("estado").and_then(Value::as_u64).unwrap_or(500);
+            return Ok(MockReply::Error {
+                status: u16::try_from(status).unwrap_or(500),
+                message: message.to_string(),
+            });
+        }
+        bail!("Usa un texto, {{\"herramienta\", \"argumentos\"}} o {{\"error\", \"estado\"}}")
+    }
+}
+
+impl Provider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn complete(&self, _key: &str) -> Result<Reply> {
+        let mut replies = self.replies.lock().map_err(|_| anyhow::anyhow!("MockProvider bloqueado"))?;
+        let reply = replies
+            .pop_front()
+            .context("El proveedor simulado no tiene más respuestas preparadas")?;
+        let message = match reply {
+            MockReply::Text(text) => DeepseekMessage {
+                content: Some(text),
+                tool_calls: None,
+            },
+            MockReply::Tool { name, arguments } => DeepseekMessage {
+                content: None,
+                tool_calls: Some(vec![ToolCall {
+                    id: format!("mock_{}", replies.len()),
+                    kind: "function".to_string(),
+                    function: FunctionCall {
+                        name,
+                        arguments: arguments.to_string(),
+                    },
+                }]),
+            },
+            MockReply::Error { status, message } => return Err(IAgentError::api(status, &message).into()),
+        };
+        Ok((message, None))
+    }
+}
+
+// Fixture grabado: la petición se guarda solo para poder leerlo y compararlo
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    request: Value,
+    message: DeepseekMessage,
+    usage: Option<Usage>,
+}
+
+// Respuestas grabadas con IAGENT_RECORD, sin red
+#[derive(Debug)]
+pub struct ReplayProvider {
+    dir: PathBuf,
+}
+
+impl ReplayProvider {
+    pub fn new(dir: &Path) -> Result<ReplayProvider> {
+        if !dir.is_dir() {
+            bail!("No existe el directorio de fixtures {}", dir.display());
+        }
+        Ok(ReplayProvider { dir: dir.to_path_buf() })
+    }
+}
+
+impl Provider for ReplayProvider {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn complete(&self, key: &str) -> Result<Reply> {
+        let path = fixture_path(&self.dir, key);
+        let text = fs::read_to_string(&path).context(format!(
+            "No hay respuesta grabada para esta petición ({}); grábala con IAGENT_RECORD={}",
+            path.display(),
+            self.dir.display()
+        ))?;
+        let fixture: Fixture = serde_json::from_str(&text).context(format!("Fixture no válido: {}", path.display()))?;
+        Ok((fixture.message, fixture.usage))
+    }
+}
+
+// Guarda la respuesta real de la API para reproducirla después
+pub fn record(dir: &Path, key: &str, request: &Value, message: &DeepseekMessage, usage: Option<Usage>) -> Result<()> {
+    fs::create_dir_all(dir).context(format!("No se pudo crear {}", dir.display()))?;
+    let fixture = Fixture {
+        request: request.clone(),
+        message: message.clone(),
+        usage,
+    };
+    let path = fixture_path(dir, key);
+    fs::write(&path, serde_json::to_string_pretty(&fixture)?).context(format!("No se pudo guardar {}", path.display()))
+}
+
+fn fixture_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}.json", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IAgentError;
+
+    #[test]
+    fn the_replies_file_accepts_text_tool_calls_and_errors_in_order() {
+        let path = crate::paths::test_dir("the_replies_file_accepts_text_tool_calls_and_errors_in_order").join("respuestas.json");
+        fs::write(
+            &path,
+            r#"[{"herramienta": "leer_excel", "argumentos": {"archivo": "ventas.xlsx"}}, "Listo", {"error": "Servicio no disponible", "estado": 503}]"#,
+        )
+        .unwrap();
+        let mock = MockProvider::from_file(&path).unwrap();
+
+        let (message, _) = mock.complete("").unwrap();
+        let call = &message.tool_calls.unwrap()[0];
+        assert_eq!(call.function.name, "leer_excel");
+        assert_eq!(serde_json::from_str::<Value>(&call.function.arguments).unwrap()["archivo"], "ventas.xlsx");
+        assert_eq!(mock.complete("").unwrap().0.content.as_deref(), Some("Listo"));
+        let error = mock.complete("").unwrap_err();
+        assert!(matches!(IAgentError::find(&error), Some(IAgentError::Api { status: 503, .. })));
+        assert!(mock.complete("").is_err());
+    }
+
+    #[test]
+    fn an_unknown_reply_is_reported_with_its_position() {
+        let path = crate::paths::test_dir("an_unknown_reply_is_reported_with_its_position").join("respuestas.json");
+        fs::write(&path, r#"["Hola", {"texto": "sin tipo"}]"#).unwrap();
+        let error = MockProvider::from_file(&path).unwrap_err();
+        assert!(format!("{:#}", error).starts_with("Respuesta simulada 2"), "{:#}", error);
+    }
+
+    #[test]
+    fn a_recorded_fixture_replays_its_message_and_usage() {
+        let dir = crate::paths::test_dir("a_recorded_fixture_replays_its_message_and_usage");
+        let message = DeepseekMessage {
+            content: Some("Hay 2 clientes".to_string()),
+            tool_calls: None,
+        };
+        let usage = Usage {
+            prompt_tokens: 120,
+            completion_tokens: 8,
+        };
+        record(&dir, "clave", &serde_json::json!({"model": "deepseek-chat"}), &message, Some(usage)).unwrap();
+
+        let (replayed, replayed_usage) = ReplayProvider::new(&dir).unwrap().complete("clave").unwrap();
+        assert_eq!(replayed.content.as_deref(), Some("Hay 2 clientes"));
+        assert_eq!(replayed_usage.map(|u| (u.prompt_tokens, u.completion_tokens)), Some((120, 8)));
+        assert!(ReplayProvider::new(&dir.join("no_existe")).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+// Hoja "Léeme" para los libros generados: qué contiene cada hoja, de dónde salen
+// los datos, qué supuestos se aplicaron y cuándo se generó
+use crate::analysis::{CohortOptions, ParetoOptions, RankOptions};
+use crate::batch::BatchOptions;
+use crate::config::Config;
+use crate::excel::{self, SheetData};
+use crate::interrupt;
+use crate::llm::{self, Message};
+use crate::report::ReportOptions;
+use crate::sampling;
+use crate::usage::UsageTracker;
+use crate::xlsx_patch::{xml_escape, XlsxPackage};
+use anyhow::Result;
+use reqwest::Client;
+use std::path::Path;
+
+pub const README_SHEET: &str = "Léeme";
+
+// Metadatos del proceso que generó el libro
+#[derive(Debug, Clone)]
+pub struct GenerationInfo {
+    pub operation: String,
+    pub sources: Vec<String>,
+    pub assumptions: Vec<String>,
+}
+
+impl GenerationInfo {
+    pub fn for_rank(options: &RankOptions) -> GenerationInfo {
+        let label = if options.ascending { "Bottom" } else { "Top" };
+        let mut assumptions = vec![format!(
+            "Las filas sin valor numérico en '{}' no se incluyen",
+            options.by
+        )];
+        if let Some(group) = &options.group_by {
+            assumptions.push(format!("Los valores de '{}' se suman por '{}'", options.by, group));
+        }
+        GenerationInfo {
+            operation: format!("Ranking {} {} por '{}'", label, options.n, options.by),
+            sources: vec![format!("{} (hoja {})", options.file, options.sheet)],
+            assumptions,
+        }
+    }
+
+    pub fn for_pareto(options: &ParetoOptions) -> GenerationInfo {
+        let mut assumptions = vec![
+            format!("Los porcentajes se calculan sobre el total de '{}'", options.by),
+            format!("Corte de Pareto en el {}% acumulado", options.cut),
+        ];
+        if let Some(group) = &options.group_by {
+            assumptions.push(format!("Los valores se suman por '{}'", group));
+        }
+        GenerationInfo {
+            operation: format!("Análisis de Pareto por '{}'", options.by),
+            sources: vec![format!("{} (hoja {})", options.file, options.sheet)],
+            assumptions,
+        }
+    }
+
+    pub fn for_cohorts(options: &CohortOptions) -> GenerationInfo {
+        let metric = match (&options.value, &options.customer) {
+            (Some(value), _) => format!("suma de '{}'", value),
+            (None, Some(customer)) => format!("clientes distintos según '{}'", customer),
+            (None, None) => "número de eventos".to_string(),
+        };
+        let mut assumptions = vec![
+            format!("Cohortes mensuales según el mes de '{}'", options.signup),
+            format!("Cada columna es el número de meses transcurridos hasta '{}'", options.event),
+            format!("Métrica: {}", metric),
+        ];
+        if options.relative {
+            assumptions.push("Valores expresados como porcentaje de la cohorte".to_string());
+        }
+        let source = match &options.sheet {
+            Some(sheet) => format!("{} (hoja {})", options.file, sheet),
+            None => options.file.clone(),
+        };
+        GenerationInfo {
+            operation: "Análisis de cohortes".to_string(),
+            sources: vec![source],
+            assumptions,
+        }
+    }
+
+    pub fn for_batch(options: &BatchOptions, files: Vec<String>) -> GenerationInfo {
+        GenerationInfo {
+            operation: format!("Pregunta en lote: \"{}\"", options.question),
+            sources: files,
+            assumptions: vec![
+                "Cada archivo se consulta por separado, sin contexto de los demás".to_string(),
+                "Las respuestas y citas las redacta el modelo a partir de cada archivo".to_string(),
+            ],
+        }
+    }
+
+    pub fn for_report(options: &ReportOptions) -> GenerationInfo {
+        GenerationInfo {
+            operation: format!("Informe generado con la plantilla {}", options.template),
+            sources: vec![options.data.clone()],
+            assumptions: vec![
+                "Las columnas, agrupaciones, totales y gráficos son los que define la plantilla".to_string(),
+                "Los totales y columnas calculadas son fórmulas que Excel recalcula".to_string(),
+            ],
+        }
+    }
+
+    pub fn for_conversion(source: &Path) -> GenerationInfo {
+        GenerationInfo {
+            operation: "Conversión de formato a xlsx".to_string(),
+            sources: vec![source.display().to_string()],
+            assumptions: vec!["Los tipos de las celdas se infieren del texto de origen".to_string()],
+        }
+    }
+}
+
+// Añade (o actualiza) la hoja Léeme como primera pestaña del libro.
+// La descripción la redacta el modelo; si falla se usan los metadatos tal cual.
+pub async fn add_readme_sheet(
+    client: &Client,
+    config: &Config,
+    usage_tracker: &mut UsageTracker,
+    path: &str,
+    info: &GenerationInfo,
+) -> Result<()> {
+    let data = excel::read_excel_file(path)?;
+    let sheets: Vec<(String, String)> = data
+        .sheets
+        .iter()
+        .filter(|sheet| !sheet.name.eq_ignore_ascii_case(README_SHEET))
+        .map(|sheet| (sheet.name.clone(), describe_sheet(sheet)))
+        .collect();
+    // Ctrl-C mientras el modelo redacta deja la descripción a partir de los metadatos
+    let drafted = interrupt::interruptible(draft_description(client, config, usage_tracker, info, &sheets)).await;
+    let description = match drafted {
+        Some(Ok(text)) if !text.trim().is_empty() => text.trim().to_string(),
+        _ => format!("{} a partir de {}.", info.operation, info.sources.join(", ")),
+    };
+
+    let mut rows: Vec<Vec<String>> = vec![
+        vec![README_SHEET.to_string()],
+        vec![],
+        vec!["Descripción".to_string(), description],
+        vec![
+            "Generado".to_string(),
+            format!("{} (UTC)", excel::excel_serial_to_iso(excel::now_serial())),
+        ],
+        vec!["Origen".to_string(), info.sources.join(", ")],
+        vec!["Proceso".to_string(), info.operation.clone()],
+    ];
+    // Con qué parámetros respondió el modelo, para comparar regeneraciones
+    if let Some(params) = sampling::last() {
+        rows.push(vec!["Modelo".to_string(), params.describe()]);
+    }
+    rows.extend([
+        vec![],
+        vec!["Hoja".to_string(), "Contenido".to_string()],
+    ]);
+    rows.extend(sheets.into_iter().map(|(name, content)| vec![name, content]));
+    if !info.assumptions.is_empty() {
+        rows.push(vec![]);
+        rows.push(vec!["Supuestos".to_string()]);
+        rows.extend(info.assumptions.iter().map(|a| vec!["-".to_string(), a.clone()]));
+    }
+
+    let mut package = XlsxPackage::open(Path::new(path))?;
+    package.insert_first_sheet(README_SHEET, sheet_xml(&rows))?;
+    package.save(Path::new(path))
+}
+
+// Pide al modelo un párrafo breve a partir de los metadatos
+async fn draft_description(
+    client: &Client,
+    config: &Config,
+    usage_tracker: &mut UsageTracker,
+    info: &GenerationInfo,
+    sheets: &[(String, String)],
+) -> Result<String> {
+    let mut metadata = format!("Proceso: {}\nOrigen: {}\nHojas:\n", info.operation, info.sources.join(", "));
+    for (name, content) in sheets {
+        metadata.push_str(&format!("- {}: {}\n", name, content));
+    }
+    metadata.push_str("Supuestos:\n");
+    for assumption in &info.assumptions {
+        metadata.push_str(&format!("- {}\n", assumption));
+    }
+    let messages = [
+        Message::new(
+            "system",
+            "Redactas la hoja 'Léeme' de libros Excel generados automáticamente. Escribe en español, en 2 a 4 frases y sin formato Markdown, qué contiene el libro y para qué sirve, dirigido a alguien que lo recibe sin más contexto. Responde solo con el texto.",
+        ),
+        Message::new("user", metadata),
+    ];
+    let completion = llm::get_deepseek_response(client, &config.for_command("leeme"), &messages, None).await?;
+    usage_tracker.record_completion(&config.provider_name(), &config.model, &completion);
+    Ok(completion.message.content.unwrap_or_default())
+}
+
+fn describe_sheet(sheet: &SheetData) -> String {
+    let headers = sheet.headers();
+    let data_rows = sheet.rows.len().saturating_sub(1);
+    if headers.is_empty() {
+        return "Hoja vacía".to_string();
+    }
+    let mut columns: Vec<String> = headers.into_iter().take(12).collect();
+    if sheet.rows[0].len() > columns.len() {
+        columns.push("…".to_string());
+    }
+    format!("{} filas de datos; columnas: {}", data_rows, columns.join(", "))
+}
+
+// XML de una hoja de dos columnas con texto en línea (sin tabla de cadenas compartidas)
+fn sheet_xml(rows: &[Vec<String>]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<worksheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\"><cols><col min=\"1\" max=\"1\" width=\"24\" customWidth=\"1\"/><col min=\"2\" max=\"2\" width=\"100\" customWidth=\"1\"/></cols><sheetData>",
+    );
+    for (row_idx, row) in rows.iter().enumerate() {
+        if row.iter().all(String::is_empty) {
+            continue;
+        }
+        xml.push_str(&format!("<row r=\"{}\">", row_idx + 1));
+        for (col_idx, value) in row.iter().enumerate() {
+            if value.is_empty() {
+                continue;
+            }
+            xml.push_str(&format!(
+                "<c r=\"{}{}\" t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+                excel::column_letters(col_idx),
+                row_idx + 1,
+                xml_escape(value)
+            ));
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData></worksheet>");
+    xml
+}
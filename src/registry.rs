@@ -0,0 +1,426 @@
+// Herramientas propias: además de las de Excel (tools.rs), el modelo puede usar
+// las que se declaren en un manifiesto TOML (IAGENT_TOOLS, o herramientas.toml en el
+// directorio de configuración si existe). Cada [[herramienta]] es de uno de estos tipos:
+//   - http: llama a una API (p. ej. una interna de la empresa) con los argumentos
+//     como cuerpo JSON (POST, por defecto) o como parámetros de consulta (GET)
+//   - comando: ejecuta un programa en el espacio de trabajo con los argumentos en
+//     JSON por la entrada estándar y devuelve lo que escriba en la salida
+//
+// [[herramienta]]
+// nombre = "tipo_cambio"
+// descripcion = "Tipo de cambio del día entre dos monedas"
+// tipo = "http"
+// url = "https://finanzas.interna/api/cambio/{origen}"
+// metodo = "GET"
+// cabeceras = { Authorization = "Bearer ${FINANZAS_TOKEN}" }
+// parametros = { origen = "texto", destino = "texto", fecha = "fecha?" }
+//
+// `parametros` usa los tipos de campos= de extraer_json, o es un texto con un JSON
+// Schema completo. En url, cabeceras y argumentos, ${VARIABLE} se sustituye por la
+// variable de entorno y {parametro} por el argumento del modelo.
+use crate::budget;
+use crate::config::HttpSettings;
+use crate::events::{self, AgentEvent};
+use crate::limits;
+use crate::llm;
+use crate::manifest;
+use crate::paths;
+use crate::profiles;
+use crate::sandbox::Workspace;
+use crate::structured::Schema;
+use crate::tools;
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+const MANIFEST_FILE: &str = "herramientas.toml";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
+
+// Lo que una herramienta recibe además de sus argumentos
+pub struct ToolContext<'a> {
+    // Tamaño máximo del resultado que se devolverá al modelo
+    pub max_tokens: usize,
+    pub workspace: &'a Workspace,
+}
+
+pub trait Tool: fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+    // Esquema en el formato de la API, como en tools::definitions
+    fn definition(&self) -> Value;
+    fn execute<'a>(&'a self, args: Value, context: ToolContext<'a>) -> ToolFuture<'a>;
+}
+
+// Herramientas de Excel más las propias
+#[derive(Debug, Default)]
+pub struct ToolRegistry {
+    custom: Vec<Box<dyn Tool>>,
+    // Manifiesto del que se cargaron, para los mensajes
+    source: Option<PathBuf>,
+}
+
+impl ToolRegistry {
+    // IAGENT_TOOLS=<manifiesto.toml>, o herramientas.toml en el directorio de configuración
+    pub fn from_env(http: &HttpSettings) -> Result<ToolRegistry> {
+        let path = match profiles::var("IAGENT_TOOLS") {
+            Ok(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+            _ => {
+                let path = paths::config_dir().join(MANIFEST_FILE);
+                if !path.exists() {
+                    return Ok(ToolRegistry::default());
+                }
+                path
+            }
+        };
+        ToolRegistry::load(&path, http)
+    }
+
+    pub fn load(path: &Path, http: &HttpSettings) -> Result<ToolRegistry> {
+        let text = fs::read_to_string(path).context(format!("No se pudo leer el manifiesto {}", path.display()))?;
+        let manifest = manifest::parse(&text).context(format!("Manifiesto de herramientas {} no válido", path.display()))?;
+        let entries = match manifest.get("herramienta").or_else(|| manifest.get("tool")) {
+            Some(Value::Array(entries)) => entries.clone(),
+            Some(_) => bail!("{}: usa [[herramienta]] para cada herramienta", path.display()),
+            None => Vec::new(),
+        };
+        let client = llm::build_client(http)?;
+        let mut registry = ToolRegistry {
+            custom: Vec::new(),
+            source: Some(path.to_path_buf()),
+        };
+        for (idx, entry) in entries.iter().enumerate() {
+            let tool = ManifestTool::parse(entry, &client)
+                .context(format!("{}: herramienta {} no válida", path.display(), idx + 1))?;
+            registry.register(Box::new(tool))?;
+        }
+        Ok(registry)
+    }
+
+    pub fn register(&mut self, tool: Box<dyn Tool>) -> Result<()> {
+        let name = tool.name().to_string();
+        if builtin_names().contains(&name) {
+            bail!("'{}' ya es una herramienta de IAgent; elige otro nombre", name);
+        }
+        if self.custom.iter().any(|t| t.name() == name) {
+            bail!("La herramienta '{}' está declarada dos veces", name);
+        }
+        self.custom.push(tool);
+        Ok(())
+    }
+
+    pub fn custom_names(&self) -> Vec<&str> {
+        self.custom.iter().map(|tool| tool.name()).collect()
+    }
+
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    pub fn definitions(&self) -> Value {
+        let mut definitions = match tools::definitions() {
+            Value::Array(items) => items,
+            _ => Vec::new(),
+        };
+        definitions.extend(self.custom.iter().map(|tool| tool.definition()));
+        Value::Array(definitions)
+    }
+
+    // Ejecuta la herramienta y avisa a los observadores de `events` con el resultado
+    pub async fn execute(&self, name: &str, arguments: &str, max_tokens: usize, workspace: &Workspace) -> Result<String> {
+        let started = Instant::now();
+        let result = self.run(name, arguments, max_tokens, workspace).await;
+        events::emit(AgentEvent::ToolInvoked {
+            name: name.to_string(),
+            ok: result.is_ok(),
+            millis: started.elapsed().as_millis() as u64,
+        });
+        if let Err(e) = &result {
+            events::emit(AgentEvent::Error {
+                source: format!("herramienta {}", name),
+                message: e.to_string(),
+            });
+        }
+        result
+    }
+
+    // Una herramienta propia o, si no lo es, una de Excel
+    async fn run(&self, name: &str, arguments: &str, max_tokens: usize, workspace: &Workspace) -> Result<String> {
+        let Some(tool) = self.custom.iter().find(|tool| tool.name() == name) else {
+            let (tool, arguments, workspace) = (name.to_string(), arguments.to_string(), workspace.clone());
+            return limits::run_blocking(&format!("La herramienta {}", name), move || {
+                tools::execute(&tool, &arguments, max_tokens, &workspace)
+            })
+            .await;
+        };
+        let args: Value = serde_json::from_str(if arguments.trim().is_empty() { "{}" } else { arguments })
+            .context("Los argumentos de la herramienta no son JSON válido")?;
+        tool.execute(args, ToolContext { max_tokens, workspace }).await
+    }
+}
+
+fn builtin_names() -> Vec<String> {
+    tools::definitions()
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.pointer("/function/name").and_then(Value::as_str))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Herramienta declarada en el manifiesto
+#[derive(Debug)]
+struct ManifestTool {
+    name: String,
+    description: String,
+    parameters: Value,
+    kind: ToolKind,
+    timeout: Duration,
+}
+
+#[derive(Debug)]
+enum ToolKind {
+    Http {
+        client: Client,
+        url: String,
+        method: String,
+        headers: Vec<(String, String)>,
+    },
+    Command {
+        program: String,
+        args: Vec<String>,
+    },
+}
+
+impl ManifestTool {
+    fn parse(entry: &Value, client: &Client) -> Result<ManifestTool> {
+        let text = |keys: &[&str]| keys.iter().find_map(|key| entry.get(*key).and_then(Value::as_str)).map(str::to_string);
+        let name = text(&["nombre", "name"]).context("Falta 'nombre'")?;
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            bail!("El nombre '{}' solo puede tener letras sin tildes, números, '_' y '-'", name);
+        }
+        let description = text(&["descripcion", "descripción", "description"])
+            .context(format!("Falta 'descripcion' en '{}'; el modelo la necesita para saber cuándo usarla", name))?;
+        let parameters = match entry.get("parametros").or_else(|| entry.get("parameters")) {
+            None => json!({ "type": "object", "properties": {} }),
+            Some(Value::String(schema)) => {
+                serde_json::from_str(schema).context(format!("'parametros' de '{}' no es un JSON Schema válido", name))?
+            }
+            Some(Value::Object(fields)) => object_schema(fields)?,
+            Some(_) => bail!("'parametros' debe ser una tabla de campos o un texto con un JSON Schema"),
+        };
+        let timeout = match entry.get("tiempo_maximo").or_else(|| entry.get("timeout")) {
+            Some(value) => Duration::from_secs(value.as_u64().context("'tiempo_maximo' debe ser un número de segundos")?),
+            None => DEFAULT_TIMEOUT,
+        };
+        let kind = match text(&["tipo", "type"]).as_deref() {
+            Some("http") => {
+                let headers = match entry.get("cabeceras").or_else(|| entry.get("headers")) {
+                    Some(Value::Object(headers)) => headers
+                        .iter()
+                        .map(|(key, value)| Ok((key.clone(), value.as_str().context("Las cabeceras deben ser textos")?.to_string())))
+                        .collect::<Result<Vec<_>>>()?,
+                    Some(_) => bail!("'cabeceras' debe ser una tabla, p. ej. {{ Authorization = \"Bearer ${{TOKEN}}\" }}"),
+                    None => Vec::new(),
+                };
+                ToolKind::Http {
+                    client: client.clone(),
+                    url: text(&["url"]).context(format!("Falta 'url' en '{}'", name))?,
+                    method: text(&["metodo", "método", "method"]).unwrap_or_else(|| "POST".to_string()).to_uppercase(),
+                    headers,
+                }
+            }
+            Some("comando" | "command") => ToolKind::Command {
+                program: text(&["programa", "program"]).context(format!("Falta 'programa' en '{}'", name))?,
+                args: match entry.get("argumentos").or_else(|| entry.get("args")) {
+                    Some(Value::Array(items)) => items
+                        .iter()
+                        .map(|item| item.as_str().map(str::to_string).context("'argumentos' debe ser una lista de textos"))
+                        .collect::<Result<_>>()?,
+                    Some(_) => bail!("'argumentos' debe ser una lista de textos"),
+                    None => Vec::new(),
+                },
+            },
+            Some(other) => bail!("Tipo '{}' desconocido en '{}' (usa http o comando)", other, name),
+            None => bail!("Falta 'tipo' (http o comando) en '{}'", name),
+        };
+        if let ToolKind::Http { method, .. } = &kind {
+            if !matches!(method.as_str(), "GET" | "POST" | "PUT" | "PATCH" | "DELETE") {
+                bail!("Método '{}' no válido en '{}'", method, name);
+            }
+        }
+        Ok(ManifestTool {
+            name,
+            description,
+            parameters,
+            kind,
+            timeout,
+        })
+    }
+
+    async fn run(&self, args: Value, context: ToolContext<'_>) -> Result<String> {
+        let output = match &self.kind {
+            ToolKind::Http { client, url, method, headers } => {
+                let url = expand(url, &args, true)?;
+                let mut request = match method.as_str() {
+                    "GET" => client.get(&url).query(&query_pairs(&args)),
+                    "DELETE" => client.delete(&url).query(&query_pairs(&args)),
+                    "PUT" => client.put(&url).json(&args),
+                    "PATCH" => client.patch(&url).json(&args),
+                    _ => client.post(&url).json(&args),
+                };
+                for (name, value) in headers {
+                    request = request.header(name.as_str(), expand(value, &args, false)?);
+                }
+                let response = request
+                    .timeout(self.timeout)
+                    .send()
+                    .await
+                    .context(format!("No se pudo llamar a {}", url))?;
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                if !status.is_success() {
+                    bail!("{} respondió {}: {}", url, status.as_u16(), budget::truncate_to_tokens(&body, 200));
+                }
+                body
+            }
+            ToolKind::Command { program, args: program_args } => {
+                let program_args = program_args
+                    .iter()
+                    .map(|arg| expand(arg, &args, false))
+                    .collect::<Result<Vec<_>>>()?;
+                let mut child = Command::new(program)
+                    .args(&program_args)
+                    .current_dir(context.workspace.root())
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .context(format!("No se pudo ejecutar {}", program))?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    // Un programa que no lee la entrada cierra la tubería; no es un error
+                    let _ = stdin.write_all(args.to_string().as_bytes()).await;
+                }
+                let output = tokio::time::timeout(self.timeout, child.wait_with_output())
+                    .await
+                    .map_err(|_| anyhow::anyhow!("{} no terminó en {} s", program, self.timeout.as_secs()))??;
+                let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    bail!("{} terminó con {}: {}", program, output.status, stderr.trim());
+                }
+                stdout
+            }
+        };
+        Ok(budget::truncate_to_tokens(&output, context.max_tokens))
+    }
+}
+
+impl Tool for ManifestTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn definition(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters
+            }
+        })
+    }
+
+    fn execute<'a>(&'a self, args: Value, context: ToolContext<'a>) -> ToolFuture<'a> {
+        Box::pin(self.run(args, context))
+    }
+}
+
+// { moneda = "texto", importe = "numero?" } como esquema de un objeto
+fn object_schema(fields: &Map<String, Value>) -> Result<Value> {
+    let spec = fields
+        .iter()
+        .map(|(name, kind)| {
+            let kind = kind.as_str().context(format!("El tipo del parámetro '{}' debe ser un texto", name))?;
+            // En campos= la marca de opcional va en el nombre
+            Ok(match kind.strip_suffix('?') {
+                Some(kind) => format!("{}?:{}", name, kind),
+                None => format!("{}:{}", name, kind),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?
+        .join(",");
+    if spec.is_empty() {
+        return Ok(json!({ "type": "object", "properties": {} }));
+    }
+    let schema = Schema::from_fields(&spec)?;
+    schema
+        .as_value()
+        .get("items")
+        .cloned()
+        .context("Esquema de parámetros no válido")
+}
+
+fn query_pairs(args: &Value) -> Vec<(String, String)> {
+    args.as_object()
+        .map(|fields| fields.iter().filter(|(_, value)| !value.is_null()).map(|(key, value)| (key.clone(), scalar_text(value))).collect())
+        .unwrap_or_default()
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Sustituye ${VARIABLE} por la variable de entorno y {parametro} por el argumento
+// (codificado para la URL si `in_url`)
+fn expand(template: &str, args: &Value, in_url: bool) -> Result<String> {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let is_env = rest[..start].ends_with('$');
+        let before = if is_env { &rest[..start - 1] } else { &rest[..start] };
+        output.push_str(before);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            output.push_str(&rest[before.len()..]);
+            return Ok(output);
+        };
+        let name = &after[..end];
+        if is_env {
+            output.push_str(&profiles::var(name).context(format!("Falta la variable de entorno {}", name))?);
+        } else {
+            let value = args.get(name).map(scalar_text).unwrap_or_default();
+            output.push_str(&if in_url { percent_encode(&value) } else { value });
+        }
+        rest = &after[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
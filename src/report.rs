@@ -0,0 +1,503 @@
+// Informes a partir de plantillas (`generar_informe`): un JSON describe las hojas
+// del informe (columnas tomadas de los datos o calculadas con fórmulas,
+// agrupaciones, totales, formatos, gráficos y formato condicional) y se rellena
+// con los datos de un libro, de modo que el mismo informe se rehace cada mes.
+// Con "detalle", cada fila de agrupar_por lleva debajo sus filas de los datos en
+// un grupo plegable ("contraido" lo deja cerrado); no se combina con "grafico".
+//
+// {
+//   "salida": "informe_ventas.xlsx",
+//   "hojas": [{
+//     "nombre": "Por cliente", "origen": "ventas", "agrupar_por": "Cliente",
+//     "columnas": [
+//       { "encabezado": "Cliente", "columna": "Cliente" },
+//       { "encabezado": "Importe", "columna": "Importe", "agregado": "suma", "formato": "#,##0.00" },
+//       { "encabezado": "IVA", "formula": "{Importe}*0.21", "formato": "#,##0.00" }
+//     ],
+//     "orden": "-Importe", "limite": 10, "totales": true, "detalle": "contraido",
+//     "grafico": { "tipo": "columnas", "categorias": "Cliente", "valores": ["Importe"] },
+//     "formato_condicional": [{ "columna": "Importe", "tipo": "barras" }],
+//     "diseño": { "congelar": 1, "autoajustar": true, "anchos": { "Cliente": 30 } },
+//     "proteger": true
+//   }]
+// }
+use crate::analysis;
+use crate::conditional_format::{self, ConditionalFormatOptions};
+use crate::convert;
+use crate::error::IAgentError;
+use crate::excel::{self, CellRange, CellValue, ChartKind, ChartSpec, SheetData, WorkbookData};
+use crate::formula;
+use crate::layout::{LayoutSpec, SheetLayout};
+use crate::outline::Span;
+use crate::outputs;
+use crate::protection::{self, SheetProtection};
+use crate::template::Template;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Argumentos que admite una función de Excel, como SUM
+const MAX_FUNCTION_ARGUMENTS: usize = 255;
+
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    pub template: String,
+    pub data: String,
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportTemplate {
+    #[serde(rename = "salida", default)]
+    output: Option<String>,
+    #[serde(rename = "hojas")]
+    sheets: Vec<SheetTemplate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SheetTemplate {
+    #[serde(rename = "nombre")]
+    name: String,
+    // Hoja de los datos; por defecto la primera
+    #[serde(rename = "origen", default)]
+    source: Option<String>,
+    #[serde(rename = "columnas")]
+    columns: Vec<ColumnTemplate>,
+    #[serde(rename = "agrupar_por", default)]
+    group_by: Option<String>,
+    // Encabezado del informe por el que ordenar; con '-' delante, de mayor a menor
+    #[serde(rename = "orden", default)]
+    sort: Option<String>,
+    #[serde(rename = "limite", default)]
+    limit: Option<usize>,
+    #[serde(rename = "totales", default)]
+    totals: bool,
+    // true o "abierto", o "contraido": las filas de cada grupo bajo su resumen
+    #[serde(rename = "detalle", default)]
+    detail: Option<Value>,
+    #[serde(rename = "grafico", default)]
+    chart: Option<ChartTemplate>,
+    // Reglas como las de la herramienta formato_condicional, con "columna" en lugar de "rango"
+    #[serde(rename = "formato_condicional", default)]
+    conditional_formats: Vec<Value>,
+    // Como el de ajustar_hoja; por defecto se inmoviliza el encabezado y se autoajusta
+    #[serde(rename = "diseño", default)]
+    layout: Option<Value>,
+    // true, o la contraseña: bloquea las fórmulas y deja editables las demás celdas
+    #[serde(rename = "proteger", default)]
+    protect: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ColumnTemplate {
+    #[serde(rename = "encabezado")]
+    header: String,
+    // Columna de los datos (encabezado, letra o número)
+    #[serde(rename = "columna", default)]
+    column: Option<String>,
+    // suma, media, cuenta, min o max; solo con agrupar_por
+    #[serde(rename = "agregado", default)]
+    aggregate: Option<String>,
+    // Fórmula por fila; {Encabezado} es la celda de esa columna en la fila y {fila} su número
+    #[serde(default)]
+    formula: Option<String>,
+    #[serde(rename = "formato", default)]
+    format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChartTemplate {
+    #[serde(rename = "tipo")]
+    kind: String,
+    #[serde(rename = "categorias")]
+    categories: String,
+    #[serde(rename = "valores")]
+    values: Vec<String>,
+    #[serde(rename = "titulo", default)]
+    title: Option<String>,
+    // Celda donde se ancla; por defecto a la derecha de la tabla
+    #[serde(rename = "posicion", default)]
+    anchor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Average,
+    Count,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn parse(value: &str) -> Option<Aggregate> {
+        match value.to_lowercase().as_str() {
+            "suma" | "sum" => Some(Aggregate::Sum),
+            "media" | "promedio" | "average" => Some(Aggregate::Average),
+            "cuenta" | "count" => Some(Aggregate::Count),
+            "min" | "mínimo" | "minimo" => Some(Aggregate::Min),
+            "max" | "máximo" | "maximo" => Some(Aggregate::Max),
+            _ => None,
+        }
+    }
+
+    fn apply(self, cells: &[&CellValue]) -> CellValue {
+        let numbers: Vec<f64> = cells.iter().filter_map(|cell| analysis::numeric_value(cell)).collect();
+        let value = match self {
+            Aggregate::Count => return CellValue::Number(cells.iter().filter(|c| **c != &CellValue::Empty).count() as f64),
+            _ if numbers.is_empty() => return CellValue::Empty,
+            Aggregate::Sum => numbers.iter().sum(),
+            Aggregate::Average => numbers.iter().sum::<f64>() / numbers.len() as f64,
+            Aggregate::Min => numbers.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregate::Max => numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        };
+        CellValue::Number(value)
+    }
+}
+
+// Hojas generadas: (nombre, filas de datos)
+pub struct ReportOutcome {
+    pub output: String,
+    pub sheets: Vec<(String, usize)>,
+    // Fórmulas que el evaluador local no sabe calcular: Excel las calculará al abrir
+    pub unsupported_formulas: usize,
+}
+
+pub fn generate(options: &ReportOptions) -> Result<ReportOutcome> {
+    let template = load_template(Path::new(&options.template))?;
+    let data = convert::read_any(Path::new(&options.data))?;
+    if template.sheets.is_empty() {
+        bail!("La plantilla {} no define ninguna hoja", options.template);
+    }
+
+    let mut report = WorkbookData::default();
+    let mut formulas = HashMap::new();
+    let mut sheets = Vec::new();
+    // (hoja, columna, filas de datos, regla) para aplicarlos después de guardar
+    let mut conditional = Vec::new();
+    for (sheet_idx, sheet_template) in template.sheets.iter().enumerate() {
+        let source = match &sheet_template.source {
+            Some(name) => data
+                .require_sheet(&options.data, name)?,
+            None => data.sheets.first().context(format!("{} no tiene hojas", options.data))?,
+        };
+        let sheet = build_sheet(sheet_template, source)
+            .context(format!("Error en la hoja '{}' de la plantilla", sheet_template.name))?;
+        for ((row, col), text) in &sheet.formulas {
+            formulas.insert((sheet_idx, *row, *col), text.clone());
+        }
+        let data_rows = sheet.rows.len() - 1 - usize::from(sheet_template.totals);
+        for rule in &sheet_template.conditional_formats {
+            let column = rule
+                .get("columna")
+                .and_then(Value::as_str)
+                .context("Cada formato condicional necesita 'columna'")?;
+            let col = header_index(sheet_template, column)?;
+            conditional.push((sheet.name.clone(), col, data_rows, conditional_format::rule_from_json(rule)?));
+        }
+        sheets.push((sheet.name.clone(), data_rows));
+        report.sheets.push(sheet);
+    }
+
+    // Los resultados de las fórmulas quedan en caché para quien lea el libro sin Excel
+    let evaluation = formula::evaluate(&mut report, &formulas);
+    let output = match options.output.clone().or(template.output) {
+        Some(output) => outputs::target(&output)?,
+        None => default_output(&options.data)?,
+    };
+    excel::save_workbook(Path::new(&output), &report)?;
+    for (sheet, col, data_rows, rule) in conditional.into_iter().filter(|entry| entry.2 > 0) {
+        conditional_format::apply(&ConditionalFormatOptions {
+            file: output.clone(),
+            sheet,
+            range: CellRange {
+                first_row: 1,
+                first_col: col,
+                last_row: data_rows,
+                last_col: col,
+            },
+            rule,
+        })?;
+    }
+    Ok(ReportOutcome {
+        output,
+        sheets,
+        unsupported_formulas: evaluation.unsupported,
+    })
+}
+
+fn load_template(path: &Path) -> Result<ReportTemplate> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    if matches!(extension.as_str(), "yaml" | "yml") {
+        bail!("las plantillas YAML no están disponibles en esta compilación; usa una plantilla .json");
+    }
+    let text = fs::read_to_string(path).context(format!("No se pudo leer la plantilla {}", path.display()))?;
+    serde_json::from_str(&text).map_err(|e| {
+        IAgentError::parse(&path.display().to_string(), format!("La plantilla no es válida ({})", e)).into()
+    })
+}
+
+fn default_output(data: &str) -> Result<String> {
+    let path = Path::new(data);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("datos");
+    outputs::derived(path, &format!("{}_informe.xlsx", stem))
+}
+
+fn header_index(template: &SheetTemplate, header: &str) -> Result<usize> {
+    template
+        .columns
+        .iter()
+        .position(|column| column.header.eq_ignore_ascii_case(header.trim()))
+        .context(format!("La plantilla no tiene la columna '{}'", header))
+}
+
+fn build_sheet(template: &SheetTemplate, source: &SheetData) -> Result<SheetData> {
+    if template.columns.is_empty() {
+        bail!("La hoja no define columnas");
+    }
+    let sources = template
+        .columns
+        .iter()
+        .map(|column| match (&column.column, &column.formula) {
+            (Some(spec), None) => analysis::require_column(source, spec).map(Some),
+            (None, Some(_)) => Ok(None),
+            _ => bail!("La columna '{}' necesita 'columna' o 'formula' (solo una)", column.header),
+        })
+        .collect::<Result<Vec<Option<usize>>>>()?;
+    let aggregates = template
+        .columns
+        .iter()
+        .map(|column| {
+            column
+                .aggregate
+                .as_deref()
+                .map(|name| Aggregate::parse(name).context(format!("Agregado no válido: {}", name)))
+                .transpose()
+        })
+        .collect::<Result<Vec<Option<Aggregate>>>>()?;
+
+    let detail = match &template.detail {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => None,
+        Some(Value::Bool(true)) => Some(false),
+        Some(Value::String(state)) => match state.to_lowercase().as_str() {
+            "abierto" | "open" | "expanded" => Some(false),
+            "contraido" | "contraído" | "collapsed" => Some(true),
+            _ => bail!("Valor no válido para 'detalle': {} (usa true, \"abierto\" o \"contraido\")", state),
+        },
+        Some(other) => bail!("Valor no válido para 'detalle': {} (usa true, \"abierto\" o \"contraido\")", other),
+    };
+    if detail.is_some() && template.group_by.is_none() {
+        bail!("'detalle' solo tiene sentido con 'agrupar_por'");
+    }
+    if detail.is_some() && template.chart.is_some() {
+        bail!("'detalle' no se puede combinar con 'grafico': el gráfico mezclaría resúmenes y detalle");
+    }
+    let source_row = |row: &Vec<CellValue>| -> Vec<CellValue> {
+        sources
+            .iter()
+            .map(|col| col.and_then(|col| row.get(col).cloned()).unwrap_or(CellValue::Empty))
+            .collect()
+    };
+
+    // Cada fila del informe con las filas de detalle que lleva debajo
+    let mut rows: Vec<(Vec<CellValue>, Vec<Vec<CellValue>>)> = match &template.group_by {
+        Some(spec) => {
+            let group_col = analysis::require_column(source, spec)?;
+            // Grupos en el orden en que aparecen
+            let mut groups: Vec<(String, Vec<&Vec<CellValue>>)> = Vec::new();
+            for row in source.rows.iter().skip(1) {
+                let key = row.get(group_col).map(ToString::to_string).unwrap_or_default();
+                match groups.iter_mut().find(|(existing, _)| *existing == key) {
+                    Some((_, members)) => members.push(row),
+                    None => groups.push((key, vec![row])),
+                }
+            }
+            groups
+                .iter()
+                .map(|(_, members)| {
+                    let summary = sources
+                        .iter()
+                        .zip(&aggregates)
+                        .map(|(col, aggregate)| match (col, aggregate) {
+                            (Some(col), Some(aggregate)) => {
+                                let cells: Vec<&CellValue> = members.iter().filter_map(|row| row.get(*col)).collect();
+                                aggregate.apply(&cells)
+                            }
+                            // Sin agregado se toma el valor de la primera fila del grupo
+                            (Some(col), None) => members[0].get(*col).cloned().unwrap_or(CellValue::Empty),
+                            (None, _) => CellValue::Empty,
+                        })
+                        .collect();
+                    let details = match detail {
+                        Some(_) => members.iter().map(|row| source_row(row)).collect(),
+                        None => Vec::new(),
+                    };
+                    (summary, details)
+                })
+                .collect()
+        }
+        None => {
+            if aggregates.iter().any(Option::is_some) {
+                bail!("'agregado' solo tiene sentido con 'agrupar_por'");
+            }
+            source.rows.iter().skip(1).map(|row| (source_row(row), Vec::new())).collect()
+        }
+    };
+
+    if let Some(sort) = &template.sort {
+        let (descending, header) = match sort.strip_prefix('-') {
+            Some(header) => (true, header),
+            None => (false, sort.as_str()),
+        };
+        let col = header_index(template, header)?;
+        if template.columns[col].formula.is_some() {
+            bail!("No se puede ordenar por la columna calculada '{}'", header);
+        }
+        rows.sort_by(|a, b| {
+            let ordering = compare_cells(&a.0[col], &b.0[col]);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+    if let Some(limit) = template.limit {
+        rows.truncate(limit);
+    }
+
+    let mut sheet = SheetData::new(&template.name);
+    sheet
+        .rows
+        .push(template.columns.iter().map(|c| CellValue::Text(c.header.clone())).collect());
+    // Filas de resumen (desde 0) y el detalle que agrupan debajo
+    let mut summaries = Vec::new();
+    for (summary, details) in rows {
+        sheet.rows.push(summary);
+        let summary_row = sheet.rows.len() - 1;
+        summaries.push(summary_row);
+        if !details.is_empty() {
+            let first = sheet.rows.len();
+            sheet.rows.extend(details);
+            sheet.outline.group(Span::Rows(first, sheet.rows.len() - 1), detail == Some(true))?;
+        }
+    }
+    sheet.outline.summary_above = true;
+    let data_rows = sheet.rows.len() - 1;
+    let headers: Vec<String> = template.columns.iter().map(|c| c.header.clone()).collect();
+    for (col, column) in template.columns.iter().enumerate() {
+        if let Some(format) = &column.format {
+            sheet.column_formats.insert(col, format.clone());
+        }
+        if let Some(text) = &column.formula {
+            let formula = Template::parse(text.trim_start_matches('='))
+                .and_then(|formula| formula.check(&headers, &["fila"]).map(|_| formula))
+                .context(format!("Fórmula de la columna '{}' no válida", column.header))?;
+            for row in 1..=data_rows {
+                sheet.formulas.insert((row, col), row_formula(template, &formula, row)?);
+            }
+        }
+    }
+
+    if template.totals && detail.is_some() && summaries.len() > MAX_FUNCTION_ARGUMENTS {
+        bail!(
+            "Con 'detalle', los totales suman cada fila de resumen y Excel admite {} como mucho ({} grupos); usa 'limite'",
+            MAX_FUNCTION_ARGUMENTS,
+            summaries.len()
+        );
+    }
+    if template.totals && data_rows > 0 {
+        let mut totals = vec![CellValue::Empty; template.columns.len()];
+        for col in 0..template.columns.len() {
+            let numeric = template.columns[col].formula.is_some()
+                || sheet.rows[1..].iter().any(|row| matches!(row[col], CellValue::Number(_)));
+            if numeric {
+                let letters = excel::column_letters(col);
+                let total = match detail {
+                    // Solo las filas de resumen, para no sumar dos veces el detalle
+                    Some(_) => {
+                        let cells: Vec<String> = summaries.iter().map(|row| format!("{}{}", letters, row + 1)).collect();
+                        format!("SUM({})", cells.join(","))
+                    }
+                    None => format!("SUM({}2:{}{})", letters, letters, data_rows + 1),
+                };
+                sheet.formulas.insert((data_rows + 1, col), total);
+            }
+        }
+        if !sheet.formulas.contains_key(&(data_rows + 1, 0)) {
+            totals[0] = CellValue::Text("Total".to_string());
+        }
+        sheet.rows.push(totals);
+    }
+
+    if let Some(chart) = &template.chart {
+        let kind = ChartKind::parse(&chart.kind).context(format!("Tipo de gráfico no válido: {}", chart.kind))?;
+        let value_cols = chart
+            .values
+            .iter()
+            .map(|header| header_index(template, header))
+            .collect::<Result<Vec<usize>>>()?;
+        let anchor = match &chart.anchor {
+            Some(cell) => excel::parse_cell_ref(cell).context(format!("Posición de gráfico no válida: {}", cell))?,
+            None => (1, template.columns.len() + 1),
+        };
+        if data_rows > 0 {
+            sheet.charts.push(ChartSpec {
+                kind,
+                title: chart.title.clone().unwrap_or_else(|| template.name.clone()),
+                category_col: header_index(template, &chart.categories)?,
+                value_cols,
+                first_row: 1,
+                last_row: data_rows,
+                anchor,
+            });
+        }
+    }
+    sheet.layout = match &template.layout {
+        Some(layout) => LayoutSpec::from_json(layout)
+            .and_then(|spec| spec.resolve(&sheet))
+            .context(format!("Diseño no válido en la hoja {}", template.name))?,
+        None => SheetLayout::readable(),
+    };
+    let password = match &template.protect {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => None,
+        Some(Value::Bool(true)) => Some(None),
+        Some(Value::String(password)) if !password.is_empty() => Some(Some(password.clone())),
+        Some(other) => bail!("Valor no válido para 'proteger' en la hoja {}: {} (usa true o una contraseña)", template.name, other),
+    };
+    if let Some(password) = password {
+        protection::lock_formulas(&mut sheet, SheetProtection { password, ..SheetProtection::default() });
+    }
+    Ok(sheet)
+}
+
+// Sustituye {Encabezado} por la celda de esa columna en la fila y {fila} por su número
+// {Encabezado} es la celda de esa columna en la fila y {fila} el número de la fila
+fn row_formula(template: &SheetTemplate, formula: &Template, row: usize) -> Result<String> {
+    formula.render(|name| {
+        if name.eq_ignore_ascii_case("fila") {
+            return Ok((row + 1).to_string());
+        }
+        let col = header_index(template, name)?;
+        Ok(format!("{}{}", excel::column_letters(col), row + 1))
+    })
+}
+
+// Números antes que textos y vacíos al final
+fn compare_cells(a: &CellValue, b: &CellValue) -> Ordering {
+    match (a.as_number(), b.as_number()) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => match (a, b) {
+            (CellValue::Empty, CellValue::Empty) => Ordering::Equal,
+            (CellValue::Empty, _) => Ordering::Greater,
+            (_, CellValue::Empty) => Ordering::Less,
+            _ => a.to_string().to_lowercase().cmp(&b.to_string().to_lowercase()),
+        },
+    }
+}
@@ -0,0 +1,380 @@
+// Búsqueda por similitud en hojas grandes: las filas se agrupan en bloques, cada
+// bloque se convierte en un vector (con una API de embeddings o localmente, con
+// términos con hash y pesos TF-IDF) y antes de cada pregunta se añaden al contexto
+// solo los bloques más parecidos a ella, en lugar de un resumen de cinco filas.
+use crate::backup;
+use crate::budget;
+use crate::config::Config;
+use crate::excel::{self, WorkbookData};
+use crate::llm;
+use crate::progress::{ProgressBar, Spinner};
+use crate::table;
+use anyhow::Result;
+use reqwest::Client;
+
+// Hojas con al menos estas filas de datos se indexan (IAGENT_RETRIEVAL_ROWS)
+pub const DEFAULT_MIN_ROWS: usize = 2_000;
+const CHUNK_ROWS: usize = 20;
+// Bloques añadidos como máximo a cada pregunta
+const TOP_CHUNKS: usize = 5;
+const LOCAL_DIMENSIONS: usize = 1024;
+// Textos por petición a la API de embeddings y longitud máxima de cada uno
+const API_BATCH: usize = 64;
+const MAX_EMBED_CHARS: usize = 4_000;
+
+// Cómo se calculan los vectores
+#[derive(Debug, Clone, PartialEq)]
+pub enum Embedder {
+    // Sin llamadas externas: términos con hash y TF-IDF
+    Local,
+    // API compatible con OpenAI (`{url}` recibe {"model", "input": [...]})
+    Api { url: String, model: String },
+}
+
+impl Embedder {
+    pub fn description(&self) -> String {
+        match self {
+            Embedder::Local => "embeddings locales".to_string(),
+            Embedder::Api { model, .. } => format!("embeddings de {}", model),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Chunk {
+    // Filas de la hoja (desde 1, como en Excel) que cubre el bloque
+    first_row: usize,
+    last_row: usize,
+    rows: Vec<Vec<String>>,
+    vector: Vec<f32>,
+}
+
+// Índice de una hoja
+#[derive(Clone)]
+struct SheetIndex {
+    path: String,
+    sheet: String,
+    headers: Vec<String>,
+    chunks: Vec<Chunk>,
+    // Pesos IDF por dimensión; solo con embeddings locales
+    idf: Option<Vec<f32>>,
+}
+
+#[derive(Default, Clone)]
+pub struct Retriever {
+    indexes: Vec<SheetIndex>,
+}
+
+impl Retriever {
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    pub fn has(&self, path: &str) -> bool {
+        self.indexes.iter().any(|index| index.path == path)
+    }
+
+    // Indexa las hojas de `data` con al menos `config.retrieval_rows` filas de datos; devuelve
+    // (hoja, bloques) de cada hoja indexada. Sustituye los índices anteriores del archivo.
+    pub async fn index_workbook(
+        &mut self,
+        client: &Client,
+        config: &Config,
+        path: &str,
+        data: &WorkbookData,
+    ) -> Result<Vec<(String, usize)>> {
+        self.indexes.retain(|index| index.path != path);
+        let mut indexed = Vec::new();
+        for sheet in &data.sheets {
+            let rows = sheet.text_rows();
+            let data_rows = rows.len().saturating_sub(1);
+            if data_rows >= config.retrieval_rows {
+                let batches = data_rows.div_ceil(CHUNK_ROWS).div_ceil(API_BATCH);
+                let progress = ProgressBar::new(&format!("Indexando {}", sheet.name), batches);
+                indexed.extend(self.add_sheet(client, config, path, &sheet.name, rows.into_iter().map(Ok), Some(progress)).await?);
+            }
+        }
+        Ok(indexed)
+    }
+
+    // Igual que `index_workbook`, leyendo las hojas por streaming: cada bloque se
+    // calcula (y con la API se envía por lotes) según llegan sus filas, sin tener
+    // la hoja entera en memoria
+    pub async fn index_streaming(&mut self, client: &Client, config: &Config, path: &str) -> Result<Vec<(String, usize)>> {
+        self.indexes.retain(|index| index.path != path);
+        let mut indexed = Vec::new();
+        for name in excel::sheet_names(path)? {
+            let rows = excel::stream_sheet(path, &name)?
+                .map(|row| row.map(|cells| cells.iter().map(ToString::to_string).collect()));
+            // Sin el total de filas no hay barra; el spinner indica que sigue avanzando
+            let _spinner = Spinner::start(format!("Indexando {}", name));
+            indexed.extend(self.add_sheet(client, config, path, &name, rows, None).await?);
+        }
+        Ok(indexed)
+    }
+
+    // Indexa una hoja (la primera fila son los encabezados) si llega a
+    // `config.retrieval_rows` filas de datos
+    async fn add_sheet(
+        &mut self,
+        client: &Client,
+        config: &Config,
+        path: &str,
+        sheet: &str,
+        mut rows: impl Iterator<Item = Result<Vec<String>>>,
+        progress: Option<ProgressBar>,
+    ) -> Result<Option<(String, usize)>> {
+        let mut builder = SheetBuilder {
+            local: config.embeddings == Embedder::Local,
+            progress,
+            ..SheetBuilder::default()
+        };
+        let headers = match rows.next() {
+            Some(row) => row?,
+            None => Vec::new(),
+        };
+        for row in rows {
+            builder.push(&headers, row?);
+            // Hasta llegar al mínimo de filas no se sabe si la hoja se indexa
+            if builder.data_rows >= config.retrieval_rows {
+                builder.embed(client, config, false).await?;
+            }
+        }
+        builder.close_block(&headers);
+        if builder.data_rows < config.retrieval_rows {
+            return Ok(None);
+        }
+        builder.embed(client, config, true).await?;
+
+        let (chunks, idf) = builder.finish();
+        let count = chunks.len();
+        self.indexes.push(SheetIndex {
+            path: path.to_string(),
+            sheet: sheet.to_string(),
+            headers,
+            chunks,
+            idf,
+        });
+        Ok(Some((sheet.to_string(), count)))
+    }
+
+    // Filas más relacionadas con `question`, como texto para el contexto, dentro de `max_tokens`
+    pub async fn context_for(
+        &self,
+        client: &Client,
+        config: &Config,
+        question: &str,
+        max_tokens: usize,
+    ) -> Result<Option<String>> {
+        let api_vector = match &config.embeddings {
+            Embedder::Api { url, model } => llm::get_embeddings(client, config, url, model, &[question.to_string()])
+                .await?
+                .pop()
+                .map(normalized),
+            Embedder::Local => None,
+        };
+        let local_counts = term_counts(question);
+
+        let mut scored: Vec<(f32, &SheetIndex, &Chunk)> = Vec::new();
+        for index in &self.indexes {
+            let query = match (&index.idf, &api_vector) {
+                (Some(idf), _) => weigh(local_counts.clone(), idf),
+                (None, Some(vector)) => vector.clone(),
+                (None, None) => continue,
+            };
+            for chunk in &index.chunks {
+                let score = dot(&query, &chunk.vector);
+                if score > 0.0 {
+                    scored.push((score, index, chunk));
+                }
+            }
+        }
+        if scored.is_empty() {
+            return Ok(None);
+        }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let header = "Filas de las hojas grandes más relacionadas con la pregunta (búsqueda por similitud; el resto de filas no se muestra):\n";
+        let mut remaining = max_tokens.saturating_sub(budget::estimate_tokens(header));
+        let mut selected = Vec::new();
+        for (_, index, chunk) in scored.into_iter().take(TOP_CHUNKS) {
+            let block = format!(
+                "\n{} — hoja {}, filas {}-{}:\n{}\n",
+                index.path,
+                index.sheet,
+                chunk.first_row,
+                chunk.last_row,
+                table::render_table(&index.headers, &chunk.rows)
+            );
+            // Los bloques entran por puntuación; el mejor se recorta si no cabe entero
+            let tokens = budget::estimate_tokens(&block);
+            if tokens > remaining {
+                if selected.is_empty() {
+                    selected.push((index, chunk.first_row, budget::truncate_to_tokens(&block, remaining)));
+                }
+                break;
+            }
+            remaining -= tokens;
+            selected.push((index, chunk.first_row, block));
+        }
+        // En el orden de la hoja, que se lee mejor que por puntuación
+        selected.sort_by(|a, b| (&a.0.path, &a.0.sheet, a.1).cmp(&(&b.0.path, &b.0.sheet, b.1)));
+        let mut text = header.to_string();
+        for (_, _, block) in selected {
+            text.push_str(&block);
+        }
+        Ok(Some(text))
+    }
+}
+
+// Bloques de una hoja en construcción, según llegan sus filas
+#[derive(Default)]
+struct SheetBuilder {
+    local: bool,
+    block: Vec<Vec<String>>,
+    chunks: Vec<Chunk>,
+    data_rows: usize,
+    // Términos de cada bloque con embeddings locales: se ponderan al terminar,
+    // cuando se conocen los IDF de toda la hoja
+    counts: Vec<Vec<f32>>,
+    // Textos de los bloques que aún no tienen vector de la API
+    pending: Vec<String>,
+    progress: Option<ProgressBar>,
+}
+
+impl SheetBuilder {
+    fn push(&mut self, headers: &[String], row: Vec<String>) {
+        self.data_rows += 1;
+        self.block.push(row);
+        if self.block.len() == CHUNK_ROWS {
+            self.close_block(headers);
+        }
+    }
+
+    fn close_block(&mut self, headers: &[String]) {
+        if self.block.is_empty() {
+            return;
+        }
+        let rows = std::mem::take(&mut self.block);
+        let text = chunk_text(headers, &rows);
+        if self.local {
+            self.counts.push(term_counts(&text));
+        } else {
+            self.pending.push(text);
+        }
+        // +2: la fila 1 son los encabezados
+        let first_row = self.chunks.last().map_or(2, |chunk| chunk.last_row + 1);
+        self.chunks.push(Chunk {
+            first_row,
+            last_row: first_row + rows.len() - 1,
+            rows,
+            vector: Vec::new(),
+        });
+    }
+
+    // Pide a la API los vectores de los bloques pendientes en lotes completos;
+    // con `all`, también el último lote incompleto
+    async fn embed(&mut self, client: &Client, config: &Config, all: bool) -> Result<()> {
+        let Embedder::Api { url, model } = &config.embeddings else {
+            return Ok(());
+        };
+        while self.pending.len() >= API_BATCH || (all && !self.pending.is_empty()) {
+            let batch: Vec<String> = self.pending.drain(..self.pending.len().min(API_BATCH)).collect();
+            let vectors = llm::get_embeddings(client, config, url, model, &batch).await?;
+            if let Some(progress) = &mut self.progress {
+                progress.inc("");
+            }
+            let first = self.chunks.len() - self.pending.len() - batch.len();
+            for (chunk, vector) in self.chunks[first..].iter_mut().zip(vectors) {
+                chunk.vector = normalized(vector);
+            }
+        }
+        Ok(())
+    }
+
+    // Bloques con su vector y los IDF de la hoja si los embeddings son locales
+    fn finish(mut self) -> (Vec<Chunk>, Option<Vec<f32>>) {
+        if !self.local {
+            return (self.chunks, None);
+        }
+        let idf = inverse_frequencies(&self.counts);
+        for (chunk, counts) in self.chunks.iter_mut().zip(self.counts) {
+            chunk.vector = weigh(counts, &idf);
+        }
+        (self.chunks, Some(idf))
+    }
+}
+
+// Texto de un bloque para la API: "Encabezado: valor" por celda, una fila por línea
+fn chunk_text(headers: &[String], rows: &[Vec<String>]) -> String {
+    let text: String = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(col, value)| match headers.get(col) {
+                    Some(header) if !header.is_empty() => format!("{}: {}", header, value),
+                    _ => value.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    text.chars().take(MAX_EMBED_CHARS).collect()
+}
+
+// Frecuencias de términos con hash: palabras completas y trigramas de letras,
+// para que "ventas" y "venta" se parezcan
+fn term_counts(text: &str) -> Vec<f32> {
+    let mut counts = vec![0.0; LOCAL_DIMENSIONS];
+    let lower = text.to_lowercase();
+    for word in lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        counts[bucket(word)] += 1.0;
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() > 3 {
+            for trigram in chars.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                counts[bucket(&format!("#{}", trigram))] += 0.5;
+            }
+        }
+    }
+    counts
+}
+
+fn bucket(term: &str) -> usize {
+    (backup::fnv1a(term.as_bytes()) % LOCAL_DIMENSIONS as u64) as usize
+}
+
+fn inverse_frequencies(documents: &[Vec<f32>]) -> Vec<f32> {
+    let total = documents.len() as f32;
+    (0..LOCAL_DIMENSIONS)
+        .map(|dim| {
+            let frequency = documents.iter().filter(|doc| doc[dim] > 0.0).count() as f32;
+            ((1.0 + total) / (1.0 + frequency)).ln() + 1.0
+        })
+        .collect()
+}
+
+fn weigh(counts: Vec<f32>, idf: &[f32]) -> Vec<f32> {
+    normalized(
+        counts
+            .into_iter()
+            .zip(idf)
+            .map(|(count, idf)| if count > 0.0 { (1.0 + count.ln()) * idf } else { 0.0 })
+            .collect(),
+    )
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
@@ -0,0 +1,236 @@
+// Una pregunta por fila (`para_cada_fila`): la plantilla se rellena con las
+// celdas de cada fila ({Encabezado}) y se envía al modelo sin herramientas; las
+// respuestas se escriben en una columna nueva. Útil para clasificar comentarios
+// o extraer un dato de un texto libre fila a fila. Las peticiones se lanzan en
+// paralelo hasta el límite de concurrencia, y las respuestas se van guardando en
+// un trabajo reanudable (`reanudar <id>`) por si el proceso se interrumpe.
+use crate::config::Config;
+use crate::excel::{self, CellValue, SheetData};
+use crate::interrupt;
+use crate::jobs::{Job, JobKind, RowsJob};
+use crate::llm::{self, Completion, Message};
+use crate::progress::ProgressBar;
+use crate::settings;
+use crate::template::Template;
+use crate::usage::UsageTracker;
+use anyhow::{bail, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+pub const DEFAULT_COLUMN: &str = "Resultado";
+pub const DEFAULT_CONCURRENCY: usize = 4;
+// Sin límite razonable la API empieza a devolver 429
+const MAX_CONCURRENCY: usize = 32;
+// Cada cuánto se guardan las respuestas en el trabajo, por si el proceso muere
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+const ROW_INSTRUCTIONS: &str = "Recibirás una instrucción sobre una fila de una hoja de cálculo. Responde solo con el resultado pedido, en una línea y sin explicaciones, para que pueda guardarse en una celda.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowPromptOptions {
+    #[serde(rename = "archivo")]
+    pub file: String,
+    #[serde(rename = "hoja")]
+    pub sheet: String,
+    #[serde(rename = "plantilla")]
+    pub template: String,
+    #[serde(rename = "columna")]
+    pub column: String,
+    #[serde(rename = "concurrencia")]
+    pub concurrency: usize,
+    #[serde(rename = "salida")]
+    pub output: Option<String>,
+    // Solo las primeras N filas de datos, para probar la plantilla
+    #[serde(rename = "filas")]
+    pub limit: Option<usize>,
+}
+
+pub struct RowPromptOutcome {
+    pub output: String,
+    pub sheet: String,
+    pub column: String,
+    pub answered: usize,
+    pub failed: usize,
+    // Filas sin respuesta porque Ctrl-C detuvo el proceso
+    pub pending: usize,
+    // Respuestas que ya estaban guardadas en el trabajo reanudado
+    pub resumed: usize,
+    // Trabajo que queda pendiente si faltan filas o alguna falló
+    pub job: Option<String>,
+}
+
+pub async fn run(
+    client: &Client,
+    config: &Config,
+    usage_tracker: &mut UsageTracker,
+    options: &RowPromptOptions,
+) -> Result<RowPromptOutcome> {
+    let job = Job::create(JobKind::Rows(RowsJob {
+        options: options.clone(),
+        answers: BTreeMap::new(),
+    }))?;
+    run_job(client, config, usage_tracker, job).await
+}
+
+// Procesa las filas que faltan en el trabajo, guardando el avance cada pocos segundos
+pub async fn run_job(
+    client: &Client,
+    config: &Config,
+    usage_tracker: &mut UsageTracker,
+    mut job: Job,
+) -> Result<RowPromptOutcome> {
+    let JobKind::Rows(state) = &job.kind else {
+        bail!("El trabajo {} no es de para_cada_fila", job.id);
+    };
+    let options = state.options.clone();
+    let mut answers = state.answers.clone();
+    let resumed = answers.len();
+    let data = excel::read_excel_file(&options.file)?;
+    let mut sheet = data.require_sheet(&options.file, &options.sheet)?.clone();
+    let headers = sheet.headers();
+    let template = Template::parse(&options.template)?;
+    if template.fields().is_empty() {
+        bail!("La plantilla no usa ninguna columna; escribe los encabezados entre llaves, p. ej. {{Comentario}}");
+    }
+    template.check(&headers, &[])?;
+
+    let total = sheet.data_rows().len();
+    let rows: Vec<usize> = (sheet.data_start()..sheet.rows.len())
+        .filter(|idx| sheet.rows[*idx].iter().any(|cell| *cell != CellValue::Empty))
+        .take(options.limit.unwrap_or(total))
+        .collect();
+    if rows.is_empty() {
+        bail!("La hoja {} no tiene filas de datos", options.sheet);
+    }
+
+    let config = Arc::new(config.for_command("para_cada_fila"));
+    // Al reanudar solo se piden las filas sin respuesta guardada
+    let todo: Vec<usize> = rows.iter().copied().filter(|idx| !answers.contains_key(idx)).collect();
+    let mut prompts_left = todo
+        .into_iter()
+        .map(|idx| (idx, row_prompt(&sheet, &headers, idx, &template)));
+    let mut running = JoinSet::new();
+    let mut results: HashMap<usize, CellValue> =
+        answers.iter().map(|(row, text)| (*row, CellValue::infer(text.trim()))).collect();
+    let mut progress = ProgressBar::new("Filas", rows.len() - resumed);
+    let mut saved_at = Instant::now();
+    let (mut answered, mut failed) = (0, 0);
+    let concurrency = options.concurrency.clamp(1, MAX_CONCURRENCY);
+
+    for (row, prompt) in prompts_left.by_ref().take(concurrency) {
+        spawn_row(&mut running, client, &config, row, prompt);
+    }
+    loop {
+        let Some(joined) = interrupt::interruptible(running.join_next()).await else {
+            running.abort_all();
+            progress.println(&format!(
+                "⏹ Detenido; se guardan las {} respuestas obtenidas",
+                answered + failed
+            ));
+            break;
+        };
+        let Some(joined) = joined else { break };
+        let (row, result) = match joined {
+            Ok(pair) => pair,
+            Err(e) => bail!("Una petición terminó de forma inesperada: {}", e),
+        };
+        let cell = match result {
+            Ok(completion) => {
+                usage_tracker.record_completion(&config.provider_name(), &config.model, &completion);
+                answered += 1;
+                let text = completion.message.content.unwrap_or_default();
+                let cell = CellValue::infer(text.trim());
+                answers.insert(row, text);
+                cell
+            }
+            Err(e) => {
+                progress.println(&format!("❌ Fila {}: {:#}", row + 1, e));
+                failed += 1;
+                CellValue::Text(format!("#ERROR: {:#}", e))
+            }
+        };
+        results.insert(row, cell);
+        progress.inc(&format!("fila {}", row + 1));
+        if saved_at.elapsed() >= SAVE_INTERVAL {
+            save_answers(&mut job, &answers)?;
+            saved_at = Instant::now();
+        }
+        if let Some((row, prompt)) = prompts_left.next() {
+            spawn_row(&mut running, client, &config, row, prompt);
+        }
+    }
+
+    write_column(&mut sheet, &options.column, results);
+    let output = options.output.clone().unwrap_or_else(|| options.file.clone());
+    excel::write_sheet_to_file(&output, sheet)?;
+    let pending = rows.len() - resumed - answered - failed;
+    let job = if failed == 0 && pending == 0 {
+        job.finish()?;
+        None
+    } else {
+        save_answers(&mut job, &answers)?;
+        Some(job.id)
+    };
+    Ok(RowPromptOutcome {
+        output,
+        sheet: options.sheet,
+        column: options.column,
+        answered: resumed + answered,
+        failed,
+        pending,
+        resumed,
+        job,
+    })
+}
+
+fn save_answers(job: &mut Job, answers: &BTreeMap<usize, String>) -> Result<()> {
+    if let JobKind::Rows(state) = &mut job.kind {
+        state.answers = answers.clone();
+    }
+    job.save()
+}
+
+fn spawn_row(
+    running: &mut JoinSet<(usize, Result<Completion>)>,
+    client: &Client,
+    config: &Arc<Config>,
+    row: usize,
+    prompt: String,
+) {
+    let (client, config) = (client.clone(), Arc::clone(config));
+    running.spawn(settings::inherit(async move {
+        let messages = vec![Message::new("system", ROW_INSTRUCTIONS), Message::new("user", prompt)];
+        (row, llm::get_deepseek_response(&client, &config, &messages, None).await)
+    }));
+}
+
+fn row_prompt(sheet: &SheetData, headers: &[String], row: usize, template: &Template) -> String {
+    let cells: Vec<String> = sheet.rows[row].iter().map(|cell| cell.to_string()).collect();
+    template.render_row(headers, &cells)
+}
+
+// Escribe las respuestas en la columna con ese encabezado, o en una nueva al final
+fn write_column(sheet: &mut SheetData, column: &str, mut results: HashMap<usize, CellValue>) {
+    let headers = sheet.headers();
+    let col = headers
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case(column))
+        .unwrap_or(headers.len());
+    let headerless = sheet.headerless;
+    for (idx, row) in sheet.rows.iter_mut().enumerate() {
+        let value = if idx == 0 && !headerless {
+            Some(CellValue::Text(column.to_string()))
+        } else {
+            results.remove(&idx)
+        };
+        let Some(value) = value else { continue };
+        if row.len() <= col {
+            row.resize(col + 1, CellValue::Empty);
+        }
+        row[col] = value;
+    }
+}
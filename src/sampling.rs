@@ -0,0 +1,167 @@
+// Parámetros de muestreo de las peticiones al modelo, para que un informe
+// regenerado el mes siguiente sea comparable: temperatura (IAGENT_TEMPERATURE),
+// top_p (IAGENT_TOP_P) y semilla (IAGENT_SEED), con temperaturas propias por
+// comando en IAGENT_TEMPERATURES ("leeme=0.3, para_cada_fila=0"). Los parámetros
+// exactos de la última petición se guardan en las propiedades de los libros que
+// escribe el agente.
+use crate::profiles;
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::sync::Mutex;
+
+// Comandos que admiten una temperatura propia; el resto usa la general
+pub const COMMANDS: &[&str] = &["leeme", "resumen", "para_cada_fila", "extraer_json"];
+
+#[derive(Debug, Clone, Default)]
+pub struct Sampling {
+    // Sin valor, la de cada tipo de petición (0.7 en la conversación, 0 en extraer_json)
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub seed: Option<u64>,
+    // (comando, temperatura)
+    pub overrides: Vec<(String, f64)>,
+}
+
+impl Sampling {
+    pub fn from_env() -> Result<Sampling> {
+        let temperature = match profiles::var("IAGENT_TEMPERATURE") {
+            Ok(value) => Some(parse_temperature("IAGENT_TEMPERATURE", &value)?),
+            Err(_) => None,
+        };
+        let top_p = match profiles::var("IAGENT_TOP_P") {
+            Ok(value) => {
+                let top_p: f64 =
+                    value.trim().parse().context(format!("Valor no válido en IAGENT_TOP_P: '{}'", value))?;
+                if !(top_p > 0.0 && top_p <= 1.0) {
+                    bail!("IAGENT_TOP_P debe estar entre 0 (sin incluir) y 1: '{}'", value);
+                }
+                Some(top_p)
+            }
+            Err(_) => None,
+        };
+        let seed = match profiles::var("IAGENT_SEED") {
+            Ok(value) => Some(
+                value
+                    .trim()
+                    .parse()
+                    .context(format!("Valor no válido en IAGENT_SEED: '{}' (usa un entero positivo)", value))?,
+            ),
+            Err(_) => None,
+        };
+        let overrides = match profiles::var("IAGENT_TEMPERATURES") {
+            Ok(value) => parse_overrides(&value)?,
+            Err(_) => Vec::new(),
+        };
+        Ok(Sampling {
+            temperature,
+            top_p,
+            seed,
+            overrides,
+        })
+    }
+
+    // Los parámetros para un comando: su temperatura propia si la tiene
+    pub fn for_command(&self, command: &str) -> Sampling {
+        let mut sampling = self.clone();
+        if let Some(temperature) = self.override_for(command) {
+            sampling.temperature = Some(temperature);
+        }
+        sampling
+    }
+
+    pub fn override_for(&self, command: &str) -> Option<f64> {
+        self.overrides.iter().find(|(name, _)| name == command).map(|(_, temperature)| *temperature)
+    }
+
+    // Añade los parámetros a una petición de chat completions
+    pub fn apply(&self, request_body: &mut Value, default_temperature: f64) {
+        request_body["temperature"] = self.temperature.unwrap_or(default_temperature).into();
+        if let Some(top_p) = self.top_p {
+            request_body["top_p"] = top_p.into();
+        }
+        if let Some(seed) = self.seed {
+            request_body["seed"] = seed.into();
+        }
+    }
+}
+
+fn parse_temperature(variable: &str, value: &str) -> Result<f64> {
+    let temperature: f64 = value.trim().parse().context(format!("Valor no válido en {}: '{}'", variable, value))?;
+    if !(0.0..=2.0).contains(&temperature) {
+        bail!("La temperatura de {} debe estar entre 0 y 2: '{}'", variable, value);
+    }
+    Ok(temperature)
+}
+
+// "leeme=0.3, para_cada_fila=0"
+fn parse_overrides(value: &str) -> Result<Vec<(String, f64)>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (command, temperature) = pair
+                .split_once('=')
+                .context(format!("Valor no válido en IAGENT_TEMPERATURES: '{}' (usa comando=temperatura)", pair))?;
+            let command = command.trim();
+            if !COMMANDS.contains(&command) {
+                bail!(
+                    "Comando desconocido en IAGENT_TEMPERATURES: '{}' (admite {})",
+                    command,
+                    COMMANDS.join(", ")
+                );
+            }
+            Ok((command.to_string(), parse_temperature("IAGENT_TEMPERATURES", temperature)?))
+        })
+        .collect()
+}
+
+// Parámetros exactos con los que se pidió una respuesta
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelParams {
+    pub model: String,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub seed: Option<u64>,
+}
+
+impl ModelParams {
+    pub fn from_request(request_body: &Value) -> ModelParams {
+        ModelParams {
+            model: request_body["model"].as_str().unwrap_or_default().to_string(),
+            temperature: request_body["temperature"].as_f64(),
+            top_p: request_body["top_p"].as_f64(),
+            seed: request_body["seed"].as_u64(),
+        }
+    }
+
+    // "deepseek-chat, temperatura 0, top_p 0.9, semilla 42"
+    pub fn describe(&self) -> String {
+        let mut text = self.model.clone();
+        if let Some(temperature) = self.temperature {
+            text.push_str(&format!(", temperatura {}", temperature));
+        }
+        if let Some(top_p) = self.top_p {
+            text.push_str(&format!(", top_p {}", top_p));
+        }
+        match self.seed {
+            Some(seed) => text.push_str(&format!(", semilla {}", seed)),
+            None => text.push_str(", sin semilla"),
+        }
+        text
+    }
+}
+
+static LAST_PARAMS: Mutex<Option<ModelParams>> = Mutex::new(None);
+
+// Lo llama cada petición que obtiene respuesta, también desde la caché
+pub fn record(request_body: &Value) {
+    if let Ok(mut last) = LAST_PARAMS.lock() {
+        *last = Some(ModelParams::from_request(request_body));
+    }
+}
+
+// Parámetros de la última respuesta de la sesión; None si aún no se ha usado el modelo
+pub fn last() -> Option<ModelParams> {
+    LAST_PARAMS.lock().ok().and_then(|last| last.clone())
+}
@@ -0,0 +1,185 @@
+// Espacio de trabajo de las herramientas del modelo: solo pueden leer y escribir
+// dentro de un directorio (por defecto el actual), con rutas relativas y sin `..`.
+// Cada operación, permitida o no, se anota en archivos.log (directorio de datos) para que un
+// libro con instrucciones inyectadas no pueda tocar el resto del disco sin dejar rastro.
+use crate::events;
+use crate::excel;
+use crate::paths;
+use anyhow::{bail, Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    fn label(self) -> &'static str {
+        match self {
+            Access::Read => "lectura",
+            Access::Write => "escritura",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    // Ruta canónica (sin enlaces simbólicos) del directorio permitido
+    root: PathBuf,
+}
+
+impl Workspace {
+    pub fn new(dir: &Path) -> Result<Workspace> {
+        let root = dir
+            .canonicalize()
+            .context(format!("No existe el espacio de trabajo {}", dir.display()))?;
+        if !root.is_dir() {
+            bail!("El espacio de trabajo {} no es un directorio", dir.display());
+        }
+        Ok(Workspace { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Ruta dentro del espacio de trabajo para `path`, o un error si sale de él
+    pub fn resolve(&self, tool: &str, path: &str, access: Access) -> Result<String> {
+        let result = self.check(path);
+        log_operation(tool, access, path, result.as_ref().err());
+        match result {
+            Ok(full) => Ok(full.to_string_lossy().into_owned()),
+            Err(e) => {
+                events::notice(format!("⚠️  {}: {} denegada: {:#}", tool, access.label(), e));
+                Err(e)
+            }
+        }
+    }
+
+    fn check(&self, path: &str) -> Result<PathBuf> {
+        let relative = Path::new(path.trim());
+        if path.trim().is_empty() {
+            bail!("La ruta está vacía");
+        }
+        for component in relative.components() {
+            match component {
+                Component::Prefix(_) | Component::RootDir => {
+                    bail!("No se permiten rutas absolutas ('{}'); usa una ruta relativa al espacio de trabajo", path)
+                }
+                Component::ParentDir => bail!("No se permite '..' en las rutas ('{}')", path),
+                Component::CurDir | Component::Normal(_) => {}
+            }
+        }
+        if path.trim().starts_with('~') {
+            bail!("No se permiten rutas del directorio personal ('{}')", path);
+        }
+
+        // Un enlace simbólico dentro del espacio podría apuntar fuera: se comprueba
+        // la ruta real del componente existente más profundo
+        let full = self.root.join(relative);
+        let mut existing = full.as_path();
+        while fs::symlink_metadata(existing).is_err() {
+            existing = existing.parent().context("Ruta no válida")?;
+        }
+        let real = existing
+            .canonicalize()
+            .context(format!("No se pudo resolver {}", existing.display()))?;
+        if !real.starts_with(&self.root) {
+            bail!("'{}' está fuera del espacio de trabajo {}", path, self.root.display());
+        }
+        Ok(full)
+    }
+}
+
+fn log_path() -> PathBuf {
+    paths::data_dir().join("archivos.log")
+}
+
+// Una línea por operación: fecha, herramienta, tipo, ruta y resultado
+fn log_operation(tool: &str, access: Access, path: &str, denied: Option<&anyhow::Error>) {
+    let outcome = match denied {
+        Some(e) => format!("denegada: {:#}", e),
+        None => "permitida".to_string(),
+    };
+    let line = format!(
+        "{}\t{}\t{}\t{}\t{}\n",
+        excel::excel_serial_to_iso(excel::now_serial()),
+        tool,
+        access.label(),
+        path,
+        outcome
+    );
+    let log = log_path();
+    let written = fs::create_dir_all(paths::data_dir())
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&log))
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    if let Err(e) = written {
+        events::notice(format!("⚠️  No se pudo anotar la operación en {}: {}", log.display(), e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(name: &str) -> (PathBuf, Workspace) {
+        let dir = paths::test_dir(name);
+        let workspace = Workspace::new(&dir).unwrap();
+        (dir, workspace)
+    }
+
+    fn denied(workspace: &Workspace, path: &str) -> String {
+        format!("{:#}", workspace.resolve("prueba", path, Access::Read).unwrap_err())
+    }
+
+    #[test]
+    fn relative_paths_inside_the_workspace_are_allowed() {
+        let (dir, workspace) = workspace("sandbox_relativas");
+        fs::create_dir_all(dir.join("datos")).unwrap();
+        fs::write(dir.join("datos").join("ventas.csv"), "a,b\n").unwrap();
+        let root = workspace.root().to_path_buf();
+        for path in ["datos/ventas.csv", "./datos/ventas.csv", "nuevo.xlsx", "datos/nueva/informe.pdf"] {
+            let resolved = workspace.resolve("prueba", path, Access::Write).unwrap();
+            assert!(Path::new(&resolved).starts_with(&root), "{}: {}", path, resolved);
+        }
+    }
+
+    #[test]
+    fn absolute_paths_are_rejected() {
+        let (_, workspace) = workspace("sandbox_absolutas");
+        let absolute = workspace.root().join("ventas.xlsx").display().to_string();
+        assert!(denied(&workspace, &absolute).contains("No se permiten rutas absolutas"));
+        assert!(denied(&workspace, "/etc/passwd").contains("No se permiten rutas absolutas"));
+        assert!(denied(&workspace, "~/ventas.xlsx").contains("directorio personal"));
+        assert!(denied(&workspace, "  ").contains("La ruta está vacía"));
+    }
+
+    #[test]
+    fn parent_directories_are_rejected() {
+        let (_, workspace) = workspace("sandbox_padre");
+        for path in ["../ventas.xlsx", "datos/../../ventas.xlsx", "datos/.."] {
+            assert!(denied(&workspace, path).contains("No se permite '..'"), "{}", path);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_escape_the_workspace() {
+        let (dir, workspace) = workspace("sandbox_enlaces");
+        let outside = paths::test_dir("sandbox_enlaces_fuera");
+        fs::write(outside.join("secreto.txt"), "clave").unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("fuera")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secreto.txt"), dir.join("secreto.txt")).unwrap();
+        for path in ["fuera/secreto.txt", "fuera/nuevo.xlsx", "secreto.txt"] {
+            assert!(denied(&workspace, path).contains("está fuera del espacio de trabajo"), "{}", path);
+        }
+
+        // Un enlace que apunta dentro del espacio sí se puede usar
+        fs::create_dir_all(dir.join("datos")).unwrap();
+        std::os::unix::fs::symlink(dir.join("datos"), dir.join("atajo")).unwrap();
+        assert!(workspace.resolve("prueba", "atajo/ventas.csv", Access::Read).is_ok());
+    }
+}
@@ -0,0 +1,194 @@
+// Memoria de esquemas: de cada libro leído se guarda su estructura (hojas,
+// columnas y el tipo de cada columna) en esquemas.json, en el directorio de
+// datos. Al empezar una sesión se añade al contexto, así el modelo conoce los
+// libros vistos en sesiones anteriores sin volver a leerlos. No se guardan
+// valores, solo nombres y tipos. `refrescar <archivo>` la actualiza.
+use crate::excel::{self, CellValue, SheetData, WorkbookData};
+use crate::paths;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// Libros que se recuerdan; al superarlo se olvidan los vistos hace más tiempo
+const MAX_REMEMBERED: usize = 50;
+// Libros que se describen al modelo al empezar la sesión
+const MAX_IN_CONTEXT: usize = 20;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SchemaMemory {
+    // Ruta absoluta -> esquema
+    #[serde(rename = "libros", default)]
+    workbooks: BTreeMap<String, WorkbookSchema>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkbookSchema {
+    #[serde(rename = "visto")]
+    seen: String,
+    // Fecha de modificación del archivo (segundos Unix) cuando se leyó
+    #[serde(rename = "modificado", default)]
+    modified: u64,
+    #[serde(rename = "hojas")]
+    sheets: Vec<SheetSchema>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SheetSchema {
+    #[serde(rename = "nombre")]
+    name: String,
+    // Filas de datos; no se conocen si el libro se leyó por streaming
+    #[serde(rename = "filas", default, skip_serializing_if = "Option::is_none")]
+    rows: Option<usize>,
+    #[serde(rename = "columnas")]
+    columns: Vec<ColumnSchema>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    #[serde(rename = "nombre")]
+    name: String,
+    #[serde(rename = "tipo")]
+    kind: String,
+}
+
+fn memory_file() -> PathBuf {
+    paths::data_dir().join("esquemas.json")
+}
+
+fn absolute(file: &str) -> String {
+    fs::canonicalize(file).unwrap_or_else(|_| PathBuf::from(file)).display().to_string()
+}
+
+fn modified_secs(path: &Path) -> u64 {
+    path.metadata()
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+impl SchemaMemory {
+    pub fn load() -> Result<SchemaMemory> {
+        let path = memory_file();
+        match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).context(format!("La memoria de esquemas está dañada ({})", path.display())),
+            Err(_) => Ok(SchemaMemory::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = memory_file();
+        fs::create_dir_all(paths::data_dir())?;
+        // Se escribe aparte y se renombra, como los trabajos
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, serde_json::to_string_pretty(self)?)
+            .context(format!("No se pudo guardar la memoria de esquemas en {}", partial.display()))?;
+        fs::rename(&partial, &path).context(format!("No se pudo guardar la memoria de esquemas en {}", path.display()))
+    }
+
+    // Guarda la estructura de un libro leído; `complete` es falso si `data` es solo
+    // una vista previa (lectura por streaming) y el número de filas no es el real
+    pub fn remember(&mut self, file: &str, data: &WorkbookData, complete: bool) -> &WorkbookSchema {
+        let schema = WorkbookSchema {
+            seen: excel::excel_serial_to_iso(excel::now_serial()),
+            modified: modified_secs(Path::new(file)),
+            sheets: data.sheets.iter().map(|sheet| sheet_schema(sheet, complete)).collect(),
+        };
+        let key = absolute(file);
+        self.workbooks.insert(key.clone(), schema);
+        while self.workbooks.len() > MAX_REMEMBERED {
+            let Some(oldest) = self.workbooks.iter().min_by(|a, b| a.1.seen.cmp(&b.1.seen)).map(|(k, _)| k.clone()) else {
+                break;
+            };
+            self.workbooks.remove(&oldest);
+        }
+        &self.workbooks[&key]
+    }
+
+    // Texto para el contexto con los libros recordados que siguen existiendo,
+    // los vistos más recientemente primero, y cuántos son; None si no hay ninguno
+    pub fn context(&self) -> Option<(usize, String)> {
+        let mut known: Vec<(&String, &WorkbookSchema)> =
+            self.workbooks.iter().filter(|(path, _)| Path::new(path.as_str()).is_file()).collect();
+        if known.is_empty() {
+            return None;
+        }
+        known.sort_by(|a, b| b.1.seen.cmp(&a.1.seen));
+        let count = known.len();
+        let current = std::env::current_dir().unwrap_or_default();
+        let mut text = String::from(
+            "Estructura de libros vistos en sesiones anteriores (no se han vuelto a leer; usa leer_excel para ver sus datos):",
+        );
+        for (path, schema) in known.into_iter().take(MAX_IN_CONTEXT) {
+            let shown = Path::new(path.as_str()).strip_prefix(&current).map_or(path.clone(), |p| p.display().to_string());
+            text.push_str(&format!("\n{} (visto el {}", shown, schema.seen));
+            if modified_secs(Path::new(path.as_str())) != schema.modified {
+                text.push_str("; ha cambiado desde entonces");
+            }
+            text.push(')');
+            text.push_str(&schema.describe());
+        }
+        Some((count, text))
+    }
+}
+
+impl WorkbookSchema {
+    // Una línea por hoja: "  Ventas (120 filas): Fecha (fecha), Importe (número)"
+    pub fn describe(&self) -> String {
+        self.sheets
+            .iter()
+            .map(|sheet| {
+                let rows = sheet.rows.map(|rows| format!(" ({} filas)", rows)).unwrap_or_default();
+                let columns: Vec<String> = sheet.columns.iter().map(|c| format!("{} ({})", c.name, c.kind)).collect();
+                format!("\n  {}{}: {}", sheet.name, rows, columns.join(", "))
+            })
+            .collect()
+    }
+}
+
+fn sheet_schema(sheet: &SheetData, complete: bool) -> SheetSchema {
+    let data = sheet.data_rows();
+    let columns = sheet
+        .headers()
+        .into_iter()
+        .enumerate()
+        .map(|(idx, header)| ColumnSchema {
+            name: if header.is_empty() { excel::column_letters(idx) } else { header },
+            kind: column_kind(data, idx).to_string(),
+        })
+        .collect();
+    SheetSchema {
+        name: sheet.name.clone(),
+        rows: complete.then_some(data.len()),
+        columns,
+    }
+}
+
+// Tipo mayoritario de los valores de una columna, como en los resúmenes
+pub fn column_kind(rows: &[Vec<CellValue>], col: usize) -> &'static str {
+    let (mut filled, mut numbers, mut dates, mut bools) = (0, 0, 0, 0);
+    for cell in rows.iter().filter_map(|row| row.get(col)) {
+        match cell {
+            CellValue::Empty => continue,
+            CellValue::Number(_) => numbers += 1,
+            CellValue::DateTime(_) => dates += 1,
+            CellValue::Bool(_) => bools += 1,
+            CellValue::Text(_) | CellValue::Error(_) => {}
+        }
+        filled += 1;
+    }
+    if filled == 0 {
+        "vacía"
+    } else if numbers * 2 >= filled {
+        "número"
+    } else if dates * 2 >= filled {
+        "fecha"
+    } else if bools * 2 >= filled {
+        "booleano"
+    } else {
+        "texto"
+    }
+}
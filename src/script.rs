@@ -0,0 +1,157 @@
+// Guiones de sesión: `exportar_sesion` escribe los comandos ejecutados (y, si se
+// pide, las preguntas al modelo) y `ia_agent --guion <archivo>` los vuelve a
+// ejecutar sin interacción, de modo que una sesión que funcionó se convierte en
+// un proceso de informes automatizado.
+//
+// Una entrada de varias líneas se escribe como en la shell: una línea que termina
+// en <<FIN sigue en las siguientes hasta una que sea solo FIN. Vale en el prompt,
+// con la entrada redirigida y en los guiones.
+use crate::excel;
+use crate::i18n::tr;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+enum Step {
+    // Comando de Excel, reproducible sin el modelo salvo que genere una hoja Léeme
+    Command(String),
+    // Pregunta al modelo o tarea del modo agente
+    Prompt(String),
+}
+
+#[derive(Default)]
+pub struct SessionLog {
+    steps: Vec<Step>,
+}
+
+impl SessionLog {
+    pub fn record_command(&mut self, input: &str) {
+        self.steps.push(Step::Command(input.to_string()));
+    }
+
+    pub fn record_prompt(&mut self, input: &str) {
+        self.steps.push(Step::Prompt(input.to_string()));
+    }
+
+    // Escribe el guion; sin `include_prompts` las preguntas quedan como comentarios.
+    // Devuelve el número de líneas ejecutables.
+    pub fn export(&self, path: &Path, include_prompts: bool) -> Result<usize> {
+        let mut text = format!(
+            "# Sesión exportada por ia_agent el {} (UTC)\n# Reprodúcela con: ia_agent --guion {}\n",
+            excel::excel_serial_to_iso(excel::now_serial()),
+            path.display()
+        );
+        let mut executable = 0;
+        for step in &self.steps {
+            match step {
+                Step::Command(command) => {
+                    text.push_str(&as_script_line(command));
+                    executable += 1;
+                }
+                Step::Prompt(prompt) if include_prompts => {
+                    text.push_str(&as_script_line(prompt));
+                    executable += 1;
+                }
+                Step::Prompt(prompt) => {
+                    text.push_str("# pregunta: ");
+                    text.push_str(&prompt.replace('\n', "\n#   "));
+                }
+            }
+            text.push('\n');
+        }
+        fs::write(path, text).context(format!("No se pudo escribir el guion {}", path.display()))?;
+        Ok(executable)
+    }
+}
+
+const HEREDOC_SENTINEL: &str = "FIN";
+
+// Las entradas de varias líneas se exportan como texto <<FIN ... FIN
+fn as_script_line(input: &str) -> String {
+    if !input.contains('\n') {
+        return input.to_string();
+    }
+    // La palabra de cierre no puede aparecer sola en una línea del texto
+    let mut sentinel = HEREDOC_SENTINEL.to_string();
+    while input.lines().any(|line| line.trim() == sentinel) {
+        sentinel.push('_');
+    }
+    format!("<<{}\n{}\n{}", sentinel, input, sentinel)
+}
+
+// Si la línea abre un texto de varias líneas (`... <<FIN`), el texto anterior y
+// la palabra que lo cierra; se admite entre comillas como en la shell (<<'FIN')
+pub fn heredoc_start(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_end();
+    let start = line.rfind("<<")?;
+    if line[..start].ends_with('<') {
+        return None;
+    }
+    let sentinel = line[start + 2..].trim_start();
+    let sentinel = sentinel
+        .strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .or_else(|| sentinel.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+        .unwrap_or(sentinel);
+    if sentinel.is_empty() || !sentinel.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((line[..start].trim(), sentinel))
+}
+
+// Completa una entrada que abre un texto de varias líneas leyendo las siguientes
+// con `next` hasta la línea de cierre (o el final de la entrada). Devuelve el
+// texto anterior a <<, un espacio y las líneas leídas; una línea normal se
+// devuelve tal cual.
+pub fn read_heredoc(line: String, mut next: impl FnMut() -> Result<Option<String>>) -> Result<String> {
+    let Some((prefix, sentinel)) = heredoc_start(&line) else {
+        return Ok(line);
+    };
+    let mut body: Vec<String> = Vec::new();
+    let mut closed = false;
+    while let Some(next_line) = next()? {
+        let next_line = next_line.trim_end_matches(['\n', '\r']);
+        if next_line.trim() == sentinel {
+            closed = true;
+            break;
+        }
+        body.push(next_line.to_string());
+    }
+    if !closed {
+        println!("{}", tr!("⚠️  Falta la línea {} que cierra el texto; se usa hasta el final de la entrada", "⚠️  The line {} that closes the text is missing; the rest of the input is used", sentinel));
+    }
+    let body = body.join("\n");
+    Ok(if prefix.is_empty() { body } else { format!("{} {}", prefix, body) })
+}
+
+// Guion en ejecución: una entrada por línea (o un texto <<FIN de varias); se
+// ignoran las vacías y las que empiezan por #
+pub struct Script {
+    lines: std::vec::IntoIter<String>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Script> {
+        let text = fs::read_to_string(path).context(format!("No se pudo leer el guion {}", path.display()))?;
+        let mut raw = text.lines();
+        let mut lines: Vec<String> = Vec::new();
+        while let Some(line) = raw.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            lines.push(read_heredoc(line.to_string(), || Ok(raw.next().map(str::to_string)))?);
+        }
+        println!("{}", tr!("📜 Ejecutando {} ({} entradas)", "📜 Running {} ({} entries)", path.display(), lines.len()));
+        Ok(Script {
+            lines: lines.into_iter(),
+        })
+    }
+
+    // Siguiente entrada, mostrada como si se hubiera escrito en el prompt
+    pub fn next_command(&mut self) -> Option<String> {
+        let line = self.lines.next()?;
+        println!("> {}", line);
+        Some(line)
+    }
+}
@@ -0,0 +1,211 @@
+// Búsqueda de un valor en todas las hojas de un libro (`buscar` y la herramienta
+// del mismo nombre): devuelve hoja, celda y encabezado de cada coincidencia, para
+// localizar un dato en libros con muchas hojas sin leerlas una a una.
+use crate::excel::{self, WorkbookData};
+use crate::i18n;
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+
+pub const DEFAULT_MAX_HITS: usize = 50;
+// Tamaño máximo del autómata de una expresión del modelo o del usuario
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+// Caracteres del valor que se muestran en cada coincidencia
+const MAX_VALUE_CHARS: usize = 80;
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub file: String,
+    pub query: String,
+    pub sheet: Option<String>,
+    pub max_hits: usize,
+    // Añade las coincidencias al historial para preguntar por ellas
+    pub add_to_context: bool,
+}
+
+pub enum Matcher {
+    // Texto ya normalizado (minúsculas y sin tildes)
+    Text(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    // "/patrón/" (o "/patrón/i" sin distinguir mayúsculas) es una expresión regular;
+    // cualquier otro texto se busca dentro de las celdas sin distinguir mayúsculas ni tildes
+    pub fn parse(query: &str) -> Result<Matcher> {
+        if let Some(body) = query.strip_prefix('/') {
+            if let Some(pattern) = body.strip_suffix("/i") {
+                return Ok(Matcher::Regex(regex(pattern, true)?));
+            }
+            if let Some(pattern) = body.strip_suffix('/') {
+                return Ok(Matcher::Regex(regex(pattern, false)?));
+            }
+        }
+        Ok(Matcher::Text(fold(query)))
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            Matcher::Text(needle) => fold(text).contains(needle.as_str()),
+            Matcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+fn regex(pattern: &str, ignore_case: bool) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .context(format!("Expresión regular no válida: /{}/", pattern))
+}
+
+// Minúsculas y sin tildes ni diéresis (la ñ se conserva)
+fn fold(text: &str) -> String {
+    text.chars().map(fold_char).collect()
+}
+
+pub fn fold_char(c: char) -> char {
+    match c.to_lowercase().next().unwrap_or(c) {
+        'á' | 'à' | 'â' | 'ä' => 'a',
+        'é' | 'è' | 'ê' | 'ë' => 'e',
+        'í' | 'ì' | 'î' | 'ï' => 'i',
+        'ó' | 'ò' | 'ô' | 'ö' => 'o',
+        'ú' | 'ù' | 'û' | 'ü' => 'u',
+        c => c,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub sheet: String,
+    // Fila y columna desde 0
+    pub row: usize,
+    pub col: usize,
+    pub header: String,
+    pub value: String,
+}
+
+impl SearchHit {
+    pub fn cell(&self) -> String {
+        format!("{}{}", excel::column_letters(self.col), self.row + 1)
+    }
+}
+
+pub struct SearchResult {
+    pub hits: Vec<SearchHit>,
+    // Coincidencias totales, aunque solo se guarden las primeras `max_hits`
+    pub total: usize,
+    pub sheets_searched: usize,
+}
+
+// Busca en todas las hojas, o solo en `sheet`
+pub fn search(data: &WorkbookData, matcher: &Matcher, sheet: Option<&str>, max_hits: usize) -> SearchResult {
+    let mut result = SearchResult {
+        hits: Vec::new(),
+        total: 0,
+        sheets_searched: 0,
+    };
+    let sheets = data
+        .sheets
+        .iter()
+        .filter(|s| sheet.is_none_or(|name| s.name.eq_ignore_ascii_case(name)));
+    for sheet in sheets {
+        result.sheets_searched += 1;
+        let headers = sheet.headers();
+        for (row_idx, row) in sheet.rows.iter().enumerate() {
+            for (col_idx, cell) in row.iter().enumerate() {
+                let value = cell.to_string();
+                if value.is_empty() || !matcher.matches(&value) {
+                    continue;
+                }
+                result.total += 1;
+                if result.hits.len() < max_hits {
+                    result.hits.push(SearchHit {
+                        sheet: sheet.name.clone(),
+                        row: row_idx,
+                        col: col_idx,
+                        header: if row_idx < sheet.data_start() { String::new() } else { headers.get(col_idx).cloned().unwrap_or_default() },
+                        value,
+                    });
+                }
+            }
+        }
+    }
+    result
+}
+
+// Encabezados y filas de la tabla de coincidencias
+pub fn table_rows(result: &SearchResult) -> (Vec<String>, Vec<Vec<String>>) {
+    let headers: Vec<String> = [("Hoja", "Sheet"), ("Celda", "Cell"), ("Columna", "Column"), ("Valor", "Value")]
+        .into_iter()
+        .map(|h| i18n::pick(h).to_string())
+        .collect();
+    let rows: Vec<Vec<String>> = result
+        .hits
+        .iter()
+        .map(|hit| {
+            vec![
+                hit.sheet.clone(),
+                hit.cell(),
+                if hit.row == 0 && hit.header.is_empty() { "(encabezado)".to_string() } else { hit.header.clone() },
+                shorten(&hit.value),
+            ]
+        })
+        .collect();
+    (headers, rows)
+}
+
+// Texto para el historial: las coincidencias con su ubicación, una por línea
+pub fn context_summary(file: &str, query: &str, result: &SearchResult) -> String {
+    let mut text = format!(
+        "Resultado de buscar '{}' en {}: {} coincidencias",
+        query, file, result.total
+    );
+    if result.total > result.hits.len() {
+        text.push_str(&format!(" (se muestran las {} primeras)", result.hits.len()));
+    }
+    for hit in &result.hits {
+        text.push_str(&format!("\n- {}!{}", hit.sheet, hit.cell()));
+        if !hit.header.is_empty() {
+            text.push_str(&format!(" [{}]", hit.header));
+        }
+        text.push_str(&format!(": {}", shorten(&hit.value)));
+    }
+    text
+}
+
+fn shorten(value: &str) -> String {
+    match value.char_indices().nth(MAX_VALUE_CHARS) {
+        Some((end, _)) => format!("{}…", &value[..end]),
+        None => value.replace('\n', " "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_ignores_case_and_accents() {
+        let matcher = Matcher::parse("jose").unwrap();
+        assert!(matcher.matches("José García"));
+        assert!(!matcher.matches("Juan"));
+        assert!(Matcher::parse("AÑO").unwrap().matches("año 2024"));
+    }
+
+    #[test]
+    fn slashes_make_a_regular_expression() {
+        let invoice = Matcher::parse(r"/^F-\d{4}$/").unwrap();
+        assert!(invoice.matches("F-2024"));
+        assert!(!invoice.matches("F-20245"));
+        assert!(!invoice.matches("f-2024"));
+        assert!(Matcher::parse(r"/^f-\d{4}$/i").unwrap().matches("F-2024"));
+        assert!(Matcher::parse("/(norte|sur)$/").unwrap().matches("Zona sur"));
+    }
+
+    #[test]
+    fn invalid_expressions_are_reported() {
+        let error = Matcher::parse("/[a-/").err().unwrap();
+        assert!(format!("{:#}", error).contains("Expresión regular no válida"));
+    }
+}
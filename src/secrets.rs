@@ -0,0 +1,340 @@
+// Clave de la API sin dejarla en claro en un .env junto a las hojas de cálculo.
+// Se busca, por orden, en:
+//   1. DEEPSEEK_API_KEY / IAGENT_API_KEY
+//   2. la salida de IAGENT_API_KEY_CMD (p. ej. "pass show deepseek")
+//   3. el llavero del sistema: secret-tool (libsecret) en Linux, security en macOS y
+//      el Administrador de credenciales en Windows
+//   4. clave_api.enc en el directorio de configuración, cifrada con una frase de paso (IAGENT_PASSPHRASE o se pide)
+//      y una sal aleatoria guardada en el propio archivo
+// Si no aparece y la entrada es una terminal, se pide una vez (sin eco; si la
+// terminal no lo permite, no se pide) y se guarda en el llavero o, si no hay, en
+// el archivo cifrado.
+use crate::crypto::ColumnKey;
+use crate::excel::CellValue;
+use crate::paths;
+use crate::profiles;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+const KEYCHAIN_SERVICE: &str = "iagent";
+const KEYCHAIN_ACCOUNT: &str = "api_key";
+
+pub fn find_api_key() -> Result<Option<String>> {
+    if let Ok(key) = profiles::var("DEEPSEEK_API_KEY").or_else(|_| profiles::var("IAGENT_API_KEY")) {
+        return Ok(Some(key));
+    }
+    if let Ok(command) = profiles::var("IAGENT_API_KEY_CMD") {
+        let key = run_shell(&command).context(format!("IAGENT_API_KEY_CMD ('{}') falló", command))?;
+        if key.is_empty() {
+            bail!("IAGENT_API_KEY_CMD ('{}') no devolvió ninguna clave", command);
+        }
+        return Ok(Some(key));
+    }
+    if let Some(key) = keychain_lookup() {
+        return Ok(Some(key));
+    }
+    let file = paths::api_key_file();
+    if file.exists() {
+        let encrypted = fs::read_to_string(&file).context(format!("No se pudo leer {}", file.display()))?;
+        let passphrase = passphrase("🔑 Frase de paso de la clave de la API: ")?;
+        let key = match ColumnKey::new(&passphrase)?.decrypt(encrypted.trim()) {
+            Some(Ok(CellValue::Text(key))) => key,
+            Some(Err(_)) => bail!("La frase de paso no es correcta para {}", file.display()),
+            _ => bail!("{} no contiene una clave cifrada válida", file.display()),
+        };
+        return Ok(Some(key));
+    }
+    Ok(None)
+}
+
+// Pide la clave en la terminal tras `notice` y la guarda; None si no hay terminal
+pub fn prompt_and_store(notice: &str) -> Result<Option<String>> {
+    if !io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    println!("🔑 {}", notice);
+    let key = read_hidden("Clave de la API: ")?;
+    if key.is_empty() {
+        return Ok(None);
+    }
+    store(&key)?;
+    Ok(Some(key))
+}
+
+fn store(key: &str) -> Result<()> {
+    match keychain_store(key) {
+        Ok(()) => {
+            println!("✅ Clave guardada en el llavero del sistema");
+            return Ok(());
+        }
+        Err(e) => println!("ℹ️  No se pudo guardar en el llavero del sistema: {:#}", e),
+    }
+    let passphrase = read_hidden("Frase de paso para cifrarla (vacía: no guardar): ")?;
+    if passphrase.is_empty() {
+        println!("ℹ️  La clave solo se usará en esta sesión");
+        return Ok(());
+    }
+    let encrypted = ColumnKey::new(&passphrase)?
+        .encrypt(&CellValue::Text(key.to_string()))?
+        .context("No se pudo cifrar la clave")?;
+    let file = paths::api_key_file();
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).context(format!("No se pudo crear {}", parent.display()))?;
+    }
+    write_private(&file, encrypted.as_bytes()).context(format!("No se pudo escribir {}", file.display()))?;
+    println!(
+        "✅ Clave cifrada en {}; en cada inicio se pedirá la frase de paso (o usa IAGENT_PASSPHRASE)",
+        file.display()
+    );
+    Ok(())
+}
+
+fn passphrase(prompt: &str) -> Result<String> {
+    if let Ok(passphrase) = profiles::var("IAGENT_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    if !io::stdin().is_terminal() {
+        bail!("La clave de la API está cifrada; define IAGENT_PASSPHRASE para usarla sin terminal");
+    }
+    read_hidden(prompt)
+}
+
+// Línea de la terminal sin eco. Si no se puede quitar el eco es un error: la
+// clave quedaría a la vista en la pantalla
+fn read_hidden(prompt: &str) -> Result<String> {
+    if !set_echo(false) {
+        bail!("No se puede ocultar lo que se escribe en esta terminal; define la clave en DEEPSEEK_API_KEY o IAGENT_API_KEY_CMD en lugar de escribirla");
+    }
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    let read = io::stdin().lock().read_line(&mut line);
+    set_echo(true);
+    println!();
+    read?;
+    Ok(line.trim().to_string())
+}
+
+#[cfg(unix)]
+fn set_echo(on: bool) -> bool {
+    Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(windows)]
+fn set_echo(on: bool) -> bool {
+    windows::set_console_echo(on)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn set_echo(_on: bool) -> bool {
+    false
+}
+
+fn run_shell(command: &str) -> Result<String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).stdin(Stdio::inherit()).output()
+    } else {
+        Command::new("sh").args(["-c", command]).stdin(Stdio::inherit()).output()
+    }
+    .context("No se pudo ejecutar el comando")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        bail!("{}", if stderr.is_empty() { output.status.to_string() } else { stderr });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(windows)]
+fn keychain_lookup() -> Option<String> {
+    windows::read_credential(&format!("{}:{}", KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT))
+}
+
+#[cfg(not(windows))]
+fn keychain_lookup() -> Option<String> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["find-generic-password", "-s", KEYCHAIN_SERVICE, "-a", KEYCHAIN_ACCOUNT, "-w"])
+            .stderr(Stdio::null())
+            .output()
+    } else if cfg!(unix) {
+        Command::new("secret-tool")
+            .args(["lookup", "service", KEYCHAIN_SERVICE, "account", KEYCHAIN_ACCOUNT])
+            .stderr(Stdio::null())
+            .output()
+    } else {
+        return None;
+    }
+    .ok()?;
+    let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !key.is_empty()).then_some(key)
+}
+
+#[cfg(windows)]
+fn keychain_store(key: &str) -> Result<()> {
+    windows::write_credential(&format!("{}:{}", KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT), KEYCHAIN_ACCOUNT, key)
+}
+
+#[cfg(not(windows))]
+fn keychain_store(key: &str) -> Result<()> {
+    // La clave va por la entrada estándar, nunca en los argumentos (los ve cualquiera con ps)
+    if cfg!(target_os = "macos") {
+        // security -i lee las órdenes de la entrada estándar
+        let quoted = key.replace('\\', "\\\\").replace('"', "\\\"");
+        let command = format!(
+            "add-generic-password -U -s {} -a {} -w \"{}\"\n",
+            KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT, quoted
+        );
+        if !run_with_input(Command::new("security").arg("-i"), &command) {
+            bail!("security no pudo guardarla en el llavero de macOS");
+        }
+        return Ok(());
+    }
+    if !cfg!(unix) {
+        bail!("este sistema no tiene un llavero compatible");
+    }
+    let stored = run_with_input(
+        Command::new("secret-tool").args(["store", "--label=IAgent API", "service", KEYCHAIN_SERVICE, "account", KEYCHAIN_ACCOUNT]),
+        key,
+    );
+    if !stored {
+        bail!("secret-tool (libsecret) no está instalado o no hay un servicio de llavero activo");
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn run_with_input(command: &mut Command, input: &str) -> bool {
+    let child = command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+    let Ok(mut child) = child else { return false };
+    let written = child
+        .stdin
+        .take()
+        .is_some_and(|mut stdin| stdin.write_all(input.as_bytes()).is_ok());
+    child.wait().is_ok_and(|status| status.success()) && written
+}
+
+// Crea el archivo ya con permisos 0600, así no hay un momento en que otros lo puedan leer
+#[cfg(unix)]
+pub fn write_private(path: &std::path::Path, contents: &[u8]) -> io::Result<()> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    let mut file = fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+    // mode() solo vale al crearlo; uno que ya existía puede tener otros permisos
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents)
+}
+
+#[cfg(not(unix))]
+pub fn write_private(path: &std::path::Path, contents: &[u8]) -> io::Result<()> {
+    fs::write(path, contents)
+}
+
+// Administrador de credenciales y consola de Windows, con las funciones del sistema
+// (advapi32 y kernel32) que usa también el crate keyring
+#[cfg(windows)]
+mod windows {
+    use anyhow::{bail, Result};
+    use std::ffi::c_void;
+    use std::ptr;
+
+    const CRED_TYPE_GENERIC: u32 = 1;
+    const CRED_PERSIST_LOCAL_MACHINE: u32 = 2;
+    const STD_INPUT_HANDLE: u32 = -10i32 as u32;
+    const ENABLE_ECHO_INPUT: u32 = 0x0004;
+
+    // CREDENTIALW de wincred.h
+    #[repr(C)]
+    struct Credential {
+        flags: u32,
+        kind: u32,
+        target_name: *mut u16,
+        comment: *mut u16,
+        last_written: [u32; 2],
+        blob_size: u32,
+        blob: *mut u8,
+        persist: u32,
+        attribute_count: u32,
+        attributes: *mut c_void,
+        target_alias: *mut u16,
+        user_name: *mut u16,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn CredReadW(target: *const u16, kind: u32, flags: u32, credential: *mut *mut Credential) -> i32;
+        fn CredWriteW(credential: *const Credential, flags: u32) -> i32;
+        fn CredFree(buffer: *mut c_void);
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+        fn GetLastError() -> u32;
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    pub fn read_credential(target: &str) -> Option<String> {
+        let target = wide(target);
+        let mut credential = ptr::null_mut();
+        // SAFETY: `target` termina en 0 y CredReadW deja en `credential` un bloque
+        // suyo, que se lee antes de liberarlo con CredFree
+        unsafe {
+            if CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) == 0 {
+                return None;
+            }
+            let blob = &*credential;
+            let key = std::slice::from_raw_parts(blob.blob, blob.blob_size as usize).to_vec();
+            CredFree(credential.cast());
+            String::from_utf8(key).ok().filter(|key| !key.is_empty())
+        }
+    }
+
+    pub fn write_credential(target: &str, user: &str, secret: &str) -> Result<()> {
+        let (mut target, mut user, mut secret) = (wide(target), wide(user), secret.as_bytes().to_vec());
+        let credential = Credential {
+            flags: 0,
+            kind: CRED_TYPE_GENERIC,
+            target_name: target.as_mut_ptr(),
+            comment: ptr::null_mut(),
+            last_written: [0; 2],
+            blob_size: secret.len() as u32,
+            blob: secret.as_mut_ptr(),
+            persist: CRED_PERSIST_LOCAL_MACHINE,
+            attribute_count: 0,
+            attributes: ptr::null_mut(),
+            target_alias: ptr::null_mut(),
+            user_name: user.as_mut_ptr(),
+        };
+        // SAFETY: los punteros son de búferes vivos hasta el final de la función y
+        // CredWriteW los copia
+        if unsafe { CredWriteW(&credential, 0) } == 0 {
+            bail!("el Administrador de credenciales la rechazó (error {})", unsafe { GetLastError() });
+        }
+        Ok(())
+    }
+
+    // Activa o quita el eco de la consola; false si la entrada no es una consola
+    pub fn set_console_echo(on: bool) -> bool {
+        // SAFETY: solo se consulta y cambia el modo del manejador de la entrada estándar
+        unsafe {
+            let console = GetStdHandle(STD_INPUT_HANDLE);
+            let mut mode = 0;
+            if console.is_null() || GetConsoleMode(console, &mut mode) == 0 {
+                return false;
+            }
+            let mode = if on { mode | ENABLE_ECHO_INPUT } else { mode & !ENABLE_ECHO_INPUT };
+            SetConsoleMode(console, mode) != 0
+        }
+    }
+}
@@ -0,0 +1,565 @@
+// Modo servidor (`ia_agent serve --port 8080`): expone el agente como una API
+// HTTP con JSON para integrarlo en otras herramientas. Permite enviar preguntas
+// (con una conversación por sesión), subir y descargar archivos del espacio de
+// trabajo y ejecutar las herramientas de Excel del modelo directamente.
+//
+// El HTTP/1.1 lo atiende hyper (cuerpos con Content-Length o por trozos,
+// conexiones persistentes), cada conexión en su propia tarea. Las preguntas de una
+// misma sesión se atienden en orden; las de sesiones distintas, a la vez. Las
+// sesiones sin usar durante SESSION_IDLE se descartan y nunca hay más de
+// MAX_SESSIONS abiertas.
+use crate::agent;
+use crate::backup;
+use crate::config::Config;
+use crate::error::IAgentError;
+use crate::llm::{self, Message};
+use crate::prompts;
+use crate::sandbox::Access;
+use crate::settings::{self, Settings};
+use crate::usage::UsageTracker;
+use crate::verify;
+use anyhow::{Context, Result};
+use hyper::body::HttpBody;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, StatusCode};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+const DEFAULT_SESSION: &str = "principal";
+const MAX_BODY_BYTES: usize = 50 * 1024 * 1024;
+// Tiempo máximo para recibir las cabeceras y, aparte, el cuerpo de una petición
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+// Conversaciones abiertas a la vez y tiempo sin preguntas tras el que se descartan
+const MAX_SESSIONS: usize = 100;
+const SESSION_IDLE: Duration = Duration::from_secs(60 * 60);
+// Número de la subida dentro del proceso, para su temporal
+static NEXT_UPLOAD: AtomicUsize = AtomicUsize::new(0);
+
+struct Request {
+    method: String,
+    // Ruta sin parámetros de consulta, ya decodificada
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn json(&self) -> Result<Value> {
+        if self.body.is_empty() {
+            return Ok(json!({}));
+        }
+        serde_json::from_slice(&self.body).context("El cuerpo de la petición no es JSON válido")
+    }
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, value: Value) -> Response {
+        Response {
+            status,
+            content_type: "application/json; charset=utf-8",
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Response {
+        Response::json(status, json!({ "error": message.into() }))
+    }
+
+    // El código depende del tipo de fallo: del cliente (400), del libro (422), del
+    // modelo (502) o del propio servidor (500)
+    fn from_error(error: &anyhow::Error) -> Response {
+        let (status, kind) = match IAgentError::find(error) {
+            Some(IAgentError::Api { .. }) => (502, "api"),
+            Some(IAgentError::Excel { .. }) => (422, "excel"),
+            Some(IAgentError::Parse { .. }) => (400, "parse"),
+            Some(IAgentError::Config(_)) => (500, "config"),
+            Some(IAgentError::Model(_)) => (502, "modelo"),
+            Some(IAgentError::Tool { .. }) => (422, "herramienta"),
+            None => (500, "interno"),
+        };
+        Response::json(status, json!({ "error": format!("{:#}", error), "tipo": kind }))
+    }
+
+    fn into_hyper(self) -> hyper::Response<Body> {
+        let mut response = hyper::Response::new(Body::from(self.body));
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, hyper::header::HeaderValue::from_static(self.content_type));
+        response
+    }
+}
+
+// Una conversación con su cerrojo, para que sus preguntas vayan en orden
+struct Session {
+    history: Arc<Mutex<Vec<Message>>>,
+    last_used: Instant,
+}
+
+// Estado compartido entre las tareas de las conexiones
+struct Server {
+    client: Client,
+    config: Config,
+    // Los del agente que arrancó el servidor; hyper lanza las tareas de las
+    // conexiones, así que cada petición los vuelve a instalar
+    settings: Arc<Settings>,
+    system_template: String,
+    sessions: Mutex<HashMap<String, Session>>,
+    usage_tracker: Mutex<UsageTracker>,
+}
+
+pub async fn run(config: &Config, system_template: &str) -> Result<()> {
+    let address = config.serve.context("Falta la dirección del servidor")?;
+    let builder = hyper::Server::try_bind(&address).context(format!("No se pudo escuchar en {}", address))?;
+    println!("🌐 Servidor escuchando en http://{}", address);
+    println!("📂 Espacio de trabajo: {}", config.workspace.root().display());
+    if config.serve_token.is_none() {
+        println!("⚠️  Sin IAGENT_SERVE_TOKEN: cualquiera que llegue a esta dirección puede usar el agente");
+    }
+
+    let server = Arc::new(Server {
+        client: llm::build_client(&config.http)?,
+        config: config.clone(),
+        settings: settings::current(),
+        system_template: system_template.to_string(),
+        sessions: Mutex::new(HashMap::new()),
+        usage_tracker: Mutex::new(UsageTracker::default()),
+    });
+    // Una petición lenta (una pregunta al modelo) no bloquea a las demás
+    let make_service = make_service_fn(|connection: &AddrStream| {
+        let server = Arc::clone(&server);
+        let peer = connection.remote_addr();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let server = Arc::clone(&server);
+                async move { Ok::<_, Infallible>(server.settings.scope(server.handle(request, peer)).await) }
+            }))
+        }
+    });
+    builder
+        .http1_header_read_timeout(READ_TIMEOUT)
+        .serve(make_service)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .context("El servidor se ha detenido por un error")?;
+    println!();
+    println!("{}", server.usage_tracker.lock().await.report());
+    Ok(())
+}
+
+impl Server {
+    async fn handle(&self, request: hyper::Request<Body>, peer: SocketAddr) -> hyper::Response<Body> {
+        let request = match tokio::time::timeout(READ_TIMEOUT, read_request(request)).await {
+            Ok(Ok(request)) => request,
+            Ok(Err(rejected)) => {
+                println!("🌐 {}: petición rechazada → {}", peer, rejected.status);
+                return rejected.into_hyper();
+            }
+            Err(_) => {
+                println!("🌐 {}: el cuerpo no llegó completo en {} s", peer, READ_TIMEOUT.as_secs());
+                return Response::error(408, format!("El cuerpo no llegó completo en {} s", READ_TIMEOUT.as_secs())).into_hyper();
+            }
+        };
+        let response = if self.authorized(&request) {
+            match self.route(&request).await {
+                Ok(response) => response,
+                Err(e) => Response::from_error(&e),
+            }
+        } else {
+            Response::error(401, "Falta el token o no es válido (Authorization: Bearer ...)")
+        };
+        println!("🌐 {} {} → {}", request.method, request.path, response.status);
+        response.into_hyper()
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        match &self.config.serve_token {
+            Some(token) => request
+                .header("authorization")
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .is_some_and(|sent| same_token(sent.trim(), token)),
+            None => true,
+        }
+    }
+
+    async fn route(&self, request: &Request) -> Result<Response> {
+        let segments: Vec<&str> = request.path.trim_matches('/').splitn(2, '/').collect();
+        let response = match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["" | "salud"]) => Response::json(200, json!({ "estado": "ok", "modelo": self.config.model })),
+            ("POST", ["preguntar"]) => self.ask(request).await?,
+            ("DELETE", ["sesiones", session]) => {
+                let removed = self.sessions.lock().await.remove(*session).is_some();
+                Response::json(if removed { 200 } else { 404 }, json!({ "sesion": session, "borrada": removed }))
+            }
+            ("GET", ["herramientas"]) => Response::json(200, self.config.tools.definitions()),
+            ("POST", ["herramientas", name]) => self.run_tool(name, request).await?,
+            ("GET", ["archivos"]) => Response::json(200, json!({ "archivos": self.list_files()? })),
+            ("GET", ["archivos", file]) => self.download(file)?,
+            ("PUT" | "POST", ["archivos", file]) => self.upload(file, &request.body)?,
+            (_, ["" | "salud" | "preguntar" | "herramientas" | "archivos"])
+            | (_, ["sesiones" | "herramientas" | "archivos", _]) => {
+                Response::error(405, format!("Método {} no permitido en {}", request.method, request.path))
+            }
+            _ => Response::error(404, format!("No existe {}", request.path)),
+        };
+        Ok(response)
+    }
+
+    // {"pregunta": "...", "sesion": "..."} -> {"sesion", "respuesta"}; el modelo
+    // puede usar las herramientas sobre los archivos del espacio de trabajo
+    async fn ask(&self, request: &Request) -> Result<Response> {
+        let body = match request.json() {
+            Ok(body) => body,
+            Err(e) => return Ok(Response::error(400, format!("{:#}", e))),
+        };
+        let Some(question) = body.get("pregunta").and_then(Value::as_str) else {
+            return Ok(Response::error(400, "Falta el argumento 'pregunta'"));
+        };
+        let session = body
+            .get("sesion")
+            .and_then(Value::as_str)
+            .unwrap_or(DEFAULT_SESSION)
+            .to_string();
+        let Some(history) = self.session(&session).await else {
+            return Ok(Response::error(
+                503,
+                format!("Hay {} sesiones respondiendo; vuelve a intentarlo cuando termine alguna", MAX_SESSIONS),
+            ));
+        };
+        let mut history = history.lock().await;
+        // El consumo de cada pregunta se suma al de la sesión del servidor al terminar
+        let mut usage = UsageTracker::default();
+        let answer = agent::answer(&self.client, &self.config, &mut history, &mut usage, question).await;
+        self.usage_tracker.lock().await.merge(usage);
+        match answer {
+            Ok(answer) => Ok(Response::json(200, json!({ "sesion": session, "respuesta": answer }))),
+            Err(e) => Ok(Response::from_error(&e)),
+        }
+    }
+
+    // Conversación de la sesión, creada si no existe. Antes se descartan las que
+    // llevan SESSION_IDLE sin usarse y, si ya hay MAX_SESSIONS, la que lleva más
+    // tiempo sin usarse; None si todas tienen una pregunta en curso
+    async fn session(&self, name: &str) -> Option<Arc<Mutex<Vec<Message>>>> {
+        let mut sessions = self.sessions.lock().await;
+        let now = Instant::now();
+        // Con una pregunta en curso alguien más tiene la conversación
+        let busy = |session: &Session| Arc::strong_count(&session.history) > 1;
+        sessions.retain(|_, session| busy(session) || now.duration_since(session.last_used) < SESSION_IDLE);
+        if !sessions.contains_key(name) && sessions.len() >= MAX_SESSIONS {
+            let oldest = sessions
+                .iter()
+                .filter(|(_, session)| !busy(session))
+                .min_by_key(|(_, session)| session.last_used)
+                .map(|(key, _)| key.clone())?;
+            sessions.remove(&oldest);
+        }
+        let session = sessions.entry(name.to_string()).or_insert_with(|| Session {
+            history: Arc::new(Mutex::new(vec![Message::new(
+                "system",
+                prompts::render(&self.system_template, &prompts::workbook_vars(&[])),
+            )])),
+            last_used: now,
+        });
+        session.last_used = now;
+        Some(Arc::clone(&session.history))
+    }
+
+    // El cuerpo son los argumentos de la herramienta, como los enviaría el modelo
+    async fn run_tool(&self, name: &str, request: &Request) -> Result<Response> {
+        let Ok(arguments) = String::from_utf8(request.body.clone()) else {
+            return Ok(Response::error(400, "El cuerpo no es UTF-8"));
+        };
+        let max_tokens = self.config.context_budget.per_item;
+        let output = self
+            .config
+            .tools
+            .execute(name, &arguments, max_tokens, &self.config.workspace)
+            .await?;
+        Ok(Response::json(200, json!({ "herramienta": name, "resultado": output })))
+    }
+
+    // Archivos del directorio raíz del espacio de trabajo
+    fn list_files(&self) -> Result<Vec<Value>> {
+        let root = self.config.workspace.root();
+        let mut files = Vec::new();
+        for entry in fs::read_dir(root).context(format!("No se pudo leer {}", root.display()))? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                files.push(json!({
+                    "nombre": entry.file_name().to_string_lossy(),
+                    "bytes": metadata.len(),
+                }));
+            }
+        }
+        files.sort_by(|a, b| a["nombre"].as_str().cmp(&b["nombre"].as_str()));
+        Ok(files)
+    }
+
+    fn download(&self, file: &str) -> Result<Response> {
+        let path = match self.config.workspace.resolve("servidor", file, Access::Read) {
+            Ok(path) => path,
+            Err(e) => return Ok(Response::error(400, format!("{:#}", e))),
+        };
+        if !Path::new(&path).is_file() {
+            return Ok(Response::error(404, format!("No existe el archivo {}", file)));
+        }
+        let body = fs::read(&path).context(format!("No se pudo leer {}", file))?;
+        Ok(Response {
+            status: 200,
+            content_type: content_type(file),
+            body,
+        })
+    }
+
+    fn upload(&self, file: &str, body: &[u8]) -> Result<Response> {
+        if body.is_empty() {
+            return Ok(Response::error(400, "El cuerpo está vacío; envía el contenido del archivo"));
+        }
+        let path = match self.config.workspace.resolve("servidor", file, Access::Write) {
+            Ok(path) => path,
+            Err(e) => return Ok(Response::error(400, format!("{:#}", e))),
+        };
+        let path = Path::new(&path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(format!("No se pudo crear el directorio de {}", file))?;
+        }
+        // Se escribe a un temporal junto al destino (con su extensión, para poder
+        // comprobarlo) y solo si se puede abrir sustituye al archivo; un libro
+        // dañado es un error del contenido enviado (422) y deja el original intacto
+        let temp = upload_temp(path);
+        fs::write(&temp, body).context(format!("No se pudo guardar {}", file))?;
+        if let Err(e) = verify::check(&temp) {
+            let _ = fs::remove_file(&temp);
+            return Err(IAgentError::excel(file, format!("{:#}", e)).into());
+        }
+        // Cada subida es una operación: deshacer recupera el archivo que sustituyó
+        backup::begin_operation();
+        if let Err(e) = backup::before_write(path) {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+        let renamed = fs::rename(&temp, path);
+        if renamed.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        renamed.context(format!("No se pudo reemplazar {}", file))?;
+        verify::after_write(path)?;
+        println!("✅ {} guardado ({} bytes)", file, body.len());
+        Ok(Response::json(201, json!({ "archivo": file, "bytes": body.len() })))
+    }
+}
+
+// Temporal de una subida junto a `path`, distinto en cada una para que dos subidas
+// simultáneas del mismo archivo no escriban en el mismo
+fn upload_temp(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let upload = NEXT_UPLOAD.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".subida-{}-{}-{}", std::process::id(), upload, name))
+}
+
+// Compara el token sin salir al primer byte distinto, para que el tiempo de
+// respuesta no indique cuántos caracteres se acertaron
+fn same_token(sent: &str, token: &str) -> bool {
+    sent.len() == token.len() && sent.bytes().zip(token.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn content_type(file: &str) -> &'static str {
+    let extension = Path::new(file)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "csv" => "text/csv; charset=utf-8",
+        "json" => "application/json; charset=utf-8",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+// Una petición que no se puede atender devuelve directamente la respuesta de error.
+// hyper ya ha leído las cabeceras y decodifica el cuerpo, venga con Content-Length
+// o por trozos (Transfer-Encoding: chunked)
+async fn read_request(request: hyper::Request<Body>) -> Result<Request, Response> {
+    let bad_request = |message: String| Response::error(400, message);
+    let too_large = |length: usize| {
+        Response::error(
+            413,
+            format!("Cuerpo demasiado grande ({} bytes, máximo {})", length, MAX_BODY_BYTES),
+        )
+    };
+    let (parts, mut body) = request.into_parts();
+    let headers: Vec<(String, String)> = parts
+        .headers
+        .iter()
+        .map(|(key, value)| (key.as_str().to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect();
+    if let Some(value) = parts.headers.get(CONTENT_LENGTH) {
+        let length = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .ok_or_else(|| bad_request(format!("Content-Length no válido: {:?}", value)))?;
+        if length > MAX_BODY_BYTES {
+            return Err(too_large(length));
+        }
+    }
+    // Por trozos no se sabe el tamaño hasta el final: se corta al pasar del máximo
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| bad_request(format!("No se pudo leer el cuerpo: {}", e)))?;
+        if bytes.len() + chunk.len() > MAX_BODY_BYTES {
+            return Err(too_large(bytes.len() + chunk.len()));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(Request {
+        method: parts.method.as_str().to_uppercase(),
+        path: percent_decode(parts.uri.path()).map_err(|e| bad_request(format!("{:#}", e)))?,
+        headers,
+        body: bytes,
+    })
+}
+
+// "/archivos/ventas%202024.xlsx" -> "/archivos/ventas 2024.xlsx"
+fn percent_decode(path: &str) -> Result<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = path.get(i + 1..i + 3).context(format!("Ruta mal codificada: {}", path))?;
+            decoded.push(u8::from_str_radix(hex, 16).context(format!("Ruta mal codificada: {}", path))?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).context(format!("La ruta no es UTF-8: {}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+    use std::path::PathBuf;
+
+    fn server(dir: &Path, token: Option<&str>) -> Server {
+        let mut config = Config::offline(Arc::new(MockProvider::new(Vec::new())), dir);
+        config.serve_token = token.map(str::to_string);
+        Server {
+            client: Client::new(),
+            config,
+            settings: settings::current(),
+            system_template: String::new(),
+            sessions: Mutex::new(HashMap::new()),
+            usage_tracker: Mutex::new(UsageTracker::default()),
+        }
+    }
+
+    fn request(method: &str, path: &str, token: Option<&str>, body: Vec<u8>) -> hyper::Request<Body> {
+        let mut builder = hyper::Request::builder().method(method).uri(path);
+        if let Some(token) = token {
+            builder = builder.header("Authorization", format!("Bearer {}", token));
+        }
+        builder.body(Body::from(body)).unwrap()
+    }
+
+    async fn send(server: &Server, request: hyper::Request<Body>) -> (u16, Value) {
+        let response = server.handle(request, SocketAddr::from(([127, 0, 0, 1], 0))).await;
+        let status = response.status().as_u16();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    // ventas.xlsx válido en el directorio de la prueba
+    fn workbook(name: &str) -> PathBuf {
+        let dir = crate::paths::test_dir(name);
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        workbook.add_worksheet().write_string(0, 0, "Cliente").unwrap();
+        workbook.save(dir.join("ventas.xlsx")).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_or_wrong_token() {
+        let dir = crate::paths::test_dir("servidor_token");
+        let server = server(&dir, Some("secreto"));
+        assert_eq!(send(&server, request("GET", "/salud", None, Vec::new())).await.0, 401);
+        assert_eq!(send(&server, request("GET", "/salud", Some("secretO"), Vec::new())).await.0, 401);
+        assert_eq!(send(&server, request("GET", "/salud", Some("secreto"), Vec::new())).await.0, 200);
+    }
+
+    #[tokio::test]
+    async fn rejects_bodies_over_the_cap() {
+        let dir = crate::paths::test_dir("servidor_limite");
+        let server = server(&dir, None);
+        let body = vec![b'a'; MAX_BODY_BYTES + 1];
+        let (status, _) = send(&server, request("PUT", "/archivos/grande.csv", None, body)).await;
+        assert_eq!(status, 413);
+        assert!(!dir.join("grande.csv").exists());
+    }
+
+    #[tokio::test]
+    async fn invalid_upload_leaves_the_original_intact() {
+        let dir = workbook("servidor_subida_invalida");
+        let original = fs::read(dir.join("ventas.xlsx")).unwrap();
+        let server = server(&dir, None);
+        let (status, body) = send(&server, request("PUT", "/archivos/ventas.xlsx", None, b"no es un libro".to_vec())).await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(body["tipo"], "excel");
+        assert_eq!(fs::read(dir.join("ventas.xlsx")).unwrap(), original);
+        let leftovers: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(leftovers, vec![std::ffi::OsString::from("ventas.xlsx")]);
+    }
+
+    #[tokio::test]
+    async fn valid_upload_replaces_the_file() {
+        let dir = workbook("servidor_subida");
+        let replacement = workbook("servidor_subida_nueva").join("ventas.xlsx");
+        let content = fs::read(&replacement).unwrap();
+        let server = server(&dir, None);
+        let (status, body) = send(&server, request("PUT", "/archivos/ventas.xlsx", None, content.clone())).await;
+        assert_eq!(status, 201, "{}", body);
+        assert_eq!(fs::read(dir.join("ventas.xlsx")).unwrap(), content);
+    }
+
+    #[test]
+    fn each_upload_has_its_own_temporary_file() {
+        let path = Path::new("datos/ventas.xlsx");
+        let (first, second) = (upload_temp(path), upload_temp(path));
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), path.parent());
+        assert_eq!(first.extension().unwrap(), "xlsx");
+    }
+}
@@ -0,0 +1,287 @@
+// Ajustes de un agente: lo que fija su configuración (idioma de la interfaz,
+// salidas, límites, páginas, auditoría y cifrado de columnas), sus observadores
+// de eventos, quién confirma las herramientas, si escribe en la terminal, el
+// manifiesto de los archivos que escribe, lo último que ha mostrado (para
+// `copiar`) y su barra de progreso activa. Cada agente tiene los suyos y los
+// instala mientras trabaja (`Settings::scope`), así que dos agentes del mismo
+// proceso no se pisan; los módulos leen los del agente en curso con `current()`.
+// Fuera de un agente rigen los valores por defecto.
+//
+// Las tareas y los hilos nuevos no heredan los ajustes por sí solos: donde se
+// lanzan se envuelven con `inherit` o `inherit_blocking`.
+use crate::clipboard::Shown;
+use crate::config::Config;
+use crate::crypto::{self, ColumnKey};
+use crate::events::{AgentEvent, AgentObserver, EventLog};
+use crate::i18n::Lang;
+use crate::limits::Limits;
+use crate::outputs::OutputPolicy;
+use crate::untrusted::ConfirmTool;
+use crate::verify::FileRecord;
+use anyhow::Result;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::task::futures::TaskLocalFuture;
+
+tokio::task_local! {
+    static CURRENT: Arc<Settings>;
+}
+
+#[derive(Default)]
+pub struct Settings {
+    configured: RwLock<Configured>,
+    observers: Mutex<Vec<Arc<dyn AgentObserver>>>,
+    // Quién decide si se ejecuta una herramienta que pide confirmación; sin nadie, no se ejecuta
+    confirmation: RwLock<Option<Arc<dyn ConfirmTool>>>,
+    // El agente es el de la terminal: muestra los avisos y el progreso
+    terminal: AtomicBool,
+    // Última entrada de cada archivo escrito, en el orden de la primera escritura
+    manifest: Mutex<Vec<FileRecord>>,
+    // Última respuesta y última tabla mostradas, para `copiar`
+    shown: Mutex<Shown>,
+    // Texto de la barra de progreso activa, para que el spinner la mantenga a su izquierda
+    bar_line: Mutex<String>,
+}
+
+#[derive(Clone, Default)]
+struct Configured {
+    lang: Lang,
+    outputs: OutputPolicy,
+    limits: Limits,
+    page_size: Option<usize>,
+    audit_log: Option<PathBuf>,
+    encryption: Option<Arc<crypto::OutputPolicy>>,
+    // Registro de IAGENT_EVENTS_LOG suscrito con esta configuración
+    event_log: Option<Arc<dyn AgentObserver>>,
+}
+
+impl Settings {
+    pub fn new(config: &Config) -> Result<Arc<Settings>> {
+        let settings = Arc::new(Settings::default());
+        settings.configure(config)?;
+        Ok(settings)
+    }
+
+    // Aplica la configuración. Se vuelve a llamar al cambiar de perfil: el registro
+    // de eventos nuevo sustituye al anterior y los demás observadores se conservan.
+    pub fn configure(&self, config: &Config) -> Result<()> {
+        // Lo que puede fallar va primero, para no dejar la configuración a medias
+        let event_log = match &config.events_log {
+            Some(path) => Some(Arc::new(EventLog::open(path)?) as Arc<dyn AgentObserver>),
+            None => None,
+        };
+        let key = config.project_key.as_deref().map(ColumnKey::new).transpose()?;
+        let configured = Configured {
+            lang: config.lang,
+            outputs: config.outputs.clone(),
+            limits: config.limits,
+            page_size: Some(config.page_size),
+            audit_log: config.audit_log.clone(),
+            encryption: crypto::OutputPolicy::new(config.encrypt_columns.clone(), key).map(Arc::new),
+            event_log: event_log.clone(),
+        };
+        let previous_log = match self.configured.write() {
+            Ok(mut current) => std::mem::replace(&mut *current, configured).event_log,
+            Err(_) => None,
+        };
+        if let Some(previous) = previous_log {
+            self.unsubscribe(&previous);
+        }
+        if let Some(log) = event_log {
+            self.subscribe(log);
+        }
+        Ok(())
+    }
+
+    fn configured(&self) -> Configured {
+        self.configured.read().map(|configured| configured.clone()).unwrap_or_default()
+    }
+
+    pub fn lang(&self) -> Lang {
+        self.configured().lang
+    }
+
+    pub fn outputs(&self) -> OutputPolicy {
+        self.configured().outputs
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.configured().limits
+    }
+
+    // Filas por página de IAGENT_PAGE_SIZE; None sin configurar
+    pub fn page_size(&self) -> Option<usize> {
+        self.configured().page_size
+    }
+
+    pub fn audit_log(&self) -> Option<PathBuf> {
+        self.configured().audit_log
+    }
+
+    // Columnas que se cifran en todos los libros que se guardan, si las hay
+    pub fn encryption(&self) -> Option<Arc<crypto::OutputPolicy>> {
+        self.configured().encryption
+    }
+
+    pub fn manifest(&self) -> &Mutex<Vec<FileRecord>> {
+        &self.manifest
+    }
+
+    pub fn shown(&self) -> &Mutex<Shown> {
+        &self.shown
+    }
+
+    pub fn bar_line(&self) -> &Mutex<String> {
+        &self.bar_line
+    }
+
+    pub fn subscribe(&self, observer: Arc<dyn AgentObserver>) {
+        if let Ok(mut observers) = self.observers.lock() {
+            observers.push(observer);
+        }
+    }
+
+    // Quita un observador suscrito, como el registro de eventos de un perfil anterior
+    pub fn unsubscribe(&self, observer: &Arc<dyn AgentObserver>) {
+        if let Ok(mut observers) = self.observers.lock() {
+            observers.retain(|existing| !Arc::ptr_eq(existing, observer));
+        }
+    }
+
+    pub fn confirm_tools_with(&self, confirmation: Arc<dyn ConfirmTool>) {
+        if let Ok(mut current) = self.confirmation.write() {
+            *current = Some(confirmation);
+        }
+    }
+
+    pub fn confirmation(&self) -> Option<Arc<dyn ConfirmTool>> {
+        self.confirmation.read().ok()?.clone()
+    }
+
+    // Solo para el agente del binario; el de la biblioteca no escribe en la terminal
+    pub fn use_terminal(&self) {
+        self.terminal.store(true, Ordering::Relaxed);
+    }
+
+    pub fn terminal(&self) -> bool {
+        self.terminal.load(Ordering::Relaxed)
+    }
+
+    pub fn emit(&self, event: AgentEvent) {
+        // Se avisa fuera del bloqueo: un observador puede emitir o suscribir a su vez
+        let observers: Vec<Arc<dyn AgentObserver>> = match self.observers.lock() {
+            Ok(observers) if !observers.is_empty() => observers.clone(),
+            _ => return,
+        };
+        for observer in observers {
+            observer.on_event(&event);
+        }
+    }
+
+    // Ejecuta `future` con estos ajustes como los del agente en curso
+    pub fn scope<F: Future>(self: &Arc<Self>, future: F) -> TaskLocalFuture<Arc<Settings>, F> {
+        CURRENT.scope(Arc::clone(self), future)
+    }
+
+    pub fn sync_scope<R>(self: &Arc<Self>, operation: impl FnOnce() -> R) -> R {
+        CURRENT.sync_scope(Arc::clone(self), operation)
+    }
+}
+
+// Ajustes del agente en curso; fuera de un agente, unos por defecto que no se
+// comparten con nadie
+pub fn current() -> Arc<Settings> {
+    CURRENT.try_with(Arc::clone).unwrap_or_default()
+}
+
+// Para tokio::spawn y JoinSet::spawn: la tarea nueva sigue con los ajustes de quien la lanza
+pub fn inherit<F: Future>(future: F) -> TaskLocalFuture<Arc<Settings>, F> {
+    current().scope(future)
+}
+
+// Para spawn_blocking y thread::spawn
+pub fn inherit_blocking<R>(operation: impl FnOnce() -> R) -> impl FnOnce() -> R {
+    let settings = current();
+    move || settings.sync_scope(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn settings(lang: Lang, max_write_cells: usize) -> Arc<Settings> {
+        let dir = crate::paths::test_dir("settings");
+        let mut config = Config::offline(Arc::new(MockProvider::new(Vec::new())), &dir);
+        config.lang = lang;
+        config.limits.max_write_cells = Some(max_write_cells);
+        Settings::new(&config).unwrap()
+    }
+
+    fn counter(count: &Arc<AtomicUsize>) -> Arc<dyn AgentObserver> {
+        let count = Arc::clone(count);
+        Arc::new(move |_: &AgentEvent| {
+            count.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+
+    fn written() -> AgentEvent {
+        AgentEvent::FileWritten { path: "a.xlsx".to_string() }
+    }
+
+    #[tokio::test]
+    async fn each_agent_reads_its_own_settings() {
+        let spanish = settings(Lang::Es, 10);
+        let english = settings(Lang::En, 20);
+        let (a, b) = tokio::join!(
+            spanish.scope(async { (crate::i18n::lang(), crate::limits::get().max_write_cells) }),
+            english.scope(async { (crate::i18n::lang(), crate::limits::get().max_write_cells) }),
+        );
+        assert_eq!(a, (Lang::Es, Some(10)));
+        assert_eq!(b, (Lang::En, Some(20)));
+        // Fuera de un agente, los valores por defecto
+        assert_eq!(crate::limits::get().max_write_cells, Limits::default().max_write_cells);
+    }
+
+    #[tokio::test]
+    async fn events_reach_only_the_observers_of_their_agent() {
+        let (first, second) = (settings(Lang::Es, 10), settings(Lang::Es, 10));
+        let (seen_first, seen_second) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        first.subscribe(counter(&seen_first));
+        second.subscribe(counter(&seen_second));
+        first.scope(async { crate::events::emit(written()) }).await;
+        crate::events::emit(written());
+        assert_eq!(seen_first.load(Ordering::SeqCst), 1);
+        assert_eq!(seen_second.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn spawned_tasks_and_threads_inherit_the_settings() {
+        let english = settings(Lang::En, 10);
+        let langs = english
+            .scope(async {
+                let task = tokio::spawn(inherit(async { crate::i18n::lang() }));
+                let blocking = tokio::task::spawn_blocking(inherit_blocking(crate::i18n::lang));
+                (task.await.unwrap(), blocking.await.unwrap())
+            })
+            .await;
+        assert_eq!(langs, (Lang::En, Lang::En));
+    }
+
+    #[test]
+    fn reconfiguring_keeps_the_observers() {
+        let settings = settings(Lang::Es, 10);
+        let seen = Arc::new(AtomicUsize::new(0));
+        settings.subscribe(counter(&seen));
+        let dir = crate::paths::test_dir("settings_reconfigure");
+        let mut config = Config::offline(Arc::new(MockProvider::new(Vec::new())), &dir);
+        config.lang = Lang::En;
+        settings.configure(&config).unwrap();
+        settings.sync_scope(|| crate::events::emit(written()));
+        assert_eq!(settings.lang(), Lang::En);
+        assert_eq!(seen.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,325 @@
+// Cambios de estructura en los libros cargados (`insertar_fila`, `eliminar_fila`,
+// `insertar_columna`, `eliminar_columna`, `mover_columna`). Se aplican a la copia
+// en memoria de `leer_excel`, que se puede consultar con `mostrar` antes de
+// reescribir el archivo con `guardar`.
+use crate::excel::{self, CellValue, SheetData};
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StructureEdit {
+    // Filas como en Excel (desde 1); se insertan antes de `at`
+    InsertRows { at: usize, count: usize },
+    DeleteRows { first: usize, last: usize },
+    // Columnas por letra, número o encabezado; se resuelven contra la hoja
+    InsertColumn { before: String, header: Option<String> },
+    DeleteColumn(String),
+    // La columna pasa a ocupar la posición de `to`
+    MoveColumn { column: String, to: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct EditOptions {
+    // Libro cargado; sin él, el último que tenga la hoja
+    pub file: Option<String>,
+    pub sheet: String,
+    pub edit: StructureEdit,
+}
+
+impl EditOptions {
+    // `<comando> <hoja> <argumentos> [archivo=<libro>]`
+    pub fn parse(command: &str, args: &[&str], options: &HashMap<&str, &str>) -> Option<EditOptions> {
+        let (sheet, rest) = args.split_first()?;
+        let edit = match (command, rest) {
+            ("insertar_fila", [row]) => StructureEdit::InsertRows {
+                at: parse_row(row)?,
+                count: match options.get("cantidad") {
+                    Some(count) => count.parse().ok().filter(|count| *count > 0)?,
+                    None => 1,
+                },
+            },
+            ("eliminar_fila", [rows]) => {
+                let (first, last) = rows.split_once('-').unwrap_or((rows, rows));
+                let (first, last) = (parse_row(first)?, parse_row(last)?);
+                StructureEdit::DeleteRows {
+                    first: first.min(last),
+                    last: first.max(last),
+                }
+            }
+            ("insertar_columna", [before]) => StructureEdit::InsertColumn {
+                before: before.to_string(),
+                header: options.get("encabezado").map(|header| header.to_string()),
+            },
+            ("eliminar_columna", [column]) => StructureEdit::DeleteColumn(column.to_string()),
+            ("mover_columna", [column, to]) => StructureEdit::MoveColumn {
+                column: column.to_string(),
+                to: to.to_string(),
+            },
+            _ => return None,
+        };
+        Some(EditOptions {
+            file: options.get("archivo").map(|file| file.to_string()),
+            sheet: sheet.to_string(),
+            edit,
+        })
+    }
+}
+
+fn parse_row(value: &str) -> Option<usize> {
+    value.trim().parse().ok().filter(|row| *row > 0)
+}
+
+// Aplica el cambio y devuelve su descripción
+pub fn apply(sheet: &mut SheetData, edit: &StructureEdit) -> Result<String> {
+    let resolve = |sheet: &SheetData, spec: &str| {
+        sheet
+            .column_index(spec)
+            .context(format!("No existe la columna '{}' en la hoja {}", spec, sheet.name))
+    };
+    match edit {
+        StructureEdit::InsertRows { at, count } => {
+            let at = at - 1;
+            if sheet.rows.len() < at {
+                sheet.rows.resize(at, Vec::new());
+            }
+            sheet.rows.splice(at..at, std::iter::repeat_with(Vec::new).take(*count));
+            remap_rows(sheet, |row| Some(if row >= at { row + count } else { row }));
+            Ok(format!("{} fila(s) insertada(s) antes de la fila {}", count, at + 1))
+        }
+        StructureEdit::DeleteRows { first, last } => {
+            let (first, last) = (first - 1, last - 1);
+            if last >= sheet.rows.len() {
+                bail!("La hoja {} solo tiene {} filas", sheet.name, sheet.rows.len());
+            }
+            sheet.rows.drain(first..=last);
+            let removed = last - first + 1;
+            remap_rows(sheet, |row| match row {
+                row if row < first => Some(row),
+                row if row > last => Some(row - removed),
+                _ => None,
+            });
+            Ok(if removed == 1 {
+                format!("Fila {} eliminada", first + 1)
+            } else {
+                format!("Filas {} a {} eliminadas", first + 1, last + 1)
+            })
+        }
+        StructureEdit::InsertColumn { before, header } => {
+            let at = resolve(sheet, before)?;
+            for (idx, row) in sheet.rows.iter_mut().enumerate() {
+                let value = match header {
+                    Some(header) if idx == 0 => CellValue::Text(header.clone()),
+                    _ => CellValue::Empty,
+                };
+                if row.len() >= at {
+                    row.insert(at, value);
+                } else if value != CellValue::Empty {
+                    row.resize(at, CellValue::Empty);
+                    row.push(value);
+                }
+            }
+            remap_columns(sheet, |col| Some(if col >= at { col + 1 } else { col }));
+            Ok(format!("Columna insertada en {}", excel::column_letters(at)))
+        }
+        StructureEdit::DeleteColumn(spec) => {
+            let col = resolve(sheet, spec)?;
+            let header = sheet.headers().get(col).cloned().unwrap_or_default();
+            delete_column(sheet, col);
+            Ok(format!("Columna {} eliminada{}", excel::column_letters(col), header_suffix(&header)))
+        }
+        StructureEdit::MoveColumn { column, to } => {
+            let from = resolve(sheet, column)?;
+            let to = resolve(sheet, to)?;
+            let header = sheet.headers().get(from).cloned().unwrap_or_default();
+            if from == to {
+                bail!("La columna ya está en {}", excel::column_letters(to));
+            }
+            move_column(sheet, from, to);
+            Ok(format!(
+                "Columna {}{} movida a {}",
+                excel::column_letters(from),
+                header_suffix(&header),
+                excel::column_letters(to)
+            ))
+        }
+    }
+}
+
+// Las mismas operaciones por índice (desde 0), para quien ya ha resuelto las
+// columnas y filas (`transformar`)
+pub fn delete_column(sheet: &mut SheetData, col: usize) {
+    for row in sheet.rows.iter_mut().filter(|row| row.len() > col) {
+        row.remove(col);
+    }
+    remap_columns(sheet, |other| match other {
+        other if other < col => Some(other),
+        other if other > col => Some(other - 1),
+        _ => None,
+    });
+}
+
+pub fn move_column(sheet: &mut SheetData, from: usize, to: usize) {
+    for row in sheet.rows.iter_mut() {
+        let original_len = row.len();
+        if row.len() <= from.max(to) {
+            row.resize(from.max(to) + 1, CellValue::Empty);
+        }
+        let value = row.remove(from);
+        row.insert(to, value);
+        // Las celdas añadidas solo para mover no alargan la fila
+        while row.len() > original_len && row.last() == Some(&CellValue::Empty) {
+            row.pop();
+        }
+    }
+    remap_columns(sheet, |col| Some(moved_index(col, from, to)));
+}
+
+// Quita las filas indicadas, no necesariamente seguidas
+pub fn delete_rows(sheet: &mut SheetData, rows: &BTreeSet<usize>) {
+    let mut new_index = Vec::with_capacity(sheet.rows.len());
+    let mut kept = 0;
+    for idx in 0..sheet.rows.len() {
+        if rows.contains(&idx) {
+            new_index.push(None);
+        } else {
+            new_index.push(Some(kept));
+            kept += 1;
+        }
+    }
+    let mut idx = 0;
+    sheet.rows.retain(|_| {
+        idx += 1;
+        !rows.contains(&(idx - 1))
+    });
+    remap_rows(sheet, |row| new_index.get(row).copied().unwrap_or(Some(row - (new_index.len() - kept))));
+}
+
+fn header_suffix(header: &str) -> String {
+    if header.trim().is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", header)
+    }
+}
+
+// Nueva posición de `col` cuando la columna `from` pasa a `to`
+fn moved_index(col: usize, from: usize, to: usize) -> usize {
+    match col {
+        col if col == from => to,
+        col if from < to && col > from && col <= to => col - 1,
+        col if to < from && col >= to && col < from => col + 1,
+        col => col,
+    }
+}
+
+// Los formatos, estilos, fórmulas, enlaces, notas, grupos, tablas y el diseño que dependen de la posición acompañan a
+// sus columnas; los de las eliminadas se descartan
+fn remap_columns(sheet: &mut SheetData, map: impl Fn(usize) -> Option<usize>) {
+    sheet.column_formats = std::mem::take(&mut sheet.column_formats)
+        .into_iter()
+        .filter_map(|(col, format)| Some((map(col)?, format)))
+        .collect();
+    sheet.formulas = std::mem::take(&mut sheet.formulas)
+        .into_iter()
+        .filter_map(|((row, col), formula)| Some(((row, map(col)?), formula)))
+        .collect();
+    sheet.hyperlinks = std::mem::take(&mut sheet.hyperlinks)
+        .into_iter()
+        .filter_map(|((row, col), target)| Some(((row, map(col)?), target)))
+        .collect();
+    sheet.notes = std::mem::take(&mut sheet.notes)
+        .into_iter()
+        .filter_map(|((row, col), note)| Some(((row, map(col)?), note)))
+        .collect();
+    sheet.outline.cols = std::mem::take(&mut sheet.outline.cols)
+        .into_iter()
+        .filter_map(|(col, level)| Some((map(col)?, level)))
+        .collect();
+    sheet.outline.hidden_cols = std::mem::take(&mut sheet.outline.hidden_cols).into_iter().filter_map(&map).collect();
+    sheet.rich_text = std::mem::take(&mut sheet.rich_text)
+        .into_iter()
+        .filter_map(|((row, col), runs)| Some(((row, map(col)?), runs)))
+        .collect();
+    sheet.styles = std::mem::take(&mut sheet.styles)
+        .into_iter()
+        .filter_map(|((row, col), style)| Some(((row, map(col)?), style)))
+        .collect();
+    let widths: BTreeMap<usize, f64> = std::mem::take(&mut sheet.layout.widths)
+        .into_iter()
+        .filter_map(|(col, width)| Some((map(col)?, width)))
+        .collect();
+    let hidden: BTreeSet<usize> = std::mem::take(&mut sheet.layout.hidden).into_iter().filter_map(&map).collect();
+    sheet.layout.widths = widths;
+    sheet.layout.hidden = hidden;
+    sheet.merges = std::mem::take(&mut sheet.merges)
+        .into_iter()
+        .filter_map(|mut merge| {
+            (merge.first_col, merge.last_col) = remap_span(merge.first_col, merge.last_col, &map)?;
+            Some(merge)
+        })
+        .filter(|merge| merge.first_row != merge.last_row || merge.first_col != merge.last_col)
+        .collect();
+    sheet.tables = std::mem::take(&mut sheet.tables)
+        .into_iter()
+        .filter_map(|mut table| {
+            (table.range.first_col, table.range.last_col) = remap_span(table.range.first_col, table.range.last_col, &map)?;
+            table.totals = table.totals.into_iter().filter_map(|(col, total)| Some((map(col)?, total))).collect();
+            Some(table)
+        })
+        .collect();
+}
+
+fn remap_rows(sheet: &mut SheetData, map: impl Fn(usize) -> Option<usize>) {
+    sheet.formulas = std::mem::take(&mut sheet.formulas)
+        .into_iter()
+        .filter_map(|((row, col), formula)| Some(((map(row)?, col), formula)))
+        .collect();
+    sheet.hyperlinks = std::mem::take(&mut sheet.hyperlinks)
+        .into_iter()
+        .filter_map(|((row, col), target)| Some(((map(row)?, col), target)))
+        .collect();
+    sheet.notes = std::mem::take(&mut sheet.notes)
+        .into_iter()
+        .filter_map(|((row, col), note)| Some(((map(row)?, col), note)))
+        .collect();
+    sheet.outline.rows = std::mem::take(&mut sheet.outline.rows)
+        .into_iter()
+        .filter_map(|(row, level)| Some((map(row)?, level)))
+        .collect();
+    sheet.outline.hidden_rows = std::mem::take(&mut sheet.outline.hidden_rows).into_iter().filter_map(&map).collect();
+    sheet.rich_text = std::mem::take(&mut sheet.rich_text)
+        .into_iter()
+        .filter_map(|((row, col), runs)| Some(((map(row)?, col), runs)))
+        .collect();
+    sheet.styles = std::mem::take(&mut sheet.styles)
+        .into_iter()
+        .filter_map(|((row, col), style)| Some(((map(row)?, col), style)))
+        .collect();
+    sheet.merges = std::mem::take(&mut sheet.merges)
+        .into_iter()
+        .filter_map(|mut merge| {
+            (merge.first_row, merge.last_row) = remap_span(merge.first_row, merge.last_row, &map)?;
+            Some(merge)
+        })
+        .filter(|merge| merge.first_row != merge.last_row || merge.first_col != merge.last_col)
+        .collect();
+    // Una tabla sin filas de datos deja de serlo
+    sheet.tables = std::mem::take(&mut sheet.tables)
+        .into_iter()
+        .filter_map(|mut table| {
+            (table.range.first_row, table.range.last_row) = remap_span(table.range.first_row, table.range.last_row, &map)?;
+            let minimum = usize::from(table.header_row) + usize::from(table.total_row);
+            (table.range.last_row - table.range.first_row >= minimum).then_some(table)
+        })
+        .collect();
+}
+
+// Un rango combinado crece con lo insertado dentro y encoge con lo eliminado; si
+// al mover una columna deja de ser contiguo en el mismo orden, se deshace
+fn remap_span(first: usize, last: usize, map: &impl Fn(usize) -> Option<usize>) -> Option<(usize, usize)> {
+    let mapped: Vec<usize> = (first..=last).filter_map(map).collect();
+    if mapped.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return None;
+    }
+    Some((*mapped.first()?, *mapped.last()?))
+}
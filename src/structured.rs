@@ -0,0 +1,469 @@
+// Respuestas estructuradas (`extraer_json`): se pide al modelo JSON con el modo de
+// salida estructurada del proveedor (`response_format`), se comprueba la respuesta
+// contra un esquema y, si no lo cumple, se le devuelven los errores para que la
+// corrija, hasta IAGENT_JSON_RETRIES veces. El esquema es un subconjunto de JSON
+// Schema (type, properties, required, additionalProperties, items, enum, minItems,
+// maxItems, minimum, maximum) o una lista de campos: `campos=producto,total:numero`
+// pide una lista de objetos con esos campos.
+use crate::backup;
+use crate::budget;
+use crate::config::Config;
+use crate::convert;
+use crate::error::IAgentError;
+use crate::events;
+use crate::excel::{self, SheetData, WorkbookData};
+use crate::llm::{self, Message};
+use crate::profiles;
+use crate::untrusted;
+use crate::usage::UsageTracker;
+use crate::verify;
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+
+const DEFAULT_RETRIES: usize = 2;
+// Clave con la que se envuelve una respuesta que no es un objeto: los modos JSON
+// de los proveedores solo admiten un objeto en la raíz
+const WRAPPER_KEY: &str = "resultado";
+// Errores de validación que se muestran y se devuelven al modelo
+const MAX_REPORTED_ERRORS: usize = 10;
+pub const DATA_SHEET: &str = "Datos";
+
+// Cómo se pide JSON al proveedor (IAGENT_JSON_MODE)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonMode {
+    // {"type": "json_object"}: lo admiten DeepSeek y la mayoría de APIs compatibles
+    #[default]
+    Object,
+    // {"type": "json_schema", ...}: el proveedor aplica el esquema (OpenAI y algunas pasarelas)
+    Schema,
+    // Sin response_format; solo las instrucciones y la validación
+    Off,
+}
+
+impl JsonMode {
+    pub fn parse(value: &str) -> Option<JsonMode> {
+        match value.trim().to_lowercase().as_str() {
+            "objeto" | "object" | "json_object" => Some(JsonMode::Object),
+            "esquema" | "schema" | "json_schema" => Some(JsonMode::Schema),
+            "no" | "off" | "ninguno" | "none" => Some(JsonMode::Off),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct JsonSettings {
+    pub mode: JsonMode,
+    // Reintentos cuando la respuesta no es JSON válido o no cumple el esquema
+    pub retries: usize,
+}
+
+impl JsonSettings {
+    // IAGENT_JSON_MODE / IAGENT_JSON_RETRIES
+    pub fn from_env() -> Result<JsonSettings> {
+        let mode = match profiles::var("IAGENT_JSON_MODE") {
+            Ok(value) => JsonMode::parse(&value).context(format!(
+                "Valor no válido en IAGENT_JSON_MODE: '{}' (usa objeto, esquema o no)",
+                value
+            ))?,
+            Err(_) => JsonMode::default(),
+        };
+        let retries = match profiles::var("IAGENT_JSON_RETRIES") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .context(format!("Valor no válido en IAGENT_JSON_RETRIES: '{}'", value))?,
+            Err(_) => DEFAULT_RETRIES,
+        };
+        Ok(JsonSettings { mode, retries })
+    }
+}
+
+// Esquema que debe cumplir la respuesta
+#[derive(Debug, Clone)]
+pub struct Schema(Value);
+
+impl Schema {
+    // Cualquier JSON válido
+    pub fn any() -> Schema {
+        Schema(json!({}))
+    }
+
+    pub fn load(path: &str) -> Result<Schema> {
+        let content = fs::read_to_string(path).context(format!("No se pudo leer el esquema {}", path))?;
+        let value: Value = serde_json::from_str(&content).context(format!("El esquema {} no es JSON válido", path))?;
+        if !value.is_object() {
+            bail!("El esquema {} debe ser un objeto JSON Schema", path);
+        }
+        Ok(Schema(value))
+    }
+
+    // "producto,total:numero,nota?": lista de objetos con esos campos; el tipo va
+    // tras ':' (texto por defecto) y '?' marca un campo opcional
+    pub fn from_fields(spec: &str) -> Result<Schema> {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for field in spec.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let (name, kind) = field.split_once(':').unwrap_or((field, "texto"));
+            let (name, optional) = match name.trim().strip_suffix('?') {
+                Some(name) => (name.trim(), true),
+                None => (name.trim(), false),
+            };
+            let kind = match kind.trim().to_lowercase().as_str() {
+                "texto" | "text" | "string" | "fecha" | "date" => "string",
+                "numero" | "número" | "number" => "number",
+                "entero" | "integer" => "integer",
+                "booleano" | "boolean" | "bool" => "boolean",
+                other => bail!("Tipo de campo desconocido: '{}' (usa texto, numero, entero, booleano o fecha)", other),
+            };
+            if name.is_empty() {
+                bail!("Hay un campo sin nombre en '{}'", spec);
+            }
+            // Los opcionales pueden venir a null
+            let kind = if optional { json!([kind, "null"]) } else { json!(kind) };
+            properties.insert(name.to_string(), json!({ "type": kind }));
+            if !optional {
+                required.push(name.to_string());
+            }
+        }
+        if properties.is_empty() {
+            bail!("campos= no contiene ningún campo");
+        }
+        Ok(Schema(json!({
+            "type": "array",
+            "items": { "type": "object", "properties": properties, "required": required }
+        })))
+    }
+
+    pub fn as_value(&self) -> &Value {
+        &self.0
+    }
+
+    fn is_object(&self) -> bool {
+        self.0.get("type").and_then(Value::as_str) == Some("object")
+    }
+
+    // Errores de `value` respecto al esquema, con la ruta de cada uno ($[2].total)
+    pub fn validate(&self, value: &Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        check(&self.0, value, "$", &mut errors);
+        errors
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(kind) => vec![kind.as_str()],
+            Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|kind| has_type(value, kind)) {
+            errors.push(format!("{}: se esperaba {} y hay {}", path, types.join(" o "), type_name(value)));
+            return;
+        }
+    }
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            errors.push(format!("{}: {} no es uno de {}", path, value, options.join(", ")));
+        }
+    }
+    match value {
+        Value::Object(fields) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !fields.contains_key(name) {
+                        errors.push(format!("{}: falta el campo '{}'", path, name));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, field) in fields {
+                match properties.and_then(|p| p.get(name)) {
+                    Some(field_schema) => check(field_schema, field, &format!("{}.{}", path, name), errors),
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        errors.push(format!("{}: el campo '{}' no está en el esquema", path, name))
+                    }
+                    None => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            let count = |key: &str| schema.get(key).and_then(Value::as_u64).map(|n| n as usize);
+            if let Some(min) = count("minItems").filter(|min| items.len() < *min) {
+                errors.push(format!("{}: hay {} elementos y se esperaban al menos {}", path, items.len(), min));
+            }
+            if let Some(max) = count("maxItems").filter(|max| items.len() > *max) {
+                errors.push(format!("{}: hay {} elementos y se esperaban como mucho {}", path, items.len(), max));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (idx, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}[{}]", path, idx), errors);
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64).filter(|min| number < *min) {
+                errors.push(format!("{}: {} es menor que el mínimo {}", path, number, min));
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64).filter(|max| number > *max) {
+                errors.push(format!("{}: {} es mayor que el máximo {}", path, number, max));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Object(_) => "un objeto",
+        Value::Array(_) => "una lista",
+        Value::String(_) => "un texto",
+        Value::Number(_) => "un número",
+        Value::Bool(_) => "un booleano",
+        Value::Null => "null",
+    }
+}
+
+// JSON de la respuesta, aunque venga en un bloque ```json o con texto alrededor
+pub fn parse_reply(text: &str) -> Result<Value, String> {
+    let text = text.trim();
+    let unfenced = text
+        .strip_prefix("```json")
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .unwrap_or(text)
+        .trim();
+    if let Ok(value) = serde_json::from_str(unfenced) {
+        return Ok(value);
+    }
+    let start = unfenced.find(['{', '[']);
+    let end = unfenced.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => {
+            serde_json::from_str(&unfenced[start..=end]).map_err(|e| format!("no es JSON válido ({})", e))
+        }
+        _ => Err("no contiene JSON".to_string()),
+    }
+}
+
+pub struct Extraction {
+    pub value: Value,
+    // Peticiones hechas hasta obtener una respuesta válida
+    pub attempts: usize,
+}
+
+// Pide al modelo una respuesta que cumpla `schema`, a continuación de `messages`
+pub async fn request(
+    client: &Client,
+    config: &Config,
+    usage_tracker: &mut UsageTracker,
+    mut messages: Vec<Message>,
+    schema: &Schema,
+) -> Result<Extraction> {
+    let settings = config.json;
+    let wrapped = settings.mode != JsonMode::Off && !schema.is_object();
+    let request_schema = if wrapped {
+        json!({ "type": "object", "properties": { WRAPPER_KEY: schema.as_value() }, "required": [WRAPPER_KEY] })
+    } else {
+        schema.as_value().clone()
+    };
+    let mut instructions = String::from(
+        "Responde solo con JSON válido, sin texto antes ni después y sin bloques de código.",
+    );
+    if request_schema != json!({}) {
+        instructions.push_str(&format!(" La respuesta debe cumplir este JSON Schema: {}", request_schema));
+    }
+    if wrapped {
+        instructions.push_str(&format!(" Pon el resultado en la clave \"{}\" de un objeto.", WRAPPER_KEY));
+    }
+    messages.insert(0, Message::new("system", instructions));
+
+    let mut response_format = match settings.mode {
+        JsonMode::Object => Some(json!({ "type": "json_object" })),
+        JsonMode::Schema => Some(json!({
+            "type": "json_schema",
+            "json_schema": { "name": "respuesta", "schema": request_schema }
+        })),
+        JsonMode::Off => None,
+    };
+    let attempts = settings.retries + 1;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let completion = match llm::get_json_response(client, config, &messages, response_format.as_ref()).await {
+            Ok(completion) => completion,
+            // Un proveedor que no conoce response_format suele responder 400; se sigue sin él
+            Err(e) if response_format.is_some() && rejects_response_format(&e) => {
+                events::notice("⚠️  El proveedor no admite response_format; se pide JSON solo con las instrucciones (IAGENT_JSON_MODE=no)");
+                response_format = None;
+                attempt -= 1;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        usage_tracker.record_completion(&config.provider_name(), &config.model, &completion);
+        let text = completion.message.content.unwrap_or_default();
+        let problems = match parse_reply(&text) {
+            Ok(value) => {
+                let value = match (wrapped, value) {
+                    (true, Value::Object(mut fields)) if fields.contains_key(WRAPPER_KEY) => {
+                        fields.remove(WRAPPER_KEY).unwrap_or_default()
+                    }
+                    (_, value) => value,
+                };
+                let errors = schema.validate(&value);
+                if errors.is_empty() {
+                    return Ok(Extraction { value, attempts: attempt });
+                }
+                errors
+            }
+            Err(problem) => vec![format!("la respuesta {}", problem)],
+        };
+        let shown: Vec<&str> = problems.iter().take(MAX_REPORTED_ERRORS).map(String::as_str).collect();
+        if attempt >= attempts {
+            bail!(
+                "La respuesta no cumple el esquema tras {} intentos: {}",
+                attempts,
+                shown.join("; ")
+            );
+        }
+        events::notice(format!("⚠️  Respuesta no válida (intento {} de {}): {}", attempt, attempts, shown.join("; ")));
+        messages.push(Message::new("assistant", text));
+        messages.push(Message::new(
+            "user",
+            format!(
+                "La respuesta no es válida: {}. Corrígela y responde solo con el JSON.",
+                shown.join("; ")
+            ),
+        ));
+    }
+}
+
+fn rejects_response_format(error: &anyhow::Error) -> bool {
+    matches!(
+        IAgentError::find(error),
+        Some(IAgentError::Api { status: 400 | 422, body, .. }) if body.contains("response_format")
+    )
+}
+
+// Opciones de `extraer_json`
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    pub file: String,
+    pub sheet: Option<String>,
+    pub instruction: String,
+    // Lista de campos (campos=) o archivo JSON Schema (esquema=); sin ninguno, cualquier JSON
+    pub fields: Option<String>,
+    pub schema_file: Option<String>,
+    pub output: Option<String>,
+}
+
+impl ExtractOptions {
+    pub fn schema(&self) -> Result<Schema> {
+        match (&self.fields, &self.schema_file) {
+            (Some(_), Some(_)) => bail!("Usa campos= o esquema=, no los dos"),
+            (Some(fields), None) => Schema::from_fields(fields),
+            (None, Some(path)) => Schema::load(path),
+            (None, None) => Ok(Schema::any()),
+        }
+    }
+}
+
+// Extrae de un libro el JSON que pide la instrucción
+pub async fn extract(
+    client: &Client,
+    config: &Config,
+    usage_tracker: &mut UsageTracker,
+    options: &ExtractOptions,
+) -> Result<Extraction> {
+    let schema = options.schema()?;
+    let data = excel::read_excel_file(&options.file)?;
+    let sheets: Vec<&SheetData> = match &options.sheet {
+        Some(name) => vec![data.require_sheet(&options.file, name)?],
+        None => data.sheets.iter().collect(),
+    };
+    let messages = vec![
+        untrusted::file_message(&format!(
+            "Datos del archivo Excel '{}':\n{}",
+            options.file,
+            sheets_text(&sheets, config.context_budget.per_item)
+        )),
+        Message::new("user", options.instruction.clone()),
+    ];
+    request(client, config, usage_tracker, messages, &schema).await
+}
+
+// Filas completas de las hojas (no un resumen): la extracción necesita todos los datos
+fn sheets_text(sheets: &[&SheetData], max_tokens: usize) -> String {
+    let mut text = String::new();
+    for sheet in sheets {
+        text.push_str(&format!("Hoja '{}':\n", sheet.name));
+        for row in &sheet.rows {
+            let cells: Vec<String> = row.iter().map(|cell| cell.to_string()).collect();
+            text.push_str(&cells.join(" | "));
+            text.push('\n');
+        }
+    }
+    budget::truncate_to_tokens(&text, max_tokens)
+}
+
+// Una lista de objetos como hoja: una columna por clave, en el orden en que aparecen
+pub fn records_sheet(value: &Value) -> Option<SheetData> {
+    let records: Vec<&Map<String, Value>> = value.as_array()?.iter().map(Value::as_object).collect::<Option<_>>()?;
+    let mut headers: Vec<&String> = Vec::new();
+    for record in &records {
+        for key in record.keys() {
+            if !headers.contains(&key) {
+                headers.push(key);
+            }
+        }
+    }
+    let mut sheet = SheetData::new(DATA_SHEET);
+    sheet.rows.push(headers.iter().map(|h| excel::CellValue::Text(h.to_string())).collect());
+    for record in records {
+        sheet.rows.push(
+            headers
+                .iter()
+                .map(|key| record.get(*key).map(convert::cell_from_json).unwrap_or(excel::CellValue::Empty))
+                .collect(),
+        );
+    }
+    Some(sheet)
+}
+
+// Guarda el resultado como .json, o como hoja si es una lista de objetos
+pub fn save(value: &Value, output: &str) -> Result<()> {
+    let path = Path::new(output);
+    let is_json = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    if is_json {
+        backup::before_write(path)?;
+        fs::write(path, serde_json::to_string_pretty(value)?).context(format!("No se pudo escribir {}", output))?;
+        verify::after_write(path)?;
+        return Ok(());
+    }
+    let sheet = records_sheet(value)
+        .context("Solo una lista de objetos se puede guardar como hoja; usa salida=<archivo.json>")?;
+    convert::write_any(path, &WorkbookData { sheets: vec![sheet] })
+}
@@ -0,0 +1,116 @@
+// Comandos mal escritos: `leer_exel datos.xlsx` o `read datos.xlsx` no llegan al
+// modelo (costaría una petición para una respuesta sin sentido). Si un solo
+// comando está a una letra de distancia, o la palabra es un sinónimo habitual, se
+// ejecuta ese comando; si hay varios parecidos o están más lejos, se sugieren.
+// Quien llama decide con los argumentos si la entrada era un comando o una
+// pregunta que empieza por una palabra parecida; en ese caso solo es una pista.
+use crate::i18n;
+
+// Palabras sueltas que casi siempre quieren decir un comando, cuando van seguidas
+// de un archivo
+const SYNONYMS: &[(&str, &str)] = &[
+    ("read", "leer_excel"),
+    ("open", "leer_excel"),
+    ("load", "leer_excel"),
+    ("leer", "leer_excel"),
+    ("abrir", "leer_excel"),
+    ("cargar", "leer_excel"),
+    ("write", "escribir_excel"),
+    ("escribir", "escribir_excel"),
+];
+
+// Sugerencias que se muestran como mucho
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Correction {
+    // Comando (en español) que se ejecuta en su lugar
+    Run(String),
+    // Comandos parecidos, del más al menos cercano
+    Suggest(Vec<String>),
+    // Comandos parecidos que no encajan con los argumentos: la entrada sigue como
+    // pregunta y solo se muestran
+    Hint(Vec<String>),
+}
+
+// Distancia de Levenshtein entre dos palabras, por caracteres, contando como un
+// solo cambio dos letras cruzadas ("slair" -> "salir"), el error más habitual
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut before_previous: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 0..a.len() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for j in 0..b.len() {
+            let substitution = previous[j] + usize::from(a[i] != b[j]);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                current[j + 1] = current[j + 1].min(before_previous[j - 1] + 1);
+            }
+        }
+        before_previous = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+// Corrección para la primera palabra de una entrada que no es un comando;
+// None si no se parece a ninguno
+pub fn correct(word: &str) -> Option<Correction> {
+    let word = word.to_lowercase();
+    if i18n::command_names().any(|name| name == word) {
+        return None;
+    }
+    if let Some((_, command)) = SYNONYMS.iter().find(|(synonym, _)| *synonym == word) {
+        return Some(Correction::Run(command.to_string()));
+    }
+    // Más de dos cambios, o más de un tercio de la palabra, ya es otra palabra
+    let limit = (word.chars().count() / 3).clamp(1, 2);
+    let mut close: Vec<(usize, &str)> = i18n::command_names()
+        .map(|name| (levenshtein(&word, name), i18n::spanish_command(name)))
+        .filter(|(distance, _)| *distance <= limit)
+        .collect();
+    close.sort();
+    close.dedup_by(|a, b| a.1 == b.1);
+    let best = close.first()?.0;
+    let mut names: Vec<String> = Vec::new();
+    for (_, name) in &close {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    let tied = close.iter().filter(|(distance, _)| *distance == best).count();
+    if best == 1 && tied == 1 {
+        return Some(Correction::Run(names.remove(0)));
+    }
+    names.truncate(MAX_SUGGESTIONS);
+    Some(Correction::Suggest(names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein_counts_edits() {
+        assert_eq!(levenshtein("salir", "salir"), 0);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("leer_exel", "leer_excel"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("año", "ano"), 1);
+    }
+
+    #[test]
+    fn swapped_letters_are_one_edit() {
+        assert_eq!(levenshtein("slair", "salir"), 1);
+        assert_eq!(levenshtein("ab", "ba"), 1);
+    }
+
+    #[test]
+    fn close_words_are_corrected_or_suggested() {
+        assert_eq!(correct("leer_exel"), Some(Correction::Run("leer_excel".to_string())));
+        assert_eq!(correct("read"), Some(Correction::Run("leer_excel".to_string())));
+        assert_eq!(correct("slair"), Some(Correction::Run("salir".to_string())));
+        assert_eq!(correct("leer_excel"), None);
+        assert_eq!(correct("clientes"), None);
+    }
+}
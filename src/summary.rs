@@ -0,0 +1,381 @@
+// Resúmenes de libros para el contexto del modelo. Cada hoja se resume con sus
+// encabezados, estadísticas por columna y una muestra de filas; la muestra se
+// reduce hasta que el resumen cabe en el presupuesto de tokens indicado.
+use crate::budget::{estimate_tokens, truncate_to_tokens};
+use crate::excel::{self, CellRange, CellValue, WorkbookData};
+use crate::formulas;
+use crate::header;
+use crate::hyperlinks;
+use crate::limits;
+use crate::merges;
+use crate::notes::{self, Note};
+use crate::outline::Outline;
+use crate::tables::{self, TableSpec};
+use anyhow::Result;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+// Filas candidatas que se guardan por hoja para la muestra
+const SAMPLE_POOL: usize = 10;
+// Tamaños de muestra que se prueban, de mayor a menor
+const SAMPLE_SIZES: &[usize] = &[10, 5, 3, 1, 0];
+// Valores distintos que se cuentan por columna de texto
+const DISTINCT_LIMIT: usize = 1_000;
+// Caracteres máximos por celda en las filas de muestra
+const CELL_CHARS: usize = 60;
+// Presupuesto mínimo por hoja cuando el libro tiene muchas
+const MIN_SHEET_TOKENS: usize = 80;
+
+// Estadísticas acumuladas de una columna
+#[derive(Debug, Default)]
+struct ColumnStats {
+    filled: usize,
+    numbers: usize,
+    dates: usize,
+    bools: usize,
+    min: f64,
+    max: f64,
+    sum: f64,
+    first_date: f64,
+    last_date: f64,
+    distinct: HashSet<String>,
+}
+
+impl ColumnStats {
+    fn add(&mut self, cell: &CellValue) {
+        match cell {
+            CellValue::Empty => return,
+            CellValue::Number(n) => {
+                if self.numbers == 0 {
+                    self.min = *n;
+                    self.max = *n;
+                }
+                self.numbers += 1;
+                self.min = self.min.min(*n);
+                self.max = self.max.max(*n);
+                self.sum += n;
+            }
+            CellValue::DateTime(serial) => {
+                if self.dates == 0 {
+                    self.first_date = *serial;
+                    self.last_date = *serial;
+                }
+                self.dates += 1;
+                self.first_date = self.first_date.min(*serial);
+                self.last_date = self.last_date.max(*serial);
+            }
+            CellValue::Bool(_) => self.bools += 1,
+            CellValue::Text(_) | CellValue::Error(_) => {}
+        }
+        self.filled += 1;
+        if self.distinct.len() <= DISTINCT_LIMIT {
+            self.distinct.insert(cell.to_string());
+        }
+    }
+
+    fn describe(&self, header: &str) -> String {
+        if self.filled == 0 {
+            return format!("{} (vacía)", header);
+        }
+        if self.numbers * 2 >= self.filled {
+            return format!(
+                "{} (número, {} valores, mín {}, máx {}, media {})",
+                header,
+                self.numbers,
+                format_number(self.min),
+                format_number(self.max),
+                format_number(self.sum / self.numbers as f64)
+            );
+        }
+        if self.dates * 2 >= self.filled {
+            let day = |serial: f64| excel::excel_serial_to_iso(serial).chars().take(10).collect::<String>();
+            return format!("{} (fecha, {} a {})", header, day(self.first_date), day(self.last_date));
+        }
+        if self.bools * 2 >= self.filled {
+            return format!("{} (booleano, {} valores)", header, self.filled);
+        }
+        let distinct = if self.distinct.len() > DISTINCT_LIMIT {
+            format!("más de {}", DISTINCT_LIMIT)
+        } else {
+            self.distinct.len().to_string()
+        };
+        format!("{} (texto, {} valores, {} distintos)", header, self.filled, distinct)
+    }
+}
+
+// Perfil de una hoja, calculado en una sola pasada sobre sus filas
+struct SheetProfile {
+    name: String,
+    total_rows: usize,
+    // La primera fila ya es de datos y las columnas se nombran por su letra
+    headerless: bool,
+    headers: Vec<String>,
+    stats: Vec<ColumnStats>,
+    // (número de fila en Excel, valores) de las filas candidatas a la muestra
+    samples: Vec<(usize, Vec<String>)>,
+    merges: Vec<CellRange>,
+    hyperlinks: BTreeMap<(usize, usize), String>,
+    notes: BTreeMap<(usize, usize), Note>,
+    outline: Outline,
+    tables: Vec<TableSpec>,
+    // Texto de las fórmulas, si se leyeron (`leer_excel ... --formulas`)
+    formulas: BTreeMap<(usize, usize), String>,
+    // Quedaron filas sin recorrer por IAGENT_MAX_SUMMARY_ROWS
+    limited: bool,
+}
+
+impl SheetProfile {
+    fn new(name: &str, headerless: bool) -> SheetProfile {
+        SheetProfile {
+            name: name.to_string(),
+            total_rows: 0,
+            headerless,
+            headers: Vec::new(),
+            stats: Vec::new(),
+            samples: Vec::new(),
+            merges: Vec::new(),
+            hyperlinks: BTreeMap::new(),
+            notes: BTreeMap::new(),
+            outline: Outline::default(),
+            tables: Vec::new(),
+            formulas: BTreeMap::new(),
+            limited: false,
+        }
+    }
+
+    // Añade una fila; `sample` indica si es candidata a la muestra
+    fn add_row(&mut self, row: &[CellValue], sample: bool) {
+        self.total_rows += 1;
+        if self.total_rows == 1 && !self.headerless {
+            self.headers = row.iter().enumerate().map(|(idx, c)| excel::header_name(idx, &c.to_string())).collect();
+            return;
+        }
+        if self.stats.len() < row.len() {
+            self.stats.resize_with(row.len(), ColumnStats::default);
+        }
+        for (stats, cell) in self.stats.iter_mut().zip(row) {
+            stats.add(cell);
+        }
+        if sample && row.iter().any(|c| *c != CellValue::Empty) {
+            let values = row.iter().map(|c| truncate_chars(&c.to_string(), CELL_CHARS)).collect();
+            self.samples.push((self.total_rows, values));
+        }
+    }
+
+    fn render(&self, sample_size: usize, with_stats: bool) -> String {
+        let mut summary = format!("Hoja: {} ({} filas)\n", self.name, self.total_rows);
+        if self.total_rows == 0 {
+            return summary;
+        }
+        if self.limited {
+            summary.push_str(&format!(
+                "Solo se han recorrido las primeras {} filas (IAGENT_MAX_SUMMARY_ROWS); las estadísticas no cubren el resto\n",
+                self.total_rows
+            ));
+        }
+        if self.headerless {
+            let columns: Vec<String> = (0..self.stats.len()).map(|idx| format!("Columna {}", excel::column_letters(idx))).collect();
+            summary.push_str(&format!("Sin encabezados (la fila 1 ya es de datos); columnas: {}\n", columns.join(", ")));
+        } else {
+            summary.push_str(&format!("Encabezados: {}\n", self.headers.join(", ")));
+        }
+        if !self.merges.is_empty() {
+            summary.push_str(&format!("Celdas combinadas: {}\n", merges::describe(&self.merges)));
+        }
+        if !self.hyperlinks.is_empty() {
+            summary.push_str(&format!("Enlaces: {}\n", hyperlinks::describe(&self.hyperlinks)));
+        }
+        if !self.tables.is_empty() {
+            summary.push_str(&format!("Tablas de Excel: {}\n", tables::describe(&self.tables)));
+        }
+        if !self.outline.rows.is_empty() || !self.outline.cols.is_empty() {
+            summary.push_str(&format!("Grupos plegables: {}\n", self.outline.describe()));
+        }
+        if !self.notes.is_empty() {
+            summary.push_str(&format!("Notas: {}\n", notes::describe(&self.notes)));
+        }
+        if !self.formulas.is_empty() {
+            summary.push_str(&format!("Fórmulas: {}\n", formulas::describe(&self.formulas)));
+        }
+        if with_stats && !self.stats.is_empty() {
+            let columns: Vec<String> = self
+                .stats
+                .iter()
+                .enumerate()
+                .map(|(idx, stats)| {
+                    let header = self
+                        .headers
+                        .get(idx)
+                        .filter(|h| !h.is_empty())
+                        .cloned()
+                        .unwrap_or_else(|| {
+                            if self.headerless {
+                                format!("Columna {}", excel::column_letters(idx))
+                            } else {
+                                excel::column_letters(idx)
+                            }
+                        });
+                    stats.describe(&header)
+                })
+                .collect();
+            summary.push_str(&format!("Columnas: {}\n", columns.join("; ")));
+        }
+        let chosen = spread(&self.samples, sample_size);
+        if !chosen.is_empty() {
+            let data_rows = self.total_rows - usize::from(!self.headerless);
+            summary.push_str(&format!("Filas de muestra ({} de {}):\n", chosen.len(), data_rows));
+            for (row_number, values) in chosen {
+                summary.push_str(&format!("  {}: {}\n", row_number, values.join(", ")));
+            }
+        }
+        summary
+    }
+
+    // La versión más completa que cabe en `max_tokens`; siempre conserva los encabezados
+    fn render_within(&self, max_tokens: usize) -> String {
+        for with_stats in [true, false] {
+            for &size in SAMPLE_SIZES {
+                let text = self.render(size, with_stats);
+                if estimate_tokens(&text) <= max_tokens {
+                    return text;
+                }
+            }
+        }
+        truncate_to_tokens(&self.render(0, false), max_tokens)
+    }
+}
+
+// Resumen de un libro en memoria que nunca supera `max_tokens`
+pub fn summarize_workbook(data: &WorkbookData, max_tokens: usize) -> String {
+    let sheet_budget = sheet_budget(max_tokens, data.sheets.len());
+    let mut summary = String::new();
+    for sheet in &data.sheets {
+        let mut profile = SheetProfile::new(&sheet.name, sheet.headerless);
+        let max_rows = limits::get().max_summary_rows.unwrap_or(usize::MAX);
+        profile.limited = sheet.rows.len() > max_rows;
+        let wanted = sample_rows(sheet.rows.len().min(max_rows), sheet.data_start());
+        for (idx, row) in sheet.rows.iter().take(max_rows).enumerate() {
+            profile.add_row(row, wanted.contains(&idx));
+        }
+        profile.merges = sheet.merges.clone();
+        profile.hyperlinks = sheet.hyperlinks.clone();
+        profile.notes = sheet.notes.clone();
+        profile.outline = sheet.outline.clone();
+        profile.tables = sheet.tables.clone();
+        profile.formulas = sheet.formulas.clone();
+        summary.push_str(&profile.render_within(sheet_budget));
+    }
+    truncate_to_tokens(&summary, max_tokens)
+}
+
+// Resumen de un libro grande recorriendo cada hoja por streaming; las
+// estadísticas cubren todas las filas y la muestra son las primeras.
+// Devuelve también una vista previa (encabezados y primeras filas).
+pub fn summarize_streaming(filename: &str, max_tokens: usize) -> Result<(WorkbookData, String)> {
+    limits::check_file_size(Path::new(filename))?;
+    let max_rows = limits::get().max_summary_rows.unwrap_or(usize::MAX);
+    let names = excel::sheet_names(filename)?;
+    let sheet_budget = sheet_budget(max_tokens, names.len());
+    let mut preview = WorkbookData::default();
+    let mut summary = String::new();
+    for name in names {
+        let mut rows = excel::stream_sheet(filename, &name)?;
+        // Las primeras filas deciden si hay encabezado antes de perfilar la hoja
+        let head = rows.by_ref().take(header::SAMPLE_ROWS + 1).collect::<Result<Vec<_>>>()?;
+        let mut sheet = excel::SheetData::new(&name);
+        sheet.headerless = !header::detect(&head);
+        let mut profile = SheetProfile::new(&name, sheet.headerless);
+        let mut all_rows = head.into_iter().map(Ok).chain(rows);
+        for row in all_rows.by_ref().take(max_rows) {
+            let row = row?;
+            let sample = profile.total_rows <= SAMPLE_POOL;
+            if sample {
+                sheet.rows.push(row.clone());
+            }
+            profile.add_row(&row, sample);
+        }
+        profile.limited = all_rows.next().is_some();
+        summary.push_str(&profile.render_within(sheet_budget));
+        preview.sheets.push(sheet);
+    }
+    Ok((preview, truncate_to_tokens(&summary, max_tokens)))
+}
+
+fn sheet_budget(max_tokens: usize, sheets: usize) -> usize {
+    (max_tokens / sheets.max(1)).max(MIN_SHEET_TOKENS).min(max_tokens)
+}
+
+// Índices de fila candidatos: los primeros, el último y el resto repartido;
+// `start` es la primera fila de datos
+fn sample_rows(total_rows: usize, start: usize) -> HashSet<usize> {
+    let data_rows = total_rows.saturating_sub(start);
+    if data_rows <= SAMPLE_POOL {
+        return (start..total_rows).collect();
+    }
+    let mut rows: HashSet<usize> = (start..start + 3).collect();
+    rows.insert(total_rows - 1);
+    let step = data_rows as f64 / (SAMPLE_POOL - 3) as f64;
+    for i in 1..SAMPLE_POOL - 3 {
+        rows.insert(start + (i as f64 * step) as usize);
+    }
+    rows
+}
+
+// Elige `count` elementos repartidos (siempre incluye el primero)
+fn spread<T: Clone>(items: &[T], count: usize) -> Vec<T> {
+    if count == 0 || items.is_empty() {
+        return Vec::new();
+    }
+    if items.len() <= count {
+        return items.to_vec();
+    }
+    (0..count)
+        .map(|i| items[i * (items.len() - 1) / (count - 1).max(1)].clone())
+        .collect()
+}
+
+pub fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+fn truncate_chars(value: &str, max_chars: usize) -> String {
+    if value.chars().count() <= max_chars {
+        return value.to_string();
+    }
+    let mut truncated: String = value.chars().take(max_chars).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::excel::SheetData;
+
+    fn sheet(rows: &[&[&str]]) -> WorkbookData {
+        WorkbookData { sheets: vec![SheetData::from_rows("Datos", rows)] }
+    }
+
+    #[test]
+    fn an_empty_header_is_named_by_its_column_letter() {
+        let data = sheet(&[&["Nombre", "Color", "Edad", ""], &["Ana", "rojo", "30", "x"]]);
+        let summary = summarize_workbook(&data, 2000);
+        assert!(summary.contains("Encabezados: Nombre, Color, Edad, D\n"), "{}", summary);
+        assert_eq!(data.sheets[0].headers(), ["Nombre", "Color", "Edad", "D"]);
+    }
+
+    #[test]
+    fn a_merged_header_is_reported_as_a_region_without_naming_its_columns() {
+        // Tras combinar_celdas A1:B1 solo A1 conserva el texto
+        let mut data = sheet(&[&["Nombre", "", "Edad"], &["Ana", "rojo", "30"]]);
+        data.sheets[0].merges.push(CellRange::parse("A1:B1").unwrap());
+        let summary = summarize_workbook(&data, 2000);
+        assert!(summary.contains("Encabezados: Nombre, B, Edad\n"), "{}", summary);
+        assert!(summary.contains("Celdas combinadas: A1:B1"), "{}", summary);
+        assert_eq!(data.sheets[0].headers(), ["Nombre", "B", "Edad"]);
+    }
+}
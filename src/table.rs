@@ -0,0 +1,155 @@
+// Renderizado de tablas alineadas para la terminal
+use crate::clipboard;
+use crate::pager;
+use crate::excel::SheetData;
+use crate::i18n::tr;
+use std::env;
+
+pub fn render_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let columns = rows
+        .iter()
+        .map(Vec::len)
+        .chain(std::iter::once(headers.len()))
+        .max()
+        .unwrap_or(0);
+    let mut widths = vec![0; columns];
+    for row in std::iter::once(headers).chain(rows.iter().map(Vec::as_slice)) {
+        for (idx, value) in row.iter().enumerate() {
+            widths[idx] = widths[idx].max(value.chars().count());
+        }
+    }
+
+    let format_row = |row: &[String]| -> String {
+        let cells: Vec<String> = (0..columns)
+            .map(|idx| {
+                let value = row.get(idx).map(String::as_str).unwrap_or("");
+                format!("{:<width$}", value, width = widths[idx])
+            })
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    };
+
+    let mut output = format_row(headers);
+    output.push('\n');
+    let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    output.push_str(&format!("|-{}-|", separator.join("-|-")));
+    for row in rows {
+        output.push('\n');
+        output.push_str(&format_row(row));
+    }
+    output
+}
+
+// Muestra una tabla en la terminal, por páginas si es larga, y la deja lista para
+// `copiar` y `siguiente`
+pub fn print_table(headers: &[String], rows: &[Vec<String>]) {
+    println!("{}", pager::show(headers, rows, false, None));
+}
+
+pub fn print_preview(sheet: &SheetData, n: usize) {
+    println!("{}", render_preview(sheet, n));
+    remember_preview(sheet, n);
+}
+
+// Filas de datos de una hoja (todas si no tiene encabezado) con los nombres de columna
+fn data_rows(sheet: &SheetData) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut rows = sheet.display_rows();
+    if !sheet.headerless && !rows.is_empty() {
+        rows.remove(0);
+    }
+    (sheet.headers(), rows)
+}
+
+// `mostrar`: las primeras `n` filas de una hoja, o todas, por páginas
+pub fn print_sheet(sheet: &SheetData, n: Option<usize>) {
+    let (headers, mut rows) = data_rows(sheet);
+    if rows.is_empty() && headers.is_empty() {
+        println!("{}", tr!("La hoja '{}' está vacía", "Sheet '{}' is empty", sheet.name));
+        return;
+    }
+    let total = rows.len();
+    if let Some(n) = n {
+        rows.truncate(n);
+    }
+    println!("{}", pager::show(&headers, &rows, true, Some(total)));
+}
+
+// Para `copiar`, las filas de la vista previa completas: sin recortar celdas ni columnas
+pub fn remember_preview(sheet: &SheetData, n: usize) {
+    let (headers, rows) = data_rows(sheet);
+    let shown: Vec<Vec<String>> = rows.into_iter().take(n).collect();
+    clipboard::remember_table(&headers, &shown);
+}
+
+// Ancho máximo de una celda en las vistas previas
+const MAX_CELL_WIDTH: usize = 30;
+const DEFAULT_TERMINAL_WIDTH: usize = 120;
+
+// Vista previa de las primeras `n` filas de datos de una hoja: recorta las celdas
+// largas y deja fuera las columnas que no caben en el ancho de la terminal
+pub fn render_preview(sheet: &SheetData, n: usize) -> String {
+    // Sin encabezado se muestran los nombres de columna y todas las filas; con
+    // encabezado, los de `headers` para nombrar también las columnas agrupadas
+    if sheet.rows.is_empty() {
+        return format!("La hoja '{}' está vacía", sheet.name);
+    }
+    let (headers, data) = data_rows(sheet);
+    let shown = &data[..n.min(data.len())];
+    let (mut output, visible, total_columns) = render_fitted(&headers, shown);
+    output.push_str(&format!("\n({} de {} filas", shown.len(), data.len()));
+    if visible < total_columns {
+        output.push_str(&format!(", {} de {} columnas", visible, total_columns));
+    }
+    output.push(')');
+    output
+}
+
+// Tabla con las celdas largas recortadas y solo las columnas que caben en la
+// terminal; devuelve también cuántas columnas se ven y cuántas hay
+pub fn render_fitted(headers: &[String], rows: &[Vec<String>]) -> (String, usize, usize) {
+    let shown: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|value| truncate_cell(value)).collect())
+        .collect();
+    let headers: Vec<String> = headers.iter().map(|value| truncate_cell(value)).collect();
+
+    // Cada columna ocupa su ancho más el separador " | "
+    let total_columns = shown.iter().map(Vec::len).chain([headers.len()]).max().unwrap_or(0);
+    let mut visible = 0;
+    let mut used = 4;
+    for idx in 0..total_columns {
+        let width = std::iter::once(&headers)
+            .chain(&shown)
+            .filter_map(|row| row.get(idx))
+            .map(|value| value.chars().count())
+            .max()
+            .unwrap_or(0);
+        if visible > 0 && used + width + 3 > terminal_width() {
+            break;
+        }
+        used += width + 3;
+        visible += 1;
+    }
+    let clip = |row: &Vec<String>| row.iter().take(visible).cloned().collect::<Vec<String>>();
+    let output = render_table(&clip(&headers), &shown.iter().map(clip).collect::<Vec<_>>());
+    (output, visible, total_columns)
+}
+
+fn truncate_cell(value: &str) -> String {
+    let value = value.replace(['\n', '\r'], " ");
+    if value.chars().count() <= MAX_CELL_WIDTH {
+        return value;
+    }
+    let mut cut: String = value.chars().take(MAX_CELL_WIDTH - 1).collect();
+    cut.push('…');
+    cut
+}
+
+// Ancho de la terminal según COLUMNS, o 120 si no está definido
+pub fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|width: &usize| *width >= 40)
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
@@ -0,0 +1,538 @@
+// Tablas de Excel (Insertar > Tabla): un rango con nombre, estilo, filas con
+// bandas, filtros en el encabezado y, si se pide, una fila de totales. Al leer un
+// libro se toman de sus xl/tables/tableN.xml a `SheetData::tables` y, al guardarlo,
+// rust_xlsxwriter las vuelve a escribir con add_table. `crear_tabla` añade una a un
+// libro existente editando el XML, para conservar el resto del libro.
+use crate::excel::{self, CellRange, CellValue, SheetData};
+use crate::merges;
+use crate::named_ranges;
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use rust_xlsxwriter::{Table, TableColumn, TableFunction, TableStyle};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const DEFAULT_STYLE: &str = "TableStyleMedium2";
+const TABLE_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.table+xml";
+
+// Estilos de tabla integrados, en el orden de la galería de Excel
+const LIGHT_STYLES: [TableStyle; 21] = [
+    TableStyle::Light1,
+    TableStyle::Light2,
+    TableStyle::Light3,
+    TableStyle::Light4,
+    TableStyle::Light5,
+    TableStyle::Light6,
+    TableStyle::Light7,
+    TableStyle::Light8,
+    TableStyle::Light9,
+    TableStyle::Light10,
+    TableStyle::Light11,
+    TableStyle::Light12,
+    TableStyle::Light13,
+    TableStyle::Light14,
+    TableStyle::Light15,
+    TableStyle::Light16,
+    TableStyle::Light17,
+    TableStyle::Light18,
+    TableStyle::Light19,
+    TableStyle::Light20,
+    TableStyle::Light21,
+];
+const MEDIUM_STYLES: [TableStyle; 28] = [
+    TableStyle::Medium1,
+    TableStyle::Medium2,
+    TableStyle::Medium3,
+    TableStyle::Medium4,
+    TableStyle::Medium5,
+    TableStyle::Medium6,
+    TableStyle::Medium7,
+    TableStyle::Medium8,
+    TableStyle::Medium9,
+    TableStyle::Medium10,
+    TableStyle::Medium11,
+    TableStyle::Medium12,
+    TableStyle::Medium13,
+    TableStyle::Medium14,
+    TableStyle::Medium15,
+    TableStyle::Medium16,
+    TableStyle::Medium17,
+    TableStyle::Medium18,
+    TableStyle::Medium19,
+    TableStyle::Medium20,
+    TableStyle::Medium21,
+    TableStyle::Medium22,
+    TableStyle::Medium23,
+    TableStyle::Medium24,
+    TableStyle::Medium25,
+    TableStyle::Medium26,
+    TableStyle::Medium27,
+    TableStyle::Medium28,
+];
+const DARK_STYLES: [TableStyle; 11] = [
+    TableStyle::Dark1,
+    TableStyle::Dark2,
+    TableStyle::Dark3,
+    TableStyle::Dark4,
+    TableStyle::Dark5,
+    TableStyle::Dark6,
+    TableStyle::Dark7,
+    TableStyle::Dark8,
+    TableStyle::Dark9,
+    TableStyle::Dark10,
+    TableStyle::Dark11,
+];
+
+// Funciones de la fila de totales: (nombre en el XML, código de SUBTOTAL)
+const TOTAL_FUNCTIONS: &[(&str, u8)] = &[
+    ("average", 101),
+    ("countNums", 102),
+    ("count", 103),
+    ("max", 104),
+    ("min", 105),
+    ("stdDev", 107),
+    ("sum", 109),
+    ("var", 110),
+];
+
+// Contenido de una celda de la fila de totales
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableTotal {
+    Label(String),
+    // Nombre de la función en el XML (sum, average, count...)
+    Function(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSpec {
+    pub name: String,
+    // Encabezado, datos y, si la hay, la fila de totales
+    pub range: CellRange,
+    // TableStyleMedium2...; `None` sin estilo
+    pub style: Option<String>,
+    pub header_row: bool,
+    pub total_row: bool,
+    pub banded_rows: bool,
+    pub banded_columns: bool,
+    pub first_column: bool,
+    pub last_column: bool,
+    pub autofilter: bool,
+    // Fila de totales por columna de la hoja
+    pub totals: BTreeMap<usize, TableTotal>,
+}
+
+impl TableSpec {
+    pub fn describe(&self) -> String {
+        let mut text = format!("{} ({}", self.name, self.range);
+        if let Some(style) = &self.style {
+            text.push_str(&format!(", {}", style));
+        }
+        if self.total_row {
+            text.push_str(", con totales");
+        }
+        text.push(')');
+        text
+    }
+}
+
+pub fn describe(tables: &[TableSpec]) -> String {
+    tables.iter().map(TableSpec::describe).collect::<Vec<_>>().join(", ")
+}
+
+// "medio9", "Medium9" o "TableStyleMedium9" -> "TableStyleMedium9"; "ninguno" -> None
+pub fn style_name(text: &str) -> Option<Option<String>> {
+    let lower = text.trim().to_lowercase();
+    if matches!(lower.as_str(), "ninguno" | "none" | "sin_estilo") {
+        return Some(None);
+    }
+    let lower = lower.strip_prefix("tablestyle").unwrap_or(&lower);
+    let (kind, number) = lower.split_at(lower.find(|c: char| c.is_ascii_digit())?);
+    let number: usize = number.parse().ok()?;
+    let (kind, count) = match kind {
+        "light" | "claro" => ("Light", LIGHT_STYLES.len()),
+        "medium" | "medio" => ("Medium", MEDIUM_STYLES.len()),
+        "dark" | "oscuro" => ("Dark", DARK_STYLES.len()),
+        _ => return None,
+    };
+    (1..=count).contains(&number).then(|| Some(format!("TableStyle{}{}", kind, number)))
+}
+
+// Un estilo personalizado del libro no se puede escribir: queda el de por defecto
+fn writer_style(style: Option<&str>) -> TableStyle {
+    let Some(style) = style else { return TableStyle::None };
+    let builtin = |prefix: &str, styles: &[TableStyle]| {
+        let number: usize = style.strip_prefix(prefix)?.parse().ok()?;
+        styles.get(number.checked_sub(1)?).copied()
+    };
+    builtin("TableStyleLight", &LIGHT_STYLES)
+        .or_else(|| builtin("TableStyleMedium", &MEDIUM_STYLES))
+        .or_else(|| builtin("TableStyleDark", &DARK_STYLES))
+        .unwrap_or(TableStyle::Medium2)
+}
+
+fn writer_function(name: &str) -> TableFunction {
+    match name {
+        "average" => TableFunction::Average,
+        "countNums" => TableFunction::CountNumbers,
+        "count" => TableFunction::Count,
+        "max" => TableFunction::Max,
+        "min" => TableFunction::Min,
+        "stdDev" => TableFunction::StdDev,
+        "sum" => TableFunction::Sum,
+        "var" => TableFunction::Var,
+        _ => TableFunction::None,
+    }
+}
+
+// Tabla declarada en un xl/tables/tableN.xml
+pub fn parse(table_xml: &str) -> Option<TableSpec> {
+    let tag = xlsx_patch::find_tags(table_xml, "table").into_iter().next()?;
+    let attr = |tag: &str, name: &str| xlsx_patch::xml_attr(tag, name).map(|value| xlsx_patch::xml_unescape(&value));
+    let range = CellRange::parse(&attr(&tag, "ref")?)?;
+    let mut totals = BTreeMap::new();
+    for (offset, column) in xlsx_patch::find_tags(table_xml, "tableColumn").iter().enumerate() {
+        let col = range.first_col + offset;
+        if let Some(label) = attr(column, "totalsRowLabel") {
+            totals.insert(col, TableTotal::Label(label));
+        } else if let Some(function) = attr(column, "totalsRowFunction").filter(|f| writer_function(f) != TableFunction::None) {
+            totals.insert(col, TableTotal::Function(function));
+        }
+    }
+    let info = xlsx_patch::find_tags(table_xml, "tableStyleInfo").into_iter().next();
+    let flag = |name: &str| info.as_deref().and_then(|info| attr(info, name)).is_some_and(|v| v == "1" || v == "true");
+    Some(TableSpec {
+        name: attr(&tag, "displayName").or_else(|| attr(&tag, "name"))?,
+        range,
+        style: info.as_deref().and_then(|info| attr(info, "name")),
+        header_row: attr(&tag, "headerRowCount").as_deref() != Some("0"),
+        total_row: attr(&tag, "totalsRowCount").is_some_and(|count| count != "0"),
+        banded_rows: flag("showRowStripes"),
+        banded_columns: flag("showColumnStripes"),
+        first_column: flag("showFirstColumn"),
+        last_column: flag("showLastColumn"),
+        autofilter: !xlsx_patch::find_tags(table_xml, "autoFilter").is_empty(),
+        totals,
+    })
+}
+
+// Nombres de las columnas: el texto del encabezado, o Columna<n> si está vacío o repetido
+fn column_names(sheet: &SheetData, spec: &TableSpec) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (offset, col) in (spec.range.first_col..=spec.range.last_col).enumerate() {
+        let header = match spec.header_row {
+            true => sheet.cell(spec.range.first_row, col).to_string().trim().to_string(),
+            false => String::new(),
+        };
+        let taken = |name: &str| names.iter().any(|existing| existing.eq_ignore_ascii_case(name));
+        let name = if header.is_empty() || taken(&header) { format!("Columna{}", offset + 1) } else { header };
+        names.push(name);
+    }
+    names
+}
+
+// Tabla para add_table al guardar un libro con rust_xlsxwriter
+pub fn writer_table(sheet: &SheetData, spec: &TableSpec) -> Table {
+    let columns: Vec<TableColumn> = column_names(sheet, spec)
+        .into_iter()
+        .zip(spec.range.first_col..)
+        .map(|(name, col)| {
+            let column = TableColumn::new().set_header(name);
+            match spec.totals.get(&col) {
+                Some(TableTotal::Label(label)) => column.set_total_label(label),
+                Some(TableTotal::Function(function)) => column.set_total_function(writer_function(function)),
+                None => column,
+            }
+        })
+        .collect();
+    let mut table = Table::new();
+    table
+        .set_name(&spec.name)
+        .set_style(writer_style(spec.style.as_deref()))
+        .set_header_row(spec.header_row)
+        .set_total_row(spec.total_row)
+        .set_banded_rows(spec.banded_rows)
+        .set_banded_columns(spec.banded_columns)
+        .set_first_column(spec.first_column)
+        .set_last_column(spec.last_column)
+        .set_autofilter(spec.autofilter && spec.header_row)
+        .set_columns(&columns);
+    table
+}
+
+fn table_xml(sheet: &SheetData, spec: &TableSpec, id: usize) -> String {
+    let name = xlsx_patch::xml_escape(&spec.name);
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<table xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" id=\"{}\" name=\"{}\" displayName=\"{}\" ref=\"{}\"",
+        id, name, name, spec.range
+    );
+    if spec.total_row {
+        xml.push_str(" totalsRowCount=\"1\"");
+    }
+    xml.push('>');
+    if spec.autofilter {
+        let filtered = CellRange {
+            last_row: spec.range.last_row - usize::from(spec.total_row),
+            ..spec.range
+        };
+        xml.push_str(&format!("<autoFilter ref=\"{}\"/>", filtered));
+    }
+    let names = column_names(sheet, spec);
+    xml.push_str(&format!("<tableColumns count=\"{}\">", names.len()));
+    for (idx, (name, col)) in names.iter().zip(spec.range.first_col..).enumerate() {
+        xml.push_str(&format!("<tableColumn id=\"{}\" name=\"{}\"", idx + 1, xlsx_patch::xml_escape(name)));
+        match spec.totals.get(&col) {
+            Some(TableTotal::Label(label)) => xml.push_str(&format!(" totalsRowLabel=\"{}\"", xlsx_patch::xml_escape(label))),
+            Some(TableTotal::Function(function)) => xml.push_str(&format!(" totalsRowFunction=\"{}\"", function)),
+            None => {}
+        }
+        xml.push_str("/>");
+    }
+    xml.push_str("</tableColumns>");
+    let flag = |value: bool| u8::from(value);
+    xml.push_str(&format!(
+        "<tableStyleInfo{} showFirstColumn=\"{}\" showLastColumn=\"{}\" showRowStripes=\"{}\" showColumnStripes=\"{}\"/></table>",
+        spec.style.as_deref().map(|style| format!(" name=\"{}\"", style)).unwrap_or_default(),
+        flag(spec.first_column),
+        flag(spec.last_column),
+        flag(spec.banded_rows),
+        flag(spec.banded_columns)
+    ));
+    xml
+}
+
+// Resultado que guarda en caché la fórmula SUBTOTAL de la fila de totales
+fn total_value(function: &str, cells: &[&CellValue]) -> CellValue {
+    let numbers: Vec<f64> = cells.iter().filter_map(|cell| cell.as_number()).collect();
+    let n = numbers.len() as f64;
+    let mean = numbers.iter().sum::<f64>() / n;
+    let variance = || numbers.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    let value = match function {
+        "count" => cells.iter().filter(|cell| ***cell != CellValue::Empty).count() as f64,
+        "countNums" => n,
+        _ if numbers.is_empty() => return CellValue::Empty,
+        "sum" => numbers.iter().sum(),
+        "average" => mean,
+        "max" => numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        "min" => numbers.iter().copied().fold(f64::INFINITY, f64::min),
+        _ if numbers.len() < 2 => return CellValue::Empty,
+        "stdDev" => variance().sqrt(),
+        "var" => variance(),
+        _ => return CellValue::Empty,
+    };
+    CellValue::Number(value)
+}
+
+// Nombre de columna dentro de una referencia Tabla[columna]: [ ] # y ' llevan ' delante
+fn structured_name(header: &str) -> String {
+    header
+        .chars()
+        .flat_map(|c| match c {
+            '[' | ']' | '#' | '\'' => vec!['\'', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+// Nombre válido para una tabla: empieza por letra o '_', sin espacios y sin
+// parecer una referencia de celda
+fn check_name(name: &str) -> Result<()> {
+    let valid = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        && name.chars().count() <= 255
+        && excel::parse_cell_ref(name).is_none();
+    if !valid {
+        bail!("'{}' no es un nombre de tabla válido: empieza por una letra, sin espacios ni aspecto de celda (A1)", name);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct TableOptions {
+    pub file: String,
+    pub sheet: String,
+    // Encabezado y datos; la fila de totales se añade debajo
+    pub range: CellRange,
+    // Como lo escribió el usuario (medio9, TableStyleLight1, ninguno...)
+    pub style: Option<String>,
+    pub name: Option<String>,
+    pub total_row: bool,
+    pub banded_rows: bool,
+    pub autofilter: bool,
+}
+
+pub struct TableOutcome {
+    pub sheet: String,
+    pub table: TableSpec,
+}
+
+// `crear_tabla <archivo> <hoja> <rango> [estilo]`, sin reescribir el resto del libro
+pub fn create(options: &TableOptions) -> Result<TableOutcome> {
+    let path = Path::new(&options.file);
+    let mut package = XlsxPackage::open(path)?;
+    let workbook = package.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+    let sheet_name = named_ranges::find_sheet(&workbook, Some(&options.sheet))?;
+    let data = excel::read_excel_file(&options.file)?;
+    let sheet = data.require_sheet(&options.file, &sheet_name)?;
+
+    let style = match &options.style {
+        None => Some(DEFAULT_STYLE.to_string()),
+        Some(text) => style_name(text).context(format!(
+            "Estilo de tabla no válido: {} (usa claro1-21, medio1-28, oscuro1-11 o ninguno)",
+            text
+        ))?,
+    };
+    let mut range = options.range;
+    if range.first_row == range.last_row {
+        bail!("{} tiene solo una fila; una tabla necesita el encabezado y al menos una fila de datos", range);
+    }
+    let headers: Vec<String> =
+        (range.first_col..=range.last_col).map(|col| sheet.cell(range.first_row, col).to_string().trim().to_string()).collect();
+    let empty: Vec<String> = headers
+        .iter()
+        .zip(range.first_col..)
+        .filter(|(header, _)| header.is_empty())
+        .map(|(_, col)| format!("{}{}", excel::column_letters(col), range.first_row + 1))
+        .collect();
+    if !empty.is_empty() {
+        bail!("La primera fila del rango es el encabezado de la tabla y tiene celdas vacías: {}", empty.join(", "));
+    }
+    if let Some((idx, header)) = headers
+        .iter()
+        .enumerate()
+        .find(|(idx, header)| headers[..*idx].iter().any(|other| other.eq_ignore_ascii_case(header)))
+    {
+        bail!("El encabezado '{}' de {}{} está repetido; cada columna de una tabla necesita un nombre distinto", header, excel::column_letters(range.first_col + idx), range.first_row + 1);
+    }
+
+    // Totales: suma en las columnas numéricas y "Total" en la primera si es de texto
+    let mut totals = BTreeMap::new();
+    if options.total_row {
+        let below = range.last_row + 1;
+        if let Some(col) = (range.first_col..=range.last_col).find(|col| *sheet.cell(below, *col) != CellValue::Empty) {
+            bail!(
+                "La fila de totales iría en la fila {}, pero {}{} tiene datos; deja vacía la fila bajo el rango",
+                below + 1,
+                excel::column_letters(col),
+                below + 1
+            );
+        }
+        for col in range.first_col..=range.last_col {
+            let numeric = (range.first_row + 1..=range.last_row).any(|row| matches!(sheet.cell(row, col), CellValue::Number(_)));
+            if numeric {
+                totals.insert(col, TableTotal::Function("sum".to_string()));
+            }
+        }
+        totals.entry(range.first_col).or_insert_with(|| TableTotal::Label("Total".to_string()));
+        range.last_row = below;
+    }
+
+    if let Some(existing) = sheet.tables.iter().find(|table| merges::overlaps(&table.range, &range)) {
+        bail!("{} se solapa con la tabla {}", range, existing.describe());
+    }
+    if let Some(merge) = sheet.merges.iter().find(|merge| merges::overlaps(merge, &range)) {
+        bail!("{} incluye las celdas combinadas {}; Excel no admite celdas combinadas en una tabla", range, merge);
+    }
+
+    let taken: Vec<&str> = data.sheets.iter().flat_map(|s| &s.tables).map(|table| table.name.as_str()).collect();
+    let defined = named_ranges::defined_names(&workbook);
+    let name = match &options.name {
+        Some(name) => {
+            check_name(name)?;
+            if taken.iter().any(|existing| existing.eq_ignore_ascii_case(name))
+                || defined.iter().any(|defined| defined.name.eq_ignore_ascii_case(name))
+            {
+                bail!("Ya existe una tabla o un nombre definido '{}' en {}", name, options.file);
+            }
+            name.clone()
+        }
+        None => (1..)
+            .map(|n| format!("Tabla{}", n))
+            .find(|name| {
+                !taken.iter().any(|existing| existing.eq_ignore_ascii_case(name))
+                    && !defined.iter().any(|defined| defined.name.eq_ignore_ascii_case(name))
+            })
+            .unwrap_or_default(),
+    };
+    let table = TableSpec {
+        name,
+        range,
+        style,
+        header_row: true,
+        total_row: options.total_row,
+        banded_rows: options.banded_rows,
+        banded_columns: false,
+        first_column: false,
+        last_column: false,
+        autofilter: options.autofilter,
+        totals,
+    };
+
+    // Los id de tabla son únicos en todo el libro
+    let id = package
+        .part_names()
+        .filter(|part| part.starts_with("xl/tables/"))
+        .filter_map(|part| package.read_part(part))
+        .filter_map(|xml| xlsx_patch::find_tags(&xml, "table").into_iter().next())
+        .filter_map(|tag| xlsx_patch::xml_attr(&tag, "id")?.parse::<usize>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let part = package.free_part("xl/tables/table", "xml");
+    let sheet_part = package.sheet_part(&sheet_name)?;
+    let (dir, file) = sheet_part.rsplit_once('/').context(format!("Ruta de hoja no válida: {}", sheet_part))?;
+    let rels_part = format!("{}/_rels/{}.rels", dir, file);
+    let rels = package.read_part(&rels_part).unwrap_or_else(|| xlsx_patch::EMPTY_RELS.to_string());
+    let (rels, rel_id) = xlsx_patch::add_relationship(&rels, xlsx_patch::TABLE_RELATIONSHIP, &part);
+
+    let mut xml = package.read_part(&sheet_part).context(format!("Falta la parte {}", sheet_part))?;
+    // El encabezado debe ser texto y coincidir con el nombre de cada columna
+    let header_cells: Vec<(usize, usize, String)> = (range.first_col..=range.last_col)
+        .filter(|col| !matches!(sheet.cell(range.first_row, *col), CellValue::Text(_)))
+        .filter_map(|col| {
+            let text = CellValue::Text(headers[col - range.first_col].clone());
+            Some((range.first_row, col, xlsx_patch::cell_content(&text)?))
+        })
+        .collect();
+    let total_cells: Vec<(usize, usize, String)> = table
+        .totals
+        .iter()
+        .filter_map(|(col, total)| {
+            let content = match total {
+                TableTotal::Label(label) => xlsx_patch::cell_content(&CellValue::Text(label.clone()))?,
+                TableTotal::Function(function) => {
+                    let code = TOTAL_FUNCTIONS.iter().find(|(name, _)| name == function)?.1;
+                    let cells: Vec<&CellValue> = (range.first_row + 1..range.last_row).map(|row| sheet.cell(row, *col)).collect();
+                    let formula = format!("SUBTOTAL({},{}[{}])", code, table.name, structured_name(&headers[col - range.first_col]));
+                    xlsx_patch::formula_content(&formula, &total_value(function, &cells))
+                }
+            };
+            Some((range.last_row, *col, content))
+        })
+        .collect();
+    xml = xlsx_patch::write_cells(&xml, &[header_cells, total_cells].concat())?;
+    package.drop_calc_chain();
+    let table_part = format!("<tablePart r:id=\"{}\"/>", rel_id);
+    xml = match xlsx_patch::find_tags(&xml, "tableParts").into_iter().next() {
+        Some(tag) => {
+            let count = xlsx_patch::find_tags(&xml, "tablePart").len() + 1;
+            let close = xml.find("</tableParts>").context("XML de hoja no válido")?;
+            let xml = format!("{}{}{}", &xml[..close], table_part, &xml[close..]);
+            xml.replacen(&tag, &format!("<tableParts count=\"{}\">", count), 1)
+        }
+        None => xlsx_patch::insert_worksheet_element(&xml, "tableParts", &format!("<tableParts count=\"1\">{}</tableParts>", table_part))?,
+    };
+    xml = xlsx_patch::with_relationships_namespace(&xml);
+
+    let types = package.read_part("[Content_Types].xml").context("Falta [Content_Types].xml")?;
+    let types = types.replacen(
+        "</Types>",
+        &format!("<Override PartName=\"/{}\" ContentType=\"{}\"/></Types>", part, TABLE_CONTENT_TYPE),
+        1,
+    );
+    package.write_part(&part, table_xml(sheet, &table, id));
+    package.write_part(&sheet_part, xml);
+    package.write_part(&rels_part, rels);
+    package.write_part("[Content_Types].xml", types);
+    package.save(path)?;
+    Ok(TableOutcome { sheet: sheet_name, table })
+}
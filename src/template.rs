@@ -0,0 +1,190 @@
+// Plantillas con campos entre llaves, compartidas por las preguntas de
+// para_cada_fila ("Clasifica: {Comentario} (cliente {Nombre})") y las fórmulas de
+// las plantillas de informe ("{Importe}*0.21"). `{{` y `}}` escriben una llave,
+// para pedir JSON en la pregunta o usar llaves en el texto. Los campos se comparan
+// sin distinguir mayúsculas ni espacios alrededor, y uno que no existe se explica
+// con los disponibles y el más parecido.
+use crate::suggest::levenshtein;
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Template> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.char_indices().peekable();
+        while let Some((pos, c)) = chars.next() {
+            match c {
+                '{' if chars.peek().is_some_and(|(_, next)| *next == '{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek().is_some_and(|(_, next)| *next == '}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let Some(end) = text[pos + 1..].find(['{', '}']).filter(|end| text[pos + 1 + end..].starts_with('}')) else {
+                        bail!("Falta '}}' para el campo que empieza en '{}' (usa {{{{ para escribir una llave)", excerpt(&text[pos..]));
+                    };
+                    let name = text[pos + 1..pos + 1 + end].trim();
+                    if name.is_empty() {
+                        bail!("Hay un campo vacío {{}} en la plantilla (usa {{{{}}}} para escribir llaves)");
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(name.to_string()));
+                    while chars.peek().is_some_and(|(next, _)| *next <= pos + 1 + end) {
+                        chars.next();
+                    }
+                }
+                '}' => bail!("Sobra '}}' en '{}' (usa }}}} para escribir una llave)", excerpt(&text[pos..])),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    // Nombres de los campos, sin repetir, en el orden en que aparecen
+    pub fn fields(&self) -> Vec<&str> {
+        let mut fields: Vec<&str> = Vec::new();
+        for part in &self.parts {
+            if let Part::Field(name) = part {
+                if !fields.iter().any(|f| f.eq_ignore_ascii_case(name)) {
+                    fields.push(name);
+                }
+            }
+        }
+        fields
+    }
+
+    // Cada campo debe ser una de las columnas (o un nombre de `extra`, como {fila});
+    // el error nombra los que faltan y la columna más parecida a cada uno
+    pub fn check(&self, columns: &[String], extra: &[&str]) -> Result<()> {
+        let known = |name: &str| {
+            columns.iter().any(|c| c.trim().eq_ignore_ascii_case(name)) || extra.iter().any(|e| e.eq_ignore_ascii_case(name))
+        };
+        let unknown: Vec<String> = self
+            .fields()
+            .into_iter()
+            .filter(|name| !known(name))
+            .map(|name| match closest(name, columns) {
+                Some(similar) => format!("{{{}}} (¿{}?)", name, similar),
+                None => format!("{{{}}}", name),
+            })
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        let mut available: Vec<String> = columns.iter().filter(|c| !c.trim().is_empty()).map(|c| c.trim().to_string()).collect();
+        available.extend(extra.iter().map(|e| e.to_string()));
+        bail!(
+            "{} {} no {} una columna (disponibles: {})",
+            if unknown.len() == 1 { "El campo" } else { "Los campos" },
+            unknown.join(", "),
+            if unknown.len() == 1 { "es" } else { "son" },
+            available.join(", ")
+        )
+    }
+
+    // El texto con cada campo sustituido por `value(nombre)`
+    pub fn render(&self, mut value: impl FnMut(&str) -> Result<String>) -> Result<String> {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => output.push_str(text),
+                Part::Field(name) => output.push_str(&value(name)?),
+            }
+        }
+        Ok(output)
+    }
+
+    // Con los valores de una fila, por encabezado; un campo sin celda queda vacío
+    pub fn render_row(&self, headers: &[String], cells: &[String]) -> String {
+        self.render(|name| {
+            Ok(headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name))
+                .and_then(|col| cells.get(col).cloned())
+                .unwrap_or_default())
+        })
+        .unwrap_or_default()
+    }
+}
+
+// Columna más parecida a un campo mal escrito, si está a dos cambios o menos
+fn closest<'a>(name: &str, columns: &'a [String]) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    columns
+        .iter()
+        .map(|column| (levenshtein(&name, &column.trim().to_lowercase()), column.trim()))
+        .filter(|(distance, column)| *distance <= 2 && !column.is_empty())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, column)| column)
+}
+
+fn excerpt(text: &str) -> String {
+    let mut excerpt: String = text.chars().take(20).collect();
+    if text.chars().count() > 20 {
+        excerpt.push('…');
+    }
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> Vec<String> {
+        ["Nombre", " Comentario ", "Importe"].iter().map(|h| h.to_string()).collect()
+    }
+
+    #[test]
+    fn fields_are_filled_from_the_row_by_header() {
+        let template = Template::parse("Clasifica: {comentario} (cliente { Nombre }, {Nombre}) como {{\"tipo\": \"...\"}}").unwrap();
+        assert_eq!(template.fields(), ["comentario", "Nombre"]);
+        let cells: Vec<String> = ["Ana", "Llegó tarde"].iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            template.render_row(&headers(), &cells),
+            "Clasifica: Llegó tarde (cliente Ana, Ana) como {\"tipo\": \"...\"}"
+        );
+        // Importe no tiene celda en la fila: queda vacío
+        assert_eq!(Template::parse("{Importe}*0.21").unwrap().render_row(&headers(), &cells), "*0.21");
+    }
+
+    #[test]
+    fn unknown_fields_name_the_closest_column() {
+        let template = Template::parse("{Nombr} {fila} {Precio}").unwrap();
+        assert!(Template::parse("{fila}").unwrap().check(&headers(), &["fila"]).is_ok());
+        let error = template.check(&headers(), &["fila"]).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Los campos {Nombr} (¿Nombre?), {Precio} no son una columna (disponibles: Nombre, Comentario, Importe, fila)"
+        );
+    }
+
+    #[test]
+    fn unbalanced_braces_are_errors_and_doubled_ones_are_text() {
+        assert!(Template::parse("{Nombre").unwrap_err().to_string().starts_with("Falta '}' para el campo que empieza en '{Nombre'"));
+        assert!(Template::parse("Nombre}").unwrap_err().to_string().starts_with("Sobra '}' en '}'"));
+        assert!(Template::parse("{ }").is_err());
+        assert!(Template::parse("{a{b}").is_err());
+        let template = Template::parse("{{}} sin campos").unwrap();
+        assert!(template.fields().is_empty());
+        assert_eq!(template.render(|_| bail!("no hay campos")).unwrap(), "{} sin campos");
+    }
+}
@@ -0,0 +1,196 @@
+// Gráficos en la terminal (`grafico_texto`), para revisar los datos antes de pedir
+// un gráfico de Excel: barras horizontales por categoría o una línea (sparkline)
+// con caracteres de bloque. Los valores de una misma categoría se suman, como en
+// un gráfico dinámico.
+use crate::analysis::{numeric_value, require_data_column};
+use crate::excel::{CellValue, SheetData};
+use crate::summary::format_number;
+use crate::table;
+use anyhow::{bail, Result};
+
+// Categorías que se dibujan como barras si no se indica max=
+pub const DEFAULT_MAX_BARS: usize = 30;
+// Longitud máxima de las etiquetas de las barras
+const MAX_LABEL_WIDTH: usize = 24;
+
+// Octavos de celda, del más fino al bloque entero
+const BAR_BLOCKS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextChartKind {
+    Bars,
+    Line,
+}
+
+impl TextChartKind {
+    pub fn parse(value: &str) -> Option<TextChartKind> {
+        match value.to_lowercase().as_str() {
+            "barras" | "bar" | "bars" => Some(TextChartKind::Bars),
+            "linea" | "línea" | "lineas" | "líneas" | "line" | "sparkline" => Some(TextChartKind::Line),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextChartOptions {
+    pub file: String,
+    pub sheet: Option<String>,
+    // Columna de las categorías o del eje X, y columna de los valores
+    pub x: String,
+    pub y: String,
+    pub kind: TextChartKind,
+    // Barras que se dibujan como mucho
+    pub max_bars: usize,
+}
+
+// Gráfico de la hoja, listo para imprimir
+pub fn render(sheet: &SheetData, options: &TextChartOptions) -> Result<String> {
+    let x = require_data_column(sheet, &options.x)?;
+    let y = require_data_column(sheet, &options.y)?;
+    let headers = sheet.headers();
+    let mut points: Vec<(String, f64)> = Vec::new();
+    let mut skipped = 0;
+    let mut grouped = false;
+    for row in sheet.data_rows() {
+        let label = row.get(x).filter(|cell| !matches!(cell, CellValue::Empty)).map(|cell| cell.to_string());
+        let (Some(label), Some(value)) = (label, row.get(y).and_then(numeric_value)) else {
+            skipped += 1;
+            continue;
+        };
+        match points.iter_mut().find(|(existing, _)| *existing == label) {
+            Some(point) => {
+                point.1 += value;
+                grouped = true;
+            }
+            None => points.push((label, value)),
+        }
+    }
+    if points.is_empty() {
+        bail!(
+            "La columna '{}' de la hoja '{}' no tiene valores numéricos que dibujar",
+            headers.get(y).map(String::as_str).unwrap_or(&options.y),
+            sheet.name
+        );
+    }
+
+    let x_name = headers.get(x).cloned().unwrap_or_else(|| options.x.clone());
+    let y_name = headers.get(y).cloned().unwrap_or_else(|| options.y.clone());
+    let title = if grouped {
+        format!("{}: suma de {} por {}", sheet.name, y_name, x_name)
+    } else {
+        format!("{}: {} por {}", sheet.name, y_name, x_name)
+    };
+    let mut output = format!("📊 {}\n\n", title);
+    match options.kind {
+        TextChartKind::Bars => output.push_str(&bars(&points, options.max_bars)),
+        TextChartKind::Line => output.push_str(&line(&points)),
+    }
+    if skipped > 0 {
+        output.push_str(&format!("\n({} filas sin categoría o sin valor numérico no se dibujan)", skipped));
+    }
+    Ok(output)
+}
+
+fn bars(points: &[(String, f64)], max_bars: usize) -> String {
+    let shown = &points[..points.len().min(max_bars.max(1))];
+    let labels: Vec<String> = shown.iter().map(|(label, _)| clip(label, MAX_LABEL_WIDTH)).collect();
+    let values: Vec<String> = shown.iter().map(|(_, value)| format_number(*value)).collect();
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let value_width = values.iter().map(|v| v.chars().count()).max().unwrap_or(0);
+    let bar_width = table::terminal_width().saturating_sub(label_width + value_width + 4).max(10);
+    // Los negativos se dibujan con sombreado, a la misma escala que los positivos
+    let scale = shown.iter().map(|(_, value)| value.abs()).fold(0.0, f64::max);
+    let mut output = String::new();
+    for ((label, value), (_, number)) in labels.iter().zip(&values).zip(shown) {
+        let eighths = if scale > 0.0 {
+            (number.abs() / scale * (bar_width * 8) as f64).round() as usize
+        } else {
+            0
+        };
+        let bar = if *number < 0.0 {
+            "░".repeat(eighths.div_ceil(8))
+        } else {
+            let mut bar = "█".repeat(eighths / 8);
+            if eighths % 8 > 0 {
+                bar.push(BAR_BLOCKS[eighths % 8 - 1]);
+            }
+            bar
+        };
+        output.push_str(&format!(
+            "{:<lw$} │{} {:>vw$}\n",
+            label,
+            bar,
+            value,
+            lw = label_width,
+            vw = value_width
+        ));
+    }
+    if points.len() > shown.len() {
+        output.push_str(&format!(
+            "… {} categorías más (usa max=<n> para ver más)\n",
+            points.len() - shown.len()
+        ));
+    }
+    if shown.iter().any(|(_, value)| *value < 0.0) {
+        output.push_str("(░ valores negativos)\n");
+    }
+    output.trim_end().to_string()
+}
+
+fn line(points: &[(String, f64)]) -> String {
+    let width = table::terminal_width().saturating_sub(4).max(10);
+    // Con más puntos que columnas, cada carácter es la media de un tramo
+    let values: Vec<f64> = if points.len() > width {
+        (0..width)
+            .map(|i| {
+                let (start, end) = (i * points.len() / width, ((i + 1) * points.len() / width).max(i * points.len() / width + 1));
+                points[start..end].iter().map(|(_, value)| value).sum::<f64>() / (end - start) as f64
+            })
+            .collect()
+    } else {
+        points.iter().map(|(_, value)| *value).collect()
+    };
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let spark: String = values
+        .iter()
+        .map(|value| {
+            let level = if max > min {
+                ((value - min) / (max - min) * 7.0).round() as usize
+            } else {
+                3
+            };
+            SPARK_BLOCKS[level.min(7)]
+        })
+        .collect();
+    let (first, last) = (&points[0], &points[points.len() - 1]);
+    let min_point = points.iter().min_by(|a, b| a.1.total_cmp(&b.1)).unwrap_or(first);
+    let max_point = points.iter().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap_or(first);
+    let first_label = clip(&first.0, width / 2);
+    let last_label = clip(&last.0, width / 2);
+    let gap = spark.chars().count().saturating_sub(first_label.chars().count() + last_label.chars().count()).max(1);
+    let mut output = format!("{}\n{}{}{}\n\n", spark, first_label, " ".repeat(gap), last_label);
+    output.push_str(&format!(
+        "{} puntos{} · inicio {} · fin {} · mínimo {} ({}) · máximo {} ({})",
+        points.len(),
+        if points.len() > values.len() { format!(" (promediados en {} columnas)", values.len()) } else { String::new() },
+        format_number(first.1),
+        format_number(last.1),
+        format_number(min_point.1),
+        clip(&min_point.0, MAX_LABEL_WIDTH),
+        format_number(max_point.1),
+        clip(&max_point.0, MAX_LABEL_WIDTH)
+    ));
+    output
+}
+
+fn clip(value: &str, max_chars: usize) -> String {
+    if value.chars().count() <= max_chars {
+        return value.to_string();
+    }
+    let mut clipped: String = value.chars().take(max_chars.saturating_sub(1)).collect();
+    clipped.push('…');
+    clipped
+}
@@ -0,0 +1,219 @@
+// Límite de peticiones a la API en el propio cliente, para que una tarea masiva
+// (para_cada_fila con concurrencia, preguntar_lote, los embeddings de una hoja
+// grande) no dispare la cuota del proveedor y deje bloqueada la clave con 429 para
+// toda la sesión. IAGENT_REQUESTS_PER_MINUTE e IAGENT_TOKENS_PER_MINUTE fijan el
+// máximo por minuto; cada proveedor (el host de su URL) tiene su propia ventana,
+// compartida por todas las operaciones en curso, y la petición que no cabe espera
+// su turno. Los tokens se estiman antes de enviar y se corrigen con los que
+// informa la API. Un perfil de iagent.toml puede dar los límites de su proveedor.
+use crate::events;
+use crate::profiles;
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimits {
+    pub requests_per_minute: Option<usize>,
+    pub tokens_per_minute: Option<usize>,
+}
+
+impl RateLimits {
+    pub fn from_env() -> Result<RateLimits> {
+        Ok(RateLimits {
+            requests_per_minute: number("IAGENT_REQUESTS_PER_MINUTE")?,
+            tokens_per_minute: number("IAGENT_TOKENS_PER_MINUTE")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests_per_minute.is_none() && self.tokens_per_minute.is_none()
+    }
+
+    // "60 peticiones y 90000 tokens por minuto"
+    pub fn describe(&self) -> String {
+        match (self.requests_per_minute, self.tokens_per_minute) {
+            (Some(requests), Some(tokens)) => format!("{} peticiones y {} tokens por minuto", requests, tokens),
+            (Some(requests), None) => format!("{} peticiones por minuto", requests),
+            (None, Some(tokens)) => format!("{} tokens por minuto", tokens),
+            (None, None) => "sin límite".to_string(),
+        }
+    }
+}
+
+// Un 0 o una variable vacía desactivan el límite
+fn number(variable: &str) -> Result<Option<usize>> {
+    match profiles::var(variable) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some(
+            value.trim().parse::<usize>().context(format!("Valor no válido en {}: '{}'", variable, value))?,
+        )
+        .filter(|n| *n > 0)),
+        Err(_) => Ok(None),
+    }
+}
+
+// Peticiones del último minuto a un proveedor
+struct Window {
+    provider: String,
+    // (hora, id, tokens) de cada petición, de la más antigua a la más reciente
+    sent: VecDeque<(Instant, u64, usize)>,
+    // Ya se ha avisado de la espera actual; así varias tareas esperando no repiten el aviso
+    notified: bool,
+}
+
+static WINDOWS: Mutex<Vec<Window>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// Petición admitida, para corregir sus tokens con settle
+pub struct Ticket {
+    provider: String,
+    id: u64,
+}
+
+// Espera hasta que una petición de ~`tokens` tokens a `url` cabe en los límites y
+// la anota; sin límites vuelve enseguida
+pub async fn acquire(limits: &RateLimits, url: &str, tokens: usize) -> Option<Ticket> {
+    if limits.is_empty() {
+        return None;
+    }
+    let provider = provider(url);
+    loop {
+        let (wait, notice) = {
+            let Ok(mut windows) = WINDOWS.lock() else { return None };
+            let idx = match windows.iter().position(|window| window.provider == provider) {
+                Some(idx) => idx,
+                None => {
+                    windows.push(Window {
+                        provider: provider.clone(),
+                        sent: VecDeque::new(),
+                        notified: false,
+                    });
+                    windows.len() - 1
+                }
+            };
+            let window = &mut windows[idx];
+            let now = Instant::now();
+            while window.sent.front().is_some_and(|(at, _, _)| now.duration_since(*at) >= WINDOW) {
+                window.sent.pop_front();
+            }
+            match wait_time(&window.sent, limits, tokens, now) {
+                None => {
+                    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+                    window.sent.push_back((now, id, tokens));
+                    window.notified = false;
+                    return Some(Ticket { provider, id });
+                }
+                Some(wait) => (wait, !std::mem::replace(&mut window.notified, true)),
+            }
+        };
+        if notice {
+            events::notice(format!(
+                "⏳ Límite de {} para {} alcanzado; la siguiente petición sale en {} s",
+                limits.describe(),
+                provider,
+                wait.as_secs().max(1)
+            ));
+        }
+        // Se vuelve a comprobar tras la espera: otra tarea puede haber ocupado el hueco
+        tokio::time::sleep(wait.max(Duration::from_millis(50))).await;
+    }
+}
+
+// Sustituye la estimación de una petición por los tokens que informó la API
+pub fn settle(ticket: Option<Ticket>, tokens: usize) {
+    let (Some(ticket), Ok(mut windows)) = (ticket, WINDOWS.lock()) else { return };
+    let Some(window) = windows.iter_mut().find(|window| window.provider == ticket.provider) else { return };
+    if let Some(entry) = window.sent.iter_mut().find(|(_, id, _)| *id == ticket.id) {
+        entry.2 = tokens;
+    }
+}
+
+// Cuánto falta para que quepa la petición; None si cabe ya
+fn wait_time(sent: &VecDeque<(Instant, u64, usize)>, limits: &RateLimits, tokens: usize, now: Instant) -> Option<Duration> {
+    let expires = |at: Instant| (at + WINDOW).saturating_duration_since(now);
+    let mut wait = Duration::ZERO;
+    if let Some(max) = limits.requests_per_minute {
+        if sent.len() >= max {
+            // Tienen que salir de la ventana las peticiones que sobran
+            wait = wait.max(expires(sent[sent.len() - max].0));
+        }
+    }
+    if let Some(max) = limits.tokens_per_minute {
+        // Una petición que supera el límite por sí sola sale cuando la ventana está vacía
+        let mut used: usize = sent.iter().map(|(_, _, tokens)| tokens).sum();
+        for (at, _, freed) in sent {
+            if used + tokens <= max {
+                break;
+            }
+            used -= freed;
+            wait = wait.max(expires(*at));
+        }
+    }
+    Some(wait).filter(|wait| !wait.is_zero())
+}
+
+// Host de la URL de la API: "api.deepseek.com"
+fn provider(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?']).next().unwrap_or(rest).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(requests: Option<usize>, tokens: Option<usize>) -> RateLimits {
+        RateLimits {
+            requests_per_minute: requests,
+            tokens_per_minute: tokens,
+        }
+    }
+
+    // Peticiones enviadas hace tantos segundos antes de `now`, con sus tokens
+    fn sent(now: Instant, requests: &[(u64, usize)]) -> VecDeque<(Instant, u64, usize)> {
+        requests
+            .iter()
+            .enumerate()
+            .map(|(id, (ago, tokens))| (now - Duration::from_secs(*ago), id as u64, *tokens))
+            .collect()
+    }
+
+    #[test]
+    fn a_request_over_the_whole_token_budget_waits_for_an_empty_window() {
+        let now = Instant::now() + WINDOW;
+        let limits = limits(None, Some(100));
+        assert_eq!(wait_time(&VecDeque::new(), &limits, 150, now), None);
+        let window = sent(now, &[(40, 20), (10, 30)]);
+        assert_eq!(wait_time(&window, &limits, 150, now), Some(Duration::from_secs(50)));
+        // Una que cabe solo espera a que salga la más antigua
+        assert_eq!(wait_time(&window, &limits, 70, now), Some(Duration::from_secs(20)));
+        assert_eq!(wait_time(&window, &limits, 50, now), None);
+    }
+
+    #[test]
+    fn requests_leave_the_window_after_a_minute() {
+        let now = Instant::now() + WINDOW * 2;
+        let limits = limits(Some(2), None);
+        assert_eq!(wait_time(&sent(now, &[(59, 1), (30, 1)]), &limits, 1, now), Some(Duration::from_secs(1)));
+        assert_eq!(wait_time(&sent(now, &[(60, 1), (30, 1)]), &limits, 1, now), None);
+        assert_eq!(wait_time(&sent(now, &[(90, 1), (61, 1)]), &limits, 1, now), None);
+        assert_eq!(wait_time(&sent(now, &[(50, 1), (30, 1), (5, 1)]), &limits, 1, now), Some(Duration::from_secs(30)));
+    }
+
+    #[tokio::test]
+    async fn settle_replaces_the_estimate_with_the_reported_tokens() {
+        let limits = limits(None, Some(100));
+        let url = "https://settle.prueba/v1/chat/completions";
+        let ticket = acquire(&limits, url, 90).await;
+        assert!(ticket.is_some());
+        // Con la estimación de 90 la siguiente tendría que esperar un minuto
+        settle(ticket, 10);
+        let next = tokio::time::timeout(Duration::from_secs(1), acquire(&limits, url, 80)).await;
+        assert!(next.is_ok_and(|ticket| ticket.is_some()));
+    }
+}
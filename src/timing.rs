@@ -0,0 +1,91 @@
+// Tiempos de ejecución de los comandos y de las herramientas de la sesión
+use std::time::Duration;
+
+// Operaciones más lentas que se listan en el informe
+const SLOWEST_SHOWN: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct OperationTiming {
+    // Comando o herramienta (p. ej. "leer_excel" o "herramienta agregar")
+    pub name: String,
+    // Detalle de la operación concreta, normalmente sus argumentos
+    pub detail: String,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct Timings {
+    entries: Vec<OperationTiming>,
+}
+
+impl Timings {
+    pub fn record(&mut self, name: &str, detail: &str, duration: Duration) {
+        self.entries.push(OperationTiming {
+            name: name.to_string(),
+            detail: detail.to_string(),
+            duration,
+        });
+    }
+
+    // Informe con los totales por operación y las ejecuciones más lentas
+    pub fn report(&self) -> String {
+        if self.entries.is_empty() {
+            return "No se ha ejecutado ninguna operación en esta sesión.".to_string();
+        }
+        // (nombre, ejecuciones, total, máximo) en orden de primera aparición
+        let mut totals: Vec<(&str, usize, Duration, Duration)> = Vec::new();
+        for entry in &self.entries {
+            match totals.iter_mut().find(|(name, ..)| *name == entry.name) {
+                Some(total) => {
+                    total.1 += 1;
+                    total.2 += entry.duration;
+                    total.3 = total.3.max(entry.duration);
+                }
+                None => totals.push((&entry.name, 1, entry.duration, entry.duration)),
+            }
+        }
+        totals.sort_by_key(|total| std::cmp::Reverse(total.2));
+
+        let mut report = String::from("Rendimiento de la sesión (por tiempo total):\n");
+        for (name, count, total, max) in &totals {
+            report.push_str(&format!(
+                "  {}: {} ejecuciones, total {}, media {}, máximo {}\n",
+                name,
+                count,
+                format_duration(*total),
+                format_duration(*total / *count as u32),
+                format_duration(*max)
+            ));
+        }
+
+        let mut slowest: Vec<&OperationTiming> = self.entries.iter().collect();
+        slowest.sort_by_key(|entry| std::cmp::Reverse(entry.duration));
+        report.push_str("Operaciones más lentas:\n");
+        for entry in slowest.into_iter().take(SLOWEST_SHOWN) {
+            report.push_str(&format!(
+                "  {} {} {}\n",
+                format_duration(entry.duration),
+                entry.name,
+                truncate(&entry.detail, 80)
+            ));
+        }
+        report.trim_end().to_string()
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() >= 1 {
+        format!("{:.2} s", duration.as_secs_f64())
+    } else {
+        format!("{} ms", duration.as_millis())
+    }
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    if value.chars().count() <= max_chars {
+        return value.to_string();
+    }
+    let mut truncated: String = value.chars().take(max_chars).collect();
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,847 @@
+// Herramientas que el modelo puede invocar mediante function calling
+use crate::analysis::{self, RankOptions, StatsOptions};
+use crate::chunks;
+use crate::conditional_format::{self, ConditionalFormatOptions};
+use crate::convert;
+use crate::export::{self, Converter, ExportOptions};
+use crate::excel::{self, CellRange, CellValue, ChartKind, ChartSpec, SheetData};
+use crate::formula_check;
+use crate::hyperlinks::{self, LinkOptions};
+use crate::layout::{self, LayoutOptions, LayoutSpec};
+use crate::merges;
+use crate::metadata::WorkbookMetadata;
+use crate::named_ranges::{self, SpillPolicy};
+use crate::notes::{self, Note, NoteAction, NoteOptions};
+use crate::protection::{self, ProtectOptions};
+use crate::sandbox::{Access, Workspace};
+use crate::search::{self, Matcher};
+use crate::summary;
+use crate::duplicates::{self, DuplicateOptions};
+use crate::join::{self, JoinOptions};
+use crate::table;
+use crate::transform;
+use crate::validation::{self, Validation, ValidationOptions};
+use crate::xlsx_patch::XlsxPackage;
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::path::Path;
+
+// Esquemas de las herramientas en el formato de la API (compatible con OpenAI)
+pub fn definitions() -> Value {
+    json!([
+        {
+            "type": "function",
+            "function": {
+                "name": "leer_excel",
+                "description": "Lee un archivo Excel y devuelve un resumen de sus hojas (encabezados, número de filas y primeras filas), sus dimensiones, propiedades del documento y nombres definidos, y la lista de fragmentos de filas que se pueden pedir con leer_fragmento.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" }
+                    },
+                    "required": ["archivo"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "leer_fragmento",
+                "description": "Devuelve las filas completas de un fragmento de una hoja (bloques de 50 filas de datos con id <hoja>#<n>, listados al leer el libro). Úsala cuando la muestra del resumen no basta y necesitas ver más filas.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "fragmento": { "type": "string", "description": "Id del fragmento, p. ej. Ventas#3" },
+                        "desde": { "type": "integer", "description": "Fila de Excel del fragmento desde la que seguir, cuando el resultado anterior no cupo entero" }
+                    },
+                    "required": ["archivo", "fragmento"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "agregar",
+                "description": "Ordena las filas de una hoja por una columna numérica o suma esa columna por grupos y devuelve las N primeras. Opcionalmente guarda el resultado como hoja.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Nombre de la hoja" },
+                        "por": { "type": "string", "description": "Columna numérica (encabezado, letra o número)" },
+                        "agrupado_por": { "type": "string", "description": "Columna por la que agrupar y sumar" },
+                        "n": { "type": "integer", "description": "Número de filas del resultado (por defecto 10)" },
+                        "orden": { "type": "string", "enum": ["desc", "asc"] },
+                        "salida": { "type": "string", "description": "Archivo .xlsx donde guardar el resultado" }
+                    },
+                    "required": ["archivo", "hoja", "por"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "estadisticas",
+                "description": "Calcula de forma exacta el recuento, la suma, la media, la mediana, la desviación típica, el mínimo, los cuartiles y el máximo de columnas numéricas. Úsala en lugar de hacer las cuentas tú.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Nombre de la hoja; por defecto la primera" },
+                        "columnas": { "type": "array", "items": { "type": "string" }, "description": "Columnas numéricas (encabezado, letra o número)" }
+                    },
+                    "required": ["archivo", "columnas"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "escribir_hoja",
+                "description": "Escribe (o reemplaza) una hoja completa en un archivo xlsx, creándolo si no existe. La primera fila deben ser los encabezados. En los textos, **así** escribe un fragmento en negrita.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Nombre de la hoja" },
+                        "filas": {
+                            "type": "array",
+                            "items": { "type": "array", "items": { "type": ["string", "number", "boolean", "null"] } },
+                            "description": "Filas de la hoja"
+                        },
+                        "diseño": {
+                            "type": "object",
+                            "description": "Opcional: {\"congelar\": 1 o \"B2\", \"anchos\": {\"A\": 20}, \"autoajustar\": true, \"ocultar\": [\"C\"]}, como en ajustar_hoja"
+                        },
+                        "validaciones": {
+                            "type": "array",
+                            "items": { "type": "object" },
+                            "description": "Opcional: validaciones de datos como en la herramienta validar, cada una con su \"rango\" en esta hoja, p. ej. {\"rango\": \"C2:C100\", \"tipo\": \"lista\", \"valores\": [\"Alta\", \"Media\", \"Baja\"]}"
+                        },
+                        "enlaces": {
+                            "type": "object",
+                            "description": "Opcional: hipervínculos por celda, p. ej. {\"A2\": \"https://...\", \"B5\": \"datos/ventas.xlsx\", \"C1\": \"#Resumen!A1\"}; el texto visible es el de la celda"
+                        },
+                        "notas": {
+                            "type": "object",
+                            "description": "Opcional: notas por celda que explican los valores calculados, p. ej. {\"E2\": \"Suma de Q1 a Q4\"}"
+                        }
+                    },
+                    "required": ["archivo", "hoja", "filas"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "ajustar_hoja",
+                "description": "Cambia el diseño de una hoja de un xlsx existente sin tocar sus datos: inmoviliza filas o columnas, fija anchos, autoajusta las columnas al contenido u oculta columnas.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Nombre de la hoja" },
+                        "congelar": { "type": ["integer", "string"], "description": "Filas superiores a inmovilizar (1 = encabezado), o la primera celda que se desplaza (B2 inmoviliza la fila 1 y la columna A); 0 quita la inmovilización" },
+                        "anchos": { "type": "object", "description": "Ancho en caracteres por columna (letra o encabezado), p. ej. {\"A\": 20, \"Total\": 12}" },
+                        "autoajustar": { "type": "boolean", "description": "Ajusta el ancho de cada columna a su contenido" },
+                        "ocultar": { "type": "array", "items": { "type": "string" }, "description": "Columnas a ocultar (letra o encabezado)" }
+                    },
+                    "required": ["archivo", "hoja"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "proteger",
+                "description": "Protege una hoja de un xlsx existente para usarla como plantilla: las celdas con fórmula quedan bloqueadas y el resto (o solo los rangos editables indicados) se puede seguir rellenando. Formatos, fórmulas y gráficos se conservan.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Nombre de la hoja" },
+                        "contraseña": { "type": "string", "description": "Contraseña para desproteger la hoja; sin ella se desprotege desde Excel sin pedirla" },
+                        "editables": { "type": "array", "items": { "type": "string" }, "description": "Rangos que quedan editables, p. ej. [\"B2:B20\", \"D2\"]; por defecto todas las celdas sin fórmula" }
+                    },
+                    "required": ["archivo", "hoja"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "escribir_rango",
+                "description": "Escribe valores en un rango de un xlsx existente sin tocar el resto del libro (formatos y fórmulas se conservan). El destino puede ser un nombre definido de la plantilla o una referencia Hoja!A1:B2; los valores empiezan en su esquina superior izquierda. Si el bloque cae sobre celdas con datos no se escribe y el error indica dónde cabría: repite con desplazar o, solo si el usuario quiere reemplazarlos, con sobrescribir.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "destino": { "type": "string", "description": "Nombre definido (p. ej. TotalFactura) o referencia como 'Hoja 1'!B2:C4" },
+                        "valores": {
+                            "type": "array",
+                            "items": { "type": "array", "items": { "type": ["string", "number", "boolean", "null"] } },
+                            "description": "Filas de valores; una sola celda es [[valor]]"
+                        },
+                        "desplazar": { "type": "string", "enum": ["abajo", "derecha"], "description": "Si el destino tiene datos, mueve el bloque a la primera posición libre hacia abajo o a la derecha" },
+                        "sobrescribir": { "type": "boolean", "description": "Reemplaza las celdas con datos del destino" }
+                    },
+                    "required": ["archivo", "destino", "valores"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "escribir_formula",
+                "description": "Escribe una fórmula de Excel en una celda de un xlsx existente, o la rellena en un rango como al arrastrarla (las referencias sin $ se mueven). Antes se comprueba: paréntesis y comillas cerradas, referencias A1 válidas a hojas que existen y nombres de función en inglés con ',' entre argumentos (SUM, IF, VLOOKUP; no SUMA ni ';'). Si no es válida no se escribe nada y el error dice qué corregir. Devuelve el valor calculado cuando se puede.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "destino": { "type": "string", "description": "Celda o rango como Ventas!C2 o Ventas!C2:C20, o un nombre definido" },
+                        "formula": { "type": "string", "description": "Fórmula para la primera celda, p. ej. =B2*1.21 o =SUMIF(A:A,\"Norte\",B:B)" },
+                        "sobrescribir": { "type": "boolean", "description": "Reemplaza las celdas del destino que ya tienen datos" }
+                    },
+                    "required": ["archivo", "destino", "formula"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "escribir_enlace",
+                "description": "Escribe un hipervínculo en una celda de un xlsx, creándolo si no existe: una URL, la ruta de un archivo (p. ej. el libro de origen) o una celda del libro (#Hoja!A1).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "celda": { "type": "string", "description": "Celda como B2 (primera hoja) o 'Hoja 1'!B2" },
+                        "destino": { "type": "string", "description": "URL, ruta de archivo o #Hoja!A1" },
+                        "texto": { "type": "string", "description": "Opcional: texto visible; por defecto el de la celda o el destino" }
+                    },
+                    "required": ["archivo", "celda", "destino"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "escribir_nota",
+                "description": "Escribe la nota (comentario) de una celda de un xlsx existente sin tocar el resto del libro, p. ej. para explicar cómo se calculó un valor generado: 'calculado como suma de Q1-Q4'. Sustituye la nota que tuviera.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "celda": { "type": "string", "description": "Celda como B2 (primera hoja) o 'Hoja 1'!B2" },
+                        "texto": { "type": "string", "description": "Texto de la nota" }
+                    },
+                    "required": ["archivo", "celda", "texto"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "combinar_celdas",
+                "description": "Combina un rango de celdas de un xlsx existente (títulos de informe, encabezados agrupados) sin tocar el resto del libro. Solo se conserva el valor de la celda superior izquierda.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "rango": { "type": "string", "description": "Referencia como 'Hoja 1'!A1:D1 o un nombre definido" }
+                    },
+                    "required": ["archivo", "rango"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "buscar_duplicados",
+                "description": "Busca filas duplicadas en una hoja (iguales en todas sus celdas o en las columnas indicadas) y, con eliminar, escribe una copia sin ellas conservando la primera de cada grupo.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo xlsx o csv (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Opcional: hoja; por defecto la primera" },
+                        "columnas": { "type": "array", "items": { "type": "string" }, "description": "Opcional: columnas que forman la clave" },
+                        "eliminar": { "type": "boolean", "description": "Escribe una copia sin las filas duplicadas" },
+                        "salida": { "type": "string", "description": "Opcional, con eliminar: archivo de destino; por defecto <archivo>_sin_duplicados" }
+                    },
+                    "required": ["archivo"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "cruzar_hojas",
+                "description": "Cruza dos hojas como BUSCARV: a cada fila de hoja_a le añade las columnas de la fila de hoja_b con la misma clave (left join) y guarda el resultado como una hoja nueva. Úsala en lugar de explicar cómo hacer un BUSCARV.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del .xlsx con hoja_a (dentro del directorio de trabajo)" },
+                        "hoja_a": { "type": "string", "description": "Hoja cuyas filas se conservan todas" },
+                        "clave_a": { "type": "string", "description": "Columna clave de hoja_a (encabezado, letra o número)" },
+                        "hoja_b": { "type": "string", "description": "Hoja de la que se buscan los valores" },
+                        "clave_b": { "type": "string", "description": "Columna clave de hoja_b" },
+                        "archivo_b": { "type": "string", "description": "Opcional: archivo con hoja_b si no está en el mismo libro" },
+                        "columnas": { "type": "array", "items": { "type": "string" }, "description": "Opcional: columnas de hoja_b que se añaden; por defecto todas menos la clave" },
+                        "nombre": { "type": "string", "description": "Opcional: nombre de la hoja resultante; por defecto hoja_a+hoja_b" },
+                        "salida": { "type": "string", "description": "Opcional: archivo .xlsx de destino; por defecto se añade la hoja al de origen" }
+                    },
+                    "required": ["archivo", "hoja_a", "clave_a", "hoja_b", "clave_b"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "transformar_hoja",
+                "description": "Limpia una hoja de un xlsx aplicando pasos en orden y la guarda: renombrar, ordenar o quitar columnas, convertir tipos, recortar espacios y quitar filas duplicadas.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Nombre de la hoja" },
+                        "pasos": {
+                            "type": "array",
+                            "description": "Cada paso es un objeto con una clave: {\"renombrar\": {\"Imp.\": \"Importe\"}}, {\"ordenar\": [\"Cliente\", \"Fecha\"]} (van primero), {\"quitar\": [\"Notas\"]}, {\"convertir\": {\"Importe\": \"número\"}} (número, entero, texto, fecha o booleano), {\"recortar\": true} o con columnas, {\"sin_duplicados\": true} o con las columnas clave",
+                            "items": { "type": "object" }
+                        },
+                        "salida": { "type": "string", "description": "Opcional: archivo .xlsx de destino; por defecto se reescribe el de origen" }
+                    },
+                    "required": ["archivo", "hoja", "pasos"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "crear_grafico",
+                "description": "Añade un gráfico a una hoja de un archivo xlsx usando una columna como categorías y una o más columnas como valores.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Nombre de la hoja" },
+                        "tipo": { "type": "string", "enum": ["columnas", "barras", "lineas", "circular", "area"] },
+                        "columna_categorias": { "type": "string", "description": "Columna de categorías" },
+                        "columnas_valores": { "type": "array", "items": { "type": "string" } },
+                        "titulo": { "type": "string" }
+                    },
+                    "required": ["archivo", "hoja", "tipo", "columna_categorias", "columnas_valores"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "formato_condicional",
+                "description": "Añade formato condicional a un rango de un archivo xlsx existente: escala de colores, barras de datos o resaltado de celdas según su valor (p. ej. negativos en rojo).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Nombre de la hoja" },
+                        "rango": { "type": "string", "description": "Rango en notación A1, p. ej. C2:C100" },
+                        "tipo": { "type": "string", "enum": ["escala", "escala3", "barras", "valor"] },
+                        "colores": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Colores RRGGBB opcionales para escalas y barras"
+                        },
+                        "operador": { "type": "string", "enum": [">", ">=", "<", "<=", "=", "!=", "entre"] },
+                        "valor": { "type": ["number", "string"], "description": "Valor de comparación para tipo 'valor'" },
+                        "valor2": { "type": ["number", "string"], "description": "Segundo valor para 'entre'" },
+                        "color": { "type": "string", "description": "Color de relleno RRGGBB para tipo 'valor'" }
+                    },
+                    "required": ["archivo", "hoja", "rango", "tipo"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "validar",
+                "description": "Añade validación de datos a un rango de un xlsx existente: lista desplegable, número entero o decimal, fecha o longitud del texto dentro de unos límites. Útil para plantillas de recogida de datos.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "rango": { "type": "string", "description": "Referencia como 'Hoja 1'!C2:C100 o nombre definido; sin hoja se usa la primera" },
+                        "tipo": { "type": "string", "enum": ["lista", "entero", "decimal", "fecha", "longitud"] },
+                        "valores": { "type": "array", "items": { "type": ["string", "number"] }, "description": "Opciones del desplegable para tipo 'lista'" },
+                        "origen": { "type": "string", "description": "Rango con las opciones del desplegable, en lugar de 'valores', p. ej. Listas!$A$2:$A$10" },
+                        "operador": { "type": "string", "enum": [">", ">=", "<", "<=", "=", "!=", "entre"] },
+                        "valor": { "type": ["number", "string"], "description": "Límite (fechas como AAAA-MM-DD)" },
+                        "valor2": { "type": ["number", "string"], "description": "Límite superior para 'entre'" },
+                        "mensaje": { "type": "string", "description": "Texto de ayuda al seleccionar la celda" },
+                        "error": { "type": "string", "description": "Mensaje si se introduce un valor no válido" },
+                        "vacio": { "type": "boolean", "description": "Si se permiten celdas vacías (por defecto sí)" }
+                    },
+                    "required": ["archivo", "rango", "tipo"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "buscar",
+                "description": "Busca un valor en todas las hojas de un xlsx y devuelve la hoja, la celda y el encabezado de columna de cada coincidencia. Útil para localizar un cliente, un código o una fecha sin leer las hojas completas.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "patron": { "type": "string", "description": "Texto a buscar dentro de las celdas (sin distinguir mayúsculas ni tildes), o una expresión regular entre barras como /^F-\\d{4}$/ (con /i al final sin distinguir mayúsculas)" },
+                        "hoja": { "type": "string", "description": "Buscar solo en esta hoja" }
+                    },
+                    "required": ["archivo", "patron"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "exportar_pdf",
+                "description": "Exporta un libro xlsx, o una de sus hojas, a PDF o PNG para adjuntarlo a un correo o a una presentación. Con LibreOffice instalado incluye gráficos y formatos; si no, solo PDF con los datos como tablas.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "archivo": { "type": "string", "description": "Ruta relativa del archivo .xlsx (dentro del directorio de trabajo)" },
+                        "hoja": { "type": "string", "description": "Hoja a exportar; por defecto el libro completo" },
+                        "salida": { "type": "string", "description": "Archivo .pdf o .png de destino; por defecto el nombre del libro con .pdf" }
+                    },
+                    "required": ["archivo"]
+                }
+            }
+        }
+    ])
+}
+
+// Ejecuta una herramienta y devuelve el texto que se enviará al modelo como resultado;
+// las herramientas que resumen datos se ajustan a `max_tokens` y los archivos
+// deben estar dentro de `workspace`
+pub fn execute(name: &str, arguments: &str, max_tokens: usize, workspace: &Workspace) -> Result<String> {
+    let args: Value = serde_json::from_str(if arguments.trim().is_empty() { "{}" } else { arguments })
+        .context("Los argumentos de la herramienta no son JSON válido")?;
+    match name {
+        "leer_excel" => {
+            let file = workspace.resolve(name, &required_str(&args, "archivo")?, Access::Read)?;
+            let data = excel::read_excel_file(&file)?;
+            let mut output = summary::summarize_workbook(&data, max_tokens);
+            if let Ok(metadata) = WorkbookMetadata::read(&file, Some(&data)) {
+                output.push('\n');
+                output.push_str(&metadata.render());
+            }
+            output.push('\n');
+            output.push_str(&chunks::index(&data));
+            Ok(output)
+        }
+        "leer_fragmento" => {
+            let file = workspace.resolve(name, &required_str(&args, "archivo")?, Access::Read)?;
+            let data = excel::read_excel_file(&file)?;
+            let from = args.get("desde").and_then(Value::as_u64).map(|row| row as usize);
+            chunks::read(&file, &data, &required_str(&args, "fragmento")?, from, max_tokens)
+        }
+        "agregar" => {
+            let options = RankOptions {
+                file: workspace.resolve(name, &required_str(&args, "archivo")?, Access::Read)?,
+                sheet: required_str(&args, "hoja")?,
+                by: required_str(&args, "por")?,
+                n: args.get("n").and_then(Value::as_u64).unwrap_or(10) as usize,
+                group_by: optional_str(&args, "agrupado_por"),
+                ascending: optional_str(&args, "orden").as_deref() == Some("asc"),
+                output: optional_str(&args, "salida")
+                    .map(|path| workspace.resolve(name, &path, Access::Write))
+                    .transpose()?,
+            };
+            let data = excel::read_excel_file(&options.file)?;
+            let sheet = data
+                .require_sheet(&options.file, &options.sheet)?;
+            let result = analysis::rank(sheet, &options)?;
+            let rows = result.text_rows();
+            let mut output = table::render_table(&rows[0], &rows[1..]);
+            if let Some(path) = &options.output {
+                excel::write_sheet_to_file(path, result)?;
+                output.push_str(&format!("\nResultado guardado en {}", path));
+            }
+            Ok(output)
+        }
+        "estadisticas" => {
+            let options = StatsOptions {
+                file: workspace.resolve(name, &required_str(&args, "archivo")?, Access::Read)?,
+                sheet: optional_str(&args, "hoja"),
+                columns: args
+                    .get("columnas")
+                    .and_then(Value::as_array)
+                    .context("Falta el argumento 'columnas'")?
+                    .iter()
+                    .filter_map(|spec| spec.as_str().map(str::to_string))
+                    .collect(),
+                output: None,
+            };
+            let data = excel::read_excel_file(&options.file)?;
+            let sheet = match &options.sheet {
+                Some(sheet) => data.require_sheet(&options.file, sheet)?,
+                None => data.sheets.first().context("El libro no tiene hojas")?,
+            };
+            let result = analysis::statistics(sheet, &options)?;
+            let rows = result.sheet.display_rows();
+            Ok(table::render_table(&rows[0], &rows[1..]))
+        }
+        "escribir_hoja" => {
+            let file = workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?;
+            let name = required_str(&args, "hoja")?;
+            let rows = args
+                .get("filas")
+                .and_then(Value::as_array)
+                .context("Falta el argumento 'filas'")?;
+            let mut sheet = SheetData::new(&name);
+            for row in rows {
+                let cells = row.as_array().context("Cada fila debe ser una lista de valores")?;
+                sheet.rows.push(cells.iter().map(convert::cell_from_json).collect());
+            }
+            hyperlinks::apply_bold_markup(&mut sheet);
+            if let Some(Value::Object(links)) = args.get("enlaces") {
+                for (cell, target) in links {
+                    let (row, col) = excel::parse_cell_ref(cell).context(format!("Celda no válida en enlaces: {}", cell))?;
+                    let target = target.as_str().context(format!("El destino del enlace de {} debe ser un texto", cell))?;
+                    sheet.hyperlinks.insert((row, col), target.to_string());
+                }
+            }
+            if let Some(Value::Object(cell_notes)) = args.get("notas") {
+                for (cell, text) in cell_notes {
+                    let (row, col) = excel::parse_cell_ref(cell).context(format!("Celda no válida en notas: {}", cell))?;
+                    let text = text.as_str().context(format!("La nota de {} debe ser un texto", cell))?;
+                    sheet.notes.insert((row, col), Note::new(text));
+                }
+            }
+            if let Some(layout) = args.get("diseño") {
+                sheet.layout = LayoutSpec::from_json(layout)?.resolve(&sheet)?;
+            }
+            // Se comprueban antes de escribir para no dejar la hoja a medias
+            let validations = match args.get("validaciones") {
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .map(|item| {
+                        let range = required_str(item, "rango")?;
+                        let range = CellRange::parse(&range).context(format!("Rango no válido: {}", range))?;
+                        Ok((range, Validation::from_json(item)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+                _ => Vec::new(),
+            };
+            let count = sheet.rows.len();
+            excel::write_sheet_to_file(&file, sheet)?;
+            if !validations.is_empty() {
+                let mut package = XlsxPackage::open(Path::new(&file))?;
+                for (range, validation) in &validations {
+                    validation::add_to_sheet(&mut package, &name, range, validation)?;
+                }
+                package.save(Path::new(&file))?;
+            }
+            Ok(format!("Escritas {} filas en la hoja '{}' de {}", count, name, file))
+        }
+        "ajustar_hoja" => {
+            let options = LayoutOptions {
+                file: workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?,
+                sheet: required_str(&args, "hoja")?,
+                spec: LayoutSpec::from_json(&args)?,
+            };
+            let layout = layout::apply(&options)?;
+            Ok(format!("Hoja '{}' de {}: {}", options.sheet, options.file, layout.describe()))
+        }
+        "proteger" => {
+            let editable = match args.get("editables") {
+                Some(Value::Array(ranges)) => ranges
+                    .iter()
+                    .map(|range| {
+                        let range = range.as_str().context("Cada rango editable debe ser un texto como B2:B20")?;
+                        CellRange::parse(range).context(format!("Rango no válido: '{}'", range))
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+                Some(Value::String(ranges)) => protection::parse_ranges(ranges)?,
+                _ => Vec::new(),
+            };
+            let options = ProtectOptions {
+                file: workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?,
+                sheet: required_str(&args, "hoja")?,
+                password: optional_str(&args, "contraseña"),
+                editable,
+            };
+            let outcome = protection::apply(&options)?;
+            Ok(format!(
+                "Hoja '{}' de {} protegida: {} celdas con fórmula bloqueadas, {} editables",
+                options.sheet, options.file, outcome.formulas, outcome.unlocked
+            ))
+        }
+        "escribir_rango" => {
+            let file = workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?;
+            let target = required_str(&args, "destino")?;
+            let rows = args
+                .get("valores")
+                .and_then(Value::as_array)
+                .context("Falta el argumento 'valores'")?
+                .iter()
+                .map(|row| {
+                    let cells = row.as_array().context("Cada fila debe ser una lista de valores")?;
+                    Ok(cells.iter().map(convert::cell_from_json).collect())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let spill = if args.get("sobrescribir").and_then(Value::as_bool).unwrap_or(false) {
+                SpillPolicy::Overwrite
+            } else {
+                match optional_str(&args, "desplazar") {
+                    Some(shift) => SpillPolicy::parse_shift(&shift).context("'desplazar' debe ser abajo o derecha")?,
+                    None => SpillPolicy::Refuse,
+                }
+            };
+            let written = named_ranges::write_range(&file, &target, &rows, spill)?;
+            let mut result = format!("Escritas {} celdas en {} de {}", written.cells, written.description, file);
+            if let Some(requested) = &written.shifted_from {
+                result.push_str(&format!(" (desplazado desde {} para no pisar datos)", requested));
+            }
+            if written.overwritten > 0 {
+                result.push_str(&format!("; se reemplazaron {} celdas con datos", written.overwritten));
+            }
+            Ok(result)
+        }
+        "escribir_formula" => {
+            let file = workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?;
+            let overwrite = args.get("sobrescribir").and_then(Value::as_bool).unwrap_or(false);
+            let written = formula_check::write_formula(
+                &file,
+                &required_str(&args, "destino")?,
+                &required_str(&args, "formula")?,
+                overwrite,
+            )?;
+            let mut result = if written.cells == 1 {
+                format!("Fórmula ={} escrita en {} de {}", written.formula, written.description, file)
+            } else {
+                format!("Fórmula ={} rellenada en {} ({} celdas) de {}", written.formula, written.description, written.cells, file)
+            };
+            match &written.value {
+                Some(CellValue::Error(error)) => {
+                    result.push_str(&format!("; su resultado es el error {}: revisa las celdas a las que apunta", error))
+                }
+                Some(value) => result.push_str(&format!("; valor calculado: {}", value)),
+                None => result.push_str("; Excel la calculará al abrir el libro"),
+            }
+            Ok(result)
+        }
+        "escribir_enlace" => {
+            let options = LinkOptions {
+                file: workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?,
+                cell: required_str(&args, "celda")?,
+                target: required_str(&args, "destino")?,
+                text: optional_str(&args, "texto"),
+            };
+            let cell = hyperlinks::write_link(&options)?;
+            Ok(format!("Enlace a {} escrito en {} de {}", options.target, cell, options.file))
+        }
+        "escribir_nota" => {
+            let options = NoteOptions {
+                file: workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?,
+                cell: required_str(&args, "celda")?,
+                action: NoteAction::Write(required_str(&args, "texto")?),
+            };
+            let outcome = notes::apply(&options)?;
+            Ok(format!("Nota escrita en {} de {}", outcome.cell, options.file))
+        }
+        "buscar_duplicados" => {
+            let remove = args.get("eliminar").and_then(Value::as_bool).unwrap_or(false);
+            let file = workspace.resolve(name, &required_str(&args, "archivo")?, Access::Read)?;
+            let output = match optional_str(&args, "salida") {
+                Some(output) if remove => Some(workspace.resolve(name, &output, Access::Write)?),
+                _ => None,
+            };
+            let options = DuplicateOptions {
+                file,
+                sheet: optional_str(&args, "hoja"),
+                columns: args
+                    .get("columnas")
+                    .and_then(Value::as_array)
+                    .map(|cols| cols.iter().filter_map(Value::as_str).map(str::to_string).collect())
+                    .unwrap_or_default(),
+                output,
+            };
+            if remove {
+                let (report, output) = duplicates::remove(&options)?;
+                Ok(format!("{}
+Copia sin duplicados guardada en {}", report.describe(), output))
+            } else {
+                Ok(duplicates::report(&options)?.describe())
+            }
+        }
+        "cruzar_hojas" => {
+            let source = required_str(&args, "archivo")?;
+            let file = workspace.resolve(name, &source, Access::Read)?;
+            let right_file = match optional_str(&args, "archivo_b") {
+                Some(other) => workspace.resolve(name, &other, Access::Read)?,
+                None => file.clone(),
+            };
+            let output = workspace.resolve(name, &optional_str(&args, "salida").unwrap_or(source), Access::Write)?;
+            let options = JoinOptions {
+                left_sheet: required_str(&args, "hoja_a")?,
+                left_key: required_str(&args, "clave_a")?,
+                right_sheet: required_str(&args, "hoja_b")?,
+                right_key: required_str(&args, "clave_b")?,
+                columns: args
+                    .get("columnas")
+                    .and_then(Value::as_array)
+                    .map(|cols| cols.iter().filter_map(Value::as_str).map(str::to_string).collect())
+                    .unwrap_or_default(),
+                name: optional_str(&args, "nombre"),
+            };
+            let mut data = excel::read_excel_file(&file)?;
+            let right_data = if right_file == file { None } else { Some(excel::read_excel_file(&right_file)?) };
+            let left = data.require_sheet(&file, &options.left_sheet)?;
+            let right = right_data.as_ref().unwrap_or(&data).require_sheet(&right_file, &options.right_sheet)?;
+            let (sheet, stats) = join::left_join(left, right, &options)?;
+            let sheet_name = sheet.name.clone();
+            data.upsert_sheet(sheet);
+            excel::save_workbook(Path::new(&output), &data)?;
+            Ok(format!(
+                "Hoja {} guardada en {}: {}",
+                sheet_name,
+                output,
+                stats.describe(&options.right_sheet)
+            ))
+        }
+        "transformar_hoja" => {
+            let source = required_str(&args, "archivo")?;
+            let file = workspace.resolve(name, &source, Access::Read)?;
+            let output = workspace.resolve(name, &optional_str(&args, "salida").unwrap_or(source), Access::Write)?;
+            let steps = transform::parse_steps(args.get("pasos").context("Falta el argumento 'pasos'")?)?;
+            let mut data = excel::read_excel_file(&file)?;
+            let mut sheet = data.require_sheet(&file, &required_str(&args, "hoja")?)?.clone();
+            let descriptions = transform::apply(&mut sheet, &steps)?;
+            let (sheet_name, headers) = (sheet.name.clone(), sheet.headers());
+            data.upsert_sheet(sheet);
+            excel::save_workbook(Path::new(&output), &data)?;
+            Ok(format!(
+                "Hoja {} transformada y guardada en {}: {}. Encabezados: {}",
+                sheet_name,
+                output,
+                descriptions.join("; "),
+                headers.join(", ")
+            ))
+        }
+        "combinar_celdas" => {
+            let file = workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?;
+            let outcome = merges::merge_cells(&file, &required_str(&args, "rango")?)?;
+            Ok(format!(
+                "Celdas {} combinadas en {} ({} celdas vaciadas)",
+                outcome.description, file, outcome.cleared
+            ))
+        }
+        "crear_grafico" => {
+            let file = workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?;
+            let name = required_str(&args, "hoja")?;
+            let kind_name = required_str(&args, "tipo")?;
+            let kind = ChartKind::parse(&kind_name)
+                .context(format!("Tipo de gráfico no válido: {}", kind_name))?;
+            let mut data = excel::read_excel_file(&file)?;
+            let mut sheet = data
+                .require_sheet(&file, &name)?
+                .clone();
+            let category_col = analysis::require_column(&sheet, &required_str(&args, "columna_categorias")?)?;
+            let value_cols = args
+                .get("columnas_valores")
+                .and_then(Value::as_array)
+                .context("Falta el argumento 'columnas_valores'")?
+                .iter()
+                .map(|spec| analysis::require_column(&sheet, spec.as_str().unwrap_or_default()))
+                .collect::<Result<Vec<usize>>>()?;
+            if value_cols.is_empty() || sheet.data_rows().is_empty() {
+                bail!("El gráfico necesita al menos una columna de valores y una fila de datos");
+            }
+            let width = sheet.rows.iter().map(Vec::len).max().unwrap_or(0);
+            sheet.charts.push(ChartSpec {
+                kind,
+                title: optional_str(&args, "titulo").unwrap_or_else(|| name.clone()),
+                category_col,
+                value_cols,
+                first_row: sheet.data_start(),
+                last_row: sheet.rows.len() - 1,
+                anchor: (1, width + 1),
+            });
+            data.upsert_sheet(sheet);
+            excel::save_workbook(Path::new(&file), &data)?;
+            Ok(format!("Gráfico de {} añadido a la hoja '{}' de {}", kind_name, name, file))
+        }
+        "formato_condicional" => {
+            let options = ConditionalFormatOptions {
+                file: workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?,
+                sheet: required_str(&args, "hoja")?,
+                range: CellRange::parse(&required_str(&args, "rango")?)
+                    .context("Rango no válido")?,
+                rule: conditional_format::rule_from_json(&args)?,
+            };
+            conditional_format::apply(&options)?;
+            Ok(format!(
+                "Formato condicional aplicado en {}!{} de {}",
+                options.sheet, options.range, options.file
+            ))
+        }
+        "validar" => {
+            let options = ValidationOptions {
+                file: workspace.resolve(name, &required_str(&args, "archivo")?, Access::Write)?,
+                target: required_str(&args, "rango")?,
+                validation: Validation::from_json(&args)?,
+            };
+            let description = validation::apply(&options)?;
+            Ok(format!(
+                "Validación ({}) añadida en {} de {}",
+                options.validation.describe(),
+                description,
+                options.file
+            ))
+        }
+        "buscar" => {
+            let file = workspace.resolve(name, &required_str(&args, "archivo")?, Access::Read)?;
+            let query = required_str(&args, "patron")?;
+            let sheet = optional_str(&args, "hoja");
+            let matcher = Matcher::parse(&query)?;
+            let data = excel::read_excel_file(&file)?;
+            if let Some(sheet) = &sheet {
+                data.require_sheet(&file, sheet)?;
+            }
+            let result = search::search(&data, &matcher, sheet.as_deref(), search::DEFAULT_MAX_HITS);
+            Ok(search::context_summary(&file, &query, &result))
+        }
+        "exportar_pdf" => {
+            let options = ExportOptions {
+                file: workspace.resolve(name, &required_str(&args, "archivo")?, Access::Read)?,
+                sheet: optional_str(&args, "hoja"),
+                output: optional_str(&args, "salida")
+                    .map(|path| workspace.resolve(name, &path, Access::Write))
+                    .transpose()?,
+                converter: Converter::Auto,
+            };
+            let outcome = export::export(&options)?;
+            let mut output = format!("Exportado a {} con {}", outcome.output, outcome.converter);
+            for note in &outcome.notes {
+                output.push_str(&format!(". {}", note));
+            }
+            Ok(output)
+        }
+        other => bail!("Herramienta desconocida: {}", other),
+    }
+}
+
+pub fn required_str(args: &Value, key: &str) -> Result<String> {
+    args.get(key)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .context(format!("Falta el argumento '{}'", key))
+}
+
+fn optional_str(args: &Value, key: &str) -> Option<String> {
+    args.get(key)
+        .and_then(Value::as_str)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
@@ -0,0 +1,228 @@
+// Recorrido guiado (`ia_agent tour` o `tour`): genera libros de ejemplo en
+// ./iagent_tour y propone, paso a paso, comandos reales del agente para leer,
+// analizar, transformar y preparar informes. Cada comando se ejecuta con el
+// mismo bucle que la entrada del usuario.
+use crate::convert;
+use crate::excel::{self, CellValue, SheetData, WorkbookData};
+use crate::i18n::pick;
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+pub const TOUR_DIR: &str = "iagent_tour";
+// 2024-01-01 como número de serie de Excel
+const FIRST_DAY: f64 = 45292.0;
+const REGIONS: [&str; 4] = ["Norte", "Sur", "Este", "Oeste"];
+const PRODUCTS: [(&str, f64); 5] = [
+    ("Portátil", 950.0),
+    ("Monitor", 210.0),
+    ("Teclado", 45.0),
+    ("Ratón", 25.0),
+    ("Silla", 180.0),
+];
+
+struct TourStep {
+    // (español, inglés)
+    title: (&'static str, &'static str),
+    explanation: (&'static str, &'static str),
+    command: &'static str,
+}
+
+const STEPS: &[TourStep] = &[
+    TourStep {
+        title: ("Leer un libro", "Read a workbook"),
+        explanation: (
+            "leer_excel carga el archivo, te muestra las primeras filas y deja un resumen en el contexto del modelo.",
+            "read_excel loads the file, shows you the first rows and leaves a summary in the model's context.",
+        ),
+        command: "leer_excel iagent_tour/ventas_tour.xlsx",
+    },
+    TourStep {
+        title: ("Ver los datos", "Look at the data"),
+        explanation: (
+            "mostrar enseña cualquier hoja ya leída como tabla, sin llamar al modelo.",
+            "show prints any sheet already read as a table, without calling the model.",
+        ),
+        command: "mostrar Ventas 8",
+    },
+    TourStep {
+        title: ("Analizar: ranking", "Analyze: ranking"),
+        explanation: (
+            "top suma el importe por región y ordena el resultado; el cálculo es local y exacto.",
+            "top sums the amount per region and sorts the result; the calculation is local and exact.",
+        ),
+        command: "top iagent_tour/ventas_tour.xlsx Ventas por=Importe agrupado_por=Región n=4",
+    },
+    TourStep {
+        title: ("Informe: Pareto con gráfico", "Report: Pareto with a chart"),
+        explanation: (
+            "pareto calcula qué clientes concentran el 80% del importe y lo guarda con un gráfico en un libro nuevo.",
+            "pareto finds which customers make up 80% of the amount and saves it with a chart in a new workbook.",
+        ),
+        command: "pareto iagent_tour/ventas_tour.xlsx Ventas por=Importe agrupado_por=Cliente grafico=columnas salida=iagent_tour/informe_tour.xlsx",
+    },
+    TourStep {
+        title: ("Dar formato", "Add formatting"),
+        explanation: (
+            "formato_condicional añade barras de datos a la columna de % del total del informe.",
+            "conditional_format adds data bars to the % of total column of the report.",
+        ),
+        command: "formato_condicional iagent_tour/informe_tour.xlsx Pareto C2:C26 barras",
+    },
+    TourStep {
+        title: ("Deshacer", "Undo"),
+        explanation: (
+            "Antes de cada escritura se guarda una copia: deshacer devuelve el informe al estado anterior al formato.",
+            "A copy is saved before every write: undo takes the report back to its state before the formatting.",
+        ),
+        command: "deshacer iagent_tour/informe_tour.xlsx",
+    },
+    TourStep {
+        title: ("Analizar: cohortes", "Analyze: cohorts"),
+        explanation: (
+            "cohortes agrupa a los clientes por mes de alta y cuenta cuántos siguen comprando cada mes.",
+            "cohorts groups customers by signup month and counts how many keep buying each month.",
+        ),
+        command: "cohortes iagent_tour/ventas_tour.xlsx fecha_alta=Alta fecha_evento=Fecha cliente=Cliente salida=iagent_tour/cohortes_tour.xlsx",
+    },
+    TourStep {
+        title: ("Transformar: convertir", "Transform: convert"),
+        explanation: (
+            "convertir pasa archivos entre csv, xlsx y json; --validar comprueba el resultado celda a celda.",
+            "convert moves files between csv, xlsx and json; --validate checks the result cell by cell.",
+        ),
+        command: "convertir iagent_tour/inventario_tour.csv --a xlsx --salida iagent_tour --validar",
+    },
+    TourStep {
+        title: ("Preguntar al modelo", "Ask the model"),
+        explanation: (
+            "Cualquier otra entrada es una pregunta para el modelo, que ya conoce el resumen del libro (necesita la clave de la API).",
+            "Anything else is a question for the model, which already knows the workbook summary (needs the API key).",
+        ),
+        command: "¿Qué región vende más y qué producto debería revisar primero?",
+    },
+];
+
+pub struct Tour {
+    next: usize,
+}
+
+impl Tour {
+    // Genera los datos de ejemplo y presenta el recorrido
+    pub fn start() -> Result<Tour> {
+        write_samples(Path::new(TOUR_DIR))?;
+        println!(
+            "{}",
+            pick((
+                "🧭 Recorrido guiado: se han creado libros de ejemplo en ./iagent_tour. En cada paso pulsa Enter para ejecutar el comando, 's' para saltarlo o 'q' para terminar.",
+                "🧭 Guided tour: sample workbooks were created in ./iagent_tour. At each step press Enter to run the command, 's' to skip it or 'q' to stop.",
+            ))
+        );
+        Ok(Tour { next: 0 })
+    }
+
+    // Presenta el siguiente paso y devuelve su comando, o None al terminar el recorrido
+    pub fn next_command(&mut self, reader: &mut impl BufRead) -> Result<Option<String>> {
+        while let Some(step) = STEPS.get(self.next) {
+            self.next += 1;
+            println!();
+            println!("🧭 {} {}/{}: {}", pick(("Paso", "Step")), self.next, STEPS.len(), pick(step.title));
+            println!("   {}", pick(step.explanation));
+            println!("   {} {}", pick(("Comando:", "Command:")), step.command);
+            print!("{} ", pick(("[Enter] ejecutar · s saltar · q terminar:", "[Enter] run · s skip · q quit:")));
+            io::stdout().flush()?;
+
+            let mut answer = String::new();
+            if reader.read_line(&mut answer)? == 0 {
+                return Ok(None);
+            }
+            match answer.trim().to_lowercase().as_str() {
+                "" => {
+                    println!("> {}", step.command);
+                    return Ok(Some(step.command.to_string()));
+                }
+                "s" | "saltar" | "skip" => continue,
+                _ => {
+                    println!("{}", pick(("Recorrido terminado.", "Tour finished.")));
+                    return Ok(None);
+                }
+            }
+        }
+        println!(
+            "🏁 {}",
+            pick((
+                "Recorrido completado. Escribe 'ayuda' para ver todos los comandos.",
+                "Tour completed. Type 'help' to see every command.",
+            ))
+        );
+        Ok(None)
+    }
+}
+
+// Datos de ejemplo reproducibles: ventas con alta de cliente e inventario en csv
+fn write_samples(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).context(format!("No se pudo crear {}", dir.display()))?;
+    let mut random = Lcg(20240101);
+
+    let mut sales = SheetData::new("Ventas");
+    sales.rows.push(text_row(&["Fecha", "Alta", "Cliente", "Región", "Producto", "Unidades", "Importe"]));
+    let customers: Vec<(String, f64, &str)> = (1..=24)
+        .map(|idx| {
+            let signup = FIRST_DAY + random.below(150) as f64;
+            (format!("Cliente {:02}", idx), signup, REGIONS[random.below(REGIONS.len() as u64) as usize])
+        })
+        .collect();
+    let mut orders = Vec::new();
+    for (name, signup, region) in &customers {
+        // Los primeros clientes compran más, para que el Pareto tenga sentido
+        let count = 2 + random.below(10) / (1 + orders.len() as u64 / 40);
+        for _ in 0..count {
+            let date = signup + random.below(180) as f64;
+            let (product, price) = PRODUCTS[random.below(PRODUCTS.len() as u64) as usize];
+            let units = 1 + random.below(5);
+            orders.push((date, *signup, name.clone(), *region, product, units, price * units as f64));
+        }
+    }
+    orders.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for (date, signup, name, region, product, units, amount) in orders {
+        sales.rows.push(vec![
+            CellValue::DateTime(date),
+            CellValue::DateTime(signup),
+            CellValue::Text(name),
+            CellValue::Text(region.to_string()),
+            CellValue::Text(product.to_string()),
+            CellValue::Number(units as f64),
+            CellValue::Number(amount),
+        ]);
+    }
+    excel::save_workbook(&dir.join("ventas_tour.xlsx"), &WorkbookData { sheets: vec![sales] })?;
+
+    let mut inventory = SheetData::new("inventario_tour");
+    inventory.rows.push(text_row(&["Producto", "Almacén", "Existencias", "Precio"]));
+    for (product, price) in PRODUCTS {
+        for warehouse in ["Madrid", "Sevilla"] {
+            inventory.rows.push(vec![
+                CellValue::Text(product.to_string()),
+                CellValue::Text(warehouse.to_string()),
+                CellValue::Number(random.below(300) as f64),
+                CellValue::Number(price),
+            ]);
+        }
+    }
+    convert::write_csv(&dir.join("inventario_tour.csv"), &inventory)
+}
+
+fn text_row(values: &[&str]) -> Vec<CellValue> {
+    values.iter().map(|v| CellValue::Text(v.to_string())).collect()
+}
+
+// Generador congruencial lineal: los datos son siempre los mismos
+struct Lcg(u64);
+
+impl Lcg {
+    fn below(&mut self, limit: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) % limit.max(1)
+    }
+}
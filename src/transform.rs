@@ -0,0 +1,518 @@
+// Transformaciones de limpieza sobre una hoja (`transformar` y la herramienta
+// transformar_hoja): renombrar, reordenar, quitar y convertir columnas, recortar
+// espacios y quitar filas duplicadas. Los pasos se aplican en orden; en la línea
+// de comandos se escriben uno tras otro y también se pueden leer de un archivo:
+//
+//   transformar Datos renombrar=Imp.:Importe,Cli:Cliente quitar=Notas recortar
+//       convertir=Importe:número,Alta:fecha ordenar=Cliente,Alta sin_duplicados=Cliente
+//   transformar Datos pasos=limpieza.json
+//
+// limpieza.json es una lista de pasos con la misma forma, o un objeto con "pasos":
+//   {"pasos": [{"renombrar": {"Imp.": "Importe"}}, {"quitar": ["Notas"]},
+//              {"convertir": {"Importe": "número"}}, {"recortar": true},
+//              {"ordenar": ["Cliente", "Alta"]}, {"sin_duplicados": ["Cliente"]}]}
+// En TOML, cada paso es una tabla [[pasos]].
+use crate::analysis;
+use crate::dates::{self, DayOrder};
+use crate::duplicates;
+use crate::excel::{CellValue, SheetData};
+use crate::manifest;
+use crate::structure;
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CastType {
+    Number,
+    Whole,
+    Text,
+    Date,
+    Bool,
+}
+
+impl CastType {
+    fn parse(value: &str) -> Option<CastType> {
+        match value.trim().to_lowercase().as_str() {
+            "número" | "numero" | "decimal" => Some(CastType::Number),
+            "entero" => Some(CastType::Whole),
+            "texto" => Some(CastType::Text),
+            "fecha" => Some(CastType::Date),
+            "booleano" => Some(CastType::Bool),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            CastType::Number => "número",
+            CastType::Whole => "entero",
+            CastType::Text => "texto",
+            CastType::Date => "fecha",
+            CastType::Bool => "booleano",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    // (columna, nuevo encabezado)
+    Rename(Vec<(String, String)>),
+    // Estas columnas pasan al principio en este orden; las demás quedan detrás
+    Reorder(Vec<String>),
+    Drop(Vec<String>),
+    Cast(Vec<(String, CastType)>),
+    // Sin columnas, todas
+    Trim(Vec<String>),
+    // Sin columnas, se compara la fila entera
+    Dedupe(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct TransformOptions {
+    // Libro cargado; sin él, el último que tenga la hoja
+    pub file: Option<String>,
+    pub sheet: String,
+    pub steps: Vec<Step>,
+}
+
+impl TransformOptions {
+    // `transformar <hoja> <paso>... [archivo=<libro>]`; el orden de los pasos se conserva
+    pub fn parse(args: &[&str]) -> Result<TransformOptions> {
+        let (sheet, rest) = args.split_first().context("Falta la hoja")?;
+        let mut file = None;
+        let mut steps = Vec::new();
+        for arg in rest {
+            let (key, value) = arg.split_once('=').unwrap_or((arg, ""));
+            match key {
+                "archivo" => file = Some(value.to_string()),
+                "pasos" => steps.extend(load_steps(value)?),
+                _ => steps.push(parse_inline(key, value)?),
+            }
+        }
+        if steps.is_empty() {
+            bail!("No hay ningún paso que aplicar");
+        }
+        Ok(TransformOptions {
+            file,
+            sheet: sheet.to_string(),
+            steps,
+        })
+    }
+}
+
+fn list(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+}
+
+// "Viejo:Nuevo,Otro:Nuevo2"
+fn pairs(key: &str, value: &str) -> Result<Vec<(String, String)>> {
+    list(value)
+        .iter()
+        .map(|pair| {
+            let (column, target) = pair
+                .split_once(':')
+                .context(format!("Valor no válido en {}: '{}' (usa columna:valor)", key, pair))?;
+            Ok((column.trim().to_string(), target.trim().to_string()))
+        })
+        .collect()
+}
+
+fn parse_inline(key: &str, value: &str) -> Result<Step> {
+    let required = |items: Vec<String>| -> Result<Vec<String>> {
+        if items.is_empty() {
+            bail!("Faltan las columnas de {}", key);
+        }
+        Ok(items)
+    };
+    Ok(match key {
+        "renombrar" => Step::Rename(pairs(key, value)?),
+        "ordenar" => Step::Reorder(required(list(value))?),
+        "quitar" => Step::Drop(required(list(value))?),
+        "convertir" => Step::Cast(
+            pairs(key, value)?
+                .into_iter()
+                .map(|(column, kind)| Ok((column, cast_type(&kind)?)))
+                .collect::<Result<_>>()?,
+        ),
+        "recortar" => Step::Trim(list(value)),
+        "sin_duplicados" => Step::Dedupe(list(value)),
+        _ => bail!(
+            "Paso desconocido: '{}' (usa renombrar, ordenar, quitar, convertir, recortar, sin_duplicados o pasos=<archivo>)",
+            key
+        ),
+    })
+}
+
+fn cast_type(value: &str) -> Result<CastType> {
+    CastType::parse(value).context(format!(
+        "Tipo no válido: '{}' (usa número, entero, texto, fecha o booleano)",
+        value
+    ))
+}
+
+// Pasos de un archivo .json o .toml
+pub fn load_steps(path: &str) -> Result<Vec<Step>> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    if matches!(extension.as_str(), "yaml" | "yml") {
+        bail!("los pasos en YAML no están disponibles en esta compilación; usa un archivo .json o .toml");
+    }
+    let text = fs::read_to_string(path).context(format!("No se pudo leer el archivo de pasos {}", path))?;
+    let value = if extension == "toml" {
+        manifest::parse(&text).context(format!("TOML no válido en {}", path))?
+    } else {
+        serde_json::from_str(&text).context(format!("JSON no válido en {}", path))?
+    };
+    parse_steps(&value).context(format!("Pasos no válidos en {}", path))
+}
+
+// Lista de pasos en JSON, sola o dentro de {"pasos": [...]}
+pub fn parse_steps(value: &Value) -> Result<Vec<Step>> {
+    let steps = match value {
+        Value::Array(steps) => steps,
+        Value::Object(object) => object
+            .get("pasos")
+            .and_then(Value::as_array)
+            .context("Falta la lista \"pasos\"")?,
+        _ => bail!("Los pasos deben ser una lista"),
+    };
+    steps.iter().enumerate().map(|(idx, step)| parse_step(step).context(format!("Paso {}", idx + 1))).collect()
+}
+
+fn parse_step(step: &Value) -> Result<Step> {
+    let object = step.as_object().filter(|o| o.len() == 1).context("Cada paso es un objeto con una sola clave")?;
+    let (key, value) = object.iter().next().context("Paso vacío")?;
+    let strings = |value: &Value| -> Result<Vec<String>> {
+        match value {
+            Value::Bool(true) => Ok(Vec::new()),
+            Value::String(text) => Ok(list(text)),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| item.as_str().map(str::to_string).context(format!("{} espera nombres de columna", key)))
+                .collect(),
+            _ => bail!("{} espera una lista de columnas", key),
+        }
+    };
+    let mapping = |value: &Value| -> Result<Vec<(String, String)>> {
+        value
+            .as_object()
+            .context(format!("{} espera un objeto {{\"columna\": \"valor\"}}", key))?
+            .iter()
+            .map(|(column, target)| {
+                let target = target.as_str().context(format!("{}: el valor de '{}' debe ser un texto", key, column))?;
+                Ok((column.clone(), target.to_string()))
+            })
+            .collect()
+    };
+    let step = match key.as_str() {
+        "renombrar" => Step::Rename(mapping(value)?),
+        "ordenar" => Step::Reorder(strings(value)?),
+        "quitar" => Step::Drop(strings(value)?),
+        "convertir" => Step::Cast(
+            mapping(value)?
+                .into_iter()
+                .map(|(column, kind)| Ok((column, cast_type(&kind)?)))
+                .collect::<Result<_>>()?,
+        ),
+        "recortar" => Step::Trim(strings(value)?),
+        "sin_duplicados" => Step::Dedupe(strings(value)?),
+        _ => bail!("Paso desconocido: '{}'", key),
+    };
+    match &step {
+        Step::Reorder(columns) | Step::Drop(columns) if columns.is_empty() => bail!("Faltan las columnas de {}", key),
+        _ => Ok(step),
+    }
+}
+
+// Aplica los pasos en orden; devuelve la descripción de cada uno. Un paso que
+// falla deja la hoja como estaba antes de empezar.
+pub fn apply(sheet: &mut SheetData, steps: &[Step]) -> Result<Vec<String>> {
+    let mut result = sheet.clone();
+    let descriptions = steps
+        .iter()
+        .map(|step| apply_step(&mut result, step))
+        .collect::<Result<Vec<String>>>()?;
+    *sheet = result;
+    Ok(descriptions)
+}
+
+fn apply_step(sheet: &mut SheetData, step: &Step) -> Result<String> {
+    let columns = |sheet: &SheetData, specs: &[String]| -> Result<Vec<usize>> {
+        specs.iter().map(|spec| analysis::require_data_column(sheet, spec)).collect()
+    };
+    match step {
+        Step::Rename(renames) => {
+            if sheet.headerless {
+                bail!("La hoja {} no tiene encabezados que renombrar", sheet.name);
+            }
+            let targets = renames
+                .iter()
+                .map(|(spec, name)| Ok((analysis::require_data_column(sheet, spec)?, name)))
+                .collect::<Result<Vec<_>>>()?;
+            let header = sheet.rows.first_mut().context("La hoja está vacía")?;
+            for (col, name) in &targets {
+                if header.len() <= *col {
+                    header.resize(col + 1, CellValue::Empty);
+                }
+                header[*col] = CellValue::Text(name.to_string());
+            }
+            Ok(format!("{} columna(s) renombrada(s)", targets.len()))
+        }
+        Step::Reorder(specs) => {
+            let names: Vec<String> = columns(sheet, specs)?.iter().map(|col| sheet.headers()[*col].clone()).collect();
+            // Se resuelven de nuevo tras cada movimiento porque los índices cambian
+            for (position, name) in names.iter().enumerate() {
+                let from = analysis::require_data_column(sheet, name)?;
+                if from != position {
+                    structure::move_column(sheet, from, position);
+                }
+            }
+            Ok(format!("Columnas ordenadas: {} primero", names.join(", ")))
+        }
+        Step::Drop(specs) => {
+            let mut cols = columns(sheet, specs)?;
+            cols.sort_unstable();
+            cols.dedup();
+            let headers = sheet.headers();
+            let names: Vec<String> = cols.iter().map(|col| headers.get(*col).cloned().unwrap_or_default()).collect();
+            for col in cols.iter().rev() {
+                structure::delete_column(sheet, *col);
+            }
+            Ok(format!("Columna(s) quitada(s): {}", names.join(", ")))
+        }
+        Step::Cast(casts) => {
+            let mut parts = Vec::new();
+            for (spec, kind) in casts {
+                let col = analysis::require_data_column(sheet, spec)?;
+                let start = sheet.data_start();
+                let (mut converted, mut failed) = (0, 0);
+                for (row_idx, row) in sheet.rows.iter_mut().enumerate().skip(start) {
+                    let Some(cell) = row.get_mut(col).filter(|cell| **cell != CellValue::Empty) else {
+                        continue;
+                    };
+                    match cast(cell, *kind) {
+                        Some(value) => {
+                            if value != *cell {
+                                *cell = value;
+                                converted += 1;
+                            }
+                        }
+                        None => failed += 1,
+                    }
+                    sheet.formulas.remove(&(row_idx, col));
+                    sheet.rich_text.remove(&(row_idx, col));
+                }
+                let mut part = format!("{} a {} ({} celdas cambiadas", spec, kind.name(), converted);
+                if failed > 0 {
+                    part.push_str(&format!(", {} sin convertir", failed));
+                }
+                part.push(')');
+                parts.push(part);
+            }
+            Ok(format!("Convertidas: {}", parts.join("; ")))
+        }
+        Step::Trim(specs) => {
+            let only: Option<HashSet<usize>> = if specs.is_empty() {
+                None
+            } else {
+                Some(columns(sheet, specs)?.into_iter().collect())
+            };
+            let mut trimmed = 0;
+            for row in sheet.rows.iter_mut() {
+                for (col, cell) in row.iter_mut().enumerate() {
+                    let CellValue::Text(text) = cell else { continue };
+                    if only.as_ref().is_some_and(|only| !only.contains(&col)) {
+                        continue;
+                    }
+                    let clean = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if clean != *text {
+                        *cell = if clean.is_empty() { CellValue::Empty } else { CellValue::Text(clean) };
+                        trimmed += 1;
+                    }
+                }
+            }
+            Ok(format!("{} celda(s) con espacios de más recortada(s)", trimmed))
+        }
+        Step::Dedupe(specs) => {
+            let duplicates = duplicates::find(sheet, specs)?.extra_rows;
+            structure::delete_rows(sheet, &duplicates);
+            Ok(format!("{} fila(s) duplicada(s) quitada(s)", duplicates.len()))
+        }
+    }
+}
+
+// Valor convertido, o None si la celda no se puede convertir (y se deja igual)
+fn cast(cell: &CellValue, kind: CastType) -> Option<CellValue> {
+    match kind {
+        CastType::Text => Some(CellValue::Text(cell.to_string())),
+        CastType::Number => match cell {
+            CellValue::Number(_) => Some(cell.clone()),
+            CellValue::Bool(b) => Some(CellValue::Number(f64::from(u8::from(*b)))),
+            CellValue::Text(text) => parse_number(text).map(CellValue::Number),
+            _ => None,
+        },
+        CastType::Whole => cast(cell, CastType::Number).and_then(|value| value.as_number()).map(|n| CellValue::Number(n.round())),
+        CastType::Date => dates::coerce(cell, DayOrder::DayMonth),
+        CastType::Bool => match cell {
+            CellValue::Bool(_) => Some(cell.clone()),
+            CellValue::Number(n) if *n == 0.0 || *n == 1.0 => Some(CellValue::Bool(*n == 1.0)),
+            CellValue::Text(text) => match text.trim().to_lowercase().as_str() {
+                "true" | "verdadero" | "sí" | "si" | "s" | "x" | "yes" => Some(CellValue::Bool(true)),
+                "false" | "falso" | "no" | "n" => Some(CellValue::Bool(false)),
+                _ => None,
+            },
+            _ => None,
+        },
+    }
+}
+
+// "1.234,56", "1,234.56", "12,5", "€ 1.200", "15%" (0,15)
+fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (text, percent) = match text.strip_suffix('%') {
+        Some(rest) => (rest.trim(), true),
+        None => (text, false),
+    };
+    let cleaned: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '€' | '$' | '£'))
+        .collect();
+    if cleaned.is_empty() || !cleaned.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-' | '+')) {
+        return None;
+    }
+    // Con los dos separadores, el último es el decimal; con uno solo repetido, es de
+    // miles; con uno solo una vez, decimal
+    let normalized = match (cleaned.rfind('.'), cleaned.rfind(',')) {
+        (Some(dot), Some(comma)) if comma > dot => cleaned.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => cleaned.replace(',', ""),
+        (None, Some(_)) if cleaned.matches(',').count() > 1 => cleaned.replace(',', ""),
+        (None, Some(_)) => cleaned.replace(',', "."),
+        (Some(_), None) if cleaned.matches('.').count() > 1 => cleaned.replace('.', ""),
+        _ => cleaned,
+    };
+    let number: f64 = normalized.parse().ok()?;
+    Some(if percent { number / 100.0 } else { number })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::excel;
+
+    fn raw() -> SheetData {
+        SheetData::from_rows(
+            "Datos",
+            &[
+                &["Cli", "Notas", "Imp.", "Alta"],
+                &["  Ana  López", "x", "1.234,56", "15/02/2024"],
+                &["Luis", "", "12,5", "2024-03-01"],
+                &["Ana López", "y", "7", "01/01/2024"],
+                &["Eva", "", "n/d", "sin fecha"],
+            ],
+        )
+    }
+
+    #[test]
+    fn inline_steps_keep_their_order() {
+        let options = TransformOptions::parse(&[
+            "Datos",
+            "renombrar=Imp.:Importe,Cli:Cliente",
+            "quitar=Notas",
+            "convertir=Importe:número",
+            "recortar",
+            "archivo=ventas.xlsx",
+        ])
+        .unwrap();
+        assert_eq!(options.file.as_deref(), Some("ventas.xlsx"));
+        assert_eq!(
+            options.steps,
+            [
+                Step::Rename(vec![("Imp.".to_string(), "Importe".to_string()), ("Cli".to_string(), "Cliente".to_string())]),
+                Step::Drop(vec!["Notas".to_string()]),
+                Step::Cast(vec![("Importe".to_string(), CastType::Number)]),
+                Step::Trim(Vec::new()),
+            ]
+        );
+        assert!(TransformOptions::parse(&["Datos"]).is_err());
+        assert!(TransformOptions::parse(&["Datos", "convertir=Importe:moneda"]).is_err());
+        assert!(TransformOptions::parse(&["Datos", "quitar="]).is_err());
+    }
+
+    #[test]
+    fn json_steps_match_the_inline_ones_and_report_the_failing_step() {
+        let steps = parse_steps(&serde_json::json!({"pasos": [
+            {"renombrar": {"Imp.": "Importe"}},
+            {"recortar": true},
+            {"sin_duplicados": ["Cliente"]}
+        ]}))
+        .unwrap();
+        assert_eq!(
+            steps,
+            [
+                Step::Rename(vec![("Imp.".to_string(), "Importe".to_string())]),
+                Step::Trim(Vec::new()),
+                Step::Dedupe(vec!["Cliente".to_string()]),
+            ]
+        );
+        let error = parse_steps(&serde_json::json!([{"recortar": true}, {"quitar": []}])).unwrap_err();
+        assert!(format!("{:#}", error).starts_with("Paso 2"), "{:#}", error);
+    }
+
+    #[test]
+    fn a_cleaning_pipeline_renames_drops_casts_trims_and_dedupes() {
+        let mut data = raw();
+        let steps = TransformOptions::parse(&[
+            "Datos",
+            "renombrar=Imp.:Importe,Cli:Cliente",
+            "quitar=Notas",
+            "ordenar=Alta",
+            "recortar",
+            "convertir=Importe:número,Alta:fecha",
+            "sin_duplicados=Cliente",
+        ])
+        .unwrap()
+        .steps;
+        let descriptions = apply(&mut data, &steps).unwrap();
+        assert_eq!(descriptions.len(), 6);
+        // "7" y "2024-03-01" ya se leen como número y fecha; solo cambian las otras dos
+        assert_eq!(descriptions[4], "Convertidas: Importe a número (2 celdas cambiadas, 1 sin convertir); Alta a fecha (2 celdas cambiadas, 1 sin convertir)");
+        assert_eq!(descriptions[5], "1 fila(s) duplicada(s) quitada(s)");
+
+        assert_eq!(data.headers(), ["Alta", "Cliente", "Importe"]);
+        assert_eq!(data.rows.len(), 4);
+        assert_eq!(data.rows[1][0], CellValue::DateTime(excel::parse_iso_datetime("2024-02-15").unwrap()));
+        assert_eq!(data.rows[1][1], CellValue::Text("Ana López".to_string()));
+        assert_eq!(data.rows[1][2], CellValue::Number(1234.56));
+        assert_eq!(data.rows[2][2], CellValue::Number(12.5));
+        // Lo que no se puede convertir se deja como estaba
+        assert_eq!(data.rows[3][0], CellValue::Text("sin fecha".to_string()));
+        assert_eq!(data.rows[3][2], CellValue::Text("n/d".to_string()));
+    }
+
+    #[test]
+    fn a_failing_step_leaves_the_sheet_unchanged() {
+        let mut data = raw();
+        let steps = [Step::Drop(vec!["Notas".to_string()]), Step::Rename(vec![("Precio".to_string(), "Importe".to_string())])];
+        assert!(apply(&mut data, &steps).is_err());
+        assert_eq!(data.rows, raw().rows);
+    }
+
+    #[test]
+    fn numbers_are_read_with_either_decimal_separator() {
+        assert_eq!(parse_number("1.234,56"), Some(1234.56));
+        assert_eq!(parse_number("1,234.56"), Some(1234.56));
+        assert_eq!(parse_number("12,5"), Some(12.5));
+        assert_eq!(parse_number("1.234.567"), Some(1234567.0));
+        assert_eq!(parse_number("€ 1.200,00"), Some(1200.0));
+        assert_eq!(parse_number("15%"), Some(0.15));
+        assert_eq!(parse_number("n/d"), None);
+        assert_eq!(cast(&CellValue::Text("sí".to_string()), CastType::Bool), Some(CellValue::Bool(true)));
+        assert_eq!(cast(&CellValue::Text("2,6".to_string()), CastType::Whole), Some(CellValue::Number(3.0)));
+    }
+}
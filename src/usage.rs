@@ -0,0 +1,173 @@
+use crate::llm::Completion;
+use crate::profiles;
+use serde::{Deserialize, Serialize};
+
+// Campo `usage` de la respuesta de la API
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+}
+
+// Precio en dólares por millón de tokens (entrada, salida)
+#[derive(Debug, Clone, Copy)]
+pub struct Pricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+// Tarifas públicas conocidas; se pueden sobrescribir con IAGENT_PRICE_INPUT / IAGENT_PRICE_OUTPUT
+fn builtin_pricing(provider: &str, model: &str) -> Option<Pricing> {
+    let (input, output) = match (provider, model) {
+        ("deepseek", "deepseek-chat") | ("deepseek", "deepseek-coder") => (0.27, 1.10),
+        ("deepseek", "deepseek-reasoner") => (0.55, 2.19),
+        _ => return None,
+    };
+    Some(Pricing {
+        input_per_million: input,
+        output_per_million: output,
+    })
+}
+
+pub fn pricing_for(provider: &str, model: &str) -> Option<Pricing> {
+    let input = profiles::var("IAGENT_PRICE_INPUT").ok().and_then(|v| v.parse().ok());
+    let output = profiles::var("IAGENT_PRICE_OUTPUT").ok().and_then(|v| v.parse().ok());
+    match (input, output, builtin_pricing(provider, model)) {
+        (Some(input), Some(output), _) => Some(Pricing {
+            input_per_million: input,
+            output_per_million: output,
+        }),
+        (input, output, Some(builtin)) => Some(Pricing {
+            input_per_million: input.unwrap_or(builtin.input_per_million),
+            output_per_million: output.unwrap_or(builtin.output_per_million),
+        }),
+        _ => None,
+    }
+}
+
+// Totales acumulados de un proveedor/modelo
+#[derive(Debug, Clone)]
+pub struct ModelUsage {
+    pub provider: String,
+    pub model: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl ModelUsage {
+    pub fn estimated_cost(&self) -> Option<f64> {
+        pricing_for(&self.provider, &self.model).map(|p| {
+            (self.prompt_tokens as f64 * p.input_per_million
+                + self.completion_tokens as f64 * p.output_per_million)
+                / 1_000_000.0
+        })
+    }
+}
+
+// Consumo de tokens de la sesión actual
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    entries: Vec<ModelUsage>,
+    // Respuestas servidas desde la caché y tokens que habrían costado
+    cache_hits: u64,
+    cached_tokens: u64,
+}
+
+impl UsageTracker {
+    // Registra una respuesta del modelo; las de la caché no cuentan como consumo
+    pub fn record_completion(&mut self, provider: &str, model: &str, completion: &Completion) {
+        if completion.cached {
+            let usage = completion.usage.unwrap_or_default();
+            self.cache_hits += 1;
+            self.cached_tokens += usage.prompt_tokens + usage.completion_tokens;
+        } else {
+            self.record(provider, model, completion.usage);
+        }
+    }
+
+    pub fn record(&mut self, provider: &str, model: &str, usage: Option<Usage>) {
+        let usage = usage.unwrap_or_default();
+        let entry = self.entry(provider, model);
+        entry.requests += 1;
+        entry.prompt_tokens += usage.prompt_tokens;
+        entry.completion_tokens += usage.completion_tokens;
+    }
+
+    // Suma el consumo de otro contador (el de una petición del servidor)
+    pub fn merge(&mut self, other: UsageTracker) {
+        for usage in other.entries {
+            let entry = self.entry(&usage.provider, &usage.model);
+            entry.requests += usage.requests;
+            entry.prompt_tokens += usage.prompt_tokens;
+            entry.completion_tokens += usage.completion_tokens;
+        }
+        self.cache_hits += other.cache_hits;
+        self.cached_tokens += other.cached_tokens;
+    }
+
+    fn entry(&mut self, provider: &str, model: &str) -> &mut ModelUsage {
+        let index = match self
+            .entries
+            .iter()
+            .position(|e| e.provider == provider && e.model == model)
+        {
+            Some(index) => index,
+            None => {
+                self.entries.push(ModelUsage {
+                    provider: provider.to_string(),
+                    model: model.to_string(),
+                    requests: 0,
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                });
+                self.entries.len() - 1
+            }
+        };
+        &mut self.entries[index]
+    }
+
+    // Informe legible del consumo por proveedor/modelo
+    pub fn report(&self) -> String {
+        if self.entries.is_empty() && self.cache_hits == 0 {
+            return "Sin consumo de la API en esta sesión.".to_string();
+        }
+        let mut report = String::from("Consumo de la sesión:\n");
+        let mut total_cost = 0.0;
+        let mut unknown_price = false;
+        for entry in &self.entries {
+            let cost = match entry.estimated_cost() {
+                Some(cost) => {
+                    total_cost += cost;
+                    format!("${:.4}", cost)
+                }
+                None => {
+                    unknown_price = true;
+                    "precio desconocido".to_string()
+                }
+            };
+            report.push_str(&format!(
+                "  {}/{}: {} peticiones, {} tokens de entrada, {} de salida, coste estimado {}\n",
+                entry.provider,
+                entry.model,
+                entry.requests,
+                entry.prompt_tokens,
+                entry.completion_tokens,
+                cost
+            ));
+        }
+        report.push_str(&format!("Total estimado: ${:.4}", total_cost));
+        if unknown_price {
+            report.push_str(" (sin contar modelos sin tarifa; usa IAGENT_PRICE_INPUT/IAGENT_PRICE_OUTPUT)");
+        }
+        if self.cache_hits > 0 {
+            report.push_str(&format!(
+                "\nDesde la caché: {} respuestas, ~{} tokens sin facturar",
+                self.cache_hits, self.cached_tokens
+            ));
+        }
+        report
+    }
+}
@@ -0,0 +1,315 @@
+// Validación de datos (Datos > Validación de datos en Excel): listas desplegables,
+// rangos numéricos, fechas y longitud de texto. rust_xlsxwriter no la ofrece en
+// esta versión, así que como el formato condicional se añade al XML de la hoja,
+// tanto en libros existentes como justo después de escribir uno nuevo.
+use crate::conditional_format::ComparisonOperator;
+use crate::excel::{self, CellRange};
+use crate::named_ranges::{self, Target};
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Excel no admite listas escritas de más de 255 caracteres
+const MAX_LIST_CHARS: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueKind {
+    Whole,
+    Decimal,
+    Date,
+    TextLength,
+}
+
+impl ValueKind {
+    fn parse(value: &str) -> Option<ValueKind> {
+        match value {
+            "entero" => Some(ValueKind::Whole),
+            "decimal" => Some(ValueKind::Decimal),
+            "fecha" => Some(ValueKind::Date),
+            "longitud" => Some(ValueKind::TextLength),
+            _ => None,
+        }
+    }
+
+    fn xml_name(&self) -> &'static str {
+        match self {
+            ValueKind::Whole => "whole",
+            ValueKind::Decimal => "decimal",
+            ValueKind::Date => "date",
+            ValueKind::TextLength => "textLength",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationRule {
+    // Desplegable con valores fijos
+    List(Vec<String>),
+    // Desplegable con los valores de un rango (=Listas!$A$2:$A$10)
+    ListRange(String),
+    // Valores de la fórmula ya preparados: números o números de serie de fecha
+    Value {
+        kind: ValueKind,
+        operator: ComparisonOperator,
+        first: String,
+        second: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validation {
+    pub rule: ValidationRule,
+    // Texto que se muestra al seleccionar la celda
+    pub prompt: Option<String>,
+    // Mensaje al introducir un valor no válido
+    pub error: Option<String>,
+    pub allow_blank: bool,
+}
+
+// Opciones del comando `validar`
+#[derive(Debug, Clone)]
+pub struct ValidationOptions {
+    pub file: String,
+    // Hoja!A1:B2, nombre definido o rango de la primera hoja
+    pub target: String,
+    pub validation: Validation,
+}
+
+impl Validation {
+    // Argumentos del comando: lista "A,B,C" | lista =Hoja!A1:A5 | entero|decimal|fecha|longitud <op> <v> [v2],
+    // más mensaje=, error= y vacio=no
+    pub fn parse(args: &[&str], options: &HashMap<&str, &str>) -> Result<Validation> {
+        let kind = args
+            .first()
+            .context("Falta el tipo de validación (lista, entero, decimal, fecha, longitud)")?;
+        let rest = &args[1..];
+        let rule = match *kind {
+            "lista" => {
+                let source = rest.join(" ");
+                let source = source.trim();
+                if source.is_empty() {
+                    bail!("Falta la lista de valores, p. ej. \"Alta,Media,Baja\"");
+                }
+                match source.strip_prefix('=') {
+                    Some(range) => ValidationRule::ListRange(range.to_string()),
+                    None => ValidationRule::List(
+                        source
+                            .trim_matches('"')
+                            .split(',')
+                            .map(|item| item.trim().to_string())
+                            .filter(|item| !item.is_empty())
+                            .collect(),
+                    ),
+                }
+            }
+            other => {
+                let kind = ValueKind::parse(other).context(format!("Tipo de validación desconocido: {}", other))?;
+                let operator = rest
+                    .first()
+                    .and_then(|op| ComparisonOperator::parse(op))
+                    .context("Operador no válido (usa >, >=, <, <=, =, != o entre)")?;
+                let first = rest.get(1).context("Falta el valor de la validación")?;
+                let second = if operator == ComparisonOperator::Between {
+                    Some(rest.get(2).context("'entre' necesita dos valores")?)
+                } else {
+                    None
+                };
+                ValidationRule::Value {
+                    kind,
+                    operator,
+                    first: formula_value(kind, first)?,
+                    second: second.map(|value| formula_value(kind, value)).transpose()?,
+                }
+            }
+        };
+        if let ValidationRule::List(items) = &rule {
+            let length = items.join(",").chars().count();
+            if items.is_empty() {
+                bail!("La lista de valores está vacía");
+            }
+            if length > MAX_LIST_CHARS {
+                bail!(
+                    "La lista ocupa {} caracteres y Excel admite {}; pon los valores en un rango y usa lista =Hoja!A1:A20",
+                    length,
+                    MAX_LIST_CHARS
+                );
+            }
+        }
+        Ok(Validation {
+            rule,
+            prompt: options.get("mensaje").map(|s| s.to_string()),
+            error: options.get("error").map(|s| s.to_string()),
+            allow_blank: !options.get("vacio").is_some_and(|v| matches!(*v, "no" | "false" | "0")),
+        })
+    }
+
+    // Desde los argumentos JSON de una herramienta: {tipo, valores | origen | operador, valor, valor2, mensaje, error, vacio}
+    pub fn from_json(args: &Value) -> Result<Validation> {
+        let text = |key: &str| args.get(key).and_then(Value::as_str).map(str::to_string);
+        let kind = text("tipo").context("Falta 'tipo'")?;
+        let mut parts = vec![kind.clone()];
+        if kind == "lista" {
+            match (args.get("valores"), text("origen")) {
+                (Some(Value::Array(values)), _) => {
+                    let values: Vec<String> = values.iter().filter_map(json_scalar).collect();
+                    parts.push(values.join(","));
+                }
+                (_, Some(range)) => parts.push(format!("={}", range.trim_start_matches('='))),
+                _ => bail!("Una lista necesita 'valores' u 'origen'"),
+            }
+        } else {
+            parts.push(text("operador").context("Falta 'operador'")?);
+            parts.push(args.get("valor").and_then(json_scalar).context("Falta 'valor'")?);
+            if let Some(second) = args.get("valor2").and_then(json_scalar) {
+                parts.push(second);
+            }
+        }
+        let mut options = HashMap::new();
+        let prompt = text("mensaje");
+        let error = text("error");
+        if let Some(prompt) = &prompt {
+            options.insert("mensaje", prompt.as_str());
+        }
+        if let Some(error) = &error {
+            options.insert("error", error.as_str());
+        }
+        if args.get("vacio").and_then(Value::as_bool) == Some(false) {
+            options.insert("vacio", "no");
+        }
+        let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+        Validation::parse(&parts, &options)
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.rule {
+            ValidationRule::List(items) => format!("lista ({})", items.join(", ")),
+            ValidationRule::ListRange(range) => format!("lista de {}", range),
+            ValidationRule::Value {
+                kind,
+                operator,
+                first,
+                second,
+            } => {
+                let show = |value: &str| match kind {
+                    ValueKind::Date => value.parse().map(excel::excel_serial_to_iso).unwrap_or_default(),
+                    _ => value.to_string(),
+                };
+                let name = match kind {
+                    ValueKind::Whole => "número entero",
+                    ValueKind::Decimal => "número",
+                    ValueKind::Date => "fecha",
+                    ValueKind::TextLength => "longitud del texto",
+                };
+                match second {
+                    Some(second) => format!("{} entre {} y {}", name, show(first), show(second)),
+                    None => format!("{} {} {}", name, operator.symbol(), show(first)),
+                }
+            }
+        }
+    }
+
+    fn xml(&self, sqref: &str) -> String {
+        let mut attrs = match &self.rule {
+            ValidationRule::List(_) | ValidationRule::ListRange(_) => " type=\"list\"".to_string(),
+            ValidationRule::Value { kind, operator, .. } => {
+                format!(" type=\"{}\" operator=\"{}\"", kind.xml_name(), operator.xml_name())
+            }
+        };
+        if self.allow_blank {
+            attrs.push_str(" allowBlank=\"1\"");
+        }
+        attrs.push_str(" showInputMessage=\"1\" showErrorMessage=\"1\"");
+        if let Some(error) = &self.error {
+            attrs.push_str(&format!(" error=\"{}\"", xlsx_patch::xml_escape(error)));
+        }
+        if let Some(prompt) = &self.prompt {
+            attrs.push_str(&format!(" prompt=\"{}\"", xlsx_patch::xml_escape(prompt)));
+        }
+        let formulas = match &self.rule {
+            ValidationRule::List(items) => format!("<formula1>\"{}\"</formula1>", xlsx_patch::xml_escape(&items.join(","))),
+            ValidationRule::ListRange(range) => format!("<formula1>{}</formula1>", xlsx_patch::xml_escape(range)),
+            ValidationRule::Value { first, second, .. } => {
+                let mut formulas = format!("<formula1>{}</formula1>", first);
+                if let Some(second) = second {
+                    formulas.push_str(&format!("<formula2>{}</formula2>", second));
+                }
+                formulas
+            }
+        };
+        format!("<dataValidation{} sqref=\"{}\">{}</dataValidation>", attrs, sqref, formulas)
+    }
+}
+
+fn json_scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+// Las fechas se guardan como número de serie; el resto debe ser un número
+fn formula_value(kind: ValueKind, value: &str) -> Result<String> {
+    let value = value.trim();
+    let number = match kind {
+        ValueKind::Date => excel::parse_date_text(value)
+            .context(format!("Fecha no válida: {} (usa AAAA-MM-DD o dd/mm/aaaa)", value))?,
+        _ => value.parse::<f64>().context(format!("Número no válido: {}", value))?,
+    };
+    if matches!(kind, ValueKind::Whole | ValueKind::TextLength) && number.fract() != 0.0 {
+        bail!("{} no es un número entero", value);
+    }
+    Ok(number.to_string())
+}
+
+// Añade la validación al libro y devuelve la descripción del rango
+pub fn apply(options: &ValidationOptions) -> Result<String> {
+    let path = Path::new(&options.file);
+    let mut package = XlsxPackage::open(path)?;
+    let workbook = package.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+    let target = match named_ranges::resolve_target(&named_ranges::defined_names(&workbook), &options.target) {
+        Ok(target) => target,
+        // Un rango sin hoja se refiere a la primera
+        Err(e) => {
+            let range = CellRange::parse(&options.target).ok_or(e)?;
+            let sheet = xlsx_patch::find_tags(&workbook, "sheet")
+                .first()
+                .and_then(|tag| xlsx_patch::xml_attr(tag, "name"))
+                .map(|name| xlsx_patch::xml_unescape(&name))
+                .context("El libro no tiene hojas")?;
+            Target {
+                description: format!("{}!{}", sheet, range),
+                sheet,
+                range,
+                named: false,
+            }
+        }
+    };
+    add_to_sheet(&mut package, &target.sheet, &target.range, &options.validation)?;
+    package.save(path)?;
+    Ok(target.description)
+}
+
+// Añade la validación al XML de la hoja, detrás de las que ya tenga
+pub fn add_to_sheet(package: &mut XlsxPackage, sheet: &str, range: &CellRange, validation: &Validation) -> Result<()> {
+    let element = validation.xml(&range.to_string());
+    package.edit_sheet(sheet, |xml| {
+        let Some(start) = xlsx_patch::find_element_start(&xml, "dataValidations") else {
+            let content = format!("<dataValidations count=\"1\">{}</dataValidations>", element);
+            return xlsx_patch::insert_worksheet_element(&xml, "dataValidations", &content);
+        };
+        let tag_end = start + xml[start..].find('>').context("XML de hoja no válido")?;
+        let close = start + xml[start..].find("</dataValidations>").context("XML de hoja no válido")?;
+        let count = xlsx_patch::find_tags(&xml[start..close], "dataValidation").len() + 1;
+        Ok(format!(
+            "{}<dataValidations count=\"{}\">{}{}{}",
+            &xml[..start],
+            count,
+            &xml[tag_end + 1..close],
+            element,
+            &xml[close..]
+        ))
+    })
+}
@@ -0,0 +1,178 @@
+// Comprobación de los archivos que escribe el agente: nada más guardar, cada
+// archivo se vuelve a abrir (un xlsx con calamine, un csv o un json con su
+// lector, un pdf por su cabecera y su final) y se anota en el manifiesto de la
+// sesión con sus hojas, filas y SHA-256. Un archivo que no se puede abrir es un
+// error de la operación que lo escribió, así un guardado fallido no pasa
+// desapercibido hasta que alguien lo abre. `manifiesto` muestra el de la sesión;
+// con IAGENT_AUDIT_LOG cada entrada se añade además a ese registro, una línea
+// JSON por archivo.
+use crate::convert::{self, FileFormat};
+use crate::crypto;
+use crate::events;
+use crate::excel;
+use crate::settings;
+use anyhow::{bail, Context, Result};
+use calamine::{open_workbook_auto, Reader};
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileRecord {
+    #[serde(rename = "archivo")]
+    pub path: String,
+    #[serde(rename = "hora")]
+    pub written: String,
+    #[serde(rename = "bytes")]
+    pub bytes: u64,
+    #[serde(rename = "sha256")]
+    pub sha256: String,
+    // Vacío en los archivos sin hojas (pdf, json que no es un libro)
+    #[serde(rename = "hojas")]
+    pub sheets: Vec<SheetRecord>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SheetRecord {
+    #[serde(rename = "nombre")]
+    pub name: String,
+    #[serde(rename = "filas")]
+    pub rows: usize,
+}
+
+impl FileRecord {
+    // "ventas.xlsx: 2 hojas (Resumen 5 filas, Detalle 120 filas), 8.4 KB, SHA-256 3fa9c2d1e0ab…"
+    pub fn describe(&self, full_hash: bool) -> String {
+        let hash = if full_hash { self.sha256.clone() } else { format!("{}…", &self.sha256[..12]) };
+        let size = if self.bytes >= 1024 { format!("{:.1} KB", self.bytes as f64 / 1024.0) } else { format!("{} bytes", self.bytes) };
+        if self.sheets.is_empty() {
+            return format!("{}: {}, SHA-256 {}", self.path, size, hash);
+        }
+        let sheets: Vec<String> = self.sheets.iter().map(|s| format!("{} {} filas", s.name, s.rows)).collect();
+        format!("{}: {} hojas ({}), {}, SHA-256 {}", self.path, self.sheets.len(), sheets.join(", "), size, hash)
+    }
+}
+
+// Vuelve a abrir `path` recién escrito y lo anota en el manifiesto; error si no se
+// puede abrir. Se llama donde se escribe cada archivo, como backup::before_write.
+pub fn after_write(path: &Path) -> Result<()> {
+    let record = inspect(path).context(format!(
+        "{} se ha escrito pero no se puede volver a abrir; deshacer {} recupera la versión anterior",
+        path.display(),
+        path.display()
+    ))?;
+    // Los pasos de un mismo guardado (notas, grupos, contraseñas) reescriben el archivo;
+    // si el contenido no ha cambiado desde la última entrada no se repite
+    if remember(&record) {
+        events::notice(format!("🔏 Verificado {}", record.describe(false)));
+        if let Some(log) = settings::current().audit_log() {
+            append_audit(&log, &record);
+        }
+    }
+    events::file_written(path);
+    Ok(())
+}
+
+// Comprueba que `path` se puede abrir, sin anotarlo en el manifiesto: para un
+// temporal que todavía no ha sustituido al archivo de destino
+pub fn check(path: &Path) -> Result<()> {
+    inspect(path).map(|_| ())
+}
+
+// Entradas del manifiesto del agente en curso
+pub fn manifest() -> Vec<FileRecord> {
+    settings::current().manifest().lock().map(|records| records.clone()).unwrap_or_default()
+}
+
+// Guarda el manifiesto de la sesión en JSON
+pub fn save_manifest(path: &Path) -> Result<usize> {
+    let records = manifest();
+    if records.is_empty() {
+        bail!("Todavía no se ha escrito ningún archivo en esta sesión");
+    }
+    fs::write(path, serde_json::to_string_pretty(&records)?).context(format!("No se pudo escribir {}", path.display()))?;
+    Ok(records.len())
+}
+
+fn remember(record: &FileRecord) -> bool {
+    let settings = settings::current();
+    let Ok(mut records) = settings.manifest().lock() else { return true };
+    match records.iter_mut().find(|existing| existing.path == record.path) {
+        Some(existing) if existing.sha256 == record.sha256 => false,
+        Some(existing) => {
+            *existing = record.clone();
+            true
+        }
+        None => {
+            records.push(record.clone());
+            true
+        }
+    }
+}
+
+fn inspect(path: &Path) -> Result<FileRecord> {
+    let content = fs::read(path).context(format!("No se pudo leer {}", path.display()))?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    let sheets = match extension.as_str() {
+        "xlsx" | "xlsm" => {
+            let mut workbook = open_workbook_auto(path).map_err(|e| anyhow::anyhow!("{}", e))?;
+            let mut sheets = Vec::new();
+            for name in workbook.sheet_names().to_vec() {
+                let range = workbook
+                    .worksheet_range(&name)
+                    .context(format!("Falta la hoja {}", name))?
+                    .map_err(|e| anyhow::anyhow!("La hoja {} está dañada: {}", name, e))?;
+                let rows = range.end().map_or(0, |(row, _)| row as usize + 1);
+                sheets.push(SheetRecord { name, rows });
+            }
+            sheets
+        }
+        "pdf" => {
+            let tail = &content[content.len().saturating_sub(1024)..];
+            if !content.starts_with(b"%PDF-") || !String::from_utf8_lossy(tail).contains("%%EOF") {
+                bail!("No es un pdf completo");
+            }
+            Vec::new()
+        }
+        "json" => {
+            let value: serde_json::Value = serde_json::from_slice(&content).context("El JSON no es válido")?;
+            match convert::workbook_from_json(&value) {
+                Ok(data) => records_of(&data),
+                // Un JSON de extraer_json no tiene forma de libro
+                Err(_) => Vec::new(),
+            }
+        }
+        _ if FileFormat::from_path(path).is_some() => records_of(&convert::read_any(path)?),
+        _ => Vec::new(),
+    };
+    Ok(FileRecord {
+        path: path.display().to_string(),
+        written: excel::excel_serial_to_iso(excel::now_serial()),
+        bytes: content.len() as u64,
+        sha256: crypto::hex(&crypto::sha256(&content)),
+        sheets,
+    })
+}
+
+fn records_of(data: &excel::WorkbookData) -> Vec<SheetRecord> {
+    data.sheets
+        .iter()
+        .map(|sheet| SheetRecord {
+            name: sheet.name.clone(),
+            rows: sheet.rows.len(),
+        })
+        .collect()
+}
+
+// Un registro que no se puede escribir se avisa, pero no deshace la operación
+fn append_audit(log: &Path, record: &FileRecord) {
+    let written = serde_json::to_string(record).map_err(anyhow::Error::from).and_then(|line| {
+        let mut file = OpenOptions::new().create(true).append(true).open(log)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    });
+    if let Err(e) = written {
+        events::notice(format!("⚠️  No se pudo anotar {} en el registro de auditoría {}: {:#}", record.path, log.display(), e));
+    }
+}
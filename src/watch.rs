@@ -0,0 +1,241 @@
+// Modo vigilancia (`ia_agent watch <dir> --tarea <tarea.json>`): revisa un
+// directorio cada pocos segundos y, cuando aparece o cambia un libro, ejecuta la
+// tarea configurada (informe desde una plantilla y/o una pregunta al modelo).
+// Sustituye a los guiones con cron que relanzaban el agente sobre cada archivo.
+//
+// {
+//   "patron": "*.xlsx",
+//   "informe": "plantillas/ventas.json",
+//   "pregunta": "¿Qué cliente ha crecido más respecto al mes anterior?",
+//   "salida": "informes/{nombre}_informe.xlsx",
+//   "respuestas": "informes/{nombre}_respuestas.xlsx",
+//   "intervalo": 5,
+//   "procesar_existentes": false
+// }
+//
+// No se usan notificaciones del sistema: se compara la fecha de modificación y el
+// tamaño en cada revisión, y un archivo solo se procesa cuando no ha cambiado entre
+// dos revisiones seguidas, para no leerlo a medio copiar.
+use crate::batch::{self, BatchOptions};
+use crate::config::Config;
+use crate::error::IAgentError;
+use crate::excel::{self, WorkbookData};
+use crate::files;
+use crate::i18n;
+use crate::interrupt;
+use crate::llm;
+use crate::report::{self, ReportOptions};
+use crate::timing::Timings;
+use crate::usage::UsageTracker;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const DEFAULT_PATTERN: &str = "*.xlsx";
+const DEFAULT_INTERVAL_SECS: u64 = 5;
+const DEFAULT_REPORT_OUTPUT: &str = "informes/{nombre}_informe.xlsx";
+const DEFAULT_ANSWERS_OUTPUT: &str = "informes/{nombre}_respuestas.xlsx";
+
+#[derive(Debug, Clone)]
+pub struct WatchSettings {
+    pub dir: PathBuf,
+    pub task: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchTask {
+    #[serde(rename = "patron", default)]
+    pattern: Option<String>,
+    // Plantilla de generar_informe
+    #[serde(rename = "informe", default)]
+    report: Option<String>,
+    #[serde(rename = "pregunta", default)]
+    question: Option<String>,
+    // Rutas relativas al directorio vigilado; {nombre} es el nombre del archivo sin extensión
+    #[serde(rename = "salida", default)]
+    output: Option<String>,
+    #[serde(rename = "respuestas", default)]
+    answers: Option<String>,
+    #[serde(rename = "intervalo", default)]
+    interval: Option<u64>,
+    #[serde(rename = "procesar_existentes", default)]
+    process_existing: bool,
+}
+
+// Fecha de modificación y tamaño con los que se detectan los cambios
+type Signature = (SystemTime, u64);
+
+pub async fn run(config: &Config, system_template: &str, settings: &WatchSettings) -> Result<()> {
+    let task = load_task(&settings.task)?;
+    if task.report.is_none() && task.question.is_none() {
+        bail!("La tarea {} necesita 'informe', 'pregunta' o ambos", settings.task.display());
+    }
+    if !settings.dir.is_dir() {
+        bail!("No existe el directorio {}", settings.dir.display());
+    }
+    let pattern = settings.dir.join(task.pattern.as_deref().unwrap_or(DEFAULT_PATTERN));
+    let pattern = pattern.to_string_lossy().into_owned();
+    let interval = Duration::from_secs(task.interval.unwrap_or(DEFAULT_INTERVAL_SECS).max(1));
+
+    interrupt::install();
+    let client = llm::build_client(&config.http)?;
+    let mut usage_tracker = UsageTracker::default();
+    let mut timings = Timings::default();
+
+    // Firma procesada de cada archivo y cambios vistos que esperan a la siguiente revisión
+    let mut processed: HashMap<PathBuf, Signature> = HashMap::new();
+    let mut pending: HashMap<PathBuf, Signature> = HashMap::new();
+    // Lo que escribe la propia tarea nunca la vuelve a lanzar
+    let mut outputs: HashSet<PathBuf> = HashSet::new();
+    if !task.process_existing {
+        processed = scan(&pattern)?;
+    }
+    println!(
+        "👀 Vigilando {} cada {} s (Ctrl-C para terminar)",
+        pattern,
+        interval.as_secs()
+    );
+    if !processed.is_empty() {
+        println!("ℹ️  {} archivos existentes no se procesan (usa \"procesar_existentes\": true)", processed.len());
+    }
+
+    let mut runs = 0;
+    'watch: loop {
+        let current = match scan(&pattern) {
+            Ok(current) => current,
+            Err(e) => {
+                println!("⚠️  {:#}", e);
+                HashMap::new()
+            }
+        };
+        pending.retain(|path, _| current.contains_key(path));
+        processed.retain(|path, _| current.contains_key(path));
+        for (path, signature) in current {
+            if outputs.contains(&path) || processed.get(&path) == Some(&signature) {
+                continue;
+            }
+            // Igual que en la revisión anterior: el archivo ya terminó de escribirse
+            if pending.get(&path) != Some(&signature) {
+                pending.insert(path, signature);
+                continue;
+            }
+            pending.remove(&path);
+            processed.insert(path.clone(), signature);
+            runs += 1;
+            println!("🔄 [{}] {}", runs, path.display());
+            match process(&client, config, &mut usage_tracker, &mut timings, system_template, &task, settings, &path).await {
+                Ok(Processed { written, interrupted }) => {
+                    for output in written {
+                        println!("✅ {}", output.display());
+                        outputs.insert(output);
+                    }
+                    if interrupted {
+                        println!("⏹ Vigilancia detenida");
+                        break 'watch;
+                    }
+                }
+                Err(e) => println!("❌ {}: {:#}", path.display(), e),
+            }
+        }
+        if interrupt::interruptible(tokio::time::sleep(interval)).await.is_none() {
+            println!("⏹ Vigilancia detenida");
+            break;
+        }
+    }
+    println!("{} archivos procesados", runs);
+    println!("{}", usage_tracker.report());
+    Ok(())
+}
+
+fn load_task(path: &Path) -> Result<WatchTask> {
+    let text = fs::read_to_string(path).context(format!("No se pudo leer la tarea {}", path.display()))?;
+    serde_json::from_str(&text).map_err(|e| {
+        IAgentError::parse(&path.display().to_string(), format!("La tarea no es válida ({})", e)).into()
+    })
+}
+
+// Archivos que coinciden con el patrón, sin los temporales de Excel (~$libro.xlsx)
+fn scan(pattern: &str) -> Result<HashMap<PathBuf, Signature>> {
+    let mut found = HashMap::new();
+    for path in files::expand_pattern(pattern)? {
+        let is_lock = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("~$"));
+        if is_lock {
+            continue;
+        }
+        if let Ok(metadata) = fs::metadata(&path) {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            found.insert(path, (modified, metadata.len()));
+        }
+    }
+    Ok(found)
+}
+
+struct Processed {
+    written: Vec<PathBuf>,
+    // Ctrl-C mientras se esperaba al modelo
+    interrupted: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn process(
+    client: &reqwest::Client,
+    config: &Config,
+    usage_tracker: &mut UsageTracker,
+    timings: &mut Timings,
+    system_template: &str,
+    task: &WatchTask,
+    settings: &WatchSettings,
+    path: &Path,
+) -> Result<Processed> {
+    let file = path.display().to_string();
+    let mut written = Vec::new();
+    if let Some(template) = &task.report {
+        let output = output_path(settings, task.output.as_deref().unwrap_or(DEFAULT_REPORT_OUTPUT), path)?;
+        let outcome = report::generate(&ReportOptions {
+            template: template.clone(),
+            data: file.clone(),
+            output: Some(output.to_string_lossy().into_owned()),
+        })?;
+        written.push(PathBuf::from(outcome.output));
+    }
+    if let Some(question) = &task.question {
+        let output = output_path(settings, task.answers.as_deref().unwrap_or(DEFAULT_ANSWERS_OUTPUT), path)?;
+        let options = BatchOptions {
+            question: question.clone(),
+            pattern: file,
+            output: output.to_string_lossy().into_owned(),
+        };
+        // La pregunta de la tarea decide el idioma de las respuestas si no está fijado
+        let lang = i18n::reply_lang(config.reply_lang, question, config.lang);
+        let template = i18n::with_reply_instruction(system_template.to_string(), Some(lang));
+        let result = batch::run(client, config, usage_tracker, timings, &template, &options).await?;
+        if !result.answers.is_empty() {
+            let workbook = WorkbookData {
+                sheets: vec![batch::answers_sheet(&result.answers)],
+            };
+            excel::save_workbook(&output, &workbook)?;
+            written.push(output);
+        }
+        if result.interrupted {
+            return Ok(Processed { written, interrupted: true });
+        }
+    }
+    Ok(Processed { written, interrupted: false })
+}
+
+// Ruta de salida con {nombre} sustituido, dentro del directorio vigilado si es relativa
+fn output_path(settings: &WatchSettings, template: &str, source: &Path) -> Result<PathBuf> {
+    let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("datos");
+    let relative = PathBuf::from(template.replace("{nombre}", stem));
+    let path = if relative.is_absolute() { relative } else { settings.dir.join(relative) };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).context(format!("No se pudo crear el directorio {}", parent.display()))?;
+    }
+    Ok(path)
+}
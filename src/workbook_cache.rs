@@ -0,0 +1,662 @@
+// Libros cargados durante la sesión, con su resumen para el contexto del modelo.
+// Los cambios se hacen en la copia cargada y se anotan como pendientes hasta
+// `guardar`: si solo son cambios de celdas se escriben en el XML del archivo de
+// una vez, sin volver a generar el libro entero (que en uno grande es lento y
+// pierde fórmulas y gráficos); si hay cambios de estructura se reescribe.
+use crate::convert;
+use crate::excel::{self, CellValue, SheetData, WorkbookData};
+use crate::formulas;
+use crate::named_ranges;
+use crate::progress::ProgressBar;
+use crate::settings;
+use crate::summary;
+use crate::xlsx_patch::{self, XlsxPackage};
+use anyhow::{bail, Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::Semaphore;
+
+#[derive(Clone)]
+pub struct CachedWorkbook {
+    pub path: String,
+    pub data: WorkbookData,
+    pub summary: String,
+    // Estado del archivo cuando se resumió
+    fingerprint: Option<Fingerprint>,
+    // Contenido ya notificado al usuario, para no repetir el aviso
+    acknowledged_hash: Option<u64>,
+    // Leído por streaming: `data` es solo una vista previa y no se puede editar
+    partial: bool,
+    // Cambios sin guardar, en orden
+    pending: Vec<PendingEdit>,
+}
+
+// Cambio hecho en la copia cargada que aún no está en el archivo
+#[derive(Debug, Clone)]
+enum PendingEdit {
+    // Valor o fórmula (sin '=') de una celda, que se puede escribir en el XML
+    Cell {
+        sheet: String,
+        row: usize,
+        col: usize,
+        value: CellValue,
+        formula: Option<String>,
+    },
+    // Filas, columnas, transformaciones u hojas nuevas: hay que reescribir el libro
+    Rewrite,
+}
+
+#[derive(Debug)]
+pub struct SaveOutcome {
+    pub target: String,
+    // Cambios de celdas escritos en el XML del archivo; 0 si se reescribió el libro
+    pub patched_cells: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Fingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl Fingerprint {
+    fn read(path: &Path) -> Option<Fingerprint> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Fingerprint {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash: content_hash(path)?,
+        })
+    }
+}
+
+fn content_hash(path: &Path) -> Option<u64> {
+    let content = fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+// Archivo cargado que ya no coincide con su resumen
+pub struct StaleWorkbook {
+    pub path: String,
+    pub missing: bool,
+}
+
+#[derive(Clone)]
+pub struct WorkbookCache {
+    entries: Vec<CachedWorkbook>,
+    // Presupuesto de tokens de cada resumen
+    summary_tokens: usize,
+}
+
+impl WorkbookCache {
+    pub fn new(summary_tokens: usize) -> WorkbookCache {
+        WorkbookCache {
+            entries: Vec::new(),
+            summary_tokens,
+        }
+    }
+
+    // Registra (o reemplaza) un libro; el último registrado pasa al final
+    pub fn insert(&mut self, path: &str, data: WorkbookData) -> &CachedWorkbook {
+        let summary = summary::summarize_workbook(&data, self.summary_tokens);
+        self.push(path, data, summary, false)
+    }
+
+    // Para lecturas por streaming, donde `data` es solo una vista previa
+    pub fn insert_with_summary(&mut self, path: &str, data: WorkbookData, summary: String) -> &CachedWorkbook {
+        self.push(path, data, summary, true)
+    }
+
+    fn push(&mut self, path: &str, data: WorkbookData, summary: String, partial: bool) -> &CachedWorkbook {
+        self.entries.retain(|entry| entry.path != path);
+        self.entries.push(CachedWorkbook {
+            path: path.to_string(),
+            data,
+            summary,
+            fingerprint: Fingerprint::read(Path::new(path)),
+            acknowledged_hash: None,
+            partial,
+            pending: Vec::new(),
+        });
+        self.entries.last().expect("entrada recién añadida")
+    }
+
+    // Aplica `edit` a una hoja de un libro cargado (sin archivo, el último que
+    // la tenga) y rehace su resumen. Devuelve el archivo y el resultado de `edit`.
+    pub fn edit_sheet<T>(
+        &mut self,
+        file: Option<&str>,
+        sheet: &str,
+        edit: impl FnOnce(&mut SheetData) -> Result<T>,
+    ) -> Result<(String, T)> {
+        let entry = match file {
+            Some(file) => self
+                .entries
+                .iter_mut()
+                .find(|entry| entry.path == file)
+                .context(format!("El archivo {} no está cargado; usa leer_excel {}", file, file))?,
+            None => self
+                .entries
+                .iter_mut()
+                .rev()
+                .find(|entry| entry.data.sheets.iter().any(|s| s.name.eq_ignore_ascii_case(sheet)))
+                .context(format!("Ningún libro cargado tiene la hoja '{}'", sheet))?,
+        };
+        if entry.partial {
+            bail!(
+                "{} se leyó por streaming y solo hay una vista previa en memoria; no se puede editar",
+                entry.path
+            );
+        }
+        let target = entry
+            .data
+            .sheets
+            .iter_mut()
+            .find(|s| s.name.eq_ignore_ascii_case(sheet))
+            .context(format!("No existe la hoja '{}' en {}", sheet, entry.path))?;
+        let result = edit(target)?;
+        entry.summary = summary::summarize_workbook(&entry.data, self.summary_tokens);
+        entry.pending.push(PendingEdit::Rewrite);
+        Ok((entry.path.clone(), result))
+    }
+
+    // Cambia una celda de la copia cargada y anota el cambio para `guardar`.
+    // Devuelve el nombre de la hoja y el valor anterior.
+    pub fn edit_cell(
+        &mut self,
+        file: &str,
+        sheet: &str,
+        (row, col): (usize, usize),
+        value: CellValue,
+        formula: Option<String>,
+    ) -> Result<(String, String)> {
+        let (_, (name, previous)) = self.edit_sheet(Some(file), sheet, |target| {
+            Ok((target.name.clone(), target.set_cell(row, col, value.clone(), formula.clone())))
+        })?;
+        // edit_sheet lo anota como reescritura; es un cambio de una sola celda
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.path == file) {
+            entry.pending.pop();
+            entry.pending.push(PendingEdit::Cell {
+                sheet: name.clone(),
+                row,
+                col,
+                value,
+                formula,
+            });
+        }
+        Ok((name, previous))
+    }
+
+    // Como edit_cell con varias celdas de una hoja, rehaciendo el resumen una sola
+    // vez. Devuelve el nombre de la hoja.
+    pub fn edit_cells(&mut self, file: &str, sheet: &str, cells: Vec<(usize, usize, CellValue, Option<String>)>) -> Result<String> {
+        let (_, name) = self.edit_sheet(Some(file), sheet, |target| {
+            for (row, col, value, formula) in &cells {
+                target.set_cell(*row, *col, value.clone(), formula.clone());
+            }
+            Ok(target.name.clone())
+        })?;
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.path == file) {
+            entry.pending.pop();
+            entry.pending.extend(cells.into_iter().map(|(row, col, value, formula)| PendingEdit::Cell {
+                sheet: name.clone(),
+                row,
+                col,
+                value,
+                formula,
+            }));
+        }
+        Ok(name)
+    }
+
+    // Cambios sin guardar de un libro
+    pub fn pending(&self, file: &str) -> usize {
+        self.get(file).map_or(0, |entry| entry.pending.len())
+    }
+
+    // Añade una hoja nueva a un libro cargado (o sustituye la que tenga ese nombre)
+    pub fn add_sheet(&mut self, file: &str, sheet: SheetData) -> Result<()> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.path == file)
+            .context(format!("El archivo {} no está cargado", file))?;
+        if entry.partial {
+            bail!("{} se leyó por streaming y solo hay una vista previa en memoria; no se puede editar", entry.path);
+        }
+        entry.data.upsert_sheet(sheet);
+        entry.summary = summary::summarize_workbook(&entry.data, self.summary_tokens);
+        entry.pending.push(PendingEdit::Rewrite);
+        Ok(())
+    }
+
+    // Libros con cambios sin guardar
+    pub fn unsaved(&self) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.pending.is_empty())
+            .map(|entry| entry.path.as_str())
+            .collect()
+    }
+
+    // Escribe un libro cargado en su archivo o en `output`. Al guardar en el
+    // propio archivo se da por sincronizado con el disco; con `rename` (guardar_como)
+    // el libro cargado pasa a ser el de `output`. Si hay que reescribirlo y se
+    // perderían fórmulas o gráficos del original, solo se hace con `rewrite`.
+    pub fn save(&mut self, file: &str, output: Option<&str>, rename: bool, rewrite: bool) -> Result<SaveOutcome> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.path == file)
+            .context(format!("El archivo {} no está cargado", file))?;
+        if entry.partial {
+            bail!("{} se leyó por streaming; no hay una copia completa que guardar", entry.path);
+        }
+        let target = output.unwrap_or(&entry.path).to_string();
+        // Si el archivo cambió desde que se leyó, los cambios de celdas se escriben
+        // sobre lo que hay ahora en disco; reescribirlo desde la copia cargada
+        // perdería lo que se escribió después
+        let changed = entry.changed_since_read();
+        let patched_cells = if entry.can_patch(&target) {
+            entry.write_pending_cells(&target)?
+        } else {
+            if changed && !rewrite {
+                bail!(
+                    "{} ha cambiado en el disco desde que se leyó y guardarlo reescribiría el archivo con la copia cargada, perdiendo esos cambios; vuelve a leerlo con leer_excel {} o añade --reescribir para guardarlo igualmente (deshacer lo recupera)",
+                    entry.path,
+                    entry.path
+                );
+            }
+            let lost = excel::lost_on_rewrite(Path::new(&entry.path), &entry.data);
+            if !lost.is_empty() && !rewrite {
+                bail!(
+                    "Para guardar {} hay que reescribirlo a partir de los valores y se perderían {} del original; añade --reescribir para guardarlo igualmente (deshacer lo recupera)",
+                    entry.path,
+                    lost.join(" y ")
+                );
+            }
+            let written = match rewrite && is_xlsx(&target) {
+                true => excel::overwrite_workbook(Path::new(&target), &entry.data),
+                false => convert::write_any(Path::new(&target), &entry.data),
+            };
+            written.context(format!("No se pudo guardar {}", target))?;
+            0
+        };
+        if output.is_none() || rename {
+            self.entries.retain(|other| other.path == file || other.path != target);
+            let entry = self
+                .entries
+                .iter_mut()
+                .find(|entry| entry.path == file)
+                .expect("libro cargado");
+            entry.path = target.clone();
+            entry.fingerprint = Fingerprint::read(Path::new(&target));
+            entry.acknowledged_hash = None;
+            entry.pending.clear();
+            // La copia cargada no tiene lo que cambió en disco: se vuelve a leer
+            if changed && patched_cells > 0 {
+                entry.reread(self.summary_tokens)?;
+            }
+        }
+        Ok(SaveOutcome { target, patched_cells })
+    }
+
+    // Vuelve a leer un libro cargado después de que otro comando lo haya escrito
+    // en disco, con los cambios de celdas sin guardar aplicados encima. Si hay
+    // cambios de estructura pendientes se deja como estaba y guardar pedirá
+    // --reescribir antes de pisar lo escrito. Un libro no cargado no se toca.
+    pub fn reload(&mut self, file: &str) -> Result<()> {
+        let summary_tokens = self.summary_tokens;
+        let Some(entry) = self.entries.iter_mut().find(|entry| entry.path == file) else {
+            return Ok(());
+        };
+        // Sin cambios en disco no hay nada que leer; leído por streaming se
+        // ofrecerá recargarlo como cualquier archivo cambiado
+        if entry.partial || !entry.changed_since_read() {
+            return Ok(());
+        }
+        if entry.pending.iter().any(|edit| matches!(edit, PendingEdit::Rewrite)) {
+            bail!(
+                "La copia cargada de {} tiene cambios de estructura sin guardar y no se ha vuelto a leer; guardar pedirá --reescribir para no perder lo que se acaba de escribir",
+                file
+            );
+        }
+        entry.reread(summary_tokens)
+    }
+
+    // Archivos modificados fuera del agente desde que se leyeron. Solo se
+    // calcula el hash si cambian la fecha o el tamaño; los avisos ya
+    // confirmados por el usuario no se repiten hasta el siguiente cambio.
+    pub fn changed_on_disk(&mut self) -> Vec<StaleWorkbook> {
+        let mut stale = Vec::new();
+        for entry in &mut self.entries {
+            let Some(previous) = &mut entry.fingerprint else {
+                continue;
+            };
+            let path = Path::new(&entry.path);
+            let Ok(metadata) = fs::metadata(path) else {
+                if entry.acknowledged_hash != Some(0) {
+                    stale.push(StaleWorkbook {
+                        path: entry.path.clone(),
+                        missing: true,
+                    });
+                }
+                continue;
+            };
+            let modified = metadata.modified().ok();
+            if modified == previous.modified && metadata.len() == previous.len {
+                continue;
+            }
+            let Some(hash) = content_hash(path) else {
+                continue;
+            };
+            if hash == previous.hash {
+                // Solo cambió la fecha: el resumen sigue siendo válido
+                previous.modified = modified;
+                previous.len = metadata.len();
+            } else if entry.acknowledged_hash != Some(hash) {
+                stale.push(StaleWorkbook {
+                    path: entry.path.clone(),
+                    missing: false,
+                });
+            }
+        }
+        stale
+    }
+
+    // El usuario decidió seguir con el resumen antiguo
+    pub fn acknowledge(&mut self, path: &str) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.path == path) {
+            entry.acknowledged_hash = Some(content_hash(Path::new(path)).unwrap_or(0));
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&CachedWorkbook> {
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+
+    // Hoja por nombre, buscando desde el último libro cargado; sin nombre,
+    // la primera hoja del libro activo. Devuelve también el archivo.
+    pub fn find_sheet(&self, name: Option<&str>) -> Option<(&str, &SheetData)> {
+        self.entries.iter().rev().find_map(|entry| {
+            let sheet = match name {
+                Some(name) => entry.data.sheets.iter().find(|s| s.name.eq_ignore_ascii_case(name))?,
+                None => entry.data.sheets.first()?,
+            };
+            Some((entry.path.as_str(), sheet))
+        })
+    }
+
+    // Nombres de archivo y de hojas para las variables de las plantillas de prompt
+    pub fn sheet_lists(&self) -> Vec<(String, Vec<String>)> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let sheets = entry.data.sheets.iter().map(|s| s.name.clone()).collect();
+                (entry.path.clone(), sheets)
+            })
+            .collect()
+    }
+}
+
+impl CachedWorkbook {
+    // Los cambios pendientes se pueden escribir en el XML: son solo de celdas, el
+    // origen y el destino son xlsx y el archivo existía al leerlo. Si ha cambiado
+    // desde entonces, se escriben sobre lo que tiene ahora.
+    fn can_patch(&self, target: &str) -> bool {
+        !self.pending.is_empty()
+            && self.pending.iter().all(|edit| matches!(edit, PendingEdit::Cell { .. }))
+            && is_xlsx(&self.path)
+            && is_xlsx(target)
+            && self.fingerprint.is_some()
+    }
+
+    fn changed_since_read(&self) -> bool {
+        self.fingerprint.is_some() && Fingerprint::read(Path::new(&self.path)) != self.fingerprint
+    }
+
+    // Lee de nuevo el archivo (con las fórmulas, si se leyeron) y le aplica los
+    // cambios de celdas pendientes
+    fn reread(&mut self, summary_tokens: usize) -> Result<()> {
+        let mut data = convert::read_any(Path::new(&self.path))?;
+        if self.data.sheets.iter().any(|sheet| !sheet.formulas.is_empty()) {
+            formulas::read(&self.path, &mut data)?;
+        }
+        for edit in &self.pending {
+            if let PendingEdit::Cell { sheet, row, col, value, formula } = edit {
+                if let Some(target) = data.sheets.iter_mut().find(|s| s.name == *sheet) {
+                    target.set_cell(*row, *col, value.clone(), formula.clone());
+                }
+            }
+        }
+        self.summary = summary::summarize_workbook(&data, summary_tokens);
+        self.data = data;
+        self.fingerprint = Fingerprint::read(Path::new(&self.path));
+        self.acknowledged_hash = None;
+        Ok(())
+    }
+
+    // Escribe los cambios de celdas pendientes en una copia del archivo leído
+    // (o en él mismo); el último cambio de cada celda es el que vale
+    fn write_pending_cells(&self, target: &str) -> Result<usize> {
+        let mut cells: BTreeMap<String, BTreeMap<(usize, usize), String>> = BTreeMap::new();
+        for edit in &self.pending {
+            let PendingEdit::Cell { sheet, row, col, value, formula } = edit else {
+                continue;
+            };
+            let content = match formula {
+                Some(formula) => xlsx_patch::formula_content(formula, value),
+                None => xlsx_patch::cell_content(value)
+                    .context(format!("No se puede escribir el valor de error '{}'", value))?,
+            };
+            cells.entry(sheet.clone()).or_default().insert((*row, *col), content);
+        }
+        let mut package = XlsxPackage::open(Path::new(&self.path))?;
+        let mut count = 0;
+        for (sheet, cells) in cells {
+            let cells: Vec<(usize, usize, String)> = cells.into_iter().map(|((row, col), content)| (row, col, content)).collect();
+            count += cells.len();
+            package.write_cells(&sheet, &cells)?;
+        }
+        // Las fórmulas que dependen de las celdas cambiadas guardan su valor anterior
+        let workbook = package.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+        package.write_part("xl/workbook.xml", named_ranges::with_full_calc_on_load(&workbook));
+        package
+            .save(Path::new(target))
+            .context(format!("No se pudo guardar {}", target))?;
+        Ok(count)
+    }
+}
+
+fn is_xlsx(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|e| e.eq_ignore_ascii_case("xlsx"))
+}
+
+// Lee varios archivos en paralelo en el pool de tareas bloqueantes, con tantos
+// hilos como núcleos. Devuelve los resultados en el mismo orden que las rutas.
+pub async fn load_many(paths: Vec<PathBuf>) -> Vec<(PathBuf, Result<WorkbookData>)> {
+    let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let semaphore = Arc::new(Semaphore::new(workers));
+    let handles: Vec<_> = paths
+        .into_iter()
+        .map(|path| {
+            let semaphore = Arc::clone(&semaphore);
+            tokio::spawn(settings::inherit(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let task_path = path.clone();
+                let result = tokio::task::spawn_blocking(settings::inherit_blocking(move || convert::read_any(&task_path)))
+                    .await
+                    .context("La tarea de lectura terminó de forma inesperada")
+                    .and_then(|result| result);
+                (path, result)
+            }))
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(handles.len());
+    let mut progress = ProgressBar::new("Leyendo archivos", handles.len());
+    for handle in handles {
+        if let Ok(result) = handle.await {
+            progress.inc(&result.0.display().to_string());
+            results.push(result);
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notes::{self, NoteAction, NoteOptions};
+    use calamine::{open_workbook, DataType, Reader, Xlsx};
+
+    // Libro con Importe en B2:B3 y una fórmula =B2*2 en C2, ya cargado
+    fn loaded(name: &str) -> (WorkbookCache, String) {
+        let path = crate::paths::test_dir(name).join("datos.xlsx");
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Datos").unwrap();
+        sheet.write_string(0, 0, "Cliente").unwrap();
+        sheet.write_string(0, 1, "Importe").unwrap();
+        sheet.write_string(1, 0, "Ana").unwrap();
+        sheet.write_number(1, 1, 10.0).unwrap();
+        sheet.write_formula(1, 2, "=B2*2").unwrap();
+        sheet.write_string(2, 0, "Luis").unwrap();
+        sheet.write_number(2, 1, 30.0).unwrap();
+        workbook.save(&path).unwrap();
+        let file = path.display().to_string();
+        let mut cache = WorkbookCache::new(500);
+        cache.insert(&file, convert::read_any(&path).unwrap());
+        (cache, file)
+    }
+
+    fn read_back(file: &str) -> (calamine::Range<DataType>, calamine::Range<String>) {
+        let mut workbook: Xlsx<_> = open_workbook(file).unwrap();
+        let values = workbook.worksheet_range("Datos").unwrap().unwrap();
+        let formulas = workbook.worksheet_formula("Datos").unwrap().unwrap();
+        (values, formulas)
+    }
+
+    #[test]
+    fn cell_edits_are_patched_into_the_file() {
+        let (mut cache, file) = loaded("cell_edits_are_patched_into_the_file");
+        cache.edit_cell(&file, "datos", (1, 1), CellValue::Number(12.0), None).unwrap();
+        cache.edit_cell(&file, "Datos", (1, 1), CellValue::Number(15.0), None).unwrap();
+        cache.edit_cells(&file, "Datos", vec![(3, 0, CellValue::Text("Eva".to_string()), None), (2, 2, CellValue::Empty, Some("B3*2".to_string()))]).unwrap();
+        assert!(cache.get(&file).unwrap().can_patch(&file));
+
+        // El último cambio de cada celda es el que vale
+        let outcome = cache.save(&file, None, false, false).unwrap();
+        assert_eq!(outcome.patched_cells, 3);
+        assert_eq!(cache.pending(&file), 0);
+        let (values, formulas) = read_back(&file);
+        assert_eq!(values.get_value((1, 1)), Some(&DataType::Float(15.0)));
+        assert_eq!(values.get_value((3, 0)), Some(&DataType::String("Eva".to_string())));
+        assert_eq!(formulas.get_value((1, 2)).map(String::as_str), Some("B2*2"));
+        assert_eq!(formulas.get_value((2, 2)).map(String::as_str), Some("B3*2"));
+    }
+
+    #[test]
+    fn saving_a_copy_patches_the_copy_only() {
+        let (mut cache, file) = loaded("saving_a_copy_patches_the_copy_only");
+        let copy = file.replace("datos.xlsx", "copia.xlsx");
+        cache.edit_cell(&file, "Datos", (2, 1), CellValue::Number(31.0), None).unwrap();
+        assert_eq!(cache.save(&file, Some(&copy), false, false).unwrap().patched_cells, 1);
+        assert_eq!(read_back(&copy).0.get_value((2, 1)), Some(&DataType::Float(31.0)));
+        assert_eq!(read_back(&file).0.get_value((2, 1)), Some(&DataType::Float(30.0)));
+        assert_eq!(cache.pending(&file), 1);
+    }
+
+    #[test]
+    fn structural_or_foreign_changes_are_not_patched() {
+        let (mut cache, file) = loaded("structural_or_foreign_changes_are_not_patched");
+        assert!(!cache.get(&file).unwrap().can_patch(&file), "sin cambios no hay nada que escribir");
+        cache.edit_cell(&file, "Datos", (1, 1), CellValue::Number(11.0), None).unwrap();
+        assert!(!cache.get(&file).unwrap().can_patch(&file.replace(".xlsx", ".csv")));
+
+        let (mut cache, file) = loaded("structural_changes_rewrite_the_workbook");
+        cache.edit_cell(&file, "Datos", (1, 1), CellValue::Number(11.0), None).unwrap();
+        cache.add_sheet(&file, SheetData::new("Nueva")).unwrap();
+        assert!(!cache.get(&file).unwrap().can_patch(&file));
+        // La fórmula de C2 no se leyó: reescribir el libro la perdería
+        let error = cache.save(&file, None, false, false).unwrap_err();
+        assert!(error.to_string().contains("1 fórmula(s)"), "{:#}", error);
+        assert_eq!(read_back(&file).1.get_value((1, 2)).map(String::as_str), Some("B2*2"));
+        assert_eq!(cache.save(&file, None, false, true).unwrap().patched_cells, 0);
+    }
+
+    fn note(file: &str, action: NoteAction) -> Option<String> {
+        let options = NoteOptions { file: file.to_string(), cell: "Datos!B3".to_string(), action };
+        notes::apply(&options).unwrap().previous.map(|note| note.text)
+    }
+
+    #[test]
+    fn changes_made_on_disk_after_reading_are_kept() {
+        let (mut cache, file) = loaded("changes_made_on_disk_after_reading_are_kept");
+        cache.edit_cell(&file, "Datos", (1, 1), CellValue::Number(99.0), None).unwrap();
+        // Otro comando escribe en el archivo después de leerlo
+        note(&file, NoteAction::Write("revisar".to_string()));
+        assert_eq!(cache.save(&file, None, false, false).unwrap().patched_cells, 1);
+        assert_eq!(note(&file, NoteAction::Show).as_deref(), Some("revisar"));
+        assert_eq!(read_back(&file).0.get_value((1, 1)), Some(&DataType::Float(99.0)));
+
+        // Con cambios de estructura habría que reescribirlo desde la copia cargada
+        cache.add_sheet(&file, SheetData::new("Nueva")).unwrap();
+        note(&file, NoteAction::Write("otra".to_string()));
+        let error = cache.save(&file, None, false, false).unwrap_err();
+        assert!(error.to_string().contains("ha cambiado en el disco"), "{:#}", error);
+        assert_eq!(note(&file, NoteAction::Show).as_deref(), Some("otra"));
+    }
+
+    #[test]
+    fn reloading_keeps_unsaved_cell_edits() {
+        let (mut cache, file) = loaded("reloading_keeps_unsaved_cell_edits");
+        cache.edit_cell(&file, "Datos", (2, 1), CellValue::Number(31.0), None).unwrap();
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Datos").unwrap();
+        sheet.write_string(0, 0, "Cliente").unwrap();
+        sheet.write_string(1, 0, "Eva").unwrap();
+        workbook.save(&file).unwrap();
+
+        cache.reload(&file).unwrap();
+        let data = &cache.get(&file).unwrap().data.sheets[0];
+        assert_eq!(data.rows[1][0], CellValue::Text("Eva".to_string()));
+        assert_eq!(data.rows[2][1], CellValue::Number(31.0));
+        assert_eq!(cache.pending(&file), 1);
+        assert!(cache.changed_on_disk().is_empty());
+
+        // Sin cambios en disco no se lee nada; con cambios de estructura no se puede
+        cache.add_sheet(&file, SheetData::new("Nueva")).unwrap();
+        cache.reload(&file).unwrap();
+        workbook.save(&file).unwrap();
+        assert!(cache.reload(&file).is_err());
+    }
+
+    #[test]
+    fn formulas_read_with_the_workbook_survive_a_rewrite() {
+        let (mut cache, file) = loaded("formulas_read_with_the_workbook_survive_a_rewrite");
+        let mut data = convert::read_any(Path::new(&file)).unwrap();
+        crate::formulas::read(&file, &mut data).unwrap();
+        cache.insert(&file, data);
+        cache.add_sheet(&file, SheetData::new("Nueva")).unwrap();
+        assert_eq!(cache.save(&file, None, false, false).unwrap().patched_cells, 0);
+        assert_eq!(read_back(&file).1.get_value((1, 2)).map(String::as_str), Some("B2*2"));
+    }
+
+    #[test]
+    fn error_values_are_refused() {
+        let (mut cache, file) = loaded("error_values_are_refused");
+        cache.edit_cell(&file, "Datos", (1, 1), CellValue::Error("#N/A".to_string()), None).unwrap();
+        let error = cache.get(&file).unwrap().write_pending_cells(&file).unwrap_err();
+        assert!(error.to_string().contains("valor de error"));
+    }
+}
@@ -0,0 +1,1136 @@
+// Edición directa del XML dentro de un .xlsx existente.
+// Se usa para funciones que rust_xlsxwriter no expone y para no perder
+// el contenido original del libro al añadirlas.
+use crate::backup;
+use crate::verify;
+use anyhow::{bail, Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::QName;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+// Tipo de la relación de una hoja con sus notas
+pub const COMMENTS_RELATIONSHIP: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/comments";
+// Tipo de la relación del libro con su cadena de cálculo (xl/calcChain.xml)
+pub const CALC_CHAIN_RELATIONSHIP: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/calcChain";
+// Tipo de la relación de una hoja con cada una de sus tablas
+pub const TABLE_RELATIONSHIP: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/table";
+// Relaciones de una parte que aún no tiene ninguna
+pub const EMPTY_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\"></Relationships>";
+
+// Orden de los elementos hijos de <worksheet> según el esquema OOXML
+const WORKSHEET_ORDER: &[&str] = &[
+    "sheetPr",
+    "dimension",
+    "sheetViews",
+    "sheetFormatPr",
+    "cols",
+    "sheetData",
+    "sheetCalcPr",
+    "sheetProtection",
+    "protectedRanges",
+    "scenarios",
+    "autoFilter",
+    "sortState",
+    "dataConsolidate",
+    "customSheetViews",
+    "mergeCells",
+    "phoneticPr",
+    "conditionalFormatting",
+    "dataValidations",
+    "hyperlinks",
+    "printOptions",
+    "pageMargins",
+    "pageSetup",
+    "headerFooter",
+    "rowBreaks",
+    "colBreaks",
+    "customProperties",
+    "cellWatches",
+    "ignoredErrors",
+    "smartTags",
+    "drawing",
+    "legacyDrawing",
+    "legacyDrawingHF",
+    "drawingHF",
+    "picture",
+    "oleObjects",
+    "controls",
+    "webPublishItems",
+    "tableParts",
+    "extLst",
+];
+
+// Libro abierto como conjunto de partes XML modificables
+pub struct XlsxPackage {
+    parts: Vec<(String, Vec<u8>)>,
+    modified: HashMap<String, String>,
+}
+
+impl XlsxPackage {
+    pub fn open(path: &Path) -> Result<XlsxPackage> {
+        let file = File::open(path).context(format!("No se pudo abrir {}", path.display()))?;
+        let mut archive = ZipArchive::new(file)
+            .context(format!("{} no es un archivo xlsx válido", path.display()))?;
+        let mut parts = Vec::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            parts.push((entry.name().to_string(), content));
+        }
+        Ok(XlsxPackage {
+            parts,
+            modified: HashMap::new(),
+        })
+    }
+
+    pub fn read_part(&self, name: &str) -> Option<String> {
+        if let Some(content) = self.modified.get(name) {
+            return Some(content.clone());
+        }
+        self.parts
+            .iter()
+            .find(|(part, _)| part == name)
+            .map(|(_, content)| String::from_utf8_lossy(content).into_owned())
+    }
+
+    pub fn has_part(&self, name: &str) -> bool {
+        self.parts.iter().any(|(part, _)| part == name)
+    }
+
+    pub fn part_names(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().map(|(part, _)| part.as_str())
+    }
+
+    // Primer `<prefijo>N.<extensión>` que no existe en el paquete
+    pub fn free_part(&self, prefix: &str, extension: &str) -> String {
+        (1..)
+            .map(|n| format!("{}{}.{}", prefix, n, extension))
+            .find(|name| !self.has_part(name))
+            .unwrap_or_default()
+    }
+
+    pub fn remove_part(&mut self, name: &str) {
+        self.parts.retain(|(part, _)| part != name);
+        self.modified.remove(name);
+    }
+
+    pub fn write_part(&mut self, name: &str, content: String) {
+        if !self.parts.iter().any(|(part, _)| part == name) {
+            self.parts.push((name.to_string(), Vec::new()));
+        }
+        self.modified.insert(name.to_string(), content);
+    }
+
+    // Ruta interna (xl/worksheets/sheetN.xml) de la hoja con ese nombre
+    pub fn sheet_part(&self, sheet_name: &str) -> Result<String> {
+        let workbook = self.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+        let rels = self
+            .read_part("xl/_rels/workbook.xml.rels")
+            .context("Falta xl/_rels/workbook.xml.rels")?;
+        sheet_part_in(&workbook, &rels, sheet_name)
+    }
+
+    // Aplica una transformación al XML de una hoja
+    pub fn edit_sheet<F>(&mut self, sheet_name: &str, edit: F) -> Result<()>
+    where
+        F: FnOnce(String) -> Result<String>,
+    {
+        let part = self.sheet_part(sheet_name)?;
+        let xml = self.read_part(&part).context(format!("Falta la parte {}", part))?;
+        let edited = edit(xml)?;
+        self.write_part(&part, edited);
+        Ok(())
+    }
+
+    // Cambia celdas de una hoja como `write_cells` y retira la cadena de cálculo,
+    // que podría nombrar fórmulas que ya no existen
+    pub fn write_cells(&mut self, sheet_name: &str, cells: &[(usize, usize, String)]) -> Result<()> {
+        self.edit_sheet(sheet_name, |xml| write_cells(&xml, cells))?;
+        self.drop_calc_chain();
+        Ok(())
+    }
+
+    // Quita xl/calcChain.xml con su relación y su tipo de contenido. Es solo una
+    // caché del orden de cálculo: Excel la rehace al abrir el libro, pero si nombra
+    // una celda que ya no tiene fórmula considera el archivo dañado y lo "repara"
+    pub fn drop_calc_chain(&mut self) {
+        let mut parts = vec!["xl/calcChain.xml".to_string()];
+        if let Some(mut rels) = self.read_part("xl/_rels/workbook.xml.rels") {
+            let tags: Vec<String> = find_tags(&rels, "Relationship")
+                .into_iter()
+                .filter(|tag| xml_attr(tag, "Type").as_deref() == Some(CALC_CHAIN_RELATIONSHIP))
+                .collect();
+            if !tags.is_empty() {
+                for tag in &tags {
+                    if let Some(target) = xml_attr(tag, "Target") {
+                        parts.push(resolve_part("xl", &xml_unescape(&target)));
+                    }
+                    rels = remove_element(&rels, tag, "Relationship");
+                }
+                self.write_part("xl/_rels/workbook.xml.rels", rels);
+            }
+        }
+        if let Some(types) = self.read_part("[Content_Types].xml") {
+            let overrides: Vec<String> = find_tags(&types, "Override")
+                .into_iter()
+                .filter(|tag| {
+                    xml_attr(tag, "PartName").is_some_and(|name| parts.iter().any(|part| name.trim_start_matches('/') == part))
+                })
+                .collect();
+            if !overrides.is_empty() {
+                let types = overrides.iter().fold(types, |types, tag| remove_element(&types, tag, "Override"));
+                self.write_part("[Content_Types].xml", types);
+            }
+        }
+        for part in &parts {
+            self.remove_part(part);
+        }
+    }
+
+    // Inserta una hoja como primera pestaña (o reemplaza su contenido si ya existe)
+    // sin tocar el resto de partes del libro
+    pub fn insert_first_sheet(&mut self, sheet_name: &str, sheet_xml: String) -> Result<()> {
+        if let Ok(part) = self.sheet_part(sheet_name) {
+            self.write_part(&part, sheet_xml);
+            return Ok(());
+        }
+        let workbook = self.read_part("xl/workbook.xml").context("Falta xl/workbook.xml")?;
+        let rels = self
+            .read_part("xl/_rels/workbook.xml.rels")
+            .context("Falta xl/_rels/workbook.xml.rels")?;
+        let types = self
+            .read_part("[Content_Types].xml")
+            .context("Falta [Content_Types].xml")?;
+
+        let next_part = (1..)
+            .find(|n| !self.parts.iter().any(|(name, _)| *name == format!("xl/worksheets/sheet{}.xml", n)))
+            .unwrap_or(1);
+        let part = format!("xl/worksheets/sheet{}.xml", next_part);
+        let rel_id = next_relationship_id(&rels);
+        let sheet_id = find_tags(&workbook, "sheet")
+            .iter()
+            .filter_map(|tag| xml_attr(tag, "sheetId")?.parse::<u32>().ok())
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        let Some(sheets_at) = find_element_start(&workbook, "sheets") else {
+            bail!("xl/workbook.xml no contiene <sheets>");
+        };
+        let sheets_open = sheets_at + workbook[sheets_at..].find('>').context("XML de libro no válido")? + 1;
+        let mut new_workbook = format!(
+            "{}<sheet name=\"{}\" sheetId=\"{}\" r:id=\"{}\"/>{}",
+            &workbook[..sheets_open],
+            xml_escape(sheet_name),
+            sheet_id,
+            rel_id,
+            &workbook[sheets_open..]
+        );
+        // Los índices de hoja se desplazan una posición
+        new_workbook = shift_attr(&new_workbook, "localSheetId");
+        new_workbook = match find_tags(&new_workbook, "workbookView").first() {
+            Some(view) if xml_attr(view, "activeTab").is_some() => shift_attr(&new_workbook, "activeTab"),
+            Some(view) => new_workbook.replacen(view, &view.replacen("<workbookView", "<workbookView activeTab=\"1\"", 1), 1),
+            None => new_workbook,
+        };
+
+        let relationship = format!(
+            "<Relationship Id=\"{}\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" Target=\"worksheets/sheet{}.xml\"/>",
+            rel_id, next_part
+        );
+        let new_rels = rels.replacen("</Relationships>", &format!("{}</Relationships>", relationship), 1);
+        let override_tag = format!(
+            "<Override PartName=\"/{}\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>",
+            part
+        );
+        let new_types = types.replacen("</Types>", &format!("{}</Types>", override_tag), 1);
+
+        self.write_part("xl/workbook.xml", new_workbook);
+        self.write_part("xl/_rels/workbook.xml.rels", new_rels);
+        self.write_part("[Content_Types].xml", new_types);
+        self.write_part(&part, sheet_xml);
+        Ok(())
+    }
+
+    // Guarda el paquete; se escribe primero a un temporal para no corromper el original
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, content) in &self.parts {
+            writer.start_file(name.as_str(), options)?;
+            match self.modified.get(name) {
+                Some(modified) => writer.write_all(modified.as_bytes())?,
+                None => writer.write_all(content)?,
+            }
+        }
+        let buffer = writer.finish()?.into_inner();
+        let tmp = path.with_extension("xlsx.tmp");
+        fs::write(&tmp, buffer).context(format!("No se pudo escribir {}", tmp.display()))?;
+        backup::before_write(path)?;
+        fs::rename(&tmp, path).context(format!("No se pudo reemplazar {}", path.display()))?;
+        verify::after_write(path)?;
+        Ok(())
+    }
+}
+
+// Ruta interna de una hoja a partir de xl/workbook.xml y sus relaciones
+pub fn sheet_part_in(workbook: &str, rels: &str, sheet_name: &str) -> Result<String> {
+    let rel_id = find_tags(workbook, "sheet")
+        .into_iter()
+        .find(|tag| xml_attr(tag, "name").is_some_and(|name| xml_unescape(&name).eq_ignore_ascii_case(sheet_name)))
+        .and_then(|tag| xml_attr(&tag, "r:id"))
+        .context(format!("No existe la hoja '{}'", sheet_name))?;
+    let target = find_tags(rels, "Relationship")
+        .into_iter()
+        .find(|tag| xml_attr(tag, "Id").as_deref() == Some(rel_id.as_str()))
+        .and_then(|tag| xml_attr(&tag, "Target"))
+        .context(format!("No se encontró la relación {} de la hoja", rel_id))?;
+    Ok(resolve_part("xl", &target))
+}
+
+// Ruta dentro del paquete del destino de una relación de las partes de `dir`
+// ("../comments1.xml" desde xl/worksheets es xl/comments1.xml)
+pub fn resolve_part(dir: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+    for piece in target.split('/') {
+        match piece {
+            ".." => {
+                parts.pop();
+            }
+            "." | "" => {}
+            piece => parts.push(piece),
+        }
+    }
+    parts.join("/")
+}
+
+// Destino de la primera relación de ese tipo
+pub fn relationship_target(rels: &str, kind: &str) -> Option<String> {
+    find_tags(rels, "Relationship")
+        .into_iter()
+        .find(|tag| xml_attr(tag, "Type").as_deref() == Some(kind))
+        .and_then(|tag| xml_attr(&tag, "Target"))
+        .map(|target| xml_unescape(&target))
+}
+
+// Destinos de todas las relaciones de ese tipo
+pub fn relationship_targets(rels: &str, kind: &str) -> Vec<String> {
+    find_tags(rels, "Relationship")
+        .into_iter()
+        .filter(|tag| xml_attr(tag, "Type").as_deref() == Some(kind))
+        .filter_map(|tag| xml_attr(&tag, "Target"))
+        .map(|target| xml_unescape(&target))
+        .collect()
+}
+
+// Añade una relación con la parte (ruta absoluta en el paquete); devuelve las
+// relaciones y el Id que se le dio
+pub fn add_relationship(rels: &str, kind: &str, part: &str) -> (String, String) {
+    let id = next_relationship_id(rels);
+    let rels = rels.replacen(
+        "</Relationships>",
+        &format!("<Relationship Id=\"{}\" Type=\"{}\" Target=\"/{}\"/></Relationships>", id, kind, part),
+        1,
+    );
+    (rels, id)
+}
+
+// Quita un elemento a partir de su etiqueta de apertura (y su cierre si no es vacía)
+fn remove_element(xml: &str, tag: &str, name: &str) -> String {
+    let Some(start) = xml.find(tag) else {
+        return xml.to_string();
+    };
+    let mut end = start + tag.len();
+    if !tag.ends_with("/>") {
+        let close = format!("</{}>", name);
+        end = xml[end..].find(&close).map_or(end, |found| end + found + close.len());
+    }
+    format!("{}{}", &xml[..start], &xml[end..])
+}
+
+// Declara el espacio de nombres r: en <worksheet> para usar atributos r:id
+pub fn with_relationships_namespace(xml: &str) -> String {
+    if xml.contains("xmlns:r=") {
+        return xml.to_string();
+    }
+    xml.replacen(
+        "<worksheet ",
+        "<worksheet xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\" ",
+        1,
+    )
+}
+
+// Primer rIdN libre en un archivo de relaciones
+pub fn next_relationship_id(rels: &str) -> String {
+    let ids: Vec<String> = find_tags(rels, "Relationship")
+        .iter()
+        .filter_map(|tag| xml_attr(tag, "Id"))
+        .collect();
+    (1..).map(|n| format!("rId{}", n)).find(|id| !ids.contains(id)).unwrap_or_default()
+}
+
+// XML de una hoja y de sus relaciones, para lo que calamine no expone
+pub struct SheetXml {
+    pub name: String,
+    pub xml: String,
+    // xl/worksheets/_rels/sheetN.xml.rels, si la hoja tiene relaciones
+    pub rels: Option<String>,
+    // xl/commentsN.xml, si la hoja tiene notas
+    pub comments: Option<String>,
+    // xl/tables/tableN.xml de cada tabla de la hoja
+    pub tables: Vec<String>,
+}
+
+// Lee el XML de todas las hojas abriendo el archivo una sola vez
+pub fn read_sheet_xml(path: &Path) -> Result<Vec<SheetXml>> {
+    let Some(mut reader) = SheetXmlReader::open(path)? else {
+        return Ok(Vec::new());
+    };
+    Ok(reader.sheet_names().iter().filter_map(|name| reader.sheet(name)).collect())
+}
+
+// Lector del XML de las hojas bajo demanda: el paquete queda abierto y cada hoja
+// se descomprime cuando se pide, para no tener todas en memoria a la vez
+pub struct SheetXmlReader {
+    archive: ZipArchive<File>,
+    workbook: String,
+    rels: String,
+}
+
+impl SheetXmlReader {
+    // `None` si el paquete no tiene libro (no es un xlsx normal)
+    pub fn open(path: &Path) -> Result<Option<SheetXmlReader>> {
+        let file = File::open(path).context(format!("No se pudo abrir {}", path.display()))?;
+        let archive = ZipArchive::new(file).context(format!("{} no es un archivo xlsx válido", path.display()))?;
+        let mut reader = SheetXmlReader {
+            archive,
+            workbook: String::new(),
+            rels: String::new(),
+        };
+        let (Some(workbook), Some(rels)) = (reader.read("xl/workbook.xml"), reader.read("xl/_rels/workbook.xml.rels")) else {
+            return Ok(None);
+        };
+        reader.workbook = workbook;
+        reader.rels = rels;
+        Ok(Some(reader))
+    }
+
+    // Rutas de todas las partes del paquete, sin descomprimir ninguna
+    pub fn part_names(&self) -> Vec<String> {
+        self.archive.file_names().map(str::to_string).collect()
+    }
+
+    pub fn sheet_names(&self) -> Vec<String> {
+        find_tags(&self.workbook, "sheet")
+            .iter()
+            .filter_map(|tag| xml_attr(tag, "name").map(|name| xml_unescape(&name)))
+            .collect()
+    }
+
+    // El XML de la hoja `name` con sus relaciones, notas y tablas
+    pub fn sheet(&mut self, name: &str) -> Option<SheetXml> {
+        let part = sheet_part_in(&self.workbook, &self.rels, name).ok()?;
+        let xml = self.read(&part)?;
+        let (dir, file) = part.rsplit_once('/')?;
+        let rels = self.read(&format!("{}/_rels/{}.rels", dir, file));
+        let comments = rels
+            .as_deref()
+            .and_then(|rels| relationship_target(rels, COMMENTS_RELATIONSHIP))
+            .and_then(|target| self.read(&resolve_part(dir, &target)));
+        let tables = rels
+            .as_deref()
+            .map(|rels| relationship_targets(rels, TABLE_RELATIONSHIP))
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|target| self.read(&resolve_part(dir, target)))
+            .collect();
+        Some(SheetXml {
+            name: name.to_string(),
+            xml,
+            rels,
+            comments,
+            tables,
+        })
+    }
+
+    fn read(&mut self, name: &str) -> Option<String> {
+        let mut content = Vec::new();
+        self.archive.by_name(name).ok()?.read_to_end(&mut content).ok()?;
+        Some(String::from_utf8_lossy(&content).into_owned())
+    }
+}
+
+// Lee una parte sin abrir el paquete entero (útil con libros grandes); con
+// `max_bytes` solo se leen los primeros bytes. `None` si la parte no existe.
+pub fn read_part_from(path: &Path, name: &str, max_bytes: Option<u64>) -> Result<Option<String>> {
+    let file = File::open(path).context(format!("No se pudo abrir {}", path.display()))?;
+    let mut archive = ZipArchive::new(file).context(format!("{} no es un archivo xlsx válido", path.display()))?;
+    let entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut content = Vec::new();
+    entry.take(max_bytes.unwrap_or(u64::MAX)).read_to_end(&mut content)?;
+    Ok(Some(String::from_utf8_lossy(&content).into_owned()))
+}
+
+// Inserta un elemento hijo de <worksheet> respetando el orden del esquema
+pub fn insert_worksheet_element(xml: &str, element: &str, content: &str) -> Result<String> {
+    let position = WORKSHEET_ORDER
+        .iter()
+        .position(|e| *e == element)
+        .context(format!("Elemento de hoja desconocido: {}", element))?;
+    let insert_at = WORKSHEET_ORDER[position + 1..]
+        .iter()
+        .filter_map(|later| find_element_start(xml, later))
+        .min()
+        .or_else(|| xml.rfind("</worksheet>"));
+    let Some(insert_at) = insert_at else {
+        bail!("XML de hoja no válido");
+    };
+    let mut output = String::with_capacity(xml.len() + content.len());
+    output.push_str(&xml[..insert_at]);
+    output.push_str(content);
+    output.push_str(&xml[insert_at..]);
+    Ok(output)
+}
+
+// Lector de fragmentos: las partes se recorren a trozos, así que no se exige que
+// cada cierre corresponda a una apertura del mismo fragmento
+fn fragment_reader(xml: &str) -> Reader<&[u8]> {
+    let mut reader = Reader::from_str(xml);
+    reader.check_end_names(false);
+    reader
+}
+
+// Etiquetas de apertura (o vacías) de un elemento con su posición (inicio, fin).
+// Las lee quick-xml, así que no confunde prefijos ni cuenta lo que hay dentro de
+// comentarios o CDATA; un fragmento mal formado se lee hasta el primer error
+fn element_tags<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+    let mut reader = fragment_reader(xml);
+    std::iter::from_fn(move || loop {
+        let start = reader.buffer_position();
+        match reader.read_event() {
+            Ok(Event::Start(tag) | Event::Empty(tag)) if tag.name().as_ref() == name.as_bytes() => {
+                return Some((start, reader.buffer_position()));
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            Ok(_) => {}
+        }
+    })
+}
+
+// Posición de la etiqueta de apertura <name> o <name ...>
+pub fn find_element_start(xml: &str, name: &str) -> Option<usize> {
+    element_tags(xml, name).next().map(|(start, _)| start)
+}
+
+// Todas las etiquetas de apertura de un elemento (sin su contenido)
+pub fn find_tags(xml: &str, name: &str) -> Vec<String> {
+    element_tags(xml, name).map(|(start, end)| xml[start..end].to_string()).collect()
+}
+
+// Elementos con contenido de texto: (etiqueta de apertura, contenido sin decodificar)
+pub fn find_elements(xml: &str, name: &str) -> Vec<(String, String)> {
+    let mut reader = fragment_reader(xml);
+    let mut elements = Vec::new();
+    loop {
+        let start = reader.buffer_position();
+        match reader.read_event() {
+            Ok(Event::Empty(tag)) if tag.name().as_ref() == name.as_bytes() => {
+                elements.push((xml[start..reader.buffer_position()].to_string(), String::new()));
+            }
+            Ok(Event::Start(tag)) if tag.name().as_ref() == name.as_bytes() => {
+                let tag_end = reader.buffer_position();
+                let Ok(content) = reader.read_to_end(QName(name.as_bytes())) else { break };
+                elements.push((xml[start..tag_end].to_string(), xml[content].to_string()));
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+    elements
+}
+
+// Celdas (fila, columna desde 0) con un valor o una fórmula; las que solo tienen
+// estilo cuentan como vacías
+pub fn filled_cells(xml: &str) -> Vec<(usize, usize)> {
+    find_elements(&with_positions(xml), "c")
+        .into_iter()
+        .filter(|(_, content)| {
+            find_element_start(content, "f").is_some()
+                || find_element_start(content, "is").is_some()
+                || find_elements(content, "v").first().is_some_and(|(_, value)| !value.is_empty())
+        })
+        .filter_map(|(tag, _)| xml_attr(&tag, "r").and_then(|r| crate::excel::parse_cell_ref(&r)))
+        .collect()
+}
+
+// Cambia el valor de celdas en el XML de una hoja conservando su estilo, de modo
+// que una plantilla mantiene formatos, anchos y el resto de su contenido.
+// Los textos se escriben en línea (sin tocar sharedStrings). No se sobrescriben
+// celdas con fórmula: en una plantilla suelen ser cálculos que hay que conservar.
+pub fn set_cell_values(xml: &str, cells: &[(usize, usize, String)]) -> Result<String> {
+    set_cells(xml, cells, false)
+}
+
+// Como set_cell_values, pero sustituye también las celdas con fórmula: son
+// cambios pedidos celda a celda (los pendientes de `editar`)
+pub fn write_cells(xml: &str, cells: &[(usize, usize, String)]) -> Result<String> {
+    set_cells(xml, cells, true)
+}
+
+fn set_cells(xml: &str, cells: &[(usize, usize, String)], replace_formulas: bool) -> Result<String> {
+    let mut xml = with_positions(xml).replacen("<sheetData/>", "<sheetData></sheetData>", 1);
+    for (row, col, content) in cells {
+        xml = set_cell(&xml, *row, *col, content, replace_formulas)?;
+    }
+    // El rango usado declarado crece con las celdas escritas fuera de él
+    let dimension = find_tags(&xml, "dimension").into_iter().next();
+    let declared = dimension
+        .as_deref()
+        .and_then(|tag| xml_attr(tag, "ref"))
+        .and_then(|reference| crate::excel::CellRange::parse(&reference));
+    if let (Some(tag), Some(mut range)) = (dimension, declared) {
+        for (row, col, _) in cells {
+            range.first_row = range.first_row.min(*row);
+            range.first_col = range.first_col.min(*col);
+            range.last_row = range.last_row.max(*row);
+            range.last_col = range.last_col.max(*col);
+        }
+        xml = xml.replacen(&tag, &format!("<dimension ref=\"{}\"/>", range), 1);
+    }
+    Ok(xml)
+}
+
+// Da su atributo r a las filas y celdas que no lo tienen. Es opcional: sin él, una
+// fila va detrás de la anterior y una celda a la derecha de la anterior de su fila.
+// Con todas las posiciones explícitas, insertar filas o celdas no desplaza las demás.
+fn with_positions(xml: &str) -> String {
+    let mut reader = fragment_reader(xml);
+    let mut output = String::new();
+    let mut copied = 0;
+    let (mut row, mut col) = (0, None);
+    loop {
+        let start = reader.buffer_position();
+        let name = match reader.read_event() {
+            Ok(Event::Start(tag) | Event::Empty(tag)) => tag.name().as_ref().to_vec(),
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(_) => continue,
+        };
+        let tag = &xml[start..reader.buffer_position()];
+        let reference = xml_attr(tag, "r");
+        let missing = match name.as_slice() {
+            b"row" => {
+                row = reference.as_deref().and_then(|r| r.parse().ok()).unwrap_or(row + 1);
+                col = None;
+                reference.is_none().then(|| (start + "<row".len(), row.to_string()))
+            }
+            b"c" => {
+                let next = col.map_or(0, |c| c + 1);
+                col = Some(reference.as_deref().and_then(crate::excel::parse_cell_ref).map_or(next, |(_, c)| c));
+                reference.is_none().then(|| (start + "<c".len(), format!("{}{}", crate::excel::column_letters(next), row)))
+            }
+            _ => None,
+        };
+        if let Some((at, position)) = missing {
+            output.push_str(&xml[copied..at]);
+            output.push_str(&format!(" r=\"{}\"", position));
+            copied = at;
+        }
+    }
+    if copied == 0 {
+        return xml.to_string();
+    }
+    output.push_str(&xml[copied..]);
+    output
+}
+
+// Contenido de <c> para un valor: (atributo t, elementos hijos)
+pub fn cell_content(value: &crate::excel::CellValue) -> Option<String> {
+    use crate::excel::CellValue;
+    Some(match value {
+        CellValue::Empty => String::new(),
+        CellValue::Number(n) | CellValue::DateTime(n) => format!("><v>{}</v>", n),
+        CellValue::Bool(b) => format!(" t=\"b\"><v>{}</v>", u8::from(*b)),
+        CellValue::Text(text) => format!(" t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is>", xml_escape(text)),
+        CellValue::Error(_) => return None,
+    })
+}
+
+// Contenido de <c> para una fórmula (sin '='), con su último resultado si lo hay
+pub fn formula_content(formula: &str, value: &crate::excel::CellValue) -> String {
+    use crate::excel::CellValue;
+    let formula = xml_escape(formula);
+    match value {
+        CellValue::Number(n) | CellValue::DateTime(n) => format!("><f>{}</f><v>{}</v>", formula, n),
+        CellValue::Bool(b) => format!(" t=\"b\"><f>{}</f><v>{}</v>", formula, u8::from(*b)),
+        CellValue::Text(text) => format!(" t=\"str\"><f>{}</f><v>{}</v>", formula, xml_escape(text)),
+        CellValue::Empty | CellValue::Error(_) => format!("><f>{}</f>", formula),
+    }
+}
+
+fn set_cell(xml: &str, row: usize, col: usize, content: &str, replace_formulas: bool) -> Result<String> {
+    let reference = format!("{}{}", crate::excel::column_letters(col), row + 1);
+    let data_start = find_element_start(xml, "sheetData").context("La hoja no tiene sheetData")?;
+    let data_end = xml[data_start..]
+        .find("</sheetData>")
+        .map(|end| data_start + end)
+        .context("XML de hoja no válido")?;
+
+    // Fila: se busca por su atributo r; si no existe se crea en su posición
+    let mut row_span = None;
+    let mut insert_row_at = data_end;
+    let mut offset = data_start;
+    while let Some(found) = find_element_start(&xml[offset..data_end], "row") {
+        let start = offset + found;
+        let tag_end = start + xml[start..].find('>').context("XML de hoja no válido")?;
+        let number: usize = xml_attr(&xml[start..=tag_end], "r").and_then(|r| r.parse().ok()).unwrap_or(0);
+        let end = if xml[start..=tag_end].ends_with("/>") {
+            tag_end + 1
+        } else {
+            tag_end + xml[tag_end..].find("</row>").context("XML de hoja no válido")? + "</row>".len()
+        };
+        if number == row + 1 {
+            row_span = Some((start, tag_end, end));
+            break;
+        }
+        if number > row + 1 {
+            insert_row_at = start;
+            break;
+        }
+        offset = end;
+    }
+
+    let new_cell = |style: Option<String>| {
+        let style = style.map(|s| format!(" s=\"{}\"", s)).unwrap_or_default();
+        if content.is_empty() {
+            format!("<c r=\"{}\"{}/>", reference, style)
+        } else {
+            format!("<c r=\"{}\"{}{}</c>", reference, style, content)
+        }
+    };
+    let Some((row_start, row_tag_end, row_end)) = row_span else {
+        let row_xml = format!("<row r=\"{}\">{}</row>", row + 1, new_cell(None));
+        return Ok(format!("{}{}{}", &xml[..insert_row_at], row_xml, &xml[insert_row_at..]));
+    };
+
+    // El atributo spans es una pista de las columnas usadas: se quita para que no quede desfasado
+    let row_tag = strip_attr(xml[row_start..=row_tag_end].trim_end_matches("/>").trim_end_matches('>'), "spans");
+    let inner = if xml[row_start..=row_tag_end].ends_with("/>") {
+        ""
+    } else {
+        &xml[row_tag_end + 1..row_end - "</row>".len()]
+    };
+
+    let mut cells = String::new();
+    let mut rest = inner;
+    let mut written = false;
+    while let Some(found) = find_element_start(rest, "c") {
+        let tag_end = found + rest[found..].find('>').context("XML de hoja no válido")?;
+        let end = if rest[found..=tag_end].ends_with("/>") {
+            tag_end + 1
+        } else {
+            tag_end + rest[tag_end..].find("</c>").context("XML de hoja no válido")? + "</c>".len()
+        };
+        let tag = &rest[found..=tag_end];
+        let cell_col = xml_attr(tag, "r")
+            .and_then(|r| crate::excel::parse_cell_ref(&r))
+            .map(|(_, c)| c)
+            .unwrap_or(usize::MAX);
+        cells.push_str(&rest[..found]);
+        if !written && cell_col == col {
+            let old = &rest[found..end];
+            if old.contains("<f") && !replace_formulas {
+                bail!("La celda {} contiene una fórmula; no se sobrescribe", reference);
+            }
+            // Las demás celdas de una fórmula compartida dependen de la de su rango
+            if find_tags(old, "f").first().is_some_and(|f| xml_attr(f, "ref").is_some()) {
+                bail!("La celda {} tiene una fórmula que comparten otras celdas; no se sustituye por separado", reference);
+            }
+            cells.push_str(&new_cell(xml_attr(tag, "s")));
+            written = true;
+        } else {
+            if !written && cell_col > col {
+                cells.push_str(&new_cell(None));
+                written = true;
+            }
+            cells.push_str(&rest[found..end]);
+        }
+        rest = &rest[end..];
+    }
+    cells.push_str(rest);
+    if !written {
+        cells.push_str(&new_cell(None));
+    }
+    Ok(format!(
+        "{}{}>{}</row>{}",
+        &xml[..row_start],
+        row_tag,
+        cells,
+        &xml[row_end..]
+    ))
+}
+
+// Quita un atributo de una etiqueta
+pub fn strip_attr(tag: &str, name: &str) -> String {
+    let pattern = format!(" {}=\"", name);
+    match tag.find(&pattern) {
+        Some(start) => {
+            let value_end = tag[start + pattern.len()..].find('"').map_or(tag.len(), |e| start + pattern.len() + e + 1);
+            format!("{}{}", &tag[..start], &tag[value_end..])
+        }
+        None => tag.to_string(),
+    }
+}
+
+// Aplica `read` a la primera etiqueta de apertura del texto, que puede venir sin
+// el '>' final
+fn with_first_tag<T>(tag: &str, read: impl FnOnce(&BytesStart) -> T) -> Option<T> {
+    let closed;
+    let tag = if tag.contains('>') {
+        tag
+    } else {
+        closed = format!("{}>", tag);
+        &closed
+    };
+    let mut reader = fragment_reader(tag);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start) | Event::Empty(start)) => return Some(read(&start)),
+            Ok(Event::Eof) | Err(_) => return None,
+            Ok(_) => {}
+        }
+    }
+}
+
+// Valor de un atributo dentro de una etiqueta, sin decodificar
+pub fn xml_attr(tag: &str, name: &str) -> Option<String> {
+    with_first_tag(tag, |start| {
+        start
+            .attributes()
+            .with_checks(false)
+            .filter_map(|attribute| attribute.ok())
+            .find(|attribute| attribute.key.as_ref() == name.as_bytes())
+            .map(|attribute| String::from_utf8_lossy(&attribute.value).into_owned())
+    })?
+}
+
+// Todos los atributos de una etiqueta, en orden y sin decodificar
+pub fn xml_attrs(tag: &str) -> Vec<(String, String)> {
+    with_first_tag(tag, |start| {
+        start
+            .attributes()
+            .with_checks(false)
+            .filter_map(|attribute| attribute.ok())
+            .map(|attribute| {
+                (
+                    String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                    String::from_utf8_lossy(&attribute.value).into_owned(),
+                )
+            })
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+// Incrementa en uno todos los valores numéricos del atributo indicado
+fn shift_attr(xml: &str, name: &str) -> String {
+    let pattern = format!(" {}=\"", name);
+    let mut output = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(found) = rest.find(&pattern) {
+        let start = found + pattern.len();
+        output.push_str(&rest[..start]);
+        let end = rest[start..].find('"').map_or(rest.len(), |e| start + e);
+        match rest[start..end].parse::<u32>() {
+            Ok(value) => output.push_str(&(value + 1).to_string()),
+            Err(_) => output.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+pub fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Decodifica las entidades XML predefinidas y las numéricas (&#NN; / &#xHH;)
+pub fn xml_unescape(value: &str) -> String {
+    if !value.contains('&') {
+        return value.to_string();
+    }
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(amp) = rest.find('&') {
+        output.push_str(&rest[..amp]);
+        let tail = &rest[amp..];
+        let Some(end) = tail.find(';') else {
+            output.push_str(tail);
+            return output;
+        };
+        let entity = &tail[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                output.push(c);
+                rest = &tail[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &tail[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{open_workbook, DataType, Reader, Xlsx};
+    use std::path::PathBuf;
+
+    // Libro con textos compartidos (rust_xlsxwriter no escribe textos en línea),
+    // una fórmula en C2 y la fila 3 sin crear
+    fn fixture(name: &str) -> PathBuf {
+        let path = crate::paths::test_dir(name).join("datos.xlsx");
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Datos").unwrap();
+        sheet.write_string(0, 0, "Cliente").unwrap();
+        sheet.write_string(0, 1, "Importe").unwrap();
+        sheet.write_string(1, 0, "Ana").unwrap();
+        sheet.write_number(1, 1, 10.0).unwrap();
+        sheet.write_formula(1, 2, "=B2*2").unwrap();
+        sheet.write_string(3, 0, "Luis").unwrap();
+        sheet.write_number(3, 1, 30.0).unwrap();
+        workbook.save(&path).unwrap();
+        path
+    }
+
+    fn text(value: &str) -> String {
+        cell_content(&crate::excel::CellValue::Text(value.to_string())).unwrap()
+    }
+
+    fn number(value: f64) -> String {
+        cell_content(&crate::excel::CellValue::Number(value)).unwrap()
+    }
+
+    #[test]
+    fn patched_workbook_reads_back_with_calamine() {
+        let path = fixture("patched_workbook_reads_back_with_calamine");
+        let mut package = XlsxPackage::open(&path).unwrap();
+        let cells = vec![(1, 0, text("Eva & Co")), (1, 1, number(15.0)), (2, 1, number(20.0)), (0, 3, text("Nota"))];
+        package.edit_sheet("Datos", |xml| write_cells(&xml, &cells)).unwrap();
+        package.save(&path).unwrap();
+
+        let mut workbook: Xlsx<_> = open_workbook(&path).unwrap();
+        let range = workbook.worksheet_range("Datos").unwrap().unwrap();
+        assert_eq!(range.get_value((0, 0)), Some(&DataType::String("Cliente".to_string())));
+        assert_eq!(range.get_value((1, 0)), Some(&DataType::String("Eva & Co".to_string())));
+        assert_eq!(range.get_value((1, 1)), Some(&DataType::Float(15.0)));
+        assert_eq!(range.get_value((2, 1)), Some(&DataType::Float(20.0)));
+        assert_eq!(range.get_value((0, 3)), Some(&DataType::String("Nota".to_string())));
+        assert_eq!(range.get_value((3, 0)), Some(&DataType::String("Luis".to_string())));
+        let formulas = workbook.worksheet_formula("Datos").unwrap().unwrap();
+        assert_eq!(formulas.get_value((1, 2)).map(String::as_str), Some("B2*2"));
+
+        // Los textos nuevos van en línea y sharedStrings no se toca
+        let package = XlsxPackage::open(&path).unwrap();
+        let xml = package.read_part("xl/worksheets/sheet1.xml").unwrap();
+        assert!(xml.contains("<c r=\"A2\" t=\"inlineStr\">"));
+        assert!(!package.read_part("xl/sharedStrings.xml").unwrap().contains("Eva"));
+    }
+
+    #[test]
+    fn shared_strings_become_inline_and_keep_the_style() {
+        let xml = "<worksheet><sheetData><row r=\"1\" spans=\"1:2\"><c r=\"A1\" s=\"3\" t=\"s\"><v>0</v></c></row></sheetData></worksheet>";
+        let patched = set_cell_values(xml, &[(0, 0, text("Nuevo"))]).unwrap();
+        assert_eq!(
+            patched,
+            "<worksheet><sheetData><row r=\"1\"><c r=\"A1\" s=\"3\" t=\"inlineStr\"><is><t xml:space=\"preserve\">Nuevo</t></is></c></row></sheetData></worksheet>"
+        );
+    }
+
+    #[test]
+    fn missing_rows_are_created_in_order() {
+        let xml = "<worksheet><sheetData><row r=\"2\"><c r=\"A2\"><v>1</v></c></row><row r=\"7\"><c r=\"A7\"><v>2</v></c></row></sheetData></worksheet>";
+        let patched = set_cell_values(xml, &[(4, 1, number(5.0)), (0, 0, number(0.0)), (8, 0, number(9.0))]).unwrap();
+        let rows: Vec<String> = find_tags(&patched, "row").iter().filter_map(|tag| xml_attr(tag, "r")).collect();
+        assert_eq!(rows, ["1", "2", "5", "7", "9"]);
+        assert!(patched.contains("<row r=\"5\"><c r=\"B5\"><v>5</v></c></row>"));
+
+        let empty = set_cell_values("<worksheet><sheetData/></worksheet>", &[(0, 0, number(1.0))]).unwrap();
+        assert_eq!(empty, "<worksheet><sheetData><row r=\"1\"><c r=\"A1\"><v>1</v></c></row></sheetData></worksheet>");
+    }
+
+    #[test]
+    fn cells_stay_in_column_order() {
+        let xml = "<worksheet><sheetData><row r=\"1\"><c r=\"A1\"><v>1</v></c><c r=\"C1\"><v>3</v></c></row></sheetData></worksheet>";
+        let patched = set_cell_values(xml, &[(0, 1, number(2.0)), (0, 27, number(28.0)), (0, 4, number(5.0))]).unwrap();
+        let columns: Vec<String> = find_tags(&patched, "c").iter().filter_map(|tag| xml_attr(tag, "r")).collect();
+        assert_eq!(columns, ["A1", "B1", "C1", "E1", "AB1"]);
+    }
+
+    #[test]
+    fn dimension_grows_with_the_written_cells() {
+        let xml = "<worksheet><dimension ref=\"A1:B2\"/><sheetData></sheetData></worksheet>";
+        let patched = set_cell_values(xml, &[(4, 3, number(1.0))]).unwrap();
+        assert!(patched.contains("<dimension ref=\"A1:D5\"/>"));
+    }
+
+    #[test]
+    fn formulas_are_kept_unless_replaced_one_by_one() {
+        let xml = "<worksheet><sheetData><row r=\"2\"><c r=\"C2\" s=\"1\"><f>B2*2</f><v>20</v></c></row></sheetData></worksheet>";
+        let error = set_cell_values(xml, &[(1, 2, number(1.0))]).unwrap_err();
+        assert!(error.to_string().contains("contiene una fórmula"));
+        let patched = write_cells(xml, &[(1, 2, number(1.0))]).unwrap();
+        assert!(patched.contains("<c r=\"C2\" s=\"1\"><v>1</v></c>"));
+    }
+
+    #[test]
+    fn shared_formulas_are_not_replaced() {
+        let xml = "<worksheet><sheetData><row r=\"2\"><c r=\"C2\"><f t=\"shared\" ref=\"C2:C4\" si=\"0\">B2*2</f><v>20</v></c></row></sheetData></worksheet>";
+        let error = write_cells(xml, &[(1, 2, formula_content("B2*3", &crate::excel::CellValue::Empty))]).unwrap_err();
+        assert!(error.to_string().contains("comparten otras celdas"));
+    }
+
+    #[test]
+    fn rows_and_cells_without_a_reference_take_it_from_their_order() {
+        let xml = "<worksheet><sheetData><row><c><v>1</v></c><c r=\"C1\"><v>3</v></c><c><v>4</v></c></row><row r=\"3\"><c/></row><row><c t=\"b\"><v>1</v></c></row></sheetData></worksheet>";
+        assert_eq!(filled_cells(xml), [(0, 0), (0, 2), (0, 3), (3, 0)]);
+        let patched = set_cell_values(xml, &[(0, 1, number(2.0)), (1, 0, number(5.0)), (3, 1, number(6.0))]).unwrap();
+        let rows: Vec<String> = find_tags(&patched, "row").iter().filter_map(|tag| xml_attr(tag, "r")).collect();
+        assert_eq!(rows, ["1", "2", "3", "4"]);
+        let columns: Vec<String> = find_tags(&patched, "c").iter().filter_map(|tag| xml_attr(tag, "r")).collect();
+        assert_eq!(columns, ["A1", "B1", "C1", "D1", "A2", "A3", "A4", "B4"]);
+        assert!(patched.contains("<c r=\"A4\" t=\"b\"><v>1</v></c>"), "{}", patched);
+    }
+
+    #[test]
+    fn writing_cells_drops_the_calc_chain() {
+        let path = fixture("writing_cells_drops_the_calc_chain");
+        let mut package = XlsxPackage::open(&path).unwrap();
+        let rels = package.read_part("xl/_rels/workbook.xml.rels").unwrap();
+        let (rels, _) = add_relationship(&rels, CALC_CHAIN_RELATIONSHIP, "xl/calcChain.xml");
+        let types = package.read_part("[Content_Types].xml").unwrap().replacen(
+            "</Types>",
+            "<Override PartName=\"/xl/calcChain.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.calcChain+xml\"/></Types>",
+            1,
+        );
+        package.write_part("xl/_rels/workbook.xml.rels", rels);
+        package.write_part("[Content_Types].xml", types);
+        package.write_part("xl/calcChain.xml", "<calcChain><c r=\"C2\" i=\"1\"/></calcChain>".to_string());
+        package.save(&path).unwrap();
+
+        // C2 deja de ser una fórmula: la cadena que la nombra desaparece con todo lo suyo
+        let mut package = XlsxPackage::open(&path).unwrap();
+        package.write_cells("Datos", &[(1, 2, number(20.0))]).unwrap();
+        package.save(&path).unwrap();
+        let package = XlsxPackage::open(&path).unwrap();
+        assert!(!package.has_part("xl/calcChain.xml"));
+        assert!(!package.read_part("xl/_rels/workbook.xml.rels").unwrap().contains("calcChain"));
+        assert!(!package.read_part("[Content_Types].xml").unwrap().contains("calcChain"));
+        let mut workbook: Xlsx<_> = open_workbook(&path).unwrap();
+        let range = workbook.worksheet_range("Datos").unwrap().unwrap();
+        assert_eq!(range.get_value((1, 2)), Some(&DataType::Float(20.0)));
+    }
+
+    #[test]
+    fn tags_inside_comments_and_cdata_or_with_a_longer_name_are_not_found() {
+        let xml = "<sheetData><!-- <row r=\"9\"> --><rows/><row\n r=\"1\"><c r=\"A1\" t=\"str\"><v><![CDATA[<row r=\"8\">]]></v></c></row><row r='2'/></sheetData>";
+        assert_eq!(find_tags(xml, "row"), ["<row\n r=\"1\">", "<row r='2'/>"]);
+        assert_eq!(find_element_start(xml, "row"), xml.find("<row\n"));
+        assert_eq!(find_element_start(xml, "col"), None);
+        let rows: Vec<String> = find_tags(xml, "row").iter().filter_map(|tag| xml_attr(tag, "r")).collect();
+        assert_eq!(rows, ["1", "2"]);
+    }
+
+    #[test]
+    fn attributes_are_read_from_the_first_tag_even_without_its_end() {
+        assert_eq!(xml_attr("<c r=\"A1\" s=\"2\"><v>1</v></c>", "s").as_deref(), Some("2"));
+        assert_eq!(xml_attr("<xf numFmtId=\"0\" applyProtection=\"1\"", "applyProtection").as_deref(), Some("1"));
+        // Sin decodificar y sin confundir un prefijo con el nombre
+        assert_eq!(xml_attr("<sheet name=\"A&amp;B\" r:id=\"rId1\"/>", "name").as_deref(), Some("A&amp;B"));
+        assert_eq!(xml_attr("<sheet r:id=\"rId1\"/>", "id"), None);
+        assert_eq!(
+            xml_attrs("<col min=\"1\" max=\"2\" width='9.5'/>"),
+            [("min", "1"), ("max", "2"), ("width", "9.5")].map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+    }
+
+    #[test]
+    fn elements_with_the_same_name_inside_are_kept_whole() {
+        let xml = "<si><t>Uno</t></si><si><r><t>Do</t></r><r><t>s</t></r></si><si/>";
+        let contents: Vec<String> = find_elements(xml, "si").into_iter().map(|(_, content)| content).collect();
+        assert_eq!(contents, ["<t>Uno</t>", "<r><t>Do</t></r><r><t>s</t></r>", ""]);
+        let nested = find_elements("<g><g>a</g>b</g>", "g");
+        assert_eq!(nested, [("<g>".to_string(), "<g>a</g>b".to_string())]);
+    }
+
+    #[test]
+    fn sheet_parts_are_resolved_from_escaped_names_and_relative_targets() {
+        let workbook = "<workbook><sheets><sheet name=\"I+D &amp; Ventas\" sheetId=\"1\" r:id=\"rId1\"/><sheet name=\"Otra\" sheetId=\"2\" r:id=\"rId2\"/></sheets></workbook>";
+        let rels = "<Relationships><Relationship Id=\"rId1\" Target=\"worksheets/sheet1.xml\"/><Relationship Id=\"rId2\" Target=\"/xl/hojas/b.xml\"/></Relationships>";
+        assert_eq!(sheet_part_in(workbook, rels, "i+d & ventas").unwrap(), "xl/worksheets/sheet1.xml");
+        assert_eq!(sheet_part_in(workbook, rels, "Otra").unwrap(), "xl/hojas/b.xml");
+        let error = sheet_part_in(workbook, rels, "Nada").unwrap_err();
+        assert_eq!(error.to_string(), "No existe la hoja 'Nada'");
+    }
+
+    #[test]
+    fn sheet_xml_reader_reads_only_the_requested_sheet() {
+        let path = fixture("sheet_xml_reader_reads_only_the_requested_sheet");
+        let mut reader = SheetXmlReader::open(&path).unwrap().unwrap();
+        assert_eq!(reader.sheet_names(), ["Datos"]);
+        let sheet = reader.sheet("Datos").unwrap();
+        assert!(sheet.xml.contains("<sheetData>"), "{}", sheet.xml);
+        assert!(reader.sheet("Otra").is_none());
+    }
+
+    #[test]
+    fn a_sheet_calamine_cannot_read_is_reported_instead_of_dropped() {
+        let path = fixture("a_sheet_calamine_cannot_read_is_reported_instead_of_dropped");
+        let mut package = XlsxPackage::open(&path).unwrap();
+        package.edit_sheet("Datos", |_| Ok("<worksheet><sheetData><row r=\"1\"><c r=\"?\"><v>1</v></c></row></sheetData></worksheet>".to_string())).unwrap();
+        // La verificación tras escribir ya avisa, pero el archivo queda escrito
+        package.save(&path).unwrap_err();
+
+        let error = crate::excel::read_excel_file(path.to_str().unwrap()).unwrap_err();
+        let Some(crate::error::IAgentError::Excel { sheet, .. }) = crate::error::IAgentError::find(&error) else {
+            panic!("{:#}", error);
+        };
+        assert_eq!(sheet.as_deref(), Some("Datos"));
+    }
+}
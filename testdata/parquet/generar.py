@@ -0,0 +1,364 @@
+#!/usr/bin/env python3
+# Genera ventas.parquet, el archivo de la prueba de lectura de Parquet de
+# `convertir` (src/parquet.rs). No lo escribe otra herramienta: es un codificador
+# propio, aparte del lector, que usa lo que el escritor integrado no escribe y los
+# archivos de otras herramientas suelen tener: columnas opcionales con un
+# diccionario (salvo la booleana) e índices RLE_DICTIONARY en páginas de datos
+# v1, compresión SNAPPY con copias, estadísticas en páginas y columnas, tipos
+# lógicos (INTEGER, DATE, TIMESTAMP en microsegundos, DECIMAL en
+# FIXED_LEN_BYTE_ARRAY) y dos grupos de filas.
+#
+#   python3 testdata/parquet/generar.py
+
+import os
+import struct
+from datetime import date, datetime, timezone
+from decimal import Decimal
+
+
+# La tabla. Las horas son fracciones exactas del día para que la prueba compare
+# las fechas sin redondeos.
+
+# Nombre, tipo y, en los decimales, precisión y escala
+COLUMNAS = [
+    ("Cliente", "texto"),
+    ("Nota", "texto"),
+    ("Importe", "doble"),
+    ("Unidades", "entero"),
+    ("Pagado", "booleano"),
+    ("Alta", "fecha"),
+    ("Registro", "marca"),
+    ("Precio", "decimal", (9, 2)),
+]
+
+FILAS = [
+    ("Ana López", "pedido urgente", 10.5, 3, True, date(2024, 1, 1), datetime(2024, 1, 1, 12), Decimal("12.34")),
+    ("Luis", "pedido normal", 20.0, 1, False, date(2024, 1, 2), datetime(2024, 1, 2, 6), Decimal("5.00")),
+    ("Ana López", "pedido urgente", 10.5, 3, True, date(2024, 1, 1), datetime(2024, 1, 1, 12), Decimal("12.34")),
+    (None, None, None, None, None, None, None, None),
+    ("Eva", "pedido urgente revisado", -3.25, 7, True, date(2024, 2, 29), datetime(2024, 2, 29, 18), Decimal("-0.50")),
+    ("Íñigo", "pedido normal", 0.0, 0, None, date(2023, 12, 31), datetime(2023, 12, 31), Decimal("1000000.00")),
+    ("Ana López", "pedido normal", 10.5, 2, False, date(2024, 3, 1), datetime(2024, 3, 1, 3), Decimal("12.34")),
+]
+
+# Dos grupos de filas: cuatro y tres
+FILAS_POR_GRUPO = 4
+
+
+# Protocolo compacto de Thrift
+
+BOOL_TRUE, BOOL_FALSE, BYTE, I32, I64, BINARY, LIST, STRUCT = 1, 2, 3, 5, 6, 8, 9, 12
+
+
+def varint(value):
+    out = bytearray()
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        if value:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def zigzag(value):
+    return (value << 1) ^ (value >> 63)
+
+
+class I8v:
+    def __init__(self, value):
+        self.value = value
+
+
+class I32v:
+    def __init__(self, value):
+        self.value = value
+
+
+class I64v:
+    def __init__(self, value):
+        self.value = value
+
+
+class Struct:
+    def __init__(self, *fields):
+        self.fields = [(id, value) for id, value in fields if value is not None]
+
+
+class List:
+    def __init__(self, kind, items):
+        self.kind = kind
+        self.items = items
+
+
+def type_of(value):
+    if isinstance(value, bool):
+        return BOOL_TRUE if value else BOOL_FALSE
+    return {I8v: BYTE, I32v: I32, I64v: I64, bytes: BINARY, str: BINARY, List: LIST, Struct: STRUCT}[type(value)]
+
+
+def encode(value):
+    if isinstance(value, I8v):
+        return struct.pack("<b", value.value)
+    if isinstance(value, (I32v, I64v)):
+        return varint(zigzag(value.value))
+    if isinstance(value, str):
+        value = value.encode()
+    if isinstance(value, bytes):
+        return varint(len(value)) + value
+    if isinstance(value, List):
+        size = len(value.items)
+        header = bytes([size << 4 | value.kind]) if size < 15 else bytes([0xF0 | value.kind]) + varint(size)
+        return header + b"".join(encode(item) for item in value.items)
+    out = bytearray()
+    last = 0
+    for id, field in value.fields:
+        kind = type_of(field)
+        if 0 < id - last <= 15:
+            out.append((id - last) << 4 | kind)
+        else:
+            out.append(kind)
+            out += varint(zigzag(id))
+        if not isinstance(field, bool):
+            out += encode(field)
+        last = id
+    out.append(0)
+    return bytes(out)
+
+
+# SNAPPY sin tramas: literales y copias de hasta 64 bytes buscadas con una tabla de
+# dispersión de 4 bytes, como hace la biblioteca de referencia
+
+def snappy(data):
+    out = bytearray(varint(len(data)))
+    table = {}
+    literal_start = 0
+    pos = 0
+
+    def literal(end):
+        chunk = data[literal_start:end]
+        if not chunk:
+            return
+        n = len(chunk) - 1
+        if n < 60:
+            out.append(n << 2)
+        elif n < 256:
+            out.extend([60 << 2, n])
+        else:
+            out.extend(bytes([61 << 2]) + struct.pack("<H", n))
+        out.extend(chunk)
+
+    while pos + 4 <= len(data):
+        key = data[pos:pos + 4]
+        candidate = table.get(key)
+        table[key] = pos
+        if candidate is None:
+            pos += 1
+            continue
+        length = 4
+        while pos + length < len(data) and length < 64 and data[candidate + length] == data[pos + length]:
+            length += 1
+        literal(pos)
+        offset = pos - candidate
+        if 4 <= length <= 11 and offset < 2048:
+            out += bytes([(offset >> 8) << 5 | (length - 4) << 2 | 1, offset & 0xFF])
+        else:
+            out += bytes([(length - 1) << 2 | 2]) + struct.pack("<H", offset)
+        pos += length
+        literal_start = pos
+    literal(len(data))
+    return bytes(out)
+
+
+# Codificación híbrida RLE / empaquetado de bits: tramos RLE para ocho o más
+# valores iguales y grupos de ocho empaquetados para el resto
+
+def hybrid(values, bit_width):
+    out = bytearray()
+    width_bytes = (bit_width + 7) // 8
+    pending = []
+
+    def flush_packed():
+        if not pending:
+            return
+        groups = (len(pending) + 7) // 8
+        padded = pending + [0] * (groups * 8 - len(pending))
+        bits = 0
+        for index, value in enumerate(padded):
+            bits |= value << (index * bit_width)
+        out.extend(varint(groups << 1 | 1))
+        out.extend(bits.to_bytes(groups * bit_width, "little"))
+        pending.clear()
+
+    index = 0
+    while index < len(values):
+        run = 1
+        while index + run < len(values) and values[index + run] == values[index]:
+            run += 1
+        if run >= 8 and len(pending) % 8 == 0:
+            flush_packed()
+            out.extend(varint(run << 1))
+            out.extend(values[index].to_bytes(width_bytes, "little"))
+            index += run
+        else:
+            pending.append(values[index])
+            index += 1
+    flush_packed()
+    return bytes(out)
+
+
+# Tipos de la tabla
+
+BOOLEAN, INT32, INT64, DOUBLE, BYTE_ARRAY, FIXED = 0, 1, 2, 5, 6, 7
+PLAIN, PLAIN_DICTIONARY, RLE, RLE_DICTIONARY = 0, 2, 3, 8
+SNAPPY = 1
+EPOCH = date(1970, 1, 1)
+
+
+def decimal_width(precision):
+    width = 1
+    while 2 ** (8 * width - 1) <= 10 ** precision:
+        width += 1
+    return width
+
+
+def physical(column):
+    kind = column[1]
+    return {"texto": BYTE_ARRAY, "doble": DOUBLE, "entero": INT64, "booleano": BOOLEAN,
+            "fecha": INT32, "marca": INT64, "decimal": FIXED}[kind]
+
+
+def plain(column, value):
+    kind = column[1]
+    if kind == "texto":
+        data = value.encode()
+        return struct.pack("<I", len(data)) + data
+    if kind == "doble":
+        return struct.pack("<d", value)
+    if kind == "entero":
+        return struct.pack("<q", value)
+    if kind == "fecha":
+        return struct.pack("<i", (value - EPOCH).days)
+    if kind == "marca":
+        delta = value.replace(tzinfo=timezone.utc) - datetime(1970, 1, 1, tzinfo=timezone.utc)
+        return struct.pack("<q", (delta.days * 86_400 + delta.seconds) * 1_000_000 + delta.microseconds)
+    if kind == "decimal":
+        precision, scale = column[2]
+        unscaled = int(value.scaleb(scale))
+        return unscaled.to_bytes(decimal_width(precision), "big", signed=True)
+    raise ValueError(kind)
+
+
+# Tipo convertido (la anotación antigua) y tipo lógico de cada columna
+ANOTACIONES = {
+    "texto": (0, Struct((1, Struct()))),
+    "doble": (None, None),
+    "entero": (18, Struct((10, Struct((1, I8v(64)), (2, True))))),
+    "booleano": (None, None),
+    "fecha": (6, Struct((6, Struct()))),
+    # Sin ajustar a UTC y en microsegundos, que no tiene tipo convertido
+    "marca": (None, Struct((8, Struct((1, False), (2, Struct((2, Struct()))))))),
+}
+
+
+def schema_element(column):
+    name, kind = column[0], column[1]
+    if kind == "decimal":
+        precision, scale = column[2]
+        return Struct((1, I32v(FIXED)), (2, I32v(decimal_width(precision))), (3, I32v(1)), (4, name),
+                      (6, I32v(5)), (7, I32v(scale)), (8, I32v(precision)),
+                      (10, Struct((5, Struct((1, I32v(scale)), (2, I32v(precision)))))))
+    converted, logical = ANOTACIONES[kind]
+    return Struct((1, I32v(physical(column))), (3, I32v(1)), (4, name),
+                  (6, None if converted is None else I32v(converted)), (10, logical))
+
+
+def statistics(column, values, nulls):
+    present = [value for value in values if value is not None]
+    if not present or column[1] == "booleano":
+        return Struct((3, I64v(nulls)))
+    key = (lambda v: v.encode()) if column[1] == "texto" else (lambda v: v)
+    low, high = min(present, key=key), max(present, key=key)
+    strip = (lambda b: b[4:]) if column[1] == "texto" else (lambda b: b)
+    return Struct((3, I64v(nulls)), (5, strip(plain(column, high))), (6, strip(plain(column, low))))
+
+
+def page_header(kind, uncompressed, compressed, body):
+    return encode(Struct((1, I32v(kind)), (2, I32v(uncompressed)), (3, I32v(compressed)), *body))
+
+
+def column_chunk(file, column, values):
+    start = len(file)
+    nulls = values.count(None)
+    defined = [0 if value is None else 1 for value in values]
+    levels = hybrid(defined, 1)
+    present = [value for value in values if value is not None]
+    stats = statistics(column, values, nulls)
+
+    dictionary_offset = None
+    if column[1] == "booleano":
+        bits = 0
+        for index, value in enumerate(present):
+            bits |= int(value) << index
+        data = bits.to_bytes((len(present) + 7) // 8, "little")
+        encoding = PLAIN
+    else:
+        entries = []
+        for value in present:
+            if value not in entries:
+                entries.append(value)
+        raw = b"".join(plain(column, value) for value in entries)
+        compressed = snappy(raw)
+        dictionary_offset = len(file)
+        file += page_header(2, len(raw), len(compressed), [(7, Struct((1, I32v(len(entries))), (2, I32v(PLAIN_DICTIONARY)), (3, False)))])
+        file += compressed
+        bit_width = max(len(entries) - 1, 0).bit_length()
+        data = bytes([bit_width]) + hybrid([entries.index(value) for value in present], bit_width)
+        encoding = RLE_DICTIONARY
+
+    raw = struct.pack("<I", len(levels)) + levels + data
+    compressed = snappy(raw)
+    data_offset = len(file)
+    file += page_header(0, len(raw), len(compressed), [(5, Struct((1, I32v(len(values))), (2, I32v(encoding)), (3, I32v(RLE)), (4, I32v(RLE)), (5, stats)))])
+    file += compressed
+
+    encodings = [PLAIN, RLE] if dictionary_offset is None else [PLAIN_DICTIONARY, PLAIN, RLE, RLE_DICTIONARY]
+    encoding_stats = [Struct((1, I32v(0)), (2, I32v(encoding)), (3, I32v(1)))]
+    if dictionary_offset is not None:
+        encoding_stats.insert(0, Struct((1, I32v(2)), (2, I32v(PLAIN_DICTIONARY)), (3, I32v(1))))
+    size = len(file) - start
+    meta = Struct((1, I32v(physical(column))), (2, List(I32, [I32v(e) for e in encodings])), (3, List(BINARY, [column[0]])),
+                  (4, I32v(SNAPPY)), (5, I64v(len(values))), (6, I64v(size)), (7, I64v(size)), (9, I64v(data_offset)),
+                  (11, None if dictionary_offset is None else I64v(dictionary_offset)), (12, stats),
+                  (13, List(STRUCT, encoding_stats)))
+    return Struct((2, I64v(start)), (3, meta)), size
+
+
+def main():
+    file = bytearray(b"PAR1")
+    groups = []
+    for ordinal, first in enumerate(range(0, len(FILAS), FILAS_POR_GRUPO)):
+        rows = FILAS[first:first + FILAS_POR_GRUPO]
+        start = len(file)
+        chunks = []
+        total = 0
+        for index, column in enumerate(COLUMNAS):
+            chunk, size = column_chunk(file, column, [row[index] for row in rows])
+            chunks.append(chunk)
+            total += size
+        groups.append(Struct((1, List(STRUCT, chunks)), (2, I64v(total)), (3, I64v(len(rows))), (5, I64v(start)),
+                             (6, I64v(total)), (7, I32v(ordinal))))
+    schema = [Struct((4, "schema"), (5, I32v(len(COLUMNAS))))] + [schema_element(column) for column in COLUMNAS]
+    metadata = encode(Struct((1, I32v(2)), (2, List(STRUCT, schema)), (3, I64v(len(FILAS))), (4, List(STRUCT, groups)),
+                             (5, List(STRUCT, [Struct((1, "origen"), (2, "testdata/parquet/generar.py"))])),
+                             (6, "generar.py"),
+                             (7, List(STRUCT, [Struct((1, Struct())) for _ in COLUMNAS]))))
+    file += metadata + struct.pack("<I", len(metadata)) + b"PAR1"
+    path = os.path.join(os.path.dirname(os.path.abspath(__file__)), "ventas.parquet")
+    with open(path, "wb") as out:
+        out.write(file)
+
+
+if __name__ == "__main__":
+    main()